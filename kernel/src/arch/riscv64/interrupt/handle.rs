@@ -5,7 +5,9 @@ use core::hint::spin_loop;
 
 use system_error::SystemError;
 
-use crate::{arch::syscall::syscall_handler, kdebug, kerror};
+use crate::{
+    arch::syscall::syscall_handler, kdebug, kerror, mm::VirtAddr, process::ProcessManager,
+};
 
 use super::TrapFrame;
 
@@ -40,6 +42,8 @@ unsafe extern "C" fn riscv64_do_irq(trap_frame: &mut TrapFrame) {
 }
 
 /// 处理中断
+///
+/// todo: 处理时钟中断时，需要调用`ProcessManager::update_process_times`来统计进程的用户态、内核态时间
 fn riscv64_do_interrupt(_trap_frame: &mut TrapFrame) {
     kdebug!("todo: riscv64_do_irq: interrupt");
     loop {
@@ -180,8 +184,33 @@ fn do_trap_load_page_fault(trap_frame: &mut TrapFrame) -> Result<(), SystemError
 // 14 reserved
 
 /// 处理页存储错误异常 #15
-fn do_trap_store_page_fault(_trap_frame: &mut TrapFrame) -> Result<(), SystemError> {
-    kerror!("riscv64_do_irq: do_trap_store_page_fault");
+///
+/// 对用户空间中写时复制的页面（fork之后被写保护）的写入会触发这个异常
+fn do_trap_store_page_fault(trap_frame: &mut TrapFrame) -> Result<(), SystemError> {
+    let vaddr = VirtAddr::new(trap_frame.badaddr);
+    if vaddr.check_user() {
+        let pcb = ProcessManager::current_pcb();
+        if let Some(vm) = pcb.basic().user_vm() {
+            // 与x86_64相同，持有自旋锁时只尝试获取一次地址空间的锁，避免死锁
+            let guard = if pcb.preempt_count() == 0 {
+                Some(vm.write_irqsave())
+            } else {
+                vm.try_write_irqsave()
+            };
+            if let Some(mut guard) = guard {
+                if guard.handle_cow_fault(vaddr).is_ok() {
+                    pcb.stats().inc_fault(false);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    kerror!(
+        "riscv64_do_irq: do_trap_store_page_fault: epc: {:#x}, vaddr={:#x}",
+        trap_frame.epc,
+        trap_frame.badaddr
+    );
     loop {
         spin_loop();
     }
@@ -112,6 +112,21 @@ impl TrapFrame {
         self.status.spp() == riscv::register::sstatus::SPP::User
     }
 
+    /// 获取中断返回后将要执行的指令的地址
+    pub fn ip(&self) -> usize {
+        return self.epc;
+    }
+
+    /// 获取中断发生时的栈指针
+    pub fn sp(&self) -> usize {
+        return self.sp;
+    }
+
+    /// 设置中断返回后将要执行的指令的地址
+    pub fn set_ip(&mut self, ip: usize) {
+        self.epc = ip;
+    }
+
     pub fn new() -> Self {
         Self {
             epc: 0,
@@ -1,3 +1,5 @@
+use system_error::SystemError;
+
 use crate::{
     arch::{sched::sched, CurrentIrqArch},
     exception::InterruptArch,
@@ -185,6 +187,8 @@ pub enum SigCode {
     AsyncIO = -4,
     /// sent by queued SIGIO
     SigIO = -5,
+    /// 通过tkill或者tgkill发送
+    Tkill = -6,
 }
 
 impl SigCode {
@@ -199,11 +203,30 @@ impl SigCode {
             -3 => Self::Mesgq,
             -4 => Self::AsyncIO,
             -5 => Self::SigIO,
+            -6 => Self::Tkill,
             _ => panic!("signal code not valid"),
         }
     }
 }
 
+impl TryFrom<i32> for SigCode {
+    type Error = SystemError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::User),
+            0x80 => Ok(Self::Kernel),
+            -1 => Ok(Self::Queue),
+            -2 => Ok(Self::Timer),
+            -3 => Ok(Self::Mesgq),
+            -4 => Ok(Self::AsyncIO),
+            -5 => Ok(Self::SigIO),
+            -6 => Ok(Self::Tkill),
+            _ => Err(SystemError::EINVAL),
+        }
+    }
+}
+
 bitflags! {
     #[repr(C,align(8))]
     #[derive(Default)]
@@ -297,21 +320,21 @@ impl Into<i32> for SigChildCode {
     }
 }
 
-/// 信号默认处理函数——终止进程
+/// 信号默认处理函数——终止进程（线程组中的所有线程）
 fn sig_terminate(sig: Signal) {
-    ProcessManager::exit(sig as usize);
+    ProcessManager::group_exit(sig as usize);
 }
 
 /// 信号默认处理函数——终止进程并生成 core dump
 fn sig_terminate_dump(sig: Signal) {
-    ProcessManager::exit(sig as usize);
+    ProcessManager::group_exit(sig as usize);
     // TODO 生成 coredump 文件
 }
 
 /// 信号默认处理函数——暂停进程
 fn sig_stop(sig: Signal) {
     let guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
-    ProcessManager::mark_stop().unwrap_or_else(|e| {
+    ProcessManager::mark_stop(sig).unwrap_or_else(|e| {
         kerror!(
             "sleep error :{:?},failed to sleep process :{:?}, with signal :{:?}",
             e,
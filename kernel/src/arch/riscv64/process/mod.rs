@@ -10,6 +10,7 @@ use core::{
     sync::atomic::{compiler_fence, Ordering},
 };
 use kdepends::memoffset::offset_of;
+use riscv::register::sstatus::SPP;
 use system_error::SystemError;
 
 use crate::{
@@ -36,6 +37,7 @@ use super::{
 
 pub mod idle;
 pub mod kthread;
+pub mod ptrace;
 pub mod syscall;
 
 #[allow(dead_code)]
@@ -69,6 +71,11 @@ impl ProcessManager {
         clone_args: KernelCloneArgs,
         current_trapframe: &TrapFrame,
     ) -> Result<(), SystemError> {
+        // 内核线程不会返回用户态，不需要拷贝调用者的栈帧
+        if new_pcb.flags().contains(ProcessFlags::KTHREAD) {
+            return Self::copy_kernel_thread(new_pcb, &clone_args);
+        }
+
         let clone_flags = clone_args.flags;
         let mut child_trapframe = *current_trapframe;
 
@@ -82,12 +89,17 @@ impl ProcessManager {
             kernel_stack_guard.stack_max_address() - core::mem::size_of::<TrapFrame>();
         new_arch_guard.set_stack(trap_frame_vaddr);
 
-        // 拷贝栈帧
+        // 拷贝栈帧。子进程的用户栈指针使用clone传入的栈（CLONE_VM创建的线程必须指定，见Syscall::clone），
+        // 没有指定时继承父进程的栈指针：fork的子进程拥有父进程的栈的一份拷贝，vfork的父进程则在子进程使用栈期间被挂起
         unsafe {
             let usp = clone_args.stack;
             if usp != 0 {
                 child_trapframe.sp = usp;
             }
+            // 设置tls：子进程的tp寄存器使用clone传入的值
+            if clone_flags.contains(CloneFlags::CLONE_SETTLS) {
+                child_trapframe.tp = clone_args.tls;
+            }
             let trap_frame_ptr = trap_frame_vaddr.data() as *mut TrapFrame;
             *trap_frame_ptr = child_trapframe;
         }
@@ -101,17 +113,78 @@ impl ProcessManager {
         drop(current_arch_guard);
 
         // 设置返回地址（子进程开始执行的指令地址）
-        if new_pcb.flags().contains(ProcessFlags::KTHREAD) {
-            let kthread_bootstrap_stage1_func_addr = kernel_thread_bootstrap_stage1 as usize;
-            new_arch_guard.ra = kthread_bootstrap_stage1_func_addr;
-        } else {
-            new_arch_guard.ra = ret_from_exception as usize;
+        new_arch_guard.ra = ret_from_exception as usize;
+
+        return Ok(());
+    }
+
+    /// 为内核线程设置开始执行时的上下文
+    ///
+    /// 在内核栈的顶部放置一个处于S模式、并且开启了中断的栈帧，
+    /// 内核线程从[`kernel_thread_bootstrap_stage1`]开始执行，并以`clone_args.fn_arg`为参数进入第二阶段。
+    /// 浮点寄存器从干净的状态开始，不继承调用者的状态
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.6.21/arch/riscv/kernel/process.c
+    fn copy_kernel_thread(
+        new_pcb: &Arc<ProcessControlBlock>,
+        clone_args: &KernelCloneArgs,
+    ) -> Result<(), SystemError> {
+        if clone_args.fn_arg.is_null() {
+            return Err(SystemError::EINVAL);
         }
 
-        // 设置tls
-        if clone_flags.contains(CloneFlags::CLONE_SETTLS) {
-            drop(new_arch_guard);
-            todo!("set tls");
+        let mut frame = TrapFrame::new();
+        frame.a0 = clone_args.fn_arg.data();
+        // 使能中断
+        frame.status.update_sie(true);
+        frame.status.update_spp(SPP::Supervisor);
+        frame.ra = kernel_thread_bootstrap_stage1 as usize;
+
+        let mut new_arch_guard = unsafe { new_pcb.arch_info() };
+        let kernel_stack_guard = new_pcb.kernel_stack();
+        let trap_frame_vaddr: VirtAddr =
+            kernel_stack_guard.stack_max_address() - core::mem::size_of::<TrapFrame>();
+        new_arch_guard.set_stack(trap_frame_vaddr);
+        unsafe { *(trap_frame_vaddr.data() as *mut TrapFrame) = frame };
+
+        new_arch_guard.fp_state = FpDExtState::new();
+        new_arch_guard.ra = kernel_thread_bootstrap_stage1 as usize;
+
+        return Ok(());
+    }
+
+    /// 检查还没有开始运行的内核线程的上下文，是否是[`Self::copy_kernel_thread`]设置的干净的上下文
+    ///
+    /// 内核线程开始运行之后，栈帧会被它自己的栈覆盖，因此只能在唤醒它之前调用。供自检使用
+    ///
+    /// ## 返回值
+    ///
+    /// 上下文不干净时，返回不正确的地方的描述
+    pub fn check_kernel_thread_frame(pcb: &Arc<ProcessControlBlock>) -> Result<(), &'static str> {
+        let arch_guard = pcb.arch_info_irqsave();
+        let kernel_stack_guard = pcb.kernel_stack();
+        let trap_frame_vaddr: VirtAddr =
+            kernel_stack_guard.stack_max_address() - core::mem::size_of::<TrapFrame>();
+
+        if arch_guard.ra != kernel_thread_bootstrap_stage1 as usize {
+            return Err("ra is not kernel_thread_bootstrap_stage1");
+        }
+        if arch_guard.ksp != trap_frame_vaddr.data() {
+            return Err("ksp does not point to the entry frame");
+        }
+
+        let frame = unsafe { &*(trap_frame_vaddr.data() as *const TrapFrame) };
+        if frame.status.spp() != SPP::Supervisor {
+            return Err("frame does not return to S-mode");
+        }
+        if !frame.status.sie() {
+            return Err("interrupts are not enabled in sstatus");
+        }
+        if frame.ra != kernel_thread_bootstrap_stage1 as usize {
+            return Err("frame ra is not kernel_thread_bootstrap_stage1");
+        }
+        if frame.a0 == 0 {
+            return Err("a0 does not hold fn_arg");
         }
 
         return Ok(());
@@ -0,0 +1,133 @@
+use alloc::sync::Arc;
+use system_error::SystemError;
+
+use crate::{
+    arch::{interrupt::TrapFrame, MMArch},
+    mm::MemoryManagementArch,
+    process::ProcessControlBlock,
+};
+
+/// 被跟踪进程的通用寄存器布局
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/arch/riscv/include/uapi/asm/ptrace.h#19
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UserRegsStruct {
+    pub pc: usize,
+    pub ra: usize,
+    pub sp: usize,
+    pub gp: usize,
+    pub tp: usize,
+    pub t0: usize,
+    pub t1: usize,
+    pub t2: usize,
+    pub s0: usize,
+    pub s1: usize,
+    pub a0: usize,
+    pub a1: usize,
+    pub a2: usize,
+    pub a3: usize,
+    pub a4: usize,
+    pub a5: usize,
+    pub a6: usize,
+    pub a7: usize,
+    pub s2: usize,
+    pub s3: usize,
+    pub s4: usize,
+    pub s5: usize,
+    pub s6: usize,
+    pub s7: usize,
+    pub s8: usize,
+    pub s9: usize,
+    pub s10: usize,
+    pub s11: usize,
+    pub t3: usize,
+    pub t4: usize,
+    pub t5: usize,
+    pub t6: usize,
+}
+
+impl UserRegsStruct {
+    /// 从进程返回用户态时将要恢复的栈帧中读取寄存器
+    pub fn from_trapframe(_pcb: &Arc<ProcessControlBlock>, frame: &TrapFrame) -> Self {
+        return Self {
+            pc: frame.epc,
+            ra: frame.ra,
+            sp: frame.sp,
+            gp: frame.gp,
+            tp: frame.tp,
+            t0: frame.t0,
+            t1: frame.t1,
+            t2: frame.t2,
+            s0: frame.s0,
+            s1: frame.s1,
+            a0: frame.a0,
+            a1: frame.a1,
+            a2: frame.a2,
+            a3: frame.a3,
+            a4: frame.a4,
+            a5: frame.a5,
+            a6: frame.a6,
+            a7: frame.a7,
+            s2: frame.s2,
+            s3: frame.s3,
+            s4: frame.s4,
+            s5: frame.s5,
+            s6: frame.s6,
+            s7: frame.s7,
+            s8: frame.s8,
+            s9: frame.s9,
+            s10: frame.s10,
+            s11: frame.s11,
+            t3: frame.t3,
+            t4: frame.t4,
+            t5: frame.t5,
+            t6: frame.t6,
+        };
+    }
+
+    /// 修改进程返回用户态时将要恢复的栈帧
+    ///
+    /// ## 返回值
+    ///
+    /// - `EIO`: pc不是用户空间的地址
+    pub fn apply_to_trapframe(&self, frame: &mut TrapFrame) -> Result<(), SystemError> {
+        if self.pc >= MMArch::USER_END_VADDR.data() {
+            return Err(SystemError::EIO);
+        }
+
+        frame.epc = self.pc;
+        frame.ra = self.ra;
+        frame.sp = self.sp;
+        frame.gp = self.gp;
+        frame.tp = self.tp;
+        frame.t0 = self.t0;
+        frame.t1 = self.t1;
+        frame.t2 = self.t2;
+        frame.s0 = self.s0;
+        frame.s1 = self.s1;
+        frame.a0 = self.a0;
+        frame.a1 = self.a1;
+        frame.a2 = self.a2;
+        frame.a3 = self.a3;
+        frame.a4 = self.a4;
+        frame.a5 = self.a5;
+        frame.a6 = self.a6;
+        frame.a7 = self.a7;
+        frame.s2 = self.s2;
+        frame.s3 = self.s3;
+        frame.s4 = self.s4;
+        frame.s5 = self.s5;
+        frame.s6 = self.s6;
+        frame.s7 = self.s7;
+        frame.s8 = self.s8;
+        frame.s9 = self.s9;
+        frame.s10 = self.s10;
+        frame.s11 = self.s11;
+        frame.t3 = self.t3;
+        frame.t4 = self.t4;
+        frame.t5 = self.t5;
+        frame.t6 = self.t6;
+        return Ok(());
+    }
+}
@@ -4,7 +4,10 @@ use system_error::SystemError;
 use x86::dtables::DescriptorTablePointer;
 
 use crate::{
-    arch::{interrupt::trap::arch_trap_init, process::table::TSSManager},
+    arch::{
+        interrupt::trap::{arch_double_fault_ist_init, arch_trap_init},
+        process::table::TSSManager,
+    },
     driver::pci::pci::pci_init,
     init::init::start_kernel,
     kdebug,
@@ -100,6 +103,10 @@ pub fn setup_arch_post() -> Result<(), SystemError> {
     hpet_instance().hpet_enable().expect("hpet enable failed");
     TSCManager::init().expect("tsc init failed");
 
+    // 内存管理初始化完成后，才能为双重错误分配独立的中断栈
+    unsafe { TSSManager::set_double_fault_stack() }.expect("set double fault stack failed");
+    arch_double_fault_ist_init();
+
     return Ok(());
 }
 
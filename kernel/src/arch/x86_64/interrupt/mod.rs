@@ -171,4 +171,19 @@ impl TrapFrame {
     pub fn is_from_user(&self) -> bool {
         return (self.cs & 0x3) != 0;
     }
+
+    /// 获取中断返回后将要执行的指令的地址
+    pub fn ip(&self) -> usize {
+        return self.rip as usize;
+    }
+
+    /// 获取中断发生时的栈指针
+    pub fn sp(&self) -> usize {
+        return self.rsp as usize;
+    }
+
+    /// 设置中断返回后将要执行的指令的地址
+    pub fn set_ip(&mut self, ip: usize) {
+        self.rip = ip as u64;
+    }
 }
@@ -1,8 +1,13 @@
 use system_error::SystemError;
 
 use crate::{
-    arch::CurrentIrqArch, exception::InterruptArch, kerror, kwarn, mm::VirtAddr, print,
-    process::ProcessManager, smp::core::smp_get_processor_id,
+    arch::{ipc::signal::Signal, process::table::DOUBLE_FAULT_IST_INDEX, CurrentIrqArch},
+    exception::InterruptArch,
+    kerror, kwarn,
+    mm::{oom::pagefault_out_of_memory, VirtAddr},
+    print,
+    process::{KernelStack, ProcessControlBlock, ProcessManager},
+    smp::core::smp_get_processor_id,
 };
 
 use super::{
@@ -65,6 +70,31 @@ pub fn arch_trap_init() -> Result<(), SystemError> {
     return Ok(());
 }
 
+/// 让双重错误(#DF)在独立的中断栈上处理
+///
+/// 必须在当前cpu已经通过`TSSManager::set_double_fault_stack`设置好中断栈之后调用，
+/// 其他cpu需要在开启中断之前设置好自己的中断栈
+pub fn arch_double_fault_ist_init() {
+    unsafe {
+        set_intr_gate(
+            8,
+            DOUBLE_FAULT_IST_INDEX,
+            VirtAddr::new(trap_double_fault as usize),
+        )
+    };
+}
+
+/// 判断异常是否由内核栈溢出（访问了内核栈的保护页）引起
+///
+/// ## 参数
+///
+/// - `regs` : 异常发生时的寄存器
+/// - `address` : 异常时访问的地址
+fn is_kernel_stack_overflow(regs: &TrapFrame, address: VirtAddr) -> bool {
+    return !regs.is_from_user()
+        && KernelStack::is_guard_page_address(address, VirtAddr::new(regs.rsp as usize));
+}
+
 /// 处理除法错误 0 #DE
 #[no_mangle]
 unsafe extern "C" fn do_divide_error(regs: &'static TrapFrame, error_code: u64) {
@@ -180,6 +210,28 @@ unsafe extern "C" fn do_dev_not_avaliable(regs: &'static TrapFrame, error_code:
 /// 处理双重错误 8 #DF
 #[no_mangle]
 unsafe extern "C" fn do_double_fault(regs: &'static TrapFrame, error_code: u64) {
+    // 当前运行在双重错误专用的中断栈上，需要把发生错误的内核栈上的pcb指针复制过来，
+    // 使得current_pcb()能正常工作。由于接下来一定会panic，因此这里不增加Weak指针的引用计数
+    let faulting_stack_base = regs.rsp as usize & !(KernelStack::ALIGN - 1);
+    let current_stack_base = x86::current::registers::rsp() as usize & !(KernelStack::ALIGN - 1);
+    if faulting_stack_base != current_stack_base && !regs.is_from_user() {
+        *(current_stack_base as *mut *const ProcessControlBlock) =
+            *(faulting_stack_base as *const *const ProcessControlBlock);
+    }
+
+    let address = VirtAddr::new(x86::controlregs::cr2() as usize);
+    if is_kernel_stack_overflow(regs, address) {
+        kerror!(
+            "kernel stack overflow, \trsp: {:#x},\trip: {:#x},\t CPU: {}, \tpid: {:?}, \nFault Address: {:#x}",
+            regs.rsp,
+            regs.rip,
+            smp_get_processor_id().data(),
+            ProcessManager::current_pid(),
+            address.data()
+        );
+        panic!("Kernel Stack Overflow");
+    }
+
     kerror!(
         "do_double_fault(8), \tError code: {:#x},\trsp: {:#x},\trip: {:#x},\t CPU: {}, \tpid: {:?}",
         error_code,
@@ -318,6 +370,62 @@ Segment Selector Index: {:#x}\n
 /// 处理页错误 14 #PF
 #[no_mangle]
 unsafe extern "C" fn do_page_fault(regs: &'static TrapFrame, error_code: u64) {
+    let address = VirtAddr::new(x86::controlregs::cr2() as usize);
+
+    // 对用户空间中已存在的页面进行写入，可能是写时复制引起的缺页
+    if (error_code & 0x03) == 0x03 && address.check_user() {
+        let pcb = ProcessManager::current_pcb();
+        let vm = pcb.basic().user_vm();
+        if let Some(vm) = vm {
+            // 触发缺页的代码如果持有自旋锁（preempt_count不为0），它可能已经持有了这个地址空间的锁，
+            // 等待这个锁会造成死锁，因此只尝试获取一次，失败时按照EFAULT处理
+            let guard = if pcb.preempt_count() == 0 {
+                Some(vm.write_irqsave())
+            } else {
+                vm.try_write_irqsave()
+            };
+            let r = match guard {
+                Some(mut guard) => guard.handle_cow_fault(address),
+                None => Err(SystemError::EFAULT),
+            };
+            match r {
+                // 写时复制不需要读取磁盘，是一次minor缺页
+                Ok(_) => {
+                    pcb.stats().inc_fault(false);
+                    return;
+                }
+                // 用户态的写时复制由于内存不足而失败时，杀死一个进程之后重新执行触发缺页的指令
+                Err(SystemError::ENOMEM) if (error_code & 0x04) != 0 => {
+                    if pagefault_out_of_memory() {
+                        return;
+                    }
+                }
+                Err(_) => {}
+            }
+        }
+    }
+
+    // 用户态访问了没有映射（例如fork时没有被子进程继承的MADV_DONTFORK区域）或者没有权限访问的地址，
+    // 向当前线程发送SIGSEGV，返回用户态之前会处理这个信号
+    if (error_code & 0x04) != 0 {
+        Signal::SIGSEGV
+            .force_send(ProcessManager::current_pcb())
+            .expect("Failed to send SIGSEGV");
+        return;
+    }
+
+    if is_kernel_stack_overflow(regs, address) {
+        kerror!(
+            "kernel stack overflow, \trsp: {:#x},\trip: {:#x},\t CPU: {}, \tpid: {:?}, \nFault Address: {:#x}",
+            regs.rsp,
+            regs.rip,
+            smp_get_processor_id().data(),
+            ProcessManager::current_pid(),
+            address.data()
+        );
+        panic!("Kernel Stack Overflow");
+    }
+
     kerror!(
         "do_page_fault(14), \tError code: {:#x},\trsp: {:#x},\trip: {:#x},\t CPU: {}, \tpid: {:?}, \nFault Address: {:#x}",
         error_code,
@@ -12,13 +12,20 @@ use crate::{
     exception::InterruptArch,
     ipc::{
         signal::set_current_sig_blocked,
-        signal_types::{SaHandlerType, SigInfo, Sigaction, SigactionType, SignalArch},
+        signal_types::{
+            PosixSigInfo, SaHandlerType, SigInfo, SigType, Sigaction, SigactionType, SignalArch,
+            SIG_KERNEL_ONLY_MASK,
+        },
     },
     kerror,
     mm::MemoryManagementArch,
-    process::ProcessManager,
+    process::{Pid, ProcessManager},
     sched::{schedule, SchedMode},
-    syscall::{user_access::UserBufferWriter, Syscall},
+    syscall::{
+        rseq::{rseq_handle_notify_resume, rseq_signal_deliver},
+        user_access::UserBufferWriter,
+        Syscall,
+    },
 };
 
 /// 信号处理的栈的栈指针的最小对齐数量
@@ -203,6 +210,8 @@ pub enum SigCode {
     AsyncIO = -4,
     /// sent by queued SIGIO
     SigIO = -5,
+    /// 通过tkill或者tgkill发送
+    Tkill = -6,
 }
 
 impl SigCode {
@@ -217,11 +226,30 @@ impl SigCode {
             -3 => Self::Mesgq,
             -4 => Self::AsyncIO,
             -5 => Self::SigIO,
+            -6 => Self::Tkill,
             _ => panic!("signal code not valid"),
         }
     }
 }
 
+impl TryFrom<i32> for SigCode {
+    type Error = SystemError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::User),
+            0x80 => Ok(Self::Kernel),
+            -1 => Ok(Self::Queue),
+            -2 => Ok(Self::Timer),
+            -3 => Ok(Self::Mesgq),
+            -4 => Ok(Self::AsyncIO),
+            -5 => Ok(Self::SigIO),
+            -6 => Ok(Self::Tkill),
+            _ => Err(SystemError::EINVAL),
+        }
+    }
+}
+
 bitflags! {
     #[repr(C,align(8))]
     #[derive(Default)]
@@ -322,7 +350,7 @@ pub struct SigFrame {
     /// 指向restorer的地址的指针。（该变量必须放在sigframe的第一位，因为这样才能在handler返回的时候，跳转到对应的代码，执行sigreturn)
     pub ret_code_ptr: *mut core::ffi::c_void,
     pub handler: *mut c_void,
-    pub info: SigInfo,
+    pub info: PosixSigInfo,
     pub context: SigContext,
 }
 
@@ -367,7 +395,7 @@ impl SigContext {
         // context.trap_num = unsafe { (*current_thread).trap_num };
         // context.err_code = unsafe { (*current_thread).err_code };
         // context.cr2 = unsafe { (*current_thread).cr2 };
-        self.reserved_for_x87_state = *archinfo_guard.fp_state();
+        self.reserved_for_x87_state = archinfo_guard.fp_state();
 
         // 保存完毕后，清空fp_state，以免下次save的时候，出现SIMD exception
         archinfo_guard.clear_fp_state();
@@ -390,7 +418,7 @@ impl SigContext {
         *arch_info.cr2_mut() = self.cr2 as usize;
         // (*current_thread).err_code = (*context).err_code;
         // 如果当前进程有fpstate，则将其恢复到pcb的fp_state中
-        *arch_info.fp_state_mut() = self.reserved_for_x87_state;
+        arch_info.set_fp_state(self.reserved_for_x87_state);
         arch_info.restore_fp_state();
         return true;
     }
@@ -406,6 +434,12 @@ pub struct SigStack {
 
 #[no_mangle]
 unsafe extern "C" fn do_signal(frame: &mut TrapFrame) {
+    if frame.is_from_user() {
+        // spawn创建的进程在第一次返回用户态之前执行新的程序
+        ProcessManager::spawn_exec(frame);
+        // 返回用户态之前处理rseq，若因此产生了信号，则可以在下面被立即处理
+        rseq_handle_notify_resume(frame);
+    }
     X86_64SignalArch::do_signal(frame);
     return;
 }
@@ -435,7 +469,9 @@ impl SignalArch for X86_64SignalArch {
         let mut sig_number: Signal;
         let mut info: Option<SigInfo>;
         let mut sigaction: Sigaction;
-        let sig_block: SigSet = *siginfo_read_guard.sig_block();
+        // SIGKILL、SIGSTOP不能被屏蔽
+        let mut sig_block: SigSet = *siginfo_read_guard.sig_block();
+        sig_block.remove(SIG_KERNEL_ONLY_MASK);
         drop(siginfo_read_guard);
 
         let sig_guard = pcb.try_sig_struct_irqsave(5);
@@ -447,7 +483,7 @@ impl SignalArch for X86_64SignalArch {
             return;
         }
 
-        let sig_guard = sig_guard.unwrap();
+        let mut sig_guard = sig_guard.unwrap();
         let mut siginfo_mut_guard = siginfo_mut.unwrap();
         loop {
             (sig_number, info) = siginfo_mut_guard.dequeue_signal(&sig_block);
@@ -456,7 +492,38 @@ impl SignalArch for X86_64SignalArch {
                 return;
             }
 
-            sigaction = sig_guard.handlers[sig_number as usize - 1];
+            // 被跟踪的进程先停止下来，由跟踪者决定如何处理这个信号（SIGKILL除外）
+            if sig_number != Signal::SIGKILL && pcb.is_traced() {
+                drop(siginfo_mut_guard);
+                drop(sig_guard);
+                let resume = ProcessManager::ptrace_signal(sig_number, frame);
+                sig_guard = pcb.sig_struct_irqsave();
+                siginfo_mut_guard = pcb.sig_info_mut();
+
+                // 跟踪者丢弃了这个信号
+                if resume == Signal::INVALID {
+                    continue;
+                }
+                // 跟踪者指定了另一个信号，将其当作由跟踪者发送的信号来处理
+                if resume != sig_number {
+                    let tracer = pcb.tracer().map(|tracer| tracer.pid()).unwrap_or(Pid(0));
+                    info = Some(SigInfo::new(
+                        resume,
+                        0,
+                        SigCode::User,
+                        SigType::Kill(tracer),
+                    ));
+                    sig_number = resume;
+                }
+            }
+
+            // SIGKILL、SIGSTOP总是按照默认方式处理，不管处理函数表中记录了什么
+            if sig_number.kernel_only() {
+                sigaction = Sigaction::default();
+                break;
+            }
+
+            sigaction = sig_guard.action(sig_number);
 
             match sigaction.action() {
                 SigactionType::SaHandler(action_type) => match action_type {
@@ -503,8 +570,11 @@ impl SignalArch for X86_64SignalArch {
         // 如果当前的rsp不来自用户态，则认为产生了错误（或被SROP攻击）
         if UserBufferWriter::new(frame, size_of::<SigFrame>(), true).is_err() {
             kerror!("rsp doesn't from user level");
-            let _r = Syscall::kill(ProcessManager::current_pcb().pid(), Signal::SIGSEGV as i32)
-                .map_err(|e| e.to_posix_errno());
+            let _r = Syscall::kill(
+                ProcessManager::current_pcb().pid().data() as i32,
+                Signal::SIGSEGV as i32,
+            )
+            .map_err(|e| e.to_posix_errno());
             return trap_frame.rax;
         }
         let mut sigmask: SigSet = unsafe { (*frame).context.oldmask };
@@ -512,8 +582,11 @@ impl SignalArch for X86_64SignalArch {
         // 从用户栈恢复sigcontext
         if !unsafe { &mut (*frame).context }.restore_sigcontext(trap_frame) {
             kerror!("unable to restore sigcontext");
-            let _r = Syscall::kill(ProcessManager::current_pcb().pid(), Signal::SIGSEGV as i32)
-                .map_err(|e| e.to_posix_errno());
+            let _r = Syscall::kill(
+                ProcessManager::current_pcb().pid().data() as i32,
+                Signal::SIGSEGV as i32,
+            )
+            .map_err(|e| e.to_posix_errno());
             // 如果这里返回 err 值的话会丢失上一个系统调用的返回值
         }
         // 由于系统调用的返回值会被系统调用模块被存放在rax寄存器，因此，为了还原原来的那个系统调用的返回值，我们需要在这里返回恢复后的rax的值
@@ -539,6 +612,9 @@ fn handle_signal(
 ) -> Result<i32, SystemError> {
     // TODO 这里要补充一段逻辑，好像是为了保证引入线程之后的地址空间不会出问题。详见https://code.dragonos.org.cn/xref/linux-6.1.9/arch/mips/kernel/signal.c#830
 
+    // 如果进程正处于rseq临界区中，则先中止临界区，使得信号处理函数返回到abort_ip
+    rseq_signal_deliver(frame);
+
     // 设置栈帧
     return setup_frame(sig, sigaction, info, oldset, frame);
 }
@@ -584,7 +660,7 @@ fn setup_frame(
                             sig as i32
                         );
                         let r = Syscall::kill(
-                            ProcessManager::current_pcb().pid(),
+                            ProcessManager::current_pcb().pid().data() as i32,
                             Signal::SIGSEGV as i32,
                         );
                         if r.is_err() {
@@ -615,7 +691,7 @@ fn setup_frame(
             return Err(SystemError::EINVAL);
         }
     }
-    let frame: *mut SigFrame = get_stack(trap_frame, size_of::<SigFrame>());
+    let frame: *mut SigFrame = get_stack(sigaction, trap_frame, size_of::<SigFrame>());
     // kdebug!("frame=0x{:016x}", frame as usize);
     // 要求这个frame的地址位于用户空间，因此进行校验
     let r: Result<UserBufferWriter<'_>, SystemError> =
@@ -623,7 +699,10 @@ fn setup_frame(
     if r.is_err() {
         // 如果地址区域位于内核空间，则直接报错
         // todo: 生成一个sigsegv
-        let r = Syscall::kill(ProcessManager::current_pcb().pid(), Signal::SIGSEGV as i32);
+        let r = Syscall::kill(
+            ProcessManager::current_pcb().pid().data() as i32,
+            Signal::SIGSEGV as i32,
+        );
         if r.is_err() {
             kerror!("In setup frame: generate SIGSEGV signal failed");
         }
@@ -632,23 +711,38 @@ fn setup_frame(
     }
 
     // 将siginfo拷贝到用户栈
-    info.copy_siginfo_to_user(unsafe { &mut ((*frame).info) as *mut SigInfo })
+    info.copy_siginfo_to_user(unsafe { &mut ((*frame).info) as *mut PosixSigInfo })
         .map_err(|e| -> SystemError {
-            let r = Syscall::kill(ProcessManager::current_pcb().pid(), Signal::SIGSEGV as i32);
+            let r = Syscall::kill(
+                ProcessManager::current_pcb().pid().data() as i32,
+                Signal::SIGSEGV as i32,
+            );
             if r.is_err() {
                 kerror!("In copy_siginfo_to_user: generate SIGSEGV signal failed");
             }
             return e;
         })?;
 
-    // todo: 拷贝处理程序备用栈的地址、大小、ss_flags
+    // 拷贝处理程序备用栈的地址、大小、ss_flags
+    let altstack = ProcessManager::current_pcb()
+        .sig_info_irqsave()
+        .sig_altstack()
+        .to_posix(trap_frame.rsp as usize);
+    unsafe {
+        (*frame).context.sc_stack.sp = altstack.ss_sp as *mut c_void;
+        (*frame).context.sc_stack.flags = altstack.ss_flags as u32;
+        (*frame).context.sc_stack.size = altstack.ss_size as u32;
+    }
 
     unsafe {
         (*frame)
             .context
             .setup_sigcontext(oldset, trap_frame)
             .map_err(|e: SystemError| -> SystemError {
-                let r = Syscall::kill(ProcessManager::current_pcb().pid(), Signal::SIGSEGV as i32);
+                let r = Syscall::kill(
+                    ProcessManager::current_pcb().pid().data() as i32,
+                    Signal::SIGSEGV as i32,
+                );
                 if r.is_err() {
                     kerror!("In setup_sigcontext: generate SIGSEGV signal failed");
                 }
@@ -664,7 +758,7 @@ fn setup_frame(
     unsafe { (*frame).handler = temp_handler };
     // 传入信号处理函数的第一个参数
     trap_frame.rdi = sig as u64;
-    trap_frame.rsi = unsafe { &(*frame).info as *const SigInfo as u64 };
+    trap_frame.rsi = unsafe { &(*frame).info as *const PosixSigInfo as u64 };
     trap_frame.rsp = frame as u64;
     trap_frame.rip = unsafe { (*frame).handler as u64 };
     // 设置cs和ds寄存器
@@ -678,12 +772,20 @@ fn setup_frame(
 }
 
 #[inline(always)]
-fn get_stack(frame: &TrapFrame, size: usize) -> *mut SigFrame {
-    // TODO:在 linux 中会根据 Sigaction 中的一个flag 的值来确定是否使用pcb中的 signal 处理程序备用堆栈，现在的
-    // pcb中也没有这个备用堆栈
+fn get_stack(sigaction: &Sigaction, frame: &TrapFrame, size: usize) -> *mut SigFrame {
+    let mut sp = frame.rsp as usize;
+    // 如果设置了SA_ONSTACK，并且当前不在备用栈上执行，则切换到备用栈
+    if sigaction.flags().contains(SigFlags::SA_ONSTACK) {
+        let altstack = *ProcessManager::current_pcb()
+            .sig_info_irqsave()
+            .sig_altstack();
+        if !altstack.is_disabled() && !altstack.on_stack(sp) {
+            sp = altstack.top();
+        }
+    }
 
     // 默认使用 用户栈的栈顶指针-128字节的红区-sigframe的大小 并且16字节对齐
-    let mut rsp: usize = (frame.rsp as usize) - 128 - size;
+    let mut rsp: usize = sp - 128 - size;
     // 按照要求进行对齐，别问为什么减8，不减8就是错的，可以看
     // https://sourcegraph.com/github.com/torvalds/linux@dd72f9c7e512da377074d47d990564959b772643/-/blob/arch/x86/kernel/signal.c?L124
     // 我猜测是跟x86汇编的某些弹栈行为有关系，它可能会出于某种原因递增 rsp
@@ -692,21 +794,21 @@ fn get_stack(frame: &TrapFrame, size: usize) -> *mut SigFrame {
     return rsp as *mut SigFrame;
 }
 
-/// 信号默认处理函数——终止进程
+/// 信号默认处理函数——终止进程（线程组中的所有线程）
 fn sig_terminate(sig: Signal) {
-    ProcessManager::exit(sig as usize);
+    ProcessManager::group_exit(sig as usize);
 }
 
 /// 信号默认处理函数——终止进程并生成 core dump
 fn sig_terminate_dump(sig: Signal) {
-    ProcessManager::exit(sig as usize);
+    ProcessManager::group_exit(sig as usize);
     // TODO 生成 coredump 文件
 }
 
 /// 信号默认处理函数——暂停进程
 fn sig_stop(sig: Signal) {
     let guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
-    ProcessManager::mark_stop().unwrap_or_else(|e| {
+    ProcessManager::mark_stop(sig).unwrap_or_else(|e| {
         kerror!(
             "sleep error :{:?},failed to sleep process :{:?}, with signal :{:?}",
             e,
@@ -0,0 +1,86 @@
+//! 进程管理相关的性能测试
+//!
+//! 这些测试不会被自动运行，需要时向`/sys/kernel/selftest`写入测试的名字来运行，
+//! 例如`echo bench_current_pcb > /sys/kernel/selftest`，结果输出到内核日志中。
+//! 见[`run_selftest`](crate::process::selftest::run_selftest)
+
+use core::hint::black_box;
+
+use system_error::SystemError;
+
+use crate::{
+    arch::fpu::FpState,
+    kinfo,
+    process::{ProcessControlBlock, ProcessManager},
+    time::timekeep::ktime_get_real_ns,
+};
+
+use super::ArchPCBInfo;
+
+/// 测量fork时复制ArchPCBInfo所需的时间
+///
+/// 分别测量立即拷贝浮点寄存器状态（原来的做法）与让子进程继承父进程的快照两种方式，
+/// 并输出平均耗时
+pub fn bench_clone_arch_info() -> Result<(), SystemError> {
+    const ROUNDS: i64 = 100000;
+
+    let pcb = ProcessManager::current_pcb();
+    let mut guard = pcb.arch_info_irqsave();
+    guard.save_fp_state();
+
+    let start = ktime_get_real_ns();
+    for _ in 0..ROUNDS {
+        let mut child = guard.clone_all();
+        child.fp_state = guard.fp_state;
+        if let Some(fp_state) = guard.fp_state.as_ref() {
+            child.fp_state = Some(*fp_state);
+        }
+        black_box(&child);
+    }
+    let eager_ns = ktime_get_real_ns() - start;
+
+    let start = ktime_get_real_ns();
+    for _ in 0..ROUNDS {
+        let mut child = guard.clone_all();
+        child.inherited_fp_state = Some(ArchPCBInfo::snapshot_fp_state());
+        black_box(&child);
+    }
+    let lazy_ns = ktime_get_real_ns() - start;
+    drop(guard);
+
+    kinfo!(
+        "bench_clone_arch_info: eager copy takes {} ns, lazy inherit takes {} ns on average (FpState: {} bytes, {} rounds)",
+        eager_ns / ROUNDS,
+        lazy_ns / ROUNDS,
+        core::mem::size_of::<FpState>(),
+        ROUNDS
+    );
+    return Ok(());
+}
+
+/// 比较从内核栈中获取当前进程的pcb（原来的做法）与读取每个cpu的当前进程缓存两种方式的耗时
+///
+/// 需要在进程切换发生过之后调用，否则缓存为空，两种方式都会从内核栈中获取
+pub fn bench_current_pcb() -> Result<(), SystemError> {
+    const ROUNDS: i64 = 1000000;
+
+    let start = ktime_get_real_ns();
+    for _ in 0..ROUNDS {
+        black_box(ProcessControlBlock::arch_current_pcb());
+    }
+    let stack_ns = ktime_get_real_ns() - start;
+
+    let start = ktime_get_real_ns();
+    for _ in 0..ROUNDS {
+        black_box(ProcessManager::current_pcb());
+    }
+    let cached_ns = ktime_get_real_ns() - start;
+
+    kinfo!(
+        "bench_current_pcb: reading the kernel stack takes {} ns, reading the per-cpu cache takes {} ns on average ({} rounds)",
+        stack_ns / ROUNDS,
+        cached_ns / ROUNDS,
+        ROUNDS
+    );
+    return Ok(());
+}
@@ -30,14 +30,15 @@ use crate::{
 
 use self::{
     kthread::kernel_thread_bootstrap_stage1,
-    syscall::ARCH_SET_FS,
-    table::{switch_fs_and_gs, KERNEL_DS, USER_DS},
+    table::{switch_fs_and_gs, KERNEL_CS, KERNEL_DS, USER_DS},
 };
 
 use super::{fpu::FpState, interrupt::TrapFrame, syscall::X86_64GSData, CurrentIrqArch};
 
+pub mod bench;
 pub mod idle;
 pub mod kthread;
+pub mod ptrace;
 pub mod syscall;
 pub mod table;
 
@@ -81,6 +82,12 @@ pub struct ArchPCBInfo {
     gsdata: X86_64GSData,
     /// 浮点寄存器的状态
     fp_state: Option<FpState>,
+    /// fork时从父进程继承的浮点寄存器状态
+    ///
+    /// 子进程不会立即拷贝这份状态，而是在切换到子进程时直接从这里恢复寄存器。
+    /// 只有在子进程第一次被切换出去时，才会把寄存器保存到自己的`fp_state`中；
+    /// 如果子进程在此之前就执行了execve，那么这份状态会被直接丢弃
+    inherited_fp_state: Option<Arc<FpState>>,
 }
 
 #[allow(dead_code)]
@@ -116,6 +123,7 @@ impl ArchPCBInfo {
             fs: KERNEL_DS,
             gs: KERNEL_DS,
             fp_state: None,
+            inherited_fp_state: None,
         };
 
         r.rsp = kstack.stack_max_address().data() - 8;
@@ -147,6 +155,10 @@ impl ArchPCBInfo {
         value
     }
 
+    /// 保存浮点寄存器
+    ///
+    /// 如果进程还在使用从父进程继承的状态，那么寄存器会被保存到进程自己的`fp_state`中，
+    /// 之后便不再使用继承的状态。继承的状态不会在这里释放，以免在进程切换的过程中释放内存
     pub fn save_fp_state(&mut self) {
         if self.fp_state.is_none() {
             self.fp_state = Some(FpState::new());
@@ -155,27 +167,54 @@ impl ArchPCBInfo {
         self.fp_state.as_mut().unwrap().save();
     }
 
+    /// 恢复浮点寄存器。进程自己的状态不存在时，从继承自父进程的状态中恢复
     pub fn restore_fp_state(&mut self) {
-        if unlikely(self.fp_state.is_none()) {
-            return;
+        if let Some(fp_state) = self.fp_state.as_ref() {
+            fp_state.restore();
+        } else if let Some(fp_state) = self.inherited_fp_state.as_ref() {
+            fp_state.restore();
         }
-
-        self.fp_state.as_mut().unwrap().restore();
     }
 
     /// 返回浮点寄存器结构体的副本
-    pub fn fp_state(&self) -> &Option<FpState> {
-        &self.fp_state
+    pub fn fp_state(&self) -> Option<FpState> {
+        self.fp_state
+            .or_else(|| self.inherited_fp_state.as_deref().copied())
+    }
+
+    /// 设置进程自己的浮点寄存器状态，并丢弃从父进程继承的状态
+    pub fn set_fp_state(&mut self, fp_state: Option<FpState>) {
+        self.inherited_fp_state = None;
+        self.fp_state = fp_state;
     }
 
     // 清空浮点寄存器
     pub fn clear_fp_state(&mut self) {
-        if unlikely(self.fp_state.is_none()) {
+        if unlikely(self.fp_state.is_none() && self.inherited_fp_state.is_none()) {
             kwarn!("fp_state is none");
             return;
         }
 
-        self.fp_state.as_mut().unwrap().clear();
+        self.inherited_fp_state = None;
+        self.fp_state.get_or_insert_with(FpState::new).clear();
+    }
+
+    /// 把浮点寄存器重置为初始状态，用于execve
+    ///
+    /// 从父进程继承的状态会被直接丢弃，不会被拷贝
+    pub fn reset_fp_state(&mut self) {
+        self.inherited_fp_state = None;
+        self.fp_state.get_or_insert_with(FpState::new).clear();
+    }
+
+    /// 为子进程生成浮点寄存器状态的快照
+    ///
+    /// 必须由当前进程调用。快照直接从寄存器中保存，而不是拷贝`fp_state`，
+    /// 因为当前进程的`fp_state`只在进程切换时更新，可能已经过时
+    fn snapshot_fp_state() -> Arc<FpState> {
+        let mut fp_state = Arc::new(FpState::new());
+        Arc::get_mut(&mut fp_state).unwrap().save();
+        return fp_state;
     }
     pub unsafe fn save_fsbase(&mut self) {
         if x86::controlregs::cr4().contains(Cr4::CR4_ENABLE_FSGSBASE) {
@@ -234,11 +273,9 @@ impl ArchPCBInfo {
         &mut self.cr2
     }
 
-    pub fn fp_state_mut(&mut self) -> &mut Option<FpState> {
-        &mut self.fp_state
-    }
-
     /// ### 克隆ArchPCBInfo,需要注意gsdata也是对应clone的
+    ///
+    /// 浮点寄存器的状态不会被克隆，它由copy_thread设置
     pub fn clone_all(&self) -> Self {
         Self {
             rflags: self.rflags,
@@ -256,7 +293,8 @@ impl ArchPCBInfo {
             fs: self.fs,
             gs: self.gs,
             gsdata: self.gsdata.clone(),
-            fp_state: self.fp_state,
+            fp_state: None,
+            inherited_fp_state: None,
         }
     }
 
@@ -306,6 +344,11 @@ impl ProcessManager {
         clone_args: KernelCloneArgs,
         current_trapframe: &TrapFrame,
     ) -> Result<(), SystemError> {
+        // 内核线程不会返回用户态，不需要拷贝调用者的栈帧
+        if new_pcb.flags().contains(ProcessFlags::KTHREAD) {
+            return Self::copy_kernel_thread(new_pcb, &clone_args);
+        }
+
         let clone_flags = clone_args.flags;
         let mut child_trapframe = *current_trapframe;
 
@@ -323,7 +366,8 @@ impl ProcessManager {
             kernel_stack_guard.stack_max_address() - core::mem::size_of::<TrapFrame>();
         new_arch_guard.set_stack(trap_frame_vaddr);
 
-        // 拷贝栈帧
+        // 拷贝栈帧。子进程的用户栈指针使用clone传入的栈（CLONE_VM创建的线程必须指定，见Syscall::clone），
+        // 没有指定时继承父进程的栈指针：fork的子进程拥有父进程的栈的一份拷贝，vfork的父进程则在子进程使用栈期间被挂起
         unsafe {
             let usp = clone_args.stack;
             if usp != 0 {
@@ -333,31 +377,134 @@ impl ProcessManager {
             *trap_frame_ptr = child_trapframe;
         }
 
-        let current_arch_guard = current_pcb.arch_info_irqsave();
+        let mut current_arch_guard = current_pcb.arch_info_irqsave();
+        // 父进程可能在用户态修改过fsbase，需要先从寄存器中读取最新的值
+        unsafe { current_arch_guard.save_fsbase() };
         new_arch_guard.fsbase = current_arch_guard.fsbase;
         new_arch_guard.gsbase = current_arch_guard.gsbase;
         new_arch_guard.fs = current_arch_guard.fs;
         new_arch_guard.gs = current_arch_guard.gs;
-        new_arch_guard.fp_state = current_arch_guard.fp_state;
 
-        // 拷贝浮点寄存器的状态
-        if let Some(fp_state) = current_arch_guard.fp_state.as_ref() {
-            new_arch_guard.fp_state = Some(*fp_state);
-        }
+        // 子进程继承父进程当前的浮点寄存器状态，直到子进程第一次被切换出去时才拥有自己的副本
+        new_arch_guard.fp_state = None;
+        new_arch_guard.inherited_fp_state = Some(ArchPCBInfo::snapshot_fp_state());
         drop(current_arch_guard);
 
         // 设置返回地址（子进程开始执行的指令地址）
-        if new_pcb.flags().contains(ProcessFlags::KTHREAD) {
-            let kthread_bootstrap_stage1_func_addr = kernel_thread_bootstrap_stage1 as usize;
-            new_arch_guard.rip = kthread_bootstrap_stage1_func_addr;
-        } else {
-            new_arch_guard.rip = ret_from_intr as usize;
-        }
+        new_arch_guard.rip = ret_from_intr as usize;
 
-        // 设置tls
+        // 设置tls：子进程的fsbase使用clone传入的值，而不是继承父进程的
         if clone_flags.contains(CloneFlags::CLONE_SETTLS) {
-            drop(new_arch_guard);
-            Syscall::do_arch_prctl_64(new_pcb, ARCH_SET_FS, clone_args.tls, true)?;
+            new_arch_guard.fsbase = clone_args.tls;
+        }
+
+        return Ok(());
+    }
+
+    /// 为内核线程设置开始执行时的上下文
+    ///
+    /// 在内核栈的顶部放置一个只包含内核段选择子、并且开启了中断的栈帧，
+    /// 内核线程从[`kernel_thread_bootstrap_stage1`]开始执行，它弹出这个栈帧之后，
+    /// 以`clone_args.fn_arg`为参数跳转到第二阶段。
+    /// 内核线程不使用用户态的tls，浮点寄存器也从干净的状态开始，不继承调用者的状态
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/arch/x86/kernel/process.c#137
+    fn copy_kernel_thread(
+        new_pcb: &Arc<ProcessControlBlock>,
+        clone_args: &KernelCloneArgs,
+    ) -> Result<(), SystemError> {
+        if clone_args.fn_arg.is_null() {
+            return Err(SystemError::EINVAL);
+        }
+
+        let mut frame = TrapFrame::new();
+        frame.rbx = clone_args.fn_arg.data() as u64;
+        frame.ds = KERNEL_DS.bits() as u64;
+        frame.es = KERNEL_DS.bits() as u64;
+        frame.cs = KERNEL_CS.bits() as u64;
+        frame.ss = KERNEL_DS.bits() as u64;
+        // 使能中断
+        frame.rflags |= 1 << 9;
+        frame.rip = kernel_thread_bootstrap_stage1 as usize as u64;
+
+        let mut new_arch_guard = unsafe { new_pcb.arch_info() };
+        let kernel_stack_guard = new_pcb.kernel_stack();
+        new_arch_guard.set_stack_base(kernel_stack_guard.stack_max_address());
+
+        let trap_frame_vaddr: VirtAddr =
+            kernel_stack_guard.stack_max_address() - core::mem::size_of::<TrapFrame>();
+        new_arch_guard.set_stack(trap_frame_vaddr);
+        unsafe { *(trap_frame_vaddr.data() as *mut TrapFrame) = frame };
+
+        new_arch_guard.reset_fp_state();
+        new_arch_guard.rip = kernel_thread_bootstrap_stage1 as usize;
+
+        return Ok(());
+    }
+
+    /// 检查还没有开始运行的内核线程的上下文，是否是[`Self::copy_kernel_thread`]设置的干净的上下文
+    ///
+    /// 内核线程开始运行之后，栈帧会被它自己的栈覆盖，因此只能在唤醒它之前调用。供自检使用
+    ///
+    /// ## 返回值
+    ///
+    /// 上下文不干净时，返回不正确的地方的描述
+    pub fn check_kernel_thread_frame(pcb: &Arc<ProcessControlBlock>) -> Result<(), &'static str> {
+        let arch_guard = pcb.arch_info_irqsave();
+        let kernel_stack_guard = pcb.kernel_stack();
+        let trap_frame_vaddr: VirtAddr =
+            kernel_stack_guard.stack_max_address() - core::mem::size_of::<TrapFrame>();
+
+        if arch_guard.rip != kernel_thread_bootstrap_stage1 as usize {
+            return Err("rip is not kernel_thread_bootstrap_stage1");
+        }
+        if arch_guard.rsp != trap_frame_vaddr.data() {
+            return Err("rsp does not point to the entry frame");
+        }
+        if arch_guard.fsbase != 0 {
+            return Err("fsbase is inherited from the caller");
+        }
+
+        let frame = unsafe { *(trap_frame_vaddr.data() as *const TrapFrame) };
+        if frame.cs != KERNEL_CS.bits() as u64
+            || frame.ds != KERNEL_DS.bits() as u64
+            || frame.es != KERNEL_DS.bits() as u64
+            || frame.ss != KERNEL_DS.bits() as u64
+        {
+            return Err("segment selectors are not kernel selectors");
+        }
+        if frame.rflags & (1 << 9) == 0 {
+            return Err("interrupts are not enabled in rflags");
+        }
+        if frame.rip != kernel_thread_bootstrap_stage1 as usize as u64 {
+            return Err("frame rip is not kernel_thread_bootstrap_stage1");
+        }
+        if frame.rbx == 0 {
+            return Err("rbx does not hold fn_arg");
+        }
+
+        // 除了rbx以外，其余的寄存器都不能带有调用者的值
+        let others = [
+            frame.r15,
+            frame.r14,
+            frame.r13,
+            frame.r12,
+            frame.r11,
+            frame.r10,
+            frame.r9,
+            frame.r8,
+            frame.rcx,
+            frame.rdx,
+            frame.rsi,
+            frame.rdi,
+            frame.rbp,
+            frame.rax,
+            frame.func,
+            frame.errcode,
+            frame.rsp,
+        ];
+        if others.iter().any(|reg| *reg != 0) {
+            return Err("general purpose registers are not zeroed");
         }
 
         return Ok(());
@@ -0,0 +1,122 @@
+use alloc::sync::Arc;
+use system_error::SystemError;
+
+use crate::{
+    arch::{interrupt::TrapFrame, MMArch},
+    mm::MemoryManagementArch,
+    process::ProcessControlBlock,
+};
+
+/// 用户程序可以通过ptrace修改的rflags中的位（CF、PF、AF、ZF、SF、TF、DF、OF、NT、RF、AC）
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/arch/x86/kernel/ptrace.c#134
+const FLAG_MASK: u64 = 0x54dd5;
+
+/// PTRACE_GETREGS/PTRACE_SETREGS使用的通用寄存器布局
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/arch/x86/include/asm/user_64.h#69
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UserRegsStruct {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rax: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub orig_rax: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub eflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+    pub fs_base: u64,
+    pub gs_base: u64,
+    pub ds: u64,
+    pub es: u64,
+    pub fs: u64,
+    pub gs: u64,
+}
+
+impl UserRegsStruct {
+    /// 从进程返回用户态时将要恢复的栈帧中读取寄存器
+    ///
+    /// 栈帧中没有保存系统调用号，因此orig_rax总是-1
+    pub fn from_trapframe(pcb: &Arc<ProcessControlBlock>, frame: &TrapFrame) -> Self {
+        let arch_info = pcb.arch_info_irqsave();
+        return Self {
+            r15: frame.r15,
+            r14: frame.r14,
+            r13: frame.r13,
+            r12: frame.r12,
+            rbp: frame.rbp,
+            rbx: frame.rbx,
+            r11: frame.r11,
+            r10: frame.r10,
+            r9: frame.r9,
+            r8: frame.r8,
+            rax: frame.rax,
+            rcx: frame.rcx,
+            rdx: frame.rdx,
+            rsi: frame.rsi,
+            rdi: frame.rdi,
+            orig_rax: u64::MAX,
+            rip: frame.rip,
+            cs: frame.cs,
+            eflags: frame.rflags,
+            rsp: frame.rsp,
+            ss: frame.ss,
+            fs_base: arch_info.fsbase() as u64,
+            gs_base: arch_info.gsbase() as u64,
+            ds: frame.ds,
+            es: frame.es,
+            fs: 0,
+            gs: 0,
+        };
+    }
+
+    /// 修改进程返回用户态时将要恢复的栈帧
+    ///
+    /// 段寄存器、orig_rax、fs_base与gs_base不能被修改，rflags中只有[`FLAG_MASK`]中的位能被修改
+    ///
+    /// ## 返回值
+    ///
+    /// - `EIO`: rip或者rsp不是用户空间的地址
+    pub fn apply_to_trapframe(&self, frame: &mut TrapFrame) -> Result<(), SystemError> {
+        // 返回用户态时，rip与rsp不能指向内核空间，否则sysret/iret会在内核态触发异常
+        if self.rip >= MMArch::USER_END_VADDR.data() as u64
+            || self.rsp >= MMArch::USER_END_VADDR.data() as u64
+        {
+            return Err(SystemError::EIO);
+        }
+
+        frame.r15 = self.r15;
+        frame.r14 = self.r14;
+        frame.r13 = self.r13;
+        frame.r12 = self.r12;
+        frame.rbp = self.rbp;
+        frame.rbx = self.rbx;
+        frame.r11 = self.r11;
+        frame.r10 = self.r10;
+        frame.r9 = self.r9;
+        frame.r8 = self.r8;
+        frame.rax = self.rax;
+        frame.rcx = self.rcx;
+        frame.rdx = self.rdx;
+        frame.rsi = self.rsi;
+        frame.rdi = self.rdi;
+        frame.rip = self.rip;
+        frame.rflags = (frame.rflags & !FLAG_MASK) | (self.eflags & FLAG_MASK);
+        frame.rsp = self.rsp;
+        return Ok(());
+    }
+}
@@ -23,6 +23,11 @@ impl Syscall {
         envp: Vec<String>,
         regs: &mut TrapFrame,
     ) -> Result<(), SystemError> {
+        // 创建新的地址空间，并在替换掉原来的地址空间之前打开可执行文件。
+        // 这样，当文件不存在或者不可执行时，可以直接返回错误，原来的程序映像不受影响
+        let address_space = AddressSpace::new(true)?;
+        let mut param = ExecParam::new(path.as_str(), address_space.clone(), ExecParamFlags::EXEC)?;
+
         // 关中断，防止在设置地址空间的时候，发生中断，然后进调度器，出现错误。
         let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
         let pcb = ProcessManager::current_pcb();
@@ -34,6 +39,8 @@ impl Syscall {
         //     envp
         // );
 
+        // 原来的地址空间即将被释放，先记录它的最大常驻集
+        pcb.record_maxrss();
         let mut basic_info = pcb.basic_mut();
         // 暂存原本的用户地址空间的引用(因为如果在切换页表之前释放了它，可能会造成内存use after free)
         let old_address_space = basic_info.user_vm();
@@ -42,8 +49,7 @@ impl Syscall {
         unsafe {
             basic_info.set_user_vm(None);
         }
-        // 创建新的地址空间并设置为当前地址空间
-        let address_space = AddressSpace::new(true).expect("Failed to create new address space");
+        // 将新的地址空间设置为当前地址空间
         unsafe {
             basic_info.set_user_vm(Some(address_space.clone()));
         }
@@ -63,7 +69,6 @@ impl Syscall {
         drop(old_address_space);
         drop(irq_guard);
         // kdebug!("to load binary file");
-        let mut param = ExecParam::new(path.as_str(), address_space.clone(), ExecParamFlags::EXEC)?;
 
         // 加载可执行文件
         let load_result = load_binary_file(&mut param)
@@ -107,6 +112,9 @@ impl Syscall {
         regs.rflags = 0x200;
         regs.rax = 1;
 
+        // 新的程序映像从初始的浮点寄存器状态开始执行，从父进程继承的状态不再需要
+        pcb.arch_info_irqsave().reset_fp_state();
+
         drop(param);
 
         // kdebug!("regs: {:?}\n", regs);
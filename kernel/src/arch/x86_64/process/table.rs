@@ -1,7 +1,9 @@
+use system_error::SystemError;
 use x86::{current::task::TaskStateSegment, segmentation::SegmentSelector, Ring};
 
 use crate::{
     mm::{percpu::PerCpu, VirtAddr},
+    process::KernelStack,
     smp::core::smp_get_processor_id,
 };
 
@@ -16,6 +18,9 @@ pub const USER_DS: SegmentSelector = SegmentSelector::new(5, Ring::Ring3);
 /// 如果改这里，记得改syscall_64里面写死的常量
 pub const USER_CS: SegmentSelector = SegmentSelector::new(6, Ring::Ring3);
 
+/// 双重错误(#DF)使用的中断栈在IDT中的IST编号
+pub const DOUBLE_FAULT_IST_INDEX: u8 = 1;
+
 static mut TSS_MANAGER: TSSManager = TSSManager::new();
 
 extern "C" {
@@ -47,6 +52,22 @@ impl TSSManager {
         &mut TSS_MANAGER.tss[smp_get_processor_id().data() as usize]
     }
 
+    /// 为当前CPU分配双重错误(#DF)专用的中断栈，并设置到TSS中
+    ///
+    /// 内核栈溢出时，cpu在压栈的过程中会再次访问保护页，从而升级为双重错误。
+    /// 因此，双重错误需要在一个独立的栈上处理，否则会导致三重错误而重启。
+    ///
+    /// 该栈在系统运行期间不会被释放
+    pub unsafe fn set_double_fault_stack() -> Result<(), SystemError> {
+        let stack = KernelStack::new()?;
+        Self::current_tss().set_ist(
+            DOUBLE_FAULT_IST_INDEX as usize - 1,
+            stack.stack_max_address().data() as u64,
+        );
+        core::mem::forget(stack);
+        return Ok(());
+    }
+
     /// 加载当前CPU的TSS
     pub unsafe fn load_tr() {
         let index = (10 + smp_get_processor_id().data() * 2) as u16;
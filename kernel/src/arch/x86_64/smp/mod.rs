@@ -85,6 +85,7 @@ unsafe extern "C" fn smp_ap_start_stage1() -> ! {
         x86::Ring::Ring0,
         current_idle.kernel_stack().stack_max_address().data() as u64,
     );
+    TSSManager::set_double_fault_stack().expect("set double fault stack failed");
     TSSManager::load_tr();
 
     CurrentIrqArch::arch_ap_early_irq_init().expect("arch_ap_early_irq_init failed");
@@ -0,0 +1,141 @@
+use core::sync::atomic::{AtomicU16, AtomicU64, Ordering};
+
+use alloc::sync::Arc;
+use system_error::SystemError;
+
+/// I/O优先级中调度类所在的位偏移
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/ioprio.h
+pub const IOPRIO_CLASS_SHIFT: u16 = 13;
+/// I/O优先级中优先级数据所占的掩码
+pub const IOPRIO_PRIO_MASK: u16 = (1 << IOPRIO_CLASS_SHIFT) - 1;
+/// 每个调度类中的优先级数量（0最高，7最低）
+pub const IOPRIO_NR_LEVELS: u16 = 8;
+
+/// I/O调度类
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/ioprio.h#23
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPrioClass {
+    /// 没有设置调度类，按照进程的cpu优先级来决定I/O优先级
+    None = 0,
+    /// 实时
+    RealTime = 1,
+    /// 尽力而为
+    BestEffort = 2,
+    /// 空闲：只有在没有其他进程进行I/O时才会被调度
+    Idle = 3,
+}
+
+/// ioprio_get/ioprio_set系统调用的which参数
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/ioprio.h#45
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPrioWho {
+    /// 单个进程（线程）
+    Process = 1,
+    /// 进程组
+    Pgrp = 2,
+    /// 用户的所有进程
+    User = 3,
+}
+
+impl TryFrom<i32> for IoPrioWho {
+    type Error = SystemError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(IoPrioWho::Process),
+            2 => Ok(IoPrioWho::Pgrp),
+            3 => Ok(IoPrioWho::User),
+            _ => Err(SystemError::EINVAL),
+        }
+    }
+}
+
+/// 检查I/O优先级是否合法
+///
+/// ## 返回值
+///
+/// - `EINVAL` : 调度类不存在，或者优先级超出了调度类的范围
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/block/ioprio.c#35
+pub fn ioprio_check(ioprio: u16) -> Result<(), SystemError> {
+    let class = ioprio >> IOPRIO_CLASS_SHIFT;
+    let data = ioprio & IOPRIO_PRIO_MASK;
+    match class {
+        c if c == IoPrioClass::RealTime as u16 || c == IoPrioClass::BestEffort as u16 => {
+            if data >= IOPRIO_NR_LEVELS {
+                return Err(SystemError::EINVAL);
+            }
+        }
+        c if c == IoPrioClass::Idle as u16 => {}
+        c if c == IoPrioClass::None as u16 => {
+            if data != 0 {
+                return Err(SystemError::EINVAL);
+            }
+        }
+        _ => return Err(SystemError::EINVAL),
+    }
+    return Ok(());
+}
+
+/// 进程的I/O上下文
+///
+/// 保存与块设备I/O调度相关的状态：I/O优先级，以及通过这个上下文提交的I/O的统计信息。
+/// 使用CLONE_IO创建的进程与父进程共享同一个I/O上下文，块设备的I/O调度器会把共享同一个上下文的
+/// 所有进程视为一个整体来保证公平性，例如多个线程协作完成同一批I/O时，它们不会因为数量多而
+/// 占用更多的磁盘带宽；修改I/O优先级也会同时作用于所有共享者。
+/// 没有使用CLONE_IO时，子进程得到一个新的I/O上下文，它只继承父进程的I/O优先级。
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/linux/iocontext.h#101
+#[derive(Debug, Default)]
+pub struct IoContext {
+    /// I/O优先级：高3位为调度类，低13位为调度类中的优先级
+    ioprio: AtomicU16,
+    /// 通过这个上下文读取的字节数
+    read_bytes: AtomicU64,
+    /// 通过这个上下文写入的字节数
+    write_bytes: AtomicU64,
+}
+
+impl IoContext {
+    pub fn new(ioprio: u16) -> Arc<Self> {
+        return Arc::new(Self {
+            ioprio: AtomicU16::new(ioprio),
+            ..Default::default()
+        });
+    }
+
+    /// 为子进程创建一个新的I/O上下文，新的上下文只继承I/O优先级，而不继承统计信息
+    pub fn fork(&self) -> Arc<Self> {
+        return Self::new(self.ioprio());
+    }
+
+    pub fn ioprio(&self) -> u16 {
+        return self.ioprio.load(Ordering::SeqCst);
+    }
+
+    /// 设置I/O优先级，调用者需要先通过[`ioprio_check`]检查它是否合法
+    pub fn set_ioprio(&self, ioprio: u16) {
+        self.ioprio.store(ioprio, Ordering::SeqCst);
+    }
+
+    /// 记录一次通过这个上下文完成的读操作
+    pub fn account_read(&self, bytes: usize) {
+        self.read_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// 记录一次通过这个上下文完成的写操作
+    pub fn account_write(&self, bytes: usize) {
+        self.write_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn read_bytes(&self) -> u64 {
+        return self.read_bytes.load(Ordering::Relaxed);
+    }
+
+    pub fn write_bytes(&self) -> u64 {
+        return self.write_bytes.load(Ordering::Relaxed);
+    }
+}
@@ -1,5 +1,6 @@
 pub mod block_device;
 pub mod disk_info;
+pub mod io_context;
 #[derive(Debug)]
 #[allow(dead_code)]
 pub enum SeekFrom {
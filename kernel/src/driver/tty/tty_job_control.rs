@@ -52,7 +52,7 @@ impl TtyJobCtrlManager {
                 .sig_info_irqsave()
                 .sig_block()
                 .contains(SigSet::from_bits_truncate(1 << sig as u64))
-                || pcb.sig_struct_irqsave().handlers[sig as usize].is_ignore()
+                || pcb.sig_struct_irqsave().action(sig).is_ignore()
             {
                 // 忽略该信号
                 if sig == Signal::SIGTTIN {
@@ -60,7 +60,7 @@ impl TtyJobCtrlManager {
                 }
             } else {
                 // 暂时使用kill而不是killpg
-                Syscall::kill(pgid, sig as i32)?;
+                Syscall::kill(pgid.data() as i32, sig as i32)?;
                 return Err(SystemError::ERESTART);
             }
         }
@@ -788,7 +788,7 @@ impl NTtyData {
         let mut ctrl_info = tty.core().contorl_info_irqsave();
         let pg = ctrl_info.pgid;
         if let Some(pg) = pg {
-            let _ = Syscall::kill(pg, signal as i32);
+            let _ = Syscall::kill(pg.data() as i32, signal as i32);
         }
 
         ctrl_info.pgid = None;
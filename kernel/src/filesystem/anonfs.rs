@@ -0,0 +1,140 @@
+//! 匿名inode文件系统
+//!
+//! pidfd、signalfd这类不对应于任何目录项的文件，它们的inode都属于这个文件系统。
+//! 这个文件系统不会被挂载，只用于向fstatfs等接口报告文件所在的文件系统
+//!
+//! 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/fs/anon_inodes.c
+
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use crate::{
+    arch::MMArch, driver::base::device::device_number::DeviceNumber, libs::spinlock::SpinLockGuard,
+    mm::MemoryManagementArch, time::PosixTimeSpec,
+};
+
+use super::vfs::{
+    core::generate_inode_id, syscall::ModeType, FilePrivateData, FileSystem, FileType, FsInfo,
+    IndexNode, Magic, Metadata, SuperBlock,
+};
+
+const ANON_INODE_MAX_NAMELEN: usize = 255;
+
+lazy_static! {
+    static ref ANON_INODE_FS: Arc<AnonInodeFs> = AnonInodeFs::new();
+}
+
+/// 获取匿名inode文件系统，匿名inode的[`IndexNode::fs`]返回它
+pub fn anon_inode_fs() -> Arc<dyn FileSystem> {
+    return ANON_INODE_FS.clone();
+}
+
+#[derive(Debug)]
+pub struct AnonInodeFs {
+    /// 根节点，它是一个空目录
+    root_inode: Arc<AnonInodeRoot>,
+}
+
+impl AnonInodeFs {
+    fn new() -> Arc<Self> {
+        return Arc::new_cyclic(|fs| Self {
+            root_inode: Arc::new(AnonInodeRoot {
+                fs: fs.clone(),
+                metadata: Metadata {
+                    dev_id: 0,
+                    inode_id: generate_inode_id(),
+                    size: 0,
+                    blk_size: 0,
+                    blocks: 0,
+                    atime: PosixTimeSpec::default(),
+                    mtime: PosixTimeSpec::default(),
+                    ctime: PosixTimeSpec::default(),
+                    file_type: FileType::Dir,
+                    mode: ModeType::from_bits_truncate(0o500),
+                    nlinks: 2,
+                    uid: 0,
+                    gid: 0,
+                    raw_dev: DeviceNumber::default(),
+                },
+            }),
+        });
+    }
+}
+
+impl FileSystem for AnonInodeFs {
+    fn root_inode(&self) -> Arc<dyn IndexNode> {
+        return self.root_inode.clone();
+    }
+
+    fn info(&self) -> FsInfo {
+        return FsInfo {
+            blk_dev_id: 0,
+            max_name_len: ANON_INODE_MAX_NAMELEN,
+        };
+    }
+
+    fn as_any_ref(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "anon_inodefs"
+    }
+
+    fn super_block(&self) -> SuperBlock {
+        return SuperBlock::new(
+            Magic::ANON_INODE_FS_MAGIC,
+            MMArch::PAGE_SIZE as u64,
+            ANON_INODE_MAX_NAMELEN as u64,
+        );
+    }
+}
+
+/// 匿名inode文件系统的根节点
+#[derive(Debug)]
+struct AnonInodeRoot {
+    fs: Weak<AnonInodeFs>,
+    metadata: Metadata,
+}
+
+impl IndexNode for AnonInodeRoot {
+    fn read_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &mut [u8],
+        _data: SpinLockGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        return Err(SystemError::EISDIR);
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &[u8],
+        _data: SpinLockGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        return Err(SystemError::EISDIR);
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        return Ok(self.metadata.clone());
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        return self.fs.upgrade().unwrap();
+    }
+
+    fn as_any_ref(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        return Ok(Vec::new());
+    }
+}
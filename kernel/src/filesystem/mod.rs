@@ -1,3 +1,4 @@
+pub mod anonfs;
 pub mod devfs;
 pub mod devpts;
 pub mod fat;
@@ -1,4 +1,7 @@
-use core::intrinsics::size_of;
+use core::{
+    intrinsics::size_of,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use alloc::{
     borrow::ToOwned,
@@ -11,25 +14,34 @@ use alloc::{
 use system_error::SystemError;
 
 use crate::{
-    arch::mm::LockedFrameAllocator,
+    arch::{mm::LockedFrameAllocator, MMArch},
     driver::base::device::device_number::DeviceNumber,
     filesystem::vfs::{
         core::{generate_inode_id, ROOT_INODE},
         FileType,
     },
-    kerror, kinfo,
+    kerror, kinfo, kwarn,
     libs::{
+        align::page_align_up,
         once::Once,
         rwlock::RwLock,
         spinlock::{SpinLock, SpinLockGuard},
     },
-    mm::allocator::page_frame::FrameAllocator,
-    process::{Pid, ProcessManager},
-    time::PosixTimeSpec,
+    mm::{
+        allocator::page_frame::FrameAllocator,
+        oom::{OOM_SCORE_ADJ_MAX, OOM_SCORE_ADJ_MIN},
+        MemoryManagementArch, VirtRegion, VmFlags,
+    },
+    namespaces::{Namespace, NamespaceType},
+    process::{
+        hooks::{register_process_hook, ProcessHook},
+        ExitState, Pid, ProcessControlBlock, ProcessManager, ProcessState,
+    },
+    time::{jiffies::jiffies_to_clock_t, PosixTimeSpec},
 };
 
 use super::vfs::{
-    file::{FileMode, FilePrivateData},
+    file::{File, FileMode, FilePrivateData},
     syscall::ModeType,
     FileSystem, FsInfo, IndexNode, InodeId, Magic, Metadata, SuperBlock,
 };
@@ -49,6 +61,24 @@ pub enum ProcFileType {
     ProcMeminfo = 1,
     /// kmsg
     ProcKmsg = 2,
+    /// 与linux的/proc/<pid>/stat格式兼容的进程统计信息
+    ProcStat = 3,
+    /// 进程（线程）的名字
+    ProcComm = 4,
+    /// 故障注入：接下来需要失败的进程注册次数
+    ProcFailRegister = 5,
+    /// 引用进程所在的namespace的文件（/proc/<pid>/ns/下的文件）
+    ProcNs = 6,
+    /// 与linux的/proc/<pid>/statm格式兼容的进程内存使用情况
+    ProcStatm = 7,
+    /// OOM killer对进程评分的调整值，可以读写
+    ProcOomScoreAdj = 8,
+    /// 进程的命令行参数，以\0分隔
+    ProcCmdline = 9,
+    /// 与linux的/proc/<pid>/maps格式兼容的进程地址空间中的映射
+    ProcMaps = 10,
+    /// pid的上限（/proc/sys/kernel/pid_max），可以读写
+    ProcPidMax = 11,
     //todo: 其他文件类型
     ///默认文件类型
     Default,
@@ -60,6 +90,15 @@ impl From<u8> for ProcFileType {
             0 => ProcFileType::ProcStatus,
             1 => ProcFileType::ProcMeminfo,
             2 => ProcFileType::ProcKmsg,
+            3 => ProcFileType::ProcStat,
+            4 => ProcFileType::ProcComm,
+            5 => ProcFileType::ProcFailRegister,
+            6 => ProcFileType::ProcNs,
+            7 => ProcFileType::ProcStatm,
+            8 => ProcFileType::ProcOomScoreAdj,
+            9 => ProcFileType::ProcCmdline,
+            10 => ProcFileType::ProcMaps,
+            11 => ProcFileType::ProcPidMax,
             _ => ProcFileType::Default,
         }
     }
@@ -72,6 +111,8 @@ pub struct InodeInfo {
     pid: Pid,
     ///文件类型
     ftype: ProcFileType,
+    /// namespace文件对应的namespace类型
+    ns_type: Option<NamespaceType>,
     //其他需要传入的信息在此定义
 }
 
@@ -93,11 +134,16 @@ pub struct ProcFS {
 #[derive(Debug, Clone)]
 pub struct ProcfsFilePrivateData {
     data: Vec<u8>,
+    /// 打开namespace文件时所引用的namespace，文件被关闭之前namespace不会被销毁
+    ns: Option<Arc<dyn Namespace>>,
 }
 
 impl ProcfsFilePrivateData {
     pub fn new() -> Self {
-        return ProcfsFilePrivateData { data: Vec::new() };
+        return ProcfsFilePrivateData {
+            data: Vec::new(),
+            ns: None,
+        };
     }
 }
 
@@ -137,87 +183,461 @@ impl ProcFSInode {
     }
     // todo:其他数据获取函数实现
 
-    /// @brief 打开status文件
+    /// 打开status文件
     ///
+    /// 前面的字段与linux的/proc/<pid>/status的格式相同，之后追加了DragonOS特有的调度信息。
+    /// 尚未支持的字段（例如Uid、Gid）填0
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/fs/proc/array.c#150
     fn open_status(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
         // 获取该pid对应的pcb结构体
         let pid = self.fdata.pid;
-        let pcb = ProcessManager::find(pid);
-        let pcb = if let Some(pcb) = pcb {
-            pcb
-        } else {
+        let pcb = ProcessManager::find_thread_by_tid(pid).ok_or_else(|| {
             kerror!(
                 "ProcFS: Cannot find pcb for pid {:?} when opening its 'status' file.",
                 pid
             );
-            return Err(SystemError::ESRCH);
-        };
-        // 传入数据
+            SystemError::ESRCH
+        })?;
+
+        let (state_char, state_name) = Self::proc_state(&pcb);
+        let ppid = pcb.real_parent().map(|ppcb| ppcb.tgid()).unwrap_or(Pid(0));
+        let tracer_pid = pcb.tracer().map(|tracer| tracer.tgid()).unwrap_or(Pid(0));
+        let num_threads = pcb.thread_group().len().max(1);
+
+        // 信号屏蔽字与尚未处理的信号
+        let sig_info = pcb.sig_info_irqsave();
+        let sig_blk = sig_info.sig_block().bits();
+        let sig_pnd = sig_info.sig_pending().pending_set().bits();
+        let shd_pnd = sig_info.sig_shared_pending().pending_set().bits();
+        drop(sig_info);
+
+        // 被忽略的信号与设置了处理函数的信号
+        let handler = pcb.sig_struct_irqsave().share_handlers();
+        let (mut sig_ign, mut sig_cgt) = (0u64, 0u64);
+        for (i, action) in handler.read_irqsave().handlers.iter().enumerate() {
+            if action.is_ignore() {
+                sig_ign |= 1 << i;
+            } else if action.action().is_customized() {
+                sig_cgt |= 1 << i;
+            }
+        }
+        drop(handler);
+
+        let mut data = format!(
+            "Name:\t{}\nState:\t{} ({})\nTgid:\t{}\nPid:\t{}\nPPid:\t{}\nTracerPid:\t{}\n",
+            pcb.basic().name(),
+            state_char,
+            state_name,
+            pcb.tgid().data(),
+            pcb.tid().data(),
+            ppid.data(),
+            tracer_pid.data(),
+        );
+        // todo: 实现用户与用户组之后，填入真实的uid与gid
+        data.push_str("Uid:\t0\t0\t0\t0\nGid:\t0\t0\t0\t0\n");
+        data.push_str(&format!(
+            "Pgid:\t{}\nSid:\t{}\n",
+            pcb.basic().pgid().data(),
+            pcb.basic().sid().data()
+        ));
+
+        if let Some(user_vm) = pcb.basic().user_vm() {
+            let guard = user_vm.read();
+            // todo: 当前进程运行过程中占用内存的峰值
+            let hiwater_vm: u64 = 0;
+            data.push_str(&format!(
+                "VmPeak:\t{} kB\nVmSize:\t{} kB\nVmRSS:\t{} kB\nVmData:\t{} kB\nVmExe:\t{} kB\n",
+                hiwater_vm,
+                guard.total_vm().bytes() / 1024,
+                guard.rss().bytes() / 1024,
+                (guard.end_data - guard.start_data) / 1024,
+                (guard.end_code - guard.start_code) / 1024,
+            ));
+        }
+
+        data.push_str(&format!(
+            "Threads:\t{}\nSigPnd:\t{:016x}\nShdPnd:\t{:016x}\nSigBlk:\t{:016x}\nSigIgn:\t{:016x}\nSigCgt:\t{:016x}\n",
+            num_threads, sig_pnd, shd_pnd, sig_blk, sig_ign, sig_cgt,
+        ));
+
+        let sched_info_guard = pcb.sched_info();
+        let cpu_id = sched_info_guard
+            .on_cpu()
+            .map(|cpu| cpu.data() as i32)
+            .unwrap_or(-1);
+        data.push_str(&format!(
+            "cpu_id:\t{}\npriority:\t{:?}\npreempt:\t{}\nvrtime:\t{}\n",
+            cpu_id,
+            sched_info_guard.policy(),
+            pcb.preempt_count(),
+            sched_info_guard.sched_entity.vruntime,
+        ));
+        data.push_str(&format!(
+            "voluntary_ctxt_switches:\t{}\nnonvoluntary_ctxt_switches:\t{}\nflags: {:?}\n",
+            pcb.stats().nvcsw(),
+            pcb.stats().nivcsw(),
+            pcb.flags().clone()
+        ));
+
         let pdata: &mut Vec<u8> = &mut pdata.data;
+        pdata.append(&mut data.as_bytes().to_owned());
 
-        pdata.append(
-            &mut format!("Name:\t{}", pcb.basic().name())
-                .as_bytes()
-                .to_owned(),
-        );
+        // 去除多余的\0
+        self.trim_string(pdata);
+
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
+
+    /// 获取进程的状态在/proc/<pid>/stat与/proc/<pid>/status中的表示
+    ///
+    /// ## 返回值
+    ///
+    /// 状态字符与状态的名字，例如`('R', "running")`
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/fs/proc/array.c#127
+    fn proc_state(pcb: &ProcessControlBlock) -> (char, &'static str) {
+        match pcb.exit_state() {
+            ExitState::Zombie => return ('Z', "zombie"),
+            ExitState::Dead => return ('X', "dead"),
+            ExitState::Running => {}
+        }
+        if pcb.is_ptrace_stopped() {
+            return ('t', "tracing stop");
+        }
+        match pcb.sched_info().inner_lock_read_irqsave().state() {
+            ProcessState::Runnable => ('R', "running"),
+            ProcessState::Blocked(true) => ('S', "sleeping"),
+            ProcessState::Blocked(false) => ('D', "disk sleep"),
+            ProcessState::Stopped => ('T', "stopped"),
+            // 进程正在执行退出流程，还没有成为僵尸进程
+            ProcessState::Exited(_) => ('Z', "zombie"),
+        }
+    }
+
+    /// 打开stat文件
+    ///
+    /// 前52个字段的含义与linux的/proc/<pid>/stat相同，尚未支持的字段填0。
+    /// 在这之后依次追加了fork次数、主动上下文切换次数、被动上下文切换次数
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/fs/proc/array.c#467
+    fn open_stat(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        let pid = self.fdata.pid;
+        let pcb = ProcessManager::find_thread_by_tid(pid).ok_or_else(|| {
+            kerror!(
+                "ProcFS: Cannot find pcb for pid {:?} when opening its 'stat' file.",
+                pid
+            );
+            SystemError::ESRCH
+        })?;
 
+        let (state_char, _) = Self::proc_state(&pcb);
         let sched_info_guard = pcb.sched_info();
         let state = sched_info_guard.inner_lock_read_irqsave().state();
         let cpu_id = sched_info_guard
             .on_cpu()
             .map(|cpu| cpu.data() as i32)
             .unwrap_or(-1);
+        let exit_code = state.exit_code().unwrap_or(0);
+
+        let ppid = pcb.real_parent().map(|ppcb| ppcb.tgid()).unwrap_or(Pid(0));
+        let num_threads = pcb.thread_group().len().max(1);
+
+        let basic = pcb.basic();
+        let (vsize, rss, start_code, end_code, start_data, end_data, start_brk) =
+            match basic.user_vm() {
+                Some(user_vm) => {
+                    let guard = user_vm.read();
+                    (
+                        guard.total_vm().bytes(),
+                        guard.rss().data(),
+                        guard.start_code.data(),
+                        guard.end_code.data(),
+                        guard.start_data.data(),
+                        guard.end_data.data(),
+                        guard.brk_start.data(),
+                    )
+                }
+                None => (0, 0, 0, 0, 0, 0, 0),
+            };
+
+        let stats = pcb.stats();
+        let data = format!(
+            "{} ({}) {} {} {} {} 0 -1 {} 0 0 0 0 0 0 0 0 0 0 {} 0 {} {} {} 0 {} {} 0 0 0 0 0 0 0 0 0 0 0 {} 0 0 0 0 0 {} {} {} 0 0 0 0 {} {} {} {}\n",
+            pid.data(),
+            basic.name(),
+            state_char,
+            ppid.data(),
+            basic.pgid().data(),
+            basic.sid().data(),
+            pcb.flags().bits(),
+            num_threads,
+            jiffies_to_clock_t(pcb.start_time()),
+            vsize,
+            rss,
+            start_code,
+            end_code,
+            cpu_id,
+            start_data,
+            end_data,
+            start_brk,
+            exit_code,
+            stats.nforks(),
+            stats.nvcsw(),
+            stats.nivcsw(),
+        );
+        drop(basic);
+
+        let pdata: &mut Vec<u8> = &mut pdata.data;
+        pdata.append(&mut data.as_bytes().to_owned());
+
+        // 去除多余的\0
+        self.trim_string(pdata);
+
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
+
+    /// 打开comm文件
+    fn open_comm(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        let pid = self.fdata.pid;
+        let pcb = ProcessManager::find_thread_by_tid(pid).ok_or_else(|| {
+            kerror!(
+                "ProcFS: Cannot find pcb for pid {:?} when opening its 'comm' file.",
+                pid
+            );
+            SystemError::ESRCH
+        })?;
+
+        let pdata: &mut Vec<u8> = &mut pdata.data;
+        pdata.append(&mut format!("{}\n", pcb.basic().name()).as_bytes().to_owned());
 
-        let priority = sched_info_guard.policy();
-        let vrtime = sched_info_guard.sched_entity.vruntime;
+        // 去除多余的\0
+        self.trim_string(pdata);
+
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
+
+    /// 打开cmdline文件
+    ///
+    /// 文件的内容为进程最近一次execve时的命令行参数，每个参数都以\0结尾。
+    /// 内核线程的命令行参数为空
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/fs/proc/base.c#340
+    fn open_cmdline(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        let pid = self.fdata.pid;
+        let pcb = ProcessManager::find_thread_by_tid(pid).ok_or(SystemError::ESRCH)?;
+
+        let pdata: &mut Vec<u8> = &mut pdata.data;
+        pdata.extend_from_slice(&pcb.cmdline());
+
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
+
+    /// 打开maps文件
+    ///
+    /// 每一行描述进程地址空间中的一个VMA，依次为地址范围、访问权限、文件偏移、设备号、inode号以及映射的名字。
+    /// 访问权限的最后一位，共享映射为`s`，私有映射（包括fork之后处于写时复制状态的映射）为`p`。
+    /// 目前只有匿名映射，因此偏移、设备号与inode号均为0，堆与栈分别被命名为`[heap]`与`[stack]`
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/fs/proc/task_mmu.c#265
+    fn open_maps(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        let pid = self.fdata.pid;
+        let pcb = ProcessManager::find_thread_by_tid(pid).ok_or_else(|| {
+            kerror!(
+                "ProcFS: Cannot find pcb for pid {:?} when opening its 'maps' file.",
+                pid
+            );
+            SystemError::ESRCH
+        })?;
+
+        // 内核线程没有用户地址空间，文件的内容为空
+        let user_vm = match pcb.basic().user_vm() {
+            Some(user_vm) => user_vm,
+            None => return Ok(0),
+        };
+        let guard = user_vm.read();
+        let heap = VirtRegion::between(guard.brk_start, guard.brk);
+        let stack = guard.user_stack.as_ref().map(|stack| stack.region());
+        let mut vmas: Vec<(VirtRegion, VmFlags)> = guard
+            .mappings
+            .iter_vmas()
+            .map(|vma| {
+                let vma = vma.lock();
+                (*vma.region(), *vma.vm_flags())
+            })
+            .collect();
+        drop(guard);
+        vmas.sort_by_key(|(region, _)| region.start());
+
+        let pdata: &mut Vec<u8> = &mut pdata.data;
+        for (region, vm_flags) in vmas {
+            let overlaps =
+                |other: Option<VirtRegion>| other.is_some_and(|o| o.intersect(&region).is_some());
+            let name = if overlaps(stack) {
+                "[stack]"
+            } else if overlaps(heap) {
+                "[heap]"
+            } else {
+                ""
+            };
+
+            let perm = |flag: VmFlags, c: char| if vm_flags.contains(flag) { c } else { '-' };
+            let shared = if vm_flags.contains(VmFlags::VM_MAYSHARE) {
+                's'
+            } else {
+                'p'
+            };
+            let line = format!(
+                "{:08x}-{:08x} {}{}{}{} {:08x} 00:00 0",
+                region.start().data(),
+                region.end().data(),
+                perm(VmFlags::VM_READ, 'r'),
+                perm(VmFlags::VM_WRITE, 'w'),
+                perm(VmFlags::VM_EXEC, 'x'),
+                shared,
+                0
+            );
+            let line = if name.is_empty() {
+                format!("{}\n", line)
+            } else {
+                // 与linux相同，名字从第74列开始
+                format!("{:<72} {}\n", line, name)
+            };
+            pdata.extend_from_slice(line.as_bytes());
+        }
+
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
+
+    /// 打开statm文件
+    ///
+    /// 文件的内容为以空格分隔的7个数字（单位均为页）：
+    /// 总大小（VSZ）、驻留内存（RSS）、共享页、代码段、库（始终为0）、数据段与栈、脏页（始终为0）
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/fs/proc/array.c#667
+    fn open_statm(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        let pid = self.fdata.pid;
+        let pcb = ProcessManager::find_thread_by_tid(pid).ok_or_else(|| {
+            kerror!(
+                "ProcFS: Cannot find pcb for pid {:?} when opening its 'statm' file.",
+                pid
+            );
+            SystemError::ESRCH
+        })?;
+
+        // 内核线程没有用户地址空间，所有的值都为0
+        let (size, resident, text, data) = match pcb.basic().user_vm() {
+            Some(user_vm) => {
+                let guard = user_vm.read();
+                let code_size = guard
+                    .end_code
+                    .data()
+                    .saturating_sub(guard.start_code.data());
+                let text = page_align_up(code_size) / MMArch::PAGE_SIZE;
+                (
+                    guard.total_vm().data(),
+                    guard.rss().data(),
+                    text,
+                    guard.data_vm().data(),
+                )
+            }
+            None => (0, 0, 0, 0),
+        };
 
-        pdata.append(&mut format!("\nState:\t{:?}", state).as_bytes().to_owned());
+        // todo: 区分私有页与共享页之后，在这里输出共享页的数量
+        let pdata: &mut Vec<u8> = &mut pdata.data;
         pdata.append(
-            &mut format!("\nPid:\t{}", pcb.pid().into())
+            &mut format!("{} {} 0 {} 0 {} 0\n", size, resident, text, data)
                 .as_bytes()
                 .to_owned(),
         );
-        pdata.append(
-            &mut format!("\nPpid:\t{}", pcb.basic().ppid().into())
+
+        // 去除多余的\0
+        self.trim_string(pdata);
+
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
+
+    /// 打开oom_score_adj文件
+    fn open_oom_score_adj(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        let pid = self.fdata.pid;
+        let pcb = ProcessManager::find_thread_by_tid(pid).ok_or_else(|| {
+            kerror!(
+                "ProcFS: Cannot find pcb for pid {:?} when opening its 'oom_score_adj' file.",
+                pid
+            );
+            SystemError::ESRCH
+        })?;
+
+        let pdata: &mut Vec<u8> = &mut pdata.data;
+        pdata.append(&mut format!("{}\n", pcb.oom_score_adj()).as_bytes().to_owned());
+
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
+
+    /// 写入oom_score_adj文件
+    ///
+    /// 与linux相同，调整值属于整个线程组，因此会同时修改线程组中的所有线程
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL` : 写入的不是[`OOM_SCORE_ADJ_MIN`, `OOM_SCORE_ADJ_MAX`]范围内的整数
+    /// - `ESRCH` : 进程已经不存在
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/fs/proc/base.c#1182
+    fn write_oom_score_adj(&self, buf: &[u8]) -> Result<(), SystemError> {
+        let adj = core::str::from_utf8(buf)
+            .ok()
+            .and_then(|s| s.trim().parse::<i16>().ok())
+            .filter(|adj| (OOM_SCORE_ADJ_MIN..=OOM_SCORE_ADJ_MAX).contains(adj))
+            .ok_or(SystemError::EINVAL)?;
+        let pcb = ProcessManager::find_thread_by_tid(self.fdata.pid).ok_or(SystemError::ESRCH)?;
+        for thread in pcb.thread_group() {
+            thread.set_oom_score_adj(adj);
+        }
+        return Ok(());
+    }
+
+    /// 打开namespace文件
+    ///
+    /// 文件的内容与linux中读取/proc/<pid>/ns/下的符号链接得到的内容相同，例如`uts:[4026531838]`
+    fn open_ns(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        let pid = self.fdata.pid;
+        let pcb = ProcessManager::find_thread_by_tid(pid).ok_or(SystemError::ESRCH)?;
+        let ns_type = self.fdata.ns_type.ok_or(SystemError::ENOENT)?;
+        let ns = ns_type.get(&pcb).ok_or(SystemError::ENOENT)?;
+
+        pdata.data.append(
+            &mut format!("{}:[{}]\n", ns_type.name(), ns.inum())
                 .as_bytes()
                 .to_owned(),
         );
-        pdata.append(&mut format!("\ncpu_id:\t{}", cpu_id).as_bytes().to_owned());
-        pdata.append(&mut format!("\npriority:\t{:?}", priority).as_bytes().to_owned());
+        pdata.ns = Some(ns);
+
+        return Ok((pdata.data.len() * size_of::<u8>()) as i64);
+    }
+
+    /// 打开fail_register文件
+    fn open_fail_register(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        let pdata: &mut Vec<u8> = &mut pdata.data;
         pdata.append(
-            &mut format!("\npreempt:\t{}", pcb.preempt_count())
+            &mut format!("{}\n", FAIL_REGISTER_COUNT.load(Ordering::SeqCst))
                 .as_bytes()
                 .to_owned(),
         );
-        pdata.append(&mut format!("\nvrtime:\t{}", vrtime).as_bytes().to_owned());
 
-        if let Some(user_vm) = pcb.basic().user_vm() {
-            let address_space_guard = user_vm.read();
-            // todo: 当前进程运行过程中占用内存的峰值
-            let hiwater_vm: u64 = 0;
-            // 进程代码段的大小
-            let text = (address_space_guard.end_code - address_space_guard.start_code) / 1024;
-            // 进程数据段的大小
-            let data = (address_space_guard.end_data - address_space_guard.start_data) / 1024;
-            drop(address_space_guard);
-            pdata.append(
-                &mut format!("\nVmPeak:\t{} kB", hiwater_vm)
-                    .as_bytes()
-                    .to_owned(),
-            );
-            pdata.append(&mut format!("\nVmData:\t{} kB", data).as_bytes().to_owned());
-            pdata.append(&mut format!("\nVmExe:\t{} kB", text).as_bytes().to_owned());
-        }
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
 
+    /// 打开/proc/sys/kernel/pid_max文件
+    fn open_pid_max(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        let pdata: &mut Vec<u8> = &mut pdata.data;
         pdata.append(
-            &mut format!("\nflags: {:?}\n", pcb.flags().clone())
+            &mut format!("{}\n", ProcessManager::pid_max())
                 .as_bytes()
                 .to_owned(),
         );
 
-        // 去除多余的\0
-        self.trim_string(pdata);
-
         return Ok((pdata.len() * size_of::<u8>()) as i64);
     }
 
@@ -328,6 +748,7 @@ impl ProcFS {
                 fdata: InodeInfo {
                     pid: Pid::new(0),
                     ftype: ProcFileType::Default,
+                    ns_type: None,
                 },
             })));
 
@@ -375,6 +796,45 @@ impl ProcFS {
             panic!("create ksmg error");
         }
 
+        // 创建fail_register文件
+        let binding = inode.create(
+            "fail_register",
+            FileType::File,
+            ModeType::from_bits_truncate(0o644),
+        );
+        if let Ok(fail_register) = binding {
+            let fail_register_file = fail_register
+                .as_any_ref()
+                .downcast_ref::<LockedProcFSInode>()
+                .unwrap();
+            fail_register_file.0.lock().fdata.ftype = ProcFileType::ProcFailRegister;
+        } else {
+            panic!("create fail_register error");
+        }
+
+        // 创建/proc/sys/kernel/pid_max文件
+        let sys_kernel = inode
+            .create("sys", FileType::Dir, ModeType::from_bits_truncate(0o555))
+            .and_then(|sys| {
+                sys.create("kernel", FileType::Dir, ModeType::from_bits_truncate(0o555))
+            });
+        let binding = sys_kernel.and_then(|kernel| {
+            kernel.create(
+                "pid_max",
+                FileType::File,
+                ModeType::from_bits_truncate(0o644),
+            )
+        });
+        if let Ok(pid_max) = binding {
+            let pid_max_file = pid_max
+                .as_any_ref()
+                .downcast_ref::<LockedProcFSInode>()
+                .unwrap();
+            pid_max_file.0.lock().fdata.ftype = ProcFileType::ProcPidMax;
+        } else {
+            panic!("create pid_max error");
+        }
+
         return result;
     }
 
@@ -390,6 +850,38 @@ impl ProcFS {
             ModeType::from_bits_truncate(0o555),
         )?;
         // 创建相关文件
+        Self::create_pid_files(&pid_dir, pid)?;
+
+        // 创建task文件夹，线程组中的每个线程（包括组长自身）都在其中有一个对应的文件夹
+        let task_dir: Arc<dyn IndexNode> =
+            pid_dir.create("task", FileType::Dir, ModeType::from_bits_truncate(0o555))?;
+        let tid_dir: Arc<dyn IndexNode> = task_dir.create(
+            &pid.to_string(),
+            FileType::Dir,
+            ModeType::from_bits_truncate(0o555),
+        )?;
+        Self::create_pid_files(&tid_dir, pid)?;
+
+        return Ok(());
+    }
+
+    /// @brief 线程注册函数
+    /// @usage 在线程组组长的task文件夹下，创建线程对应的文件
+    pub fn register_thread(&self, tgid: Pid, tid: Pid) -> Result<(), SystemError> {
+        let task_dir: Arc<dyn IndexNode> =
+            self.root_inode().find(&tgid.to_string())?.find("task")?;
+        let tid_dir: Arc<dyn IndexNode> = task_dir.create(
+            &tid.to_string(),
+            FileType::Dir,
+            ModeType::from_bits_truncate(0o555),
+        )?;
+        Self::create_pid_files(&tid_dir, tid)?;
+
+        return Ok(());
+    }
+
+    /// @brief 在进程（线程）对应的文件夹下，创建相关文件
+    fn create_pid_files(pid_dir: &Arc<dyn IndexNode>, pid: Pid) -> Result<(), SystemError> {
         // status文件
         let binding: Arc<dyn IndexNode> = pid_dir.create(
             "status",
@@ -403,6 +895,95 @@ impl ProcFS {
         status_file.0.lock().fdata.pid = pid;
         status_file.0.lock().fdata.ftype = ProcFileType::ProcStatus;
 
+        // stat文件
+        let binding: Arc<dyn IndexNode> =
+            pid_dir.create("stat", FileType::File, ModeType::from_bits_truncate(0o444))?;
+        let stat_file: &LockedProcFSInode = binding
+            .as_any_ref()
+            .downcast_ref::<LockedProcFSInode>()
+            .unwrap();
+        stat_file.0.lock().fdata.pid = pid;
+        stat_file.0.lock().fdata.ftype = ProcFileType::ProcStat;
+
+        // comm文件
+        let binding: Arc<dyn IndexNode> =
+            pid_dir.create("comm", FileType::File, ModeType::from_bits_truncate(0o444))?;
+        let comm_file: &LockedProcFSInode = binding
+            .as_any_ref()
+            .downcast_ref::<LockedProcFSInode>()
+            .unwrap();
+        comm_file.0.lock().fdata.pid = pid;
+        comm_file.0.lock().fdata.ftype = ProcFileType::ProcComm;
+
+        // cmdline文件
+        let binding: Arc<dyn IndexNode> = pid_dir.create(
+            "cmdline",
+            FileType::File,
+            ModeType::from_bits_truncate(0o444),
+        )?;
+        let cmdline_file: &LockedProcFSInode = binding
+            .as_any_ref()
+            .downcast_ref::<LockedProcFSInode>()
+            .unwrap();
+        cmdline_file.0.lock().fdata.pid = pid;
+        cmdline_file.0.lock().fdata.ftype = ProcFileType::ProcCmdline;
+
+        // maps文件
+        let binding: Arc<dyn IndexNode> =
+            pid_dir.create("maps", FileType::File, ModeType::from_bits_truncate(0o444))?;
+        let maps_file: &LockedProcFSInode = binding
+            .as_any_ref()
+            .downcast_ref::<LockedProcFSInode>()
+            .unwrap();
+        maps_file.0.lock().fdata.pid = pid;
+        maps_file.0.lock().fdata.ftype = ProcFileType::ProcMaps;
+
+        // statm文件
+        let binding: Arc<dyn IndexNode> =
+            pid_dir.create("statm", FileType::File, ModeType::from_bits_truncate(0o444))?;
+        let statm_file: &LockedProcFSInode = binding
+            .as_any_ref()
+            .downcast_ref::<LockedProcFSInode>()
+            .unwrap();
+        statm_file.0.lock().fdata.pid = pid;
+        statm_file.0.lock().fdata.ftype = ProcFileType::ProcStatm;
+
+        // oom_score_adj文件
+        let binding: Arc<dyn IndexNode> = pid_dir.create(
+            "oom_score_adj",
+            FileType::File,
+            ModeType::from_bits_truncate(0o644),
+        )?;
+        let oom_score_adj_file: &LockedProcFSInode = binding
+            .as_any_ref()
+            .downcast_ref::<LockedProcFSInode>()
+            .unwrap();
+        oom_score_adj_file.0.lock().fdata.pid = pid;
+        oom_score_adj_file.0.lock().fdata.ftype = ProcFileType::ProcOomScoreAdj;
+
+        // ns文件夹，进程所在的每个（已经实现的）namespace都在其中有一个对应的文件
+        let ns_dir: Arc<dyn IndexNode> =
+            pid_dir.create("ns", FileType::Dir, ModeType::from_bits_truncate(0o555))?;
+        if let Some(pcb) = ProcessManager::find_thread_by_tid(pid) {
+            for ns_type in NamespaceType::ALL {
+                if ns_type.get(&pcb).is_none() {
+                    continue;
+                }
+                let binding: Arc<dyn IndexNode> = ns_dir.create(
+                    ns_type.name(),
+                    FileType::File,
+                    ModeType::from_bits_truncate(0o444),
+                )?;
+                let ns_file: &LockedProcFSInode = binding
+                    .as_any_ref()
+                    .downcast_ref::<LockedProcFSInode>()
+                    .unwrap();
+                ns_file.0.lock().fdata.pid = pid;
+                ns_file.0.lock().fdata.ftype = ProcFileType::ProcNs;
+                ns_file.0.lock().fdata.ns_type = Some(ns_type);
+            }
+        }
+
         //todo: 创建其他文件
 
         return Ok(());
@@ -415,8 +996,23 @@ impl ProcFS {
         let proc: Arc<dyn IndexNode> = self.root_inode();
         // 获取进程文件夹
         let pid_dir: Arc<dyn IndexNode> = proc.find(&pid.to_string())?;
-        // 删除进程文件夹下文件
-        pid_dir.unlink("status")?;
+        // 删除进程文件夹下文件。注册过程中途失败时，部分文件可能并未被创建
+        for name in [
+            "status",
+            "stat",
+            "statm",
+            "oom_score_adj",
+            "comm",
+            "cmdline",
+            "maps",
+            "ns",
+            "task",
+        ] {
+            match pid_dir.unlink(name) {
+                Ok(_) | Err(SystemError::ENOENT) => {}
+                Err(e) => return Err(e),
+            }
+        }
 
         // 查看进程文件是否还存在
         // let pf= pid_dir.find("status").expect("Cannot find status");
@@ -426,6 +1022,33 @@ impl ProcFS {
 
         return Ok(());
     }
+
+    /// @brief 解除线程注册
+    ///
+    pub fn unregister_thread(&self, tgid: Pid, tid: Pid) -> Result<(), SystemError> {
+        // 线程组组长可能先于线程解除注册，此时线程对应的文件夹已经随之删除
+        let task_dir: Arc<dyn IndexNode> = match self
+            .root_inode()
+            .find(&tgid.to_string())
+            .and_then(|pid_dir| pid_dir.find("task"))
+        {
+            Ok(task_dir) => task_dir,
+            Err(SystemError::ENOENT) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let tid_dir: Arc<dyn IndexNode> = task_dir.find(&tid.to_string())?;
+        tid_dir.unlink("status")?;
+        tid_dir.unlink("stat")?;
+        tid_dir.unlink("statm")?;
+        tid_dir.unlink("oom_score_adj")?;
+        tid_dir.unlink("comm")?;
+        tid_dir.unlink("cmdline")?;
+        tid_dir.unlink("maps")?;
+        tid_dir.unlink("ns")?;
+        task_dir.unlink(&tid.to_string())?;
+
+        return Ok(());
+    }
 }
 
 impl IndexNode for LockedProcFSInode {
@@ -446,6 +1069,15 @@ impl IndexNode for LockedProcFSInode {
         let file_size = match inode.fdata.ftype {
             ProcFileType::ProcStatus => inode.open_status(&mut private_data)?,
             ProcFileType::ProcMeminfo => inode.open_meminfo(&mut private_data)?,
+            ProcFileType::ProcStat => inode.open_stat(&mut private_data)?,
+            ProcFileType::ProcComm => inode.open_comm(&mut private_data)?,
+            ProcFileType::ProcFailRegister => inode.open_fail_register(&mut private_data)?,
+            ProcFileType::ProcNs => inode.open_ns(&mut private_data)?,
+            ProcFileType::ProcStatm => inode.open_statm(&mut private_data)?,
+            ProcFileType::ProcOomScoreAdj => inode.open_oom_score_adj(&mut private_data)?,
+            ProcFileType::ProcCmdline => inode.open_cmdline(&mut private_data)?,
+            ProcFileType::ProcMaps => inode.open_maps(&mut private_data)?,
+            ProcFileType::ProcPidMax => inode.open_pid_max(&mut private_data)?,
             _ => {
                 todo!()
             }
@@ -503,6 +1135,17 @@ impl IndexNode for LockedProcFSInode {
             ProcFileType::ProcMeminfo => {
                 return inode.proc_read(offset, len, buf, &mut private_data)
             }
+            ProcFileType::ProcStat
+            | ProcFileType::ProcComm
+            | ProcFileType::ProcFailRegister
+            | ProcFileType::ProcNs
+            | ProcFileType::ProcStatm
+            | ProcFileType::ProcOomScoreAdj
+            | ProcFileType::ProcCmdline
+            | ProcFileType::ProcMaps
+            | ProcFileType::ProcPidMax => {
+                return inode.proc_read(offset, len, buf, &mut private_data)
+            }
             ProcFileType::ProcKmsg => (),
             ProcFileType::Default => (),
         };
@@ -525,11 +1168,37 @@ impl IndexNode for LockedProcFSInode {
     fn write_at(
         &self,
         _offset: usize,
-        _len: usize,
-        _buf: &[u8],
+        len: usize,
+        buf: &[u8],
         _data: SpinLockGuard<FilePrivateData>,
     ) -> Result<usize, SystemError> {
-        return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
+        if buf.len() < len {
+            return Err(SystemError::EINVAL);
+        }
+        let inode: SpinLockGuard<ProcFSInode> = self.0.lock();
+        match inode.fdata.ftype {
+            ProcFileType::ProcFailRegister => {
+                let count = core::str::from_utf8(&buf[..len])
+                    .ok()
+                    .and_then(|s| s.trim().parse::<usize>().ok())
+                    .ok_or(SystemError::EINVAL)?;
+                FAIL_REGISTER_COUNT.store(count, Ordering::SeqCst);
+                return Ok(len);
+            }
+            ProcFileType::ProcOomScoreAdj => {
+                inode.write_oom_score_adj(&buf[..len])?;
+                return Ok(len);
+            }
+            ProcFileType::ProcPidMax => {
+                let pid_max = core::str::from_utf8(&buf[..len])
+                    .ok()
+                    .and_then(|s| s.trim().parse::<usize>().ok())
+                    .ok_or(SystemError::EINVAL)?;
+                ProcessManager::set_pid_max(pid_max)?;
+                return Ok(len);
+            }
+            _ => return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP),
+        }
     }
 
     fn fs(&self) -> Arc<dyn FileSystem> {
@@ -614,6 +1283,7 @@ impl IndexNode for LockedProcFSInode {
                 fdata: InodeInfo {
                     pid: Pid::new(0),
                     ftype: ProcFileType::Default,
+                    ns_type: None,
                 },
             })));
 
@@ -770,8 +1440,22 @@ impl IndexNode for LockedProcFSInode {
     }
 }
 
+/// 故障注入：接下来需要失败的进程注册次数，通过/proc/fail_register设置
+///
+/// 用于测试进程注册失败时fork的错误处理
+static FAIL_REGISTER_COUNT: AtomicUsize = AtomicUsize::new(0);
+
 /// @brief 向procfs注册进程
-pub fn procfs_register_pid(pid: Pid) -> Result<(), SystemError> {
+///
+/// 线程组组长注册在/proc/<pid>下，其他线程注册在/proc/<tgid>/task/<tid>下
+pub fn procfs_register_pid(tid: Pid, tgid: Pid) -> Result<(), SystemError> {
+    if FAIL_REGISTER_COUNT
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+        .is_ok()
+    {
+        return Err(SystemError::ENOMEM);
+    }
+
     let procfs_inode = ROOT_INODE().find("proc")?;
 
     let procfs_inode = procfs_inode
@@ -781,13 +1465,17 @@ pub fn procfs_register_pid(pid: Pid) -> Result<(), SystemError> {
     let procfs: &ProcFS = fs.as_any_ref().downcast_ref::<ProcFS>().unwrap();
 
     // 调用注册函数
-    procfs.register_pid(pid)?;
+    if tid == tgid {
+        procfs.register_pid(tid)?;
+    } else {
+        procfs.register_thread(tgid, tid)?;
+    }
 
     return Ok(());
 }
 
 /// @brief 在ProcFS中,解除进程的注册
-pub fn procfs_unregister_pid(pid: Pid) -> Result<(), SystemError> {
+pub fn procfs_unregister_pid(tid: Pid, tgid: Pid) -> Result<(), SystemError> {
     // 获取procfs实例
     let procfs_inode: Arc<dyn IndexNode> = ROOT_INODE().find("proc")?;
 
@@ -798,7 +1486,54 @@ pub fn procfs_unregister_pid(pid: Pid) -> Result<(), SystemError> {
     let procfs: &ProcFS = fs.as_any_ref().downcast_ref::<ProcFS>().unwrap();
 
     // 调用解除注册函数
-    return procfs.unregister_pid(pid);
+    if tid == tgid {
+        return procfs.unregister_pid(tid);
+    } else {
+        return procfs.unregister_thread(tgid, tid);
+    }
+}
+
+/// 如果文件是打开的/proc/<pid>/ns/下的namespace文件，则返回它所引用的namespace
+pub fn procfs_ns_of_file(file: &File) -> Option<Arc<dyn Namespace>> {
+    file.inode().downcast_ref::<LockedProcFSInode>()?;
+    match &*file.private_data.lock() {
+        FilePrivateData::Procfs(pdata) => pdata.ns.clone(),
+        _ => None,
+    }
+}
+
+/// 在进程被创建时向procfs注册进程，在进程被释放时解除注册
+#[derive(Debug)]
+struct ProcFSProcessHook;
+
+impl ProcessHook for ProcFSProcessHook {
+    fn name(&self) -> &'static str {
+        "procfs"
+    }
+
+    fn fork(&self, pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+        // 注册失败只会导致这次fork失败，对调用者来说等同于内存不足
+        return procfs_register_pid(pcb.tid(), pcb.tgid()).map_err(|e| {
+            kwarn!(
+                "ProcFS: Failed to register tid {:?}, error: {:?}",
+                pcb.tid(),
+                e
+            );
+            SystemError::ENOMEM
+        });
+    }
+
+    fn exit(&self, pcb: &ProcessControlBlock) {
+        // fork失败的进程可能从未注册过，或者只注册了一部分
+        match procfs_unregister_pid(pcb.tid(), pcb.tgid()) {
+            Ok(_) | Err(SystemError::ENOENT) => {}
+            Err(e) => kwarn!(
+                "ProcFS: Failed to unregister tid {:?}, error: {:?}",
+                pcb.tid(),
+                e
+            ),
+        }
+    }
 }
 
 pub fn procfs_init() -> Result<(), SystemError> {
@@ -816,7 +1551,7 @@ pub fn procfs_init() -> Result<(), SystemError> {
             .mount(procfs)
             .expect("Failed to mount proc");
         kinfo!("ProcFS mounted.");
-        result = Some(Ok(()));
+        result = Some(register_process_hook(Arc::new(ProcFSProcessHook)));
     });
 
     return result.unwrap();
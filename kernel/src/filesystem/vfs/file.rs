@@ -13,7 +13,10 @@ use crate::{
         tty::tty_device::TtyFilePrivateData,
     },
     filesystem::procfs::ProcfsFilePrivateData,
-    ipc::pipe::{LockedPipeInode, PipeFsPrivateData},
+    ipc::{
+        pipe::{LockedPipeInode, PipeFsPrivateData},
+        signalfd::SignalFdPrivateData,
+    },
     kerror,
     libs::{rwlock::RwLock, spinlock::SpinLock},
     net::{
@@ -39,6 +42,8 @@ pub enum FilePrivateData {
     Tty(TtyFilePrivateData),
     /// epoll私有信息
     EPoll(EPollPrivateData),
+    /// signalfd私有信息
+    SignalFd(SignalFdPrivateData),
     /// 不需要文件私有信息
     Unused,
 }
@@ -51,8 +56,10 @@ impl Default for FilePrivateData {
 
 impl FilePrivateData {
     pub fn update_mode(&mut self, mode: FileMode) {
-        if let FilePrivateData::Pipefs(pdata) = self {
-            pdata.set_mode(mode);
+        match self {
+            FilePrivateData::Pipefs(pdata) => pdata.set_mode(mode),
+            FilePrivateData::SignalFd(pdata) => pdata.set_mode(mode),
+            _ => {}
         }
     }
 }
@@ -581,7 +588,7 @@ impl FileDescriptorVec {
     /// @return false 不合法
     #[inline]
     pub fn validate_fd(fd: i32) -> bool {
-        return !(fd < 0 || fd as usize > FileDescriptorVec::PROCESS_MAX_FD);
+        return !(fd < 0 || fd as usize >= FileDescriptorVec::PROCESS_MAX_FD);
     }
 
     /// 申请文件描述符，并把文件对象存入其中。
@@ -591,12 +598,18 @@ impl FileDescriptorVec {
     /// - `file` 要存放的文件对象
     /// - `fd` 如果为Some(i32)，表示指定要申请这个文件描述符，如果这个文件描述符已经被使用，那么返回EBADF
     ///
+    /// 文件描述符的编号不能超过当前进程的RLIMIT_NOFILE
+    ///
     /// ## 返回值
     ///
     /// - `Ok(i32)` 申请成功，返回申请到的文件描述符
     /// - `Err(SystemError)` 申请失败，返回错误码，并且，file对象将被drop掉
     pub fn alloc_fd(&mut self, file: File, fd: Option<i32>) -> Result<i32, SystemError> {
+        let limit = ProcessManager::current_pcb().nofile_limit();
         if let Some(new_fd) = fd {
+            if new_fd < 0 || new_fd as usize >= limit {
+                return Err(SystemError::EBADF);
+            }
             let x = &mut self.fds[new_fd as usize];
             if x.is_none() {
                 *x = Some(Arc::new(file));
@@ -606,7 +619,7 @@ impl FileDescriptorVec {
             }
         } else {
             // 没有指定要申请的文件描述符编号
-            for i in 0..FileDescriptorVec::PROCESS_MAX_FD {
+            for i in 0..limit {
                 if self.fds[i].is_none() {
                     self.fds[i] = Some(Arc::new(file));
                     return Ok(i as i32);
@@ -653,6 +666,14 @@ impl FileDescriptorVec {
         return FileDescriptorIterator::new(self);
     }
 
+    /// 取出所有的文件描述符，使得表变为空
+    ///
+    /// 关闭某些文件（例如epoll）时需要访问当前进程的文件描述符表，
+    /// 因此调用者需要在释放表的锁之后，再drop返回的文件来关闭它们
+    pub fn take_all(&mut self) -> Vec<Arc<File>> {
+        self.fds.iter_mut().filter_map(|file| file.take()).collect()
+    }
+
     pub fn close_on_exec(&mut self) {
         for i in 0..FileDescriptorVec::PROCESS_MAX_FD {
             if let Some(file) = &self.fds[i] {
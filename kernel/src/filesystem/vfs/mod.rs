@@ -648,6 +648,7 @@ bitflags! {
         const PROC_MAGIC = 0x9fa0;
         const RAMFS_MAGIC = 0x858458f6;
         const MOUNT_MAGIC = 61267;
+        const ANON_INODE_FS_MAGIC = 0x09041934;
     }
 }
 
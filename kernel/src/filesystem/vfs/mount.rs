@@ -3,15 +3,12 @@ use core::{
     sync::atomic::{compiler_fence, Ordering},
 };
 
-use alloc::{
-    collections::BTreeMap,
-    sync::{Arc, Weak},
-};
+use alloc::sync::{Arc, Weak};
 use system_error::SystemError;
 
 use crate::{
-    driver::base::device::device_number::DeviceNumber,
-    libs::spinlock::{SpinLock, SpinLockGuard},
+    driver::base::device::device_number::DeviceNumber, libs::spinlock::SpinLockGuard,
+    namespaces::mnt_namespace::current_mnt_ns,
 };
 
 use super::{
@@ -23,12 +20,13 @@ const MOUNTFS_BLOCK_SIZE: u64 = 512;
 const MOUNTFS_MAX_NAMELEN: u64 = 64;
 /// @brief 挂载文件系统
 /// 挂载文件系统的时候，套了MountFS这一层，以实现文件系统的递归挂载
+///
+/// 挂载在这个文件系统中的子文件系统记录在进程所在的mount namespace的挂载表中，
+/// 因此不同mount namespace中的进程看到的挂载树可以不同
 #[derive(Debug)]
 pub struct MountFS {
     // MountFS内部的文件系统
     inner_filesystem: Arc<dyn FileSystem>,
-    /// 用来存储InodeID->挂载点的MountFS的B树
-    mountpoints: SpinLock<BTreeMap<InodeId, Arc<MountFS>>>,
     /// 当前文件系统挂载到的那个挂载点的Inode
     self_mountpoint: Option<Arc<MountFSInode>>,
     /// 指向当前MountFS的弱引用
@@ -54,7 +52,6 @@ impl MountFS {
     ) -> Arc<Self> {
         return MountFS {
             inner_filesystem: inner_fs,
-            mountpoints: SpinLock::new(BTreeMap::new()),
             self_mountpoint,
             self_ref: Weak::default(),
         }
@@ -134,26 +131,20 @@ impl MountFSInode {
     fn overlaid_inode(&self) -> Arc<MountFSInode> {
         let inode_id = self.metadata().unwrap().inode_id;
 
-        if let Some(sub_mountfs) = self.mount_fs.mountpoints.lock().get(&inode_id) {
+        if let Some(sub_mountfs) = current_mnt_ns().lookup(&self.mount_fs, inode_id) {
             return sub_mountfs.mountpoint_root_inode();
         } else {
             return self.self_ref.upgrade().unwrap();
         }
     }
 
-    /// 将新的挂载点-挂载文件系统添加到父级的挂载树
+    /// 将新的挂载点-挂载文件系统添加到当前进程所在的mount namespace的挂载表
     pub(super) fn do_mount(
         &self,
         inode_id: InodeId,
         new_mount_fs: Arc<MountFS>,
     ) -> Result<(), SystemError> {
-        let mut guard = self.mount_fs.mountpoints.lock();
-        if guard.contains_key(&inode_id) {
-            return Err(SystemError::EBUSY);
-        }
-        guard.insert(inode_id, new_mount_fs);
-
-        return Ok(());
+        return current_mnt_ns().add_mount(&self.mount_fs, inode_id, new_mount_fs);
     }
 
     pub(super) fn inode_id(&self) -> InodeId {
@@ -265,7 +256,7 @@ impl IndexNode for MountFSInode {
         let inode_id = self.inner_inode.find(name)?.metadata()?.inode_id;
 
         // 先检查这个inode是否为一个挂载点，如果当前inode是一个挂载点，那么就不能删除这个inode
-        if self.mount_fs.mountpoints.lock().contains_key(&inode_id) {
+        if current_mnt_ns().is_mountpoint(&self.mount_fs, inode_id) {
             return Err(SystemError::EBUSY);
         }
         // 调用内层的inode的方法来删除这个inode
@@ -277,7 +268,7 @@ impl IndexNode for MountFSInode {
         let inode_id = self.inner_inode.find(name)?.metadata()?.inode_id;
 
         // 先检查这个inode是否为一个挂载点，如果当前inode是一个挂载点，那么就不能删除这个inode
-        if self.mount_fs.mountpoints.lock().contains_key(&inode_id) {
+        if current_mnt_ns().is_mountpoint(&self.mount_fs, inode_id) {
             return Err(SystemError::EBUSY);
         }
         // 调用内层的rmdir的方法来删除这个inode
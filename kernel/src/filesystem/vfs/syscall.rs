@@ -14,7 +14,7 @@ use crate::{
     mm::{verify_area, VirtAddr},
     process::ProcessManager,
     syscall::{
-        user_access::{self, check_and_clone_cstr, UserBufferWriter},
+        user_access::{self, access_ok, check_and_clone_cstr, UserBufferWriter},
         Syscall,
     },
     time::PosixTimeSpec,
@@ -978,6 +978,8 @@ impl Syscall {
             .ok_or(SystemError::EBADF)?;
 
         let new_file = old_file.try_clone().ok_or(SystemError::EBADF)?;
+        // close-on-exec标志属于文件描述符，新的文件描述符不继承它
+        new_file.set_close_on_exec(false);
         // 申请文件描述符，并把文件对象存入其中
         let res = fd_table_guard.alloc_fd(new_file, None).map(|x| x as usize);
         return res;
@@ -994,17 +996,58 @@ impl Syscall {
     ///
     /// ## 返回值
     ///
-    /// - 成功：新文件描述符
-    /// - 失败：错误码
+    /// - 成功：新文件描述符。若oldfd与newfd相等，则直接返回newfd
+    /// - `EBADF`：oldfd没有打开，或者newfd超出了范围
     pub fn dup2(oldfd: i32, newfd: i32) -> Result<usize, SystemError> {
         let binding = ProcessManager::current_pcb().fd_table();
         let mut fd_table_guard = binding.write();
-        return Self::do_dup2(oldfd, newfd, &mut fd_table_guard);
+        return Self::do_dup2(oldfd, newfd, false, &mut fd_table_guard);
     }
 
+    /// 与dup2相同，但是可以通过flags为新的文件描述符设置O_CLOEXEC
+    ///
+    /// ## 参数
+    ///
+    /// - `oldfd`：旧文件描述符
+    /// - `newfd`：新文件描述符
+    /// - `flags`：只能为0或者O_CLOEXEC
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：新文件描述符
+    /// - `EINVAL`：oldfd与newfd相等，或者flags不合法
+    /// - `EBADF`：oldfd没有打开，或者newfd超出了范围
+    pub fn dup3(oldfd: i32, newfd: i32, flags: u32) -> Result<usize, SystemError> {
+        let flags = FileMode::from_bits(flags).ok_or(SystemError::EINVAL)?;
+        if !flags.difference(FileMode::O_CLOEXEC).is_empty() {
+            return Err(SystemError::EINVAL);
+        }
+        if oldfd == newfd {
+            return Err(SystemError::EINVAL);
+        }
+
+        let binding = ProcessManager::current_pcb().fd_table();
+        let mut fd_table_guard = binding.write();
+        return Self::do_dup2(
+            oldfd,
+            newfd,
+            flags.contains(FileMode::O_CLOEXEC),
+            &mut fd_table_guard,
+        );
+    }
+
+    /// 复制文件描述符
+    ///
+    /// ## 参数
+    ///
+    /// - `oldfd`：旧文件描述符
+    /// - `newfd`：新文件描述符
+    /// - `cloexec`：新文件描述符是否设置close-on-exec标志
+    /// - `fd_table_guard`：文件描述符表的写锁
     fn do_dup2(
         oldfd: i32,
         newfd: i32,
+        cloexec: bool,
         fd_table_guard: &mut RwLockWriteGuard<'_, FileDescriptorVec>,
     ) -> Result<usize, SystemError> {
         // 确认oldfd, newid是否有效
@@ -1012,10 +1055,20 @@ impl Syscall {
             return Err(SystemError::EBADF);
         }
 
+        // 先确认oldfd已经打开，再关闭newfd，避免出错时newfd被意外关闭
+        let old_file = fd_table_guard
+            .get_file_by_fd(oldfd)
+            .ok_or(SystemError::EBADF)?;
+
         if oldfd == newfd {
             // 若oldfd与newfd相等
             return Ok(newfd as usize);
         }
+
+        let new_file = old_file.try_clone().ok_or(SystemError::EBADF)?;
+        // close-on-exec标志属于文件描述符，由调用者决定新的文件描述符是否设置
+        new_file.set_close_on_exec(cloexec);
+
         let new_exists = fd_table_guard.get_file_by_fd(newfd).is_some();
         if new_exists {
             // close newfd
@@ -1024,11 +1077,6 @@ impl Syscall {
                 return Err(SystemError::EIO);
             }
         }
-
-        let old_file = fd_table_guard
-            .get_file_by_fd(oldfd)
-            .ok_or(SystemError::EBADF)?;
-        let new_file = old_file.try_clone().ok_or(SystemError::EBADF)?;
         // 申请文件描述符，并把文件对象存入其中
         let res = fd_table_guard
             .alloc_fd(new_file, Some(newfd))
@@ -1045,16 +1093,18 @@ impl Syscall {
     /// - `arg`：参数
     pub fn fcntl(fd: i32, cmd: FcntlCommand, arg: i32) -> Result<usize, SystemError> {
         match cmd {
-            FcntlCommand::DupFd => {
-                if arg < 0 || arg as usize >= FileDescriptorVec::PROCESS_MAX_FD {
-                    return Err(SystemError::EBADF);
+            FcntlCommand::DupFd | FcntlCommand::DupFdCloexec => {
+                let limit = ProcessManager::current_pcb().nofile_limit();
+                if arg < 0 || arg as usize >= limit {
+                    return Err(SystemError::EINVAL);
                 }
+                let cloexec = cmd == FcntlCommand::DupFdCloexec;
                 let arg = arg as usize;
-                for i in arg..FileDescriptorVec::PROCESS_MAX_FD {
+                for i in arg..limit {
                     let binding = ProcessManager::current_pcb().fd_table();
                     let mut fd_table_guard = binding.write();
                     if fd_table_guard.get_file_by_fd(i as i32).is_none() {
-                        return Self::do_dup2(fd, i as i32, &mut fd_table_guard);
+                        return Self::do_dup2(fd, i as i32, cloexec, &mut fd_table_guard);
                     }
                 }
                 return Err(SystemError::EMFILE);
@@ -1070,6 +1120,7 @@ impl Syscall {
                     if file.close_on_exec() {
                         return Ok(FD_CLOEXEC as usize);
                     }
+                    return Ok(0);
                 }
                 return Err(SystemError::EBADF);
             }
@@ -1112,7 +1163,9 @@ impl Syscall {
 
                 if let Some(file) = fd_table_guard.get_file_by_fd(fd) {
                     let arg = arg as u32;
-                    let mode = FileMode::from_bits(arg).ok_or(SystemError::EINVAL)?;
+                    let mut mode = FileMode::from_bits(arg).ok_or(SystemError::EINVAL)?;
+                    // close-on-exec标志只能通过F_SETFD修改
+                    mode.set(FileMode::O_CLOEXEC, file.close_on_exec());
                     // drop guard 以避免无法调度的问题
                     drop(fd_table_guard);
                     file.set_mode(mode)?;
@@ -1574,7 +1627,7 @@ impl IoVecs {
     pub unsafe fn from_user(
         iov: *const IoVec,
         iovcnt: usize,
-        _readv: bool,
+        readv: bool,
     ) -> Result<Self, SystemError> {
         // 检查iov指针所在空间是否合法
         verify_area(
@@ -1594,11 +1647,8 @@ impl IoVecs {
                 continue;
             }
 
-            verify_area(
-                VirtAddr::new(iov.iov_base as usize),
-                iovcnt * core::mem::size_of::<IoVec>(),
-            )
-            .map_err(|_| SystemError::EFAULT)?;
+            // readv会写入缓冲区，因此需要检查写权限
+            access_ok(VirtAddr::new(iov.iov_base as usize), iov.iov_len, readv)?;
 
             slices.push(core::slice::from_raw_parts_mut(iov.iov_base, iov.iov_len));
         }
@@ -1,5 +1,7 @@
 pub mod pipe;
+pub mod sem;
 pub mod shm;
 pub mod signal;
 pub mod signal_types;
+pub mod signalfd;
 pub mod syscall;
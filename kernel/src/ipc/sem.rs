@@ -0,0 +1,81 @@
+use alloc::vec::Vec;
+
+use crate::libs::spinlock::SpinLock;
+
+/// 进程对某个System V信号量集合所做调整的累计值
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/ipc/sem.c#145
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct SemUndo {
+    /// 信号量集合的id
+    pub semid: usize,
+    /// 对集合中每个信号量所做调整的累计值，进程退出时会被撤销
+    pub semadj: Vec<i16>,
+}
+
+/// System V信号量的撤销列表
+///
+/// 记录进程通过SEM_UNDO对信号量所做的调整。使用CLONE_SYSVSEM创建的进程与父进程共享同一个列表，
+/// 否则子进程拥有一个空的列表。列表的最后一个使用者退出时，列表中的调整会被撤销
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/ipc/sem.c#158
+#[derive(Debug, Default)]
+pub struct SemUndoList {
+    undos: SpinLock<Vec<SemUndo>>,
+}
+
+#[allow(dead_code)]
+impl SemUndoList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 查找进程对信号量集合`semid`所做的调整
+    pub fn find(&self, semid: usize) -> Option<SemUndo> {
+        return self
+            .undos
+            .lock_irqsave()
+            .iter()
+            .find(|undo| undo.semid == semid)
+            .cloned();
+    }
+
+    /// 记录进程对信号量集合`semid`中第`semnum`个信号量所做的调整
+    ///
+    /// ## 参数
+    ///
+    /// - `semid` : 信号量集合的id
+    /// - `nsems` : 集合中信号量的数量
+    /// - `semnum` : 信号量在集合中的下标
+    /// - `adj` : 本次操作需要在退出时撤销的调整值
+    pub fn adjust(&self, semid: usize, nsems: usize, semnum: usize, adj: i16) {
+        let mut undos = self.undos.lock_irqsave();
+        let index = match undos.iter().position(|undo| undo.semid == semid) {
+            Some(index) => index,
+            None => {
+                undos.push(SemUndo {
+                    semid,
+                    semadj: vec![0; nsems],
+                });
+                undos.len() - 1
+            }
+        };
+        let semadj = &mut undos[index].semadj[semnum];
+        *semadj = semadj.saturating_add(adj);
+    }
+
+    /// 信号量集合被删除时，移除与它相关的调整
+    pub fn remove(&self, semid: usize) {
+        self.undos.lock_irqsave().retain(|undo| undo.semid != semid);
+    }
+
+    /// 取出列表中所有的调整，用于在进程退出时撤销它们
+    pub fn take_all(&self) -> Vec<SemUndo> {
+        return core::mem::take(&mut *self.undos.lock_irqsave());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.undos.lock_irqsave().is_empty();
+    }
+}
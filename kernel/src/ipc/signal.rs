@@ -11,56 +11,202 @@ use crate::{
     process::{pid::PidType, Pid, ProcessControlBlock, ProcessFlags, ProcessManager},
 };
 
-use super::signal_types::{
-    SaHandlerType, SigInfo, SigType, Sigaction, SignalStruct, SIG_KERNEL_STOP_MASK,
+use super::{
+    signal_types::{
+        SaHandlerType, SigInfo, SigType, Sigaction, SignalStruct, SIG_KERNEL_IGNORE_MASK,
+        SIG_KERNEL_ONLY_MASK, SIG_KERNEL_STOP_MASK,
+    },
+    signalfd::signalfd_notify,
 };
 
 impl Signal {
+    /// 判断信号是否只能由内核处理（SIGKILL、SIGSTOP），这些信号既不能被捕获、忽略，也不能被屏蔽
+    pub fn kernel_only(&self) -> bool {
+        return !(self.into_sigset() & SIG_KERNEL_ONLY_MASK).is_empty();
+    }
+
     /// 向目标进程发送信号
     ///
     /// ## 参数
     ///
-    /// - `sig` 要发送的信号
+    /// - `sig` 要发送的信号，为[`Signal::INVALID`]（即0号信号）时只检查目标进程是否存在，不发送信号
     /// - `info` 要发送的信息
-    /// -  `pid` 进程id（目前只支持pid>0)
+    /// - `pid` 目标进程的pid
+    ///
+    /// ## 返回值
+    ///
+    /// - `ESRCH`：目标进程不存在
     pub fn send_signal_info(
         &self,
         info: Option<&mut SigInfo>,
         pid: Pid,
     ) -> Result<i32, SystemError> {
-        // TODO:暂时不支持特殊的信号操作，待引入进程组后补充
-        // 如果 pid 大于 0，那么会发送信号给 pid 指定的进程
-        // 如果 pid 等于 0，那么会发送信号给与调用进程同组的每个进程，包括调用进程自身
-        // 如果 pid 小于 -1，那么会向组 ID 等于该 pid 绝对值的进程组内所有下属进程发送信号。向一个进程组的所有进程发送信号在 shell 作业控制中有特殊有途
-        // 如果 pid 等于 -1，那么信号的发送范围是：调用进程有权将信号发往的每个目标进程，除去 init（进程 ID 为 1）和调用进程自身。如果特权级进程发起这一调用，那么会发送信号给系统中的所有进程，上述两个进程除外。显而易见，有时也将这种信号发送方式称之为广播信号
-        // 如果并无进程与指定的 pid 相匹配，那么 kill() 调用失败，同时将 errno 置为 ESRCH（“查无此进程”）
-        if pid.lt(&Pid::from(0)) {
-            kwarn!("Kill operation not support: pid={:?}", pid);
-            return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
-        }
         compiler_fence(core::sync::atomic::Ordering::SeqCst);
         // 检查sig是否符合要求，如果不符合要求，则退出。
         if !self.is_valid() {
             return Err(SystemError::EINVAL);
         }
-        let mut retval = Err(SystemError::ESRCH);
-        let pcb = ProcessManager::find(pid);
+        let pcb = ProcessManager::find(pid).ok_or(SystemError::ESRCH)?;
+        return self.send_signal_info_to_pcb(info, pcb);
+    }
 
-        if pcb.is_none() {
-            kwarn!("No such process.");
-            return retval;
+    /// 向pcb对应的进程发送信号
+    ///
+    /// 调用者已经持有了目标进程的pcb（例如通过pidfd），因此不需要再通过pid查找，避免pid被复用导致发错对象。
+    /// 信号会被发送给pcb所在的整个线程组，由组内任意一个没有屏蔽它的线程处理
+    pub fn send_signal_info_to_pcb(
+        &self,
+        info: Option<&mut SigInfo>,
+        pcb: Arc<ProcessControlBlock>,
+    ) -> Result<i32, SystemError> {
+        return self.do_send_signal_info(info, pcb, PidType::TGID);
+    }
+
+    /// 向pcb对应的线程发送信号，信号只会由这个线程处理
+    pub fn send_signal_info_to_thread(
+        &self,
+        info: Option<&mut SigInfo>,
+        pcb: Arc<ProcessControlBlock>,
+    ) -> Result<i32, SystemError> {
+        return self.do_send_signal_info(info, pcb, PidType::PID);
+    }
+
+    fn do_send_signal_info(
+        &self,
+        info: Option<&mut SigInfo>,
+        pcb: Arc<ProcessControlBlock>,
+        pt: PidType,
+    ) -> Result<i32, SystemError> {
+        if !self.is_valid() {
+            return Err(SystemError::EINVAL);
+        }
+        // 0号信号只用于检查目标进程是否存在
+        if *self == Signal::INVALID {
+            return Ok(0);
         }
 
-        let pcb = pcb.unwrap();
-        // println!("Target pcb = {:?}", pcb.as_ref().unwrap());
         compiler_fence(core::sync::atomic::Ordering::SeqCst);
         // 发送信号
-        retval = self.send_signal(info, pcb.clone(), PidType::PID);
+        let retval = self.send_signal(info, pcb, pt);
 
         compiler_fence(core::sync::atomic::Ordering::SeqCst);
         return retval;
     }
 
+    /// 由内核强制向进程发送信号（force_sig）
+    ///
+    /// 用于进程已经无法正常运行下去的场景（例如返回用户态之前访问用户空间失败）。
+    /// 如果信号被屏蔽或者被忽略，则先解除屏蔽并把它的处理方式恢复为默认，
+    /// 保证信号一定会被处理，而不会让进程带着错误的状态继续运行
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/signal.c#1290
+    pub fn force_send(&self, pcb: Arc<ProcessControlBlock>) -> Result<i32, SystemError> {
+        if !self.is_valid() || *self == Signal::INVALID {
+            return Err(SystemError::EINVAL);
+        }
+
+        let blocked = pcb
+            .sig_info_irqsave()
+            .sig_block()
+            .contains(self.into_sigset());
+        {
+            let sig_guard = pcb.sig_struct_irqsave();
+            let mut handler_guard = sig_guard.handlers_mut();
+            let action = &mut handler_guard.handlers[*self as usize - 1];
+            if blocked || action.is_ignore() {
+                action.set_action(SigactionType::SaHandler(SaHandlerType::Default));
+            }
+        }
+        if blocked {
+            pcb.sig_info_mut()
+                .sig_block_mut()
+                .remove(self.into_sigset());
+        }
+
+        let mut info = SigInfo::new(
+            *self,
+            0,
+            SigCode::Kernel,
+            SigType::Kill(ProcessManager::current_pcb().pid()),
+        );
+        return self.send_signal_info_to_thread(Some(&mut info), pcb);
+    }
+
+    /// 向线程组`tgid`中的线程`tid`发送信号（tgkill）
+    ///
+    /// ## 参数
+    ///
+    /// - `info` 要发送的信息
+    /// - `tgid` 目标线程所在的线程组id，为None时不检查目标线程所在的线程组（tkill）
+    /// - `tid` 目标线程的id
+    ///
+    /// ## 返回值
+    ///
+    /// - `ESRCH`：目标线程不存在，或者它不属于线程组`tgid`
+    pub fn send_signal_to_thread(
+        &self,
+        info: Option<&mut SigInfo>,
+        tgid: Option<Pid>,
+        tid: Pid,
+    ) -> Result<i32, SystemError> {
+        let pcb = ProcessManager::find_thread_by_tid(tid).ok_or(SystemError::ESRCH)?;
+        if tgid.is_some_and(|tgid| pcb.tgid() != tgid) {
+            return Err(SystemError::ESRCH);
+        }
+        return self.send_signal_info_to_thread(info, pcb);
+    }
+
+    /// 向进程组`pgid`中的每个进程发送信号
+    ///
+    /// ## 返回值
+    ///
+    /// 只要有一个进程成功接收了信号，就返回成功，否则返回最后一次发送的错误码。
+    /// 如果进程组中没有进程，则返回`ESRCH`
+    pub fn send_signal_to_pgrp(
+        &self,
+        mut info: Option<&mut SigInfo>,
+        pgid: Pid,
+    ) -> Result<i32, SystemError> {
+        let targets = ProcessManager::all_processes()
+            .into_iter()
+            .filter(|pcb| Self::is_user_process(pcb) && pcb.basic().pgid() == pgid);
+
+        let mut retval = Err(SystemError::ESRCH);
+        for pcb in targets {
+            let r = self.send_signal_info(info.as_deref_mut(), pcb.pid());
+            if retval.is_err() {
+                retval = r;
+            }
+        }
+        return retval;
+    }
+
+    /// 向调用者有权发送信号的所有进程发送信号，init进程以及调用者所在的线程组除外
+    ///
+    /// ## 返回值
+    ///
+    /// 只要有一个进程成功接收了信号，就返回成功。如果没有可以发送信号的进程，则返回`ESRCH`
+    pub fn send_signal_to_all(&self, mut info: Option<&mut SigInfo>) -> Result<i32, SystemError> {
+        let current = ProcessManager::current_pcb();
+        let targets = ProcessManager::all_processes().into_iter().filter(|pcb| {
+            Self::is_user_process(pcb) && pcb.pid() > Pid(1) && pcb.tgid() != current.tgid()
+        });
+
+        let mut retval = Err(SystemError::ESRCH);
+        for pcb in targets {
+            let r = self.send_signal_info(info.as_deref_mut(), pcb.pid());
+            if retval.is_err() {
+                retval = r;
+            }
+        }
+        return retval;
+    }
+
+    /// 判断pcb能否被按进程组或者广播的方式选中（只会选中用户进程的线程组组长）
+    fn is_user_process(pcb: &Arc<ProcessControlBlock>) -> bool {
+        return pcb.is_thread_group_leader() && !pcb.flags().contains(ProcessFlags::KTHREAD);
+    }
+
     /// @brief 判断是否需要强制发送信号，然后发送信号
     /// 进入函数后加锁
     ///
@@ -86,12 +232,36 @@ impl Signal {
             return Err(SystemError::EINVAL);
         }
         // kdebug!("force send={}", force_send);
-        let pcb_info = pcb.sig_info_irqsave();
-        let pending = if matches!(pt, PidType::PID) {
-            pcb_info.sig_shared_pending()
+        let new_sig_info = match info {
+            Some(siginfo) => {
+                // 已经显式指定了siginfo，则直接使用它。
+                *siginfo
+            }
+            None => {
+                // 不需要显示指定siginfo，因此设置为默认值
+                SigInfo::new(
+                    *self,
+                    0,
+                    SigCode::User,
+                    SigType::Kill(ProcessManager::current_pcb().pid()),
+                )
+            }
+        };
+
+        // 发送给线程组的信号，由组内一个没有屏蔽它的线程来处理
+        let pcb = if pt == PidType::PID || pcb.flags().contains(ProcessFlags::KTHREAD) {
+            pcb
         } else {
-            pcb_info.sig_pending()
+            match self.select_group_thread(&pcb, new_sig_info) {
+                Some(thread) => thread,
+                // 所有线程都屏蔽了这个信号，它已经被留在线程组共享的待处理队列中
+                None => return Ok(0),
+            }
         };
+
+        let pcb_info = pcb.sig_info_irqsave();
+        // 信号的siginfo会被加入到sig_pending的队列中，因此需要在这个队列中检查是否已经有相同的信号
+        let pending = pcb_info.sig_pending();
         compiler_fence(core::sync::atomic::Ordering::SeqCst);
         // 如果是kill或者目标pcb是内核线程，则无需获取sigqueue，直接发送信号即可
         if matches!(self, Signal::SIGKILL) || pcb.flags().contains(ProcessFlags::KTHREAD) {
@@ -99,27 +269,12 @@ impl Signal {
             drop(pcb_info);
             self.complete_signal(pcb.clone(), pt);
         }
-        // 如果不是实时信号的话，同一时刻信号队列里只会有一个待处理的信号，如果重复接收就不做处理
+        // 如果不是实时信号的话，同一时刻信号队列里只会有一个待处理的信号，如果重复接收就不做处理。
+        // 实时信号则会按照发送的顺序排队，同一个信号可以有多个实例，每个实例都带有自己的siginfo
         else if !self.is_rt_signal() && pending.queue().find(*self).0.is_some() {
             return Ok(0);
         } else {
-            // TODO signalfd_notify 完善 signalfd 机制
             // 如果是其他信号，则加入到sigqueue内，然后complete_signal
-            let new_sig_info = match info {
-                Some(siginfo) => {
-                    // 已经显式指定了siginfo，则直接使用它。
-                    *siginfo
-                }
-                None => {
-                    // 不需要显示指定siginfo，因此设置为默认值
-                    SigInfo::new(
-                        *self,
-                        0,
-                        SigCode::User,
-                        SigType::Kill(ProcessManager::current_pcb().pid()),
-                    )
-                }
-            };
             drop(pcb_info);
             pcb.sig_info_mut()
                 .sig_pending_mut()
@@ -127,6 +282,9 @@ impl Signal {
                 .q
                 .push(new_sig_info);
 
+            // 通知正在等待这个信号的signalfd
+            signalfd_notify(&pcb);
+
             // if pt == PidType::PGID || pt == PidType::SID {}
             self.complete_signal(pcb.clone(), pt);
         }
@@ -134,10 +292,58 @@ impl Signal {
         return Ok(0);
     }
 
-    /// @brief 将信号添加到目标进程的sig_pending。在引入进程组后，本函数还将负责把信号传递给整个进程组。
+    /// 为发送给线程组的信号挑选一个处理它的线程
+    ///
+    /// 优先选择`pcb`本身，其次是组内其他没有屏蔽这个信号、并且没有在退出的线程。
+    /// 如果所有线程都屏蔽了这个信号，则把它留在线程组共享的待处理队列（组长的sig_shared_pending）中，
+    /// 等到某个线程解除屏蔽之后再由这个线程处理（见[`recalc_sigpending`]）
+    ///
+    /// ## 返回值
+    ///
+    /// - `Some(thread)` 处理这个信号的线程
+    /// - `None` 信号已经被放入线程组共享的待处理队列
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/signal.c#989
+    fn select_group_thread(
+        &self,
+        pcb: &Arc<ProcessControlBlock>,
+        info: SigInfo,
+    ) -> Option<Arc<ProcessControlBlock>> {
+        let mut threads = pcb.thread_group();
+        threads.retain(|thread| !Arc::ptr_eq(thread, pcb));
+        threads.insert(0, pcb.clone());
+
+        let leader = ProcessManager::find(pcb.tgid()).unwrap_or_else(|| pcb.clone());
+        // 在挑选线程期间持有组长的sig_info锁，与正在解除屏蔽的线程互斥（它同样需要先获取这个锁），
+        // 保证信号要么被交给这个线程，要么能被这个线程从共享队列中取走
+        let mut leader_info = leader.sig_info_mut();
+        let target = threads.into_iter().find(|thread| {
+            if thread.flags().contains(ProcessFlags::EXITING) {
+                return false;
+            }
+            let blocked = if Arc::ptr_eq(thread, &leader) {
+                *leader_info.sig_block()
+            } else {
+                *thread.sig_info_irqsave().sig_block()
+            };
+            !blocked.contains(self.into_sigset())
+        });
+
+        if target.is_none() {
+            let shared = leader_info.sig_shared_pending_mut();
+            if self.is_rt_signal() || shared.queue().find(*self).0.is_none() {
+                shared.queue_mut().q.push(info);
+            }
+            drop(leader_info);
+            signalfd_notify(&leader);
+        }
+        return target;
+    }
+
+    /// @brief 将信号添加到目标线程的sig_pending，并唤醒它。致命信号还会杀死目标线程所在的整个线程组。
     ///
     /// @param sig 信号
-    /// @param pcb 目标pcb
+    /// @param pcb 目标pcb（对于发送给线程组的信号，是已经由select_group_thread挑选出来的线程）
     /// @param pt siginfo结构体中，pid字段代表的含义
     #[allow(clippy::if_same_then_else)]
     fn complete_signal(&self, pcb: Arc<ProcessControlBlock>, pt: PidType) {
@@ -145,8 +351,6 @@ impl Signal {
 
         compiler_fence(core::sync::atomic::Ordering::SeqCst);
         // ===== 寻找需要wakeup的目标进程 =====
-        // 备注：由于当前没有进程组的概念，每个进程只有1个对应的线程，因此不需要通知进程组内的每个进程。
-        //      todo: 当引入进程组的概念后，需要完善这里，使得它能寻找一个目标进程来唤醒，接着执行信号处理的操作。
 
         // let _signal = pcb.sig_struct();
 
@@ -169,13 +373,31 @@ impl Signal {
             return;
         } else {
             /*
-             * Otherwise try to find a suitable thread.
-             * 由于目前每个进程只有1个线程，因此当前情况可以返回。信号队列的dequeue操作不需要考虑同步阻塞的问题。
+             * 处理这个信号的线程已经由select_group_thread挑选出来，
+             * 它在下一次返回用户态之前会从信号队列中取出这个信号。
              */
             return;
         }
 
-        // TODO:引入进程组后，在这里挑选一个进程来唤醒，让它执行相应的操作。
+        // 致命信号会杀死整个线程组：记录线程组的退出码，然后让组内的每个线程都处理SIGKILL。
+        // 被跟踪的线程由跟踪者决定如何处理信号，因此只有SIGKILL会直接杀死它所在的线程组
+        if !pcb.flags().contains(ProcessFlags::KTHREAD)
+            && self.sig_fatal(pcb.clone())
+            && (*self == Signal::SIGKILL || !pcb.is_traced())
+            && ProcessManager::start_group_exit(&pcb, *self as usize)
+        {
+            for thread in pcb.thread_group() {
+                thread
+                    .sig_info_mut()
+                    .sig_pending_mut()
+                    .signal_mut()
+                    .insert(Signal::SIGKILL.into());
+                let guard = thread.sig_struct();
+                signal_wake_up(thread.clone(), guard, true);
+            }
+            return;
+        }
+
         compiler_fence(core::sync::atomic::Ordering::SeqCst);
         // TODO: 到这里，信号已经被放置在共享的pending队列中，我们在这里把目标进程唤醒。
         if let Some(target_pcb) = target_pcb {
@@ -213,18 +435,20 @@ impl Signal {
         return pcb.sig_info_irqsave().sig_pending().signal().bits() == 0;
     }
 
-    /// @brief 判断signal的处理是否可能使得整个进程组退出
-    /// @return true 可能会导致退出（不一定）
-    #[allow(dead_code)]
+    /// 判断信号是否会导致整个线程组退出：它采用默认处理方式，并且默认处理方式是终止进程
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/linux/signal.h
     #[inline]
     fn sig_fatal(&self, pcb: Arc<ProcessControlBlock>) -> bool {
-        let action = pcb.sig_struct().handlers[*self as usize - 1].action();
-        // 如果handler是空，采用默认函数，signal处理可能会导致进程退出。
+        if !(self.into_sigset() & (SIG_KERNEL_IGNORE_MASK | SIG_KERNEL_STOP_MASK)).is_empty() {
+            return false;
+        }
+        let action = pcb.sig_struct().action(*self).action();
+        // 如果handler是空，采用默认函数，signal处理会导致进程退出。
         match action {
             SigactionType::SaHandler(handler) => handler.is_sig_default(),
             SigactionType::SaSigaction(sigaction) => sigaction.is_none(),
         }
-        // todo: 参照linux的sig_fatal实现完整功能
     }
 
     /// 检查信号是否能被发送，并且而且要处理 SIGCONT 和 STOP 信号
@@ -241,22 +465,18 @@ impl Signal {
     ///
     /// - `false` 不能发送信号
     fn prepare_sianal(&self, pcb: Arc<ProcessControlBlock>, _force: bool) -> bool {
-        let flush: SigSet;
         if !(self.into_sigset() & SIG_KERNEL_STOP_MASK).is_empty() {
-            flush = Signal::SIGCONT.into_sigset();
-            pcb.sig_info_mut()
-                .sig_shared_pending_mut()
-                .flush_by_mask(&flush);
-            // TODO 对每个子线程 flush mask
+            // 停止信号会取消线程组中所有尚未处理的SIGCONT
+            let flush = Signal::SIGCONT.into_sigset();
+            for thread in pcb.thread_group() {
+                let mut sig_info = thread.sig_info_mut();
+                sig_info.sig_pending_mut().flush_by_mask(&flush);
+                sig_info.sig_shared_pending_mut().flush_by_mask(&flush);
+            }
         } else if *self == Signal::SIGCONT {
-            flush = SIG_KERNEL_STOP_MASK;
-            assert!(!flush.is_empty());
-            pcb.sig_info_mut()
-                .sig_shared_pending_mut()
-                .flush_by_mask(&flush);
-            let _r = ProcessManager::wakeup_stop(&pcb);
-            // TODO 对每个子线程 flush mask
-            // 这里需要补充一段逻辑，详见https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/signal.c#952
+            // SIGCONT会取消线程组中所有尚未处理的停止信号，并让整个线程组继续运行。
+            // 即使SIGCONT被忽略或者屏蔽，也会让线程组继续运行
+            ProcessManager::group_continue(&pcb);
         }
 
         // 一个被阻塞了的信号肯定是要被处理的
@@ -267,7 +487,7 @@ impl Signal {
         {
             return true;
         }
-        return !pcb.sig_struct().handlers[*self as usize - 1].is_ignore();
+        return !pcb.sig_struct().action(*self).is_ignore();
 
         //TODO 仿照 linux 中的prepare signal完善逻辑，linux 中还会根据例如当前进程状态(Existing)进行判断，现在的信号能否发出就只是根据 ignored 来判断
     }
@@ -298,7 +518,8 @@ fn signal_wake_up(pcb: Arc<ProcessControlBlock>, _guard: SpinLockGuard<SignalStr
                 e
             );
         });
-    } else if state.is_stopped() {
+    } else if state.is_stopped() && fatal {
+        // 停止的进程只会被致命信号唤醒，其他信号要等到SIGCONT让它继续运行之后才会处理
         ProcessManager::wakeup_stop(&pcb).unwrap_or_else(|e| {
             wakeup_ok = false;
             kwarn!(
@@ -321,26 +542,66 @@ fn signal_wake_up(pcb: Arc<ProcessControlBlock>, _guard: SpinLockGuard<SignalStr
     }
 }
 
-/// @brief 当一个进程具有多个线程之后，在这里需要重新计算线程的flag中的TIF_SIGPENDING位
+/// 重新计算当前线程待处理的信号，使得在屏蔽期间进入队列的信号能够在解除屏蔽后被处理
+///
+/// 线程组共享的待处理队列中，当前线程没有屏蔽的信号也会被转交给当前线程处理
 fn recalc_sigpending() {
-    // todo:
+    let pcb = ProcessManager::current_pcb();
+    let leader = ProcessManager::find(pcb.tgid()).filter(|leader| !Arc::ptr_eq(leader, &pcb));
+    // 与select_group_thread相同，先获取组长的sig_info锁，再获取当前线程的
+    let mut leader_info = leader.as_ref().map(|leader| leader.sig_info_mut());
+    let mut sig_info = pcb.sig_info_mut();
+    let blocked = *sig_info.sig_block();
+
+    if let Some(leader_info) = leader_info.as_mut() {
+        leader_info
+            .sig_shared_pending_mut()
+            .move_unblocked(sig_info.sig_pending_mut(), &blocked);
+    } else {
+        let mut shared = core::mem::take(sig_info.sig_shared_pending_mut());
+        shared.move_unblocked(sig_info.sig_pending_mut(), &blocked);
+        *sig_info.sig_shared_pending_mut() = shared;
+    }
+    sig_info.sig_pending_mut().recalc(&blocked);
+}
+
+/// 获取线程`pcb`尚未处理的信号
+///
+/// 包括发送给这个线程的信号（线程私有的待处理信号），以及发送给它所在的线程组、尚未被任何线程取走的信号
+pub fn pending_signals(pcb: &Arc<ProcessControlBlock>) -> SigSet {
+    let private = pcb.sig_info_irqsave().sig_pending().pending_set();
+    let leader = ProcessManager::find(pcb.tgid()).unwrap_or_else(|| pcb.clone());
+    let shared = leader.sig_info_irqsave().sig_shared_pending().pending_set();
+    return private | shared;
 }
 
-/// @brief 刷新指定进程的sighand的sigaction，将满足条件的sigaction恢复为Default
-///     除非某个信号被设置为ignore且force_default为false，否则都不会将其恢复
+/// 重置指定进程的信号处理函数表
 ///
-/// @param pcb 要被刷新的pcb
-/// @param force_default 是否强制将sigaction恢复成默认状态
+/// 所有设置了处理函数的信号都被恢复为默认处理方式（SIG_DFL），被忽略（SIG_IGN）的信号是否恢复由`force_default`决定。
+/// 无论是否恢复，每个信号的sa_flags、sa_mask以及sa_restorer都会被清空。
+///
+/// execve与CLONE_CLEAR_SIGHAND都使用`force_default = false`：
+/// 原来的处理函数在新的程序映像（或者重置之后的子进程）中已经没有意义，而忽略信号的设置仍然有效
+///
+/// ## 参数
+///
+/// - `pcb`：要重置信号处理函数表的进程。若它的表与其他进程共享，调用者需要先拷贝一份
+/// - `force_default`：
+///   - `true`：被忽略的信号也恢复为默认处理方式
+///   - `false`：被忽略的信号保持忽略
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/signal.c#541
 pub fn flush_signal_handlers(pcb: Arc<ProcessControlBlock>, force_default: bool) {
     compiler_fence(core::sync::atomic::Ordering::SeqCst);
     // kdebug!("hand=0x{:018x}", hand as *const sighand_struct as usize);
-    let actions = &mut pcb.sig_struct_irqsave().handlers;
+    let sig_guard = pcb.sig_struct_irqsave();
+    let mut handler_guard = sig_guard.handlers_mut();
 
-    for sigaction in actions.iter_mut() {
+    for sigaction in handler_guard.handlers.iter_mut() {
         if force_default || !sigaction.is_ignore() {
             sigaction.set_action(SigactionType::SaHandler(SaHandlerType::Default));
         }
-        // 清除flags中，除了DFL和IGN以外的所有标志
+        *sigaction.flags_mut() = SigFlags::empty();
         sigaction.set_restorer(None);
         sigaction.mask_mut().remove(SigSet::all());
         compiler_fence(core::sync::atomic::Ordering::SeqCst);
@@ -356,9 +617,15 @@ pub(super) fn do_sigaction(
     if sig == Signal::INVALID {
         return Err(SystemError::EINVAL);
     }
+    // SIGKILL和SIGSTOP的处理方式不能被修改
+    if act.is_some() && sig.kernel_only() {
+        return Err(SystemError::EINVAL);
+    }
     let pcb = ProcessManager::current_pcb();
     // 指向当前信号的action的引用
-    let action: &mut Sigaction = &mut pcb.sig_struct().handlers[sig as usize - 1];
+    let sig_guard = pcb.sig_struct();
+    let mut handler_guard = sig_guard.handlers_mut();
+    let action: &mut Sigaction = &mut handler_guard.handlers[sig as usize - 1];
 
     // 对比 MUSL 和 relibc ， 暂时不设置这个标志位
     // if action.flags().contains(SigFlags::SA_FLAG_IMMUTABLE) {
@@ -390,8 +657,7 @@ pub(super) fn do_sigaction(
 
     if let Some(ac) = act {
         // 将act.sa_mask的SIGKILL SIGSTOP的屏蔽清除
-        ac.mask_mut()
-            .remove(<Signal as Into<SigSet>>::into(Signal::SIGKILL) | Signal::SIGSTOP.into());
+        ac.mask_mut().remove(SIG_KERNEL_ONLY_MASK);
 
         // 将新的sigaction拷贝到进程的action中
         *action = *ac;
@@ -418,9 +684,7 @@ pub(super) fn do_sigaction(
 ///
 /// - `new_set` 新的屏蔽信号bitmap的值
 pub fn set_current_sig_blocked(new_set: &mut SigSet) {
-    let to_remove: SigSet =
-        <Signal as Into<SigSet>>::into(Signal::SIGKILL) | Signal::SIGSTOP.into();
-    new_set.remove(to_remove);
+    new_set.remove(SIG_KERNEL_ONLY_MASK);
     //TODO 把这个散装函数用 sigsetops 替换掉
     let pcb = ProcessManager::current_pcb();
 
@@ -433,7 +697,6 @@ pub fn set_current_sig_blocked(new_set: &mut SigSet) {
     }
 
     let guard = pcb.sig_struct_irqsave();
-    // todo: 当一个进程有多个线程后，在这里需要设置每个线程的block字段，并且 retarget_shared_pending（虽然我还没搞明白linux这部分是干啥的）
 
     // 设置当前进程的sig blocked
     *pcb.sig_info_mut().sig_block_mut() = *new_set;
@@ -5,7 +5,7 @@ use core::{
     sync::atomic::AtomicI64,
 };
 
-use alloc::{boxed::Box, vec::Vec};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
 use system_error::SystemError;
 
 use crate::{
@@ -14,9 +14,11 @@ use crate::{
         interrupt::TrapFrame,
         ipc::signal::{SigCode, SigFlags, SigSet, Signal, MAX_SIG_NUM},
     },
+    libs::rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard},
     mm::VirtAddr,
     process::Pid,
     syscall::user_access::UserBufferWriter,
+    time::NSEC_PER_SEC,
 };
 
 /// 用户态程序传入的SIG_DFL的值
@@ -25,10 +27,16 @@ pub const USER_SIG_DFL: u64 = 0;
 pub const USER_SIG_IGN: u64 = 1;
 /// 用户态程序传入的SIG_ERR的值
 pub const USER_SIG_ERR: u64 = 2;
+/// 用户态使用的clock_t的频率，siginfo中的si_utime、si_stime以它为单位
+pub const USER_HZ: u64 = 100;
+
+/// 把纳秒转换为以USER_HZ为单位的clock_t
+fn nsec_to_clock_t(nsec: u64) -> i64 {
+    (nsec / (NSEC_PER_SEC as u64 / USER_HZ)) as i64
+}
 
 // 因为 Rust 编译器不能在常量声明中正确识别级联的 "|" 运算符(experimental feature： https://github.com/rust-lang/rust/issues/67792)，因此
 // 暂时只能通过这种方法来声明这些常量，这些常量暂时没有全部用到，但是都出现在 linux 的判断逻辑中，所以都保留下来了
-#[allow(dead_code)]
 pub const SIG_KERNEL_ONLY_MASK: SigSet =
     Signal::into_sigset(Signal::SIGSTOP).union(Signal::into_sigset(Signal::SIGKILL));
 
@@ -47,15 +55,11 @@ pub const SIG_KERNEL_COREDUMP_MASK: SigSet = Signal::into_sigset(Signal::SIGQUIT
     .union(Signal::into_sigset(Signal::SIGSYS))
     .union(Signal::into_sigset(Signal::SIGXCPU))
     .union(Signal::into_sigset(Signal::SIGXFSZ));
-#[allow(dead_code)]
+/// 默认处理方式为忽略（或者继续运行）的信号
 pub const SIG_KERNEL_IGNORE_MASK: SigSet = Signal::into_sigset(Signal::SIGCONT)
-    .union(Signal::into_sigset(Signal::SIGFPE))
-    .union(Signal::into_sigset(Signal::SIGSEGV))
-    .union(Signal::into_sigset(Signal::SIGBUS))
-    .union(Signal::into_sigset(Signal::SIGTRAP))
     .union(Signal::into_sigset(Signal::SIGCHLD))
-    .union(Signal::into_sigset(Signal::SIGIO_OR_POLL))
-    .union(Signal::into_sigset(Signal::SIGSYS));
+    .union(Signal::into_sigset(Signal::SIGWINCH))
+    .union(Signal::into_sigset(Signal::SIGURG));
 
 /// SignalStruct 在 pcb 中加锁
 #[derive(Debug)]
@@ -66,9 +70,46 @@ pub struct SignalStruct {
 #[derive(Debug)]
 pub struct InnerSignalStruct {
     pub cnt: AtomicI64,
-    /// 如果对应linux，这部分会有一个引用计数，但是没发现在哪里有用到需要计算引用的地方，因此
-    /// 暂时删掉，不然这个Arc会导致其他地方的代码十分丑陋
-    pub handlers: [Sigaction; MAX_SIG_NUM],
+    /// 信号处理函数表。使用CLONE_SIGHAND创建的进程与父进程共享同一个表。
+    ///
+    /// 这个表可能被多个进程共享，因此只持有`SignalStruct`的锁是不够的，
+    /// 所有读写都必须经过表自身的锁。信号可能在中断上下文中被发送，因此这个锁总是以irqsave的方式获取
+    handler: Arc<RwLock<SigHandStruct>>,
+}
+
+impl InnerSignalStruct {
+    /// 获取信号处理函数表的读者守卫
+    pub fn handlers(&self) -> RwLockReadGuard<SigHandStruct> {
+        self.handler.read_irqsave()
+    }
+
+    /// 获取信号处理函数表的写者守卫。修改会对所有共享这个表的进程生效
+    pub fn handlers_mut(&self) -> RwLockWriteGuard<SigHandStruct> {
+        self.handler.write_irqsave()
+    }
+
+    /// 获取信号`sig`的处理方式的拷贝
+    pub fn action(&self, sig: Signal) -> Sigaction {
+        self.handlers().handlers[sig as usize - 1]
+    }
+
+    /// 获取信号处理函数表的引用，用于与其他进程共享（CLONE_SIGHAND）
+    pub fn share_handlers(&self) -> Arc<RwLock<SigHandStruct>> {
+        self.handler.clone()
+    }
+
+    /// 替换信号处理函数表
+    pub fn set_handlers(&mut self, handler: Arc<RwLock<SigHandStruct>>) {
+        self.handler = handler;
+    }
+
+    /// 如果信号处理函数表与其他进程共享，则拷贝一份私有的表，使得之后的修改不会影响到其他进程
+    pub fn unshare_handlers(&mut self) {
+        if Arc::strong_count(&self.handler) > 1 {
+            let handler = self.handlers().clone();
+            self.handler = Arc::new(RwLock::new(handler));
+        }
+    }
 }
 
 impl SignalStruct {
@@ -94,11 +135,42 @@ impl DerefMut for SignalStruct {
     }
 }
 
+/// 信号处理函数表
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/linux/sched/signal.h#21
+#[derive(Debug, Clone)]
+pub struct SigHandStruct {
+    pub handlers: [Sigaction; MAX_SIG_NUM],
+}
+
+impl Default for SigHandStruct {
+    fn default() -> Self {
+        Self {
+            handlers: [Sigaction::default(); MAX_SIG_NUM],
+        }
+    }
+}
+
+impl SigHandStruct {
+    /// 将SIGKILL、SIGSTOP的处理方式恢复为默认，并将它们从所有处理函数的sa_mask中移除
+    ///
+    /// SIGKILL和SIGSTOP既不能被捕获，也不能被屏蔽。从其他地方拷贝来的处理函数表
+    /// 不一定满足这一点，因此需要调用这个函数来修正
+    pub fn reset_kernel_only_actions(&mut self) {
+        for sig in [Signal::SIGKILL, Signal::SIGSTOP] {
+            self.handlers[sig as usize - 1] = Sigaction::DEFAULT_SIGACTION;
+        }
+        for action in self.handlers.iter_mut() {
+            action.mask_mut().remove(SIG_KERNEL_ONLY_MASK);
+        }
+    }
+}
+
 impl Default for InnerSignalStruct {
     fn default() -> Self {
         Self {
             cnt: Default::default(),
-            handlers: [Sigaction::default(); MAX_SIG_NUM],
+            handler: Arc::new(RwLock::new(SigHandStruct::default())),
         }
     }
 }
@@ -275,6 +347,99 @@ impl Sigaction {
     };
 }
 
+/// 备用信号栈的最小大小
+pub const MINSIGSTKSZ: usize = 2048;
+
+bitflags! {
+    /// sigaltstack的ss_flags
+    pub struct SigStackFlags: i32 {
+        /// 进程正在备用栈上执行
+        const SS_ONSTACK = 1;
+        /// 备用栈被禁用
+        const SS_DISABLE = 2;
+    }
+}
+
+/// 用户态传入的stack_t结构体（符合posix规范）
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PosixSigStack {
+    pub ss_sp: usize,
+    pub ss_flags: i32,
+    pub ss_size: usize,
+}
+
+/// 信号处理程序的备用栈
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/linux/sched/signal.h#579
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SigAltStack {
+    /// 备用栈的起始地址
+    pub sp: usize,
+    /// 备用栈的大小，为0表示备用栈被禁用
+    pub size: usize,
+}
+
+impl SigAltStack {
+    pub fn is_disabled(&self) -> bool {
+        return self.size == 0;
+    }
+
+    /// 备用栈的栈顶地址
+    pub fn top(&self) -> usize {
+        return self.sp + self.size;
+    }
+
+    /// 判断栈指针`sp`是否位于备用栈上
+    pub fn on_stack(&self, sp: usize) -> bool {
+        return sp > self.sp && sp - self.sp <= self.size;
+    }
+
+    /// 转换为用户态使用的stack_t结构体
+    ///
+    /// ## 参数
+    ///
+    /// - `sp` 当前用户态的栈指针，用于判断是否正在备用栈上执行
+    pub fn to_posix(&self, sp: usize) -> PosixSigStack {
+        let flags = if self.on_stack(sp) {
+            SigStackFlags::SS_ONSTACK
+        } else if self.is_disabled() {
+            SigStackFlags::SS_DISABLE
+        } else {
+            SigStackFlags::empty()
+        };
+        return PosixSigStack {
+            ss_sp: self.sp,
+            ss_flags: flags.bits(),
+            ss_size: self.size,
+        };
+    }
+}
+
+/// sigprocmask的how参数，表示如何修改信号屏蔽字
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigHow {
+    /// 将set中的信号加入屏蔽字
+    Block = 0,
+    /// 将set中的信号从屏蔽字中移除
+    Unblock = 1,
+    /// 将屏蔽字设置为set
+    SetMask = 2,
+}
+
+impl TryFrom<i32> for SigHow {
+    type Error = SystemError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(SigHow::Block),
+            1 => Ok(SigHow::Unblock),
+            2 => Ok(SigHow::SetMask),
+            _ => Err(SystemError::EINVAL),
+        }
+    }
+}
+
 /// 用户态传入的sigaction结构体（符合posix规范）
 /// 请注意，我们会在sys_sigaction函数里面将其转换成内核使用的sigaction结构体
 #[repr(C)]
@@ -308,7 +473,41 @@ impl SigInfo {
     pub fn set_sig_type(&mut self, sig_type: SigType) {
         self.sig_type = sig_type;
     }
-    /// @brief 将siginfo结构体拷贝到用户栈
+
+    pub fn sig_type(&self) -> SigType {
+        self.sig_type
+    }
+
+    /// 转换为用户态使用的siginfo_t结构体
+    pub fn to_posix(&self) -> PosixSigInfo {
+        let (pid, value, code) = match self.sig_type {
+            SigType::Kill(pid) => (pid, 0, self.sig_code as i32),
+            SigType::Rt(pid, value) => (pid, value, self.sig_code as i32),
+            // SIGCHLD的si_status与sigval位于同一位置
+            SigType::SigChild(pid, code, status, _, _) => (pid, status as u32 as u64, code),
+        };
+        let (utime, stime) = match self.sig_type {
+            SigType::SigChild(_, _, _, utime, stime) => {
+                (nsec_to_clock_t(utime), nsec_to_clock_t(stime))
+            }
+            _ => (0, 0),
+        };
+        return PosixSigInfo {
+            si_signo: self.sig_no,
+            si_errno: self.errno,
+            si_code: code,
+            _pad0: 0,
+            si_pid: pid.data() as i32,
+            // todo: 增加credit功能之后，需要填写发送者的uid
+            si_uid: 0,
+            si_value: value,
+            si_utime: utime,
+            si_stime: stime,
+            _pad1: [0; 10],
+        };
+    }
+
+    /// @brief 将siginfo结构体转换为siginfo_t的格式，并拷贝到用户栈
     /// ## 参数
     ///
     /// `to` 用户空间指针
@@ -319,24 +518,89 @@ impl SigInfo {
     /// Linux还提供了 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/signal.c#3383 用来实现
     /// kernel_siginfo 保存到 用户的 compact_siginfo 的功能，但是我们系统内还暂时没有对这两种
     /// siginfo做区分，因此暂时不需要第二个函数
-    pub fn copy_siginfo_to_user(&self, to: *mut SigInfo) -> Result<i32, SystemError> {
+    pub fn copy_siginfo_to_user(&self, to: *mut PosixSigInfo) -> Result<i32, SystemError> {
         // 验证目标地址是否为用户空间
-        let mut user_buffer = UserBufferWriter::new(to, size_of::<SigInfo>(), true)?;
+        let mut user_buffer = UserBufferWriter::new(to, size_of::<PosixSigInfo>(), true)?;
 
         let retval: Result<i32, SystemError> = Ok(0);
 
-        user_buffer.copy_one_to_user(self, 0)?;
+        user_buffer.copy_one_to_user(&self.to_posix(), 0)?;
         return retval;
     }
 }
 
+/// 用户态使用的siginfo_t结构体（符合posix规范，大小为128字节）
+///
+/// 目前只支持kill、sigqueue产生的信号以及SIGCHLD所使用的字段
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/asm-generic/siginfo.h#31
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct PosixSigInfo {
+    pub si_signo: i32,
+    pub si_errno: i32,
+    pub si_code: i32,
+    _pad0: i32,
+    /// 发送者的pid
+    pub si_pid: i32,
+    /// 发送者的uid
+    pub si_uid: u32,
+    /// sigqueue携带的数据（union sigval）。SIGCHLD的si_status位于它的低32位
+    pub si_value: u64,
+    /// SIGCHLD：子进程在用户态消耗的cpu时间（以USER_HZ为单位）
+    pub si_utime: i64,
+    /// SIGCHLD：子进程在内核态消耗的cpu时间（以USER_HZ为单位）
+    pub si_stime: i64,
+    _pad1: [u64; 10],
+}
+
+impl PosixSigInfo {
+    /// 所有字段都为0的siginfo
+    pub const fn zeroed() -> Self {
+        Self {
+            si_signo: 0,
+            si_errno: 0,
+            si_code: 0,
+            _pad0: 0,
+            si_pid: 0,
+            si_uid: 0,
+            si_value: 0,
+            si_utime: 0,
+            si_stime: 0,
+            _pad1: [0; 10],
+        }
+    }
+
+    /// 构造描述子进程状态变化的siginfo，用于waitid返回给用户
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 子进程的pid
+    /// - `code` : 子进程状态变化的原因（CLD_EXITED、CLD_STOPPED等）
+    /// - `status` : 子进程的退出码，或者导致状态变化的信号
+    pub fn child_status(pid: Pid, code: i32, status: i32) -> Self {
+        let mut info = Self::zeroed();
+        info.si_signo = Signal::SIGCHLD as i32;
+        info.si_code = code;
+        info.si_pid = pid.data() as i32;
+        // SIGCHLD的si_status与sigval位于同一位置
+        info.si_value = status as u32 as u64;
+        return info;
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum SigType {
+    /// kill产生的信号，记录了发送者的pid
     Kill(Pid),
+    /// sigqueue产生的信号，记录了发送者的pid以及携带的数据
+    Rt(Pid, u64),
+    /// 子进程状态变化时发送给父进程的SIGCHLD，记录了子进程的pid、
+    /// 状态变化的原因（CLD_STOPPED等）、子进程的退出码或者导致状态变化的信号，
+    /// 以及子进程在用户态、内核态消耗的cpu时间（纳秒）
+    SigChild(Pid, i32, i32, u64, u64),
     // 后续完善下列中的具体字段
     // Timer,
-    // Rt,
-    // SigChild,
     // SigFault,
     // SigPoll,
     // SigSys,
@@ -388,7 +652,7 @@ impl SigPending {
     pub fn next_signal(&self, sig_mask: &SigSet) -> Signal {
         let mut sig = Signal::INVALID;
 
-        let s = self.signal();
+        let s = self.pending_set();
         let m = *sig_mask;
         m.is_empty();
         // 获取第一个待处理的信号的号码
@@ -447,8 +711,50 @@ impl SigPending {
     /// @brief 从sigpending中删除mask中被置位的信号。也就是说，比如mask的第1位被置为1,那么就从sigqueue中删除所有signum为2的信号的信息。
     pub fn flush_by_mask(&mut self, mask: &SigSet) {
         // 定义过滤器，从sigqueue中删除mask中被置位的信号
-        let filter = |x: &SigInfo| !mask.contains(SigSet::from_bits_truncate(x.sig_no as u64));
+        let filter = |x: &SigInfo| !mask.contains(SigSet::from_bits_truncate(1 << (x.sig_no - 1)));
         self.queue.q.retain(filter);
+        self.signal.remove(*mask);
+    }
+
+    /// 将队列中没有被屏蔽的信号标记为待处理
+    ///
+    /// 信号被屏蔽时，它的siginfo仍然会被加入队列，但是不会被标记为待处理。
+    /// 因此在信号被解除屏蔽之后，需要调用本函数，使得这些信号能够被处理
+    pub fn recalc(&mut self, blocked: &SigSet) {
+        let pending = self.pending_set();
+        self.signal.insert(pending & !*blocked);
+    }
+
+    /// 把队列中所有没有被`blocked`屏蔽的信号转移到`dst`的队列中
+    ///
+    /// 用于把线程组共享的待处理信号转交给一个不再屏蔽它们的线程。
+    /// 如果`dst`中已经有相同的非实时信号，则丢弃这个信号
+    pub fn move_unblocked(&mut self, dst: &mut SigPending, blocked: &SigSet) {
+        let mut moved = Vec::new();
+        self.queue.q.retain(|info| {
+            if blocked.contains(SigSet::from_bits_truncate(1 << (info.sig_no - 1))) {
+                return true;
+            }
+            moved.push(*info);
+            false
+        });
+        self.signal &= *blocked;
+
+        for info in moved {
+            let sig = Signal::from(info.sig_no);
+            if sig.is_rt_signal() || dst.queue.find(sig).0.is_none() {
+                dst.queue.q.push(info);
+            }
+        }
+    }
+
+    /// 获取所有待处理的信号，包括在被屏蔽期间进入队列、尚未被标记为待处理的信号
+    pub fn pending_set(&self) -> SigSet {
+        let mut set = self.signal;
+        for info in self.queue.q.iter() {
+            set.insert(SigSet::from_bits_truncate(1 << (info.sig_no - 1)));
+        }
+        return set;
     }
 }
 
@@ -0,0 +1,316 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+use system_error::SystemError;
+
+use crate::{
+    arch::{
+        ipc::signal::{SigSet, Signal},
+        CurrentIrqArch,
+    },
+    driver::base::device::device_number::DeviceNumber,
+    exception::InterruptArch,
+    filesystem::{
+        anonfs::anon_inode_fs,
+        vfs::{
+            core::generate_inode_id, file::FileMode, syscall::ModeType, FilePrivateData,
+            FileSystem, FileType, IndexNode, Metadata,
+        },
+    },
+    libs::spinlock::{SpinLock, SpinLockGuard},
+    net::event_poll::{EPollEventType, EPollItem, EventPoll},
+    process::{ProcessControlBlock, ProcessManager},
+    sched::{schedule, SchedMode},
+    syscall::user_access::UserBufferReader,
+    time::PosixTimeSpec,
+};
+
+use super::signal_types::{SigInfo, SIG_KERNEL_ONLY_MASK};
+
+/// 用户态通过read从signalfd读出的结构体（符合linux的signalfd_siginfo，大小为128字节）
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/signalfd.h#23
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PosixSignalfdSiginfo {
+    pub ssi_signo: u32,
+    pub ssi_errno: i32,
+    pub ssi_code: i32,
+    pub ssi_pid: u32,
+    pub ssi_uid: u32,
+    pub ssi_fd: i32,
+    pub ssi_tid: u32,
+    pub ssi_band: u32,
+    pub ssi_overrun: u32,
+    pub ssi_trapno: u32,
+    pub ssi_status: i32,
+    pub ssi_int: i32,
+    pub ssi_ptr: u64,
+    pub ssi_utime: u64,
+    pub ssi_stime: u64,
+    pub ssi_addr: u64,
+    pub ssi_addr_lsb: u16,
+    _pad2: u16,
+    pub ssi_syscall: i32,
+    pub ssi_call_addr: u64,
+    pub ssi_arch: u32,
+    _pad: [u8; 28],
+}
+
+impl From<SigInfo> for PosixSignalfdSiginfo {
+    fn from(info: SigInfo) -> Self {
+        let info = info.to_posix();
+        Self {
+            ssi_signo: info.si_signo as u32,
+            ssi_errno: info.si_errno,
+            ssi_code: info.si_code,
+            ssi_pid: info.si_pid as u32,
+            ssi_uid: info.si_uid,
+            ssi_fd: 0,
+            ssi_tid: 0,
+            ssi_band: 0,
+            ssi_overrun: 0,
+            ssi_trapno: 0,
+            ssi_status: 0,
+            ssi_int: info.si_value as i32,
+            ssi_ptr: info.si_value,
+            ssi_utime: 0,
+            ssi_stime: 0,
+            ssi_addr: 0,
+            ssi_addr_lsb: 0,
+            _pad2: 0,
+            ssi_syscall: 0,
+            ssi_call_addr: 0,
+            ssi_arch: 0,
+            _pad: [0; 28],
+        }
+    }
+}
+
+bitflags! {
+    /// signalfd4的flags参数
+    pub struct SignalFdFlags: u32 {
+        const SFD_NONBLOCK = FileMode::O_NONBLOCK.bits();
+        const SFD_CLOEXEC = FileMode::O_CLOEXEC.bits();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SignalFdPrivateData {
+    mode: FileMode,
+}
+
+impl SignalFdPrivateData {
+    pub fn set_mode(&mut self, mode: FileMode) {
+        self.mode = mode;
+    }
+}
+
+/// signalfd对应的inode
+///
+/// 读取signalfd时，会从读者自己的待处理信号中取出属于mask的信号。
+/// 这些信号需要先通过sigprocmask屏蔽，否则它们会在返回用户态时被正常地处理掉。
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/fs/signalfd.c
+#[derive(Debug)]
+pub struct SignalFdInode {
+    /// signalfd所关心的信号
+    mask: SpinLock<SigSet>,
+    /// INode 元数据
+    metadata: Metadata,
+}
+
+impl SignalFdInode {
+    pub fn new(mask: SigSet) -> Arc<Self> {
+        return Arc::new(Self {
+            mask: SpinLock::new(mask - SIG_KERNEL_ONLY_MASK),
+            metadata: Metadata {
+                dev_id: 0,
+                inode_id: generate_inode_id(),
+                size: 0,
+                blk_size: 0,
+                blocks: 0,
+                atime: PosixTimeSpec::default(),
+                mtime: PosixTimeSpec::default(),
+                ctime: PosixTimeSpec::default(),
+                file_type: FileType::File,
+                mode: ModeType::from_bits_truncate(0o600),
+                nlinks: 1,
+                uid: 0,
+                gid: 0,
+                raw_dev: DeviceNumber::default(),
+            },
+        });
+    }
+
+    /// 修改signalfd所关心的信号。SIGKILL、SIGSTOP不能通过signalfd读取，会被忽略
+    pub fn set_mask(&self, mask: SigSet) {
+        *self.mask.lock_irqsave() = mask - SIG_KERNEL_ONLY_MASK;
+    }
+
+    /// 判断当前进程是否有属于mask的待处理信号
+    fn has_pending(&self, pcb: &Arc<ProcessControlBlock>) -> bool {
+        let mask = *self.mask.lock_irqsave();
+        let sig_info = pcb.sig_info_irqsave();
+        let pending =
+            sig_info.sig_pending().pending_set() | sig_info.sig_shared_pending().pending_set();
+        return !(pending & mask).is_empty();
+    }
+}
+
+/// 通知正在等待信号的signalfd，有新的信号到来
+///
+/// ## 参数
+///
+/// - `pcb` 接收到信号的进程
+pub fn signalfd_notify(pcb: &Arc<ProcessControlBlock>) {
+    pcb.signalfd_wait().wakeup_all(None);
+
+    // 移除那些epoll已经被关闭的epitem
+    pcb.signalfd_epitems()
+        .lock_irqsave()
+        .retain(|epitem| epitem.epoll().strong_count() > 0);
+
+    let pollflag = EPollEventType::EPOLLIN | EPollEventType::EPOLLRDNORM;
+    let cnt = pcb.signalfd_epitems().lock_irqsave().len();
+    for _ in 0..cnt {
+        let _ = EventPoll::wakeup_epoll(pcb.signalfd_epitems(), pollflag);
+    }
+}
+
+impl IndexNode for SignalFdInode {
+    fn as_any_ref(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn open(
+        &self,
+        mut data: SpinLockGuard<FilePrivateData>,
+        mode: &FileMode,
+    ) -> Result<(), SystemError> {
+        *data = FilePrivateData::SignalFd(SignalFdPrivateData { mode: *mode });
+        return Ok(());
+    }
+
+    fn close(&self, _data: SpinLockGuard<FilePrivateData>) -> Result<(), SystemError> {
+        return Ok(());
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        return Ok(self.metadata.clone());
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        return anon_inode_fs();
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        return Err(SystemError::ENOTDIR);
+    }
+
+    /// 从当前进程的待处理信号中取出属于mask的信号，每个信号对应一个signalfd_siginfo
+    fn read_at(
+        &self,
+        _offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        data: SpinLockGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let mode = if let FilePrivateData::SignalFd(pdata) = &*data {
+            pdata.mode
+        } else {
+            return Err(SystemError::EBADF);
+        };
+        drop(data);
+
+        let size = core::mem::size_of::<PosixSignalfdSiginfo>();
+        let count = core::cmp::min(len, buf.len()) / size;
+        if count == 0 {
+            return Err(SystemError::EINVAL);
+        }
+
+        let pcb = ProcessManager::current_pcb();
+        let mut nr = 0;
+        while nr < count {
+            let mask = *self.mask.lock_irqsave();
+            let (sig, info) = pcb.sig_info_mut().dequeue_signal(&!mask);
+            if sig == Signal::INVALID {
+                // 已经读到了信号，或者不允许阻塞，则直接返回
+                if nr > 0 {
+                    break;
+                }
+                if mode.contains(FileMode::O_NONBLOCK) {
+                    return Err(SystemError::EAGAIN_OR_EWOULDBLOCK);
+                }
+                // 有其他没有被屏蔽的信号需要处理
+                let sig_info = pcb.sig_info_irqsave();
+                if !(sig_info.sig_pending().pending_set() & !*sig_info.sig_block()).is_empty() {
+                    return Err(SystemError::ERESTARTSYS);
+                }
+                drop(sig_info);
+
+                unsafe {
+                    let irq_guard = CurrentIrqArch::save_and_disable_irq();
+                    pcb.signalfd_wait().sleep_without_schedule();
+                    drop(irq_guard);
+                }
+                schedule(SchedMode::SM_NONE);
+                continue;
+            }
+
+            let ssi = PosixSignalfdSiginfo::from(info.unwrap());
+            let bytes = unsafe {
+                core::slice::from_raw_parts(&ssi as *const PosixSignalfdSiginfo as *const u8, size)
+            };
+            buf[nr * size..(nr + 1) * size].copy_from_slice(bytes);
+            nr += 1;
+        }
+
+        return Ok(nr * size);
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &[u8],
+        _data: SpinLockGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        return Err(SystemError::EINVAL);
+    }
+
+    fn ioctl(
+        &self,
+        cmd: u32,
+        data: usize,
+        _private_data: &FilePrivateData,
+    ) -> Result<usize, SystemError> {
+        match cmd {
+            EventPoll::ADD_EPOLLITEM => {
+                let _ = UserBufferReader::new(
+                    data as *const Arc<EPollItem>,
+                    core::mem::size_of::<Arc<EPollItem>>(),
+                    false,
+                )?;
+                let epitem = unsafe { &*(data as *const Arc<EPollItem>) };
+                ProcessManager::current_pcb()
+                    .signalfd_epitems()
+                    .lock_irqsave()
+                    .push_back(epitem.clone());
+                return Ok(0);
+            }
+            _ => return Err(SystemError::ENOTTY),
+        }
+    }
+
+    fn poll(&self, _private_data: &FilePrivateData) -> Result<usize, SystemError> {
+        let mut events = EPollEventType::empty();
+        if self.has_pending(&ProcessManager::current_pcb()) {
+            events.insert(EPollEventType::EPOLLIN | EPollEventType::EPOLLRDNORM);
+        }
+        return Ok(events.bits() as usize);
+    }
+}
+
+/// 检查inode是否为signalfd，如果是，则返回它
+pub fn as_signalfd(inode: &Arc<dyn IndexNode>) -> Option<&SignalFdInode> {
+    return inode.as_any_ref().downcast_ref::<SignalFdInode>();
+}
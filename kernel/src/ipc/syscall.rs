@@ -23,9 +23,9 @@ use crate::{
         page::{page_manager_lock_irqsave, PageFlags, PageFlushAll},
         syscall::ProtFlags,
         ucontext::{AddressSpace, VMA},
-        VirtAddr, VmFlags,
+        verify_area, VirtAddr, VmFlags,
     },
-    process::{Pid, ProcessManager},
+    process::{pidfd::as_pidfd, Pid, ProcessManager},
     syscall::{
         user_access::{UserBufferReader, UserBufferWriter},
         Syscall,
@@ -35,10 +35,12 @@ use crate::{
 use super::{
     pipe::{LockedPipeInode, PipeFsPrivateData},
     shm::{ShmCtlCmd, ShmFlags, ShmId, ShmKey},
+    signal::{pending_signals, set_current_sig_blocked},
     signal_types::{
-        SaHandlerType, SigInfo, SigType, Sigaction, SigactionType, UserSigaction, USER_SIG_DFL,
-        USER_SIG_ERR, USER_SIG_IGN,
+        PosixSigInfo, PosixSigStack, SaHandlerType, SigAltStack, SigHow, SigInfo, SigStackFlags,
+        SigType, Sigaction, SigactionType, UserSigaction, USER_SIG_DFL, USER_SIG_ERR, USER_SIG_IGN,
     },
+    signalfd::{as_signalfd, SignalFdFlags, SignalFdInode},
 };
 
 impl Syscall {
@@ -92,28 +94,422 @@ impl Syscall {
         Ok(0)
     }
 
-    pub fn kill(pid: Pid, sig: c_int) -> Result<usize, SystemError> {
-        let sig = Signal::from(sig);
-        if sig == Signal::INVALID {
+    /// 向进程或者进程组发送信号
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` 接收信号的目标：
+    ///     - `pid > 0`：发送给pid对应的进程
+    ///     - `pid == 0`：发送给调用者所在进程组中的每个进程
+    ///     - `pid == -1`：发送给调用者有权发送信号的每个进程，init进程和调用者自身除外
+    ///     - `pid < -1`：发送给进程组`-pid`中的每个进程
+    /// - `sig` 要发送的信号，为0时只检查目标是否存在，不发送信号
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL`：信号值不合法
+    /// - `ESRCH`：没有与pid相匹配的进程
+    pub fn kill(pid: i32, sig: c_int) -> Result<usize, SystemError> {
+        let sig = Self::null_or_valid_signal(sig)?;
+        let current = ProcessManager::current_pcb();
+
+        // 初始化signal info
+        let mut info = SigInfo::new(sig, 0, SigCode::User, SigType::Kill(current.pid()));
+
+        compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+        let retval = match pid {
+            i32::MIN => Err(SystemError::ESRCH),
+            -1 => sig.send_signal_to_all(Some(&mut info)),
+            0 => sig.send_signal_to_pgrp(Some(&mut info), current.basic().pgid()),
+            pid if pid < 0 => sig.send_signal_to_pgrp(Some(&mut info), Pid::new(-pid as usize)),
+            pid => sig.send_signal_info(Some(&mut info), Pid::new(pid as usize)),
+        }
+        .map(|x| x as usize);
+
+        compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+        return retval;
+    }
+
+    /// 向线程组`tgid`中的线程`tid`发送信号
+    ///
+    /// ## 参数
+    ///
+    /// - `tgid` 目标线程所在的线程组id
+    /// - `tid` 目标线程的id
+    /// - `sig` 要发送的信号，为0时只检查目标线程是否存在，不发送信号
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL`：信号值不合法，或者`tgid`、`tid`不是正数
+    /// - `ESRCH`：目标线程不存在，或者它不属于线程组`tgid`
+    pub fn tgkill(tgid: i32, tid: i32, sig: c_int) -> Result<usize, SystemError> {
+        if tgid <= 0 {
+            return Err(SystemError::EINVAL);
+        }
+        return Self::do_tkill(Some(Pid::new(tgid as usize)), tid, sig);
+    }
+
+    /// 向线程`tid`发送信号
+    ///
+    /// 由于线程id可能被复用，推荐使用[`Syscall::tgkill`]
+    pub fn tkill(tid: i32, sig: c_int) -> Result<usize, SystemError> {
+        return Self::do_tkill(None, tid, sig);
+    }
+
+    fn do_tkill(tgid: Option<Pid>, tid: i32, sig: c_int) -> Result<usize, SystemError> {
+        if tid <= 0 {
+            return Err(SystemError::EINVAL);
+        }
+        let sig = Self::null_or_valid_signal(sig)?;
+
+        // 用户态传入的是当前pid namespace中的id
+        let tgid = tgid
+            .map(|tgid| ProcessManager::vpid_to_pid(tgid).ok_or(SystemError::ESRCH))
+            .transpose()?;
+        let tid = ProcessManager::vpid_to_pid(Pid::new(tid as usize)).ok_or(SystemError::ESRCH)?;
+
+        let mut info = SigInfo::new(
+            sig,
+            0,
+            SigCode::Tkill,
+            SigType::Kill(ProcessManager::current_pcb().pid()),
+        );
+
+        compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        let retval = sig
+            .send_signal_to_thread(Some(&mut info), tgid, tid)
+            .map(|x| x as usize);
+        compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+        return retval;
+    }
+
+    /// 把用户传入的信号值转换为[`Signal`]，0号信号会被转换为[`Signal::INVALID`]
+    fn null_or_valid_signal(sig: c_int) -> Result<Signal, SystemError> {
+        let signal = Signal::from(sig);
+        if signal == Signal::INVALID && sig != 0 {
             // 传入的signal数值不合法
             kwarn!("Not a valid signal number");
             return Err(SystemError::EINVAL);
         }
+        return Ok(signal);
+    }
 
-        // 初始化signal info
-        let mut info = SigInfo::new(sig, 0, SigCode::User, SigType::Kill(pid));
+    /// 向进程发送一个带有数据的信号（sigqueue）
+    ///
+    /// 与kill不同，同一个实时信号可以排队多次，每一次都会携带用户传入的siginfo
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` 目标进程的pid
+    /// - `sig` 要发送的信号
+    /// - `uinfo` 用户空间传入的siginfo_t
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL`：信号值不合法，或者si_code不被支持
+    /// - `EPERM`：试图向其他进程发送伪装成kill或内核产生的信号
+    /// - `ESRCH`：目标进程不存在
+    pub fn rt_sigqueueinfo(
+        pid: Pid,
+        sig: c_int,
+        uinfo: *const PosixSigInfo,
+    ) -> Result<usize, SystemError> {
+        let sig = Signal::from(sig);
+        if sig == Signal::INVALID {
+            return Err(SystemError::EINVAL);
+        }
+        // 用户态传入的是当前pid namespace中的pid
+        let pid = ProcessManager::vpid_to_pid(pid).ok_or(SystemError::ESRCH)?;
 
-        compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        let reader = UserBufferReader::new(uinfo, core::mem::size_of::<PosixSigInfo>(), true)?;
+        let uinfo = *reader.read_one_from_user::<PosixSigInfo>(0)?;
+        let mut info = Self::siginfo_from_user(sig, &uinfo, pid)?;
 
+        compiler_fence(core::sync::atomic::Ordering::SeqCst);
         let retval = sig
             .send_signal_info(Some(&mut info), pid)
             .map(|x| x as usize);
+        compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+        return retval;
+    }
+
+    /// 通过pidfd向进程发送信号
+    ///
+    /// 与kill不同，pidfd始终指向创建它时的那个进程，因此不会因为pid被复用而把信号发送给其他进程
+    ///
+    /// ## 参数
+    ///
+    /// - `pidfd` 指向目标进程的pidfd
+    /// - `sig` 要发送的信号，为0时只检查目标进程是否存在，不发送信号
+    /// - `uinfo` 用户空间传入的siginfo_t，为NULL时与kill发送的siginfo相同
+    /// - `flags` 目前必须为0
+    ///
+    /// ## 返回值
+    ///
+    /// - `EBADF`：pidfd不是一个合法的pidfd
+    /// - `EINVAL`：flags不为0，信号值不合法，或者siginfo中的信号与sig不一致
+    /// - `EPERM`：试图向其他进程发送伪装成kill或内核产生的信号
+    /// - `ESRCH`：目标进程已经退出
+    pub fn pidfd_send_signal(
+        pidfd: i32,
+        sig: c_int,
+        uinfo: *const PosixSigInfo,
+        flags: u32,
+    ) -> Result<usize, SystemError> {
+        if flags != 0 {
+            return Err(SystemError::EINVAL);
+        }
+
+        let file = ProcessManager::current_pcb()
+            .fd_table()
+            .read()
+            .get_file_by_fd(pidfd)
+            .ok_or(SystemError::EBADF)?;
+        let inode = file.inode();
+        let pidfd_inode = as_pidfd(&inode).ok_or(SystemError::EBADF)?;
+
+        let pcb = pidfd_inode.pcb().ok_or(SystemError::ESRCH)?;
+        if pidfd_inode.exited() {
+            return Err(SystemError::ESRCH);
+        }
+
+        let sig = Self::null_or_valid_signal(sig)?;
+        let mut info = if uinfo.is_null() {
+            SigInfo::new(
+                sig,
+                0,
+                SigCode::User,
+                SigType::Kill(ProcessManager::current_pcb().pid()),
+            )
+        } else {
+            let reader = UserBufferReader::new(uinfo, core::mem::size_of::<PosixSigInfo>(), true)?;
+            let uinfo = *reader.read_one_from_user::<PosixSigInfo>(0)?;
+            if uinfo.si_signo != sig as i32 {
+                return Err(SystemError::EINVAL);
+            }
+            Self::siginfo_from_user(sig, &uinfo, pcb.pid())?
+        };
 
         compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        let retval = sig
+            .send_signal_info_to_pcb(Some(&mut info), pcb)
+            .map(|x| x as usize);
+        compiler_fence(core::sync::atomic::Ordering::SeqCst);
 
         return retval;
     }
 
+    /// 根据用户传入的siginfo_t，构造发送给进程`pid`的siginfo
+    ///
+    /// 用户只能向自己发送si_code为kill、tkill或者内核产生的信号，否则返回`EPERM`
+    fn siginfo_from_user(
+        sig: Signal,
+        uinfo: &PosixSigInfo,
+        pid: Pid,
+    ) -> Result<SigInfo, SystemError> {
+        if (uinfo.si_code >= 0 || uinfo.si_code == SigCode::Tkill as i32)
+            && pid != ProcessManager::current_pcb().pid()
+        {
+            return Err(SystemError::EPERM);
+        }
+        let code = SigCode::try_from(uinfo.si_code)?;
+
+        return Ok(SigInfo::new(
+            sig,
+            uinfo.si_errno,
+            code,
+            SigType::Rt(Pid::new(uinfo.si_pid as usize), uinfo.si_value),
+        ));
+    }
+
+    /// 获取/修改当前进程的信号屏蔽字
+    ///
+    /// ## 参数
+    ///
+    /// - `how` 如何修改信号屏蔽字，见[`SigHow`]
+    /// - `new_set` 用户空间传入的信号集合，为NULL时只获取当前的信号屏蔽字
+    /// - `old_set` 用户空间传入的用来保存旧的信号屏蔽字的指针，可以为NULL
+    /// - `sigsetsize` 用户空间的sigset_t的大小，必须与内核的SigSet大小相同
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL`：how不合法，或者sigsetsize不正确
+    /// - `EFAULT`：new_set或old_set指向的地址不合法
+    ///
+    /// ## 注意
+    ///
+    /// SIGKILL和SIGSTOP不能被屏蔽，它们会被从new_set中静默地移除
+    pub fn rt_sigprocmask(
+        how: i32,
+        new_set: *const SigSet,
+        old_set: *mut SigSet,
+        sigsetsize: usize,
+    ) -> Result<usize, SystemError> {
+        if sigsetsize != core::mem::size_of::<SigSet>() {
+            return Err(SystemError::EINVAL);
+        }
+
+        let pcb = ProcessManager::current_pcb();
+        let old_mask = *pcb.sig_info_irqsave().sig_block();
+
+        if !new_set.is_null() {
+            let reader = UserBufferReader::new(new_set, core::mem::size_of::<SigSet>(), true)?;
+            let set = *reader.read_one_from_user::<SigSet>(0)?;
+            let mut new_mask = match SigHow::try_from(how)? {
+                SigHow::Block => old_mask | set,
+                SigHow::Unblock => old_mask & !set,
+                SigHow::SetMask => set,
+            };
+            set_current_sig_blocked(&mut new_mask);
+        }
+
+        if !old_set.is_null() {
+            let mut writer = UserBufferWriter::new(old_set, core::mem::size_of::<SigSet>(), true)?;
+            writer.copy_one_to_user(&old_mask, 0)?;
+        }
+
+        return Ok(0);
+    }
+
+    /// 获取当前线程被屏蔽、尚未处理的信号
+    ///
+    /// 包括发送给当前线程的信号（例如tgkill），以及发送给当前线程所在的线程组、尚未被任何线程取走的信号
+    ///
+    /// ## 参数
+    ///
+    /// - `set` 用户空间传入的用来保存信号集合的指针
+    /// - `sigsetsize` 用户空间的sigset_t的大小，必须与内核的SigSet大小相同
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL`：sigsetsize不正确
+    /// - `EFAULT`：set指向的地址不合法
+    pub fn rt_sigpending(set: *mut SigSet, sigsetsize: usize) -> Result<usize, SystemError> {
+        if sigsetsize != core::mem::size_of::<SigSet>() {
+            return Err(SystemError::EINVAL);
+        }
+
+        let pcb = ProcessManager::current_pcb();
+        let pending = pending_signals(&pcb) & *pcb.sig_info_irqsave().sig_block();
+
+        let mut writer = UserBufferWriter::new(set, core::mem::size_of::<SigSet>(), true)?;
+        writer.copy_one_to_user(&pending, 0)?;
+        return Ok(0);
+    }
+
+    /// 设置/获取当前进程的信号处理程序备用栈
+    ///
+    /// ## 参数
+    ///
+    /// - `ss` 用户空间传入的新的备用栈，为NULL时只获取当前的备用栈
+    /// - `old_ss` 用户空间传入的用来保存旧的备用栈的指针，可以为NULL
+    /// - `sp` 进入系统调用前，用户态的栈指针
+    ///
+    /// ## 返回值
+    ///
+    /// - `EPERM`：当前正在备用栈上执行，不能修改备用栈
+    /// - `EINVAL`：ss_flags不合法
+    /// - `ENOMEM`：备用栈的大小小于MINSIGSTKSZ
+    /// - `EFAULT`：ss或old_ss指向的地址不合法
+    pub fn sigaltstack(
+        ss: *const PosixSigStack,
+        old_ss: *mut PosixSigStack,
+        sp: usize,
+    ) -> Result<usize, SystemError> {
+        let pcb = ProcessManager::current_pcb();
+        let old = *pcb.sig_info_irqsave().sig_altstack();
+
+        if !ss.is_null() {
+            let reader = UserBufferReader::new(ss, core::mem::size_of::<PosixSigStack>(), true)?;
+            let ss = *reader.read_one_from_user::<PosixSigStack>(0)?;
+
+            if old.on_stack(sp) {
+                return Err(SystemError::EPERM);
+            }
+
+            let flags = SigStackFlags::from_bits(ss.ss_flags).ok_or(SystemError::EINVAL)?;
+            let new = if flags == SigStackFlags::SS_DISABLE {
+                SigAltStack::default()
+            } else if flags.contains(SigStackFlags::SS_DISABLE) {
+                return Err(SystemError::EINVAL);
+            } else {
+                // 为了兼容旧的程序，SS_ONSTACK被当作0处理
+                if ss.ss_size < MINSIGSTKSZ {
+                    return Err(SystemError::ENOMEM);
+                }
+                SigAltStack {
+                    sp: ss.ss_sp,
+                    size: ss.ss_size,
+                }
+            };
+            *pcb.sig_info_mut().sig_altstack_mut() = new;
+        }
+
+        if !old_ss.is_null() {
+            let mut writer =
+                UserBufferWriter::new(old_ss, core::mem::size_of::<PosixSigStack>(), true)?;
+            writer.copy_one_to_user(&old.to_posix(sp), 0)?;
+        }
+
+        return Ok(0);
+    }
+
+    /// 创建一个signalfd，或者修改已有的signalfd所关心的信号
+    ///
+    /// ## 参数
+    ///
+    /// - `fd` 为-1时创建新的signalfd，否则修改fd对应的signalfd
+    /// - `mask` 用户空间传入的信号集合，signalfd只能读取这些信号
+    /// - `sizemask` 用户空间的sigset_t的大小，必须与内核的SigSet大小相同
+    /// - `flags` SFD_NONBLOCK、SFD_CLOEXEC
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功时返回signalfd的文件描述符
+    /// - `EINVAL`：flags或sizemask不合法，或者fd不是signalfd
+    /// - `EBADF`：fd不是一个合法的文件描述符
+    pub fn signalfd4(
+        fd: i32,
+        mask: *const SigSet,
+        sizemask: usize,
+        flags: u32,
+    ) -> Result<usize, SystemError> {
+        let flags = SignalFdFlags::from_bits(flags).ok_or(SystemError::EINVAL)?;
+        if sizemask != core::mem::size_of::<SigSet>() {
+            return Err(SystemError::EINVAL);
+        }
+        let reader = UserBufferReader::new(mask, core::mem::size_of::<SigSet>(), true)?;
+        let mask = *reader.read_one_from_user::<SigSet>(0)?;
+
+        let fd_table = ProcessManager::current_pcb().fd_table();
+        if fd != -1 {
+            let file = fd_table
+                .read()
+                .get_file_by_fd(fd)
+                .ok_or(SystemError::EBADF)?;
+            let inode = file.inode();
+            as_signalfd(&inode)
+                .ok_or(SystemError::EINVAL)?
+                .set_mask(mask);
+            return Ok(fd as usize);
+        }
+
+        let mut mode = FileMode::O_RDONLY;
+        if flags.contains(SignalFdFlags::SFD_NONBLOCK) {
+            mode.insert(FileMode::O_NONBLOCK);
+        }
+        let file = File::new(SignalFdInode::new(mask), mode)?;
+        if flags.contains(SignalFdFlags::SFD_CLOEXEC) {
+            file.set_close_on_exec(true);
+        }
+        let fd = fd_table.write().alloc_fd(file, None)?;
+        return Ok(fd as usize);
+    }
+
     /// 通用信号注册函数
     ///
     /// ## 参数
@@ -121,16 +517,30 @@ impl Syscall {
     /// - `sig` 信号的值
     /// - `act` 用户空间传入的 Sigaction 指针
     /// - `old_act` 用户空间传入的用来保存旧 Sigaction 的指针
+    /// - `sigsetsize` 用户空间的sigset_t的大小，必须与内核的SigSet大小相同
     /// - `from_user` 用来标识这个函数调用是否来自用户空间
     ///
-    /// @return int 错误码
+    /// ## 返回值
+    ///
+    /// - `EINVAL`：信号值不合法、试图修改SIGKILL/SIGSTOP的处理方式，或者sigsetsize不正确
+    /// - `EFAULT`：act或old_act指向的地址不合法
     #[no_mangle]
     pub fn sigaction(
         sig: c_int,
         new_act: usize,
         old_act: usize,
+        sigsetsize: usize,
         from_user: bool,
     ) -> Result<usize, SystemError> {
+        if sigsetsize != core::mem::size_of::<SigSet>() {
+            return Err(SystemError::EINVAL);
+        }
+        let sig = Signal::from(sig);
+        // 如果给出的信号值不合法
+        if sig == Signal::INVALID {
+            return Err(SystemError::EINVAL);
+        }
+
         // 请注意：用户态传进来的user_sigaction结构体类型，请注意，这个结构体与内核实际的不一样
         let act: *mut UserSigaction = new_act as *mut UserSigaction;
         let old_act = old_act as *mut UserSigaction;
@@ -139,7 +549,7 @@ impl Syscall {
         // 如果传入的，新的sigaction不为空
         if !act.is_null() {
             // 如果参数的范围不在用户空间，则返回错误
-            let r = UserBufferWriter::new(act, core::mem::size_of::<Sigaction>(), from_user);
+            let r = UserBufferReader::new(act, core::mem::size_of::<UserSigaction>(), from_user);
             if r.is_err() {
                 return Err(SystemError::EFAULT);
             }
@@ -179,7 +589,7 @@ impl Syscall {
                 new_ka.flags_mut().insert(SigFlags::SA_RESTORER);
             } else if new_ka.action().is_customized() {
                 kerror!(
-                "pid:{:?}: in sys_sigaction: User must manually sprcify a sa_restorer for signal {}.",
+                "pid:{:?}: in sys_sigaction: User must manually sprcify a sa_restorer for signal {:?}.",
                 ProcessManager::current_pcb().pid(),
                 sig
             );
@@ -188,12 +598,6 @@ impl Syscall {
             *new_ka.mask_mut() = mask;
         }
 
-        let sig = Signal::from(sig);
-        // 如果给出的信号值不合法
-        if sig == Signal::INVALID {
-            return Err(SystemError::EINVAL);
-        }
-
         let retval = super::signal::do_sigaction(
             sig,
             if act.is_null() {
@@ -341,6 +745,7 @@ impl Syscall {
 
                 // 将VMA加入到当前进程的VMA列表中
                 address_write_guard.mappings.insert_vma(vma);
+                address_write_guard.account_mapped(count.data(), count.data());
 
                 region.start().data()
             }
@@ -355,8 +760,8 @@ impl Syscall {
                     return Err(SystemError::EINVAL);
                 }
 
-                // 验证用户虚拟内存区域是否有效
-                let _ = UserBufferReader::new(vaddr.data() as *const u8, size, true)?;
+                // 验证用户虚拟内存区域是否有效。这里已经持有了地址空间的锁，不能使用access_ok
+                verify_area(vaddr, size).map_err(|_| SystemError::EFAULT)?;
 
                 // 必须在取消映射前获取到PageFlags
                 let page_flags = address_write_guard
@@ -368,7 +773,9 @@ impl Syscall {
 
                 // 取消原映射
                 let flusher: PageFlushAll<MMArch> = PageFlushAll::new();
+                let old_pages = vma.lock().region().size() / MMArch::PAGE_SIZE;
                 vma.unmap(&mut address_write_guard.user_mapper.utable, flusher);
+                address_write_guard.account_unmapped(0, old_pages);
 
                 // 将该虚拟内存区域映射到共享内存区域
                 let mut page_manager_guard = page_manager_lock_irqsave();
@@ -395,6 +802,7 @@ impl Syscall {
 
                 // 更新vma的映射状态
                 vma.lock().set_mapped(true);
+                address_write_guard.account_mapped(0, count.data());
 
                 vaddr.data()
             }
@@ -454,9 +862,11 @@ impl Syscall {
         kernel_shm.update_dtim();
         drop(shm_manager_guard);
 
-        // 取消映射
+        // 取消映射。VMA仍然保留在地址空间中，因此只减少RSS
         let flusher: PageFlushAll<MMArch> = PageFlushAll::new();
+        let pages = vma.lock().region().size() / MMArch::PAGE_SIZE;
         vma.unmap(&mut address_write_guard.user_mapper.utable, flusher);
+        address_write_guard.account_unmapped(0, pages);
 
         return Ok(0);
     }
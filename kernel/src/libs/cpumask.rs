@@ -57,6 +57,11 @@ impl CpuMask {
         self.bmp.get(cpu.data() as usize)
     }
 
+    /// 将所有cpu对应的位设置为`value`
+    pub fn set_all(&mut self, value: bool) {
+        self.bmp.set_all(value);
+    }
+
     pub fn is_empty(&self) -> bool {
         self.bmp.is_empty()
     }
@@ -31,7 +31,7 @@ use crate::{
         exec::{BinaryLoader, BinaryLoaderResult, ExecError, ExecLoadMode, ExecParam},
         ProcessFlags, ProcessManager,
     },
-    syscall::user_access::{clear_user, copy_to_user},
+    syscall::user_access::{clear_user_locked, copy_to_user_locked},
 };
 
 use super::rwlock::RwLockWriteGuard;
@@ -272,6 +272,7 @@ impl ElfLoader {
 
             // 加载文件到内存
             self.do_load_file(
+                user_vm_guard,
                 map_addr + beginning_page_offset,
                 seg_in_file_size,
                 file_offset,
@@ -297,6 +298,7 @@ impl ElfLoader {
 
             // 加载文件到内存
             self.do_load_file(
+                user_vm_guard,
                 map_addr + beginning_page_offset,
                 seg_in_file_size,
                 file_offset,
@@ -319,12 +321,14 @@ impl ElfLoader {
     ///
     /// ## 参数
     ///
+    /// - `user_vm_guard`：用户地址空间（必须是当前的地址空间）
     /// - `vaddr`：要加载到的虚拟地址
     /// - `size`：要加载的大小
     /// - `offset_in_file`：在文件内的偏移量
     /// - `param`：执行参数
     fn do_load_file(
         &self,
+        user_vm_guard: &mut RwLockWriteGuard<'_, InnerAddressSpace>,
         mut vaddr: VirtAddr,
         size: usize,
         offset_in_file: usize,
@@ -346,7 +350,8 @@ impl ElfLoader {
             file.read(read_size, &mut buf[..read_size])?;
             // kdebug!("copy_to_user: vaddr={:?}, read_size = {read_size}", vaddr);
             unsafe {
-                copy_to_user(vaddr, &buf[..read_size]).map_err(|_| SystemError::EFAULT)?;
+                copy_to_user_locked(user_vm_guard, vaddr, &buf[..read_size])
+                    .map_err(|_| SystemError::EFAULT)?;
             }
 
             vaddr += read_size;
@@ -356,11 +361,16 @@ impl ElfLoader {
     }
 
     /// 我们需要显式的把数据段之后剩余的内存页都清零。
-    fn pad_zero(&self, elf_bss: VirtAddr) -> Result<(), SystemError> {
+    fn pad_zero(
+        &self,
+        user_vm_guard: &mut RwLockWriteGuard<'_, InnerAddressSpace>,
+        elf_bss: VirtAddr,
+    ) -> Result<(), SystemError> {
         let nbyte = self.elf_page_offset(elf_bss);
         if nbyte > 0 {
             let nbyte = CurrentElfArch::ELF_PAGE_SIZE - nbyte;
-            unsafe { clear_user(elf_bss, nbyte).map_err(|_| SystemError::EFAULT) }?;
+            unsafe { clear_user_locked(user_vm_guard, elf_bss, nbyte) }
+                .map_err(|_| SystemError::EFAULT)?;
         }
         return Ok(());
     }
@@ -648,7 +658,7 @@ impl BinaryLoader for ElfLoader {
                     unsafe {
                         // This bss-zeroing can fail if the ELF file specifies odd protections.
                         // So we don't check the return value.
-                        clear_user(elf_bss + load_bias, nbyte).ok();
+                        clear_user_locked(&mut user_vm, elf_bss + load_bias, nbyte).ok();
                     }
                 }
             }
@@ -815,7 +825,7 @@ impl BinaryLoader for ElfLoader {
         // );
         self.set_elf_brk(&mut user_vm, elf_bss, elf_brk, bss_prot_flags)?;
 
-        if likely(elf_bss != elf_brk) && unlikely(self.pad_zero(elf_bss).is_err()) {
+        if likely(elf_bss != elf_brk) && unlikely(self.pad_zero(&mut user_vm, elf_bss).is_err()) {
             // kdebug!("elf_bss = {elf_bss:?}, elf_brk = {elf_brk:?}");
             return Err(ExecError::BadAddress(Some(elf_bss)));
         }
@@ -6,7 +6,7 @@ use core::hash::{Hash, Hasher};
 use core::{
     intrinsics::{likely, unlikely},
     mem,
-    sync::atomic::AtomicU64,
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
 };
 
 use hashbrown::HashMap;
@@ -19,7 +19,7 @@ use crate::{
     mm::{ucontext::AddressSpace, MemoryManagementArch, VirtAddr},
     process::{Pid, ProcessControlBlock, ProcessManager},
     sched::{schedule, SchedMode},
-    syscall::user_access::{UserBufferReader, UserBufferWriter},
+    syscall::user_access::{access_ok, UserBufferReader, UserBufferWriter},
     time::{
         timer::{next_n_us_timer_jiffies, Timer, WakeUpHelper},
         PosixTimeSpec,
@@ -644,11 +644,20 @@ impl Futex {
 //用于指示在处理robust list是最多处理多少个条目
 const ROBUST_LIST_LIMIT: isize = 2048;
 
+/// 用户空间的robust list节点，与`struct robust_list`的布局相同
+#[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct RobustList {
     next: VirtAddr,
 }
 
+/// 用户空间的robust list头部，与`struct robust_list_head`的布局相同
+///
+/// 头部位于用户空间，用户程序（例如libc）在加锁、解锁时会不断修改其中的`list`与`list_op_pending`，
+/// 因此内核只记录头部的用户空间地址，在线程退出时才从用户空间读取它的内容。
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/futex.h#98
+#[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct RobustListHead {
     list: RobustList,
@@ -659,45 +668,43 @@ pub struct RobustListHead {
 impl RobustListHead {
     /// # 获得futex的用户空间地址
     pub fn futex_uaddr(&self, entry: VirtAddr) -> VirtAddr {
-        return VirtAddr::new(entry.data() + self.futex_offset as usize);
+        return VirtAddr::new(entry.data().wrapping_add(self.futex_offset as usize));
     }
 
     /// #获得list_op_peding的用户空间地址
     pub fn pending_uaddr(&self) -> Option<VirtAddr> {
-        if self.list_op_pending.is_null() {
+        let pending = robust_entry(self.list_op_pending);
+        if pending.is_null() {
             return None;
         } else {
-            return Some(self.futex_uaddr(self.list_op_pending));
+            return Some(self.futex_uaddr(pending));
         }
     }
 
     /// # 在内核注册robust list
     /// ## 参数
     /// - head_uaddr：robust list head用户空间地址
-    /// - len：robust list head的长度    
+    /// - len：robust list head的长度
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/futex/syscalls.c#28
     pub fn set_robust_list(head_uaddr: VirtAddr, len: usize) -> Result<usize, SystemError> {
-        let robust_list_head_len = mem::size_of::<&RobustListHead>();
-        if unlikely(len != robust_list_head_len) {
+        if unlikely(len != mem::size_of::<RobustListHead>()) {
             return Err(SystemError::EINVAL);
         }
 
-        let user_buffer_reader = UserBufferReader::new(
-            head_uaddr.as_ptr::<RobustListHead>(),
-            mem::size_of::<RobustListHead>(),
-            true,
-        )?;
-        let robust_list_head = *user_buffer_reader.read_one_from_user::<RobustListHead>(0)?;
-
-        // 向内核注册robust list
-        ProcessManager::current_pcb().set_robust_list(Some(robust_list_head));
+        // 向内核注册robust list，只记录头部的地址，在线程退出时再读取它的内容
+        ProcessManager::current_pcb().set_robust_list(Some(head_uaddr));
 
         return Ok(0);
     }
 
     /// # 获取robust list head到用户空间
     /// ## 参数
-    /// - pid：当前进程/线程的pid
-    /// -
+    /// - pid：目标线程的pid，为0时表示当前线程
+    /// - head_uaddr：用于保存robust list head地址的用户空间指针
+    /// - len_ptr_uaddr：用于保存robust list head长度的用户空间指针
+    ///
+    /// 如果目标线程没有注册robust list，则返回的头部地址为NULL
     pub fn get_robust_list(
         pid: usize,
         head_uaddr: VirtAddr,
@@ -707,7 +714,9 @@ impl RobustListHead {
         let pcb: Arc<ProcessControlBlock> = if pid == 0 {
             ProcessManager::current_pcb()
         } else {
-            ProcessManager::find(Pid::new(pid)).ok_or(SystemError::ESRCH)?
+            ProcessManager::vpid_to_pid(Pid::new(pid))
+                .and_then(ProcessManager::find)
+                .ok_or(SystemError::ESRCH)?
         };
 
         // TODO: 检查当前进程是否能ptrace另一个进程
@@ -716,8 +725,8 @@ impl RobustListHead {
             return Err(SystemError::EPERM);
         }
 
-        //获取当前线程的robust list head 和 长度
-        let robust_list_head = (*pcb.get_robust_list()).ok_or(SystemError::EINVAL)?;
+        //获取目标线程的robust list head的地址
+        let robust_list_head = pcb.get_robust_list().unwrap_or(VirtAddr::new(0));
 
         // 将len拷贝到用户空间len_ptr
         let mut user_writer = UserBufferWriter::new(
@@ -726,114 +735,142 @@ impl RobustListHead {
             true,
         )?;
         user_writer.copy_one_to_user(&mem::size_of::<RobustListHead>(), 0)?;
-        // 将head拷贝到用户空间head
-        let mut user_writer = UserBufferWriter::new(
-            head_uaddr.as_ptr::<RobustListHead>(),
-            mem::size_of::<RobustListHead>(),
-            true,
-        )?;
-        user_writer.copy_one_to_user(&robust_list_head, 0)?;
+        // 将head的地址拷贝到用户空间head
+        let mut user_writer =
+            UserBufferWriter::new(head_uaddr.as_ptr::<usize>(), mem::size_of::<usize>(), true)?;
+        user_writer.copy_one_to_user(&robust_list_head.data(), 0)?;
 
         return Ok(0);
     }
 
     /// # 进程/线程退出时清理工作
+    ///
+    /// 遍历线程的robust list，将线程仍然持有的futex标记为FUTEX_OWNER_DIED，并唤醒等待者。
+    /// 只能由即将退出的线程自己调用，因为需要访问它的地址空间。
+    ///
     /// ## 参数
-    /// - current：当前进程/线程的pcb
-    /// - pid：当前进程/线程的pid
+    /// - pcb：当前进程/线程的pcb
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/futex/core.c#775
     pub fn exit_robust_list(pcb: Arc<ProcessControlBlock>) {
-        //指向当前进程的robust list头部的指针
-        let head = match *pcb.get_robust_list() {
-            Some(rl) => rl,
+        let head_uaddr = match pcb.get_robust_list() {
+            Some(head_uaddr) => head_uaddr,
             None => {
                 return;
             }
         };
+        pcb.set_robust_list(None);
+
+        // 读取用户空间中robust list头部的最新内容，用户可能已经解除了它的映射，此时直接忽略
+        let head = match UserBufferReader::new(
+            head_uaddr.as_ptr::<RobustListHead>(),
+            mem::size_of::<RobustListHead>(),
+            true,
+        )
+        .and_then(|reader| reader.read_one_from_user::<RobustListHead>(0).copied())
+        {
+            Ok(head) => head,
+            Err(_) => {
+                return;
+            }
+        };
+
         // 遍历当前进程/线程的robust list
-        for futex_uaddr in head.futexes() {
-            let ret = Self::handle_futex_death(futex_uaddr, pcb.pid().into() as u32);
+        let pid = pcb.pid().data() as u32;
+        for (futex_uaddr, pending_op) in head.futexes(head_uaddr) {
+            let ret = Self::handle_futex_death(futex_uaddr, pid, pending_op);
             if ret.is_err() {
                 return;
             }
         }
-        pcb.set_robust_list(None);
     }
 
     /// # 返回robust list的迭代器，将robust list list_op_pending 放到最后（如果存在）
-    fn futexes(&self) -> FutexIterator<'_> {
-        return FutexIterator::new(self);
+    fn futexes(&self, head_uaddr: VirtAddr) -> FutexIterator<'_> {
+        return FutexIterator::new(self, head_uaddr);
     }
 
     /// # 处理进程即将死亡时，进程已经持有的futex，唤醒其他等待该futex的线程
     /// ## 参数
     /// - futex_uaddr：futex的用户空间地址
     /// - pid: 当前进程/线程的pid
-    fn handle_futex_death(futex_uaddr: VirtAddr, pid: u32) -> Result<usize, SystemError> {
-        let futex_val = {
-            if futex_uaddr.is_null() {
-                return Err(SystemError::EINVAL);
-            }
-            let user_buffer_reader = UserBufferReader::new(
-                futex_uaddr.as_ptr::<u32>(),
-                core::mem::size_of::<u32>(),
-                true,
-            )?;
-            *user_buffer_reader.read_one_from_user::<u32>(0)?
-        };
+    /// - pending_op: 这个futex是否来自list_op_pending，即线程死亡时正在获取或者释放它
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/futex/core.c#623
+    fn handle_futex_death(
+        futex_uaddr: VirtAddr,
+        pid: u32,
+        pending_op: bool,
+    ) -> Result<usize, SystemError> {
+        // futex字需要4字节对齐
+        if futex_uaddr.is_null() || futex_uaddr.data() & (mem::size_of::<u32>() - 1) != 0 {
+            return Err(SystemError::EINVAL);
+        }
+        access_ok(futex_uaddr, mem::size_of::<u32>(), true)?;
 
-        let mut uval = futex_val;
+        // 用户空间的其他线程可能同时在修改futex字（例如设置FUTEX_WAITERS），因此需要使用原子操作
+        let futex = unsafe { &*(futex_uaddr.data() as *const AtomicU32) };
+        let mut uval = futex.load(Ordering::SeqCst);
         loop {
+            // 线程在释放锁之后、唤醒等待者之前死亡，此时需要代替它唤醒一个等待者，
+            // 否则等待者将永远不会被唤醒
+            if pending_op && uval == 0 {
+                Self::wake_robust_waiter(futex_uaddr);
+                return Ok(0);
+            }
+
             // 该futex可能被其他进程占有
-            let owner = uval & FUTEX_TID_MASK;
-            if owner != pid {
-                break;
+            if uval & FUTEX_TID_MASK != pid {
+                return Ok(0);
             }
 
-            // 判断是否有FUTEX_WAITERS和标记FUTEX_OWNER_DIED
+            // 清除持有者，保留FUTEX_WAITERS并标记FUTEX_OWNER_DIED，
+            // 下一个获得锁的线程据此得知上一个持有者已经死亡
             let mval = (uval & FUTEX_WAITERS) | FUTEX_OWNER_DIED;
-
-            // 读取用户空间目前的futex字段，判断在此之前futex是否有被修改
-            let user_buffer_reader = UserBufferReader::new(
-                futex_uaddr.as_ptr::<u32>(),
-                core::mem::size_of::<u32>(),
-                true,
-            )?;
-            let nval = *user_buffer_reader.read_one_from_user::<u32>(0)?;
-            if nval != mval {
-                uval = nval;
-                let mut user_buffer_writer = UserBufferWriter::new(
-                    futex_uaddr.as_ptr::<u32>(),
-                    core::mem::size_of::<u32>(),
-                    true,
-                )?;
-                user_buffer_writer.copy_one_to_user(&mval, 0)?;
-                continue;
+            match futex.compare_exchange(uval, mval, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => break,
+                Err(nval) => uval = nval,
             }
+        }
 
-            // 有等待者,进行唤醒操作
-            if nval & FUTEX_WAITERS != 0 {
-                let mut flags = FutexFlag::FLAGS_MATCH_NONE;
-                flags.insert(FutexFlag::FLAGS_SHARED);
-                Futex::futex_wake(futex_uaddr, flags, 1, FUTEX_BITSET_MATCH_ANY)?;
-            }
-            break;
+        // 有等待者,进行唤醒操作
+        if uval & FUTEX_WAITERS != 0 {
+            Self::wake_robust_waiter(futex_uaddr);
         }
 
         return Ok(0);
     }
+
+    fn wake_robust_waiter(futex_uaddr: VirtAddr) {
+        let mut flags = FutexFlag::FLAGS_MATCH_NONE;
+        flags.insert(FutexFlag::FLAGS_SHARED);
+        // 没有等待者时futex_wake会返回错误，忽略即可
+        let _ = Futex::futex_wake(futex_uaddr, flags, 1, FUTEX_BITSET_MATCH_ANY);
+    }
+}
+
+/// 去掉robust list节点地址的最低位
+///
+/// 用户空间使用节点地址的最低位来标记这是一个PI futex
+#[inline(always)]
+fn robust_entry(entry: VirtAddr) -> VirtAddr {
+    return VirtAddr::new(entry.data() & !1);
 }
 
 pub struct FutexIterator<'a> {
     robust_list_head: &'a RobustListHead,
+    /// robust list头部的用户空间地址，回到这个地址时说明已经遍历完整个链表
+    head_uaddr: VirtAddr,
     entry: VirtAddr,
     count: isize,
 }
 
 impl<'a> FutexIterator<'a> {
-    pub fn new(robust_list_head: &'a RobustListHead) -> Self {
+    pub fn new(robust_list_head: &'a RobustListHead, head_uaddr: VirtAddr) -> Self {
         return Self {
             robust_list_head,
-            entry: robust_list_head.list.next,
+            head_uaddr,
+            entry: robust_entry(robust_list_head.list.next),
             count: 0,
         };
     }
@@ -844,47 +881,57 @@ impl<'a> FutexIterator<'a> {
 }
 
 impl<'a> Iterator for FutexIterator<'a> {
-    type Item = VirtAddr;
+    /// futex的用户空间地址，以及它是否来自list_op_pending
+    type Item = (VirtAddr, bool);
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.is_end() {
             return None;
         }
 
-        while self.entry.data() != &self.robust_list_head.list as *const RobustList as usize {
+        let pending = robust_entry(self.robust_list_head.list_op_pending);
+        // RobustList是RobustListHead的第一个字段，因此链表的终点就是头部的地址
+        while self.entry != self.head_uaddr {
             if self.count == ROBUST_LIST_LIMIT {
                 break;
             }
             if self.entry.is_null() {
+                self.count = -1;
                 return None;
             }
 
-            //获取futex val地址
-            let futex_uaddr = if self.entry.data() != self.robust_list_head.list_op_pending.data() {
+            //获取futex val地址，list_op_pending留到最后处理
+            let futex_uaddr = if self.entry != pending {
                 Some(self.robust_list_head.futex_uaddr(self.entry))
             } else {
                 None
             };
 
-            let user_buffer_reader = UserBufferReader::new(
+            let next_entry = UserBufferReader::new(
                 self.entry.as_ptr::<RobustList>(),
                 mem::size_of::<RobustList>(),
                 true,
             )
-            .ok()?;
-            let next_entry = user_buffer_reader
-                .read_one_from_user::<RobustList>(0)
-                .ok()?;
+            .and_then(|reader| reader.read_one_from_user::<RobustList>(0).copied());
+            let next_entry = match next_entry {
+                Ok(next_entry) => next_entry,
+                Err(_) => {
+                    self.count = -1;
+                    return None;
+                }
+            };
 
-            self.entry = next_entry.next;
+            self.entry = robust_entry(next_entry.next);
 
             self.count += 1;
 
-            if futex_uaddr.is_some() {
-                return futex_uaddr;
+            if let Some(futex_uaddr) = futex_uaddr {
+                return Some((futex_uaddr, false));
             }
         }
-        self.count -= 1;
-        self.robust_list_head.pending_uaddr()
+        self.count = -1;
+        self.robust_list_head
+            .pending_uaddr()
+            .map(|futex_uaddr| (futex_uaddr, true))
     }
 }
@@ -25,5 +25,6 @@ pub mod volatile;
 pub mod futex;
 pub mod rand;
 pub mod wait_queue;
+pub mod wait_queue_test;
 
 pub mod font;
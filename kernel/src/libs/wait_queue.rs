@@ -2,9 +2,10 @@
 use core::intrinsics::unlikely;
 
 use alloc::{collections::LinkedList, sync::Arc, vec::Vec};
+use system_error::SystemError;
 
 use crate::{
-    arch::CurrentIrqArch,
+    arch::{ipc::signal::Signal, CurrentIrqArch},
     exception::InterruptArch,
     kerror,
     process::{ProcessControlBlock, ProcessManager, ProcessState},
@@ -180,6 +181,101 @@ impl WaitQueue {
         schedule(SchedMode::SM_NONE);
     }
 
+    /// 让当前进程在等待队列上等待，直到`cond`返回true。允许被信号打断
+    ///
+    /// `cond`在持有等待队列的锁（并且关中断）的情况下被检查，若条件不成立，当前进程在释放锁之前加入等待队列。
+    /// 因此，唤醒者只要先使条件成立，再调用[`WaitQueue::wakeup`]或[`WaitQueue::wakeup_all`]，
+    /// 就不会出现“检查条件之后、睡眠之前条件成立”而导致的唤醒丢失。
+    ///
+    /// 由于`cond`在持有自旋锁的情况下执行，它不能睡眠，并且可能被执行多次。
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(())`：条件成立
+    /// - `Err(SystemError::ERESTARTSYS)`：条件成立之前，当前进程有待处理的信号
+    pub fn wait_event_interruptible<F>(&self, cond: F) -> Result<(), SystemError>
+    where
+        F: FnMut() -> bool,
+    {
+        return self.do_wait_event(cond, true, false);
+    }
+
+    /// 让当前进程在等待队列上等待，直到`cond`返回true。不允许被信号打断
+    ///
+    /// 与[`WaitQueue::wait_event_interruptible`]相同，只是不会因为信号而返回
+    pub fn wait_event_uninterruptible<F>(&self, cond: F)
+    where
+        F: FnMut() -> bool,
+    {
+        self.do_wait_event(cond, false, false).ok();
+    }
+
+    /// 让当前进程在等待队列上等待，直到`cond`返回true。只允许被致命信号（SIGKILL）打断
+    ///
+    /// 与[`WaitQueue::wait_event_uninterruptible`]相同，只是收到致命信号时返回。
+    /// 其他信号不会使等待提前结束，它们在等待结束之后照常处理
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(())`：条件成立
+    /// - `Err(SystemError::ERESTARTSYS)`：条件成立之前，当前进程收到了致命信号
+    pub fn wait_event_killable<F>(&self, cond: F) -> Result<(), SystemError>
+    where
+        F: FnMut() -> bool,
+    {
+        return self.do_wait_event(cond, false, true);
+    }
+
+    fn do_wait_event<F>(
+        &self,
+        mut cond: F,
+        interruptible: bool,
+        killable: bool,
+    ) -> Result<(), SystemError>
+    where
+        F: FnMut() -> bool,
+    {
+        before_sleep_check(0);
+        let pcb = ProcessManager::current_pcb();
+        loop {
+            let mut guard: SpinLockGuard<InnerWaitQueue> = self.0.lock_irqsave();
+            if cond() {
+                return Ok(());
+            }
+            if interruptible && pcb.sig_info_irqsave().sig_pending().has_pending() {
+                return Err(SystemError::ERESTARTSYS);
+            }
+            // 致命信号会唤醒处于任何睡眠状态的进程，因此可以不可中断地睡眠
+            if killable
+                && pcb
+                    .sig_info_irqsave()
+                    .sig_pending()
+                    .signal()
+                    .contains(Signal::SIGKILL.into())
+            {
+                return Err(SystemError::ERESTARTSYS);
+            }
+            ProcessManager::mark_sleep(interruptible).unwrap_or_else(|e| {
+                panic!("sleep error: {:?}", e);
+            });
+            guard.wait_list.push_back(pcb.clone());
+            drop(guard);
+            schedule(SchedMode::SM_NONE);
+
+            // 被信号唤醒时，当前进程仍然在队列中，需要将其移除
+            self.remove(&pcb);
+        }
+    }
+
+    /// 把指定的进程从等待队列中移除（不唤醒它）
+    fn remove(&self, pcb: &Arc<ProcessControlBlock>) {
+        let mut guard: SpinLockGuard<InnerWaitQueue> = self.0.lock_irqsave();
+        if guard.wait_list.iter().any(|p| Arc::ptr_eq(p, pcb)) {
+            let list = core::mem::take(&mut guard.wait_list);
+            guard.wait_list = list.into_iter().filter(|p| !Arc::ptr_eq(p, pcb)).collect();
+        }
+    }
+
     /// @brief 唤醒在队列中等待的第一个进程。
     /// 如果这个进程的state与给定的state进行and操作之后，结果不为0,则唤醒它。
     ///
@@ -0,0 +1,98 @@
+//! 等待队列的测试
+//!
+//! 这些测试不会被自动运行，需要时向`/sys/kernel/selftest`写入`wait_queue`来运行，
+//! 见[`run_selftest`](crate::process::selftest::run_selftest)
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::string::ToString;
+use system_error::SystemError;
+
+use crate::{
+    kerror, kinfo,
+    process::{
+        kthread::{KernelThreadClosure, KernelThreadMechanism},
+        selftest::wait_kthread_exit,
+    },
+};
+
+use super::wait_queue::WaitQueue;
+
+const ROUNDS: usize = 10000;
+
+/// 两个内核线程轮流对它加1：ping线程使它变为奇数，pong线程使它变为偶数
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+/// ping线程在这里等待pong线程
+static PING_WAIT: WaitQueue = WaitQueue::default();
+/// pong线程在这里等待ping线程
+static PONG_WAIT: WaitQueue = WaitQueue::default();
+
+/// 测试[`WaitQueue::wait_event_uninterruptible`]不会丢失唤醒
+///
+/// 这个函数会创建两个内核线程，它们轮流修改同一个计数器并唤醒对方，然后等待对方修改计数器，
+/// 总共交替[`ROUNDS`]次。若唤醒被丢失，其中一个线程会永远睡眠，等待它们退出会超时
+///
+/// ## 返回值
+///
+/// - `Ok(())`：测试通过
+/// - `Err(SystemError::EIO)`：交替结束之后，计数器或者等待队列的状态不正确
+/// - `Err(SystemError::ETIMEDOUT)`：线程没有在规定的时间内退出（唤醒被丢失）
+pub fn test_wait_queue() -> Result<(), SystemError> {
+    COUNTER.store(0, Ordering::SeqCst);
+
+    let closure = KernelThreadClosure::StaticEmptyClosure((&(pong_thread as fn() -> i32), ()));
+    let pong = KernelThreadMechanism::create_and_run(closure, "test_wait_queue_pong".to_string())
+        .ok_or(SystemError::ENOMEM)?;
+
+    let closure = KernelThreadClosure::StaticEmptyClosure((&(ping_thread as fn() -> i32), ()));
+    let ping = KernelThreadMechanism::create_and_run(closure, "test_wait_queue_ping".to_string())
+        .ok_or(SystemError::ENOMEM)?;
+
+    let r = wait_kthread_exit(&ping).and_then(|ping_code| {
+        let pong_code = wait_kthread_exit(&pong)?;
+        if ping_code != 0 || pong_code != 0 {
+            return Err(SystemError::EIO);
+        }
+        Ok(())
+    });
+
+    match r {
+        Ok(_) => kinfo!("test_wait_queue: ok ({} rounds)", ROUNDS),
+        Err(_) => kerror!("test_wait_queue: failed"),
+    }
+    return r;
+}
+
+fn ping_thread() -> i32 {
+    for i in 0..ROUNDS {
+        COUNTER.fetch_add(1, Ordering::SeqCst);
+        PONG_WAIT.wakeup_all(None);
+        PING_WAIT.wait_event_uninterruptible(|| COUNTER.load(Ordering::SeqCst) == 2 * (i + 1));
+    }
+
+    // 所有等待者都已经被唤醒并离开了队列
+    if COUNTER.load(Ordering::SeqCst) != 2 * ROUNDS
+        || PING_WAIT.len() != 0
+        || PONG_WAIT.len() != 0
+        || PING_WAIT.wakeup(None)
+    {
+        kerror!(
+            "test_wait_queue: counter is {}, waiters left: ping {}, pong {}",
+            COUNTER.load(Ordering::SeqCst),
+            PING_WAIT.len(),
+            PONG_WAIT.len()
+        );
+        return -1;
+    }
+
+    return 0;
+}
+
+fn pong_thread() -> i32 {
+    for i in 0..ROUNDS {
+        PONG_WAIT.wait_event_uninterruptible(|| COUNTER.load(Ordering::SeqCst) == 2 * i + 1);
+        COUNTER.fetch_add(1, Ordering::SeqCst);
+        PING_WAIT.wakeup_all(None);
+    }
+    return 0;
+}
@@ -1,12 +1,17 @@
 use crate::{
     driver::base::{kobject::KObject, kset::KSet},
     filesystem::{
-        sysfs::{sysfs_instance, Attribute, AttributeGroup},
+        sysfs::{
+            file::sysfs_emit_str, sysfs_instance, Attribute, AttributeGroup, SysFSOpsSupport,
+            SYSFS_ATTR_MODE_RW,
+        },
         vfs::syscall::ModeType,
     },
     init::initcall::INITCALL_CORE,
+    process::{fork::FORK_RATE_LIMIT, selftest::run_selftest},
 };
-use alloc::{string::ToString, sync::Arc};
+use alloc::{format, string::ToString, sync::Arc};
+use core::sync::atomic::Ordering;
 use system_error::SystemError;
 use unified_init::macros::unified_init;
 
@@ -50,7 +55,7 @@ impl AttributeGroup for KernelAttrGroup {
     }
 
     fn attrs(&self) -> &[&'static dyn Attribute] {
-        &[]
+        &[&AttrSelftest, &AttrForkRateLimit]
     }
 
     fn is_visible(
@@ -61,3 +66,68 @@ impl AttributeGroup for KernelAttrGroup {
         Some(attr.mode())
     }
 }
+
+/// `/sys/kernel/selftest`：写入自检的名字来运行它，测试通过时写入成功，否则写入返回错误
+///
+/// 见[`run_selftest`]
+#[derive(Debug)]
+struct AttrSelftest;
+
+impl Attribute for AttrSelftest {
+    fn mode(&self) -> ModeType {
+        ModeType::S_IWUSR
+    }
+
+    fn name(&self) -> &str {
+        "selftest"
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_STORE
+    }
+
+    fn store(&self, _kobj: Arc<dyn KObject>, buf: &[u8]) -> Result<usize, SystemError> {
+        let name = core::str::from_utf8(buf)
+            .map_err(|_| SystemError::EINVAL)?
+            .trim_end_matches('\0')
+            .trim();
+        run_selftest(name)?;
+        return Ok(buf.len());
+    }
+}
+
+/// `/sys/kernel/fork_rate_limit`：fork速率限制的阈值，为0时不限制
+///
+/// 见[`FORK_RATE_LIMIT`]
+#[derive(Debug)]
+struct AttrForkRateLimit;
+
+impl Attribute for AttrForkRateLimit {
+    fn mode(&self) -> ModeType {
+        SYSFS_ATTR_MODE_RW
+    }
+
+    fn name(&self) -> &str {
+        "fork_rate_limit"
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_STORE | SysFSOpsSupport::ATTR_SHOW
+    }
+
+    fn store(&self, _kobj: Arc<dyn KObject>, buf: &[u8]) -> Result<usize, SystemError> {
+        let limit = core::str::from_utf8(buf)
+            .map_err(|_| SystemError::EINVAL)?
+            .trim_end_matches('\0')
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| SystemError::EINVAL)?;
+        FORK_RATE_LIMIT.store(limit, Ordering::Relaxed);
+        return Ok(buf.len());
+    }
+
+    fn show(&self, _kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let limit = FORK_RATE_LIMIT.load(Ordering::Relaxed);
+        return sysfs_emit_str(buf, format!("{limit}\n").as_str());
+    }
+}
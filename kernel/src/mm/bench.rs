@@ -0,0 +1,95 @@
+//! 内存管理相关的性能测试
+//!
+//! 这些测试不会被自动运行，需要时向`/sys/kernel/selftest`写入测试的名字来运行，
+//! 例如`echo bench_copy_mm > /sys/kernel/selftest`，结果输出到内核日志中。
+//! 见[`run_selftest`](crate::process::selftest::run_selftest)
+
+use system_error::SystemError;
+
+use crate::{kinfo, time::timekeep::ktime_get_real_ns};
+
+use super::{
+    syscall::{MapFlags, ProtFlags},
+    ucontext::AddressSpace,
+    VirtAddr,
+};
+
+/// 测量拷贝一个映射了256MB内存的地址空间（即fork时的copy_mm）所需的时间
+///
+/// 这个函数会建立一个新的地址空间并映射256MB的匿名内存，然后多次拷贝这个地址空间，并输出平均耗时。
+/// 新的地址空间不是当前的地址空间，因此测量的是拷贝其他进程的地址空间的情况
+pub fn bench_copy_mm() -> Result<(), SystemError> {
+    const SIZE: usize = 256 * 1024 * 1024;
+    const ROUNDS: i64 = 8;
+
+    // try_clone要求地址空间带有用户栈
+    let address_space = AddressSpace::new(true)?;
+    address_space.write().map_anonymous(
+        VirtAddr::new(0),
+        SIZE,
+        ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+        MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS,
+        false,
+    )?;
+
+    let mut total_ns = 0;
+    for _ in 0..ROUNDS {
+        let start = ktime_get_real_ns();
+        let child = address_space.write().try_clone()?;
+        total_ns += ktime_get_real_ns() - start;
+        drop(child);
+    }
+
+    kinfo!(
+        "bench_copy_mm: copying {} MB takes {} us on average ({} rounds)",
+        SIZE / (1024 * 1024),
+        total_ns / ROUNDS / 1000,
+        ROUNDS
+    );
+    return Ok(());
+}
+
+/// 比较fork与spawn为子进程准备地址空间的耗时
+///
+/// fork需要拷贝父进程的地址空间（包括每一个VMA以及页表），而spawn创建的子进程只需要一个空的地址空间，
+/// 两者随后执行execve的开销是相同的。这个函数会建立一个映射了64MB内存的地址空间，
+/// 分别测量这两种方式的平均耗时
+pub fn bench_spawn_mm() -> Result<(), SystemError> {
+    const SIZE: usize = 64 * 1024 * 1024;
+    const ROUNDS: i64 = 8;
+
+    let address_space = AddressSpace::new(true)?;
+    address_space.write().map_anonymous(
+        VirtAddr::new(0),
+        SIZE,
+        ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+        MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS,
+        false,
+    )?;
+
+    let mut fork_ns = 0;
+    for _ in 0..ROUNDS {
+        let start = ktime_get_real_ns();
+        let child = address_space.write().try_clone()?;
+        fork_ns += ktime_get_real_ns() - start;
+        drop(child);
+    }
+
+    let mut spawn_ns = 0;
+    for _ in 0..ROUNDS {
+        let start = ktime_get_real_ns();
+        let child = AddressSpace::new(false)?;
+        spawn_ns += ktime_get_real_ns() - start;
+        drop(child);
+    }
+
+    kinfo!(
+        "bench_spawn_mm: fork takes {} us, spawn takes {} us on average ({} MB mapped, {} rounds, {}x faster)",
+        fork_ns / ROUNDS / 1000,
+        spawn_ns / ROUNDS / 1000,
+        SIZE / (1024 * 1024),
+        ROUNDS,
+        fork_ns / spawn_ns.max(1)
+    );
+    return Ok(());
+}
@@ -20,6 +20,7 @@ use self::{
 };
 
 pub mod allocator;
+pub mod bench;
 pub mod c_adapter;
 pub mod early_ioremap;
 pub mod init;
@@ -27,6 +28,7 @@ pub mod kernel_mapper;
 pub mod memblock;
 pub mod mmio_buddy;
 pub mod no_init;
+pub mod oom;
 pub mod page;
 pub mod percpu;
 pub mod syscall;
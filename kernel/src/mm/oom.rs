@@ -0,0 +1,223 @@
+//! OOM killer
+//!
+//! 物理内存耗尽时，选择一个占用内存最多的用户进程并将其杀死，以释放内存，
+//! 而不是让申请内存的地方panic。
+//!
+//! 进程的评分为它的驻留内存页数（RSS），再加上`oom_score_adj`对应的调整量：
+//! `oom_score_adj`的取值范围为[-1000, 1000]，每一个单位相当于物理内存总页数的千分之一。
+//! 内核线程、init进程以及`oom_score_adj`为[`OOM_SCORE_ADJ_MIN`]的进程不会被选中。
+//!
+//! 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/mm/oom_kill.c
+
+use alloc::sync::{Arc, Weak};
+use system_error::SystemError;
+
+use crate::{
+    arch::{
+        ipc::signal::{SigCode, Signal},
+        mm::LockedFrameAllocator,
+    },
+    ipc::signal_types::{SigInfo, SigType},
+    kerror,
+    libs::spinlock::SpinLock,
+    process::{Pid, ProcessControlBlock, ProcessFlags, ProcessManager},
+    time::{sleep::nanosleep, PosixTimeSpec},
+};
+
+use super::allocator::page_frame::FrameAllocator;
+
+/// `oom_score_adj`的最小值，设置为这个值的进程不会被OOM killer选中
+pub const OOM_SCORE_ADJ_MIN: i16 = -1000;
+/// `oom_score_adj`的最大值，设置为这个值的进程总是最先被OOM killer选中
+pub const OOM_SCORE_ADJ_MAX: i16 = 1000;
+
+/// 空闲的物理页数少于总页数的`1/OOM_FREE_RATIO`时，才认为分配失败是由于物理内存耗尽引起的
+///
+/// 地址空间中没有足够大的空闲区域时同样会返回ENOMEM，这种情况下不应该杀死进程
+const OOM_FREE_RATIO: usize = 64;
+/// 一次分配最多触发OOM killer的次数
+const OOM_MAX_RETRIES: usize = 8;
+/// 等待受害者退出的最大轮数，每一轮睡眠[`OOM_WAIT_INTERVAL_NS`]纳秒
+const OOM_WAIT_ROUNDS: usize = 100;
+const OOM_WAIT_INTERVAL_NS: i64 = 10_000_000;
+
+/// 最近一次被OOM killer杀死的进程
+///
+/// 在它释放内存之前，不会再选择其他的受害者，避免一次内存不足杀死多个进程
+static OOM_VICTIM: SpinLock<Option<Weak<ProcessControlBlock>>> = SpinLock::new(None);
+
+/// 计算进程被OOM killer选中的评分，评分越高越优先被选中
+///
+/// ## 参数
+///
+/// - `pcb` : 线程组组长的pcb
+/// - `totalpages` : 物理内存的总页数
+///
+/// ## 返回值
+///
+/// - `Some(score)` : 进程的评分
+/// - `None` : 进程不能被OOM killer选中
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/mm/oom_kill.c#201
+pub fn oom_badness(pcb: &Arc<ProcessControlBlock>, totalpages: usize) -> Option<isize> {
+    // 内核线程与init进程不能被杀死
+    if pcb.flags().contains(ProcessFlags::KTHREAD) || pcb.pid() == Pid::new(1) {
+        return None;
+    }
+
+    let adj = pcb.oom_score_adj();
+    if adj == OOM_SCORE_ADJ_MIN || pcb.flags().contains(ProcessFlags::EXITING) {
+        return None;
+    }
+
+    // 已经释放了地址空间的进程，杀死它也无法得到更多的内存
+    let vm = pcb.basic().user_vm()?;
+    let rss = vm.read_irqsave().rss().data() as isize;
+
+    return Some(rss + adj as isize * (totalpages / 1000) as isize);
+}
+
+/// 在所有的用户进程中，选择评分最高的一个
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/mm/oom_kill.c#366
+fn select_bad_process(totalpages: usize) -> Option<(Arc<ProcessControlBlock>, isize)> {
+    return ProcessManager::all_processes()
+        .into_iter()
+        .filter(|pcb| pcb.is_thread_group_leader())
+        .filter_map(|pcb| oom_badness(&pcb, totalpages).map(|score| (pcb, score)))
+        .max_by_key(|(_, score)| *score);
+}
+
+/// 判断被OOM killer杀死的进程是否已经释放了它的内存
+fn oom_victim_released(victim: &Weak<ProcessControlBlock>) -> bool {
+    return victim
+        .upgrade()
+        .map_or(true, |pcb| pcb.basic().user_vm().is_none());
+}
+
+/// 当前进程是否已经收到了SIGKILL（例如自己被OOM killer选中）
+fn current_killed() -> bool {
+    return ProcessManager::current_pcb()
+        .sig_info_irqsave()
+        .sig_pending()
+        .signal()
+        .contains(Signal::SIGKILL.into());
+}
+
+/// 物理内存是否已经接近耗尽
+fn memory_is_low() -> bool {
+    let usage = unsafe { LockedFrameAllocator.usage() };
+    return usage.free().data() < usage.total().data() / OOM_FREE_RATIO;
+}
+
+/// 物理内存耗尽时，选择一个进程并将其杀死
+///
+/// 调用者不能持有任何进程的地址空间的锁，因为计算评分时需要读取各个进程的RSS
+///
+/// ## 返回值
+///
+/// - `true` : 已经有一个进程被杀死（或者上一个受害者还没有释放内存），调用者可以在它退出之后重试
+/// - `false` : 没有可以杀死的进程，调用者只能返回ENOMEM
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/mm/oom_kill.c#1068
+pub fn out_of_memory() -> bool {
+    let mut victim_guard = OOM_VICTIM.lock_irqsave();
+    if let Some(victim) = victim_guard.as_ref() {
+        if !oom_victim_released(victim) {
+            return true;
+        }
+    }
+
+    let totalpages = unsafe { LockedFrameAllocator.usage() }.total().data();
+    let (victim, score) = match select_bad_process(totalpages) {
+        Some(r) => r,
+        None => {
+            kerror!("Out of memory and no killable processes");
+            *victim_guard = None;
+            return false;
+        }
+    };
+
+    let (total_vm, rss) = victim
+        .basic()
+        .user_vm()
+        .map(|vm| {
+            let vm = vm.read_irqsave();
+            (vm.total_vm().bytes(), vm.rss().bytes())
+        })
+        .unwrap_or_default();
+    kerror!(
+        "Out of memory: Killed process {:?} ({}) score {} total-vm:{}kB, rss:{}kB, oom_score_adj:{}",
+        victim.pid(),
+        victim.basic().name(),
+        score,
+        total_vm >> 10,
+        rss >> 10,
+        victim.oom_score_adj()
+    );
+
+    let mut info = SigInfo::new(
+        Signal::SIGKILL,
+        0,
+        SigCode::Kernel,
+        SigType::Kill(ProcessManager::current_pcb().pid()),
+    );
+    let _ = Signal::SIGKILL.send_signal_info_to_pcb(Some(&mut info), victim.clone());
+    *victim_guard = Some(Arc::downgrade(&victim));
+    return true;
+}
+
+/// 等待被OOM killer杀死的进程释放内存，最多等待[`OOM_WAIT_ROUNDS`]轮
+fn wait_for_oom_victim() {
+    for _ in 0..OOM_WAIT_ROUNDS {
+        let victim = OOM_VICTIM.lock_irqsave().clone();
+        if victim.map_or(true, |v| oom_victim_released(&v)) {
+            return;
+        }
+        nanosleep(PosixTimeSpec {
+            tv_sec: 0,
+            tv_nsec: OOM_WAIT_INTERVAL_NS,
+        })
+        .ok();
+    }
+}
+
+/// 执行一个可能因为物理内存耗尽而失败的操作。失败时触发OOM killer，等待受害者退出之后重试
+///
+/// `f`在返回之前必须释放它持有的所有锁，并且失败时不能留下部分完成的修改，因为它可能会被多次调用。
+/// 只能在可以睡眠的上下文中调用
+///
+/// ## 参数
+///
+/// - `f` : 要执行的操作，物理内存不足时返回ENOMEM
+///
+/// ## 返回值
+///
+/// `f`最后一次执行的结果。当前进程自己被选中时，直接返回ENOMEM，让它尽快退出
+pub fn retry_on_oom<T>(mut f: impl FnMut() -> Result<T, SystemError>) -> Result<T, SystemError> {
+    let mut retries = 0;
+    loop {
+        let r = f();
+        if !matches!(r, Err(SystemError::ENOMEM)) || retries >= OOM_MAX_RETRIES {
+            return r;
+        }
+        if !memory_is_low() || !out_of_memory() || current_killed() {
+            return r;
+        }
+        wait_for_oom_victim();
+        retries += 1;
+    }
+}
+
+/// 用户态的缺页由于内存不足而无法处理时调用
+///
+/// ## 返回值
+///
+/// - `true` : 已经杀死了一个进程。缺页处理程序可以直接返回，让触发缺页的指令重新执行：
+///   受害者释放内存之后缺页就能被处理；如果受害者是当前进程，它会在返回用户态时被杀死
+/// - `false` : 没有可以杀死的进程
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/mm/oom_kill.c#1141
+pub fn pagefault_out_of_memory() -> bool {
+    return out_of_memory();
+}
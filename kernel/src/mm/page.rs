@@ -6,7 +6,7 @@ use core::{
     sync::atomic::{compiler_fence, Ordering},
 };
 
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use hashbrown::{HashMap, HashSet};
 
 use crate::{
@@ -15,6 +15,7 @@ use crate::{
     ipc::shm::ShmId,
     kerror, kwarn,
     libs::spinlock::{SpinLock, SpinLockGuard},
+    smp::cpu::smp_cpu_manager,
 };
 
 use super::{
@@ -117,6 +118,11 @@ impl Page {
         self.map_count -= 1;
     }
 
+    /// 获取当前物理页的映射计数
+    pub fn map_count(&self) -> usize {
+        self.map_count
+    }
+
     /// 判断当前物理页是否能被回
     pub fn can_deallocate(&self) -> bool {
         self.map_count == 0 && self.free_when_zero
@@ -1091,6 +1097,101 @@ impl Drop for InactiveFlusher {
     }
 }
 
+/// 批量刷新TLB的刷新器
+///
+/// 批量修改页表时（例如fork时写保护父进程的页表项），如果每修改一个页面就刷新一次TLB，
+/// 并且向其他核心发送IPI，会产生大量的IPI。这个刷新器先记录下被修改的页面，
+/// 等到被drop时再统一刷新：
+///
+/// - 如果页表在当前核心上被加载，那么逐个刷新被修改的页面。被修改的页面数量超过
+///   [`TlbFlushBatch::FLUSH_ALL_THRESHOLD`]时，改为刷新整个TLB
+/// - 如果系统中有多个核心，那么只向其他核心发送一次刷新TLB的IPI
+#[derive(Debug)]
+pub struct TlbFlushBatch {
+    /// 被修改的页面，相邻的页面会被合并为同一个范围：(起始地址, 页面数量)。
+    /// 只有在第一次修改页表时才会分配内存
+    ranges: Vec<(VirtAddr, usize)>,
+    /// 被修改的页面总数
+    nr_pages: usize,
+    /// 页表是否在当前核心上被加载
+    active: bool,
+}
+
+impl TlbFlushBatch {
+    /// 被修改的页面数量超过这个值时，逐页刷新的开销将超过刷新整个TLB，因此改为刷新整个TLB
+    pub const FLUSH_ALL_THRESHOLD: usize = 33;
+
+    /// 创建一个批量刷新器
+    ///
+    /// ## 参数
+    ///
+    /// - `active`：被修改的页表是否在当前核心上被加载
+    pub fn new(active: bool) -> Self {
+        return Self {
+            ranges: Vec::new(),
+            nr_pages: 0,
+            active,
+        };
+    }
+
+    /// 记录一个被修改的页面
+    fn add_page(&mut self, virt: VirtAddr) {
+        self.nr_pages += 1;
+        // 超过阈值之后会刷新整个TLB，不再需要记录具体的页面
+        if self.nr_pages > Self::FLUSH_ALL_THRESHOLD {
+            if !self.ranges.is_empty() {
+                self.ranges = Vec::new();
+            }
+            return;
+        }
+
+        if let Some((start, count)) = self.ranges.last_mut() {
+            if *start + *count * MMArch::PAGE_SIZE == virt {
+                *count += 1;
+                return;
+            }
+        }
+        self.ranges.push((virt, 1));
+    }
+
+    /// 立即刷新TLB
+    pub fn flush(self) {
+        drop(self);
+    }
+}
+
+impl Flusher<MMArch> for TlbFlushBatch {
+    fn consume(&mut self, flush: PageFlush<MMArch>) {
+        self.add_page(flush.virt);
+        unsafe { flush.ignore() };
+    }
+}
+
+impl Drop for TlbFlushBatch {
+    fn drop(&mut self) {
+        if self.nr_pages == 0 {
+            return;
+        }
+
+        if self.active {
+            if self.nr_pages > Self::FLUSH_ALL_THRESHOLD {
+                unsafe { MMArch::invalidate_all() };
+            } else {
+                for &(start, count) in self.ranges.iter() {
+                    for i in 0..count {
+                        unsafe { MMArch::invalidate_page(start + i * MMArch::PAGE_SIZE) };
+                    }
+                }
+            }
+        }
+
+        // 其他核心上可能正在运行共享这个页表的线程，因此需要通知它们刷新TLB
+        if smp_cpu_manager().present_cpus_count() > 1 {
+            send_ipi(IpiKind::FlushTLB, IpiTarget::Other);
+        }
+    }
+}
+
 /// # 把一个地址向下对齐到页大小
 pub fn round_down_to_page_size(addr: usize) -> usize {
     addr & !(MMArch::PAGE_SIZE - 1)
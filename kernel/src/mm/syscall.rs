@@ -1,6 +1,7 @@
 use core::intrinsics::unlikely;
 
 use alloc::sync::Arc;
+use num_traits::FromPrimitive;
 use system_error::SystemError;
 
 use crate::{
@@ -14,6 +15,7 @@ use crate::{
 
 use super::{
     allocator::page_frame::{PageFrameCount, VirtPageFrame},
+    oom::retry_on_oom,
     ucontext::{AddressSpace, DEFAULT_MMAP_MIN_ADDR},
     verify_area, VirtAddr, VmFlags,
 };
@@ -74,6 +76,84 @@ bitflags! {
     }
 }
 
+/// madvise的内存使用建议
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/asm-generic/mman-common.h
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+pub enum MadviseBehavior {
+    /// no further special treatment
+    Normal = 0,
+    /// expect random page references
+    Random = 1,
+    /// expect sequential page references
+    Sequential = 2,
+    /// will need these pages
+    WillNeed = 3,
+    /// don't need these pages
+    DontNeed = 4,
+    /// free pages only if memory pressure
+    Free = 8,
+    /// remove these pages & resources
+    Remove = 9,
+    /// don't inherit across fork
+    DontFork = 10,
+    /// do inherit across fork
+    DoFork = 11,
+    /// KSM may merge identical pages
+    Mergeable = 12,
+    /// KSM may not merge identical pages
+    Unmergeable = 13,
+    /// worth backing with hugepages
+    HugePage = 14,
+    /// not worth backing with hugepages
+    NoHugePage = 15,
+    /// explicity exclude from the core dump, overrides the coredump filter bits
+    DontDump = 16,
+    /// clear the MADV_DONTDUMP flag
+    DoDump = 17,
+    /// zero memory on fork, child only
+    WipeOnFork = 18,
+    /// undo MADV_WIPEONFORK
+    KeepOnFork = 19,
+    /// deactivate these pages
+    Cold = 20,
+    /// reclaim these pages
+    PageOut = 21,
+    /// populate (prefault) page tables readable
+    PopulateRead = 22,
+    /// populate (prefault) page tables writable
+    PopulateWrite = 23,
+    /// like DONTNEED, but drop locked pages too
+    DontNeedLocked = 24,
+    /// Synchronous hugepage collapse
+    Collapse = 25,
+}
+
+impl MadviseBehavior {
+    /// 根据建议计算VMA的新标志
+    ///
+    /// ## 返回值
+    ///
+    /// 返回None表示这个建议不需要修改VMA的标志（目前内核不对这些建议做任何处理）
+    pub fn update_vm_flags(&self, vm_flags: VmFlags) -> Option<VmFlags> {
+        let vm_flags = match self {
+            MadviseBehavior::Normal => vm_flags - (VmFlags::VM_RAND_READ | VmFlags::VM_SEQ_READ),
+            MadviseBehavior::Random => (vm_flags - VmFlags::VM_SEQ_READ) | VmFlags::VM_RAND_READ,
+            MadviseBehavior::Sequential => {
+                (vm_flags - VmFlags::VM_RAND_READ) | VmFlags::VM_SEQ_READ
+            }
+            MadviseBehavior::DontFork => vm_flags | VmFlags::VM_DONTCOPY,
+            MadviseBehavior::DoFork => vm_flags - VmFlags::VM_DONTCOPY,
+            MadviseBehavior::DontDump => vm_flags | VmFlags::VM_DONTDUMP,
+            MadviseBehavior::DoDump => vm_flags - VmFlags::VM_DONTDUMP,
+            MadviseBehavior::WipeOnFork => vm_flags | VmFlags::VM_WIPEONFORK,
+            MadviseBehavior::KeepOnFork => vm_flags - VmFlags::VM_WIPEONFORK,
+            _ => return None,
+        };
+        return Some(vm_flags);
+    }
+}
+
 impl From<MapFlags> for VmFlags {
     fn from(map_flags: MapFlags) -> Self {
         let mut vm_flags = VmFlags::VM_NONE;
@@ -90,6 +170,10 @@ impl From<MapFlags> for VmFlags {
             vm_flags |= VmFlags::VM_SYNC;
         }
 
+        if map_flags.contains(MapFlags::MAP_SHARED) {
+            vm_flags |= VmFlags::VM_SHARED | VmFlags::VM_MAYSHARE;
+        }
+
         vm_flags
     }
 }
@@ -116,7 +200,8 @@ impl From<ProtFlags> for VmFlags {
 
 impl From<ShmFlags> for VmFlags {
     fn from(shm_flags: ShmFlags) -> Self {
-        let mut vm_flags = VmFlags::VM_NONE;
+        // 共享内存总是在进程间共享的
+        let mut vm_flags = VmFlags::VM_SHARED | VmFlags::VM_MAYSHARE;
 
         if shm_flags.contains(ShmFlags::SHM_RDONLY) {
             vm_flags |= VmFlags::VM_READ;
@@ -183,8 +268,8 @@ impl From<VmFlags> for ProtFlags {
 impl Syscall {
     pub fn brk(new_addr: VirtAddr) -> Result<VirtAddr, SystemError> {
         // kdebug!("brk: new_addr={:?}", new_addr);
-        let address_space = AddressSpace::current()?;
-        let mut address_space = address_space.write();
+        let current_address_space = AddressSpace::current()?;
+        let address_space = current_address_space.read();
 
         if new_addr < address_space.brk_start || new_addr >= MMArch::USER_END_VADDR {
             return Ok(address_space.brk);
@@ -192,29 +277,28 @@ impl Syscall {
         if new_addr == address_space.brk {
             return Ok(address_space.brk);
         }
+        drop(address_space);
 
-        unsafe {
-            address_space
-                .set_brk(VirtAddr::new(page_align_up(new_addr.data())))
-                .ok();
+        // 扩展失败时，brk保持不变
+        let new_brk = VirtAddr::new(page_align_up(new_addr.data()));
+        retry_on_oom(|| unsafe { current_address_space.write().set_brk(new_brk) }).ok();
 
-            return Ok(address_space.sbrk(0).unwrap());
-        }
+        return Ok(current_address_space.read().brk);
     }
 
     pub fn sbrk(incr: isize) -> Result<VirtAddr, SystemError> {
         let address_space = AddressSpace::current()?;
         assert!(address_space.read().user_mapper.utable.is_current());
-        let mut address_space = address_space.write();
-        let r = unsafe { address_space.sbrk(incr) };
 
-        return r;
+        return retry_on_oom(|| unsafe { address_space.write().sbrk(incr) });
     }
 
     /// ## mmap系统调用
     ///
     /// 该函数的实现参考了Linux内核的实现，但是并不完全相同。因为有些功能咱们还没实现
     ///
+    /// 目前只支持匿名映射。指定MAP_FIXED时，目标区域中已有的映射会被替换
+    ///
     /// ## 参数
     ///
     /// - `start_vaddr`：映射的起始地址
@@ -236,7 +320,22 @@ impl Syscall {
         _offset: usize,
     ) -> Result<usize, SystemError> {
         let map_flags = MapFlags::from_bits_truncate(map_flags as u64);
-        let prot_flags = ProtFlags::from_bits_truncate(prot_flags as u64);
+        let prot_flags = ProtFlags::from_bits(prot_flags as u64).ok_or(SystemError::EINVAL)?;
+
+        // 必须指定映射的类型（同时指定两者即MAP_SHARED_VALIDATE，按照共享映射处理）
+        if unlikely(!map_flags.intersects(MapFlags::MAP_SHARED | MapFlags::MAP_PRIVATE)) {
+            return Err(SystemError::EINVAL);
+        }
+        if unlikely(len == 0) {
+            return Err(SystemError::EINVAL);
+        }
+        // MAP_FIXED要求地址对齐到页，不能像普通的hint那样被向下对齐
+        if unlikely(
+            map_flags.contains(MapFlags::MAP_FIXED)
+                && !start_vaddr.check_aligned(MMArch::PAGE_SIZE),
+        ) {
+            return Err(SystemError::EINVAL);
+        }
 
         if start_vaddr < VirtAddr::new(DEFAULT_MMAP_MIN_ADDR)
             && map_flags.contains(MapFlags::MAP_FIXED)
@@ -259,13 +358,16 @@ impl Syscall {
             return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
         }
         let current_address_space = AddressSpace::current()?;
-        let start_page = current_address_space.write().map_anonymous(
-            start_vaddr,
-            len,
-            prot_flags,
-            map_flags,
-            true,
-        )?;
+        // 内存不足时，由OOM killer杀死一个进程来释放内存之后重试
+        let start_page = retry_on_oom(|| {
+            current_address_space.write().map_anonymous(
+                start_vaddr,
+                len,
+                prot_flags,
+                map_flags,
+                true,
+            )
+        })?;
         return Ok(start_page.virt_address().data());
     }
 
@@ -396,6 +498,10 @@ impl Syscall {
     /// - `start_vaddr`：起始地址(已经对齐到页)
     /// - `len`：长度(已经对齐到页)
     /// - `prot_flags`：保护标志
+    ///
+    /// ## 返回值
+    ///
+    /// 成功时返回0，区域中包含没有被映射的地址时返回ENOMEM
     pub fn mprotect(
         start_vaddr: VirtAddr,
         len: usize,
@@ -419,8 +525,45 @@ impl Syscall {
 
         current_address_space
             .write()
-            .mprotect(start_frame, page_count, prot_flags)
-            .map_err(|_| SystemError::EINVAL)?;
+            .mprotect(start_frame, page_count, prot_flags)?;
+        return Ok(0);
+    }
+    /// ## madvise系统调用
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/mm/madvise.c
+    ///
+    /// ## 参数
+    ///
+    /// - `start_vaddr`：起始地址(已经对齐到页)
+    /// - `len`：长度(已经对齐到页)
+    /// - `behavior`：内存使用建议，见[`MadviseBehavior`]
+    ///
+    /// ## 返回值
+    ///
+    /// 成功时返回0。区域中包含没有被映射的地址时返回ENOMEM，但已经映射的部分仍然会被设置
+    pub fn madvise(
+        start_vaddr: VirtAddr,
+        len: usize,
+        behavior: usize,
+    ) -> Result<usize, SystemError> {
+        assert!(start_vaddr.check_aligned(MMArch::PAGE_SIZE));
+        assert!(check_aligned(len, MMArch::PAGE_SIZE));
+
+        let behavior = MadviseBehavior::from_usize(behavior).ok_or(SystemError::EINVAL)?;
+        if unlikely(verify_area(start_vaddr, len).is_err()) {
+            return Err(SystemError::EINVAL);
+        }
+        if len == 0 || behavior.update_vm_flags(VmFlags::VM_NONE).is_none() {
+            return Ok(0);
+        }
+
+        let current_address_space: Arc<AddressSpace> = AddressSpace::current()?;
+        let start_frame = VirtPageFrame::new(start_vaddr);
+        let page_count = PageFrameCount::new(len / MMArch::PAGE_SIZE);
+
+        current_address_space
+            .write()
+            .madvise(start_frame, page_count, behavior)?;
         return Ok(0);
     }
 }
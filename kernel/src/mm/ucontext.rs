@@ -25,17 +25,17 @@ use crate::{
         rwlock::{RwLock, RwLockWriteGuard},
         spinlock::{SpinLock, SpinLockGuard},
     },
-    mm::page::page_manager_lock_irqsave,
+    mm::page::{page_manager_lock_irqsave, Page},
     process::ProcessManager,
-    syscall::user_access::{UserBufferReader, UserBufferWriter},
 };
 
 use super::{
     allocator::page_frame::{
-        deallocate_page_frames, PageFrameCount, PhysPageFrame, VirtPageFrame, VirtPageFrameIter,
+        deallocate_page_frames, FrameAllocator, PageFrameCount, PhysPageFrame, VirtPageFrame,
+        VirtPageFrameIter,
     },
-    page::{Flusher, InactiveFlusher, PageFlags, PageFlushAll},
-    syscall::{MapFlags, MremapFlags, ProtFlags},
+    page::{Flusher, InactiveFlusher, PageFlags, PageFlushAll, TlbFlushBatch},
+    syscall::{MadviseBehavior, MapFlags, MremapFlags, ProtFlags},
     MemoryManagementArch, PageTableKind, VirtAddr, VirtRegion, VmFlags,
 };
 
@@ -124,6 +124,13 @@ pub struct InnerAddressSpace {
     pub end_code: VirtAddr,
     pub start_data: VirtAddr,
     pub end_data: VirtAddr,
+
+    /// 地址空间中所有VMA的总页数（VSZ）
+    total_vm: usize,
+    /// 已经映射了物理页的页数（RSS）。写时复制而共享的物理页，同时计入共享它的每个地址空间
+    rss: usize,
+    /// RSS曾经达到的最大值（页数）
+    hiwater_rss: usize,
 }
 
 impl InnerAddressSpace {
@@ -141,6 +148,9 @@ impl InnerAddressSpace {
             end_code: VirtAddr(0),
             start_data: VirtAddr(0),
             end_data: VirtAddr(0),
+            total_vm: 0,
+            rss: 0,
+            hiwater_rss: 0,
         };
         if create_stack {
             // kdebug!("to create user stack.");
@@ -152,6 +162,15 @@ impl InnerAddressSpace {
 
     /// 尝试克隆当前进程的地址空间，包括这些映射都会被克隆
     ///
+    /// 每个VMA根据其类型，采用不同的拷贝方式（参见[`VMA::is_cow_mapping`]）：
+    ///
+    /// - 私有的可写映射采用写时复制：父子进程的页表项都被设置为只读，并指向同一个物理页，
+    ///   直到某一方第一次写入时，才在缺页处理中为其复制出私有的页面。
+    /// - 共享映射（MAP_SHARED、共享内存）直接共享物理页，不做写保护，因此一方的写入对另一方可见。
+    /// - 不可能被写入的私有映射（没有VM_MAYWRITE，例如只读的文件映射）同样直接共享物理页。
+    ///
+    /// 无论哪种方式，都只是增加物理页在页面管理器中的引用计数，而不会复制页面的内容。
+    ///
     /// # Returns
     ///
     /// 返回克隆后的，新的地址空间的Arc指针
@@ -161,77 +180,236 @@ impl InnerAddressSpace {
         let new_addr_space = AddressSpace::new(false)?;
         let mut new_guard = new_addr_space.write();
 
-        // 拷贝用户栈的结构体信息，但是不拷贝用户栈的内容（因为后面VMA的拷贝会拷贝用户栈的内容）
+        // 拷贝用户栈的结构体信息，但是不拷贝用户栈的内容（因为后面VMA的拷贝会共享用户栈的页面）
         unsafe {
             new_guard.user_stack = Some(self.user_stack.as_ref().unwrap().clone_info_only());
         }
-        let _current_stack_size = self.user_stack.as_ref().unwrap().stack_size();
-
-        let current_mapper = &mut self.user_mapper.utable;
 
         // 拷贝空洞
         new_guard.mappings.vm_holes = self.mappings.vm_holes.clone();
 
-        for vma in self.mappings.vmas.iter() {
-            // TODO: 增加对VMA是否为文件映射的判断，如果是的话，就跳过
+        // 拷贝堆以及代码段、数据段的位置。堆所在的VMA与其他VMA一样在下面被拷贝（写时复制），
+        // 之后子进程的brk只会修改它自己的地址空间
+        new_guard.mmap_min = self.mmap_min;
+        new_guard.elf_brk_start = self.elf_brk_start;
+        new_guard.elf_brk = self.elf_brk;
+        new_guard.brk_start = self.brk_start;
+        new_guard.brk = self.brk;
+        new_guard.start_code = self.start_code;
+        new_guard.end_code = self.end_code;
+        new_guard.start_data = self.start_data;
+        new_guard.end_data = self.end_data;
+
+        // 父进程的页表项被写保护之后，需要刷新TLB。
+        // 所有的修改都完成之后再统一刷新，避免每写保护一个页面就向其他核心发送一次IPI
+        let mut flusher = TlbFlushBatch::new(self.is_current());
 
-            let vma_guard: SpinLockGuard<'_, VMA> = vma.lock();
-            let old_flags = vma_guard.flags();
-            let tmp_flags: PageFlags<MMArch> = PageFlags::new().set_write(true);
-
-            // 分配内存页并创建新的VMA
-            let new_vma = VMA::zeroed(
-                VirtPageFrame::new(vma_guard.region.start()),
-                PageFrameCount::new(vma_guard.region.size() / MMArch::PAGE_SIZE),
-                *vma_guard.vm_flags(),
-                tmp_flags,
-                &mut new_guard.user_mapper.utable,
-                (),
-            )?;
-            new_guard.mappings.vmas.insert(new_vma.clone());
-            // kdebug!("new vma: {:x?}", new_vma);
-            let mut new_vma_guard = new_vma.lock();
-            for page in new_vma_guard.pages().map(|p| p.virt_address()) {
-                // kdebug!("page: {:x?}", page);
-                let current_frame = unsafe {
-                    MMArch::phys_2_virt(
-                        current_mapper
-                            .translate(page)
-                            .expect("VMA page not mapped")
-                            .0,
-                    )
-                }
-                .expect("Phys2Virt: vaddr overflow.")
-                .data() as *mut u8;
+        let current_mapper = &mut self.user_mapper.utable;
+        let mut page_manager_guard = page_manager_lock_irqsave();
+        // 被标记为MADV_DONTFORK而没有拷贝到子进程的页数
+        let (mut dontcopy_vm, mut dontcopy_rss) = (0, 0);
 
-                let new_frame = unsafe {
-                    MMArch::phys_2_virt(
+        for vma in self.mappings.vmas.iter() {
+            let vma_guard: SpinLockGuard<'_, VMA> = vma.lock();
+            if vma_guard.vm_flags.contains(VmFlags::VM_DONTCOPY) {
+                // 子进程中不存在这个区域，把它归还为空洞
+                let pages = vma_guard.region.size() / MMArch::PAGE_SIZE;
+                dontcopy_vm += pages;
+                dontcopy_rss += if vma_guard.mapped { pages } else { 0 };
+                new_guard.mappings.unreserve_hole(&vma_guard.region);
+                continue;
+            }
+            // 被标记为MADV_WIPEONFORK的区域，子进程得到的是全新的、清零的页面
+            let wipe = vma_guard.vm_flags.contains(VmFlags::VM_WIPEONFORK);
+            let cow = !wipe && vma_guard.is_cow_mapping();
+            let start = vma_guard.region.start();
+
+            let new_vma = LockedVMA::new(VMA {
+                region: vma_guard.region,
+                vm_flags: vma_guard.vm_flags,
+                flags: vma_guard.flags,
+                mapped: true,
+                user_address_space: None,
+                self_ref: Weak::default(),
+                provider: Provider::Allocated,
+            });
+
+            for page in vma_guard.pages().map(|p| p.virt_address()) {
+                let child = current_mapper
+                    .translate(page)
+                    .and_then(|(paddr, pte_flags)| {
+                        if wipe {
+                            // 父进程的页表项可能因为尚未处理的写时复制而被写保护，因此使用VMA的页面标志
+                            unsafe { current_mapper.allocator_mut().allocate_one() }.map(
+                                |new_paddr| {
+                                    unsafe {
+                                        let vaddr = MMArch::phys_2_virt(new_paddr).unwrap();
+                                        MMArch::write_bytes(vaddr, 0, MMArch::PAGE_SIZE);
+                                    }
+                                    (new_paddr, vma_guard.flags)
+                                },
+                            )
+                        } else if cow {
+                            // 写保护父进程的页表项，使得父进程的下一次写入也会触发写时复制
+                            if pte_flags.has_write() {
+                                let r = unsafe {
+                                    current_mapper.remap(page, pte_flags.set_write(false))
+                                }?;
+                                flusher.consume(r);
+                            }
+                            Some((paddr, pte_flags.set_write(false)))
+                        } else {
+                            Some((paddr, pte_flags))
+                        }
+                    });
+
+                let mapped = child.and_then(|(paddr, child_flags)| {
+                    let r = unsafe {
                         new_guard
                             .user_mapper
                             .utable
-                            .translate(page)
-                            .expect("VMA page not mapped")
-                            .0,
-                    )
+                            .map_phys(page, paddr, child_flags)
+                    };
+                    if r.is_none() && wipe {
+                        unsafe {
+                            deallocate_page_frames(
+                                PhysPageFrame::new(paddr),
+                                PageFrameCount::new(1),
+                                &mut page_manager_guard,
+                            )
+                        };
+                    }
+                    r.map(|r| (r, paddr))
+                });
+                let (r, paddr) = match mapped {
+                    Some(mapped) => mapped,
+                    None => {
+                        // 没有内存来分配页表（或者清零的页面）了，或者无法写保护父进程的页表项。
+                        // 只保留已经映射的部分，新的地址空间被释放时会将这些页面取消映射。
+                        // 父进程中已经被写保护的页面，会在下一次写入时通过写时复制恢复写权限
+                        if page > start {
+                            new_vma.lock().region = VirtRegion::new(start, page - start);
+                            new_guard.mappings.vmas.insert(new_vma);
+                        }
+                        flusher.flush();
+                        drop(vma_guard);
+                        drop(page_manager_guard);
+                        drop(new_guard);
+                        drop(new_addr_space);
+                        drop(irq_guard);
+                        return Err(SystemError::ENOMEM);
+                    }
+                };
+                if wipe {
+                    page_manager_guard.insert(paddr, Page::new(false));
                 }
-                .expect("Phys2Virt: vaddr overflow.")
-                .data() as *mut u8;
+                // 新的页表还没有被加载，不需要刷新TLB
+                unsafe { r.ignore() };
 
-                unsafe {
-                    // 拷贝数据
-                    new_frame.copy_from_nonoverlapping(current_frame, MMArch::PAGE_SIZE);
-                }
+                // 增加物理页的引用计数
+                page_manager_guard
+                    .get_mut(&paddr)
+                    .insert_vma(new_vma.clone());
             }
             drop(vma_guard);
 
-            new_vma_guard.remap(old_flags, &mut new_guard.user_mapper.utable, ())?;
-            drop(new_vma_guard);
+            new_guard.mappings.vmas.insert(new_vma);
         }
+        // 除了MADV_DONTFORK的区域之外，子进程映射了与父进程数量相同的页面，
+        // 写时复制的页面在被复制之前同时计入父子进程的RSS
+        new_guard.total_vm = self.total_vm - dontcopy_vm;
+        new_guard.rss = self.rss - dontcopy_rss;
+        new_guard.hiwater_rss = new_guard.rss;
+        flusher.flush();
+        drop(page_manager_guard);
         drop(new_guard);
         drop(irq_guard);
         return Ok(new_addr_space);
     }
 
+    /// 处理写时复制引起的缺页
+    ///
+    /// 如果物理页只剩下当前VMA在使用，那么直接恢复页表项的写权限，
+    /// 否则分配一个新的物理页，拷贝原页面的内容，并把它映射到触发缺页的地址。
+    ///
+    /// ## 参数
+    ///
+    /// - `vaddr`：触发缺页的虚拟地址
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(())`：缺页已经被处理，可以重新执行触发缺页的指令
+    /// - `Err(SystemError::EFAULT)`：该地址不是写时复制的页面
+    /// - `Err(SystemError::ENOMEM)`：内存不足
+    pub fn handle_cow_fault(&mut self, vaddr: VirtAddr) -> Result<(), SystemError> {
+        let vma = self.mappings.contains(vaddr).ok_or(SystemError::EFAULT)?;
+        let vma_guard = vma.lock();
+        if !vma_guard.is_cow_mapping() || !vma_guard.flags().has_write() {
+            return Err(SystemError::EFAULT);
+        }
+
+        let page_vaddr = VirtAddr::new(vaddr.data() & MMArch::PAGE_MASK);
+        let mapper = &mut self.user_mapper.utable;
+        let (old_paddr, pte_flags) = mapper.translate(page_vaddr).ok_or(SystemError::EFAULT)?;
+        if pte_flags.has_write() {
+            // 缺页已经被同一地址空间内的其他线程处理了
+            return Ok(());
+        }
+        let new_flags = pte_flags.set_write(true);
+
+        // 无论是恢复写权限还是复制页面，这个地址在处理前后都映射了一个物理页，因此RSS不变
+        let mut page_manager_guard = page_manager_lock_irqsave();
+        let old_page = page_manager_guard.get_mut(&old_paddr);
+        if old_page.map_count() == 1 {
+            // 只有当前VMA映射了这个物理页，直接恢复写权限即可
+            unsafe { mapper.remap(page_vaddr, new_flags) }
+                .ok_or(SystemError::EFAULT)?
+                .flush();
+            return Ok(());
+        }
+
+        // 分配新的物理页，并拷贝原页面的内容
+        let new_paddr =
+            unsafe { mapper.allocator_mut().allocate_one() }.ok_or(SystemError::ENOMEM)?;
+        unsafe {
+            let src = MMArch::phys_2_virt(old_paddr).unwrap().data() as *const u8;
+            let dst = MMArch::phys_2_virt(new_paddr).unwrap().data() as *mut u8;
+            dst.copy_from_nonoverlapping(src, MMArch::PAGE_SIZE);
+        }
+
+        unsafe {
+            let (_, _, flush) = match mapper.unmap_phys(page_vaddr, false) {
+                Some(r) => r,
+                None => {
+                    mapper.allocator_mut().free_one(new_paddr);
+                    return Err(SystemError::EFAULT);
+                }
+            };
+            flush.flush();
+            match mapper.map_phys(page_vaddr, new_paddr, new_flags) {
+                Some(flush) => flush.flush(),
+                None => {
+                    // 释放新的物理页，并恢复原来的只读映射。上级页表没有被释放，因此恢复不需要分配内存
+                    mapper.allocator_mut().free_one(new_paddr);
+                    if let Some(flush) = mapper.map_phys(page_vaddr, old_paddr, pte_flags) {
+                        flush.flush();
+                    }
+                    return Err(SystemError::ENOMEM);
+                }
+            }
+        }
+        old_page.remove_vma(&vma);
+
+        page_manager_guard.insert(new_paddr, Page::new(false));
+        page_manager_guard
+            .get_mut(&new_paddr)
+            .insert_vma(vma.clone());
+
+        drop(page_manager_guard);
+        drop(vma_guard);
+        return Ok(());
+    }
+
     /// 判断当前的地址空间是否是当前进程的地址空间
     #[inline]
     pub fn is_current(&self) -> bool {
@@ -340,6 +518,18 @@ impl InnerAddressSpace {
         // 找到未使用的区域
         let region = match addr {
             Some(vaddr) => {
+                if map_flags.contains(MapFlags::MAP_FIXED)
+                    && !map_flags.contains(MapFlags::MAP_FIXED_NOREPLACE)
+                {
+                    // MAP_FIXED：取消目标区域中已有的映射，新的映射将替换它们
+                    let requested = VirtRegion::new(vaddr, page_count.bytes());
+                    if requested.end() >= MMArch::USER_END_VADDR
+                        || !vaddr.check_aligned(MMArch::PAGE_SIZE)
+                    {
+                        return Err(SystemError::EINVAL);
+                    }
+                    self.munmap(VirtPageFrame::new(vaddr), page_count)?;
+                }
                 self.mappings
                     .find_free_at(self.mmap_min, vaddr, page_count.bytes(), map_flags)?
             }
@@ -371,6 +561,8 @@ impl InnerAddressSpace {
             &mut self.user_mapper.utable,
             flusher,
         )?);
+        // map_func会立即为整个区域映射物理页
+        self.account_mapped(page_count.data(), page_count.data());
 
         return Ok(page);
     }
@@ -431,15 +623,24 @@ impl InnerAddressSpace {
         let new_page = self.map_anonymous(new_vaddr, new_len, prot_flags, map_flags, true)?;
         let new_page_vaddr = new_page.virt_address();
 
-        // 拷贝旧内存区域内容到新内存区域
-        let old_buffer_reader =
-            UserBufferReader::new(old_vaddr.data() as *const u8, old_len, true)?;
-        let old_buf: &[u8] = old_buffer_reader.read_from_user(0)?;
-        let mut new_buffer_writer =
-            UserBufferWriter::new(new_page_vaddr.data() as *mut u8, new_len, true)?;
-        let new_buf: &mut [u8] = new_buffer_writer.buffer(0)?;
-        let len = old_buf.len().min(new_buf.len());
-        new_buf[..len].copy_from_slice(&old_buf[..len]);
+        // 拷贝旧内存区域内容到新内存区域。这里持有地址空间的锁，内核访问用户空间时触发的缺页
+        // （例如写入只读的新区域）无法被处理，因此通过物理页在内核中的映射进行拷贝
+        let len = old_len.min(new_len);
+        let mapper = &self.user_mapper.utable;
+        for offset in (0..len).step_by(MMArch::PAGE_SIZE) {
+            let (src, _) = mapper
+                .translate(old_vaddr + offset)
+                .ok_or(SystemError::EFAULT)?;
+            let (dst, _) = mapper
+                .translate(new_page_vaddr + offset)
+                .ok_or(SystemError::EFAULT)?;
+            let count = core::cmp::min(MMArch::PAGE_SIZE, len - offset);
+            unsafe {
+                let src = MMArch::phys_2_virt(src).unwrap().data() as *const u8;
+                let dst = MMArch::phys_2_virt(dst).unwrap().data() as *mut u8;
+                dst.copy_from_nonoverlapping(src, count);
+            }
+        }
 
         return Ok(new_page_vaddr);
     }
@@ -483,6 +684,8 @@ impl InnerAddressSpace {
                 self.mappings.insert_vma(after);
             }
 
+            let pages = intersection.size() / MMArch::PAGE_SIZE;
+            self.account_unmapped(pages, if r.mapped() { pages } else { 0 });
             r.unmap(&mut self.user_mapper.utable, &mut flusher);
         }
 
@@ -491,6 +694,15 @@ impl InnerAddressSpace {
         return Ok(());
     }
 
+    /// 修改地址空间中一段区域的访问权限
+    ///
+    /// 写时复制的页面在权限被放宽之后仍然保持写保护（见[`VMA::remap`]），
+    /// 因此缺页处理可以根据VMA的权限区分写时复制引起的缺页与真正的权限错误
+    ///
+    /// # Errors
+    ///
+    /// - `ENOMEM`：区域中包含没有被映射的地址
+    /// - `EACCES`：VMA不允许指定的权限
     pub fn mprotect(
         &mut self,
         start_page: VirtPageFrame,
@@ -518,6 +730,15 @@ impl InnerAddressSpace {
         let regions = self.mappings.conflicts(region).collect::<Vec<_>>();
         // kdebug!("mprotect: regions: {:?}", regions);
 
+        // 区域中包含没有被映射的地址时，不修改任何VMA
+        let covered: usize = regions
+            .iter()
+            .map(|r| r.lock().region().intersect(&region).unwrap().size())
+            .sum();
+        if covered != region.size() {
+            return Err(SystemError::ENOMEM);
+        }
+
         for r in regions {
             // kdebug!("mprotect: r: {:?}", r);
             let r = *r.lock().region();
@@ -549,10 +770,77 @@ impl InnerAddressSpace {
                 .set_write(prot_flags.contains(ProtFlags::PROT_WRITE));
 
             r_guard.remap(new_flags, mapper, &mut flusher)?;
+            // 同步VMA的访问权限（/proc/<pid>/maps中显示的就是这些标志）
+            let vm_flags = (*r_guard.vm_flags()
+                - (VmFlags::VM_READ | VmFlags::VM_WRITE | VmFlags::VM_EXEC))
+                | VmFlags::from(prot_flags);
+            r_guard.set_vm_flags(vm_flags);
+            drop(r_guard);
+            self.mappings.insert_vma(r);
+        }
+
+        return Ok(());
+    }
+
+    /// 为地址空间中的一段区域设置内存使用建议（修改VMA的标志）
+    ///
+    /// 与mprotect相同，区域的边界落在VMA的中间时，会拆分这个VMA
+    ///
+    /// ## 参数
+    ///
+    /// - `start_page`：起始页帧
+    /// - `page_count`：页帧数量
+    /// - `behavior`：内存使用建议
+    ///
+    /// ## 返回值
+    ///
+    /// - `Err(SystemError::EINVAL)`：不能对共享映射设置MADV_WIPEONFORK
+    /// - `Err(SystemError::ENOMEM)`：区域中包含没有被映射的地址，已经映射的部分仍然会被设置
+    pub fn madvise(
+        &mut self,
+        start_page: VirtPageFrame,
+        page_count: PageFrameCount,
+        behavior: MadviseBehavior,
+    ) -> Result<(), SystemError> {
+        let region = VirtRegion::new(start_page.virt_address(), page_count.bytes());
+        let regions = self.mappings.conflicts(region).collect::<Vec<_>>();
+
+        if behavior == MadviseBehavior::WipeOnFork
+            && regions
+                .iter()
+                .any(|r| r.lock().vm_flags().contains(VmFlags::VM_SHARED))
+        {
+            return Err(SystemError::EINVAL);
+        }
+
+        let mut covered = 0;
+        for r in regions {
+            let r = *r.lock().region();
+            let r = self.mappings.remove_vma(&r).unwrap();
+
+            let intersection = r.lock().region().intersect(&region).unwrap();
+            let split_result = r
+                .extract(intersection, &self.user_mapper.utable)
+                .expect("Failed to extract VMA");
+
+            if let Some(before) = split_result.prev {
+                self.mappings.insert_vma(before);
+            }
+            if let Some(after) = split_result.after {
+                self.mappings.insert_vma(after);
+            }
+
+            let mut r_guard = r.lock();
+            let vm_flags = behavior.update_vm_flags(*r_guard.vm_flags()).unwrap();
+            r_guard.set_vm_flags(vm_flags);
             drop(r_guard);
             self.mappings.insert_vma(r);
+            covered += intersection.size();
         }
 
+        if covered != region.size() {
+            return Err(SystemError::ENOMEM);
+        }
         return Ok(());
     }
 
@@ -581,6 +869,60 @@ impl InnerAddressSpace {
                 vma.unmap(&mut self.user_mapper.utable, &mut flusher);
             }
         }
+        self.rss = 0;
+    }
+
+    /// 地址空间中所有VMA的总大小（VSZ）
+    pub fn total_vm(&self) -> PageFrameCount {
+        return PageFrameCount::new(self.total_vm);
+    }
+
+    /// 地址空间中已经映射了物理页的页数（RSS）
+    pub fn rss(&self) -> PageFrameCount {
+        return PageFrameCount::new(self.rss);
+    }
+
+    /// 地址空间的RSS曾经达到的最大值
+    pub fn hiwater_rss(&self) -> PageFrameCount {
+        return PageFrameCount::new(self.hiwater_rss);
+    }
+
+    /// 私有的可写映射的总大小（不包括代码段），对应linux的/proc/<pid>/statm中的data
+    pub fn data_vm(&self) -> PageFrameCount {
+        let pages = self
+            .mappings
+            .iter_vmas()
+            .map(|vma| vma.lock())
+            .filter(|vma| {
+                vma.vm_flags().contains(VmFlags::VM_WRITE)
+                    && !vma.vm_flags().contains(VmFlags::VM_SHARED)
+            })
+            .map(|vma| vma.region().size() / MMArch::PAGE_SIZE)
+            .sum();
+        return PageFrameCount::new(pages);
+    }
+
+    /// 记录新增的映射
+    ///
+    /// ## 参数
+    ///
+    /// - `vm_pages`：新增的VMA的页数
+    /// - `rss_pages`：其中已经映射了物理页的页数
+    pub fn account_mapped(&mut self, vm_pages: usize, rss_pages: usize) {
+        self.total_vm += vm_pages;
+        self.rss += rss_pages;
+        self.hiwater_rss = core::cmp::max(self.hiwater_rss, self.rss);
+    }
+
+    /// 记录被取消的映射
+    ///
+    /// ## 参数
+    ///
+    /// - `vm_pages`：被移除的VMA的页数
+    /// - `rss_pages`：被解除映射的物理页的页数
+    pub fn account_unmapped(&mut self, vm_pages: usize, rss_pages: usize) {
+        self.total_vm -= vm_pages;
+        self.rss -= rss_pages;
     }
 
     /// 设置进程的堆的内存空间
@@ -604,8 +946,14 @@ impl InnerAddressSpace {
         if new_brk > self.brk {
             let len = new_brk - self.brk;
             let prot_flags = ProtFlags::PROT_READ | ProtFlags::PROT_WRITE | ProtFlags::PROT_EXEC;
-            let map_flags = MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS | MapFlags::MAP_FIXED;
-            self.map_anonymous(old_brk, len, prot_flags, map_flags, true)?;
+            // 不能覆盖堆之后已有的映射（例如mmap得到的区域），此时扩展失败
+            let map_flags =
+                MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS | MapFlags::MAP_FIXED_NOREPLACE;
+            self.map_anonymous(old_brk, len, prot_flags, map_flags, true)
+                .map_err(|e| match e {
+                    SystemError::EEXIST => SystemError::ENOMEM,
+                    e => e,
+                })?;
 
             self.brk = new_brk;
             return Ok(old_brk);
@@ -778,8 +1126,8 @@ impl UserMappings {
             }
 
             if flags.contains(MapFlags::MAP_FIXED) {
-                // todo: 支持MAP_FIXED标志对已有的VMA进行覆盖
-                return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
+                // MAP_FIXED要覆盖的映射应当已经被调用者取消（见AddressSpace::mmap）
+                return Err(SystemError::EEXIST);
             }
 
             // 如果没有指定MAP_FIXED标志，那么就对地址做修正
@@ -931,22 +1279,9 @@ impl LockedVMA {
         &self,
         flags: PageFlags<MMArch>,
         mapper: &mut PageMapper,
-        mut flusher: impl Flusher<MMArch>,
+        flusher: impl Flusher<MMArch>,
     ) -> Result<(), SystemError> {
-        let mut guard = self.lock();
-        assert!(guard.mapped);
-        for page in guard.region.pages() {
-            // 暂时要求所有的页帧都已经映射到页表
-            // TODO: 引入Lazy Mapping, 通过缺页中断来映射页帧，这里就不必要求所有的页帧都已经映射到页表了
-            let r = unsafe {
-                mapper
-                    .remap(page.virt_address(), flags)
-                    .expect("Failed to remap, beacuse of some page is not mapped")
-            };
-            flusher.consume(r);
-        }
-        guard.flags = flags;
-        return Ok(());
+        return self.lock().remap(flags, mapper, flusher);
     }
 
     pub fn unmap(&self, mapper: &mut PageMapper, mut flusher: impl Flusher<MMArch>) {
@@ -1187,6 +1522,15 @@ impl VMA {
         return self.flags;
     }
 
+    /// 判断当前VMA在fork时是否需要写时复制
+    ///
+    /// 私有的、可能被写入的映射需要写时复制，共享映射则由父子进程真正共享
+    #[inline(always)]
+    pub fn is_cow_mapping(&self) -> bool {
+        return !self.vm_flags.contains(VmFlags::VM_SHARED)
+            && self.vm_flags.contains(VmFlags::VM_MAYWRITE);
+    }
+
     pub fn pages(&self) -> VirtPageFrameIter {
         return VirtPageFrameIter::new(
             VirtPageFrame::new(self.region.start()),
@@ -1194,6 +1538,10 @@ impl VMA {
         );
     }
 
+    /// 调整当前VMA的页面的标志位
+    ///
+    /// 对于写时复制的映射，仍然被其他VMA（例如fork出的子进程）映射的页面会保持写保护，
+    /// 即使新的标志位允许写入：写入这些页面时，由缺页处理复制页面，而不是直接写入共享的物理页
     pub fn remap(
         &mut self,
         flags: PageFlags<MMArch>,
@@ -1201,13 +1549,32 @@ impl VMA {
         mut flusher: impl Flusher<MMArch>,
     ) -> Result<(), SystemError> {
         assert!(self.mapped);
+        let cow = self.is_cow_mapping() && flags.has_write();
+        let page_manager_guard = if cow {
+            Some(page_manager_lock_irqsave())
+        } else {
+            None
+        };
         for page in self.region.pages() {
             // kdebug!("remap page {:?}", page.virt_address());
+            let mut page_flags = flags;
+            if let Some(page_manager_guard) = page_manager_guard.as_ref() {
+                let paddr = mapper
+                    .translate(page.virt_address())
+                    .expect("Failed to remap, beacuse of some page is not mapped")
+                    .0;
+                let shared = page_manager_guard
+                    .get(&paddr)
+                    .map_or(false, |p| p.map_count() > 1);
+                if shared {
+                    page_flags = flags.set_write(false);
+                }
+            }
             // 暂时要求所有的页帧都已经映射到页表
             // TODO: 引入Lazy Mapping, 通过缺页中断来映射页帧，这里就不必要求所有的页帧都已经映射到页表了
             let r = unsafe {
                 mapper
-                    .remap(page.virt_address(), flags)
+                    .remap(page.virt_address(), page_flags)
                     .expect("Failed to remap, beacuse of some page is not mapped")
             };
             // kdebug!("consume page {:?}", page.virt_address());
@@ -1322,9 +1689,26 @@ impl VMA {
             //     "VMA::zeroed: cur_dest={cur_dest:?}, vaddr = {:?}",
             //     cur_dest.virt_address()
             // );
-            let r = unsafe { mapper.map(cur_dest.virt_address(), flags) }
-                .expect("Failed to map zero, may be OOM error");
-            // todo: 增加OOM处理
+            let r = match unsafe { mapper.map(cur_dest.virt_address(), flags) } {
+                Some(r) => r,
+                None => {
+                    // 内存不足：释放已经映射的页面，由调用者决定是否触发OOM killer并重试
+                    let mut page_manager_guard = page_manager_lock_irqsave();
+                    for frame in VirtPageFrameIter::new(destination, cur_dest) {
+                        let (paddr, _, flush) =
+                            unsafe { mapper.unmap_phys(frame.virt_address(), true) }.unwrap();
+                        unsafe {
+                            deallocate_page_frames(
+                                PhysPageFrame::new(paddr),
+                                PageFrameCount::new(1),
+                                &mut page_manager_guard,
+                            )
+                        };
+                        flusher.consume(flush);
+                    }
+                    return Err(SystemError::ENOMEM);
+                }
+            };
 
             // 稍后再刷新TLB，这里取消刷新
             flusher.consume(r);
@@ -1553,4 +1937,9 @@ impl UserStack {
     pub fn stack_size(&self) -> usize {
         return self.mapped_size - Self::GUARD_PAGES_NUM * MMArch::PAGE_SIZE;
     }
+
+    /// 获取用户栈（不包括保护页）所在的虚拟地址范围
+    pub fn region(&self) -> VirtRegion {
+        return VirtRegion::new(self.stack_bottom - self.mapped_size, self.stack_size());
+    }
 }
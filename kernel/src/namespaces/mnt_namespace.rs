@@ -0,0 +1,114 @@
+//! mount namespace
+//!
+//! mount namespace隔离了挂载表：挂载点到被挂载的文件系统的映射保存在namespace中，
+//! 而不是保存在[`MountFS`]中。fork时子进程与父进程共享同一个mount namespace，
+//! 使用CLONE_NEWNS时，子进程得到父进程的挂载表的一份拷贝，此后双方的挂载互不影响。
+//!
+//! 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/fs/namespace.c
+
+use alloc::{collections::BTreeMap, sync::Arc};
+use system_error::SystemError;
+
+use crate::{
+    filesystem::vfs::{InodeId, MountFS},
+    libs::spinlock::SpinLock,
+    process::{ProcessControlBlock, ProcessManager},
+};
+
+use super::{alloc_ns_inum, Namespace, NamespaceType};
+
+lazy_static! {
+    /// 初始mount namespace，系统启动时的挂载都位于这个namespace中
+    pub static ref INIT_MNT_NS: Arc<MntNamespace> = Arc::new(MntNamespace {
+        inum: alloc_ns_inum(),
+        mounts: SpinLock::new(BTreeMap::new()),
+    });
+}
+
+/// 挂载点：挂载点所在的MountFS的地址，以及挂载点在这个MountFS中的inode号
+///
+/// 被挂载的文件系统持有它的挂载点所在的MountFS的引用，因此只要挂载还在挂载表中，这个地址就不会被复用
+type MountPoint = (usize, InodeId);
+
+#[derive(Debug)]
+pub struct MntNamespace {
+    inum: usize,
+    /// 挂载表
+    mounts: SpinLock<BTreeMap<MountPoint, Arc<MountFS>>>,
+}
+
+impl MntNamespace {
+    /// 创建一个新的mount namespace，它的挂载表是当前namespace的挂载表的拷贝（CLONE_NEWNS）
+    pub fn copy(&self) -> Arc<Self> {
+        return Arc::new(Self {
+            inum: alloc_ns_inum(),
+            mounts: SpinLock::new(self.mounts.lock().clone()),
+        });
+    }
+
+    fn mountpoint(parent: &MountFS, inode_id: InodeId) -> MountPoint {
+        return (parent as *const MountFS as usize, inode_id);
+    }
+
+    /// 查找挂载在`parent`中的`inode_id`上的文件系统
+    pub fn lookup(&self, parent: &MountFS, inode_id: InodeId) -> Option<Arc<MountFS>> {
+        return self
+            .mounts
+            .lock()
+            .get(&Self::mountpoint(parent, inode_id))
+            .cloned();
+    }
+
+    /// 判断`parent`中的`inode_id`是否为一个挂载点
+    pub fn is_mountpoint(&self, parent: &MountFS, inode_id: InodeId) -> bool {
+        return self
+            .mounts
+            .lock()
+            .contains_key(&Self::mountpoint(parent, inode_id));
+    }
+
+    /// 将`mount_fs`挂载到`parent`中的`inode_id`上
+    ///
+    /// ## 返回值
+    ///
+    /// - `EBUSY` : 这个挂载点上已经挂载了文件系统
+    pub fn add_mount(
+        &self,
+        parent: &MountFS,
+        inode_id: InodeId,
+        mount_fs: Arc<MountFS>,
+    ) -> Result<(), SystemError> {
+        let mut mounts = self.mounts.lock();
+        let key = Self::mountpoint(parent, inode_id);
+        if mounts.contains_key(&key) {
+            return Err(SystemError::EBUSY);
+        }
+        mounts.insert(key, mount_fs);
+        return Ok(());
+    }
+}
+
+impl Namespace for MntNamespace {
+    fn ns_type(&self) -> NamespaceType {
+        NamespaceType::Mnt
+    }
+
+    fn inum(&self) -> usize {
+        self.inum
+    }
+
+    fn install(self: Arc<Self>, pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+        pcb.set_mnt_ns(self);
+        return Ok(());
+    }
+}
+
+/// 获取当前进程所在的mount namespace
+///
+/// 进程管理初始化完成之前（例如vfs初始化时）使用初始mount namespace
+pub fn current_mnt_ns() -> Arc<MntNamespace> {
+    if !ProcessManager::initialized() {
+        return INIT_MNT_NS.clone();
+    }
+    return ProcessManager::current_pcb().mnt_ns();
+}
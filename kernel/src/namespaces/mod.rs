@@ -0,0 +1,119 @@
+//! 命名空间（namespace）
+//!
+//! 每种namespace都实现[`Namespace`]，并通过`Arc`在进程之间共享，
+//! 最后一个引用被释放时namespace随之被销毁。用户态可以通过/proc/<pid>/ns/下的文件引用一个namespace，
+//! 并通过setns加入它。
+//!
+//! 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/linux/proc_ns.h
+
+use core::{
+    fmt::Debug,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use alloc::sync::Arc;
+use system_error::SystemError;
+
+use crate::process::{fork::CloneFlags, ProcessControlBlock};
+
+pub mod mnt_namespace;
+pub mod nsproxy;
+pub mod pid_namespace;
+pub mod syscall;
+pub mod uts_namespace;
+
+/// 动态分配的namespace编号的起始值
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/linux/proc_ns.h#48
+const PROC_DYNAMIC_FIRST: usize = 0xF000_0000;
+
+static NEXT_NS_INUM: AtomicUsize = AtomicUsize::new(PROC_DYNAMIC_FIRST);
+
+/// 为新的namespace分配编号（/proc/<pid>/ns/下的文件显示的编号）
+pub fn alloc_ns_inum() -> usize {
+    return NEXT_NS_INUM.fetch_add(1, Ordering::SeqCst);
+}
+
+/// namespace的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamespaceType {
+    Mnt,
+    Uts,
+    Ipc,
+    User,
+    Pid,
+    Net,
+    Cgroup,
+}
+
+impl NamespaceType {
+    pub const ALL: [NamespaceType; 7] = [
+        NamespaceType::Mnt,
+        NamespaceType::Uts,
+        NamespaceType::Ipc,
+        NamespaceType::User,
+        NamespaceType::Pid,
+        NamespaceType::Net,
+        NamespaceType::Cgroup,
+    ];
+
+    /// namespace在/proc/<pid>/ns/下对应的文件名
+    pub fn name(&self) -> &'static str {
+        match self {
+            NamespaceType::Mnt => "mnt",
+            NamespaceType::Uts => "uts",
+            NamespaceType::Ipc => "ipc",
+            NamespaceType::User => "user",
+            NamespaceType::Pid => "pid",
+            NamespaceType::Net => "net",
+            NamespaceType::Cgroup => "cgroup",
+        }
+    }
+
+    /// 创建这种namespace时使用的clone标志
+    pub fn clone_flag(&self) -> CloneFlags {
+        match self {
+            NamespaceType::Mnt => CloneFlags::CLONE_NEWNS,
+            NamespaceType::Uts => CloneFlags::CLONE_NEWUTS,
+            NamespaceType::Ipc => CloneFlags::CLONE_NEWIPC,
+            NamespaceType::User => CloneFlags::CLONE_NEWUSER,
+            NamespaceType::Pid => CloneFlags::CLONE_NEWPID,
+            NamespaceType::Net => CloneFlags::CLONE_NEWNET,
+            NamespaceType::Cgroup => CloneFlags::CLONE_NEWCGROUP,
+        }
+    }
+
+    /// 所有namespace的clone标志的组合
+    pub fn all_clone_flags() -> CloneFlags {
+        return Self::ALL
+            .iter()
+            .fold(CloneFlags::empty(), |flags, ty| flags | ty.clone_flag());
+    }
+
+    /// 获取进程所在的这种类型的namespace
+    ///
+    /// ## 返回值
+    ///
+    /// 如果这种namespace还没有被实现，则返回None
+    pub fn get(&self, pcb: &ProcessControlBlock) -> Option<Arc<dyn Namespace>> {
+        match self {
+            NamespaceType::Mnt => Some(pcb.nsproxy().mnt_ns.clone()),
+            NamespaceType::Pid => Some(pcb.pid_ns()),
+            NamespaceType::Uts => Some(pcb.nsproxy().uts_ns.clone()),
+            // todo: 实现其他namespace之后，在这里返回进程所在的namespace
+            _ => None,
+        }
+    }
+}
+
+/// namespace对象
+pub trait Namespace: Debug + Send + Sync {
+    /// namespace的类型
+    fn ns_type(&self) -> NamespaceType;
+
+    /// namespace的编号，同一个namespace的编号永远不变，不同的namespace的编号互不相同
+    fn inum(&self) -> usize;
+
+    /// 让进程加入这个namespace（setns）
+    fn install(self: Arc<Self>, pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError>;
+}
@@ -0,0 +1,79 @@
+//! nsproxy
+//!
+//! 进程所在的各个namespace被集中保存在[`NsProxy`]中。fork时，若没有使用任何CLONE_NEW*标志，
+//! 子进程与父进程共享同一个NsProxy；否则为子进程创建一个新的NsProxy，
+//! 其中只有对应标志被设置的namespace被替换，其他namespace仍然与父进程共享。
+//!
+//! 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/nsproxy.c
+
+use alloc::sync::Arc;
+use system_error::SystemError;
+
+use crate::process::fork::CloneFlags;
+
+use super::{
+    mnt_namespace::{MntNamespace, INIT_MNT_NS},
+    pid_namespace::{PidNamespace, INIT_PID_NS},
+    uts_namespace::{UtsNamespace, INIT_UTS_NS},
+};
+
+lazy_static! {
+    /// 初始nsproxy，系统启动时创建的进程都位于其中的namespace中
+    pub static ref INIT_NSPROXY: Arc<NsProxy> = Arc::new(NsProxy {
+        uts_ns: INIT_UTS_NS.clone(),
+        mnt_ns: INIT_MNT_NS.clone(),
+        pid_ns_for_children: INIT_PID_NS.clone(),
+    });
+}
+
+/// 进程所在的各个namespace
+///
+/// NsProxy本身不会被修改：进程进入新的namespace（clone、unshare、setns）时，
+/// 总是为它创建一个新的NsProxy，从而不会影响与它共享原来的NsProxy的其他进程
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/linux/nsproxy.h#31
+#[derive(Debug, Clone)]
+pub struct NsProxy {
+    /// 进程所在的uts namespace
+    pub uts_ns: Arc<UtsNamespace>,
+    /// 进程所在的mount namespace
+    pub mnt_ns: Arc<MntNamespace>,
+    /// 进程之后创建的子进程所在的pid namespace。进程自身所在的pid namespace由它的pid决定，
+    /// 见[`ProcessControlBlock::pid_ns`](crate::process::ProcessControlBlock::pid_ns)
+    pub pid_ns_for_children: Arc<PidNamespace>,
+    // todo: 实现ipc、net namespace之后，在这里加入对应的字段
+}
+
+impl NsProxy {
+    /// 支持通过clone、unshare创建的namespace的标志
+    pub fn supported_clone_flags() -> CloneFlags {
+        return CloneFlags::CLONE_NEWUTS | CloneFlags::CLONE_NEWNS | CloneFlags::CLONE_NEWPID;
+    }
+
+    /// 基于当前的NsProxy创建一个新的NsProxy，`clone_flags`中设置了CLONE_NEW*标志的namespace被替换为新的namespace
+    ///
+    /// ## 参数
+    ///
+    /// - `clone_flags`: 克隆标志：
+    ///   - `CLONE_NEWUTS`、`CLONE_NEWNS`：使用原来的namespace的一份拷贝
+    ///   - `CLONE_NEWPID`：使用pid_ns_for_children的一个新的子namespace
+    ///
+    /// ## 返回值
+    ///
+    /// - `ENOSPC`：pid namespace的层数超过了上限
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/nsproxy.c#67
+    pub fn create_new_namespaces(&self, clone_flags: &CloneFlags) -> Result<Self, SystemError> {
+        let mut new = self.clone();
+        if clone_flags.contains(CloneFlags::CLONE_NEWUTS) {
+            new.uts_ns = self.uts_ns.copy();
+        }
+        if clone_flags.contains(CloneFlags::CLONE_NEWNS) {
+            new.mnt_ns = self.mnt_ns.copy();
+        }
+        if clone_flags.contains(CloneFlags::CLONE_NEWPID) {
+            new.pid_ns_for_children = self.pid_ns_for_children.new_child()?;
+        }
+        return Ok(new);
+    }
+}
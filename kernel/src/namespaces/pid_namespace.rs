@@ -0,0 +1,237 @@
+//! pid namespace
+//!
+//! 每个进程在它所在的pid namespace以及所有祖先namespace中各有一个pid（见[`UPid`]），
+//! 第0层（初始namespace）中的pid就是内核内部使用的全局pid（[`ProcessControlBlock::pid`]）。
+//! 系统调用向用户态返回pid时，需要转换为调用者所在namespace中的pid。
+//!
+//! 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/pid_namespace.c
+
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use crate::{
+    arch::ipc::signal::Signal,
+    libs::spinlock::SpinLock,
+    process::{fork::MAX_PID_NS_LEVEL, pid::PidMap, Pid, ProcessControlBlock, ProcessManager},
+    syscall::Syscall,
+};
+
+use super::{alloc_ns_inum, Namespace, NamespaceType};
+
+/// 初始pid namespace的编号
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/linux/proc_ns.h#44
+const PROC_PID_INIT_INO: usize = 0xEFFF_FFFC;
+
+lazy_static! {
+    /// 初始pid namespace，系统启动时创建的进程都位于这个namespace中
+    pub static ref INIT_PID_NS: Arc<PidNamespace> = Arc::new(PidNamespace {
+        parent: None,
+        level: 0,
+        inum: PROC_PID_INIT_INO,
+        inner: SpinLock::new(InnerPidNamespace::new()),
+    });
+}
+
+/// 进程在某一层pid namespace中的pid
+#[derive(Debug, Clone)]
+pub struct UPid {
+    pub ns: Arc<PidNamespace>,
+    pub nr: Pid,
+}
+
+impl UPid {
+    pub fn new(ns: Arc<PidNamespace>, nr: Pid) -> Self {
+        Self { ns, nr }
+    }
+}
+
+#[derive(Debug)]
+pub struct PidNamespace {
+    /// 父namespace，初始namespace没有父namespace
+    parent: Option<Arc<PidNamespace>>,
+    /// namespace的层数，初始namespace为第0层
+    level: usize,
+    inum: usize,
+    inner: SpinLock<InnerPidNamespace>,
+}
+
+#[derive(Debug)]
+struct InnerPidNamespace {
+    /// namespace中已经被分配的pid
+    pid_map: PidMap,
+    /// namespace中的pid到全局pid的映射（初始namespace中的pid就是全局pid，因此不使用这个映射）
+    pids: BTreeMap<Pid, Pid>,
+    /// namespace中的init进程（pid为1的进程），负责收养namespace中的孤儿进程
+    child_reaper: Weak<ProcessControlBlock>,
+    /// init进程退出之后，namespace中不能再创建新的进程
+    dead: bool,
+}
+
+impl InnerPidNamespace {
+    fn new() -> Self {
+        Self {
+            pid_map: PidMap::new(),
+            pids: BTreeMap::new(),
+            child_reaper: Weak::new(),
+            dead: false,
+        }
+    }
+}
+
+impl PidNamespace {
+    /// 创建一个以当前namespace为父namespace的新namespace
+    ///
+    /// ## 返回值
+    ///
+    /// - `ENOSPC` : namespace的层数超过了[`MAX_PID_NS_LEVEL`]
+    pub fn new_child(self: &Arc<Self>) -> Result<Arc<Self>, SystemError> {
+        if self.level + 1 > MAX_PID_NS_LEVEL {
+            return Err(SystemError::ENOSPC);
+        }
+        return Ok(Arc::new(Self {
+            parent: Some(self.clone()),
+            level: self.level + 1,
+            inum: alloc_ns_inum(),
+            inner: SpinLock::new(InnerPidNamespace::new()),
+        }));
+    }
+
+    #[inline(always)]
+    pub fn level(&self) -> usize {
+        return self.level;
+    }
+
+    #[inline(always)]
+    pub fn parent(&self) -> Option<&Arc<PidNamespace>> {
+        return self.parent.as_ref();
+    }
+
+    /// 判断当前namespace是否是`other`自身或者它的祖先
+    pub fn is_ancestor_of(&self, other: &PidNamespace) -> bool {
+        let mut ns = other;
+        while ns.level > self.level {
+            ns = ns.parent.as_ref().unwrap();
+        }
+        return core::ptr::eq(ns, self);
+    }
+
+    /// 在namespace中为全局pid为`global`的进程分配一个pid
+    ///
+    /// 与全局pid的分配方式相同，从上一次分配的pid之后开始查找，到达上限之后从`RESERVED_PIDS`开始重新查找。
+    /// 所有namespace共用同一个pid上限。只能在非初始namespace中调用，初始namespace中的pid在创建pcb时就已经分配
+    ///
+    /// ## 返回值
+    ///
+    /// - `ENOMEM` : namespace的init进程已经退出
+    /// - `EAGAIN` : namespace中没有空闲的pid
+    pub fn alloc_pid(&self, global: Pid) -> Result<Pid, SystemError> {
+        assert!(
+            self.level > 0,
+            "pids of the initial pid namespace are global pids"
+        );
+        let mut inner = self.inner.lock_irqsave();
+        if inner.dead {
+            return Err(SystemError::ENOMEM);
+        }
+
+        let nr = inner.pid_map.alloc(ProcessManager::pid_max())?;
+        inner.pids.insert(nr, global);
+        return Ok(nr);
+    }
+
+    /// 释放namespace中的pid
+    pub fn free_pid(&self, nr: Pid) {
+        if self.level > 0 {
+            let mut inner = self.inner.lock_irqsave();
+            inner.pids.remove(&nr);
+            inner.pid_map.free(nr);
+        }
+    }
+
+    /// 将namespace中的pid转换为全局pid
+    ///
+    /// ## 返回值
+    ///
+    /// 如果namespace中没有这个pid，则返回None
+    pub fn pid_to_global(&self, nr: Pid) -> Option<Pid> {
+        if self.level == 0 {
+            return Some(nr);
+        }
+        return self.inner.lock_irqsave().pids.get(&nr).copied();
+    }
+
+    /// 将全局pid转换为namespace中的pid，是[`PidNamespace::pid_to_global`]的逆操作
+    ///
+    /// ## 返回值
+    ///
+    /// 如果全局pid为`global`的进程不在这个namespace中，则返回None
+    pub fn global_to_pid(&self, global: Pid) -> Option<Pid> {
+        if self.level == 0 {
+            return Some(global);
+        }
+        return self
+            .inner
+            .lock_irqsave()
+            .pids
+            .iter()
+            .find(|(_, g)| **g == global)
+            .map(|(nr, _)| *nr);
+    }
+
+    /// 获取namespace的init进程
+    pub fn child_reaper(&self) -> Option<Arc<ProcessControlBlock>> {
+        if self.level == 0 {
+            return ProcessManager::find(Pid(1));
+        }
+        return self.inner.lock_irqsave().child_reaper.upgrade();
+    }
+
+    pub fn set_child_reaper(&self, pcb: &Arc<ProcessControlBlock>) {
+        self.inner.lock_irqsave().child_reaper = Arc::downgrade(pcb);
+    }
+
+    /// namespace的init进程退出时调用，杀死namespace中的其他所有进程，并且不再允许创建新的进程
+    ///
+    /// ## 参数
+    ///
+    /// - `reaper` : 正在退出的init进程的全局pid
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/pid_namespace.c#182
+    pub fn zap_processes(&self, reaper: Pid) {
+        let pids: Vec<Pid> = {
+            let mut inner = self.inner.lock_irqsave();
+            inner.dead = true;
+            inner.pids.values().copied().collect()
+        };
+        for pid in pids {
+            if pid != reaper {
+                let _ = Syscall::kill(pid.data() as i32, Signal::SIGKILL as i32);
+            }
+        }
+    }
+}
+
+impl Namespace for PidNamespace {
+    fn ns_type(&self) -> NamespaceType {
+        NamespaceType::Pid
+    }
+
+    fn inum(&self) -> usize {
+        self.inum
+    }
+
+    /// 加入pid namespace不会改变进程自身的pid，只会改变之后创建的子进程所在的namespace，
+    /// 并且只能加入进程当前所在的namespace或者它的子孙namespace
+    fn install(self: Arc<Self>, pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+        if !pcb.pid_ns().is_ancestor_of(&self) {
+            return Err(SystemError::EINVAL);
+        }
+        pcb.set_pid_ns_for_children(self);
+        return Ok(());
+    }
+}
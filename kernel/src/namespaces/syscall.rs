@@ -0,0 +1,70 @@
+use alloc::{sync::Arc, vec::Vec};
+use system_error::SystemError;
+
+use crate::{
+    filesystem::procfs::procfs_ns_of_file,
+    process::{fork::CloneFlags, pidfd::as_pidfd, ProcessManager},
+    syscall::Syscall,
+};
+
+use super::{Namespace, NamespaceType};
+
+impl Syscall {
+    /// 让当前进程加入fd所引用的namespace
+    ///
+    /// ## 参数
+    ///
+    /// - `fd` : /proc/<pid>/ns/下的namespace文件，或者一个pidfd
+    /// - `nstype` : 对于namespace文件，为0（不检查namespace的类型）或者这个namespace的CLONE_NEW*标志；
+    ///   对于pidfd，为需要加入的目标进程的各个namespace的CLONE_NEW*标志的组合
+    ///
+    /// ## 返回值
+    ///
+    /// - `EBADF` : fd不是一个打开的文件描述符
+    /// - `EINVAL` : fd既不是namespace文件也不是pidfd，nstype与namespace的类型不匹配，
+    ///   或者nstype中包含尚未实现的namespace
+    /// - `ESRCH` : pidfd指向的进程已经退出
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/nsproxy.c#546
+    pub fn setns(fd: i32, nstype: i32) -> Result<usize, SystemError> {
+        let flags = CloneFlags::from_bits(nstype as u32 as u64).ok_or(SystemError::EINVAL)?;
+        if !NamespaceType::all_clone_flags().contains(flags) {
+            return Err(SystemError::EINVAL);
+        }
+
+        let current = ProcessManager::current_pcb();
+        let file = current
+            .fd_table()
+            .read()
+            .get_file_by_fd(fd)
+            .ok_or(SystemError::EBADF)?;
+        let inode = file.inode();
+
+        let namespaces: Vec<Arc<dyn Namespace>> = if let Some(pidfd) = as_pidfd(&inode) {
+            // 使用pidfd时，必须指明要加入哪些namespace
+            if flags.is_empty() {
+                return Err(SystemError::EINVAL);
+            }
+            let pcb = pidfd.pcb().ok_or(SystemError::ESRCH)?;
+            if pidfd.exited() {
+                return Err(SystemError::ESRCH);
+            }
+            NamespaceType::ALL
+                .iter()
+                .filter(|ty| flags.contains(ty.clone_flag()))
+                .map(|ty| ty.get(&pcb).ok_or(SystemError::EINVAL))
+                .collect::<Result<Vec<_>, SystemError>>()?
+        } else {
+            let ns = procfs_ns_of_file(&file).ok_or(SystemError::EINVAL)?;
+            if !flags.is_empty() && flags != ns.ns_type().clone_flag() {
+                return Err(SystemError::EINVAL);
+            }
+            vec![ns]
+        };
+
+        for ns in namespaces {
+            ns.install(&current)?;
+        }
+        return Ok(0);
+    }
+}
@@ -0,0 +1,116 @@
+//! uts namespace
+//!
+//! uts namespace隔离了主机名（nodename）与域名（domainname）。
+//! fork时子进程与父进程共享同一个uts namespace，使用CLONE_NEWUTS时，子进程得到父进程的namespace的一份拷贝。
+//!
+//! 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/utsname.c
+
+use alloc::sync::Arc;
+use system_error::SystemError;
+
+use crate::{libs::spinlock::SpinLock, process::ProcessControlBlock};
+
+use super::{alloc_ns_inum, Namespace, NamespaceType};
+
+/// 主机名与域名的最大长度（不包含结尾的\0）
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/utsname.h#24
+pub const NEW_UTS_LEN: usize = 64;
+
+/// 初始uts namespace的编号
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/linux/proc_ns.h#42
+const PROC_UTS_INIT_INO: usize = 0xEFFF_FFFE;
+
+lazy_static! {
+    /// 初始uts namespace
+    pub static ref INIT_UTS_NS: Arc<UtsNamespace> = Arc::new(UtsNamespace {
+        inum: PROC_UTS_INIT_INO,
+        name: SpinLock::new(UtsName::new(b"DragonOS", b"(none)")),
+    });
+}
+
+/// 以\0结尾的主机名与域名
+#[derive(Debug, Clone)]
+pub struct UtsName {
+    pub nodename: [u8; NEW_UTS_LEN + 1],
+    pub domainname: [u8; NEW_UTS_LEN + 1],
+}
+
+impl UtsName {
+    fn new(nodename: &[u8], domainname: &[u8]) -> Self {
+        let mut r = Self {
+            nodename: [0; NEW_UTS_LEN + 1],
+            domainname: [0; NEW_UTS_LEN + 1],
+        };
+        r.nodename[..nodename.len()].copy_from_slice(nodename);
+        r.domainname[..domainname.len()].copy_from_slice(domainname);
+        return r;
+    }
+}
+
+#[derive(Debug)]
+pub struct UtsNamespace {
+    inum: usize,
+    name: SpinLock<UtsName>,
+}
+
+impl UtsNamespace {
+    /// 创建一个新的uts namespace，它的主机名与域名是当前namespace的拷贝（CLONE_NEWUTS）
+    pub fn copy(&self) -> Arc<Self> {
+        return Arc::new(Self {
+            inum: alloc_ns_inum(),
+            name: SpinLock::new(self.name()),
+        });
+    }
+
+    /// 获取namespace的主机名与域名
+    pub fn name(&self) -> UtsName {
+        return self.name.lock_irqsave().clone();
+    }
+
+    /// 设置namespace的主机名
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL` : 主机名的长度超过了[`NEW_UTS_LEN`]
+    pub fn set_nodename(&self, nodename: &[u8]) -> Result<(), SystemError> {
+        if nodename.len() > NEW_UTS_LEN {
+            return Err(SystemError::EINVAL);
+        }
+        let mut name = self.name.lock_irqsave();
+        name.nodename = [0; NEW_UTS_LEN + 1];
+        name.nodename[..nodename.len()].copy_from_slice(nodename);
+        return Ok(());
+    }
+
+    /// 设置namespace的域名
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL` : 域名的长度超过了[`NEW_UTS_LEN`]
+    pub fn set_domainname(&self, domainname: &[u8]) -> Result<(), SystemError> {
+        if domainname.len() > NEW_UTS_LEN {
+            return Err(SystemError::EINVAL);
+        }
+        let mut name = self.name.lock_irqsave();
+        name.domainname = [0; NEW_UTS_LEN + 1];
+        name.domainname[..domainname.len()].copy_from_slice(domainname);
+        return Ok(());
+    }
+}
+
+impl Namespace for UtsNamespace {
+    fn ns_type(&self) -> NamespaceType {
+        NamespaceType::Uts
+    }
+
+    fn inum(&self) -> usize {
+        self.inum
+    }
+
+    fn install(self: Arc<Self>, pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+        pcb.set_uts_ns(self);
+        return Ok(());
+    }
+}
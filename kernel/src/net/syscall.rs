@@ -15,7 +15,7 @@ use crate::{
     mm::{verify_area, VirtAddr},
     net::socket::{AddressFamily, SOL_SOCKET},
     process::ProcessManager,
-    syscall::Syscall,
+    syscall::{user_access::access_ok, Syscall},
 };
 
 use super::{
@@ -657,19 +657,14 @@ impl SockAddr {
         }
 
         // 检查用户传入的地址是否合法
-        verify_area(
-            VirtAddr::new(addr as usize),
-            core::mem::size_of::<SockAddr>(),
-        )
-        .map_err(|_| SystemError::EFAULT)?;
-
-        verify_area(
+        access_ok(
             VirtAddr::new(addr_len as usize),
             core::mem::size_of::<u32>(),
-        )
-        .map_err(|_| SystemError::EFAULT)?;
-
+            true,
+        )?;
         let to_write = min(self.len()?, *addr_len as usize);
+        access_ok(VirtAddr::new(addr as usize), to_write, true)?;
+
         if to_write > 0 {
             let buf = core::slice::from_raw_parts_mut(addr as *mut u8, to_write);
             buf.copy_from_slice(core::slice::from_raw_parts(
@@ -1,3 +1,5 @@
+use system_error::SystemError;
+
 /// An enumeration of the possible values for the `AT_*` constants.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum AtType {
@@ -98,3 +100,30 @@ bitflags! {
         const WCLONE = 0x80000000;
     }
 }
+
+/// waitid的idtype参数，表示如何解释id参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitIdType {
+    /// 等待任意子进程
+    All = 0,
+    /// 等待pid为id的子进程
+    Pid = 1,
+    /// 等待进程组id为id的子进程
+    Pgid = 2,
+    /// 等待pidfd为id的子进程
+    Pidfd = 3,
+}
+
+impl TryFrom<i32> for WaitIdType {
+    type Error = SystemError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(WaitIdType::All),
+            1 => Ok(WaitIdType::Pid),
+            2 => Ok(WaitIdType::Pgid),
+            3 => Ok(WaitIdType::Pidfd),
+            _ => Err(SystemError::EINVAL),
+        }
+    }
+}
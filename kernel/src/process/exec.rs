@@ -7,7 +7,7 @@ use crate::{
     driver::base::block::SeekFrom,
     filesystem::vfs::{
         file::{File, FileMode},
-        ROOT_INODE,
+        FileType, ROOT_INODE,
     },
     libs::elf::ELF_LOADER,
     mm::{
@@ -117,6 +117,10 @@ impl ExecParam {
         flags: ExecParamFlags,
     ) -> Result<Self, SystemError> {
         let inode = ROOT_INODE().lookup(file_path)?;
+        // 只有普通文件才能被执行
+        if inode.metadata()?.file_type != FileType::File {
+            return Err(SystemError::EACCES);
+        }
 
         // 读取文件头部，用于判断文件类型
         let file = File::new(inode, FileMode::O_RDONLY)?;
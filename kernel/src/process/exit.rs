@@ -1,21 +1,20 @@
 use core::intrinsics::likely;
 
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use system_error::SystemError;
 
 use crate::{
-    arch::{
-        ipc::signal::{SigChildCode, Signal},
-        CurrentIrqArch,
-    },
-    exception::InterruptArch,
-    sched::{schedule, SchedMode},
+    arch::ipc::signal::{SigChildCode, Signal},
+    ipc::signal_types::PosixSigInfo,
     syscall::user_access::UserBufferWriter,
 };
 
 use super::{
-    abi::WaitOption, pid::PidType, resource::RUsage, Pid, ProcessControlBlock, ProcessManager,
-    ProcessState,
+    abi::{WaitIdType, WaitOption},
+    pid::PidType,
+    pidfd::as_pidfd,
+    resource::{RUsage, RUsageWho},
+    ExitState, Pid, ProcessControlBlock, ProcessManager,
 };
 
 /// 内核wait4时的参数
@@ -69,12 +68,11 @@ pub fn kernel_wait4(
         pidtype = PidType::MAX;
     } else if pid < 0 {
         pidtype = PidType::PGID;
-        kwarn!("kernel_wait4: currently not support pgid, default to wait for pid\n");
         pid = -pid;
     } else if pid == 0 {
+        // 等待与当前进程同一进程组的任意子进程
         pidtype = PidType::PGID;
-        kwarn!("kernel_wait4: currently not support pgid, default to wait for pid\n");
-        pid = ProcessManager::current_pcb().pid().data() as i64;
+        pid = ProcessManager::current_pcb().basic().pgid().data() as i64;
     } else {
         pidtype = PidType::PID;
     }
@@ -103,152 +101,331 @@ pub fn kernel_wait4(
     return Ok(r);
 }
 
+/// waitid的内核实现
+///
+/// ## 参数
+///
+/// - `idtype` : 如何解释`id`参数
+/// - `id` : 要等待的子进程的pid、进程组id或者pidfd
+/// - `infop` : 用于返回子进程状态变化信息的siginfo_t
+/// - `options` : 等待选项，必须包含WEXITED、WSTOPPED、WCONTINUED中的至少一个
+/// - `rusage_buf` : 用于返回资源使用情况的缓冲区
+///
+/// ## 返回值
+///
+/// 成功时返回0。设置了WNOHANG并且没有子进程的状态发生变化时，`infop`被清零
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/exit.c#1630
+pub fn kernel_waitid(
+    idtype: WaitIdType,
+    id: i32,
+    infop: Option<UserBufferWriter<'_>>,
+    options: WaitOption,
+    rusage_buf: Option<&mut RUsage>,
+) -> Result<usize, SystemError> {
+    if !options.intersects(WaitOption::WEXITED | WaitOption::WSTOPPED | WaitOption::WCONTINUED) {
+        return Err(SystemError::EINVAL);
+    }
+
+    let (pid_type, pid) = match idtype {
+        WaitIdType::All => (PidType::MAX, Pid(0)),
+        WaitIdType::Pid => {
+            if id <= 0 {
+                return Err(SystemError::EINVAL);
+            }
+            // 用户态传入的是当前pid namespace中的pid
+            let pid = ProcessManager::vpid_to_pid(Pid(id as usize)).ok_or(SystemError::ECHILD)?;
+            (PidType::PID, pid)
+        }
+        WaitIdType::Pgid => {
+            if id < 0 {
+                return Err(SystemError::EINVAL);
+            }
+            // id为0时，等待与当前进程同一进程组的子进程
+            let pgid = if id == 0 {
+                ProcessManager::current_pcb().basic().pgid()
+            } else {
+                Pid(id as usize)
+            };
+            (PidType::PGID, pgid)
+        }
+        WaitIdType::Pidfd => {
+            if id < 0 {
+                return Err(SystemError::EINVAL);
+            }
+            let file = ProcessManager::current_pcb()
+                .fd_table()
+                .read()
+                .get_file_by_fd(id)
+                .ok_or(SystemError::EBADF)?;
+            let inode = file.inode();
+            let pidfd = as_pidfd(&inode).ok_or(SystemError::EINVAL)?;
+            (PidType::PID, pidfd.pid())
+        }
+    };
+
+    let mut kwo = KernelWaitOption::new(pid_type, pid, options);
+    kwo.ret_info = Some(WaitIdInfo {
+        pid: Pid(0),
+        status: 0,
+        cause: 0,
+    });
+    kwo.ret_rusage = rusage_buf;
+
+    let r = do_wait(&mut kwo)?;
+
+    if let Some(mut infop) = infop {
+        let info = match kwo.ret_info.take() {
+            Some(info) if r != 0 => PosixSigInfo::child_status(info.pid, info.cause, info.status),
+            _ => PosixSigInfo::zeroed(),
+        };
+        infop.copy_one_to_user(&info, 0)?;
+    }
+
+    return Ok(0);
+}
+
 /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/exit.c#1573
 fn do_wait(kwo: &mut KernelWaitOption) -> Result<usize, SystemError> {
-    let mut retval: Result<usize, SystemError>;
-    // todo: 在signal struct里面增加等待队列，并在这里初始化子进程退出的回调，使得子进程退出时，能唤醒当前进程。
+    let current_pcb = ProcessManager::current_pcb();
 
     loop {
         kwo.no_task_error = Some(SystemError::ECHILD);
-        let child_pcb = ProcessManager::find(kwo.pid).ok_or(SystemError::ECHILD);
-        if kwo.pid_type != PidType::MAX && child_pcb.is_err() {
-            if let Some(err) = &kwo.no_task_error {
-                retval = Err(err.clone());
-            } else {
-                retval = Ok(0);
-            }
 
-            if !kwo.options.contains(WaitOption::WNOHANG) {
-                retval = Err(SystemError::ERESTARTSYS);
-                if !ProcessManager::current_pcb()
-                    .sig_info_irqsave()
-                    .sig_pending()
-                    .has_pending()
-                {
-                    // todo: 增加子进程退出的回调后，这里可以直接等待在自身的child_wait等待队列上。
-                    continue;
-                } else {
-                    break;
-                }
-            } else {
-                break;
-            }
+        let mut no_children = false;
+        let mut ready = None;
+        // 在持有wait_chldexit队列的锁的情况下检查子进程的状态。
+        // 子进程退出或停止时，会在更新状态之后唤醒这个队列，
+        // 因此不会出现“检查完之后、睡眠之前子进程退出”而导致的唤醒丢失。
+        current_pcb.wait_chldexit.wait_event_interruptible(|| {
+            let children = wait_children(&current_pcb, kwo);
+            no_children = children.is_empty();
+            ready = children
+                .into_iter()
+                .find(|child| is_waitable(&current_pcb, child, kwo));
+            no_children || ready.is_some() || kwo.options.contains(WaitOption::WNOHANG)
+        })?;
+
+        if no_children {
+            return Err(kwo.no_task_error.take().unwrap());
         }
+        kwo.no_task_error = None;
 
-        if kwo.pid_type == PidType::PID {
-            let child_pcb = child_pcb.unwrap();
-            // 获取weak引用，以便于在do_waitpid中能正常drop pcb
-            let child_weak = Arc::downgrade(&child_pcb);
-            let r = do_waitpid(child_pcb, kwo);
-            if let Some(r) = r {
-                return r;
-            } else {
-                child_weak.upgrade().unwrap().wait_queue.sleep();
-            }
-        } else if kwo.pid_type == PidType::MAX {
-            // 等待任意子进程
-            // todo: 这里有问题！如果正在for循环的过程中，子进程退出了，可能会导致父进程永远等待。
-            let current_pcb = ProcessManager::current_pcb();
-            let rd_childen = current_pcb.children.read();
-            let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
-            for pid in rd_childen.iter() {
-                let pcb = ProcessManager::find(*pid).ok_or(SystemError::ECHILD)?;
-                let state = pcb.sched_info().inner_lock_read_irqsave().state();
-                if state.is_exited() {
-                    kwo.ret_status = state.exit_code().unwrap() as i32;
-                    drop(pcb);
-                    unsafe { ProcessManager::release(*pid) };
-                    return Ok((*pid).into());
-                } else {
-                    unsafe { pcb.wait_queue.sleep_without_schedule() };
+        match ready {
+            Some(child) => {
+                if let Some(r) = do_waitpid(child, kwo) {
+                    return r;
                 }
             }
-            drop(irq_guard);
-            schedule(SchedMode::SM_NONE);
-        } else {
-            // todo: 对于pgid的处理
-            kwarn!("kernel_wait4: currently not support {:?}", kwo.pid_type);
-            return Err(SystemError::EINVAL);
+            // WNOHANG，并且没有可以报告的子进程
+            None => return Ok(0),
         }
     }
+}
+
+/// 获取当前进程中，与等待条件相匹配的所有子进程，以及被当前进程跟踪的进程
+fn wait_children(
+    parent: &Arc<ProcessControlBlock>,
+    kwo: &KernelWaitOption,
+) -> Vec<Arc<ProcessControlBlock>> {
+    let mut children = parent.children();
+    for tracee in parent.ptraced() {
+        if !children.iter().any(|child| Arc::ptr_eq(child, &tracee)) {
+            children.push(tracee);
+        }
+    }
+
+    children
+        .into_iter()
+        .filter(|child| match kwo.pid_type {
+            PidType::PID => child.pid() == kwo.pid,
+            PidType::PGID => child.basic().pgid() == kwo.pid,
+            _ => true,
+        })
+        .collect()
+}
+
+/// 判断子进程当前的状态是否能被wait4所报告
+///
+/// 子进程只有在成为僵尸进程之后才会被当作已经退出，此时它的退出码已经可见。
+/// 被跟踪者的ptrace停止事件总是会报告给跟踪者，不需要WSTOPPED
+fn is_waitable(
+    parent: &Arc<ProcessControlBlock>,
+    child: &Arc<ProcessControlBlock>,
+    kwo: &KernelWaitOption,
+) -> bool {
+    if child.take_ptrace_stop(parent, false).is_some() {
+        return true;
+    }
+    // 其他事件只报告给父进程
+    if !is_real_child(parent, child) {
+        return false;
+    }
+    if child.exit_state() == ExitState::Zombie {
+        return kwo.options.contains(WaitOption::WEXITED);
+    }
+    if kwo.options.contains(WaitOption::WSTOPPED) && child.take_stop_signal(false).is_some() {
+        return true;
+    }
+    return kwo.options.contains(WaitOption::WCONTINUED) && child.take_continued(false);
+}
 
-    return retval;
+/// `child`是否是`parent`的子进程（而不仅仅是被`parent`跟踪）
+fn is_real_child(parent: &Arc<ProcessControlBlock>, child: &Arc<ProcessControlBlock>) -> bool {
+    parent.children.read_irqsave().contains(&child.pid())
 }
 
 fn do_waitpid(
     child_pcb: Arc<ProcessControlBlock>,
     kwo: &mut KernelWaitOption,
 ) -> Option<Result<usize, SystemError>> {
-    let state = child_pcb.sched_info().inner_lock_read_irqsave().state();
-    // 获取退出码
-    match state {
-        ProcessState::Runnable => {
-            if kwo.options.contains(WaitOption::WNOHANG)
-                || kwo.options.contains(WaitOption::WNOWAIT)
-            {
-                if let Some(info) = &mut kwo.ret_info {
-                    *info = WaitIdInfo {
-                        pid: child_pcb.pid(),
-                        status: Signal::SIGCONT as i32,
-                        cause: SigChildCode::Continued.into(),
-                    };
-                } else {
-                    kwo.ret_status = 0xffff;
-                }
+    let current_pcb = ProcessManager::current_pcb();
+    match child_pcb.exit_state() {
+        ExitState::Zombie if is_real_child(&current_pcb, &child_pcb) => {
+            return wait_zombie(child_pcb, kwo)
+        }
+        ExitState::Zombie | ExitState::Dead => return None,
+        ExitState::Running => {}
+    }
 
-                return Some(Ok(0));
-            }
+    // WNOWAIT只报告状态，事件仍然保留，之后还可以被等待
+    let consume = !kwo.options.contains(WaitOption::WNOWAIT);
+
+    // ptrace停止事件只报告给跟踪者
+    if let Some(sig) = child_pcb.take_ptrace_stop(&current_pcb, consume) {
+        kwo.ret_status = ((sig as i32) << 8) | 0x7f;
+        if let Some(infop) = &mut kwo.ret_info {
+            *infop = WaitIdInfo {
+                pid: child_pcb.pid_vnr(),
+                status: sig as i32,
+                cause: SigChildCode::Trapped.into(),
+            };
         }
-        ProcessState::Blocked(_) | ProcessState::Stopped => {
-            // todo: 在stopped里面，添加code字段，表示停止的原因
-            let exitcode = 0;
-            // 由于目前不支持ptrace，因此这个值为false
-            let ptrace = false;
-
-            if (!ptrace) && (!kwo.options.contains(WaitOption::WUNTRACED)) {
-                kwo.ret_status = 0;
-                return Some(Ok(0));
-            }
+        return Some(Ok(child_pcb.pid_vnr().data()));
+    }
 
-            if likely(!(kwo.options.contains(WaitOption::WNOWAIT))) {
-                kwo.ret_status = (exitcode << 8) | 0x7f;
-            }
+    // 线程组的停止、继续事件只报告给父进程
+    if !is_real_child(&current_pcb, &child_pcb) {
+        return None;
+    }
+
+    // 线程组的停止事件只有以WUNTRACED等待时才报告
+    if kwo.options.contains(WaitOption::WSTOPPED) {
+        if let Some(sig) = child_pcb.take_stop_signal(consume) {
+            kwo.ret_status = ((sig as i32) << 8) | 0x7f;
             if let Some(infop) = &mut kwo.ret_info {
                 *infop = WaitIdInfo {
-                    pid: child_pcb.pid(),
-                    status: exitcode,
+                    pid: child_pcb.pid_vnr(),
+                    status: sig as i32,
                     cause: SigChildCode::Stopped.into(),
                 };
             }
+            return Some(Ok(child_pcb.pid_vnr().data()));
+        }
+    }
 
-            return Some(Ok(child_pcb.pid().data()));
+    if kwo.options.contains(WaitOption::WCONTINUED) && child_pcb.take_continued(consume) {
+        kwo.ret_status = 0xffff;
+        if let Some(infop) = &mut kwo.ret_info {
+            *infop = WaitIdInfo {
+                pid: child_pcb.pid_vnr(),
+                status: Signal::SIGCONT as i32,
+                cause: SigChildCode::Continued.into(),
+            };
         }
-        ProcessState::Exited(status) => {
-            let pid = child_pcb.pid();
-            // kdebug!("wait4: child exited, pid: {:?}, status: {status}\n", pid);
+        return Some(Ok(child_pcb.pid_vnr().data()));
+    }
 
-            if likely(!kwo.options.contains(WaitOption::WEXITED)) {
-                return None;
-            }
+    // 进程还在运行，或者还在执行退出流程，等到它成为僵尸进程之后再报告
+    return None;
+}
 
-            // todo: 增加对线程组的group leader的处理
+/// 报告并回收一个僵尸子进程
+fn wait_zombie(
+    child_pcb: Arc<ProcessControlBlock>,
+    kwo: &mut KernelWaitOption,
+) -> Option<Result<usize, SystemError>> {
+    if likely(!kwo.options.contains(WaitOption::WEXITED)) {
+        return None;
+    }
 
-            if let Some(infop) = &mut kwo.ret_info {
-                *infop = WaitIdInfo {
-                    pid,
-                    status: status as i32,
-                    cause: SigChildCode::Exited.into(),
-                };
-            }
+    let pid = child_pcb.pid();
+    // 向用户态报告的是子进程在当前pid namespace中的pid
+    let vpid = child_pcb.pid_vnr();
+    let status = child_pcb.exit_code();
+    // kdebug!("wait4: child exited, pid: {:?}, status: {status}\n", pid);
 
-            kwo.ret_status = status as i32;
+    // todo: 增加对线程组的group leader的处理
 
-            drop(child_pcb);
-            // kdebug!("wait4: to release {pid:?}");
-            unsafe { ProcessManager::release(pid) };
-            return Some(Ok(pid.into()));
+    // WNOWAIT只报告状态，子进程仍然保持僵尸状态，之后还可以被等待。
+    // 否则其他线程可能同时在等待这个子进程，只有成功把它标记为已回收的线程才能报告它的退出
+    let reap = !kwo.options.contains(WaitOption::WNOWAIT);
+    if reap && !child_pcb.try_mark_dead() {
+        return None;
+    }
+
+    if let Some(infop) = &mut kwo.ret_info {
+        let (cause, status) = exit_cause(status);
+        *infop = WaitIdInfo {
+            pid: vpid,
+            status,
+            cause: cause.into(),
+        };
+    }
+
+    kwo.ret_status = status as i32;
+
+    // 报告的资源使用情况包括子进程已经回收的子进程
+    if let Some(ret_rusage) = kwo.ret_rusage.as_mut() {
+        if let Some(rusage) = child_pcb.get_rusage(RUsageWho::RUsageBoth) {
+            **ret_rusage = rusage;
         }
-    };
+    }
 
-    return None;
+    if !reap {
+        return Some(Ok(vpid.into()));
+    }
+
+    // 子进程及其已经回收的子进程消耗的cpu时间、缺页次数以及最大常驻集，计入当前进程
+    let (utime, stime) = child_pcb.cputime();
+    let (cutime, cstime) = child_pcb.stats().children_cputime();
+    let (min_flt, maj_flt) = child_pcb.faults();
+    let (cmin_flt, cmaj_flt) = child_pcb.stats().children_faults();
+    let current = ProcessManager::current_pcb();
+    let leader = ProcessManager::find(current.tgid()).unwrap_or(current);
+    leader
+        .stats()
+        .add_children_cputime((utime + cutime, stime + cstime));
+    leader
+        .stats()
+        .add_children_faults((min_flt + cmin_flt, maj_flt + cmaj_flt));
+    leader.stats().update_children_maxrss(core::cmp::max(
+        child_pcb.stats().maxrss(),
+        child_pcb.stats().children_maxrss(),
+    ));
+
+    let generation = child_pcb.pid_generation();
+    drop(child_pcb);
+    // 只回收之前找到的那个子进程，避免pid被复用时回收了别的进程
+    // kdebug!("wait4: to release {pid:?}");
+    if ProcessManager::find_checked(pid, generation).is_some() {
+        unsafe { ProcessManager::release(pid) };
+    }
+    return Some(Ok(vpid.into()));
+}
+
+/// 解析进程的退出码，得到进程退出的原因（CLD_EXITED、CLD_KILLED或者CLD_DUMPED），
+/// 以及退出码（正常退出时）或者导致进程终止的信号
+///
+/// 退出码的低7位为导致进程终止的信号，第7位表示是否产生了core dump，否则第8~15位为进程的退出码
+pub(super) fn exit_cause(status: usize) -> (SigChildCode, i32) {
+    let term_sig = (status & 0x7f) as i32;
+    if term_sig == 0 {
+        return (SigChildCode::Exited, ((status >> 8) & 0xff) as i32);
+    }
+    if status & 0x80 != 0 {
+        (SigChildCode::Dumped, term_sig)
+    } else {
+        (SigChildCode::Killed, term_sig)
+    }
 }
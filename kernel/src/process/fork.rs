@@ -1,28 +1,45 @@
-use core::{intrinsics::unlikely, sync::atomic::Ordering};
+use core::{
+    intrinsics::unlikely,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
 
-use alloc::{string::ToString, sync::Arc};
+use alloc::{collections::VecDeque, string::ToString, sync::Arc, vec::Vec};
 use system_error::SystemError;
 
 use crate::{
-    arch::{interrupt::TrapFrame, ipc::signal::Signal},
-    filesystem::procfs::procfs_register_pid,
-    ipc::signal::flush_signal_handlers,
-    libs::rwlock::RwLock,
-    mm::VirtAddr,
+    arch::{
+        interrupt::TrapFrame,
+        ipc::signal::{Signal, MAX_SIG_NUM},
+    },
+    ipc::{sem::SemUndoList, signal::flush_signal_handlers},
+    libs::{rwlock::RwLock, spinlock::SpinLock, wait_queue::WaitQueue},
+    mm::{oom::retry_on_oom, ucontext::AddressSpace, VirtAddr},
+    namespaces::{nsproxy::NsProxy, pid_namespace::UPid},
     process::ProcessFlags,
     sched::{sched_cgroup_fork, sched_fork},
     smp::core::smp_get_processor_id,
-    syscall::user_access::UserBufferWriter,
+    syscall::user_access::{UserBufferReader, UserBufferWriter},
+    time::{jiffies::NSEC_PER_JIFFY, timer::clock},
 };
 
 use super::{
+    hooks::call_fork_hooks,
     kthread::{KernelThreadPcbPrivate, WorkerPrivate},
+    resource::RLimitID,
     KernelStack, Pid, ProcessControlBlock, ProcessManager,
 };
 
 bitflags! {
-    /// 进程克隆标志
+    /// 进程克隆标志，取值与Linux相同
+    ///
+    /// 旧的clone系统调用只使用flags参数的低32位，并且其中的低8位（[`CSIGNAL`]）是子进程的退出信号，
+    /// 而不是标志位，因此需要在转换为CloneFlags之前将它分离出来。clone3通过单独的字段传递退出信号，
+    /// 从而可以使用这些位（目前只有`CLONE_NEWTIME`）以及高32位中的标志
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/sched.h#10
     pub struct CloneFlags: u64 {
+        /// 将其放置在一个新的time命名空间中（与[`CSIGNAL`]重叠，因此只能通过clone3、unshare使用）
+        const CLONE_NEWTIME = 0x00000080;
         /// 在进程间共享虚拟内存空间
         const CLONE_VM = 0x00000100;
         /// 在进程间共享文件系统信息
@@ -33,7 +50,7 @@ bitflags! {
         const CLONE_SIGHAND = 0x00000800;
         /// 返回进程的文件描述符
         const CLONE_PIDFD = 0x00001000;
-        /// 使克隆对象成为父进程的跟踪对象
+        /// 如果父进程正在被跟踪，则父进程的跟踪者同时跟踪子进程
         const CLONE_PTRACE = 0x00002000;
         /// 在执行 exec() 或 _exit() 之前挂起父进程的执行
         const CLONE_VFORK = 0x00004000;
@@ -43,7 +60,10 @@ bitflags! {
         const CLONE_THREAD = 0x00010000;
         /// 创建一个新的命名空间，其中包含独立的文件系统挂载点层次结构。
         const CLONE_NEWNS =	0x00020000;
-        /// 与父进程共享 System V 信号量。
+        /// 与父进程共享 System V 信号量的撤销列表（semadj）。
+        ///
+        /// 设置时，子进程与父进程共享同一个撤销列表，双方通过SEM_UNDO所做的调整都记录在其中，
+        /// 直到最后一个共享者退出时才会被撤销。未设置时，子进程拥有一个空的撤销列表
         const CLONE_SYSVSEM = 0x00040000;
         /// 设置其线程本地存储
         const CLONE_SETTLS = 0x00080000;
@@ -53,7 +73,7 @@ bitflags! {
         const CLONE_CHILD_CLEARTID = 0x00200000;
         /// 创建一个新线程，将其设置为分离状态
         const CLONE_DETACHED = 0x00400000;
-        /// 使其在创建者进程或线程视角下成为无法跟踪的。
+        /// 子进程不会被跟踪，即使父进程正在被跟踪，此时CLONE_PTRACE也会被忽略
         const CLONE_UNTRACED = 0x00800000;
         /// 设置其子进程线程 ID
         const CLONE_CHILD_SETTID = 0x01000000;
@@ -69,12 +89,135 @@ bitflags! {
         const CLONE_NEWPID = 0x20000000;
         /// 将其放置在一个新的网络命名空间中
         const CLONE_NEWNET = 0x40000000;
-        /// 在新的 I/O 上下文中运行它
+        /// 与父进程共享I/O上下文，块设备的I/O调度器将共享同一个I/O上下文的进程视为一个整体
         const CLONE_IO = 0x80000000;
         /// 克隆时，与父进程共享信号结构体
         const CLONE_SIGNAL = 0x00010000 | 0x00000800;
-        /// 克隆时，将原本被设置为SIG_IGNORE的信号，设置回SIG_DEFAULT
+        /// 子进程的信号处理函数表中，设置了处理函数的信号被恢复为SIG_DFL，被忽略（SIG_IGN）的信号保持忽略。
+        /// 不能与CLONE_SIGHAND同时使用
         const CLONE_CLEAR_SIGHAND = 0x100000000;
+        /// 将子进程放入clone3参数指定的cgroup中（仅clone3可用）
+        const CLONE_INTO_CGROUP = 0x200000000;
+    }
+}
+
+/// clone的flags参数中，低8位用于指定子进程退出时发送的信号
+pub const CSIGNAL: u64 = 0xff;
+
+/// 旧的clone系统调用可以使用的标志（flags参数的低32位中，除了退出信号以外的部分）
+pub const CLONE_LEGACY_FLAGS: u64 = 0xffffffff & !CSIGNAL;
+
+/// 从旧的clone系统调用的flags参数中，取出子进程退出时发送给父进程的信号
+///
+/// 为0或者不是合法的信号时返回`Signal::INVALID`，此时子进程退出时不会向父进程发送信号
+pub fn legacy_clone_exit_signal(raw_flags: u64) -> Signal {
+    let sig = (raw_flags & CSIGNAL) as usize;
+    if sig > MAX_SIG_NUM {
+        return Signal::INVALID;
+    }
+    return Signal::from(sig);
+}
+
+/// set_tid数组的最大长度（pid namespace的最大层数）
+pub const MAX_PID_NS_LEVEL: usize = 32;
+
+/// fork速率限制：在[`FORK_RATE_WINDOW_MS`]毫秒的滑动窗口内，最多允许创建的用户进程数
+///
+/// 为0时不限制（默认）。可以通过`/sys/kernel/fork_rate_limit`调整
+pub static FORK_RATE_LIMIT: AtomicUsize = AtomicUsize::new(0);
+/// fork速率限制的滑动窗口长度（毫秒）
+pub const FORK_RATE_WINDOW_MS: u64 = 1000;
+
+/// 最近的fork发生的时刻（jiffies），按照时间先后排列
+///
+/// TODO: 引入用户的概念之后，改为按用户分别统计
+static FORK_HISTORY: SpinLock<VecDeque<u64>> = SpinLock::new(VecDeque::new());
+
+/// clone3系统调用的用户态参数
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/sched.h#92
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PosixCloneArgs {
+    pub flags: u64,
+    pub pidfd: u64,
+    pub child_tid: u64,
+    pub parent_tid: u64,
+    pub exit_signal: u64,
+    pub stack: u64,
+    pub stack_size: u64,
+    pub tls: u64,
+    pub set_tid: u64,
+    pub set_tid_size: u64,
+    pub cgroup: u64,
+}
+
+impl PosixCloneArgs {
+    /// 第一个版本的结构体大小
+    pub const SIZE_VER0: usize = 64;
+    /// 增加了set_tid和set_tid_size
+    pub const SIZE_VER1: usize = 80;
+    /// 增加了cgroup
+    pub const SIZE_VER2: usize = 88;
+}
+
+impl TryFrom<PosixCloneArgs> for KernelCloneArgs {
+    type Error = SystemError;
+
+    /// 校验clone3的参数，并转换为内核的参数载体
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/fork.c#2927
+    fn try_from(args: PosixCloneArgs) -> Result<Self, Self::Error> {
+        // 退出信号通过exit_signal字段传递，flags中不能再包含它。
+        // CSIGNAL中只有与CLONE_NEWTIME重叠的位可以使用，CLONE_DETACHED则被保留
+        if args.flags
+            & (CloneFlags::CLONE_DETACHED.bits() | (CSIGNAL & !CloneFlags::CLONE_NEWTIME.bits()))
+            != 0
+        {
+            return Err(SystemError::EINVAL);
+        }
+        let flags = CloneFlags::from_bits(args.flags).ok_or(SystemError::EINVAL)?;
+
+        if args.set_tid_size as usize > MAX_PID_NS_LEVEL
+            || (args.set_tid == 0 && args.set_tid_size != 0)
+            || (args.set_tid != 0 && args.set_tid_size == 0)
+        {
+            return Err(SystemError::EINVAL);
+        }
+
+        if args.exit_signal & !CSIGNAL != 0 || args.exit_signal as usize > MAX_SIG_NUM {
+            return Err(SystemError::EINVAL);
+        }
+        let exit_signal = Signal::from(args.exit_signal as usize);
+        // 线程以及CLONE_PARENT创建的进程，退出时不会向调用者发送信号
+        if exit_signal != Signal::INVALID
+            && flags.intersects(CloneFlags::CLONE_THREAD | CloneFlags::CLONE_PARENT)
+        {
+            return Err(SystemError::EINVAL);
+        }
+
+        // stack与stack_size必须同时指定，或者同时不指定
+        if (args.stack == 0) != (args.stack_size == 0) {
+            return Err(SystemError::EINVAL);
+        }
+
+        let mut kargs = KernelCloneArgs::new();
+        kargs.flags = flags;
+        kargs.pidfd = VirtAddr::new(args.pidfd as usize);
+        kargs.child_tid = VirtAddr::new(args.child_tid as usize);
+        kargs.parent_tid = VirtAddr::new(args.parent_tid as usize);
+        kargs.set_tid = VirtAddr::new(args.set_tid as usize);
+        kargs.exit_signal = exit_signal;
+        // 栈向下增长，因此子进程的栈指针位于栈的最高处
+        kargs.stack = (args.stack as usize)
+            .checked_add(args.stack_size as usize)
+            .ok_or(SystemError::EINVAL)?;
+        kargs.stack_size = args.stack_size as usize;
+        kargs.tls = args.tls as usize;
+        kargs.set_tid_size = args.set_tid_size as usize;
+        kargs.cgroup = args.cgroup as i32;
+
+        return Ok(kargs);
     }
 }
 
@@ -109,6 +252,8 @@ pub struct KernelCloneArgs {
     pub idle: bool,
     pub func: VirtAddr,
     pub fn_arg: VirtAddr,
+    /// 新进程马上就会执行execve（见[`ProcessManager::spawn`]），不需要拷贝父进程的地址空间
+    pub spawn: bool,
     // cgrp 和 cset?
 }
 
@@ -132,11 +277,239 @@ impl KernelCloneArgs {
             idle: false,
             func: null_addr,
             fn_arg: null_addr,
+            spawn: false,
+        }
+    }
+
+    /// 从clone3的set_tid数组中，读取调用者为新进程指定的pid
+    ///
+    /// set_tid数组的第i个元素，对应新进程在第i层pid namespace中的pid（从新进程所在的namespace开始向外数）。
+    /// 目前只支持为位于初始pid namespace中的新进程指定pid
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(None)`：没有指定pid
+    /// - `Ok(Some(pid))`：调用者指定的pid
+    /// - `EINVAL`：set_tid数组的长度超过了pid namespace的层数，或者pid超出了合法的范围
+    pub fn requested_pid(&self) -> Result<Option<Pid>, SystemError> {
+        if self.set_tid_size == 0 {
+            return Ok(None);
+        }
+        if self.set_tid_size > 1 {
+            return Err(SystemError::EINVAL);
+        }
+
+        let reader = UserBufferReader::new(
+            self.set_tid.data() as *const i32,
+            core::mem::size_of::<i32>() * self.set_tid_size,
+            true,
+        )?;
+        let tid = *reader.read_one_from_user::<i32>(0)?;
+        if tid <= 0 || tid as usize >= ProcessManager::pid_max() {
+            return Err(SystemError::EINVAL);
         }
+
+        return Ok(Some(Pid::new(tid as usize)));
+    }
+}
+
+/// vfork的父进程等待子进程不再使用它的地址空间（execve或者退出）
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/fork.c#1442
+#[derive(Debug)]
+pub struct VforkDone {
+    done: AtomicBool,
+    wait_queue: WaitQueue,
+}
+
+impl VforkDone {
+    pub fn new() -> Self {
+        Self {
+            done: AtomicBool::new(false),
+            wait_queue: WaitQueue::default(),
+        }
+    }
+
+    /// 子进程不再使用父进程的地址空间，唤醒父进程
+    pub fn complete(&self) {
+        self.done.store(true, Ordering::SeqCst);
+        self.wait_queue.wakeup_all(None);
+    }
+
+    /// 等待子进程调用[`VforkDone::complete`]
+    ///
+    /// 子进程可能正在使用父进程的用户栈，因此父进程在此之前不能返回用户态，
+    /// 等待只会被致命信号打断（此时父进程不会再返回用户态）
+    ///
+    /// ## 返回值
+    ///
+    /// - `Err(SystemError::ERESTARTSYS)`：等待期间父进程收到了致命信号
+    pub fn wait(&self) -> Result<(), SystemError> {
+        return self
+            .wait_queue
+            .wait_event_killable(|| self.done.load(Ordering::SeqCst));
     }
 }
 
 impl ProcessManager {
+    /// 检查clone标志位的组合是否合法
+    ///
+    /// 这个函数只检查标志位本身，不依赖于当前进程的状态，因此需要在分配新进程的pcb之前调用，
+    /// 以便尽早地拒绝非法的请求。被拒绝的组合如下：
+    ///
+    /// - `CLONE_NEWNS | CLONE_FS`、`CLONE_NEWUSER | CLONE_FS`：不允许与处于不同namespace的进程共享根目录
+    /// - `CLONE_THREAD`而没有`CLONE_SIGHAND`：同一个线程组中的线程必须共享信号处理函数
+    /// - `CLONE_SIGHAND`而没有`CLONE_VM`：信号处理函数的地址只在同一个地址空间中有意义
+    /// - `CLONE_SIGHAND | CLONE_CLEAR_SIGHAND`：共享的信号处理函数不能被重置
+    /// - `CLONE_THREAD`与`CLONE_NEWUSER`或者`CLONE_NEWPID`：新的namespace中的进程不能与调用者处于同一个线程组
+    /// - `CLONE_PIDFD`与`CLONE_DETACHED`或者`CLONE_THREAD`：pidfd只能指向一个进程，而不能是线程
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL`：标志位的组合不合法
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/fork.c#2008
+    pub fn validate_clone_flags(clone_flags: CloneFlags) -> Result<(), SystemError> {
+        if clone_flags.contains(CloneFlags::CLONE_NEWNS | CloneFlags::CLONE_FS)
+            || clone_flags.contains(CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_FS)
+        {
+            return Err(SystemError::EINVAL);
+        }
+
+        if clone_flags.contains(CloneFlags::CLONE_THREAD)
+            && !clone_flags.contains(CloneFlags::CLONE_SIGHAND)
+        {
+            return Err(SystemError::EINVAL);
+        }
+
+        if clone_flags.contains(CloneFlags::CLONE_SIGHAND)
+            && !clone_flags.contains(CloneFlags::CLONE_VM)
+        {
+            return Err(SystemError::EINVAL);
+        }
+
+        if clone_flags.contains(CloneFlags::CLONE_SIGHAND | CloneFlags::CLONE_CLEAR_SIGHAND) {
+            return Err(SystemError::EINVAL);
+        }
+
+        if clone_flags.contains(CloneFlags::CLONE_THREAD)
+            && clone_flags.intersects(CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWPID)
+        {
+            return Err(SystemError::EINVAL);
+        }
+
+        if clone_flags.contains(CloneFlags::CLONE_PIDFD)
+            && clone_flags.intersects(CloneFlags::CLONE_DETACHED | CloneFlags::CLONE_THREAD)
+        {
+            return Err(SystemError::EINVAL);
+        }
+
+        return Ok(());
+    }
+
+    /// 让当前进程不再与其他进程共享`flags`所指定的资源（unshare）
+    ///
+    /// 与fork使用相同的copy_*函数，只是源进程与目标进程都是当前进程：
+    ///
+    /// - `CLONE_FILES`：拷贝文件描述符表
+    /// - `CLONE_FS`：拷贝文件系统上下文
+    /// - `CLONE_SYSVSEM`：使用一个新的空的System V信号量撤销列表
+    /// - `CLONE_NEWUTS`、`CLONE_NEWNS`：当前进程进入一个新的namespace，`CLONE_NEWNS`同时意味着`CLONE_FS`
+    /// - `CLONE_NEWPID`：当前进程自身的pid不变，之后创建的子进程位于一个新的pid namespace中
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL`：`flags`中含有不支持的标志，或者使用`CLONE_NEWPID`时，
+    ///   之前的unshare或者setns已经为子进程指定了另一个pid namespace
+    /// - `ENOSPC`：pid namespace的层数超过了上限
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/fork.c#3178
+    pub fn unshare(mut flags: CloneFlags) -> Result<(), SystemError> {
+        let supported = CloneFlags::CLONE_FILES
+            | CloneFlags::CLONE_FS
+            | CloneFlags::CLONE_SYSVSEM
+            | CloneFlags::CLONE_NEWUTS
+            | CloneFlags::CLONE_NEWNS
+            | CloneFlags::CLONE_NEWPID;
+        if !supported.contains(flags) {
+            return Err(SystemError::EINVAL);
+        }
+        // 新的mount namespace中的进程不能与其他mount namespace中的进程共享根目录和工作目录
+        if flags.contains(CloneFlags::CLONE_NEWNS) {
+            flags |= CloneFlags::CLONE_FS;
+        }
+
+        let pcb = ProcessManager::current_pcb();
+
+        // 先完成可能失败的操作，避免只unshare了一部分资源
+        if flags.contains(CloneFlags::CLONE_NEWPID)
+            && !Arc::ptr_eq(&pcb.pid_ns(), &pcb.pid_ns_for_children())
+        {
+            return Err(SystemError::EINVAL);
+        }
+        Self::copy_namespaces(&flags, &pcb, &pcb)?;
+
+        // copy_*函数的CLONE_FILES、CLONE_FS、CLONE_SYSVSEM表示共享，因此不传入这些标志，从而得到一份拷贝
+        if flags.contains(CloneFlags::CLONE_FILES) {
+            Self::copy_files(&CloneFlags::empty(), &pcb, &pcb)?;
+        }
+        if flags.contains(CloneFlags::CLONE_FS) {
+            Self::copy_fs(&CloneFlags::empty(), &pcb, &pcb)?;
+        }
+        if flags.contains(CloneFlags::CLONE_SYSVSEM) {
+            Self::copy_sysvsem(&CloneFlags::empty(), &pcb, &pcb)?;
+        }
+
+        return Ok(());
+    }
+
+    /// fork速率限制是否作用于这次clone
+    ///
+    /// 用于防止fork炸弹在RLIMIT_NPROC生效之前耗尽pid和内存。
+    /// 内核线程的创建以及同一线程组内创建线程（CLONE_THREAD）不受限制
+    fn fork_rate_limited(current_pcb: &Arc<ProcessControlBlock>, clone_flags: CloneFlags) -> bool {
+        return FORK_RATE_LIMIT.load(Ordering::Relaxed) != 0
+            && !current_pcb.flags().contains(ProcessFlags::KTHREAD)
+            && !clone_flags.contains(CloneFlags::CLONE_THREAD);
+    }
+
+    /// 从fork的历史记录中，去掉已经不在滑动窗口内的记录
+    fn fork_history_expire(history: &mut VecDeque<u64>, now: u64) {
+        let window = FORK_RATE_WINDOW_MS * 1000000 / NSEC_PER_JIFFY as u64;
+        while history
+            .front()
+            .is_some_and(|&stamp| stamp.saturating_add(window) <= now)
+        {
+            history.pop_front();
+        }
+    }
+
+    /// 检查fork的速率是否超过了限制
+    ///
+    /// 只有fork成功之后，才会通过[`Self::fork_rate_record`]记录本次fork，
+    /// 因此并发的fork可能使窗口内的进程数稍微超过限制
+    ///
+    /// ## 返回值
+    ///
+    /// - `EAGAIN`：在最近的[`FORK_RATE_WINDOW_MS`]毫秒内，已经创建了[`FORK_RATE_LIMIT`]个进程
+    fn fork_rate_check() -> Result<(), SystemError> {
+        let limit = FORK_RATE_LIMIT.load(Ordering::Relaxed);
+        let mut history = FORK_HISTORY.lock_irqsave();
+        Self::fork_history_expire(&mut history, clock());
+        if limit != 0 && history.len() >= limit {
+            return Err(SystemError::EAGAIN_OR_EWOULDBLOCK);
+        }
+        return Ok(());
+    }
+
+    /// 在fork成功之后，把本次fork记录到滑动窗口中
+    fn fork_rate_record() {
+        let now = clock();
+        let mut history = FORK_HISTORY.lock_irqsave();
+        Self::fork_history_expire(&mut history, now);
+        history.push_back(now);
+    }
+
     /// 创建一个新进程
     ///
     /// ## 参数
@@ -156,17 +529,101 @@ impl ProcessManager {
         current_trapframe: &TrapFrame,
         clone_flags: CloneFlags,
     ) -> Result<Pid, SystemError> {
+        let mut args = KernelCloneArgs::new();
+        args.flags = clone_flags;
+        args.exit_signal = Signal::SIGCHLD;
+        let pcb = Self::create_process(args, current_trapframe, |_| Ok(()))?;
+        return Ok(pcb.pid());
+    }
+
+    /// 创建一个内核线程
+    ///
+    /// 内核线程不会返回用户态，因此不需要拷贝调用者的栈帧：它从内核线程的入口跳板函数开始执行，
+    /// 跳板函数再以`fn_arg`为参数调用[`kernel_thread_bootstrap_stage2`](super::kthread::kernel_thread_bootstrap_stage2)
+    ///
+    /// ## 参数
+    ///
+    /// - `clone_flags`: 克隆标志
+    /// - `fn_arg`: 传递给入口函数的参数，是一个指向`KernelThreadCreateInfo`的指针
+    /// - `setup`: 在新的内核线程可以被调度之前，对它的pcb进行设置（例如设置名字）
+    ///
+    /// ## 返回值
+    ///
+    /// 返回新创建的内核线程的pcb
+    pub fn fork_kernel_thread<F>(
+        clone_flags: CloneFlags,
+        fn_arg: VirtAddr,
+        setup: F,
+    ) -> Result<Arc<ProcessControlBlock>, SystemError>
+    where
+        F: FnOnce(&Arc<ProcessControlBlock>),
+    {
+        assert!(
+            ProcessManager::current_pcb()
+                .flags()
+                .contains(ProcessFlags::KTHREAD),
+            "kernel threads can only be created by kernel threads"
+        );
+        let mut args = KernelCloneArgs::new();
+        args.flags = clone_flags;
+        args.exit_signal = Signal::SIGCHLD;
+        args.kthread = true;
+        args.fn_arg = fn_arg;
+        // copy_thread不会使用这个栈帧
+        return Self::create_process(args, &TrapFrame::new(), |pcb| {
+            setup(pcb);
+            Ok(())
+        });
+    }
+
+    /// 创建一个新进程，并返回它的pcb
+    ///
+    /// 与[`ProcessManager::fork`]不同，调用者可以直接得到新进程的pcb，而不需要再通过pid查找它，
+    /// 并且可以在新进程加入进程表、可以被调度之前，通过`setup`对它进行设置
+    ///
+    /// ## 参数
+    ///
+    /// - `args`: 克隆参数
+    /// - `current_trapframe`: 当前进程的trapframe，新进程返回用户态时从它的拷贝恢复上下文（内核线程不使用）
+    /// - `setup`: 在新进程加入进程表之前调用，此时新进程还不能被其他进程找到，也不会被调度。
+    ///     它返回错误时，新进程不会被创建。`setup`之后的步骤也可能失败，此时调用者需要自行撤销`setup`所做的修改
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：返回新进程的pcb，新进程已经被唤醒
+    /// - 失败：返回Err(SystemError)，新进程不会执行
+    pub fn create_process<F>(
+        args: KernelCloneArgs,
+        current_trapframe: &TrapFrame,
+        setup: F,
+    ) -> Result<Arc<ProcessControlBlock>, SystemError>
+    where
+        F: FnOnce(&Arc<ProcessControlBlock>) -> Result<(), SystemError>,
+    {
+        Self::validate_clone_flags(args.flags)?;
+
         let current_pcb = ProcessManager::current_pcb();
+        let rate_limited = Self::fork_rate_limited(&current_pcb, args.flags);
+
+        // clone3可以通过set_tid为新进程指定pid
+        let requested_pid = args.requested_pid()?;
+        // 指定的是全局pid，因此新进程必须位于初始pid namespace中
+        if requested_pid.is_some()
+            && (args.flags.contains(CloneFlags::CLONE_NEWPID)
+                || current_pcb.pid_ns_for_children().level() > 0)
+        {
+            return Err(SystemError::EINVAL);
+        }
 
         let new_kstack: KernelStack = KernelStack::new()?;
 
         let name = current_pcb.basic().name().to_string();
 
-        let pcb = ProcessControlBlock::new(name, new_kstack);
+        let pcb = match requested_pid {
+            Some(pid) => ProcessControlBlock::new_with_pid(name, new_kstack, pid)?,
+            None => ProcessControlBlock::new(name, new_kstack)?,
+        };
 
-        let mut args = KernelCloneArgs::new();
-        args.flags = clone_flags;
-        args.exit_signal = Signal::SIGCHLD;
         Self::copy_process(&current_pcb, &pcb, args, current_trapframe).map_err(|e| {
             kerror!(
                 "fork: Failed to copy process, current pid: [{:?}], new pid: [{:?}]. Error: {:?}",
@@ -176,20 +633,20 @@ impl ProcessManager {
             );
             e
         })?;
+        setup(&pcb)?;
         ProcessManager::add_pcb(pcb.clone());
 
-        // 向procfs注册进程
-        procfs_register_pid(pcb.pid()).unwrap_or_else(|e| {
-            panic!(
-                "fork: Failed to register pid to procfs, pid: [{:?}]. Error: {:?}",
-                pcb.pid(),
-                e
-            )
-        });
+        // 通知需要为每个进程维护状态的子系统（例如procfs）
+        Self::register_forked_pcb(&pcb)?;
+
+        if rate_limited {
+            Self::fork_rate_record();
+        }
 
-        pcb.sched_info().set_on_cpu(Some(smp_get_processor_id()));
+        let cpu = ProcessManager::select_task_cpu(&pcb, smp_get_processor_id());
+        pcb.sched_info().set_on_cpu(Some(cpu));
 
-        ProcessManager::wakeup(&pcb).unwrap_or_else(|e| {
+        ProcessManager::wake_up_new_task(&pcb).unwrap_or_else(|e| {
             panic!(
                 "fork: Failed to wakeup new process, pid: [{:?}]. Error: {:?}",
                 pcb.pid(),
@@ -197,17 +654,65 @@ impl ProcessManager {
             )
         });
 
-        return Ok(pcb.pid());
+        return Ok(pcb);
+    }
+
+    /// 为新创建的进程调用进程生命周期钩子（见[`super::hooks`]）
+    ///
+    /// 某个钩子失败时，已经调用过的钩子会被撤销，并且会撤销fork对全局状态的修改（进程表、线程组），
+    /// 新进程的pcb随后被释放时，会释放它的内核栈
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb`: 新进程的pcb，必须已经通过`ProcessManager::add_pcb`加入进程表
+    fn register_forked_pcb(pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+        let err = match call_fork_hooks(pcb) {
+            Ok(_) => return Ok(()),
+            Err(e) => e,
+        };
+
+        if !pcb.is_thread_group_leader() {
+            if let Some(leader) = pcb.thread.read_irqsave().group_leader() {
+                leader
+                    .thread_group
+                    .write_irqsave()
+                    .retain(|pid| *pid != pcb.pid());
+            }
+        }
+        unsafe { ProcessManager::release(pcb.pid()) };
+
+        return Err(err);
     }
 
     fn copy_flags(
         clone_flags: &CloneFlags,
         new_pcb: &Arc<ProcessControlBlock>,
     ) -> Result<(), SystemError> {
+        // 先继承父进程的标志位，再去掉那些只描述父进程自身当前状态的标志位。
+        // KTHREAD仍然会被继承：只有内核线程才会带有这个标志，而它创建的子进程
+        // 也需要从内核态开始运行（见copy_thread），用户进程的子进程不会带有它
+        let mut flags = *ProcessManager::current_pcb().flags();
+        flags.remove(
+            ProcessFlags::NEED_SCHEDULE
+                | ProcessFlags::VFORK
+                | ProcessFlags::EXITING
+                | ProcessFlags::WAKEKILL
+                | ProcessFlags::SIGNALED
+                | ProcessFlags::NEED_MIGRATE
+                | ProcessFlags::NEED_RSEQ
+                | ProcessFlags::NEED_SET_CHILD_TID
+                | ProcessFlags::NEED_SPAWN_EXEC,
+        );
         if clone_flags.contains(CloneFlags::CLONE_VM) {
-            new_pcb.flags().insert(ProcessFlags::VFORK);
+            flags.insert(ProcessFlags::VFORK);
         }
-        *new_pcb.flags.get_mut() = *ProcessManager::current_pcb().flags();
+        if clone_flags.contains(CloneFlags::CLONE_CHILD_SETTID) {
+            flags.insert(ProcessFlags::NEED_SET_CHILD_TID);
+        }
+        flags.insert(ProcessFlags::FORKNOEXEC);
+        // 在创建者调用wake_up_new_task之前，新进程不能被其他地方唤醒
+        flags.insert(ProcessFlags::NEW);
+        *new_pcb.flags.get_mut() = flags;
         return Ok(());
     }
 
@@ -216,6 +721,7 @@ impl ProcessManager {
     /// ## 参数
     ///
     /// - `clone_vm`: 是否与父进程共享地址空间。true表示共享
+    /// - `spawn`: 新进程马上就会执行execve，只需要为它创建一个空的地址空间
     /// - `new_pcb`: 新进程的pcb
     ///
     /// ## 返回值
@@ -223,15 +729,25 @@ impl ProcessManager {
     /// - 成功：返回Ok(())
     /// - 失败：返回Err(SystemError)
     ///
+    /// - 为新地址空间分配内存失败时，返回Err(SystemError::ENOMEM)
+    ///
     /// ## Panic
     ///
     /// - 如果当前进程没有用户地址空间，则panic
     #[inline(never)]
     fn copy_mm(
         clone_flags: &CloneFlags,
+        spawn: bool,
         current_pcb: &Arc<ProcessControlBlock>,
         new_pcb: &Arc<ProcessControlBlock>,
     ) -> Result<(), SystemError> {
+        if spawn {
+            // execve会创建新的地址空间，这里的地址空间只在execve之前占位，不需要任何映射
+            let new_address_space = AddressSpace::new(false)?;
+            unsafe { new_pcb.basic_mut().set_user_vm(Some(new_address_space)) };
+            return Ok(());
+        }
+
         let old_address_space = current_pcb.basic().user_vm().unwrap_or_else(|| {
             panic!(
                 "copy_mm: Failed to get address space of current process, current pid: [{:?}]",
@@ -243,12 +759,15 @@ impl ProcessManager {
             unsafe { new_pcb.basic_mut().set_user_vm(Some(old_address_space)) };
             return Ok(());
         }
-        let new_address_space = old_address_space.write_irqsave().try_clone().unwrap_or_else(|e| {
-            panic!(
+        // 内存不足时，由OOM killer杀死一个进程来释放内存之后重试
+        let new_address_space =
+            retry_on_oom(|| old_address_space.write_irqsave().try_clone()).map_err(|e| {
+            kerror!(
                 "copy_mm: Failed to clone address space of current process, current pid: [{:?}], new pid: [{:?}]. Error: {:?}",
                 current_pcb.pid(), new_pcb.pid(), e
-            )
-        });
+            );
+            e
+        })?;
         unsafe { new_pcb.basic_mut().set_user_vm(Some(new_address_space)) };
         return Ok(());
     }
@@ -274,28 +793,206 @@ impl ProcessManager {
         return Ok(());
     }
 
-    #[allow(dead_code)]
-    fn copy_sighand(
+    /// 拷贝进程的文件系统上下文（根目录、工作目录、umask）
+    ///
+    /// ## 参数
+    ///
+    /// - `clone_flags`: 克隆标志，若包含CLONE_FS，则与父进程共享文件系统上下文
+    /// - `current_pcb`: 父进程的pcb
+    /// - `new_pcb`: 新进程的pcb
+    #[inline(never)]
+    fn copy_fs(
         clone_flags: &CloneFlags,
         current_pcb: &Arc<ProcessControlBlock>,
         new_pcb: &Arc<ProcessControlBlock>,
     ) -> Result<(), SystemError> {
-        // // 将信号的处理函数设置为default(除了那些被手动屏蔽的)
-        if clone_flags.contains(CloneFlags::CLONE_CLEAR_SIGHAND) {
-            flush_signal_handlers(new_pcb.clone(), false);
+        let current_fs = current_pcb.basic().fs();
+        if clone_flags.contains(CloneFlags::CLONE_FS) {
+            // 共享文件系统上下文，子进程chdir时父进程也能看到
+            new_pcb.basic_mut().set_fs(current_fs);
+        } else {
+            let new_fs = current_fs.read().clone();
+            new_pcb.basic_mut().set_fs(Arc::new(RwLock::new(new_fs)));
+        }
+        return Ok(());
+    }
+
+    /// 拷贝System V信号量的撤销列表
+    ///
+    /// ## 参数
+    ///
+    /// - `clone_flags`: 克隆标志，若包含CLONE_SYSVSEM，则与父进程共享撤销列表
+    /// - `current_pcb`: 父进程的pcb
+    /// - `new_pcb`: 新进程的pcb
+    fn copy_sysvsem(
+        clone_flags: &CloneFlags,
+        current_pcb: &Arc<ProcessControlBlock>,
+        new_pcb: &Arc<ProcessControlBlock>,
+    ) -> Result<(), SystemError> {
+        if clone_flags.contains(CloneFlags::CLONE_SYSVSEM) {
+            new_pcb.set_sysvsem(current_pcb.sysvsem());
+        } else {
+            new_pcb.set_sysvsem(Arc::new(SemUndoList::new()));
+        }
+        return Ok(());
+    }
+
+    /// 拷贝I/O上下文
+    ///
+    /// ## 参数
+    ///
+    /// - `clone_flags`: 克隆标志，若包含CLONE_IO，则与父进程共享I/O上下文（包括I/O优先级），
+    ///   否则子进程得到一个新的I/O上下文，它只继承父进程的I/O优先级
+    /// - `current_pcb`: 父进程的pcb
+    /// - `new_pcb`: 新进程的pcb
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/fork.c#1620
+    fn copy_io(
+        clone_flags: &CloneFlags,
+        current_pcb: &Arc<ProcessControlBlock>,
+        new_pcb: &Arc<ProcessControlBlock>,
+    ) -> Result<(), SystemError> {
+        if clone_flags.contains(CloneFlags::CLONE_IO) {
+            new_pcb.set_io_context(current_pcb.io_context());
+        } else {
+            new_pcb.set_io_context(current_pcb.io_context().fork());
+        }
+        return Ok(());
+    }
+
+    /// 在新进程所在的pid namespace以及它的各层祖先namespace中为新进程分配pid
+    ///
+    /// 新进程位于它的nsproxy中的pid_ns_for_children中（见[`ProcessManager::copy_namespaces`]）。
+    /// 使用CLONE_NEWPID时，这是一个新的namespace，新进程成为这个namespace的init进程（pid为1）。
+    /// 第0层（初始namespace）的pid在创建pcb时就已经分配。
+    ///
+    /// ## 参数
+    ///
+    /// - `clone_flags`: 克隆标志
+    /// - `current_pcb`: 父进程的pcb
+    /// - `new_pcb`: 新进程的pcb
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL`：使用CLONE_THREAD创建线程，但当前进程的pid_ns_for_children不是它所在的namespace
+    /// - `ENOSPC`：pid namespace的层数超过了上限
+    /// - `ENOMEM`：新进程所在的namespace的init进程已经退出
+    fn copy_pid(
+        clone_flags: &CloneFlags,
+        current_pcb: &Arc<ProcessControlBlock>,
+        new_pcb: &Arc<ProcessControlBlock>,
+    ) -> Result<(), SystemError> {
+        let ns = new_pcb.pid_ns_for_children();
+        // 同一个线程组中的线程必须位于同一个pid namespace中
+        if clone_flags.contains(CloneFlags::CLONE_THREAD)
+            && !Arc::ptr_eq(&ns, &current_pcb.pid_ns())
+        {
+            return Err(SystemError::EINVAL);
+        }
+
+        // 从第1层开始，依次在每一层namespace中分配pid
+        let mut chain = Vec::new();
+        let mut cur = Some(&ns);
+        while let Some(n) = cur.filter(|n| n.level() > 0) {
+            chain.push(n.clone());
+            cur = n.parent();
+        }
+        for n in chain.into_iter().rev() {
+            // 分配失败时，已经分配的pid会在新进程的pcb被释放时归还
+            let nr = n.alloc_pid(new_pcb.pid())?;
+            if nr == Pid(1) {
+                n.set_child_reaper(new_pcb);
+            }
+            new_pcb.pid_links.write_irqsave().push(UPid::new(n, nr));
+        }
+        return Ok(());
+    }
+
+    /// 拷贝namespace
+    ///
+    /// 若`clone_flags`中没有CLONE_NEW*标志，则新进程与父进程共享同一个nsproxy，
+    /// 否则新进程得到一个新的nsproxy，其中只有对应标志被设置的namespace被替换
+    ///
+    /// ## 参数
+    ///
+    /// - `clone_flags`: 克隆标志
+    /// - `current_pcb`: 父进程的pcb
+    /// - `new_pcb`: 新进程的pcb
+    ///
+    /// ## 返回值
+    ///
+    /// - `ENOSPC`：pid namespace的层数超过了上限
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/nsproxy.c#151
+    fn copy_namespaces(
+        clone_flags: &CloneFlags,
+        current_pcb: &Arc<ProcessControlBlock>,
+        new_pcb: &Arc<ProcessControlBlock>,
+    ) -> Result<(), SystemError> {
+        let nsproxy = current_pcb.nsproxy();
+        if !clone_flags.intersects(NsProxy::supported_clone_flags()) {
+            new_pcb.set_nsproxy(nsproxy);
+            return Ok(());
         }
 
+        let new_nsproxy = nsproxy.create_new_namespaces(clone_flags)?;
+        new_pcb.set_nsproxy(Arc::new(new_nsproxy));
+        return Ok(());
+    }
+
+    /// 拷贝资源限制，子进程总是继承父进程的资源限制，以及OOM评分的调整值
+    ///
+    /// ## 参数
+    ///
+    /// - `current_pcb`: 父进程的pcb
+    /// - `new_pcb`: 新进程的pcb
+    fn copy_rlimit(
+        current_pcb: &Arc<ProcessControlBlock>,
+        new_pcb: &Arc<ProcessControlBlock>,
+    ) -> Result<(), SystemError> {
+        *new_pcb.rlimits.write_irqsave() = current_pcb.rlimits();
+        new_pcb.set_oom_score_adj(current_pcb.oom_score_adj());
+        return Ok(());
+    }
+
+    /// 拷贝信号处理函数
+    ///
+    /// ## 参数
+    ///
+    /// - `clone_flags`: 克隆标志，若包含CLONE_SIGHAND，则与父进程共享信号处理结构体
+    /// - `current_pcb`: 父进程的pcb
+    /// - `new_pcb`: 新进程的pcb
+    fn copy_sighand(
+        clone_flags: &CloneFlags,
+        current_pcb: &Arc<ProcessControlBlock>,
+        new_pcb: &Arc<ProcessControlBlock>,
+    ) -> Result<(), SystemError> {
         if clone_flags.contains(CloneFlags::CLONE_SIGHAND) {
-            new_pcb.sig_struct_irqsave().handlers = current_pcb.sig_struct_irqsave().handlers;
+            // 与父进程共享同一个信号处理函数表
+            let current_handler = current_pcb.sig_struct_irqsave().share_handlers();
+            new_pcb.sig_struct_irqsave().set_handlers(current_handler);
+            return Ok(());
+        }
+
+        // 否则，拷贝一份父进程的信号处理函数表。
+        // 无论父进程的表中记录了什么，子进程的SIGKILL、SIGSTOP都必须保持默认处理方式
+        let mut new_handler = current_pcb.sig_struct_irqsave().handlers().clone();
+        new_handler.reset_kernel_only_actions();
+        new_pcb
+            .sig_struct_irqsave()
+            .set_handlers(Arc::new(RwLock::new(new_handler)));
+
+        // 设置了处理函数的信号恢复为默认处理方式，被忽略的信号保持忽略
+        if clone_flags.contains(CloneFlags::CLONE_CLEAR_SIGHAND) {
+            flush_signal_handlers(new_pcb.clone(), false);
         }
         return Ok(());
     }
 
     /// 拷贝进程信息
     ///
-    /// ## panic:
-    /// 某一步拷贝失败时会引发panic
-    /// 例如：copy_mm等失败时会触发panic
+    /// 某一步拷贝失败时，会直接返回错误。此时新进程尚未加入进程表，
+    /// 调用者只需丢弃新进程的pcb，即可释放它占用的资源
     ///
     /// ## 参数
     ///
@@ -313,50 +1010,32 @@ impl ProcessManager {
         current_trapframe: &TrapFrame,
     ) -> Result<(), SystemError> {
         let clone_flags = clone_args.flags;
-        // 不允许与不同namespace的进程共享根目录
-        if (clone_flags == (CloneFlags::CLONE_NEWNS | CloneFlags::CLONE_FS))
-            || clone_flags == (CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_FS)
-        {
+        // init进程的兄弟进程退出后，没有进程能够回收它们（init进程的父进程是idle进程，
+        // 或者位于pid namespace之外），因此不允许init进程使用CLONE_PARENT创建兄弟进程
+        if clone_flags.contains(CloneFlags::CLONE_PARENT) && current_pcb.is_pid_ns_init() {
             return Err(SystemError::EINVAL);
         }
 
-        // 线程组必须共享信号，分离线程只能在线程组内启动。
-        if clone_flags.contains(CloneFlags::CLONE_THREAD)
-            && !clone_flags.contains(CloneFlags::CLONE_SIGHAND)
-        {
-            return Err(SystemError::EINVAL);
-        }
+        // TODO: 如果新进程将处于不同的time namespace，则不能让它共享vm或线程组
 
-        // 共享信号处理器意味着共享vm。
-        // 线程组也意味着共享vm。阻止这种情况可以简化其他代码。
-        if clone_flags.contains(CloneFlags::CLONE_SIGHAND)
-            && !clone_flags.contains(CloneFlags::CLONE_VM)
+        // 检查RLIMIT_NPROC。新进程此时还没有被加入到进程列表中，因此不会被计算在内
+        // TODO: 引入用户的概念之后，需要只统计与当前进程属于同一用户的进程，并允许特权进程越过限制
+        if !current_pcb.flags().contains(ProcessFlags::KTHREAD)
+            && ProcessManager::nr_user_processes() as u64
+                >= current_pcb.rlimit(RLimitID::Nproc).rlim_cur
         {
-            return Err(SystemError::EINVAL);
+            return Err(SystemError::EAGAIN_OR_EWOULDBLOCK);
         }
 
-        // TODO: 处理CLONE_PARENT 与 SIGNAL_UNKILLABLE的情况
-
-        // 如果新进程使用不同的 pid 或 namespace，
-        // 则不允许它与分叉任务共享线程组。
-        if clone_flags.contains(CloneFlags::CLONE_THREAD)
-            && clone_flags.contains(CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWPID)
-        {
-            return Err(SystemError::EINVAL);
-            // TODO: 判断新进程与当前进程namespace是否相同，不同则返回错误
+        if Self::fork_rate_limited(current_pcb, clone_flags) {
+            Self::fork_rate_check()?;
         }
 
-        // 如果新进程将处于不同的time namespace，
-        // 则不能让它共享vm或线程组。
-        if clone_flags.contains(CloneFlags::CLONE_THREAD | CloneFlags::CLONE_VM) {
-            // TODO: 判断time namespace，不同则返回错误
-        }
+        // 拷贝namespace，新进程的pid namespace由此确定
+        Self::copy_namespaces(&clone_flags, current_pcb, pcb)?;
 
-        if clone_flags.contains(CloneFlags::CLONE_PIDFD)
-            && clone_flags.contains(CloneFlags::CLONE_DETACHED | CloneFlags::CLONE_THREAD)
-        {
-            return Err(SystemError::EINVAL);
-        }
+        // 在pid namespace中为新进程分配pid
+        Self::copy_pid(&clone_flags, current_pcb, pcb)?;
 
         // TODO: 克隆前应该锁信号处理，等待克隆完成后再处理
 
@@ -376,7 +1055,8 @@ impl ProcessManager {
             pcb.thread.write_irqsave().clear_child_tid = Some(clone_args.child_tid);
         }
 
-        // 设置child_tid，意味着子线程能够知道自己的id
+        // 设置child_tid，意味着子线程能够知道自己的id。
+        // 子进程第一次被调度时，才会在它自己的地址空间中写入tid（见ProcessManager::switch_finish_hook）
         if clone_flags.contains(CloneFlags::CLONE_CHILD_SETTID) {
             pcb.thread.write_irqsave().set_child_tid = Some(clone_args.child_tid);
         }
@@ -389,74 +1069,104 @@ impl ProcessManager {
                 true,
             )?;
 
-            writer.copy_one_to_user(&(pcb.pid().0 as i32), 0)?;
+            // 写入的是子进程在当前进程所在的pid namespace中的tid
+            writer.copy_one_to_user(&(pcb.pid_nr_ns(&current_pcb.pid_ns()).0 as i32), 0)?;
         }
 
-        sched_fork(pcb).unwrap_or_else(|e| {
-            panic!(
-                "fork: Failed to set sched info from current process, current pid: [{:?}], new pid: [{:?}]. Error: {:?}",
-                current_pcb.pid(), pcb.pid(), e
-            )
-        });
+        sched_fork(pcb)?;
 
         // 拷贝标志位
-        Self::copy_flags(&clone_flags, pcb).unwrap_or_else(|e| {
-            panic!(
-                "fork: Failed to copy flags from current process, current pid: [{:?}], new pid: [{:?}]. Error: {:?}",
-                current_pcb.pid(), pcb.pid(), e
-            )
-        });
+        Self::copy_flags(&clone_flags, pcb)?;
+
+        // 子进程执行execve之前，与父进程的命令行参数相同
+        pcb.set_cmdline(current_pcb.cmdline());
+
+        // 拷贝System V信号量的撤销列表
+        Self::copy_sysvsem(&clone_flags, current_pcb, pcb)?;
 
         // 拷贝用户地址空间
-        Self::copy_mm(&clone_flags, current_pcb, pcb).unwrap_or_else(|e| {
-            panic!(
-                "fork: Failed to copy mm from current process, current pid: [{:?}], new pid: [{:?}]. Error: {:?}",
-                current_pcb.pid(), pcb.pid(), e
-            )
-        });
+        Self::copy_mm(&clone_flags, clone_args.spawn, current_pcb, pcb)?;
 
         // 拷贝文件描述符表
-        Self::copy_files(&clone_flags, current_pcb, pcb).unwrap_or_else(|e| {
-            panic!(
-                "fork: Failed to copy files from current process, current pid: [{:?}], new pid: [{:?}]. Error: {:?}",
-                current_pcb.pid(), pcb.pid(), e
-            )
-        });
+        Self::copy_files(&clone_flags, current_pcb, pcb)?;
+
+        // 继承资源限制
+        Self::copy_rlimit(current_pcb, pcb)?;
+
+        // 拷贝文件系统上下文
+        Self::copy_fs(&clone_flags, current_pcb, pcb)?;
+
+        // 拷贝I/O上下文
+        Self::copy_io(&clone_flags, current_pcb, pcb)?;
 
         // 拷贝信号相关数据
-        Self::copy_sighand(&clone_flags, current_pcb, pcb).unwrap_or_else(|e| {
-            panic!(
-                "fork: Failed to copy sighand from current process, current pid: [{:?}], new pid: [{:?}]. Error: {:?}",
-                current_pcb.pid(), pcb.pid(), e
-            )
-        });
+        Self::copy_sighand(&clone_flags, current_pcb, pcb)?;
+
+        // 信号屏蔽字属于每个线程，子进程/线程获得父进程屏蔽字的一份拷贝
+        *pcb.sig_info_mut().sig_block_mut() = *current_pcb.sig_info_irqsave().sig_block();
+
+        // 继承信号处理程序的备用栈。如果与父进程共享地址空间（vfork除外），
+        // 子进程不能与父进程同时使用同一个备用栈，因此将其禁用
+        if !clone_flags.contains(CloneFlags::CLONE_VM)
+            || clone_flags.contains(CloneFlags::CLONE_VFORK)
+        {
+            let altstack = *current_pcb.sig_info_irqsave().sig_altstack();
+            *pcb.sig_info_mut().sig_altstack_mut() = altstack;
+        }
+
+        // 子进程不继承rseq的注册信息
+        pcb.rseq_fork();
+
+        // 子进程不继承robust list：fork出的进程需要重新注册，新线程则使用位于自己TLS中的robust list
+        pcb.set_robust_list(None);
 
         // 拷贝线程
-        Self::copy_thread(current_pcb, pcb, clone_args,current_trapframe).unwrap_or_else(|e| {
-            panic!(
-                "fork: Failed to copy thread from current process, current pid: [{:?}], new pid: [{:?}]. Error: {:?}",
-                current_pcb.pid(), pcb.pid(), e
-            )
-        });
+        Self::copy_thread(current_pcb, pcb, clone_args, current_trapframe)?;
 
-        // 设置线程组id、组长
+        // 设置线程组id、组长。
+        // 新线程在创建pcb时已经分配了自己的tid，使用CLONE_THREAD时它继承当前线程组的id
         if clone_flags.contains(CloneFlags::CLONE_THREAD) {
-            pcb.thread.write_irqsave().group_leader =
-                current_pcb.thread.read_irqsave().group_leader.clone();
+            let leader = current_pcb.thread.read_irqsave().group_leader.clone();
+            pcb.thread.write_irqsave().group_leader = leader.clone();
             unsafe {
                 let ptr = pcb.as_ref() as *const ProcessControlBlock as *mut ProcessControlBlock;
                 (*ptr).tgid = current_pcb.tgid;
             }
+
+            // 将新线程加入组长的线程组
+            if let Some(leader) = leader.upgrade() {
+                leader.thread_group.write_irqsave().push(pcb.tid());
+            }
+
+            // 线程不是当前进程的子进程，它与组长拥有相同的父进程，且不能被wait4回收
+            current_pcb
+                .children
+                .write_irqsave()
+                .retain(|pid| *pid != pcb.pid());
+            *pcb.parent_pcb.write_irqsave() = current_pcb.parent_pcb.read_irqsave().clone();
         } else {
             pcb.thread.write_irqsave().group_leader = Arc::downgrade(pcb);
             unsafe {
                 let ptr = pcb.as_ref() as *const ProcessControlBlock as *mut ProcessControlBlock;
                 (*ptr).tgid = pcb.tgid;
             }
+
+            // CLONE_PARENT创建的是当前进程的兄弟进程，由当前进程的父进程负责wait4回收
+            if clone_flags.contains(CloneFlags::CLONE_PARENT) {
+                current_pcb
+                    .children
+                    .write_irqsave()
+                    .retain(|pid| *pid != pcb.pid());
+                let parent = current_pcb.parent_pcb.read_irqsave().clone();
+                if let Some(parent) = parent.upgrade() {
+                    parent.children.write_irqsave().push(pcb.pid());
+                }
+                *pcb.parent_pcb.write_irqsave() = parent;
+            }
         }
 
         // CLONE_PARENT re-uses the old parent
-        if clone_flags.contains(CloneFlags::CLONE_PARENT | CloneFlags::CLONE_THREAD) {
+        if clone_flags.intersects(CloneFlags::CLONE_PARENT | CloneFlags::CLONE_THREAD) {
             *pcb.real_parent_pcb.write_irqsave() =
                 current_pcb.real_parent_pcb.read_irqsave().clone();
 
@@ -485,8 +1195,13 @@ impl ProcessManager {
 
         // todo: 增加线程组相关的逻辑。 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/fork.c#2437
 
+        // 决定子进程是否被父进程的跟踪者跟踪
+        Self::copy_ptrace(&clone_flags, clone_args.exit_signal, current_pcb, pcb);
+
         sched_cgroup_fork(pcb);
 
+        current_pcb.stats().inc_nforks();
+
         Ok(())
     }
 }
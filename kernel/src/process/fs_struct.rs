@@ -0,0 +1,55 @@
+use alloc::string::{String, ToString};
+
+use crate::filesystem::vfs::syscall::ModeType;
+
+/// 进程的文件系统上下文
+///
+/// 包括根目录、当前工作目录以及umask。使用CLONE_FS创建的进程会与父进程共享同一个FsStruct，
+/// 否则子进程会得到父进程的FsStruct的一份拷贝。
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/linux/fs_struct.h#9
+#[derive(Debug, Clone)]
+pub struct FsStruct {
+    /// 根目录
+    root: String,
+    /// 当前工作目录
+    cwd: String,
+    /// 创建文件时的权限掩码
+    umask: ModeType,
+}
+
+impl FsStruct {
+    /// 默认的umask
+    pub const DEFAULT_UMASK: ModeType = ModeType::from_bits_truncate(0o022);
+
+    pub fn new(cwd: String) -> Self {
+        Self {
+            root: "/".to_string(),
+            cwd,
+            umask: Self::DEFAULT_UMASK,
+        }
+    }
+
+    pub fn root(&self) -> String {
+        return self.root.clone();
+    }
+
+    pub fn cwd(&self) -> String {
+        return self.cwd.clone();
+    }
+
+    pub fn set_cwd(&mut self, cwd: String) {
+        self.cwd = cwd;
+    }
+
+    pub fn umask(&self) -> ModeType {
+        return self.umask;
+    }
+
+    /// 设置新的umask，并返回旧的umask
+    pub fn set_umask(&mut self, umask: ModeType) -> ModeType {
+        let old = self.umask;
+        self.umask = umask & ModeType::S_IRWXUGO;
+        return old;
+    }
+}
@@ -0,0 +1,85 @@
+//! 进程生命周期钩子
+//!
+//! 需要为每个进程维护状态的子系统（例如procfs）通过这里注册钩子，
+//! 在进程被创建以及被释放时得到通知，而不需要修改fork/exit的代码
+
+use core::fmt::Debug;
+
+use alloc::{sync::Arc, vec::Vec};
+use system_error::SystemError;
+
+use crate::{kerror, libs::rwlock::RwLock};
+
+use super::ProcessControlBlock;
+
+/// 已注册的钩子，按优先级从高到低排列，优先级相同的按注册顺序排列
+static PROCESS_HOOKS: RwLock<Vec<Arc<dyn ProcessHook>>> = RwLock::new(Vec::new());
+
+/// 进程生命周期钩子
+pub trait ProcessHook: Debug + Send + Sync {
+    /// 钩子的名字，用于输出日志
+    fn name(&self) -> &'static str;
+
+    /// 钩子的优先级，fork时优先级高的先被调用，释放时优先级低的先被调用
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// 进程被创建时调用
+    ///
+    /// 此时新进程已经加入了进程表，但还没有开始运行。
+    /// 返回错误时fork会失败，并且已经调用过的钩子会按相反的顺序被撤销（调用`exit`）
+    fn fork(&self, pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError>;
+
+    /// 进程的pcb被释放时调用
+    ///
+    /// fork失败时也会调用，因此需要能够处理`fork`没有被调用或者已经被撤销的情况
+    fn exit(&self, pcb: &ProcessControlBlock);
+}
+
+/// 注册进程生命周期钩子
+///
+/// ## 返回值
+///
+/// - `EEXIST` : 钩子已经注册过
+pub fn register_process_hook(hook: Arc<dyn ProcessHook>) -> Result<(), SystemError> {
+    let mut hooks = PROCESS_HOOKS.write_irqsave();
+    if hooks.iter().any(|h| Arc::ptr_eq(h, &hook)) {
+        return Err(SystemError::EEXIST);
+    }
+    let index = hooks
+        .iter()
+        .position(|h| h.priority() < hook.priority())
+        .unwrap_or(hooks.len());
+    hooks.insert(index, hook);
+    return Ok(());
+}
+
+/// 按顺序调用所有钩子的`fork`
+///
+/// 某个钩子返回错误时，按相反的顺序撤销之前已经成功的钩子，然后返回这个错误
+pub(super) fn call_fork_hooks(pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+    let hooks = PROCESS_HOOKS.read_irqsave();
+    for (i, hook) in hooks.iter().enumerate() {
+        if let Err(e) = hook.fork(pcb) {
+            kerror!(
+                "fork: process hook '{}' failed, pid: [{:?}]. Error: {:?}",
+                hook.name(),
+                pcb.pid(),
+                e
+            );
+            for hook in hooks[..i].iter().rev() {
+                hook.exit(pcb);
+            }
+            return Err(e);
+        }
+    }
+    return Ok(());
+}
+
+/// 按与fork相反的顺序调用所有钩子的`exit`
+pub(super) fn call_exit_hooks(pcb: &ProcessControlBlock) {
+    for hook in PROCESS_HOOKS.read_irqsave().iter().rev() {
+        hook.exit(pcb);
+    }
+}
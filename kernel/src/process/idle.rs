@@ -48,7 +48,8 @@ impl ProcessManager {
                 })
             };
 
-            let idle_pcb = ProcessControlBlock::new_idle(i, kstack);
+            let idle_pcb = ProcessControlBlock::new_idle(i, kstack)
+                .unwrap_or_else(|e| panic!("Failed to create idle pcb for cpu {}: {:?}", i, e));
 
             assert!(idle_pcb.basic().user_vm().is_none());
             unsafe {
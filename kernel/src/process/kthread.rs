@@ -18,6 +18,7 @@ use crate::{
     init::initial_kthread::initial_kernel_thread,
     kinfo,
     libs::{once::Once, spinlock::SpinLock},
+    mm::VirtAddr,
     process::{ProcessManager, ProcessState},
     sched::{schedule, SchedMode},
 };
@@ -305,12 +306,11 @@ impl KernelThreadMechanism {
             let info = KernelThreadCreateInfo::new(closure, "kthreadd".to_string());
             info.set_to_mark_sleep(false)
                 .expect("kthreadadd should be run first");
-            let kthreadd_pid: Pid = Self::__inner_create(
+            let pcb = Self::__inner_create(
                 &info,
                 CloneFlags::CLONE_VM | CloneFlags::CLONE_FS | CloneFlags::CLONE_SIGNAL,
             )
             .expect("Failed to create kthread daemon");
-            let pcb = ProcessManager::find(kthreadd_pid).unwrap();
             ProcessManager::wakeup(&pcb).expect("Failed to wakeup kthread daemon");
             unsafe {
                 KTHREAD_DAEMON_PCB.replace(pcb);
@@ -319,6 +319,36 @@ impl KernelThreadMechanism {
         });
     }
 
+    /// 创建内核线程
+    ///
+    /// 新的内核线程从架构相关的入口跳板函数开始执行，然后进入[`kernel_thread_bootstrap_stage2`]
+    ///
+    /// ## 返回值
+    ///
+    /// 返回创建的内核线程的pcb
+    pub fn __inner_create(
+        info: &Arc<KernelThreadCreateInfo>,
+        clone_flags: CloneFlags,
+    ) -> Result<Arc<ProcessControlBlock>, SystemError> {
+        // WARNING: If create failed, we must drop the info manually or it will cause memory leak. (refcount will not decrease when create failed)
+        let create_info: *const KernelThreadCreateInfo =
+            KernelThreadCreateInfo::generate_unsafe_arc_ptr(info.clone());
+
+        // fork失败的话，子线程不会执行。否则将导致内存安全问题。
+        // 内核线程不能被跟踪
+        let clone_flags = clone_flags | CloneFlags::CLONE_UNTRACED;
+        let name = info.name().clone();
+        return ProcessManager::fork_kernel_thread(
+            clone_flags,
+            VirtAddr::new(create_info as usize),
+            |pcb| pcb.set_name(name),
+        )
+        .map_err(|e| {
+            unsafe { KernelThreadCreateInfo::parse_unsafe_arc_ptr(create_info) };
+            e
+        });
+    }
+
     /// 创建一个新的内核线程
     ///
     /// ## 参数
@@ -458,7 +488,7 @@ impl KernelThreadMechanism {
             while let Some(info) = list.pop_front() {
                 drop(list);
                 // create a new kernel thread
-                let result: Result<Pid, SystemError> = Self::__inner_create(
+                let result = Self::__inner_create(
                     &info,
                     CloneFlags::CLONE_VM | CloneFlags::CLONE_FS | CloneFlags::CLONE_SIGNAL,
                 );
@@ -3,35 +3,42 @@ use core::{
     hint::spin_loop,
     intrinsics::{likely, unlikely},
     mem::ManuallyDrop,
-    sync::atomic::{compiler_fence, fence, AtomicBool, AtomicUsize, Ordering},
+    sync::atomic::{
+        compiler_fence, fence, AtomicBool, AtomicI16, AtomicU64, AtomicUsize, Ordering,
+    },
 };
 
 use alloc::{
+    collections::LinkedList,
     string::{String, ToString},
     sync::{Arc, Weak},
     vec::Vec,
 };
+use atomic_enum::atomic_enum;
 use hashbrown::HashMap;
 use system_error::SystemError;
 
 use crate::{
     arch::{
         cpu::current_cpu_id,
-        ipc::signal::{AtomicSignal, SigSet, Signal},
+        ipc::signal::{AtomicSignal, SigChildCode, SigCode, SigFlags, SigSet, Signal},
         process::ArchPCBInfo,
-        CurrentIrqArch,
+        CurrentIrqArch, MMArch,
     },
-    driver::tty::tty_core::TtyCore,
+    driver::{base::block::io_context::IoContext, tty::tty_core::TtyCore},
     exception::InterruptArch,
-    filesystem::{
-        procfs::procfs_unregister_pid,
-        vfs::{file::FileDescriptorVec, FileType},
+    filesystem::vfs::{file::FileDescriptorVec, FileType},
+    ipc::{
+        sem::SemUndoList,
+        signal_types::{
+            SigAltStack, SigInfo, SigPending, SigType, SignalStruct, SIG_KERNEL_STOP_MASK,
+        },
     },
-    ipc::signal_types::{SigInfo, SigPending, SignalStruct},
     kdebug, kinfo,
     libs::{
         align::AlignedBox,
         casting::DowncastArc,
+        cpumask::CpuMask,
         futex::{
             constant::{FutexFlag, FUTEX_BITSET_MATCH_ANY},
             futex::{Futex, RobustListHead},
@@ -42,45 +49,102 @@ use crate::{
         wait_queue::WaitQueue,
     },
     mm::{
+        kernel_mapper::KernelMapper,
+        page::PageFlags,
         percpu::{PerCpu, PerCpuVar},
         set_IDLE_PROCESS_ADDRESS_SPACE,
         ucontext::AddressSpace,
-        VirtAddr,
+        MemoryManagementArch, VirtAddr,
+    },
+    namespaces::{
+        mnt_namespace::MntNamespace,
+        nsproxy::{NsProxy, INIT_NSPROXY},
+        pid_namespace::{PidNamespace, UPid, INIT_PID_NS},
+        uts_namespace::UtsNamespace,
+    },
+    net::{
+        event_poll::{EPollEventType, EPollItem, EventPoll},
+        socket::SocketInode,
     },
-    net::socket::SocketInode,
-    sched::completion::Completion,
     sched::{
-        cpu_rq, fair::FairSchedEntity, prio::MAX_PRIO, DequeueFlag, EnqueueFlag, OnRq, SchedMode,
-        WakeupFlags, __schedule,
+        cpu_rq, cputime::CpuTimeFunc, fair::FairSchedEntity, prio::MAX_PRIO, schedule, DequeueFlag,
+        EnqueueFlag, OnRq, SchedMode, SchedPolicy, WakeupFlags, __schedule,
     },
     smp::{
         core::smp_get_processor_id,
-        cpu::{AtomicProcessorId, ProcessorId},
+        cpu::{smp_cpu_manager, AtomicProcessorId, ProcessorId},
         kick_cpu,
     },
-    syscall::{user_access::clear_user, Syscall},
+    syscall::{
+        rseq::{rseq_preempt, RseqRegistration},
+        user_access::{access_ok, clear_user, UserBufferWriter},
+    },
+    time::timer::clock,
 };
 
-use self::kthread::WorkerPrivate;
+use self::{
+    exit::exit_cause,
+    fork::VforkDone,
+    fs_struct::FsStruct,
+    kthread::WorkerPrivate,
+    pid::PidMap,
+    ptrace::PtraceLink,
+    resource::{RLimit64, RLimitID},
+    spawn::SpawnRequest,
+};
 
 pub mod abi;
 pub mod c_adapter;
 pub mod exec;
 pub mod exit;
 pub mod fork;
+pub mod fs_struct;
+pub mod hooks;
 pub mod idle;
 pub mod kthread;
 pub mod pid;
+pub mod pidfd;
+pub mod prctl;
+pub mod ptrace;
 pub mod resource;
+pub mod selftest;
+pub mod spawn;
 pub mod stdio;
 pub mod syscall;
 pub mod utils;
 
 /// 系统中所有进程的pcb
-static ALL_PROCESS: SpinLock<Option<HashMap<Pid, Arc<ProcessControlBlock>>>> = SpinLock::new(None);
+static ALL_PROCESS: RwLock<Option<HashMap<Pid, Arc<ProcessControlBlock>>>> = RwLock::new(None);
+
+lazy_static! {
+    /// 已经被分配出去的全局pid（对应的pcb被释放时，才会归还）
+    static ref USED_PIDS: SpinLock<PidMap> = SpinLock::new(PidMap::new());
+}
+
+/// pid上限的最大值（不包含），与Linux的PID_MAX_LIMIT保持一致
+pub const PID_MAX_LIMIT: usize = 4 * 1024 * 1024;
+
+/// 默认的pid上限（不包含），与Linux的PID_MAX_DEFAULT保持一致
+pub const PID_MAX_DEFAULT: usize = 0x8000;
+
+/// 当前的pid上限（不包含），可以通过/proc/sys/kernel/pid_max修改。
+/// 调低上限不会影响已经分配出去的pid
+static PID_MAX: AtomicUsize = AtomicUsize::new(PID_MAX_DEFAULT);
+
+/// pid分配到上限之后，从这个值开始重新查找空闲的pid。小于它的pid通常属于系统服务，不会被复用
+pub const RESERVED_PIDS: usize = 300;
+
+/// 下一次分配pid时使用的代数。每次分配pid都会使它增加，因此(pid, 代数)能够唯一地标识一个进程
+static NEXT_PID_GENERATION: AtomicU64 = AtomicU64::new(1);
 
 pub static mut PROCESS_SWITCH_RESULT: Option<PerCpuVar<SwitchResult>> = None;
 
+/// 每个cpu上正在运行的进程的pcb，见[`ProcessManager::current_pcb`]
+///
+/// 只会在本cpu关中断的情况下被修改：上下文切换完成、新进程开始运行之前由[`ProcessManager::switch_finish_hook`]更新，
+/// cpu下线时（`SmpCpuManager::set_offline_cpu`）由[`ProcessManager::invalidate_current_pcb_cache`]清空。为None时，从内核栈中获取当前进程的pcb
+static mut CURRENT_PCB_CACHE: Option<PerCpuVar<Option<Arc<ProcessControlBlock>>>> = None;
+
 /// 一个只改变1次的全局变量，标志进程管理器是否已经初始化完成
 static mut __PROCESS_MANAGEMENT_INIT_DONE: bool = false;
 
@@ -88,6 +152,10 @@ static mut __PROCESS_MANAGEMENT_INIT_DONE: bool = false;
 pub struct SwitchResult {
     pub prev_pcb: Option<Arc<ProcessControlBlock>>,
     pub next_pcb: Option<Arc<ProcessControlBlock>>,
+    /// 在本cpu上被换出、等待迁移到其他cpu的进程，以及是否需要在迁移完成后唤醒它
+    ///
+    /// 由下一次进入调度器时的[`ProcessManager::finish_migrate`]处理
+    pub migrate_pcb: Option<(Arc<ProcessControlBlock>, bool)>,
 }
 
 impl SwitchResult {
@@ -95,6 +163,7 @@ impl SwitchResult {
         Self {
             prev_pcb: None,
             next_pcb: None,
+            migrate_pcb: None,
         }
     }
 }
@@ -123,8 +192,9 @@ impl ProcessManager {
             compiler_fence(Ordering::SeqCst);
         };
 
-        ALL_PROCESS.lock_irqsave().replace(HashMap::new());
+        ALL_PROCESS.write_irqsave().replace(HashMap::new());
         Self::init_switch_result();
+        Self::init_current_pcb_cache();
         Self::arch_init();
         kdebug!("process arch init done.");
         Self::init_idle();
@@ -144,12 +214,26 @@ impl ProcessManager {
         }
     }
 
+    fn init_current_pcb_cache() {
+        let mut cache_vec: Vec<Option<Arc<ProcessControlBlock>>> = Vec::new();
+        for _ in 0..PerCpu::MAX_CPU_NUM {
+            cache_vec.push(None);
+        }
+        unsafe {
+            CURRENT_PCB_CACHE = Some(PerCpuVar::new(cache_vec).unwrap());
+        }
+    }
+
     /// 判断进程管理器是否已经初始化完成
     pub fn initialized() -> bool {
         unsafe { __PROCESS_MANAGEMENT_INIT_DONE }
     }
 
     /// 获取当前进程的pcb
+    ///
+    /// 优先读取本cpu的[`CURRENT_PCB_CACHE`]，这个过程不需要加锁。
+    /// 读取期间需要关中断，避免读到一半时进程被迁移到其他cpu上，从而读到其他cpu上正在运行的进程。
+    /// cpu上还没有发生过进程切换时（例如刚启动时的idle进程），缓存为空，此时从内核栈中获取
     pub fn current_pcb() -> Arc<ProcessControlBlock> {
         if unlikely(unsafe { !__PROCESS_MANAGEMENT_INIT_DONE }) {
             kerror!("unsafe__PROCESS_MANAGEMENT_INIT_DONE == false");
@@ -157,7 +241,24 @@ impl ProcessManager {
                 spin_loop();
             }
         }
-        return ProcessControlBlock::arch_current_pcb();
+        let cached = {
+            let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+            unsafe { CURRENT_PCB_CACHE.as_ref().unwrap().get().clone() }
+        };
+        return cached.unwrap_or_else(ProcessControlBlock::arch_current_pcb);
+    }
+
+    /// 清空cpu的当前进程缓存，需要在cpu下线时调用
+    ///
+    /// ## Safety
+    ///
+    /// 调用时，`cpu`上不能有正在运行的进程
+    pub unsafe fn invalidate_current_pcb_cache(cpu: ProcessorId) {
+        CURRENT_PCB_CACHE
+            .as_ref()
+            .unwrap()
+            .force_get_mut(cpu)
+            .take();
     }
 
     /// 获取当前进程的pid
@@ -197,7 +298,89 @@ impl ProcessManager {
     ///
     /// 如果找到了对应的进程，那么返回该进程的pcb，否则返回None
     pub fn find(pid: Pid) -> Option<Arc<ProcessControlBlock>> {
-        return ALL_PROCESS.lock_irqsave().as_ref()?.get(&pid).cloned();
+        return ALL_PROCESS.read_irqsave().as_ref()?.get(&pid).cloned();
+    }
+
+    /// 根据pid获取进程（线程组组长）的pcb
+    ///
+    /// 只持有进程表的读锁，并且返回的是pcb的Arc指针的拷贝，因此调用者在使用返回值时，不会持有进程表的锁。
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 进程的pid
+    ///
+    /// ## 返回值
+    ///
+    /// 如果存在pid对应的进程，并且它是线程组组长，那么返回它的pcb，否则返回None。
+    /// 如果要查找任意的线程，请使用[`ProcessManager::find_thread_by_tid`]
+    ///
+    /// 用法：
+    ///
+    /// ```rust
+    /// let pcb = ProcessManager::find_process_by_pid(pid).ok_or(SystemError::ESRCH)?;
+    /// let pgid = pcb.basic().pgid();
+    /// ```
+    pub fn find_process_by_pid(pid: Pid) -> Option<Arc<ProcessControlBlock>> {
+        return Self::find(pid).filter(|pcb| pcb.is_thread_group_leader());
+    }
+
+    /// 将当前进程所在的pid namespace中的pid转换为全局pid
+    ///
+    /// 用户态传入的pid都是调用者所在namespace中的pid，需要先转换为全局pid，才能在进程表中查找
+    ///
+    /// ## 返回值
+    ///
+    /// 如果当前进程的namespace中没有这个pid，则返回None
+    pub fn vpid_to_pid(vpid: Pid) -> Option<Pid> {
+        return ProcessManager::current_pcb().pid_ns().pid_to_global(vpid);
+    }
+
+    /// 将全局pid转换为当前进程所在的pid namespace中的pid，是[`ProcessManager::vpid_to_pid`]的逆操作
+    ///
+    /// 用于向用户态返回进程组id、会话id这类以全局pid保存的id。
+    /// 对于pcb，应当使用[`ProcessControlBlock::pid_vnr`]
+    ///
+    /// ## 返回值
+    ///
+    /// 如果这个pid在当前进程的namespace中不可见，则返回0
+    pub fn pid_to_vpid(pid: Pid) -> Pid {
+        return ProcessManager::current_pcb()
+            .pid_ns()
+            .global_to_pid(pid)
+            .unwrap_or(Pid(0));
+    }
+
+    /// 根据tid获取线程的pcb
+    ///
+    /// 与[`ProcessManager::find_process_by_pid`]不同，这个函数也会返回不是线程组组长的线程
+    ///
+    /// ## 参数
+    ///
+    /// - `tid` : 线程的tid
+    ///
+    /// ## 返回值
+    ///
+    /// 如果找到了对应的线程，那么返回它的pcb，否则返回None
+    pub fn find_thread_by_tid(tid: Pid) -> Option<Arc<ProcessControlBlock>> {
+        return Self::find(tid);
+    }
+
+    /// 根据pid和pid的代数获取进程的pcb
+    ///
+    /// pid被释放后会被复用，因此仅凭pid，可能会找到一个在保存pid之后才创建的无关进程。
+    /// 调用者在保存pid的同时保存[`ProcessControlBlock::pid_generation`]，
+    /// 就能够通过这个函数确认找到的仍然是原来的进程。
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 进程的pid
+    /// - `generation` : 保存pid时，进程的pid代数
+    ///
+    /// ## 返回值
+    ///
+    /// 如果找到了pid与代数都相同的进程，则返回该进程的pcb，否则返回None
+    pub fn find_checked(pid: Pid, generation: u64) -> Option<Arc<ProcessControlBlock>> {
+        return Self::find(pid).filter(|pcb| pcb.pid_generation() == generation);
     }
 
     /// 向系统中添加一个进程的pcb
@@ -211,14 +394,65 @@ impl ProcessManager {
     /// 无
     pub fn add_pcb(pcb: Arc<ProcessControlBlock>) {
         ALL_PROCESS
-            .lock_irqsave()
+            .write_irqsave()
             .as_mut()
             .unwrap()
             .insert(pcb.pid(), pcb.clone());
     }
 
+    /// 获取系统中所有进程（包括线程）的pcb
+    ///
+    /// 返回的是调用时的一个快照，因此调用者可以在遍历时对这些进程进行操作，而不会持有全局的进程表锁
+    pub fn all_processes() -> Vec<Arc<ProcessControlBlock>> {
+        return ALL_PROCESS
+            .read_irqsave()
+            .as_ref()
+            .map(|all| all.values().cloned().collect())
+            .unwrap_or_default();
+    }
+
+    /// 对系统中的每个进程（即线程组组长，包括内核线程）调用一次`f`
+    ///
+    /// ## 锁
+    ///
+    /// 遍历的是[`ProcessManager::all_processes`]得到的快照，调用`f`时不持有全局进程表的锁，
+    /// 因此`f`可以fork、唤醒或者杀死进程，也可以再次查找进程，而不会死锁。代价是：
+    ///
+    /// - 遍历期间新创建的进程不会被传入`f`
+    /// - 传入`f`的进程可能已经退出，调用者需要的话可以检查它的[`ProcessControlBlock::exit_state`]
+    ///
+    /// `f`仍然不能在持有自旋锁的情况下睡眠，调用者也不应该在持有进程表的锁时调用这个函数
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/linux/sched/signal.h#637
+    pub fn for_each_process(mut f: impl FnMut(&Arc<ProcessControlBlock>)) {
+        for pcb in Self::all_processes() {
+            if pcb.is_thread_group_leader() {
+                f(&pcb);
+            }
+        }
+    }
+
+    /// 获取系统中用户进程（包括线程）的数量，内核线程不计算在内
+    pub fn nr_user_processes() -> usize {
+        return ALL_PROCESS
+            .read_irqsave()
+            .as_ref()
+            .map(|all| {
+                all.values()
+                    .filter(|pcb| !pcb.flags().contains(ProcessFlags::KTHREAD))
+                    .count()
+            })
+            .unwrap_or(0);
+    }
+
     /// 唤醒一个进程
+    ///
+    /// 还没有被[`ProcessManager::wake_up_new_task`]唤醒过的新进程不会被唤醒，
+    /// 发送给它的信号等会在它第一次运行、返回用户态之前被处理
     pub fn wakeup(pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+        if pcb.flags().contains(ProcessFlags::NEW) {
+            return Ok(());
+        }
         let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
         let state = pcb.sched_info().inner_lock_read_irqsave().state();
         if state.is_blocked() {
@@ -231,8 +465,11 @@ impl ProcessManager {
                 // avoid deadlock
                 drop(writer);
 
-                let rq =
-                    cpu_rq(pcb.sched_info().on_cpu().unwrap_or(current_cpu_id()).data() as usize);
+                let cpu = pcb
+                    .sched_info()
+                    .on_cpu()
+                    .unwrap_or_else(|| Self::select_task_cpu(pcb, current_cpu_id()));
+                let rq = cpu_rq(cpu.data() as usize);
 
                 let (rq, _guard) = rq.self_lock();
                 rq.update_rq_clock();
@@ -257,6 +494,18 @@ impl ProcessManager {
         }
     }
 
+    /// 第一次唤醒新创建的进程
+    ///
+    /// 创建者必须在新进程完全初始化（加入进程表、通知进程生命周期钩子、设置vfork_done等）之后，
+    /// 才调用这个函数，从而保证新进程在第一次运行时不会看到不完整的状态
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/sched/core.c#4651
+    pub fn wake_up_new_task(pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+        fence(Ordering::SeqCst);
+        pcb.flags().remove(ProcessFlags::NEW);
+        return Self::wakeup(pcb);
+    }
+
     /// 唤醒暂停的进程
     pub fn wakeup_stop(pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
         let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
@@ -294,6 +543,94 @@ impl ProcessManager {
         }
     }
 
+    /// 让进程所在的线程组从停止状态中恢复（SIGCONT）
+    ///
+    /// 清除组内所有线程中尚未处理的停止信号，唤醒已经停止的线程。
+    /// 如果线程组之前处于停止状态，则通知父进程
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb` : 收到SIGCONT的进程
+    pub fn group_continue(pcb: &Arc<ProcessControlBlock>) {
+        let mut threads = pcb.thread_group();
+        if threads.is_empty() {
+            threads.push(pcb.clone());
+        }
+
+        let mut resumed = false;
+        for thread in threads.iter() {
+            // 处于ptrace停止状态的线程只能由跟踪者唤醒
+            if thread.is_ptrace_stopped() {
+                continue;
+            }
+            let mut sig_info = thread.sig_info_mut();
+            sig_info
+                .sig_pending_mut()
+                .flush_by_mask(&SIG_KERNEL_STOP_MASK);
+            sig_info
+                .sig_shared_pending_mut()
+                .flush_by_mask(&SIG_KERNEL_STOP_MASK);
+            drop(sig_info);
+
+            let state = thread.sched_info().inner_lock_read_irqsave().state();
+            if state.is_stopped() && ProcessManager::wakeup_stop(thread).is_ok() {
+                resumed = true;
+            }
+        }
+
+        let leader = &threads[0];
+        let stopped = leader.stop_signal.swap(Signal::INVALID, Ordering::SeqCst) != Signal::INVALID;
+        if resumed || stopped {
+            leader.continued.store(true, Ordering::SeqCst);
+            ProcessManager::notify_parent_cldstop(leader, SigChildCode::Continued, Signal::SIGCONT);
+        }
+    }
+
+    /// 线程组停止或者继续时，通知组长的父进程
+    ///
+    /// 向父进程发送SIGCHLD（父进程的SIGCHLD处理设置了SA_NOCLDSTOP时除外），
+    /// 并唤醒在wait4/waitid中等待的父进程
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/signal.c#2158
+    fn notify_parent_cldstop(leader: &Arc<ProcessControlBlock>, why: SigChildCode, sig: Signal) {
+        if let Some(parent) = leader.parent() {
+            ProcessManager::do_notify_cldstop(&parent, leader, why, sig);
+        }
+    }
+
+    /// 向`parent`报告`child`停止或者继续运行
+    ///
+    /// ## 参数
+    ///
+    /// - `parent` : 接收通知的进程（父进程或者跟踪者）
+    /// - `child` : 状态发生变化的进程
+    /// - `why` : 状态变化的原因
+    /// - `sig` : 导致状态变化的信号
+    pub(super) fn do_notify_cldstop(
+        parent: &Arc<ProcessControlBlock>,
+        child: &Arc<ProcessControlBlock>,
+        why: SigChildCode,
+        sig: Signal,
+    ) {
+        let nocldstop = parent
+            .sig_struct_irqsave()
+            .action(Signal::SIGCHLD)
+            .flags()
+            .contains(SigFlags::SA_NOCLDSTOP);
+        if !nocldstop {
+            let (utime, stime) = child.cputime();
+            let mut info = SigInfo::new(
+                Signal::SIGCHLD,
+                0,
+                SigCode::Kernel,
+                SigType::SigChild(child.pid(), why.into(), sig as i32, utime, stime),
+            );
+            let _r = Signal::SIGCHLD.send_signal_info(Some(&mut info), parent.pid());
+        }
+
+        ProcessManager::wakeup_wait_chldexit(parent);
+    }
+
     /// 标志当前进程永久睡眠，但是发起调度的工作，应该由调用者完成
     ///
     /// ## 注意
@@ -325,7 +662,11 @@ impl ProcessManager {
     ///
     /// - 进入当前函数之前，不能持有sched_info的锁
     /// - 进入当前函数之前，必须关闭中断
-    pub fn mark_stop() -> Result<(), SystemError> {
+    ///
+    /// ## 参数
+    ///
+    /// - `sig` : 导致进程停止的信号，会通过wait4/waitid报告给父进程
+    pub fn mark_stop(sig: Signal) -> Result<(), SystemError> {
         assert!(
             !CurrentIrqArch::is_irq_enabled(),
             "interrupt must be disabled before enter ProcessManager::mark_stop()"
@@ -338,6 +679,35 @@ impl ProcessManager {
             pcb.flags().insert(ProcessFlags::NEED_SCHEDULE);
             drop(writer);
 
+            // 整个线程组一起停止：让组内其他还在运行的线程也处理这个信号
+            let threads = pcb.thread_group();
+            for thread in threads.iter() {
+                if Arc::ptr_eq(thread, &pcb) {
+                    continue;
+                }
+                let state = thread.sched_info().inner_lock_read_irqsave().state();
+                if !state.is_stopped() && !state.is_exited() {
+                    let _r = sig.send_signal_info_to_thread(None, thread.clone());
+                }
+            }
+
+            // 线程组中的所有线程都停止之后，通知父进程。
+            // 由于停止事件记录在组长上，因此即使多个线程同时停止，也只会通知一次
+            let all_stopped = threads.iter().all(|thread| {
+                let state = thread.sched_info().inner_lock_read_irqsave().state();
+                state.is_stopped() || state.is_exited()
+            });
+            let leader = threads.first().cloned().unwrap_or(pcb);
+            if all_stopped
+                && leader
+                    .stop_signal
+                    .compare_exchange(Signal::INVALID, sig, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+            {
+                leader.continued.store(false, Ordering::SeqCst);
+                ProcessManager::notify_parent_cldstop(&leader, SigChildCode::Stopped, sig);
+            }
+
             return Ok(());
         }
         return Err(SystemError::EINTR);
@@ -345,29 +715,205 @@ impl ProcessManager {
     /// 当子进程退出后向父进程发送通知
     fn exit_notify() {
         let current = ProcessManager::current_pcb();
+        // 解除与跟踪者、被跟踪者之间的联系，被跟踪者不会再等待当前进程让它继续运行
+        ProcessManager::exit_ptrace(&current);
         // 让INIT进程收养所有子进程
         if current.pid() != Pid(1) {
+            // pid namespace的init进程退出时，namespace中的其他进程也会被杀死
+            let pid_ns = current.pid_ns();
+            if pid_ns.level() > 0 && current.is_pid_ns_init() {
+                pid_ns.zap_processes(current.pid());
+            }
+
+            // 收养会修改子进程的real_parent，因此要在此之前发送父进程退出信号
+            ProcessManager::send_pdeath_signals(&current);
             unsafe {
                 current
                     .adopt_childen()
                     .unwrap_or_else(|e| panic!("adopte_childen failed: error: {e:?}"))
             };
-            let r = current.parent_pcb.read_irqsave().upgrade();
+
+            // 非组长线程退出时不通知父进程，而是从线程组中移除，并直接由内核回收
+            if !current.is_thread_group_leader() {
+                if let Some(leader) = ProcessManager::find(current.tgid()) {
+                    // 线程消耗的cpu时间仍然属于进程
+                    leader
+                        .stats()
+                        .add_dead_thread_cputime(current.thread_cputime());
+                    leader
+                        .stats()
+                        .add_dead_thread_faults(current.stats().faults());
+                    leader
+                        .thread_group
+                        .write_irqsave()
+                        .retain(|pid| *pid != current.pid());
+                }
+                unsafe { ProcessManager::release(current.pid()) };
+                return;
+            }
+
+            // 通知指向该进程的pidfd，进程已经退出
+            let pollflag = EPollEventType::EPOLLIN | EPollEventType::EPOLLRDNORM;
+            let cnt = current.pidfd_epitems.lock_irqsave().len();
+            for _ in 0..cnt {
+                let _ = EventPoll::wakeup_epoll(&current.pidfd_epitems, pollflag);
+            }
+
+            let r = current.parent();
             if r.is_none() {
+                current
+                    .exit_state
+                    .store(ExitState::Zombie, Ordering::SeqCst);
                 return;
             }
             let parent_pcb = r.unwrap();
-            let r = Syscall::kill(parent_pcb.pid(), Signal::SIGCHLD as i32);
-            if r.is_err() {
+            ProcessManager::do_notify_parent(&current, &parent_pcb);
+            // todo: 这里还需要根据线程组的信息，决定信号的发送
+        }
+    }
+
+    /// 通知父进程`child`已经退出
+    ///
+    /// 向父进程发送`child`的退出信号（通常是SIGCHLD），siginfo中记录了子进程的pid、退出原因、
+    /// 退出码以及消耗的cpu时间，然后唤醒在wait4中等待的父进程。
+    ///
+    /// 如果退出信号是SIGCHLD，并且父进程忽略了SIGCHLD或者为它设置了SA_NOCLDWAIT，
+    /// 那么子进程不会成为僵尸进程，而是直接被回收。
+    /// 这个判断在唤醒父进程之前完成，因此父进程的wait4不会回收这样的子进程，并且被唤醒时
+    /// 子进程已经从它的子进程列表中移除；对于已经是僵尸进程的子进程，
+    /// 通过[`ProcessControlBlock::try_mark_dead`]保证它只会被回收一次
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/signal.c#2045
+    fn do_notify_parent(child: &Arc<ProcessControlBlock>, parent: &Arc<ProcessControlBlock>) {
+        let mut sig = child.exit_signal.load(Ordering::SeqCst);
+        let mut autoreap = false;
+        if sig == Signal::SIGCHLD {
+            let action = parent.sig_struct_irqsave().action(Signal::SIGCHLD);
+            if action.is_ignore() || action.flags().contains(SigFlags::SA_NOCLDWAIT) {
+                autoreap = true;
+            }
+            // 父进程忽略了SIGCHLD时，也不需要发送信号
+            if action.is_ignore() {
+                sig = Signal::INVALID;
+            }
+        }
+
+        // 退出码对父进程可见之后，再转换为僵尸状态（或者直接标记为已回收）。
+        // 必须在唤醒父进程之前完成，否则被唤醒的父进程可能观察不到子进程的退出
+        if autoreap {
+            if child.exit_state() == ExitState::Running {
+                child.exit_state.store(ExitState::Dead, Ordering::SeqCst);
+            } else {
+                autoreap = child.try_mark_dead();
+            }
+        } else {
+            child.exit_state.store(ExitState::Zombie, Ordering::SeqCst);
+        }
+
+        if sig != Signal::INVALID {
+            let (why, status) = exit_cause(child.exit_code());
+            let (utime, stime) = child.cputime();
+            let mut info = SigInfo::new(
+                sig,
+                0,
+                SigCode::Kernel,
+                SigType::SigChild(child.pid(), why.into(), status, utime, stime),
+            );
+            if let Err(e) = sig.send_signal_info_to_pcb(Some(&mut info), parent.clone()) {
                 kwarn!(
-                    "failed to send kill signal to {:?}'s parent pcb {:?}",
-                    current.pid(),
-                    parent_pcb.pid()
+                    "failed to send exit signal {:?} of {:?} to its parent {:?}: {:?}",
+                    sig,
+                    child.pid(),
+                    parent.pid(),
+                    e
                 );
             }
-            // todo: 这里需要向父进程发送SIGCHLD信号
-            // todo: 这里还需要根据线程组的信息，决定信号的发送
         }
+        if autoreap {
+            unsafe { ProcessManager::release(child.pid()) };
+        }
+        ProcessManager::wakeup_wait_chldexit(parent);
+    }
+
+    /// 向real_parent为`parent`，并且设置了父进程退出信号的所有进程发送该信号
+    ///
+    /// 通过CLONE_PARENT创建的进程，其real_parent是调用者的父进程，因此只会在调用者的父进程退出时收到信号
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/exit.c#689
+    fn send_pdeath_signals(parent: &Arc<ProcessControlBlock>) {
+        for pcb in ProcessManager::all_processes() {
+            let sig = pcb.pdeath_signal();
+            if sig == Signal::INVALID
+                || !pcb
+                    .real_parent()
+                    .is_some_and(|real_parent| Arc::ptr_eq(&real_parent, parent))
+            {
+                continue;
+            }
+
+            let mut info = SigInfo::new(sig, 0, SigCode::User, SigType::Kill(parent.pid()));
+            if let Err(e) = sig.send_signal_info_to_pcb(Some(&mut info), pcb.clone()) {
+                kwarn!(
+                    "failed to send pdeath signal {:?} to {:?}: {:?}",
+                    sig,
+                    pcb.pid(),
+                    e
+                );
+            }
+        }
+    }
+
+    /// 唤醒在指定进程的wait_chldexit队列上等待子进程状态变化的进程
+    ///
+    /// 调用者必须先更新子进程的状态，再调用本函数。wait4在持有队列的锁的情况下检查子进程的状态，
+    /// 因此不会丢失唤醒
+    pub fn wakeup_wait_chldexit(parent: &Arc<ProcessControlBlock>) {
+        parent
+            .wait_chldexit
+            .wakeup_all(Some(ProcessState::Blocked(true)));
+    }
+
+    /// 退出当前线程所在线程组中的所有线程
+    ///
+    /// 只有第一个调用者会记录线程组的退出码并杀死组内的其他线程，
+    /// 之后并发调用exit_group的线程只会以同样的退出码退出自身。
+    ///
+    /// ## 参数
+    ///
+    /// - `exit_code` : 线程组的退出码
+    pub fn group_exit(exit_code: usize) -> ! {
+        let current = ProcessManager::current_pcb();
+
+        if ProcessManager::start_group_exit(&current, exit_code) {
+            for thread in current.thread_group() {
+                if thread.pid() != current.pid() {
+                    let _r = Signal::SIGKILL.send_signal_info_to_thread(None, thread);
+                }
+            }
+        }
+
+        drop(current);
+        // 线程组的退出码已经记录在组长中，exit会使用它
+        ProcessManager::exit(exit_code);
+    }
+
+    /// 记录`pcb`所在线程组的退出码，标志着整个线程组开始退出
+    ///
+    /// ## 返回值
+    ///
+    /// - `true`：线程组第一次开始退出，调用者需要负责杀死组内的其他线程
+    /// - `false`：线程组已经在退出，退出码保持不变
+    pub fn start_group_exit(pcb: &Arc<ProcessControlBlock>, exit_code: usize) -> bool {
+        let leader = match ProcessManager::find(pcb.tgid()) {
+            Some(leader) => leader,
+            None => return true,
+        };
+        let mut leader_thread = leader.thread.write_irqsave();
+        if leader_thread.group_exit_code.is_some() {
+            return false;
+        }
+        leader_thread.group_exit_code = Some(exit_code);
+        return true;
     }
 
     /// 退出当前进程
@@ -380,6 +926,11 @@ impl ProcessManager {
         let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
         let pcb = ProcessManager::current_pcb();
         let pid = pcb.pid();
+        // 如果线程组正在通过exit_group退出，则使用线程组的退出码，使得父进程能够得到正确的退出状态
+        let exit_code = ProcessManager::find(pcb.tgid())
+            .and_then(|leader| leader.thread.read_irqsave().group_exit_code)
+            .unwrap_or(exit_code);
+        pcb.exit_code.store(exit_code, Ordering::SeqCst);
         pcb.sched_info
             .inner_lock_write_irqsave()
             .set_state(ProcessState::Exited(exit_code));
@@ -394,27 +945,37 @@ impl ProcessManager {
         drop(guard);
 
         // 进行进程退出后的工作
-        let thread = pcb.thread.write_irqsave();
-        if let Some(addr) = thread.set_child_tid {
-            unsafe { clear_user(addr, core::mem::size_of::<i32>()).expect("clear tid failed") };
-        }
+        // 先处理robust list，再处理clear_child_tid：等待线程退出的线程被唤醒时，
+        // 这个线程持有的robust futex都应该已经被标记为FUTEX_OWNER_DIED
+        RobustListHead::exit_robust_list(pcb.clone());
 
+        let thread = pcb.thread.write_irqsave();
         if let Some(addr) = thread.clear_child_tid {
-            if Arc::strong_count(&pcb.basic().user_vm().expect("User VM Not found")) > 1 {
+            // 只有在还有其他进程（线程）共享地址空间时，才需要通知它们。
+            // 引用计数包含pcb中的引用以及这里临时获取的引用
+            let shared_vm = pcb
+                .basic()
+                .user_vm()
+                .map(|vm| Arc::strong_count(&vm) > 2)
+                .unwrap_or(false);
+            // 用户可能已经解除了这个地址的映射，此时直接忽略
+            if shared_vm && access_ok(addr, core::mem::size_of::<i32>(), true).is_ok() {
+                // 先清零再唤醒，这样被唤醒的等待者才能看到线程已经退出
+                unsafe { clear_user(addr, core::mem::size_of::<i32>()).ok() };
                 let _ =
                     Futex::futex_wake(addr, FutexFlag::FLAGS_MATCH_NONE, 1, FUTEX_BITSET_MATCH_ANY);
             }
-            unsafe { clear_user(addr, core::mem::size_of::<i32>()).expect("clear tid failed") };
         }
 
-        RobustListHead::exit_robust_list(pcb.clone());
-
-        // 如果是vfork出来的进程，则需要处理completion
-        if thread.vfork_done.is_some() {
-            thread.vfork_done.as_ref().unwrap().complete_all();
-        }
         drop(thread);
+        // 如果是vfork出来的进程，则需要唤醒父进程
+        ProcessManager::complete_vfork_done(&pcb);
+        pcb.record_maxrss();
+        // 释放地址空间。如果没有其他进程共享它，那么所有的VMA都会被取消映射，
+        // 私有的页面被释放，写时复制的页面的引用计数被减少
         unsafe { pcb.basic_mut().set_user_vm(None) };
+        ProcessManager::exit_files(&pcb);
+        ProcessManager::exit_signals(&pcb);
         drop(pcb);
         ProcessManager::exit_notify();
         // unsafe { CurrentIrqArch::interrupt_enable() };
@@ -426,6 +987,37 @@ impl ProcessManager {
         }
     }
 
+    /// 释放退出的进程的文件描述符表
+    ///
+    /// 如果文件描述符表没有与其他进程共享，则关闭其中所有的文件，
+    /// 使得管道等文件的另一端能够在父进程被唤醒之前观察到这个进程已经关闭了它们
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/fs/file.c#436
+    fn exit_files(pcb: &Arc<ProcessControlBlock>) {
+        let fd_table = pcb.basic().fd_table();
+        if let Some(fd_table) = fd_table {
+            // 引用计数包含pcb中的引用以及这里临时获取的引用
+            if Arc::strong_count(&fd_table) <= 2 {
+                let files = fd_table.write().take_all();
+                // 释放表的锁之后再关闭文件
+                drop(files);
+            }
+        }
+        pcb.basic_mut().set_fd_table(None);
+    }
+
+    /// 释放退出的线程尚未处理的信号
+    ///
+    /// 线程不会再处理这些信号。线程组共享的信号以及信号处理函数表仍然保留：
+    /// 它们可能仍被线程组中的其他线程使用，并且在进程被回收之前，仍然可能有信号被发送给它
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/signal.c#150
+    fn exit_signals(pcb: &Arc<ProcessControlBlock>) {
+        pcb.sig_info_mut()
+            .sig_pending_mut()
+            .flush_by_mask(&SigSet::all());
+    }
+
     pub unsafe fn release(pid: Pid) {
         let pcb = ProcessManager::find(pid);
         if pcb.is_some() {
@@ -443,7 +1035,15 @@ impl ProcessManager {
             //     panic!()
             // }
 
-            ALL_PROCESS.lock_irqsave().as_mut().unwrap().remove(&pid);
+            let pcb = pcb.unwrap();
+            pcb.exit_state.store(ExitState::Dead, Ordering::SeqCst);
+
+            // 将该进程从父进程的子进程列表中移除，避免被重复回收
+            if let Some(ppcb) = pcb.parent() {
+                ppcb.children.write_irqsave().retain(|p| *p != pid);
+            }
+
+            ALL_PROCESS.write_irqsave().as_mut().unwrap().remove(&pid);
         }
     }
 
@@ -465,9 +1065,178 @@ impl ProcessManager {
             .take()
             .expect("next_pcb is None");
 
+        // 在新进程执行任何代码之前更新当前进程缓存。被替换掉的是prev_pcb，此时已经不在它的内核栈上运行，可以安全地释放
+        CURRENT_PCB_CACHE
+            .as_ref()
+            .unwrap()
+            .get_mut()
+            .replace(next_pcb.clone());
+
         // 由于进程切换前使用了SpinLockGuard::leak()，所以这里需要手动释放锁
         prev_pcb.arch_info.force_unlock();
         next_pcb.arch_info.force_unlock();
+
+        // 已经退出的进程不会再被调度。此时已经切换到了next_pcb的内核栈上，可以释放prev_pcb的内核栈
+        if prev_pcb
+            .sched_info()
+            .inner_lock_read_irqsave()
+            .state()
+            .is_exited()
+        {
+            prev_pcb.kernel_stack_mut().free();
+        }
+
+        // 进程可能被切换到了其他cpu上，返回用户态之前需要更新rseq
+        rseq_preempt(&next_pcb);
+
+        // 此时已经切换到了新进程的地址空间，可以写入set_child_tid
+        if unlikely(next_pcb.flags().contains(ProcessFlags::NEED_SET_CHILD_TID)) {
+            next_pcb.flags().remove(ProcessFlags::NEED_SET_CHILD_TID);
+            Self::write_set_child_tid(&next_pcb);
+        }
+    }
+
+    /// 唤醒因为vfork而等待该进程的父进程
+    ///
+    /// 在子进程execve或者退出，不再使用父进程的地址空间时调用
+    pub fn complete_vfork_done(pcb: &Arc<ProcessControlBlock>) {
+        pcb.flags().remove(ProcessFlags::VFORK);
+        let vfork_done = pcb.thread.write_irqsave().vfork_done.take();
+        if let Some(vfork_done) = vfork_done {
+            vfork_done.complete();
+        }
+    }
+
+    /// 把tid写入到进程的set_child_tid地址中
+    ///
+    /// 必须在进程自己的地址空间中调用。地址不可访问时，直接忽略
+    fn write_set_child_tid(pcb: &Arc<ProcessControlBlock>) {
+        let addr = match pcb.thread.read_irqsave().set_child_tid {
+            Some(addr) => addr,
+            None => return,
+        };
+        if access_ok(addr, core::mem::size_of::<i32>(), true).is_err() {
+            return;
+        }
+        if let Ok(mut writer) =
+            UserBufferWriter::new(addr.as_ptr::<i32>(), core::mem::size_of::<i32>(), true)
+        {
+            // 写入的是子进程在它自己所在的pid namespace中的tid
+            writer
+                .copy_one_to_user(&(pcb.pid_nr_ns(&pcb.pid_ns()).data() as i32), 0)
+                .ok();
+        }
+    }
+
+    /// 为进程选择一个允许它运行的cpu
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb` : 进程的pcb
+    /// - `prefer` : 优先选择的cpu，如果进程允许在该cpu上运行，则直接返回它
+    ///
+    /// ## 返回值
+    ///
+    /// 进程的cpu掩码中第一个已经上线的cpu。如果没有这样的cpu，返回`prefer`
+    pub fn select_task_cpu(pcb: &Arc<ProcessControlBlock>, prefer: ProcessorId) -> ProcessorId {
+        if pcb.sched_info().cpu_allowed(prefer) {
+            return prefer;
+        }
+        let present = smp_cpu_manager().present_cpus();
+        return pcb
+            .sched_info()
+            .cpus_allowed()
+            .iter_cpu()
+            .find(|cpu| present.get(*cpu).unwrap_or(false))
+            .unwrap_or(prefer);
+    }
+
+    /// 设置进程允许运行的cpu集合
+    ///
+    /// 如果进程当前所在的cpu不在新的集合中，会为它置位`NEED_MIGRATE`。
+    /// 进程在下一次被换出时会被迁移到允许的cpu上。若设置的是当前进程，则立即发起调度来完成迁移
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb` : 进程的pcb
+    /// - `mask` : 新的cpu掩码
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL` : 掩码中没有任何已经上线的cpu，或者目标是idle进程
+    pub fn set_cpus_allowed(
+        pcb: &Arc<ProcessControlBlock>,
+        mask: &CpuMask,
+    ) -> Result<(), SystemError> {
+        if pcb.sched_info().policy() == SchedPolicy::IDLE {
+            return Err(SystemError::EINVAL);
+        }
+
+        let present = smp_cpu_manager().present_cpus();
+        let mut new_mask = CpuMask::new();
+        for cpu in mask.iter_cpu() {
+            if present.get(cpu).unwrap_or(false) {
+                new_mask.set(cpu, true);
+            }
+        }
+        if new_mask.is_empty() {
+            return Err(SystemError::EINVAL);
+        }
+
+        let need_migrate = pcb
+            .sched_info()
+            .on_cpu()
+            .map(|cpu| !new_mask.get(cpu).unwrap_or(false))
+            .unwrap_or(false);
+        pcb.sched_info().set_cpus_allowed(new_mask);
+
+        if !need_migrate {
+            return Ok(());
+        }
+
+        pcb.flags().insert(ProcessFlags::NEED_MIGRATE);
+        if Arc::ptr_eq(pcb, &ProcessManager::current_pcb()) {
+            schedule(SchedMode::SM_NONE);
+        } else {
+            // 如果目标进程正在其他cpu上运行，让它尽快被换出
+            pcb.flags().insert(ProcessFlags::NEED_SCHEDULE);
+            Self::kick(pcb);
+        }
+
+        return Ok(());
+    }
+
+    /// 完成上一次调度时记录下来的进程迁移
+    ///
+    /// 必须在本cpu上的进程切换完成之后调用，此时被迁移的进程已经不在本cpu上运行，
+    /// 可以安全地修改它的`on_cpu`并将它加入目标cpu的运行队列
+    pub fn finish_migrate() {
+        let migrate = unsafe {
+            PROCESS_SWITCH_RESULT
+                .as_mut()
+                .unwrap()
+                .get_mut()
+                .migrate_pcb
+                .take()
+        };
+        let (pcb, need_wakeup) = match migrate {
+            Some(migrate) => migrate,
+            None => return,
+        };
+
+        let writer = pcb.sched_info().inner_lock_write_irqsave();
+        if !writer.state().is_blocked() {
+            // 进程已经在原来的cpu上被唤醒，等到它下一次被换出时再迁移
+            return;
+        }
+        pcb.flags().remove(ProcessFlags::NEED_MIGRATE);
+        let cpu = Self::select_task_cpu(&pcb, current_cpu_id());
+        pcb.sched_info().set_on_cpu(Some(cpu));
+        drop(writer);
+
+        if need_wakeup {
+            Self::wakeup(&pcb).ok();
+        }
     }
 
     /// 如果目标进程正在目标CPU上运行，那么就让这个cpu陷入内核态
@@ -475,7 +1244,6 @@ impl ProcessManager {
     /// ## 参数
     ///
     /// - `pcb` : 进程的pcb
-    #[allow(dead_code)]
     pub fn kick(pcb: &Arc<ProcessControlBlock>) {
         ProcessManager::current_pcb().preempt_disable();
         let cpu_id = pcb.sched_info().on_cpu();
@@ -566,6 +1334,22 @@ impl ProcessState {
     }
 }
 
+/// 进程的退出状态，供wait4等待子进程时观察
+///
+/// 状态只会按照`Running -> Zombie -> Dead`的顺序转换：
+/// - 进程完成退出流程、通知父进程之前，转换为`Zombie`
+/// - 进程被回收时，转换为`Dead`
+#[atomic_enum]
+#[derive(PartialEq, Eq)]
+pub enum ExitState {
+    /// 进程尚未退出，或者还在执行退出流程
+    Running,
+    /// 进程已经退出，等待父进程回收。此时退出码已经可见
+    Zombie,
+    /// 进程已经被回收
+    Dead,
+}
+
 bitflags! {
     /// pcb的标志位
     pub struct ProcessFlags: usize {
@@ -587,14 +1371,30 @@ bitflags! {
         const NEED_MIGRATE = 1 << 7;
         /// 随机化的虚拟地址空间，主要用于动态链接器的加载
         const RANDOMIZE = 1 << 8;
+        /// 进程在返回用户态之前需要更新rseq
+        const NEED_RSEQ = 1 << 9;
+        /// 进程第一次被调度时，需要把tid写入set_child_tid
+        const NEED_SET_CHILD_TID = 1 << 10;
+        /// 进程是fork出来的，并且还没有执行过execve
+        const FORKNOEXEC = 1 << 11;
+        /// 进程由spawn创建，在返回用户态之前需要执行spawn指定的程序
+        const NEED_SPAWN_EXEC = 1 << 12;
+        /// 进程刚刚被创建，还没有被[`ProcessManager::wake_up_new_task`]第一次唤醒。
+        /// 此时它的pcb可能还没有完全初始化（例如还没有注册procfs），其他地方对它的唤醒都会被忽略
+        const NEW = 1 << 13;
     }
 }
 
 #[derive(Debug)]
 pub struct ProcessControlBlock {
-    /// 当前进程的pid
+    /// 当前线程的tid
+    ///
+    /// tid与pid从同一个id空间中分配，线程组中的每个线程都有各自的tid。
+    /// 线程组组长的tid与线程组id相同
     pid: Pid,
     /// 当前进程的线程组id（这个值在同一个线程组内永远不变）
+    ///
+    /// 使用CLONE_THREAD创建的线程继承组长的线程组id，否则等于自身的tid
     tgid: Pid,
 
     basic: RwLock<ProcessBasicInfo>,
@@ -617,8 +1417,23 @@ pub struct ProcessControlBlock {
     sig_info: RwLock<ProcessSignalInfo>,
     /// 信号处理结构体
     sig_struct: SpinLock<SignalStruct>,
-    /// 退出信号S
+    /// 进程退出时发送给父进程的信号，为`Signal::INVALID`时不发送
     exit_signal: AtomicSignal,
+    /// 父进程退出时，向当前进程发送的信号（通过prctl(PR_SET_PDEATHSIG)设置）
+    pdeath_signal: AtomicSignal,
+    /// OOM killer选择受害者时对评分的调整值，范围为[`OOM_SCORE_ADJ_MIN`, `OOM_SCORE_ADJ_MAX`]
+    ///
+    /// [`OOM_SCORE_ADJ_MIN`]: crate::mm::oom::OOM_SCORE_ADJ_MIN
+    /// [`OOM_SCORE_ADJ_MAX`]: crate::mm::oom::OOM_SCORE_ADJ_MAX
+    oom_score_adj: AtomicI16,
+    /// 进程的退出状态
+    exit_state: AtomicExitState,
+    /// 进程的退出码，在进程成为僵尸进程之前写入
+    exit_code: AtomicUsize,
+    /// 导致进程停止、并且还没有被wait报告的信号。没有未报告的停止事件时为`Signal::INVALID`
+    stop_signal: AtomicSignal,
+    /// 进程从停止状态中恢复，并且还没有被wait以WCONTINUED报告
+    continued: AtomicBool,
 
     /// 父进程指针
     parent_pcb: RwLock<Weak<ProcessControlBlock>>,
@@ -628,14 +1443,64 @@ pub struct ProcessControlBlock {
     /// 子进程链表
     children: RwLock<Vec<Pid>>,
 
+    /// 线程组中除组长以外的其他线程（仅在线程组组长中有效）
+    thread_group: RwLock<Vec<Pid>>,
+
     /// 等待队列
     wait_queue: WaitQueue,
 
+    /// 等待子进程状态变化（退出、停止、继续）的等待队列，wait4等系统调用会在这里睡眠
+    wait_chldexit: WaitQueue,
+
     /// 线程信息
     thread: RwLock<ThreadInfo>,
 
-    /// 进程的robust lock列表
-    robust_list: RwLock<Option<RobustListHead>>,
+    /// 跟踪当前进程的进程（见[`ptrace`]）
+    ptrace: SpinLock<PtraceLink>,
+
+    /// 当前进程正在跟踪的进程
+    ptraced: RwLock<Vec<Pid>>,
+
+    /// 线程注册的robust lock列表头部的用户空间地址
+    robust_list: RwLock<Option<VirtAddr>>,
+
+    /// 进程注册的rseq
+    rseq: RwLock<Option<RseqRegistration>>,
+
+    /// 进程最近一次execve时的命令行参数，每个参数都以\0结尾（/proc/<pid>/cmdline的内容）
+    cmdline: RwLock<Arc<[u8]>>,
+
+    /// 通过epoll等待该进程退出的pidfd
+    pidfd_epitems: SpinLock<LinkedList<Arc<EPollItem>>>,
+
+    /// 进程的资源限制
+    rlimits: RwLock<[RLimit64; RLimitID::Nlimits as usize]>,
+
+    /// System V信号量的撤销列表，使用CLONE_SYSVSEM创建的进程之间共享
+    sysvsem: RwLock<Arc<SemUndoList>>,
+
+    /// I/O上下文，使用CLONE_IO创建的进程之间共享
+    io_context: RwLock<Arc<IoContext>>,
+
+    /// 在signalfd上等待信号到来的等待队列
+    signalfd_wait: WaitQueue,
+    /// 通过epoll监听signalfd的epitem
+    signalfd_epitems: SpinLock<LinkedList<Arc<EPollItem>>>,
+
+    /// 进程的fork次数、上下文切换次数等统计信息
+    stats: ProcessStats,
+
+    /// pid的代数，用于区分先后使用同一个pid的不同进程
+    pid_generation: u64,
+
+    /// 进程被创建的时间（自系统启动以来的jiffies）
+    start_time: u64,
+
+    /// 进程在各层pid namespace中的pid，下标为namespace的层数。
+    /// 第0项是初始namespace中的pid（即`pid`），最后一项是进程所在的namespace中的pid
+    pid_links: RwLock<Vec<UPid>>,
+    /// 进程所在的各个namespace
+    nsproxy: RwLock<Arc<NsProxy>>,
 }
 
 impl ProcessControlBlock {
@@ -648,30 +1513,67 @@ impl ProcessControlBlock {
     ///
     /// ## 返回值
     ///
-    /// 返回一个新的pcb
-    pub fn new(name: String, kstack: KernelStack) -> Arc<Self> {
-        return Self::do_create_pcb(name, kstack, false);
+    /// - 成功：返回一个新的pcb
+    /// - 失败：为系统调用栈分配内存失败时，返回Err(SystemError::ENOMEM)
+    pub fn new(name: String, kstack: KernelStack) -> Result<Arc<Self>, SystemError> {
+        return Self::do_create_pcb(name, kstack, false, None);
+    }
+
+    /// 使用指定的pid创建一个新的pcb（用于clone3的set_tid）
+    ///
+    /// ## 参数
+    ///
+    /// - `name` : 进程的名字
+    /// - `kstack` : 进程的内核栈
+    /// - `pid` : 新进程的pid
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：返回一个新的pcb
+    /// - `EINVAL`：pid超出了合法的范围
+    /// - `EEXIST`：pid已经被占用
+    pub fn new_with_pid(
+        name: String,
+        kstack: KernelStack,
+        pid: Pid,
+    ) -> Result<Arc<Self>, SystemError> {
+        return Self::do_create_pcb(name, kstack, false, Some(pid));
     }
 
     /// 创建一个新的idle进程
     ///
     /// 请注意，这个函数只能在进程管理初始化的时候调用。
-    pub fn new_idle(cpu_id: u32, kstack: KernelStack) -> Arc<Self> {
+    pub fn new_idle(cpu_id: u32, kstack: KernelStack) -> Result<Arc<Self>, SystemError> {
         let name = format!("idle-{}", cpu_id);
-        return Self::do_create_pcb(name, kstack, true);
+        return Self::do_create_pcb(name, kstack, true, None);
     }
 
     #[inline(never)]
-    fn do_create_pcb(name: String, kstack: KernelStack, is_idle: bool) -> Arc<Self> {
-        let (pid, ppid, cwd) = if is_idle {
-            (Pid(0), Pid(0), "/".to_string())
+    fn do_create_pcb(
+        name: String,
+        kstack: KernelStack,
+        is_idle: bool,
+        pid: Option<Pid>,
+    ) -> Result<Arc<Self>, SystemError> {
+        // 先分配系统调用栈，避免分配失败时浪费pid
+        let syscall_stack = KernelStack::new()?;
+
+        let (pid, ppid, pgid, sid, cwd) = if is_idle {
+            (Pid(0), Pid(0), Pid(0), Pid(0), "/".to_string())
         } else {
+            let pid = match pid {
+                Some(pid) => Self::alloc_pid(pid)?,
+                None => Self::generate_pid()?,
+            };
             let ppid = ProcessManager::current_pcb().pid();
+            // 子进程与父进程位于同一个进程组和会话中
+            let pgid = ProcessManager::current_pcb().basic().pgid();
+            let sid = ProcessManager::current_pcb().basic().sid();
             let cwd = ProcessManager::current_pcb().basic().cwd();
-            (Self::generate_pid(), ppid, cwd)
+            (pid, ppid, pgid, sid, cwd)
         };
 
-        let basic_info = ProcessBasicInfo::new(Pid(0), ppid, name, cwd, None);
+        let basic_info = ProcessBasicInfo::new(pgid, sid, ppid, name, cwd, None);
         let preempt_count = AtomicUsize::new(0);
         let flags = unsafe { LockFreeFlags::new(ProcessFlags::empty()) };
 
@@ -689,19 +1591,42 @@ impl ProcessControlBlock {
             preempt_count,
             flags,
             kernel_stack: RwLock::new(kstack),
-            syscall_stack: RwLock::new(KernelStack::new().unwrap()),
+            syscall_stack: RwLock::new(syscall_stack),
             worker_private: SpinLock::new(None),
             sched_info,
             arch_info,
             sig_info: RwLock::new(ProcessSignalInfo::default()),
             sig_struct: SpinLock::new(SignalStruct::new()),
             exit_signal: AtomicSignal::new(Signal::SIGCHLD),
+            pdeath_signal: AtomicSignal::new(Signal::INVALID),
+            oom_score_adj: AtomicI16::new(0),
+            exit_state: AtomicExitState::new(ExitState::Running),
+            exit_code: AtomicUsize::new(0),
+            stop_signal: AtomicSignal::new(Signal::INVALID),
+            continued: AtomicBool::new(false),
             parent_pcb: RwLock::new(ppcb.clone()),
             real_parent_pcb: RwLock::new(ppcb),
             children: RwLock::new(Vec::new()),
+            thread_group: RwLock::new(Vec::new()),
             wait_queue: WaitQueue::default(),
+            wait_chldexit: WaitQueue::default(),
             thread: RwLock::new(ThreadInfo::new()),
+            ptrace: SpinLock::new(PtraceLink::new()),
+            ptraced: RwLock::new(Vec::new()),
             robust_list: RwLock::new(None),
+            rseq: RwLock::new(None),
+            cmdline: RwLock::new(Arc::from(Vec::new())),
+            pidfd_epitems: SpinLock::new(LinkedList::new()),
+            rlimits: RwLock::new(RLimit64::INIT_RLIMITS),
+            sysvsem: RwLock::new(Arc::new(SemUndoList::new())),
+            io_context: RwLock::new(IoContext::new(0)),
+            signalfd_wait: WaitQueue::default(),
+            signalfd_epitems: SpinLock::new(LinkedList::new()),
+            stats: ProcessStats::default(),
+            pid_generation: NEXT_PID_GENERATION.fetch_add(1, Ordering::SeqCst),
+            start_time: clock(),
+            pid_links: RwLock::new(vec![UPid::new(INIT_PID_NS.clone(), pid)]),
+            nsproxy: RwLock::new(INIT_NSPROXY.clone()),
         };
 
         // 初始化系统调用栈
@@ -723,125 +1648,477 @@ impl ProcessControlBlock {
                 .set_pcb(Arc::downgrade(&pcb))
                 .unwrap();
 
-            pcb.syscall_stack
-                .write()
-                .set_pcb(Arc::downgrade(&pcb))
-                .unwrap()
-        };
+            pcb.syscall_stack
+                .write()
+                .set_pcb(Arc::downgrade(&pcb))
+                .unwrap()
+        };
+
+        // 将当前pcb加入父进程的子进程哈希表中
+        if pcb.pid() > Pid(1) {
+            if let Some(ppcb_arc) = pcb.parent_pcb.read_irqsave().upgrade() {
+                let mut children = ppcb_arc.children.write_irqsave();
+                children.push(pcb.pid());
+            } else {
+                panic!("parent pcb is None");
+            }
+        }
+
+        return Ok(pcb);
+    }
+
+    /// 返回当前的pid上限（不包含）
+    pub fn pid_max() -> usize {
+        return PID_MAX.load(Ordering::SeqCst);
+    }
+
+    /// 设置pid上限（不包含）
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL`：上限不在(`RESERVED_PIDS`, `PID_MAX_LIMIT`]范围内
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/pid.c#61
+    pub fn set_pid_max(pid_max: usize) -> Result<(), SystemError> {
+        if pid_max <= RESERVED_PIDS || pid_max > PID_MAX_LIMIT {
+            return Err(SystemError::EINVAL);
+        }
+        PID_MAX.store(pid_max, Ordering::SeqCst);
+        return Ok(());
+    }
+
+    /// 生成一个新的pid
+    ///
+    /// 从上一次分配的pid之后开始，查找第一个没有被占用的pid。到达[`ProcessManager::pid_max`]之后，
+    /// 从[`RESERVED_PIDS`]开始重新查找，以复用已经被释放的pid。
+    ///
+    /// ## 返回值
+    ///
+    /// - `EAGAIN`：没有空闲的pid
+    fn generate_pid() -> Result<Pid, SystemError> {
+        return USED_PIDS.lock_irqsave().alloc(ProcessManager::pid_max());
+    }
+
+    /// 分配一个指定的pid
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL`：pid超出了合法的范围
+    /// - `EEXIST`：pid已经被占用
+    fn alloc_pid(pid: Pid) -> Result<Pid, SystemError> {
+        return USED_PIDS
+            .lock_irqsave()
+            .alloc_specific(pid, ProcessManager::pid_max());
+    }
+
+    /// 返回当前进程的锁持有计数
+    #[inline(always)]
+    pub fn preempt_count(&self) -> usize {
+        return self.preempt_count.load(Ordering::SeqCst);
+    }
+
+    /// 增加当前进程的锁持有计数
+    #[inline(always)]
+    pub fn preempt_disable(&self) {
+        self.preempt_count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// 减少当前进程的锁持有计数
+    #[inline(always)]
+    pub fn preempt_enable(&self) {
+        self.preempt_count.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    #[inline(always)]
+    pub unsafe fn set_preempt_count(&self, count: usize) {
+        self.preempt_count.store(count, Ordering::SeqCst);
+    }
+
+    #[inline(always)]
+    pub fn flags(&self) -> &mut ProcessFlags {
+        return self.flags.get_mut();
+    }
+
+    /// 请注意，这个值能在中断上下文中读取，但不能被中断上下文修改
+    /// 否则会导致死锁
+    #[inline(always)]
+    pub fn basic(&self) -> RwLockReadGuard<ProcessBasicInfo> {
+        return self.basic.read_irqsave();
+    }
+
+    #[inline(always)]
+    pub fn set_name(&self, name: String) {
+        self.basic.write().set_name(name);
+    }
+
+    #[inline(always)]
+    pub fn basic_mut(&self) -> RwLockWriteGuard<ProcessBasicInfo> {
+        return self.basic.write_irqsave();
+    }
+
+    /// # 获取arch info的锁，同时关闭中断
+    #[inline(always)]
+    pub fn arch_info_irqsave(&self) -> SpinLockGuard<ArchPCBInfo> {
+        return self.arch_info.lock_irqsave();
+    }
+
+    /// # 获取arch info的锁，但是不关闭中断
+    ///
+    /// 由于arch info在进程切换的时候会使用到，
+    /// 因此在中断上下文外，获取arch info 而不irqsave是不安全的.
+    ///
+    /// 只能在以下情况下使用这个函数：
+    /// - 在中断上下文中（中断已经禁用），获取arch info的锁。
+    /// - 刚刚创建新的pcb
+    #[inline(always)]
+    pub unsafe fn arch_info(&self) -> SpinLockGuard<ArchPCBInfo> {
+        return self.arch_info.lock();
+    }
+
+    #[inline(always)]
+    pub fn kernel_stack(&self) -> RwLockReadGuard<KernelStack> {
+        return self.kernel_stack.read();
+    }
+
+    #[inline(always)]
+    #[allow(dead_code)]
+    pub fn kernel_stack_mut(&self) -> RwLockWriteGuard<KernelStack> {
+        return self.kernel_stack.write();
+    }
+
+    #[inline(always)]
+    pub fn sched_info(&self) -> &ProcessSchedulerInfo {
+        return &self.sched_info;
+    }
+
+    #[inline(always)]
+    pub fn worker_private(&self) -> SpinLockGuard<Option<WorkerPrivate>> {
+        return self.worker_private.lock();
+    }
+
+    /// 获取当前线程的tid，与[`ProcessControlBlock::tid`]相同
+    ///
+    /// 如果需要的是用户态看到的进程id（getpid），请使用[`ProcessControlBlock::tgid`]
+    #[inline(always)]
+    pub fn pid(&self) -> Pid {
+        return self.pid;
+    }
+
+    /// 获取当前线程的tid（gettid）
+    #[inline(always)]
+    pub fn tid(&self) -> Pid {
+        return self.pid;
+    }
+
+    /// 获取当前线程所在线程组的id，也就是用户态看到的进程id（getpid）
+    #[inline(always)]
+    pub fn tgid(&self) -> Pid {
+        return self.tgid;
+    }
+
+    /// 获取进程所在的pid namespace
+    pub fn pid_ns(&self) -> Arc<PidNamespace> {
+        return self.pid_links.read_irqsave().last().unwrap().ns.clone();
+    }
+
+    /// 获取进程所在的各个namespace
+    pub fn nsproxy(&self) -> Arc<NsProxy> {
+        return self.nsproxy.read_irqsave().clone();
+    }
+
+    pub fn set_nsproxy(&self, nsproxy: Arc<NsProxy>) {
+        *self.nsproxy.write_irqsave() = nsproxy;
+    }
+
+    /// 替换进程的nsproxy中的某个namespace
+    ///
+    /// nsproxy可能与其他进程共享，因此总是为当前进程创建一个新的nsproxy
+    fn update_nsproxy(&self, f: impl FnOnce(&mut NsProxy)) {
+        let mut guard = self.nsproxy.write_irqsave();
+        let mut nsproxy = NsProxy::clone(&guard);
+        f(&mut nsproxy);
+        *guard = Arc::new(nsproxy);
+    }
+
+    /// 获取子进程所在的pid namespace
+    pub fn pid_ns_for_children(&self) -> Arc<PidNamespace> {
+        return self.nsproxy.read_irqsave().pid_ns_for_children.clone();
+    }
+
+    pub fn set_pid_ns_for_children(&self, ns: Arc<PidNamespace>) {
+        self.update_nsproxy(|nsproxy| nsproxy.pid_ns_for_children = ns);
+    }
 
-        // 将当前pcb加入父进程的子进程哈希表中
-        if pcb.pid() > Pid(1) {
-            if let Some(ppcb_arc) = pcb.parent_pcb.read_irqsave().upgrade() {
-                let mut children = ppcb_arc.children.write_irqsave();
-                children.push(pcb.pid());
-            } else {
-                panic!("parent pcb is None");
-            }
+    /// 获取进程（线程）在pid namespace `ns`中的tid
+    ///
+    /// ## 返回值
+    ///
+    /// 如果进程在`ns`中不可见（进程位于`ns`的祖先namespace或者与`ns`无关的namespace中），则返回0
+    pub fn pid_nr_ns(&self, ns: &PidNamespace) -> Pid {
+        return self
+            .pid_links
+            .read_irqsave()
+            .get(ns.level())
+            .filter(|upid| core::ptr::eq(upid.ns.as_ref(), ns))
+            .map(|upid| upid.nr)
+            .unwrap_or(Pid(0));
+    }
+
+    /// 获取进程（线程）在当前进程所在的pid namespace中的tid，用于向用户态返回
+    pub fn pid_vnr(&self) -> Pid {
+        return self.pid_nr_ns(&ProcessManager::current_pcb().pid_ns());
+    }
+
+    /// 获取进程的线程组id在当前进程所在的pid namespace中的值，用于向用户态返回
+    pub fn tgid_vnr(&self) -> Pid {
+        if self.is_thread_group_leader() {
+            return self.pid_vnr();
         }
+        return ProcessManager::find(self.tgid)
+            .map(|leader| leader.pid_vnr())
+            .unwrap_or(Pid(0));
+    }
 
-        return pcb;
+    /// 获取进程所在的uts namespace
+    pub fn uts_ns(&self) -> Arc<UtsNamespace> {
+        return self.nsproxy.read_irqsave().uts_ns.clone();
     }
 
-    /// 生成一个新的pid
-    #[inline(always)]
-    fn generate_pid() -> Pid {
-        static NEXT_PID: AtomicPid = AtomicPid::new(Pid(1));
-        return NEXT_PID.fetch_add(Pid(1), Ordering::SeqCst);
+    pub fn set_uts_ns(&self, ns: Arc<UtsNamespace>) {
+        self.update_nsproxy(|nsproxy| nsproxy.uts_ns = ns);
     }
 
-    /// 返回当前进程的锁持有计数
-    #[inline(always)]
-    pub fn preempt_count(&self) -> usize {
-        return self.preempt_count.load(Ordering::SeqCst);
+    /// 获取进程所在的mount namespace
+    pub fn mnt_ns(&self) -> Arc<MntNamespace> {
+        return self.nsproxy.read_irqsave().mnt_ns.clone();
     }
 
-    /// 增加当前进程的锁持有计数
-    #[inline(always)]
-    pub fn preempt_disable(&self) {
-        self.preempt_count.fetch_add(1, Ordering::SeqCst);
+    pub fn set_mnt_ns(&self, ns: Arc<MntNamespace>) {
+        self.update_nsproxy(|nsproxy| nsproxy.mnt_ns = ns);
     }
 
-    /// 减少当前进程的锁持有计数
-    #[inline(always)]
-    pub fn preempt_enable(&self) {
-        self.preempt_count.fetch_sub(1, Ordering::SeqCst);
+    /// 判断进程是否为它所在的pid namespace中的init进程
+    pub fn is_pid_ns_init(&self) -> bool {
+        return self.pid_nr_ns(&self.pid_ns()) == Pid(1);
     }
 
+    /// 获取进程的System V信号量撤销列表
     #[inline(always)]
-    pub unsafe fn set_preempt_count(&self, count: usize) {
-        self.preempt_count.store(count, Ordering::SeqCst);
+    pub fn sysvsem(&self) -> Arc<SemUndoList> {
+        return self.sysvsem.read_irqsave().clone();
     }
 
-    #[inline(always)]
-    pub fn flags(&self) -> &mut ProcessFlags {
-        return self.flags.get_mut();
+    /// 设置进程的System V信号量撤销列表
+    pub fn set_sysvsem(&self, sysvsem: Arc<SemUndoList>) {
+        *self.sysvsem.write_irqsave() = sysvsem;
     }
 
-    /// 请注意，这个值能在中断上下文中读取，但不能被中断上下文修改
-    /// 否则会导致死锁
+    /// 获取进程的I/O上下文
     #[inline(always)]
-    pub fn basic(&self) -> RwLockReadGuard<ProcessBasicInfo> {
-        return self.basic.read_irqsave();
+    pub fn io_context(&self) -> Arc<IoContext> {
+        return self.io_context.read_irqsave().clone();
     }
 
-    #[inline(always)]
-    pub fn set_name(&self, name: String) {
-        self.basic.write().set_name(name);
+    /// 设置进程的I/O上下文
+    pub fn set_io_context(&self, io_context: Arc<IoContext>) {
+        *self.io_context.write_irqsave() = io_context;
     }
 
+    /// 返回进程的退出状态
     #[inline(always)]
-    pub fn basic_mut(&self) -> RwLockWriteGuard<ProcessBasicInfo> {
-        return self.basic.write_irqsave();
+    pub fn exit_state(&self) -> ExitState {
+        return self.exit_state.load(Ordering::SeqCst);
     }
 
-    /// # 获取arch info的锁，同时关闭中断
+    /// 返回进程的退出码。只有在退出状态为[`ExitState::Zombie`]之后才有意义
     #[inline(always)]
-    pub fn arch_info_irqsave(&self) -> SpinLockGuard<ArchPCBInfo> {
-        return self.arch_info.lock_irqsave();
+    pub fn exit_code(&self) -> usize {
+        return self.exit_code.load(Ordering::SeqCst);
     }
 
-    /// # 获取arch info的锁，但是不关闭中断
+    /// 返回当前线程消耗的cpu时间（纳秒），分别为用户态与内核态的时间
     ///
-    /// 由于arch info在进程切换的时候会使用到，
-    /// 因此在中断上下文外，获取arch info 而不irqsave是不安全的.
+    /// 总的运行时间来自调度器，按照时钟tick采样得到的比例划分为用户态与内核态的时间
+    pub fn thread_cputime(&self) -> (u64, u64) {
+        let (utime, stime) = self.stats.sampled_cputime();
+        return CpuTimeFunc::cputime_adjust(
+            self.sched_info.sched_entity().sum_exec_runtime,
+            utime,
+            stime,
+        );
+    }
+
+    /// 返回进程（整个线程组）消耗的cpu时间（纳秒），分别为用户态与内核态的时间
     ///
-    /// 只能在以下情况下使用这个函数：
-    /// - 在中断上下文中（中断已经禁用），获取arch info的锁。
-    /// - 刚刚创建新的pcb
+    /// 包括线程组中仍在运行的线程，以及已经退出的线程，不包括子进程
+    pub fn cputime(&self) -> (u64, u64) {
+        let leader = match ProcessManager::find(self.tgid) {
+            Some(leader) => leader,
+            None => return self.thread_cputime(),
+        };
+        let (mut utime, mut stime) = leader.stats.dead_threads_cputime();
+        let threads = leader.thread_group.read_irqsave().clone();
+        let threads = threads.into_iter().filter_map(ProcessManager::find);
+        for thread in core::iter::once(leader.clone()).chain(threads) {
+            let (u, s) = thread.thread_cputime();
+            utime += u;
+            stime += s;
+        }
+        return (utime, stime);
+    }
+
+    /// 返回进程（整个线程组）的缺页次数，分别为minor与major缺页的次数
+    ///
+    /// 包括线程组中仍在运行的线程，以及已经退出的线程，不包括子进程
+    pub fn faults(&self) -> (usize, usize) {
+        let leader = match ProcessManager::find(self.tgid) {
+            Some(leader) => leader,
+            None => return self.stats.faults(),
+        };
+        let (mut min_flt, mut maj_flt) = leader.stats.dead_threads_faults();
+        let threads = leader.thread_group.read_irqsave().clone();
+        let threads = threads.into_iter().filter_map(ProcessManager::find);
+        for thread in core::iter::once(leader.clone()).chain(threads) {
+            let (min, maj) = thread.stats.faults();
+            min_flt += min;
+            maj_flt += maj;
+        }
+        return (min_flt, maj_flt);
+    }
+
+    /// 取出进程尚未被报告的停止事件
+    ///
+    /// ## 参数
+    ///
+    /// - `consume` : 是否在取出之后清除该事件。为false时，之后还能再次取出
+    ///
+    /// ## 返回值
+    ///
+    /// 进程处于停止状态，并且停止事件还没有被报告时，返回导致停止的信号
+    pub fn take_stop_signal(&self, consume: bool) -> Option<Signal> {
+        let state = self.sched_info().inner_lock_read_irqsave().state();
+        if !state.is_stopped() {
+            return None;
+        }
+        let sig = if consume {
+            self.stop_signal.swap(Signal::INVALID, Ordering::SeqCst)
+        } else {
+            self.stop_signal.load(Ordering::SeqCst)
+        };
+        return (sig != Signal::INVALID).then_some(sig);
+    }
+
+    /// 取出进程尚未被报告的继续事件
+    ///
+    /// ## 参数
+    ///
+    /// - `consume` : 是否在取出之后清除该事件
+    pub fn take_continued(&self, consume: bool) -> bool {
+        if consume {
+            return self.continued.swap(false, Ordering::SeqCst);
+        }
+        return self.continued.load(Ordering::SeqCst);
+    }
+
+    /// 把僵尸进程标记为已回收
+    ///
+    /// ## 返回值
+    ///
+    /// 如果进程之前是僵尸进程，并且由本次调用完成了标记，返回true。
+    /// 多个线程同时等待同一个子进程时，只有一个能够成功回收它
+    pub fn try_mark_dead(&self) -> bool {
+        return self
+            .exit_state
+            .compare_exchange(
+                ExitState::Zombie,
+                ExitState::Dead,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            )
+            .is_ok();
+    }
+
+    /// 返回父进程退出时，当前进程会收到的信号。没有设置时返回`Signal::INVALID`
     #[inline(always)]
-    pub unsafe fn arch_info(&self) -> SpinLockGuard<ArchPCBInfo> {
-        return self.arch_info.lock();
+    pub fn pdeath_signal(&self) -> Signal {
+        return self.pdeath_signal.load(Ordering::SeqCst);
     }
 
     #[inline(always)]
-    pub fn kernel_stack(&self) -> RwLockReadGuard<KernelStack> {
-        return self.kernel_stack.read();
+    pub fn set_pdeath_signal(&self, sig: Signal) {
+        self.pdeath_signal.store(sig, Ordering::SeqCst);
     }
 
+    /// 返回OOM killer对当前进程评分的调整值
     #[inline(always)]
-    #[allow(dead_code)]
-    pub fn kernel_stack_mut(&self) -> RwLockWriteGuard<KernelStack> {
-        return self.kernel_stack.write();
+    pub fn oom_score_adj(&self) -> i16 {
+        return self.oom_score_adj.load(Ordering::SeqCst);
     }
 
+    /// 设置OOM killer对当前进程评分的调整值，调用者需要保证它在合法范围内
     #[inline(always)]
-    pub fn sched_info(&self) -> &ProcessSchedulerInfo {
-        return &self.sched_info;
+    pub fn set_oom_score_adj(&self, adj: i16) {
+        self.oom_score_adj.store(adj, Ordering::SeqCst);
     }
 
+    /// 返回进程的pid代数，参见[`ProcessManager::find_checked`]
     #[inline(always)]
-    pub fn worker_private(&self) -> SpinLockGuard<Option<WorkerPrivate>> {
-        return self.worker_private.lock();
+    pub fn pid_generation(&self) -> u64 {
+        return self.pid_generation;
     }
 
+    /// 返回进程被创建的时间（自系统启动以来的jiffies）
+    ///
+    /// fork时子进程记录的是自己被创建的时间，而不是继承父进程的时间
     #[inline(always)]
-    pub fn pid(&self) -> Pid {
-        return self.pid;
+    pub fn start_time(&self) -> u64 {
+        return self.start_time;
     }
 
+    /// 获取父进程的pcb，如果父进程已经不存在，则返回None
     #[inline(always)]
-    pub fn tgid(&self) -> Pid {
-        return self.tgid;
+    pub fn parent(&self) -> Option<Arc<ProcessControlBlock>> {
+        return self.parent_pcb.read_irqsave().upgrade();
+    }
+
+    /// 获取真实父进程的pcb，如果真实父进程已经不存在，则返回None
+    #[inline(always)]
+    pub fn real_parent(&self) -> Option<Arc<ProcessControlBlock>> {
+        return self.real_parent_pcb.read_irqsave().upgrade();
+    }
+
+    /// 当前进程是否为线程组的组长
+    #[inline(always)]
+    pub fn is_thread_group_leader(&self) -> bool {
+        return self.pid == self.tgid;
+    }
+
+    /// 获取线程组中的所有线程（包括组长）
+    pub fn thread_group(&self) -> Vec<Arc<ProcessControlBlock>> {
+        let leader = match ProcessManager::find(self.tgid) {
+            Some(leader) => leader,
+            None => return Vec::new(),
+        };
+        let mut threads: Vec<Arc<ProcessControlBlock>> = leader
+            .thread_group
+            .read_irqsave()
+            .iter()
+            .filter_map(|pid| ProcessManager::find(*pid))
+            .collect();
+        threads.insert(0, leader);
+        return threads;
+    }
+
+    /// 获取所有子进程的pcb
+    ///
+    /// 已经被回收的子进程不会出现在返回的列表中
+    pub fn children(&self) -> Vec<Arc<ProcessControlBlock>> {
+        return self
+            .children
+            .read_irqsave()
+            .iter()
+            .filter_map(|pid| ProcessManager::find(*pid))
+            .collect();
     }
 
     /// 获取文件描述符表的Arc指针
@@ -876,20 +2153,57 @@ impl ProcessControlBlock {
         return Some(socket);
     }
 
-    /// 当前进程退出时,让初始进程收养所有子进程
+    /// 当前进程退出时,让init进程收养所有子进程
+    ///
+    /// 收养子进程的是当前进程所在的pid namespace的init进程。如果退出的就是这个init进程，
+    /// 则由上一层namespace的init进程收养。
+    ///
+    /// 被收养的子进程中，如果有已经退出（成为僵尸进程）的：
+    /// - 若init进程忽略了SIGCHLD或者为它设置了SA_NOCLDWAIT，则直接由内核回收它们
+    /// - 否则向init进程发送SIGCHLD，由init进程通过wait4回收
     unsafe fn adopt_childen(&self) -> Result<(), SystemError> {
-        match ProcessManager::find(Pid(1)) {
-            Some(init_pcb) => {
-                let childen_guard = self.children.write();
-                let mut init_childen_guard = init_pcb.children.write();
+        let init_pcb = self.find_child_reaper().ok_or(SystemError::ECHILD)?;
+
+        let mut zombies = Vec::new();
+        let mut childen_guard = self.children.write_irqsave();
+        let mut init_childen_guard = init_pcb.children.write_irqsave();
+
+        childen_guard.drain(..).for_each(|pid| {
+            if let Some(child) = ProcessManager::find(pid) {
+                *child.parent_pcb.write_irqsave() = Arc::downgrade(&init_pcb);
+                *child.real_parent_pcb.write_irqsave() = Arc::downgrade(&init_pcb);
+                if child.exit_state() == ExitState::Zombie {
+                    zombies.push(child);
+                }
+            }
+            init_childen_guard.push(pid);
+        });
+        drop(init_childen_guard);
+        drop(childen_guard);
 
-                childen_guard.iter().for_each(|pid| {
-                    init_childen_guard.push(*pid);
-                });
+        // 像这些子进程刚刚退出一样通知init进程
+        for child in zombies {
+            ProcessManager::do_notify_parent(&child, &init_pcb);
+        }
 
-                return Ok(());
+        return Ok(());
+    }
+
+    /// 查找收养当前进程的子进程的init进程
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/exit.c#576
+    fn find_child_reaper(&self) -> Option<Arc<ProcessControlBlock>> {
+        let mut ns = self.pid_ns();
+        loop {
+            if let Some(reaper) = ns.child_reaper() {
+                if reaper.pid() != self.pid && reaper.exit_state() == ExitState::Running {
+                    return Some(reaper);
+                }
             }
-            _ => Err(SystemError::ECHILD),
+            ns = match ns.parent() {
+                Some(parent) => parent.clone(),
+                None => return ProcessManager::find(Pid(1)),
+            };
         }
     }
 
@@ -949,30 +2263,67 @@ impl ProcessControlBlock {
         self.sig_struct.lock_irqsave()
     }
 
+    pub fn signalfd_wait(&self) -> &WaitQueue {
+        &self.signalfd_wait
+    }
+
+    pub fn signalfd_epitems(&self) -> &SpinLock<LinkedList<Arc<EPollItem>>> {
+        &self.signalfd_epitems
+    }
+
+    #[inline(always)]
+    pub fn stats(&self) -> &ProcessStats {
+        &self.stats
+    }
+
     #[inline(always)]
-    pub fn get_robust_list(&self) -> RwLockReadGuard<Option<RobustListHead>> {
-        return self.robust_list.read_irqsave();
+    pub fn get_robust_list(&self) -> Option<VirtAddr> {
+        return *self.robust_list.read_irqsave();
     }
 
     #[inline(always)]
-    pub fn set_robust_list(&self, new_robust_list: Option<RobustListHead>) {
+    pub fn set_robust_list(&self, new_robust_list: Option<VirtAddr>) {
         *self.robust_list.write_irqsave() = new_robust_list;
     }
+
+    #[inline(always)]
+    pub fn get_rseq(&self) -> RwLockReadGuard<Option<RseqRegistration>> {
+        return self.rseq.read_irqsave();
+    }
+
+    #[inline(always)]
+    pub fn set_rseq(&self, new_rseq: Option<RseqRegistration>) {
+        *self.rseq.write_irqsave() = new_rseq;
+    }
+
+    /// 获取进程的命令行参数，每个参数都以\0结尾。内核线程以及没有执行过execve的进程的命令行参数为空
+    pub fn cmdline(&self) -> Arc<[u8]> {
+        return self.cmdline.read_irqsave().clone();
+    }
+
+    pub fn set_cmdline(&self, cmdline: Arc<[u8]>) {
+        *self.cmdline.write_irqsave() = cmdline;
+    }
 }
 
 impl Drop for ProcessControlBlock {
     fn drop(&mut self) {
         let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
-        // 在ProcFS中,解除进程的注册
-        procfs_unregister_pid(self.pid())
-            .unwrap_or_else(|e| panic!("procfs_unregister_pid failed: error: {e:?}"));
+        // 通知各个子系统释放与进程相关的状态（例如删除procfs中的文件）
+        hooks::call_exit_hooks(self);
 
-        if let Some(ppcb) = self.parent_pcb.read_irqsave().upgrade() {
+        if let Some(ppcb) = self.parent() {
             ppcb.children
                 .write_irqsave()
                 .retain(|pid| *pid != self.pid());
         }
 
+        // 归还pid
+        for upid in self.pid_links.read_irqsave().iter().skip(1) {
+            upid.ns.free_pid(upid.nr);
+        }
+        USED_PIDS.lock_irqsave().free(self.pid());
+
         drop(irq_guard);
     }
 }
@@ -984,9 +2335,13 @@ pub struct ThreadInfo {
     clear_child_tid: Option<VirtAddr>,
     set_child_tid: Option<VirtAddr>,
 
-    vfork_done: Option<Arc<Completion>>,
+    vfork_done: Option<Arc<VforkDone>>,
+    /// 由spawn创建的进程在返回用户态之前需要执行的程序
+    spawn: Option<Arc<SpawnRequest>>,
     /// 线程组的组长
     group_leader: Weak<ProcessControlBlock>,
+    /// 线程组的退出码，由exit_group设置（仅在线程组组长中有效）
+    group_exit_code: Option<usize>,
 }
 
 impl ThreadInfo {
@@ -995,7 +2350,9 @@ impl ThreadInfo {
             clear_child_tid: None,
             set_child_tid: None,
             vfork_done: None,
+            spawn: None,
             group_leader: Weak::default(),
+            group_exit_code: None,
         }
     }
 
@@ -1011,13 +2368,15 @@ impl ThreadInfo {
 pub struct ProcessBasicInfo {
     /// 当前进程的进程组id
     pgid: Pid,
+    /// 当前进程所在会话的id
+    sid: Pid,
     /// 当前进程的父进程的pid
     ppid: Pid,
     /// 进程的名字
     name: String,
 
-    /// 当前进程的工作目录
-    cwd: String,
+    /// 文件系统上下文（根目录、工作目录、umask）
+    fs: Arc<RwLock<FsStruct>>,
 
     /// 用户地址空间
     user_vm: Option<Arc<AddressSpace>>,
@@ -1030,6 +2389,7 @@ impl ProcessBasicInfo {
     #[inline(never)]
     pub fn new(
         pgid: Pid,
+        sid: Pid,
         ppid: Pid,
         name: String,
         cwd: String,
@@ -1038,9 +2398,10 @@ impl ProcessBasicInfo {
         let fd_table = Arc::new(RwLock::new(FileDescriptorVec::new()));
         return RwLock::new(Self {
             pgid,
+            sid,
             ppid,
             name,
-            cwd,
+            fs: Arc::new(RwLock::new(FsStruct::new(cwd))),
             user_vm,
             fd_table: Some(fd_table),
         });
@@ -1050,6 +2411,18 @@ impl ProcessBasicInfo {
         return self.pgid;
     }
 
+    pub fn set_pgid(&mut self, pgid: Pid) {
+        self.pgid = pgid;
+    }
+
+    pub fn sid(&self) -> Pid {
+        return self.sid;
+    }
+
+    pub fn set_sid(&mut self, sid: Pid) {
+        self.sid = sid;
+    }
+
     pub fn ppid(&self) -> Pid {
         return self.ppid;
     }
@@ -1063,10 +2436,18 @@ impl ProcessBasicInfo {
     }
 
     pub fn cwd(&self) -> String {
-        return self.cwd.clone();
+        return self.fs.read().cwd();
     }
     pub fn set_cwd(&mut self, path: String) {
-        return self.cwd = path;
+        self.fs.write().set_cwd(path);
+    }
+
+    pub fn fs(&self) -> Arc<RwLock<FsStruct>> {
+        return self.fs.clone();
+    }
+
+    pub fn set_fs(&mut self, fs: Arc<RwLock<FsStruct>>) {
+        self.fs = fs;
     }
 
     pub fn user_vm(&self) -> Option<Arc<AddressSpace>> {
@@ -1086,6 +2467,193 @@ impl ProcessBasicInfo {
     }
 }
 
+/// 进程的统计信息
+///
+/// 这些计数器属于进程自身，不会在fork时被子进程继承
+#[derive(Debug, Default)]
+pub struct ProcessStats {
+    /// 进程成功fork出的子进程（线程）的个数
+    nforks: AtomicUsize,
+    /// 主动让出cpu（如进入睡眠）导致的上下文切换次数
+    nvcsw: AtomicUsize,
+    /// 被抢占导致的上下文切换次数
+    nivcsw: AtomicUsize,
+    /// 时钟tick采样得到的用户态运行时间（纳秒）
+    utime: AtomicU64,
+    /// 时钟tick采样得到的内核态运行时间（纳秒）
+    stime: AtomicU64,
+    /// 线程组中已经退出的其他线程消耗的用户态、内核态时间（纳秒），只在组长线程中使用
+    dead_utime: AtomicU64,
+    dead_stime: AtomicU64,
+    /// 已经被回收的子进程（包括它们回收的子进程）消耗的用户态、内核态时间（纳秒），只在组长线程中使用
+    cutime: AtomicU64,
+    cstime: AtomicU64,
+    /// 不需要读取磁盘就能处理的缺页（例如写时复制）的次数
+    min_flt: AtomicUsize,
+    /// 需要读取磁盘才能处理的缺页的次数
+    maj_flt: AtomicUsize,
+    /// 线程组中已经退出的其他线程的缺页次数，只在组长线程中使用
+    dead_min_flt: AtomicUsize,
+    dead_maj_flt: AtomicUsize,
+    /// 已经被回收的子进程（包括它们回收的子进程）的缺页次数，只在组长线程中使用
+    cmin_flt: AtomicUsize,
+    cmaj_flt: AtomicUsize,
+    /// 进程已经释放（退出或者execve）的地址空间的最大常驻集（KB），只在组长线程中使用
+    maxrss: AtomicUsize,
+    /// 已经被回收的子进程中最大的常驻集（KB），只在组长线程中使用
+    cmaxrss: AtomicUsize,
+}
+
+impl ProcessStats {
+    pub fn nforks(&self) -> usize {
+        self.nforks.load(Ordering::Relaxed)
+    }
+
+    pub fn nvcsw(&self) -> usize {
+        self.nvcsw.load(Ordering::Relaxed)
+    }
+
+    pub fn nivcsw(&self) -> usize {
+        self.nivcsw.load(Ordering::Relaxed)
+    }
+
+    pub fn inc_nforks(&self) {
+        self.nforks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录进程在一个时钟tick中运行的时间
+    ///
+    /// ## 参数
+    ///
+    /// - `user` : 进程是否在用户态运行
+    /// - `ns` : 运行的时间（纳秒）
+    pub fn account_cputime(&self, user: bool, ns: u64) {
+        if user {
+            self.utime.fetch_add(ns, Ordering::Relaxed);
+        } else {
+            self.stime.fetch_add(ns, Ordering::Relaxed);
+        }
+    }
+
+    /// 返回时钟tick采样得到的(用户态时间, 内核态时间)
+    pub fn sampled_cputime(&self) -> (u64, u64) {
+        (
+            self.utime.load(Ordering::Relaxed),
+            self.stime.load(Ordering::Relaxed),
+        )
+    }
+
+    /// 线程组中的一个线程退出时，把它消耗的cpu时间计入组长线程
+    pub fn add_dead_thread_cputime(&self, (utime, stime): (u64, u64)) {
+        self.dead_utime.fetch_add(utime, Ordering::Relaxed);
+        self.dead_stime.fetch_add(stime, Ordering::Relaxed);
+    }
+
+    /// 返回线程组中已经退出的其他线程消耗的(用户态时间, 内核态时间)
+    pub fn dead_threads_cputime(&self) -> (u64, u64) {
+        (
+            self.dead_utime.load(Ordering::Relaxed),
+            self.dead_stime.load(Ordering::Relaxed),
+        )
+    }
+
+    /// 回收子进程时，把子进程消耗的cpu时间计入父进程的组长线程
+    pub fn add_children_cputime(&self, (utime, stime): (u64, u64)) {
+        self.cutime.fetch_add(utime, Ordering::Relaxed);
+        self.cstime.fetch_add(stime, Ordering::Relaxed);
+    }
+
+    /// 返回已经被回收的子进程消耗的(用户态时间, 内核态时间)
+    pub fn children_cputime(&self) -> (u64, u64) {
+        (
+            self.cutime.load(Ordering::Relaxed),
+            self.cstime.load(Ordering::Relaxed),
+        )
+    }
+
+    /// 记录一次缺页
+    ///
+    /// ## 参数
+    ///
+    /// - `major` : 处理缺页时是否需要读取磁盘
+    pub fn inc_fault(&self, major: bool) {
+        if major {
+            self.maj_flt.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.min_flt.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// 返回当前线程的(minor缺页次数, major缺页次数)
+    pub fn faults(&self) -> (usize, usize) {
+        (
+            self.min_flt.load(Ordering::Relaxed),
+            self.maj_flt.load(Ordering::Relaxed),
+        )
+    }
+
+    /// 线程组中的一个线程退出时，把它的缺页次数计入组长线程
+    pub fn add_dead_thread_faults(&self, (min_flt, maj_flt): (usize, usize)) {
+        self.dead_min_flt.fetch_add(min_flt, Ordering::Relaxed);
+        self.dead_maj_flt.fetch_add(maj_flt, Ordering::Relaxed);
+    }
+
+    /// 返回线程组中已经退出的其他线程的(minor缺页次数, major缺页次数)
+    pub fn dead_threads_faults(&self) -> (usize, usize) {
+        (
+            self.dead_min_flt.load(Ordering::Relaxed),
+            self.dead_maj_flt.load(Ordering::Relaxed),
+        )
+    }
+
+    /// 回收子进程时，把子进程的缺页次数计入父进程的组长线程
+    pub fn add_children_faults(&self, (min_flt, maj_flt): (usize, usize)) {
+        self.cmin_flt.fetch_add(min_flt, Ordering::Relaxed);
+        self.cmaj_flt.fetch_add(maj_flt, Ordering::Relaxed);
+    }
+
+    /// 返回已经被回收的子进程的(minor缺页次数, major缺页次数)
+    pub fn children_faults(&self) -> (usize, usize) {
+        (
+            self.cmin_flt.load(Ordering::Relaxed),
+            self.cmaj_flt.load(Ordering::Relaxed),
+        )
+    }
+
+    /// 返回进程已经释放的地址空间的最大常驻集（KB）
+    pub fn maxrss(&self) -> usize {
+        self.maxrss.load(Ordering::Relaxed)
+    }
+
+    /// 释放地址空间时，用它的最大常驻集（KB）更新进程的最大常驻集
+    pub fn update_maxrss(&self, kb: usize) {
+        self.maxrss.fetch_max(kb, Ordering::Relaxed);
+    }
+
+    /// 返回已经被回收的子进程中最大的常驻集（KB）
+    pub fn children_maxrss(&self) -> usize {
+        self.cmaxrss.load(Ordering::Relaxed)
+    }
+
+    /// 回收子进程时，用子进程（及其回收的子进程）的最大常驻集（KB）更新父进程的记录
+    pub fn update_children_maxrss(&self, kb: usize) {
+        self.cmaxrss.fetch_max(kb, Ordering::Relaxed);
+    }
+
+    /// 记录一次上下文切换
+    ///
+    /// ## 参数
+    ///
+    /// - `voluntary` : 是否为进程主动让出cpu
+    pub fn inc_csw(&self, voluntary: bool) {
+        if voluntary {
+            self.nvcsw.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.nivcsw.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ProcessSchedulerInfo {
     /// 当前进程所在的cpu
@@ -1094,6 +2662,8 @@ pub struct ProcessSchedulerInfo {
     /// 该字段存储要被迁移到的目标处理器核心号
     // migrate_to: AtomicProcessorId,
     inner_locked: RwLock<InnerSchedInfo>,
+    /// 允许进程运行的cpu集合
+    cpus_allowed: RwLock<CpuMask>,
     /// 进程的调度优先级
     // priority: SchedPriority,
     /// 当前进程的虚拟运行时间
@@ -1173,6 +2743,8 @@ impl ProcessSchedulerInfo {
     #[inline(never)]
     pub fn new(on_cpu: Option<ProcessorId>) -> Self {
         let cpu_id = on_cpu.unwrap_or(ProcessorId::INVALID);
+        let mut cpus_allowed = CpuMask::new();
+        cpus_allowed.set_all(true);
         return Self {
             on_cpu: AtomicProcessorId::new(cpu_id),
             // migrate_to: AtomicProcessorId::new(ProcessorId::INVALID),
@@ -1180,6 +2752,7 @@ impl ProcessSchedulerInfo {
                 state: ProcessState::Blocked(false),
                 sleep: false,
             }),
+            cpus_allowed: RwLock::new(cpus_allowed),
             // virtual_runtime: AtomicIsize::new(0),
             // rt_time_slice: AtomicIsize::new(0),
             // priority: SchedPriority::new(100).unwrap(),
@@ -1204,6 +2777,23 @@ impl ProcessSchedulerInfo {
         }
     }
 
+    /// 获取允许进程运行的cpu集合
+    pub fn cpus_allowed(&self) -> CpuMask {
+        return self.cpus_allowed.read_irqsave().clone();
+    }
+
+    /// 设置允许进程运行的cpu集合
+    ///
+    /// 该函数只修改掩码本身，不会迁移进程。需要迁移时应当使用[`ProcessManager::set_cpus_allowed`]
+    pub fn set_cpus_allowed(&self, mask: CpuMask) {
+        *self.cpus_allowed.write_irqsave() = mask;
+    }
+
+    /// 判断进程是否允许在指定的cpu上运行
+    pub fn cpu_allowed(&self, cpu: ProcessorId) -> bool {
+        return self.cpus_allowed.read_irqsave().get(cpu).unwrap_or(false);
+    }
+
     pub fn set_on_cpu(&self, on_cpu: Option<ProcessorId>) {
         if let Some(cpu_id) = on_cpu {
             self.on_cpu.store(cpu_id, Ordering::SeqCst);
@@ -1293,24 +2883,92 @@ impl ProcessSchedulerInfo {
     }
 }
 
+/// 内核栈
+///
+/// 内核栈的布局如下（从低地址到高地址）：
+/// - 第0页：最低地址处存放着pcb的Weak指针
+/// - 第1页：保护页，不进行映射，内核栈溢出时访问它会触发缺页异常
+/// - 其余页：真正可用的栈空间
 #[derive(Debug, Clone)]
 pub struct KernelStack {
     stack: Option<AlignedBox<[u8; KernelStack::SIZE], { KernelStack::ALIGN }>>,
     /// 标记该内核栈是否可以被释放
     can_be_freed: bool,
+    /// 保护页被取消映射前的页表项标志（如果设置了保护页）
+    guard_page: Option<PageFlags<MMArch>>,
 }
 
 impl KernelStack {
-    pub const SIZE: usize = 0x4000;
-    pub const ALIGN: usize = 0x4000;
+    pub const SIZE: usize = 0x8000;
+    pub const ALIGN: usize = 0x8000;
+    /// 保护页相对于内核栈起始地址的偏移量
+    pub const GUARD_PAGE_OFFSET: usize = MMArch::PAGE_SIZE;
 
     pub fn new() -> Result<Self, SystemError> {
-        return Ok(Self {
+        let mut stack = Self {
             stack: Some(
                 AlignedBox::<[u8; KernelStack::SIZE], { KernelStack::ALIGN }>::new_zeroed()?,
             ),
             can_be_freed: true,
-        });
+            guard_page: None,
+        };
+        unsafe { stack.set_guard_page() };
+        return Ok(stack);
+    }
+
+    /// 返回保护页的起始虚拟地址
+    pub fn guard_page_address(&self) -> VirtAddr {
+        return self.start_address() + Self::GUARD_PAGE_OFFSET;
+    }
+
+    /// 判断地址是否位于某个内核栈的保护页中
+    ///
+    /// ## 参数
+    ///
+    /// - `addr` : 要判断的地址
+    /// - `sp` : 访问该地址时的栈指针，用于确认该地址与栈指针位于同一个内核栈中
+    pub fn is_guard_page_address(addr: VirtAddr, sp: VirtAddr) -> bool {
+        let stack_base = sp.data() & !(Self::ALIGN - 1);
+        if addr.data() & !(Self::ALIGN - 1) != stack_base {
+            return false;
+        }
+        let offset = addr.data() - stack_base;
+        return (Self::GUARD_PAGE_OFFSET..Self::GUARD_PAGE_OFFSET + MMArch::PAGE_SIZE)
+            .contains(&offset);
+    }
+
+    /// 取消保护页的映射，使得内核栈溢出时能够触发缺页异常，而不是悄无声息地破坏其他内存
+    #[cfg(target_arch = "x86_64")]
+    unsafe fn set_guard_page(&mut self) {
+        let vaddr = self.guard_page_address();
+        let mut kernel_mapper = KernelMapper::lock();
+        if let Some(mapper) = kernel_mapper.as_mut() {
+            if let Some((_, flags, flusher)) = mapper.unmap_phys(vaddr, false) {
+                // 其他cpu上可能仍缓存着这个页面的映射，这只会导致在它们上面无法检测到溢出，而不会破坏其他内存
+                flusher.flush();
+                self.guard_page = Some(flags);
+            }
+        }
+    }
+
+    /// 目前只在x86_64上，内核的线性映射区域使用4K页进行映射，因此只在x86_64上设置保护页
+    #[cfg(not(target_arch = "x86_64"))]
+    unsafe fn set_guard_page(&mut self) {}
+
+    /// 恢复保护页的映射，在释放内核栈之前调用
+    unsafe fn clear_guard_page(&mut self) {
+        if let Some(flags) = self.guard_page.take() {
+            let vaddr = self.guard_page_address();
+            let paddr = MMArch::virt_2_phys(vaddr).expect("kernel stack is not in linear mapping");
+            let mut kernel_mapper = KernelMapper::lock();
+            let mapper = kernel_mapper
+                .as_mut()
+                .expect("Failed to get kernel mapper when restoring guard page");
+            mapper
+                .map_phys(vaddr, paddr, flags)
+                .expect("Failed to restore the guard page of kernel stack")
+                .flush();
+        }
     }
 
     /// 根据已有的空间，构造一个内核栈结构体
@@ -1328,9 +2986,28 @@ impl KernelStack {
                 ),
             ),
             can_be_freed: false,
+            guard_page: None,
         });
     }
 
+    /// 释放内核栈占用的内存
+    ///
+    /// 只能在进程退出，并且已经切换到其他进程的内核栈上之后调用。释放之后，不能再获取内核栈的地址
+    pub unsafe fn free(&mut self) {
+        if self.stack.is_none() || !self.can_be_freed {
+            return;
+        }
+        let stack = core::mem::replace(
+            self,
+            Self {
+                stack: None,
+                can_be_freed: true,
+                guard_page: None,
+            },
+        );
+        drop(stack);
+    }
+
     /// 返回内核栈的起始虚拟地址(低地址)
     pub fn start_address(&self) -> VirtAddr {
         return VirtAddr::new(self.stack.as_ref().unwrap().as_ptr() as usize);
@@ -1405,6 +3082,8 @@ impl Drop for KernelStack {
                 drop(pcb_ptr);
             }
         }
+        // 释放内存之前，需要先恢复保护页的映射
+        unsafe { self.clear_guard_page() };
         // 如果该内核栈不可以被释放，那么，这里就forget，不调用AlignedBox的drop函数
         if !self.can_be_freed {
             let bx = self.stack.take();
@@ -1421,12 +3100,16 @@ pub fn process_init() {
 pub struct ProcessSignalInfo {
     // 当前进程
     sig_block: SigSet,
-    // sig_pending 中存储当前线程要处理的信号
+    // sig_pending 中存储只能由当前线程处理的信号（tkill、tgkill以及内核发送给这个线程的信号），
+    // 即使信号被当前线程屏蔽，也会留在这里直到当前线程解除屏蔽
     sig_pending: SigPending,
-    // sig_shared_pending 中存储当前线程所属进程要处理的信号
+    // sig_shared_pending 中存储当前线程所属进程要处理、但是被组内所有线程屏蔽的信号。
+    // 只有线程组组长的这个队列会被使用，组内的线程解除屏蔽时会从中取走信号（见recalc_sigpending）
     sig_shared_pending: SigPending,
     // 当前进程对应的tty
     tty: Option<Arc<TtyCore>>,
+    // 信号处理程序的备用栈
+    sig_altstack: SigAltStack,
 }
 
 impl ProcessSignalInfo {
@@ -1458,10 +3141,23 @@ impl ProcessSignalInfo {
         self.tty.clone()
     }
 
+    pub fn sig_altstack(&self) -> &SigAltStack {
+        &self.sig_altstack
+    }
+
+    pub fn sig_altstack_mut(&mut self) -> &mut SigAltStack {
+        &mut self.sig_altstack
+    }
+
     pub fn set_tty(&mut self, tty: Arc<TtyCore>) {
         self.tty = Some(tty);
     }
 
+    /// 使进程失去控制终端
+    pub fn clear_tty(&mut self) {
+        self.tty = None;
+    }
+
     /// 从 pcb 的 siginfo中取出下一个要处理的信号，先处理线程信号，再处理进程信号
     ///
     /// ## 参数
@@ -1485,6 +3181,7 @@ impl Default for ProcessSignalInfo {
             sig_pending: SigPending::default(),
             sig_shared_pending: SigPending::default(),
             tty: None,
+            sig_altstack: SigAltStack::default(),
         }
     }
 }
@@ -1,3 +1,8 @@
+use bitmap::{traits::BitMapOps, AllocBitmap};
+use system_error::SystemError;
+
+use super::{Pid, PID_MAX_DEFAULT, PID_MAX_LIMIT, RESERVED_PIDS};
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy)]
 #[repr(u8)]
@@ -16,3 +21,108 @@ impl PartialEq for PidType {
         *self as u8 == *other as u8
     }
 }
+
+/// pid位图，记录一个pid namespace中已经被分配的pid
+///
+/// 分配时从上一次分配的pid之后开始查找空闲的pid，到达上限之后从[`RESERVED_PIDS`]开始重新查找
+/// （还没有分配到[`RESERVED_PIDS`]时，从1开始），使得刚被释放的pid不会马上被复用。
+/// 位图的大小跟随pid的上限增长
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/pid.c#158
+pub struct PidMap {
+    bitmap: AllocBitmap,
+    /// 下一次分配pid时开始查找的位置
+    next: usize,
+}
+
+impl PidMap {
+    /// 创建一个空的pid位图。pid 0不会被分配出去
+    pub fn new() -> Self {
+        let mut bitmap = AllocBitmap::new(PID_MAX_DEFAULT);
+        bitmap.set(0, true);
+        Self { bitmap, next: 1 }
+    }
+
+    /// 分配一个小于`pid_max`的空闲pid
+    ///
+    /// ## 返回值
+    ///
+    /// - `EAGAIN` : 没有空闲的pid
+    pub fn alloc(&mut self, pid_max: usize) -> Result<Pid, SystemError> {
+        self.grow(pid_max);
+        let next = self.next.min(pid_max);
+        let wrap = if next > RESERVED_PIDS {
+            RESERVED_PIDS
+        } else {
+            1
+        };
+        let pid = self
+            .find_free(next, pid_max)
+            .or_else(|| self.find_free(wrap, next))
+            .ok_or(SystemError::EAGAIN_OR_EWOULDBLOCK)?;
+
+        self.bitmap.set(pid, true);
+        self.next = pid + 1;
+        return Ok(Pid::new(pid));
+    }
+
+    /// 分配指定的pid
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL` : pid不在[1, `pid_max`)范围内
+    /// - `EEXIST` : pid已经被占用
+    pub fn alloc_specific(&mut self, pid: Pid, pid_max: usize) -> Result<Pid, SystemError> {
+        if pid.data() == 0 || pid.data() >= pid_max {
+            return Err(SystemError::EINVAL);
+        }
+        self.grow(pid_max);
+        if self.bitmap.get(pid.data()) == Some(true) {
+            return Err(SystemError::EEXIST);
+        }
+        self.bitmap.set(pid.data(), true);
+        return Ok(pid);
+    }
+
+    /// 释放pid。pid的上限被调低之后，超出上限的pid仍然能够被正常释放
+    pub fn free(&mut self, pid: Pid) {
+        if pid.data() != 0 {
+            self.bitmap.set(pid.data(), false);
+        }
+    }
+
+    /// 在[start, end)中查找第一个空闲的pid
+    fn find_free(&self, start: usize, end: usize) -> Option<usize> {
+        if start >= end {
+            return None;
+        }
+        let pid = match self.bitmap.get(start) {
+            Some(false) => Some(start),
+            _ => self.bitmap.next_false_index(start),
+        };
+        return pid.filter(|pid| *pid < end);
+    }
+
+    /// 扩大位图，使得它能够容纳小于`pid_max`的所有pid
+    fn grow(&mut self, pid_max: usize) {
+        if pid_max <= self.bitmap.len() {
+            return;
+        }
+        let mut bitmap = AllocBitmap::new(pid_max.max(self.bitmap.len() * 2).min(PID_MAX_LIMIT));
+        let mut used = self.bitmap.first_index();
+        while let Some(pid) = used {
+            bitmap.set(pid, true);
+            used = self.bitmap.next_index(pid);
+        }
+        self.bitmap = bitmap;
+    }
+}
+
+impl core::fmt::Debug for PidMap {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PidMap")
+            .field("len", &self.bitmap.len())
+            .field("next", &self.next)
+            .finish()
+    }
+}
@@ -0,0 +1,174 @@
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use crate::{
+    driver::base::device::device_number::DeviceNumber,
+    filesystem::{
+        anonfs::anon_inode_fs,
+        vfs::{
+            core::generate_inode_id, file::FileMode, syscall::ModeType, FilePrivateData,
+            FileSystem, FileType, IndexNode, Metadata,
+        },
+    },
+    libs::spinlock::SpinLockGuard,
+    net::event_poll::{EPollEventType, EPollItem, EventPoll},
+    syscall::user_access::UserBufferReader,
+    time::PosixTimeSpec,
+};
+
+use super::{Pid, ProcessControlBlock, ProcessState};
+
+/// pidfd对应的inode
+///
+/// pidfd是一个指向进程的文件描述符，用于避免pid被复用导致的竞争问题。
+/// 当进程退出后，pidfd变为可读，从而可以通过epoll等待进程退出。
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/pid.c#565
+#[derive(Debug)]
+pub struct PidfdInode {
+    /// pidfd指向的进程
+    pcb: Weak<ProcessControlBlock>,
+    /// pidfd指向的进程的pid
+    pid: Pid,
+    /// INode 元数据
+    metadata: Metadata,
+}
+
+impl PidfdInode {
+    pub fn new(pcb: &Arc<ProcessControlBlock>) -> Arc<Self> {
+        return Arc::new(Self {
+            pcb: Arc::downgrade(pcb),
+            pid: pcb.pid(),
+            metadata: Metadata {
+                dev_id: 0,
+                inode_id: generate_inode_id(),
+                size: 0,
+                blk_size: 0,
+                blocks: 0,
+                atime: PosixTimeSpec::default(),
+                mtime: PosixTimeSpec::default(),
+                ctime: PosixTimeSpec::default(),
+                file_type: FileType::File,
+                mode: ModeType::from_bits_truncate(0o600),
+                nlinks: 1,
+                uid: 0,
+                gid: 0,
+                raw_dev: DeviceNumber::default(),
+            },
+        });
+    }
+
+    /// 返回pidfd指向的进程的pid
+    #[allow(dead_code)]
+    pub fn pid(&self) -> Pid {
+        return self.pid;
+    }
+
+    /// 返回pidfd指向的进程，如果进程已经被回收，则返回None
+    pub fn pcb(&self) -> Option<Arc<ProcessControlBlock>> {
+        return self.pcb.upgrade();
+    }
+
+    /// 判断pidfd指向的进程是否已经退出
+    pub fn exited(&self) -> bool {
+        match self.pcb.upgrade() {
+            Some(pcb) => matches!(
+                pcb.sched_info().inner_lock_read_irqsave().state(),
+                ProcessState::Exited(_)
+            ),
+            None => true,
+        }
+    }
+}
+
+impl IndexNode for PidfdInode {
+    fn as_any_ref(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn open(
+        &self,
+        _data: SpinLockGuard<FilePrivateData>,
+        _mode: &FileMode,
+    ) -> Result<(), SystemError> {
+        return Ok(());
+    }
+
+    fn close(&self, _data: SpinLockGuard<FilePrivateData>) -> Result<(), SystemError> {
+        return Ok(());
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        return Ok(self.metadata.clone());
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        return anon_inode_fs();
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        return Err(SystemError::ENOTDIR);
+    }
+
+    fn read_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &mut [u8],
+        _data: SpinLockGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        return Err(SystemError::EINVAL);
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &[u8],
+        _data: SpinLockGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        return Err(SystemError::EINVAL);
+    }
+
+    fn ioctl(
+        &self,
+        cmd: u32,
+        data: usize,
+        _private_data: &FilePrivateData,
+    ) -> Result<usize, SystemError> {
+        match cmd {
+            EventPoll::ADD_EPOLLITEM => {
+                let _ = UserBufferReader::new(
+                    data as *const Arc<EPollItem>,
+                    core::mem::size_of::<Arc<EPollItem>>(),
+                    false,
+                )?;
+                let epitem = unsafe { &*(data as *const Arc<EPollItem>) };
+
+                // 进程已经被回收时，不会再有状态变化，无需再登记
+                if let Some(pcb) = self.pcb.upgrade() {
+                    pcb.pidfd_epitems.lock_irqsave().push_back(epitem.clone());
+                }
+                return Ok(0);
+            }
+            _ => return Err(SystemError::ENOTTY),
+        }
+    }
+
+    fn poll(&self, _private_data: &FilePrivateData) -> Result<usize, SystemError> {
+        let mut events = EPollEventType::empty();
+        if self.exited() {
+            events.insert(EPollEventType::EPOLLIN | EPollEventType::EPOLLRDNORM);
+        }
+        return Ok(events.bits() as usize);
+    }
+}
+
+/// 检查inode是否为pidfd，如果是，则返回它
+pub fn as_pidfd(inode: &Arc<dyn IndexNode>) -> Option<&PidfdInode> {
+    return inode.as_any_ref().downcast_ref::<PidfdInode>();
+}
@@ -0,0 +1,35 @@
+use system_error::SystemError;
+
+/// 进程名的最大长度（包含结尾的'\0'）
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/linux/sched.h#296
+pub const TASK_COMM_LEN: usize = 16;
+
+/// prctl系统调用的option参数
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/prctl.h
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrctlOption {
+    /// 设置父进程退出时，当前进程收到的信号
+    SetPdeathSig = 1,
+    /// 获取父进程退出时，当前进程收到的信号
+    GetPdeathSig = 2,
+    /// 设置当前线程的名字
+    SetName = 15,
+    /// 获取当前线程的名字
+    GetName = 16,
+}
+
+impl TryFrom<usize> for PrctlOption {
+    type Error = SystemError;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(PrctlOption::SetPdeathSig),
+            2 => Ok(PrctlOption::GetPdeathSig),
+            15 => Ok(PrctlOption::SetName),
+            16 => Ok(PrctlOption::GetName),
+            _ => Err(SystemError::EINVAL),
+        }
+    }
+}
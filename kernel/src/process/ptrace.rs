@@ -0,0 +1,552 @@
+use core::mem;
+
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use crate::{
+    arch::{
+        interrupt::TrapFrame,
+        ipc::signal::{SigChildCode, SigCode, Signal, MAX_SIG_NUM},
+        process::ptrace::UserRegsStruct,
+        CurrentIrqArch,
+    },
+    exception::InterruptArch,
+    ipc::signal_types::{SigInfo, SigType},
+    libs::spinlock::SpinLockGuard,
+    mm::VirtAddr,
+    sched::{schedule, SchedMode},
+    syscall::user_access::{UserBufferReader, UserBufferWriter},
+};
+
+use super::{fork::CloneFlags, ProcessControlBlock, ProcessFlags, ProcessManager, ProcessState};
+
+/// ptrace系统调用的request参数
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/ptrace.h
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PtraceRequest {
+    /// 当前进程请求被父进程跟踪
+    TraceMe = 0,
+    /// 让被跟踪者继续运行，并且可以指定一个要递送给它的信号
+    Cont = 7,
+    /// 读取被跟踪者的通用寄存器
+    GetRegs = 12,
+    /// 修改被跟踪者的通用寄存器
+    SetRegs = 13,
+    /// 开始跟踪一个进程，并向它发送SIGSTOP
+    Attach = 16,
+    /// 停止跟踪一个进程，并让它继续运行
+    Detach = 17,
+}
+
+impl TryFrom<usize> for PtraceRequest {
+    type Error = SystemError;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(PtraceRequest::TraceMe),
+            7 => Ok(PtraceRequest::Cont),
+            12 => Ok(PtraceRequest::GetRegs),
+            13 => Ok(PtraceRequest::SetRegs),
+            16 => Ok(PtraceRequest::Attach),
+            17 => Ok(PtraceRequest::Detach),
+            _ => Err(SystemError::EIO),
+        }
+    }
+}
+
+bitflags! {
+    /// 进程被跟踪的状态，以及跟踪者通过PTRACE_SETOPTIONS设置的选项
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/linux/ptrace.h#30
+    pub struct PtraceFlags: u32 {
+        /// 进程正在被跟踪
+        const PT_PTRACED = 0x00000001;
+        /// 进程是通过PTRACE_SEIZE开始被跟踪的
+        const PT_SEIZED = 0x00010000;
+        /// 系统调用停止时，报告的信号为SIGTRAP | 0x80
+        const PT_TRACESYSGOOD = 1 << 3;
+        /// 跟踪者同时跟踪被跟踪者fork出的子进程
+        const PT_TRACE_FORK = 1 << 4;
+        /// 跟踪者同时跟踪被跟踪者vfork出的子进程
+        const PT_TRACE_VFORK = 1 << 5;
+        /// 跟踪者同时跟踪被跟踪者clone出的子进程（退出信号不是SIGCHLD）
+        const PT_TRACE_CLONE = 1 << 6;
+        /// 被跟踪者执行execve时停止
+        const PT_TRACE_EXEC = 1 << 7;
+        /// vfork的子进程执行execve或者退出，父进程被唤醒时停止
+        const PT_TRACE_VFORK_DONE = 1 << 8;
+        /// 被跟踪者退出时停止
+        const PT_TRACE_EXIT = 1 << 9;
+        /// 跟踪者退出时，向被跟踪者发送SIGKILL
+        const PT_EXITKILL = 1 << 23;
+    }
+}
+
+/// ptrace事件
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/ptrace.h#140
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PtraceEvent {
+    Fork = 1,
+    Vfork = 2,
+    Clone = 3,
+    Exec = 4,
+    VforkDone = 5,
+    Exit = 6,
+}
+
+impl PtraceEvent {
+    /// 跟踪者通过哪个选项来启用这个事件
+    pub fn flag(&self) -> PtraceFlags {
+        return PtraceFlags::from_bits_truncate(1 << (*self as u32 + 3));
+    }
+}
+
+/// 进程与跟踪它的进程之间的联系
+#[derive(Debug)]
+pub struct PtraceLink {
+    /// 跟踪者
+    tracer: Option<Weak<ProcessControlBlock>>,
+    /// 跟踪状态与选项
+    flags: PtraceFlags,
+    /// 进程正处于ptrace停止状态，等待跟踪者让它继续运行
+    stopped: bool,
+    /// 导致进程停止、并且还没有被跟踪者通过wait报告的信号
+    stop_signal: Signal,
+    /// 跟踪者让进程继续运行时，要递送给进程的信号。为`Signal::INVALID`时丢弃导致停止的信号
+    resume_signal: Signal,
+    /// 进程停止时，返回用户态时将要恢复的寄存器（位于进程的内核栈上）的地址
+    user_regs: VirtAddr,
+}
+
+impl PtraceLink {
+    /// 未被跟踪的状态
+    pub const fn new() -> Self {
+        return Self {
+            tracer: None,
+            flags: PtraceFlags::empty(),
+            stopped: false,
+            stop_signal: Signal::INVALID,
+            resume_signal: Signal::INVALID,
+            user_regs: VirtAddr::new(0),
+        };
+    }
+
+    fn is_traced_by(&self, tracer: &ProcessControlBlock) -> bool {
+        return self.flags.contains(PtraceFlags::PT_PTRACED)
+            && self
+                .tracer
+                .as_ref()
+                .is_some_and(|t| core::ptr::eq(t.as_ptr(), tracer));
+    }
+}
+
+impl ProcessControlBlock {
+    /// 获取当前进程的跟踪者
+    ///
+    /// 进程没有被跟踪，或者跟踪者已经被释放时，返回None
+    pub fn tracer(&self) -> Option<Arc<ProcessControlBlock>> {
+        let link = self.ptrace.lock_irqsave();
+        if !link.flags.contains(PtraceFlags::PT_PTRACED) {
+            return None;
+        }
+        return link.tracer.as_ref().and_then(|tracer| tracer.upgrade());
+    }
+
+    /// 进程是否正在被跟踪
+    pub fn is_traced(&self) -> bool {
+        return self.tracer().is_some();
+    }
+
+    /// 获取跟踪状态与选项
+    pub fn ptrace_flags(&self) -> PtraceFlags {
+        return self.ptrace.lock_irqsave().flags;
+    }
+
+    /// 获取当前进程正在跟踪的所有进程
+    pub fn ptraced(&self) -> Vec<Arc<ProcessControlBlock>> {
+        return self
+            .ptraced
+            .read_irqsave()
+            .iter()
+            .filter_map(|pid| ProcessManager::find(*pid))
+            .collect();
+    }
+
+    /// 使当前进程被`tracer`跟踪，并加入跟踪者的被跟踪进程列表
+    ///
+    /// ## 参数
+    ///
+    /// - `tracer`: 跟踪者
+    /// - `flags`: 跟踪状态与选项，会被自动加上`PT_PTRACED`
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/ptrace.c#67
+    pub fn ptrace_link(&self, tracer: &Arc<ProcessControlBlock>, flags: PtraceFlags) {
+        let mut link = self.ptrace.lock_irqsave();
+        link.tracer = Some(Arc::downgrade(tracer));
+        link.flags = flags | PtraceFlags::PT_PTRACED;
+        drop(link);
+        tracer.ptraced.write_irqsave().push(self.pid());
+    }
+
+    /// 解除当前进程与跟踪者之间的联系
+    ///
+    /// ## 返回值
+    ///
+    /// 进程之前处于ptrace停止状态时，返回true，调用者需要唤醒它
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/ptrace.c#117
+    pub fn ptrace_unlink(&self) -> bool {
+        let mut link = self.ptrace.lock_irqsave();
+        let tracer = link.tracer.take().and_then(|tracer| tracer.upgrade());
+        link.flags = PtraceFlags::empty();
+        link.stop_signal = Signal::INVALID;
+        let stopped = mem::replace(&mut link.stopped, false);
+        drop(link);
+        if let Some(tracer) = tracer {
+            tracer
+                .ptraced
+                .write_irqsave()
+                .retain(|pid| *pid != self.pid());
+        }
+        return stopped;
+    }
+
+    /// 获取进程因为ptrace而停止、还没有被`tracer`通过wait报告的信号
+    ///
+    /// ## 参数
+    ///
+    /// - `tracer`: 调用wait的进程，只有跟踪者才能看到ptrace停止事件
+    /// - `consume`: 是否将这个事件标记为已经报告
+    pub fn take_ptrace_stop(&self, tracer: &ProcessControlBlock, consume: bool) -> Option<Signal> {
+        let mut link = self.ptrace.lock_irqsave();
+        if !link.stopped || !link.is_traced_by(tracer) || link.stop_signal == Signal::INVALID {
+            return None;
+        }
+        let sig = link.stop_signal;
+        if consume {
+            link.stop_signal = Signal::INVALID;
+        }
+        return Some(sig);
+    }
+
+    /// 进程是否处于ptrace停止状态
+    pub fn is_ptrace_stopped(&self) -> bool {
+        return self.ptrace.lock_irqsave().stopped;
+    }
+}
+
+impl ProcessManager {
+    /// fork时，决定子进程是否与父进程被同一个跟踪者跟踪
+    ///
+    /// 只有父进程正在被跟踪时，子进程才可能被跟踪：
+    /// - `CLONE_UNTRACED`：子进程一定不会被跟踪，即使父进程正在被跟踪（例如内核线程）
+    /// - `CLONE_PTRACE`：父进程的跟踪者同时跟踪子进程
+    /// - 否则，跟踪者通过PTRACE_O_TRACEFORK等选项启用了对应的事件时，子进程被跟踪
+    ///
+    /// 子进程继承父进程的跟踪选项
+    ///
+    /// ## 参数
+    ///
+    /// - `clone_flags`: clone标志位
+    /// - `exit_signal`: 子进程退出时发送给父进程的信号，用于区分clone事件与fork事件
+    /// - `current_pcb`: 父进程
+    /// - `new_pcb`: 子进程
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/fork.c#2660
+    pub(super) fn copy_ptrace(
+        clone_flags: &CloneFlags,
+        exit_signal: Signal,
+        current_pcb: &Arc<ProcessControlBlock>,
+        new_pcb: &Arc<ProcessControlBlock>,
+    ) {
+        if clone_flags.contains(CloneFlags::CLONE_UNTRACED) {
+            return;
+        }
+
+        let tracer = match current_pcb.tracer() {
+            Some(tracer) => tracer,
+            None => return,
+        };
+        let flags = current_pcb.ptrace_flags();
+
+        let event = if clone_flags.contains(CloneFlags::CLONE_VFORK) {
+            PtraceEvent::Vfork
+        } else if exit_signal != Signal::SIGCHLD {
+            PtraceEvent::Clone
+        } else {
+            PtraceEvent::Fork
+        };
+
+        if clone_flags.contains(CloneFlags::CLONE_PTRACE) || flags.contains(event.flag()) {
+            new_pcb.ptrace_link(&tracer, flags);
+        }
+    }
+
+    /// 被跟踪的进程在处理信号之前停止，由跟踪者决定如何处理这个信号
+    ///
+    /// 进程进入ptrace停止状态，并通知跟踪者，直到跟踪者通过PTRACE_CONT、PTRACE_DETACH让它继续运行，
+    /// 或者进程收到SIGKILL
+    ///
+    /// 进入当前函数之前，不能持有当前进程的信号相关的锁
+    ///
+    /// ## 参数
+    ///
+    /// - `sig`: 即将被处理的信号
+    /// - `frame`: 进程返回用户态时将要恢复的寄存器，跟踪者可以通过PTRACE_GETREGS/PTRACE_SETREGS访问它
+    ///
+    /// ## 返回值
+    ///
+    /// 进程接下来要处理的信号：跟踪者指定的信号，或者为`Signal::INVALID`，表示丢弃这个信号
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/signal.c#2610
+    pub fn ptrace_signal(sig: Signal, frame: &mut TrapFrame) -> Signal {
+        let pcb = ProcessManager::current_pcb();
+        let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+
+        let mut link = pcb.ptrace.lock_irqsave();
+        let tracer = match link.tracer.as_ref().and_then(|tracer| tracer.upgrade()) {
+            Some(tracer) if link.flags.contains(PtraceFlags::PT_PTRACED) => tracer,
+            _ => return sig,
+        };
+        link.stopped = true;
+        link.stop_signal = sig;
+        link.resume_signal = Signal::INVALID;
+        link.user_regs = VirtAddr::new(frame as *mut TrapFrame as usize);
+
+        // 在持有link锁的情况下进入停止状态，这样跟踪者看到stopped之后，一定能把进程唤醒
+        let mut writer = pcb.sched_info().inner_lock_write_irqsave();
+        writer.set_state(ProcessState::Stopped);
+        pcb.flags().insert(ProcessFlags::NEED_SCHEDULE);
+        drop(writer);
+        drop(link);
+
+        ProcessManager::do_notify_cldstop(&tracer, &pcb, SigChildCode::Trapped, sig);
+        drop(tracer);
+        drop(irq_guard);
+        schedule(SchedMode::SM_NONE);
+
+        let mut link = pcb.ptrace.lock_irqsave();
+        link.stopped = false;
+        link.stop_signal = Signal::INVALID;
+        link.user_regs = VirtAddr::new(0);
+        return mem::replace(&mut link.resume_signal, Signal::INVALID);
+    }
+
+    /// PTRACE_TRACEME：当前进程请求被父进程跟踪
+    ///
+    /// ## 返回值
+    ///
+    /// - `EPERM`: 当前进程已经被跟踪，或者没有父进程
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/ptrace.c#481
+    pub fn ptrace_traceme() -> Result<(), SystemError> {
+        let current = ProcessManager::current_pcb();
+        if current.ptrace_flags().contains(PtraceFlags::PT_PTRACED) {
+            return Err(SystemError::EPERM);
+        }
+        let parent = current.real_parent().ok_or(SystemError::EPERM)?;
+        current.ptrace_link(&parent, PtraceFlags::empty());
+        return Ok(());
+    }
+
+    /// PTRACE_ATTACH：开始跟踪`child`，并向它发送SIGSTOP，使它进入ptrace停止状态
+    ///
+    /// ## 返回值
+    ///
+    /// - `EPERM`: `child`是内核线程、与当前进程位于同一个线程组，或者已经被跟踪
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/ptrace.c#389
+    pub fn ptrace_attach(child: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+        let current = ProcessManager::current_pcb();
+        // TODO: 引入权限检查之后，需要检查当前进程是否有权限跟踪child
+        if child.flags().contains(ProcessFlags::KTHREAD)
+            || child.flags().contains(ProcessFlags::EXITING)
+            || child.tgid() == current.tgid()
+        {
+            return Err(SystemError::EPERM);
+        }
+
+        let mut link = child.ptrace.lock_irqsave();
+        if link.flags.contains(PtraceFlags::PT_PTRACED) {
+            return Err(SystemError::EPERM);
+        }
+        link.tracer = Some(Arc::downgrade(&current));
+        link.flags = PtraceFlags::PT_PTRACED;
+        drop(link);
+        current.ptraced.write_irqsave().push(child.pid());
+
+        let mut info = SigInfo::new(
+            Signal::SIGSTOP,
+            0,
+            SigCode::Kernel,
+            SigType::Kill(current.pid()),
+        );
+        Signal::SIGSTOP
+            .send_signal_info_to_thread(Some(&mut info), child.clone())
+            .map(|_| ())
+    }
+
+    /// 检查`child`是否正在被当前进程跟踪，并且处于ptrace停止状态
+    ///
+    /// ## 返回值
+    ///
+    /// - `ESRCH`: `child`没有被当前进程跟踪，或者没有停止
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/ptrace.c#242
+    pub fn ptrace_check_attach(child: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+        let current = ProcessManager::current_pcb();
+        let link = child.ptrace.lock_irqsave();
+        if !link.is_traced_by(&current) || !link.stopped {
+            return Err(SystemError::ESRCH);
+        }
+        return Ok(());
+    }
+
+    /// PTRACE_CONT：让处于ptrace停止状态的`child`继续运行
+    ///
+    /// ## 参数
+    ///
+    /// - `child`: 被跟踪者，调用者需要先通过[`ProcessManager::ptrace_check_attach`]进行检查
+    /// - `data`: 要递送给被跟踪者的信号，为0时丢弃导致它停止的信号
+    ///
+    /// ## 返回值
+    ///
+    /// - `EIO`: 信号不合法
+    /// - `ESRCH`: `child`没有被当前进程跟踪，或者没有停止
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/ptrace.c#832
+    pub fn ptrace_resume(child: &Arc<ProcessControlBlock>, data: usize) -> Result<(), SystemError> {
+        let sig = Self::ptrace_data_signal(data)?;
+        let current = ProcessManager::current_pcb();
+        let mut link = child.ptrace.lock_irqsave();
+        if !link.is_traced_by(&current) || !link.stopped {
+            return Err(SystemError::ESRCH);
+        }
+        link.resume_signal = sig;
+        link.stopped = false;
+        drop(link);
+        // 被跟踪者可能同时被SIGKILL唤醒，此时不需要再唤醒它
+        let _ = ProcessManager::wakeup_stop(child);
+        return Ok(());
+    }
+
+    /// PTRACE_DETACH：停止跟踪`child`，并让它继续运行
+    ///
+    /// ## 参数
+    ///
+    /// - `child`: 被跟踪者，调用者需要先通过[`ProcessManager::ptrace_check_attach`]进行检查
+    /// - `data`: 要递送给被跟踪者的信号，为0时丢弃导致它停止的信号
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/ptrace.c#513
+    pub fn ptrace_detach(child: &Arc<ProcessControlBlock>, data: usize) -> Result<(), SystemError> {
+        let sig = Self::ptrace_data_signal(data)?;
+        child.ptrace.lock_irqsave().resume_signal = sig;
+        if child.ptrace_unlink() {
+            let _ = ProcessManager::wakeup_stop(child);
+        }
+        return Ok(());
+    }
+
+    /// 进程退出时，解除它与跟踪者、被跟踪者之间的联系
+    ///
+    /// 被跟踪者继续运行；跟踪者设置了PTRACE_O_EXITKILL时，被跟踪者会被杀死
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/ptrace.c#573
+    pub(super) fn exit_ptrace(pcb: &Arc<ProcessControlBlock>) {
+        pcb.ptrace_unlink();
+
+        for tracee in pcb.ptraced() {
+            let exitkill = tracee.ptrace_flags().contains(PtraceFlags::PT_EXITKILL);
+            if tracee.ptrace_unlink() {
+                let _ = ProcessManager::wakeup_stop(&tracee);
+            }
+            if exitkill {
+                let mut info = SigInfo::new(
+                    Signal::SIGKILL,
+                    0,
+                    SigCode::Kernel,
+                    SigType::Kill(pcb.pid()),
+                );
+                let _ = Signal::SIGKILL.send_signal_info_to_thread(Some(&mut info), tracee);
+            }
+        }
+        pcb.ptraced.write_irqsave().clear();
+    }
+
+    /// PTRACE_GETREGS：将被跟踪者的通用寄存器拷贝到用户空间
+    ///
+    /// ## 返回值
+    ///
+    /// - `ESRCH`: `child`没有被当前进程跟踪，或者没有停止
+    /// - `EFAULT`: `data`不是合法的用户空间地址
+    pub fn ptrace_getregs(
+        child: &Arc<ProcessControlBlock>,
+        data: VirtAddr,
+    ) -> Result<(), SystemError> {
+        let mut writer = UserBufferWriter::new(
+            data.as_ptr::<UserRegsStruct>(),
+            mem::size_of::<UserRegsStruct>(),
+            true,
+        )?;
+
+        let regs = {
+            let link = Self::ptrace_stopped_link(child)?;
+            // 被跟踪者处于停止状态，不会修改自己的寄存器
+            let frame = unsafe { &*(link.user_regs.data() as *const TrapFrame) };
+            UserRegsStruct::from_trapframe(child, frame)
+        };
+        writer.copy_one_to_user(&regs, 0)?;
+        return Ok(());
+    }
+
+    /// PTRACE_SETREGS：使用用户空间提供的值修改被跟踪者的通用寄存器
+    ///
+    /// ## 返回值
+    ///
+    /// - `ESRCH`: `child`没有被当前进程跟踪，或者没有停止
+    /// - `EFAULT`: `data`不是合法的用户空间地址
+    /// - `EIO`: 寄存器的值不合法
+    pub fn ptrace_setregs(
+        child: &Arc<ProcessControlBlock>,
+        data: VirtAddr,
+    ) -> Result<(), SystemError> {
+        let reader = UserBufferReader::new(
+            data.as_ptr::<UserRegsStruct>(),
+            mem::size_of::<UserRegsStruct>(),
+            true,
+        )?;
+        let regs = *reader.read_one_from_user::<UserRegsStruct>(0)?;
+
+        let link = Self::ptrace_stopped_link(child)?;
+        // 被跟踪者处于停止状态，不会修改自己的寄存器
+        let frame = unsafe { &mut *(link.user_regs.data() as *mut TrapFrame) };
+        return regs.apply_to_trapframe(frame);
+    }
+
+    /// 获取被当前进程跟踪、并且处于ptrace停止状态的进程的跟踪状态
+    ///
+    /// 返回的锁守卫保证被跟踪者在此期间不会被跟踪者唤醒
+    fn ptrace_stopped_link(
+        child: &Arc<ProcessControlBlock>,
+    ) -> Result<SpinLockGuard<PtraceLink>, SystemError> {
+        let current = ProcessManager::current_pcb();
+        let link = child.ptrace.lock_irqsave();
+        if !link.is_traced_by(&current) || !link.stopped || link.user_regs.is_null() {
+            return Err(SystemError::ESRCH);
+        }
+        return Ok(link);
+    }
+
+    /// 将ptrace的data参数转换为要递送的信号
+    fn ptrace_data_signal(data: usize) -> Result<Signal, SystemError> {
+        if data > MAX_SIG_NUM {
+            return Err(SystemError::EIO);
+        }
+        return Ok(Signal::from(data));
+    }
+}
@@ -1,9 +1,14 @@
 use num_traits::FromPrimitive;
 use system_error::SystemError;
 
-use crate::time::PosixTimeSpec;
+use crate::{
+    filesystem::vfs::file::FileDescriptorVec,
+    mm::ucontext::UserStack,
+    syscall::Syscall,
+    time::{PosixTimeSpec, NSEC_PER_SEC},
+};
 
-use super::ProcessControlBlock;
+use super::{ProcessControlBlock, ProcessManager};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[repr(C)]
@@ -84,6 +89,78 @@ pub struct RLimit64 {
     pub rlim_max: u64,
 }
 
+impl RLimit64 {
+    /// 表示不限制资源的使用
+    pub const RLIM_INFINITY: u64 = u64::MAX;
+
+    pub const INFINITY: Self = Self {
+        rlim_cur: Self::RLIM_INFINITY,
+        rlim_max: Self::RLIM_INFINITY,
+    };
+
+    /// init进程的资源限制，其他进程通过fork继承
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/asm-generic/resource.h#17
+    pub const INIT_RLIMITS: [Self; RLimitID::Nlimits as usize] = [
+        // RLIMIT_CPU
+        Self::INFINITY,
+        // RLIMIT_FSIZE
+        Self::INFINITY,
+        // RLIMIT_DATA
+        Self::INFINITY,
+        // RLIMIT_STACK
+        Self {
+            rlim_cur: UserStack::DEFAULT_USER_STACK_SIZE as u64,
+            rlim_max: Self::RLIM_INFINITY,
+        },
+        // RLIMIT_CORE
+        Self {
+            rlim_cur: 0,
+            rlim_max: Self::RLIM_INFINITY,
+        },
+        // RLIMIT_RSS
+        Self::INFINITY,
+        // RLIMIT_NPROC
+        Self::INFINITY,
+        // RLIMIT_NOFILE
+        Self {
+            rlim_cur: FileDescriptorVec::PROCESS_MAX_FD as u64,
+            rlim_max: FileDescriptorVec::PROCESS_MAX_FD as u64,
+        },
+        // RLIMIT_MEMLOCK
+        Self {
+            rlim_cur: 8 * 1024 * 1024,
+            rlim_max: 8 * 1024 * 1024,
+        },
+        // RLIMIT_AS
+        Self::INFINITY,
+        // RLIMIT_LOCKS
+        Self::INFINITY,
+        // RLIMIT_SIGPENDING
+        Self {
+            rlim_cur: 0,
+            rlim_max: 0,
+        },
+        // RLIMIT_MSGQUEUE
+        Self {
+            rlim_cur: 819200,
+            rlim_max: 819200,
+        },
+        // RLIMIT_NICE
+        Self {
+            rlim_cur: 0,
+            rlim_max: 0,
+        },
+        // RLIMIT_RTPRIO
+        Self {
+            rlim_cur: 0,
+            rlim_max: 0,
+        },
+        // RLIMIT_RTTIME
+        Self::INFINITY,
+    ];
+}
+
 /// Resource limit IDs
 ///
 /// ## Note
@@ -133,19 +210,182 @@ impl TryFrom<usize> for RLimitID {
     type Error = SystemError;
 
     fn try_from(value: usize) -> Result<Self, Self::Error> {
-        <Self as FromPrimitive>::from_usize(value).ok_or(SystemError::EINVAL)
+        match <Self as FromPrimitive>::from_usize(value) {
+            Some(RLimitID::Nlimits) | None => Err(SystemError::EINVAL),
+            Some(id) => Ok(id),
+        }
+    }
+}
+
+impl RUsage {
+    /// 把纳秒转换为rusage中的时间
+    ///
+    /// 用户态的struct rusage中的时间是struct timeval，它与PosixTimeSpec的布局相同，
+    /// 但是第二个字段的单位是微秒
+    fn time_from_ns(ns: u64) -> PosixTimeSpec {
+        PosixTimeSpec {
+            tv_sec: (ns / NSEC_PER_SEC as u64) as i64,
+            tv_nsec: (ns % NSEC_PER_SEC as u64 / 1000) as i64,
+        }
     }
 }
 
 impl ProcessControlBlock {
     /// 获取进程资源使用情况
     ///
-    /// ## TODO
+    /// 目前统计了cpu时间（ru_utime、ru_stime）、最大常驻集（ru_maxrss）、
+    /// 缺页次数（ru_minflt、ru_majflt）以及上下文切换次数（ru_nvcsw、ru_nivcsw，只统计当前线程）
+    ///
+    /// ## 参数
+    ///
+    /// - `who` : 统计的对象。RUSAGE_CHILDREN统计的是已经被回收的子进程，RUSAGE_BOTH是进程自身与它们的总和
     ///
-    /// 当前函数尚未实现，只是返回了一个默认的RUsage结构体
-    pub fn get_rusage(&self, _who: RUsageWho) -> Option<RUsage> {
-        let rusage = RUsage::default();
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/sys.c
+    pub fn get_rusage(&self, who: RUsageWho) -> Option<RUsage> {
+        let leader = ProcessManager::find(self.tgid());
+        let children_cputime = || {
+            leader
+                .as_ref()
+                .map(|leader| leader.stats().children_cputime())
+                .unwrap_or_default()
+        };
+        let children = || {
+            let (cutime, cstime) = children_cputime();
+            let (cminflt, cmajflt) = leader
+                .as_ref()
+                .map(|leader| leader.stats().children_faults())
+                .unwrap_or_default();
+            RUsage {
+                ru_utime: RUsage::time_from_ns(cutime),
+                ru_stime: RUsage::time_from_ns(cstime),
+                ru_maxrss: leader
+                    .as_ref()
+                    .map(|leader| leader.stats().children_maxrss())
+                    .unwrap_or_default(),
+                ru_minflt: cminflt,
+                ru_majflt: cmajflt,
+                ..Default::default()
+            }
+        };
+        let own = |(utime, stime): (u64, u64), (minflt, majflt): (usize, usize)| RUsage {
+            ru_utime: RUsage::time_from_ns(utime),
+            ru_stime: RUsage::time_from_ns(stime),
+            ru_maxrss: self.maxrss(),
+            ru_minflt: minflt,
+            ru_majflt: majflt,
+            ru_nvcsw: self.stats().nvcsw(),
+            ru_nivcsw: self.stats().nivcsw(),
+            ..Default::default()
+        };
 
+        let rusage = match who {
+            RUsageWho::RUsageSelf => own(self.cputime(), self.faults()),
+            RUsageWho::RusageThread => own(self.thread_cputime(), self.stats().faults()),
+            RUsageWho::RUsageChildren => children(),
+            RUsageWho::RUsageBoth => {
+                // 时间需要先以纳秒相加，再转换为timeval，避免微秒部分溢出
+                let (utime, stime) = self.cputime();
+                let (cutime, cstime) = children_cputime();
+                let children = children();
+                let mut rusage = own((utime + cutime, stime + cstime), self.faults());
+                rusage.ru_maxrss = core::cmp::max(rusage.ru_maxrss, children.ru_maxrss);
+                rusage.ru_minflt += children.ru_minflt;
+                rusage.ru_majflt += children.ru_majflt;
+                rusage
+            }
+        };
         Some(rusage)
     }
+
+    /// 进程的最大常驻集（KB），包括当前的地址空间以及之前已经释放的地址空间
+    fn maxrss(&self) -> usize {
+        let released = ProcessManager::find(self.tgid())
+            .map(|leader| leader.stats().maxrss())
+            .unwrap_or_default();
+        let current = self
+            .basic()
+            .user_vm()
+            .map(|vm| vm.read_irqsave().hiwater_rss().bytes() / 1024)
+            .unwrap_or_default();
+        return core::cmp::max(released, current);
+    }
+
+    /// 在释放（退出）或者替换（execve）进程的地址空间之前，把它的最大常驻集记录到组长线程中，
+    /// 使得之后的getrusage以及父进程的wait4仍然能够得到这个值
+    pub fn record_maxrss(&self) {
+        let vm = match self.basic().user_vm() {
+            Some(vm) => vm,
+            None => return,
+        };
+        let kb = vm.read_irqsave().hiwater_rss().bytes() / 1024;
+        if let Some(leader) = ProcessManager::find(self.tgid()) {
+            leader.stats().update_maxrss(kb);
+        }
+    }
+
+    /// 获取进程的某项资源限制
+    pub fn rlimit(&self, resource: RLimitID) -> RLimit64 {
+        return self.rlimits.read_irqsave()[resource as usize];
+    }
+
+    /// 获取进程所有的资源限制
+    pub fn rlimits(&self) -> [RLimit64; RLimitID::Nlimits as usize] {
+        return *self.rlimits.read_irqsave();
+    }
+
+    /// 设置进程的某项资源限制
+    ///
+    /// 降低限制总是被允许的，而提高硬限制需要特权。
+    /// 降低限制并不会回收已经超出限制的资源（例如已经打开的文件描述符），只会影响之后的分配
+    ///
+    /// ## 参数
+    ///
+    /// - `resource` : 资源的类型
+    /// - `rlimit` : 新的资源限制
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(RLimit64)`：设置成功，返回旧的资源限制
+    /// - `EINVAL`：软限制大于硬限制
+    /// - `EPERM`：没有特权却试图提高硬限制，或者RLIMIT_NOFILE的硬限制超过了文件描述符表的容量
+    pub fn set_rlimit(
+        &self,
+        resource: RLimitID,
+        rlimit: RLimit64,
+    ) -> Result<RLimit64, SystemError> {
+        if rlimit.rlim_cur > rlimit.rlim_max {
+            return Err(SystemError::EINVAL);
+        }
+        if resource == RLimitID::Nofile
+            && rlimit.rlim_max > FileDescriptorVec::PROCESS_MAX_FD as u64
+        {
+            return Err(SystemError::EPERM);
+        }
+
+        let mut rlimits = self.rlimits.write_irqsave();
+        let old = rlimits[resource as usize];
+        if rlimit.rlim_max > old.rlim_max && !Self::capable_sys_resource() {
+            return Err(SystemError::EPERM);
+        }
+        rlimits[resource as usize] = rlimit;
+        return Ok(old);
+    }
+
+    /// 判断当前进程是否有权限提高资源的硬限制（CAP_SYS_RESOURCE）
+    ///
+    /// ## TODO
+    ///
+    /// 目前还没有实现credential，所有进程都以root身份运行，因此总是有权限。
+    /// 增加credential功能之后，需要改为检查CAP_SYS_RESOURCE
+    fn capable_sys_resource() -> bool {
+        return Syscall::geteuid() == Ok(0);
+    }
+
+    /// 获取进程能够使用的文件描述符的上限（不包含）
+    pub fn nofile_limit(&self) -> usize {
+        return core::cmp::min(
+            self.rlimit(RLimitID::Nofile).rlim_cur,
+            FileDescriptorVec::PROCESS_MAX_FD as u64,
+        ) as usize;
+    }
 }
@@ -0,0 +1,160 @@
+//! 进程管理相关的自检
+//!
+//! 这些测试不会被自动运行，需要时向`/sys/kernel/selftest`写入测试的名字来运行，
+//! 例如`echo kthread_entry > /sys/kernel/selftest`。测试通过时写入成功，否则写入返回错误
+
+use alloc::{string::ToString, sync::Arc};
+use system_error::SystemError;
+
+use crate::{
+    arch::CurrentIrqArch,
+    exception::InterruptArch,
+    kerror, kinfo,
+    libs::wait_queue_test::test_wait_queue,
+    mm::{
+        bench::{bench_copy_mm, bench_spawn_mm},
+        VirtAddr,
+    },
+    process::{
+        fork::CloneFlags,
+        kthread::{KernelThreadClosure, KernelThreadCreateInfo, KernelThreadMechanism},
+        ProcessControlBlock, ProcessFlags, ProcessManager, ProcessState,
+    },
+    time::{sleep::nanosleep, PosixTimeSpec},
+};
+
+/// 等待测试用的内核线程退出的最长时间（毫秒）
+const KTHREAD_EXIT_TIMEOUT_MS: usize = 5000;
+
+/// 运行名字为`name`的自检（或者性能测试，它的结果输出到内核日志中）
+///
+/// ## 返回值
+///
+/// - `Ok(())`：测试通过
+/// - `Err(SystemError::EINVAL)`：没有这个测试
+/// - `Err(SystemError::EIO)`：测试失败
+/// - `Err(SystemError::ETIMEDOUT)`：测试用的内核线程没有在规定的时间内退出
+pub fn run_selftest(name: &str) -> Result<(), SystemError> {
+    match name {
+        "kthread_entry" => test_kthread_entry(),
+        "wait_queue" => test_wait_queue(),
+        "bench_copy_mm" => bench_copy_mm(),
+        "bench_spawn_mm" => bench_spawn_mm(),
+        #[cfg(target_arch = "x86_64")]
+        "bench_clone_arch_info" => crate::arch::process::bench::bench_clone_arch_info(),
+        #[cfg(target_arch = "x86_64")]
+        "bench_current_pcb" => crate::arch::process::bench::bench_current_pcb(),
+        _ => Err(SystemError::EINVAL),
+    }
+}
+
+/// 等待测试用的内核线程退出，并返回它的退出码
+///
+/// 每次检查之间睡眠一小段时间，超过[`KTHREAD_EXIT_TIMEOUT_MS`]仍未退出则返回`ETIMEDOUT`
+pub fn wait_kthread_exit(pcb: &Arc<ProcessControlBlock>) -> Result<usize, SystemError> {
+    for _ in 0..KTHREAD_EXIT_TIMEOUT_MS / 10 {
+        if let ProcessState::Exited(code) = pcb.sched_info().inner_lock_read_irqsave().state() {
+            return Ok(code);
+        }
+        // 被信号打断时，继续等待即可
+        nanosleep(PosixTimeSpec {
+            tv_sec: 0,
+            tv_nsec: 10_000_000,
+        })
+        .ok();
+    }
+    kerror!("selftest: kthread {:?} did not exit in time", pcb.pid());
+    return Err(SystemError::ETIMEDOUT);
+}
+
+/// 检查内核线程是否从干净的上下文开始执行
+///
+/// 在新的内核线程可以被调度之前检查它的入口栈帧：只包含内核段选择子、开启了中断、
+/// 从`kernel_thread_bootstrap_stage1`开始执行，并且除了入口参数之外的寄存器都被清零。
+/// 然后检查它开始执行时带有KTHREAD标志、已经开启了中断，并且栈指针位于它自己的内核栈中
+///
+/// 只有内核线程才能创建内核线程，因此测试在一个单独的内核线程中进行
+fn test_kthread_entry() -> Result<(), SystemError> {
+    let closure =
+        KernelThreadClosure::StaticEmptyClosure((&(test_kthread_entry_creator as fn() -> i32), ()));
+    let pcb = KernelThreadMechanism::create_and_run(closure, "test_kthread_creator".to_string())
+        .ok_or(SystemError::ENOMEM)?;
+
+    if wait_kthread_exit(&pcb)? != 0 {
+        kerror!("test_kthread_entry: failed");
+        return Err(SystemError::EIO);
+    }
+    kinfo!("test_kthread_entry: ok");
+    return Ok(());
+}
+
+/// 创建被测试的内核线程，在它被调度之前检查它的入口栈帧，然后等待它退出
+fn test_kthread_entry_creator() -> i32 {
+    let closure =
+        KernelThreadClosure::StaticEmptyClosure((&(test_kthread_entry_thread as fn() -> i32), ()));
+    let info = KernelThreadCreateInfo::new(closure, "test_kthread_entry".to_string());
+    // 创建之后直接运行入口函数，不需要再被唤醒
+    info.set_to_mark_sleep(false).ok();
+    let create_info = KernelThreadCreateInfo::generate_unsafe_arc_ptr(info.clone());
+
+    let mut frame_check = Ok(());
+    let pcb = match ProcessManager::fork_kernel_thread(
+        CloneFlags::CLONE_VM
+            | CloneFlags::CLONE_FS
+            | CloneFlags::CLONE_SIGNAL
+            | CloneFlags::CLONE_UNTRACED,
+        VirtAddr::new(create_info as usize),
+        |pcb| {
+            pcb.set_name(info.name().clone());
+            // 此时新的内核线程还不会被调度，入口栈帧还没有被使用
+            frame_check = ProcessManager::check_kernel_thread_frame(pcb);
+        },
+    ) {
+        Ok(pcb) => pcb,
+        Err(e) => {
+            unsafe { KernelThreadCreateInfo::parse_unsafe_arc_ptr(create_info) };
+            kerror!("test_kthread_entry: create kthread failed: {:?}", e);
+            return 1;
+        }
+    };
+
+    let mut failed = 0;
+    if let Err(what) = frame_check {
+        kerror!("test_kthread_entry: entry frame is not clean: {}", what);
+        failed = 1;
+    }
+    if !matches!(wait_kthread_exit(&pcb), Ok(0)) {
+        failed = 1;
+    }
+    return failed;
+}
+
+fn test_kthread_entry_thread() -> i32 {
+    let pcb = ProcessManager::current_pcb();
+    let mut failed = 0;
+
+    if !pcb.flags().contains(ProcessFlags::KTHREAD) {
+        kerror!("test_kthread_entry: KTHREAD is not set");
+        failed = 1;
+    }
+
+    if !CurrentIrqArch::is_irq_enabled() {
+        kerror!("test_kthread_entry: interrupts are disabled");
+        failed = 1;
+    }
+
+    // 局部变量位于当前的栈上
+    let sp = VirtAddr::new(&failed as *const i32 as usize);
+    let kstack = pcb.kernel_stack();
+    if sp < kstack.start_address() || sp >= kstack.stack_max_address() {
+        kerror!(
+            "test_kthread_entry: sp {:?} is out of the kernel stack [{:?}, {:?})",
+            sp,
+            kstack.start_address(),
+            kstack.stack_max_address()
+        );
+        failed = 1;
+    }
+
+    return failed;
+}
@@ -0,0 +1,134 @@
+//! 创建一个马上执行新程序的子进程（posix_spawn）
+//!
+//! 对于fork之后立即execve的场景，即使是写时复制的fork，也需要拷贝父进程的每一个VMA以及页表，
+//! 而这些拷贝在execve时又会被全部丢弃。spawn创建的子进程从一个空的地址空间开始，
+//! 在第一次返回用户态之前就在内核中执行execve，因此完全不需要拷贝父进程的地址空间。
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use system_error::SystemError;
+
+use crate::{
+    arch::{interrupt::TrapFrame, ipc::signal::Signal, CurrentIrqArch},
+    exception::InterruptArch,
+    libs::spinlock::SpinLock,
+    syscall::Syscall,
+};
+
+use super::{
+    abi::WaitOption,
+    exit::kernel_wait4,
+    fork::{CloneFlags, KernelCloneArgs, VforkDone},
+    Pid, ProcessFlags, ProcessManager,
+};
+
+/// spawn创建的子进程在返回用户态之前需要执行的程序
+#[derive(Debug)]
+pub struct SpawnRequest {
+    path: String,
+    argv: Vec<String>,
+    envp: Vec<String>,
+    /// 子进程执行execve失败时的错误码
+    error: SpinLock<Option<SystemError>>,
+}
+
+impl SpawnRequest {
+    pub fn new(path: String, argv: Vec<String>, envp: Vec<String>) -> Self {
+        Self {
+            path,
+            argv,
+            envp,
+            error: SpinLock::new(None),
+        }
+    }
+}
+
+impl ProcessManager {
+    /// 创建一个执行`path`的子进程
+    ///
+    /// 子进程不会拷贝父进程的地址空间，而是从一个空的地址空间开始，并在第一次返回用户态之前执行execve。
+    /// 除了地址空间之外，子进程与fork创建的子进程相同（继承文件描述符、信号屏蔽字等）。
+    /// 父进程会等待子进程执行execve的结果
+    ///
+    /// ## 参数
+    ///
+    /// - `current_trapframe` : 当前进程的栈帧
+    /// - `path` : 子进程要执行的程序
+    /// - `argv` : 命令行参数
+    /// - `envp` : 环境变量
+    ///
+    /// ## 返回值
+    ///
+    /// 成功时返回子进程在当前pid namespace中的pid。子进程执行execve失败时，子进程会被回收，并返回execve的错误码
+    pub fn spawn(
+        current_trapframe: &TrapFrame,
+        path: String,
+        argv: Vec<String>,
+        envp: Vec<String>,
+    ) -> Result<Pid, SystemError> {
+        let mut args = KernelCloneArgs::new();
+        args.flags = CloneFlags::empty();
+        args.exit_signal = Signal::SIGCHLD;
+        args.spawn = true;
+
+        let request = Arc::new(SpawnRequest::new(path, argv, envp));
+        let done = Arc::new(VforkDone::new());
+        let pcb = Self::create_process(args, current_trapframe, |pcb| {
+            let mut thread = pcb.thread.write_irqsave();
+            thread.spawn = Some(request.clone());
+            thread.vfork_done = Some(done.clone());
+            drop(thread);
+            pcb.flags().insert(ProcessFlags::NEED_SPAWN_EXEC);
+            Ok(())
+        })?;
+
+        // 等待子进程execve或者退出
+        if done.wait().is_err() {
+            // 父进程被致命信号打断，子进程仍然会继续执行，只是不再报告execve的结果
+            pcb.thread.write_irqsave().vfork_done = None;
+            return Ok(pcb.pid_vnr());
+        }
+
+        let error = request.error.lock_irqsave().take();
+        if let Some(e) = error {
+            // 子进程已经退出，回收它，避免调用者看到一个从未执行过的子进程
+            kernel_wait4(pcb.pid().data() as i64, None, WaitOption::WEXITED, None).ok();
+            return Err(e);
+        }
+
+        return Ok(pcb.pid_vnr());
+    }
+
+    /// 返回用户态之前调用，若当前进程是由spawn创建的，则执行spawn指定的程序
+    ///
+    /// execve失败时，记录错误码并唤醒父进程，然后以127退出
+    pub fn spawn_exec(frame: &mut TrapFrame) {
+        let pcb = ProcessManager::current_pcb();
+        if !pcb.flags().contains(ProcessFlags::NEED_SPAWN_EXEC) {
+            return;
+        }
+        pcb.flags().remove(ProcessFlags::NEED_SPAWN_EXEC);
+
+        let request = match pcb.thread.write_irqsave().spawn.take() {
+            Some(request) => request,
+            None => return,
+        };
+
+        // 加载程序需要读取文件，因此需要开中断
+        unsafe { CurrentIrqArch::interrupt_enable() };
+        let r = Syscall::kernel_execve(
+            request.path.clone(),
+            request.argv.clone(),
+            request.envp.clone(),
+            frame,
+        );
+        unsafe { CurrentIrqArch::interrupt_disable() };
+
+        if let Err(e) = r {
+            *request.error.lock_irqsave() = Some(e);
+            drop(request);
+            ProcessManager::complete_vfork_done(&pcb);
+            drop(pcb);
+            ProcessManager::exit(127 << 8);
+        }
+    }
+}
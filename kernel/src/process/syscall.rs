@@ -8,28 +8,38 @@ use alloc::{
 use system_error::SystemError;
 
 use super::{
-    abi::WaitOption,
-    exit::kernel_wait4,
-    fork::{CloneFlags, KernelCloneArgs},
+    abi::{WaitIdType, WaitOption},
+    exit::{kernel_wait4, kernel_waitid},
+    fork::{CloneFlags, KernelCloneArgs, PosixCloneArgs, VforkDone},
+    pidfd::PidfdInode,
+    prctl::{PrctlOption, TASK_COMM_LEN},
+    ptrace::PtraceRequest,
     resource::{RLimit64, RLimitID, RUsage, RUsageWho},
-    KernelStack, Pid, ProcessManager,
+    Pid, ProcessFlags, ProcessManager,
 };
 use crate::{
-    arch::{interrupt::TrapFrame, MMArch},
-    filesystem::{
-        procfs::procfs_register_pid,
-        vfs::{file::FileDescriptorVec, MAX_PATHLEN},
+    arch::{interrupt::TrapFrame, ipc::signal::Signal, MMArch},
+    driver::base::block::io_context::{ioprio_check, IoPrioWho},
+    filesystem::vfs::{
+        file::{File, FileMode},
+        MAX_PATHLEN,
     },
-    mm::{ucontext::UserStack, verify_area, MemoryManagementArch, VirtAddr},
+    ipc::{
+        signal::flush_signal_handlers,
+        signal_types::{PosixSigInfo, SigAltStack},
+    },
+    mm::{verify_area, MemoryManagementArch, VirtAddr},
+    namespaces::uts_namespace::NEW_UTS_LEN,
     process::ProcessControlBlock,
-    sched::completion::Completion,
     syscall::{
-        user_access::{check_and_clone_cstr, check_and_clone_cstr_array, UserBufferWriter},
+        user_access::{
+            check_and_clone_cstr, check_and_clone_cstr_array, UserBufferReader, UserBufferWriter,
+        },
         Syscall,
     },
 };
 
-//参考资料：https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/utsname.h#17
+//参考资料：https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/utsname.h#25
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct PosixOldUtsName {
@@ -38,6 +48,7 @@ pub struct PosixOldUtsName {
     pub release: [u8; 65],
     pub version: [u8; 65],
     pub machine: [u8; 65],
+    pub domainname: [u8; 65],
 }
 
 impl PosixOldUtsName {
@@ -62,6 +73,7 @@ impl PosixOldUtsName {
             release: [0; 65],
             version: [0; 65],
             machine: [0; 65],
+            domainname: [0; 65],
         };
 
         r.sysname[0..SYS_NAME.len()].copy_from_slice(SYS_NAME);
@@ -76,20 +88,44 @@ impl PosixOldUtsName {
 
 impl Syscall {
     pub fn fork(frame: &TrapFrame) -> Result<usize, SystemError> {
-        ProcessManager::fork(frame, CloneFlags::empty()).map(|pid| pid.into())
+        let pid = ProcessManager::fork(frame, CloneFlags::empty())?;
+        // 向用户态返回子进程在当前pid namespace中的pid
+        return Ok(ProcessManager::find(pid)
+            .map(|pcb| pcb.pid_vnr())
+            .unwrap_or(pid)
+            .into());
     }
 
+    /// 创建一个与父进程共享地址空间的子进程
+    ///
+    /// 父进程会被挂起，直到子进程execve或者退出，期间子进程借用父进程的地址空间与用户栈
     pub fn vfork(frame: &TrapFrame) -> Result<usize, SystemError> {
-        // 由于Linux vfork需要保证子进程先运行（除非子进程调用execve或者exit），
-        // 而我们目前没有实现这个特性，所以暂时使用fork代替vfork（linux文档表示这样也是也可以的）
-        Self::fork(frame)
+        let mut clone_args = KernelCloneArgs::new();
+        clone_args.flags = CloneFlags::CLONE_VM | CloneFlags::CLONE_VFORK;
+        Self::clone(frame, clone_args)
+    }
 
-        // 下面是以前的实现，除非我们实现了子进程先运行的特性，否则不要使用，不然会导致父进程数据损坏
-        // ProcessManager::fork(
-        //     frame,
-        //     CloneFlags::CLONE_VM | CloneFlags::CLONE_FS | CloneFlags::CLONE_SIGNAL,
-        // )
-        // .map(|pid| pid.into())
+    /// 创建一个执行`path`的子进程（见[`ProcessManager::spawn`]），用于实现posix_spawn
+    ///
+    /// 与fork之后再execve相比，子进程不需要拷贝父进程的地址空间
+    ///
+    /// ## 返回值
+    ///
+    /// 成功时返回子进程的pid，子进程执行execve失败时返回execve的错误码
+    pub fn spawn(
+        frame: &TrapFrame,
+        path: *const u8,
+        argv: *const *const u8,
+        envp: *const *const u8,
+    ) -> Result<usize, SystemError> {
+        if path.is_null() {
+            return Err(SystemError::EINVAL);
+        }
+
+        let path: String = check_and_clone_cstr(path, Some(MAX_PATHLEN))?;
+        let argv: Vec<String> = check_and_clone_cstr_array(argv)?;
+        let envp: Vec<String> = check_and_clone_cstr_array(envp)?;
+        ProcessManager::spawn(frame, path, argv, envp).map(|pid| pid.into())
     }
 
     pub fn execve(
@@ -113,25 +149,67 @@ impl Syscall {
             return Err(SystemError::EINVAL);
         }
 
-        let x = || {
-            let path: String = check_and_clone_cstr(path, Some(MAX_PATHLEN))?;
-            let argv: Vec<String> = check_and_clone_cstr_array(argv)?;
-            let envp: Vec<String> = check_and_clone_cstr_array(envp)?;
-            Ok((path, argv, envp))
-        };
-        let r: Result<(String, Vec<String>, Vec<String>), SystemError> = x();
-        if let Err(e) = r {
-            panic!("Failed to execve: {:?}", e);
+        let path: String = check_and_clone_cstr(path, Some(MAX_PATHLEN))?;
+        let argv: Vec<String> = check_and_clone_cstr_array(argv)?;
+        let envp: Vec<String> = check_and_clone_cstr_array(envp)?;
+        return Self::kernel_execve(path, argv, envp, frame);
+    }
+
+    /// 在当前进程中执行新的程序，参数已经被拷贝到内核中
+    ///
+    /// 除了加载程序映像之外，还会重置与原来的程序映像相关的状态（信号处理函数、rseq、O_CLOEXEC的文件等）
+    ///
+    /// ## 参数
+    ///
+    /// - `path` : 可执行文件的路径
+    /// - `argv` : 命令行参数
+    /// - `envp` : 环境变量
+    /// - `frame` : 返回用户态时使用的栈帧，会被修改为从新程序的入口开始执行
+    pub fn kernel_execve(
+        path: String,
+        argv: Vec<String>,
+        envp: Vec<String>,
+        frame: &mut TrapFrame,
+    ) -> Result<(), SystemError> {
+        let name = ProcessControlBlock::generate_name(&path, &argv);
+        let mut cmdline = Vec::new();
+        for arg in argv.iter() {
+            cmdline.extend_from_slice(arg.as_bytes());
+            cmdline.push(0);
         }
-        let (path, argv, envp) = r.unwrap();
-        ProcessManager::current_pcb()
-            .basic_mut()
-            .set_name(ProcessControlBlock::generate_name(&path, &argv));
 
         Self::do_execve(path, argv, envp, frame)?;
 
+        // 新的程序映像已经加载完成，此后不会再返回到原来的程序中
+        let pcb = ProcessManager::current_pcb();
+        pcb.basic_mut().set_name(name);
+        pcb.set_cmdline(Arc::from(cmdline));
+
+        // 新的地址空间中不再存在之前注册的rseq
+        pcb.set_rseq(None);
+        pcb.flags().remove(ProcessFlags::NEED_RSEQ);
+
+        // 之前注册的robust list位于旧的地址空间中
+        pcb.set_robust_list(None);
+
+        // 执行过execve之后，父进程不能再修改这个进程的进程组
+        pcb.flags().remove(ProcessFlags::FORKNOEXEC);
+
+        // 新的程序不一定期望在父进程退出时收到信号，因此清除父进程退出信号
+        pcb.set_pdeath_signal(Signal::INVALID);
+
+        // 子进程已经不再使用父进程的地址空间，唤醒因为vfork而等待的父进程
+        ProcessManager::complete_vfork_done(&pcb);
+
+        // 原来的信号处理函数已经不存在于新的程序映像中，除了被忽略的信号，全部恢复为默认处理方式。
+        // 如果信号处理函数表与其他进程共享，需要先拷贝一份，避免影响到其他进程
+        pcb.sig_struct_irqsave().unshare_handlers();
+        flush_signal_handlers(pcb.clone(), false);
+        // 备用信号栈位于原来的地址空间中，也需要一并清除
+        *pcb.sig_info_mut().sig_altstack_mut() = SigAltStack::default();
+
         // 关闭设置了O_CLOEXEC的文件描述符
-        let fd_table = ProcessManager::current_pcb().fd_table();
+        let fd_table = pcb.fd_table();
         fd_table.write().close_on_exec();
         // kdebug!(
         //     "after execve: strong count: {}",
@@ -165,6 +243,14 @@ impl Syscall {
             Some(RUsage::default())
         };
 
+        // 用户态传入的是当前pid namespace中的pid
+        let pid = if pid > 0 {
+            ProcessManager::vpid_to_pid(Pid::new(pid as usize))
+                .ok_or(SystemError::ECHILD)?
+                .data() as i64
+        } else {
+            pid
+        };
         let r = kernel_wait4(pid, wstatus_buf, options, tmp_rusage.as_mut())?;
 
         if !rusage.is_null() {
@@ -178,19 +264,79 @@ impl Syscall {
         return Ok(r);
     }
 
+    /// 等待子进程的状态发生变化
+    ///
+    /// ## 参数
+    ///
+    /// - `idtype` : 如何解释`id`参数（P_ALL、P_PID、P_PGID、P_PIDFD）
+    /// - `id` : 要等待的子进程
+    /// - `infop` : 用于返回子进程状态变化信息的siginfo_t，可以为空
+    /// - `options` : 等待选项
+    /// - `rusage` : 用于返回资源使用情况的缓冲区，可以为空
+    pub fn waitid(
+        idtype: i32,
+        id: i32,
+        infop: *mut PosixSigInfo,
+        options: i32,
+        rusage: *mut c_void,
+    ) -> Result<usize, SystemError> {
+        let idtype = WaitIdType::try_from(idtype)?;
+        let options = WaitOption::from_bits(options as u32).ok_or(SystemError::EINVAL)?;
+
+        let infop_buf = if infop.is_null() {
+            None
+        } else {
+            Some(UserBufferWriter::new(
+                infop,
+                core::mem::size_of::<PosixSigInfo>(),
+                true,
+            )?)
+        };
+
+        let mut tmp_rusage = if rusage.is_null() {
+            None
+        } else {
+            Some(RUsage::default())
+        };
+
+        let r = kernel_waitid(idtype, id, infop_buf, options, tmp_rusage.as_mut())?;
+
+        if !rusage.is_null() {
+            let mut rusage_buf = UserBufferWriter::new::<RUsage>(
+                rusage as *mut RUsage,
+                core::mem::size_of::<RUsage>(),
+                true,
+            )?;
+            rusage_buf.copy_one_to_user(&tmp_rusage.unwrap(), 0)?;
+        }
+        return Ok(r);
+    }
+
     /// # 退出进程
     ///
     /// ## 参数
     ///
     /// - status: 退出状态
     pub fn exit(status: usize) -> ! {
-        ProcessManager::exit(status);
+        // 按照wait4的约定编码退出状态：低8位为终止进程的信号，次低8位为退出码
+        ProcessManager::exit((status & 0xff) << 8);
+    }
+
+    /// # 退出线程组中的所有线程
+    ///
+    /// ## 参数
+    ///
+    /// - status: 退出状态
+    pub fn exit_group(status: usize) -> ! {
+        ProcessManager::group_exit((status & 0xff) << 8);
     }
 
     /// @brief 获取当前进程的pid
+    ///
+    /// 同一线程组中的所有线程共享相同的pid，即线程组组长的tgid
     pub fn getpid() -> Result<Pid, SystemError> {
         let current_pcb = ProcessManager::current_pcb();
-        return Ok(current_pcb.tgid());
+        return Ok(current_pcb.tgid_vnr());
     }
 
     /// @brief 获取指定进程的pgid
@@ -199,20 +345,174 @@ impl Syscall {
     ///
     /// @return 成功，指定进程的进程组id
     /// @return 错误，不存在该进程
-    pub fn getpgid(mut pid: Pid) -> Result<Pid, SystemError> {
-        if pid == Pid(0) {
-            let current_pcb = ProcessManager::current_pcb();
-            pid = current_pcb.pid();
+    pub fn getpgid(pid: Pid) -> Result<Pid, SystemError> {
+        let target_proc = if pid == Pid(0) {
+            ProcessManager::current_pcb()
+        } else {
+            // 用户态传入的是当前pid namespace中的pid
+            ProcessManager::vpid_to_pid(pid)
+                .and_then(ProcessManager::find)
+                .ok_or(SystemError::ESRCH)?
+        };
+        return Ok(ProcessManager::pid_to_vpid(target_proc.basic().pgid()));
+    }
+
+    /// 设置进程的进程组
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` 目标进程，为0时表示当前进程。目标进程只能是当前进程或者当前进程的子进程
+    /// - `pgid` 目标进程组，为0时表示使用`pid`作为进程组id。
+    ///     如果进程组不是以目标进程自身为组长的新进程组，那么它必须已经存在
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL`：pgid为负数，或者目标进程不是线程组的组长
+    /// - `ESRCH`：目标进程不存在，或者它既不是当前进程也不是当前进程的子进程
+    /// - `EACCES`：目标子进程已经执行过execve
+    /// - `EPERM`：目标子进程与当前进程不在同一个会话中，目标进程是会话的首进程，
+    ///     或者目标进程组在当前会话中不存在
+    pub fn setpgid(pid: i32, pgid: i32) -> Result<usize, SystemError> {
+        if pgid < 0 {
+            return Err(SystemError::EINVAL);
+        }
+        if pid < 0 {
+            return Err(SystemError::ESRCH);
+        }
+
+        let current = ProcessManager::current_pcb();
+        // 用户态传入的是当前pid namespace中的id。进程组必须位于当前namespace中
+        let pid = if pid == 0 {
+            current.tgid()
+        } else {
+            ProcessManager::vpid_to_pid(Pid::new(pid as usize)).ok_or(SystemError::ESRCH)?
+        };
+        let pgid = if pgid == 0 {
+            pid
+        } else {
+            ProcessManager::vpid_to_pid(Pid::new(pgid as usize)).ok_or(SystemError::EPERM)?
+        };
+
+        let target = ProcessManager::find(pid).ok_or(SystemError::ESRCH)?;
+        if !target.is_thread_group_leader() {
+            return Err(SystemError::EINVAL);
+        }
+
+        let sid = current.basic().sid();
+        let is_child = target
+            .real_parent()
+            .is_some_and(|parent| parent.tgid() == current.tgid());
+        if is_child {
+            if target.basic().sid() != sid {
+                return Err(SystemError::EPERM);
+            }
+            if !target.flags().contains(ProcessFlags::FORKNOEXEC) {
+                return Err(SystemError::EACCES);
+            }
+        } else if target.tgid() != current.tgid() {
+            return Err(SystemError::ESRCH);
+        }
+
+        // 会话的首进程不能改变自己的进程组
+        if target.basic().sid() == pid {
+            return Err(SystemError::EPERM);
+        }
+
+        // 加入一个已有的进程组时，这个进程组必须存在，并且与当前进程位于同一个会话中
+        if pgid != pid
+            && !ProcessManager::all_processes().iter().any(|pcb| {
+                let basic = pcb.basic();
+                pcb.is_thread_group_leader() && basic.pgid() == pgid && basic.sid() == sid
+            })
+        {
+            return Err(SystemError::EPERM);
+        }
+
+        // 线程组中的所有线程属于同一个进程组
+        for thread in target.thread_group() {
+            thread.basic_mut().set_pgid(pgid);
+        }
+        return Ok(0);
+    }
+
+    /// 创建一个新的会话
+    ///
+    /// 当前进程成为新会话和新进程组的首进程，并且失去控制终端
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功时返回新会话的id，即当前进程的pid
+    /// - `EPERM`：当前进程已经是一个进程组的组长
+    pub fn setsid() -> Result<Pid, SystemError> {
+        let current = ProcessManager::current_pcb();
+        let pid = current.tgid();
+
+        // 如果已经存在以当前进程的pid为id的进程组，那么新的会话将无法拥有一个独立的进程组
+        if ProcessManager::all_processes()
+            .iter()
+            .any(|pcb| pcb.basic().pgid() == pid)
+        {
+            return Err(SystemError::EPERM);
+        }
+
+        for thread in current.thread_group() {
+            let mut basic = thread.basic_mut();
+            basic.set_sid(pid);
+            basic.set_pgid(pid);
+            drop(basic);
+            thread.sig_info_mut().clear_tty();
         }
-        let target_proc = ProcessManager::find(pid).ok_or(SystemError::ESRCH)?;
-        return Ok(target_proc.basic().pgid());
+        return Ok(current.tgid_vnr());
     }
+
+    /// 获取进程所在会话的id
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` 目标进程，为0时表示当前进程
+    ///
+    /// ## 返回值
+    ///
+    /// - `ESRCH`：目标进程不存在
+    pub fn getsid(pid: Pid) -> Result<Pid, SystemError> {
+        let target = if pid == Pid(0) {
+            ProcessManager::current_pcb()
+        } else {
+            // 用户态传入的是当前pid namespace中的pid
+            ProcessManager::vpid_to_pid(pid)
+                .and_then(ProcessManager::find)
+                .ok_or(SystemError::ESRCH)?
+        };
+        return Ok(ProcessManager::pid_to_vpid(target.basic().sid()));
+    }
+
     /// @brief 获取当前进程的父进程id
 
     /// 若为initproc则ppid设置为0   
     pub fn getppid() -> Result<Pid, SystemError> {
         let current_pcb = ProcessManager::current_pcb();
-        return Ok(current_pcb.basic().ppid());
+        // 父进程退出后，当前进程会被init进程收养，因此需要实时获取父进程
+        // 父进程位于当前进程的pid namespace之外时，返回0
+        let ppid = current_pcb
+            .real_parent()
+            .map(|ppcb| ppcb.tgid_vnr())
+            .unwrap_or(Pid(0));
+        return Ok(ppid);
+    }
+
+    /// 让当前进程不再与其他进程共享flags所指定的资源，见[`ProcessManager::unshare`]
+    ///
+    /// ## 参数
+    ///
+    /// - `flags` : CLONE_FILES、CLONE_FS、CLONE_SYSVSEM、CLONE_NEWUTS、CLONE_NEWNS、CLONE_NEWPID的组合
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL` : flags中含有不支持的标志
+    pub fn unshare(flags: u64) -> Result<usize, SystemError> {
+        let flags = CloneFlags::from_bits(flags).ok_or(SystemError::EINVAL)?;
+        ProcessManager::unshare(flags)?;
+        return Ok(0);
     }
 
     pub fn clone(
@@ -221,7 +521,14 @@ impl Syscall {
     ) -> Result<usize, SystemError> {
         let flags = clone_args.flags;
 
-        let vfork = Arc::new(Completion::new());
+        // 与父进程共享地址空间的子进程必须使用自己的用户栈，否则两者会同时修改同一个栈。
+        // vfork的父进程在子进程execve或者退出之前不会运行，因此子进程可以借用父进程的栈
+        if flags.contains(CloneFlags::CLONE_VM)
+            && !flags.contains(CloneFlags::CLONE_VFORK)
+            && clone_args.stack == 0
+        {
+            return Err(SystemError::EINVAL);
+        }
 
         if flags.contains(CloneFlags::CLONE_PIDFD)
             && flags.contains(CloneFlags::CLONE_PARENT_SETTID)
@@ -229,48 +536,108 @@ impl Syscall {
             return Err(SystemError::EINVAL);
         }
 
+        // 提前检查pidfd的写入地址，避免子进程创建之后才发现地址不合法
+        let mut pidfd_writer = if flags.contains(CloneFlags::CLONE_PIDFD) {
+            Some(UserBufferWriter::new(
+                clone_args.pidfd.data() as *mut i32,
+                core::mem::size_of::<i32>(),
+                true,
+            )?)
+        } else {
+            None
+        };
+
         let current_pcb = ProcessManager::current_pcb();
-        let new_kstack = KernelStack::new()?;
-        let name = current_pcb.basic().name().to_string();
-        let pcb = ProcessControlBlock::new(name, new_kstack);
-        // 克隆pcb
-        ProcessManager::copy_process(&current_pcb, &pcb, clone_args, current_trapframe)?;
-        ProcessManager::add_pcb(pcb.clone());
-
-        // 向procfs注册进程
-        procfs_register_pid(pcb.pid()).unwrap_or_else(|e| {
-            panic!(
-                "fork: Failed to register pid to procfs, pid: [{:?}]. Error: {:?}",
-                pcb.pid(),
-                e
-            )
+        let vfork = Arc::new(VforkDone::new());
+        let mut pidfd = None;
+        let r = ProcessManager::create_process(clone_args, current_trapframe, |pcb| {
+            // 在父进程中为子进程分配pidfd
+            if let Some(writer) = pidfd_writer.as_mut() {
+                let file = File::new(PidfdInode::new(pcb), FileMode::O_RDWR)?;
+                file.set_close_on_exec(true);
+                let fd = current_pcb.fd_table().write().alloc_fd(file, None)?;
+                pidfd = Some(fd);
+                writer.copy_one_to_user(&fd, 0)?;
+            }
+
+            if flags.contains(CloneFlags::CLONE_VFORK) {
+                pcb.thread.write_irqsave().vfork_done = Some(vfork.clone());
+            }
+            Ok(())
         });
+        let pcb = match r {
+            Ok(pcb) => pcb,
+            Err(e) => {
+                if let Some(pidfd) = pidfd {
+                    current_pcb.fd_table().write().drop_fd(pidfd).ok();
+                }
+                return Err(e);
+            }
+        };
 
         if flags.contains(CloneFlags::CLONE_VFORK) {
-            pcb.thread.write_irqsave().vfork_done = Some(vfork.clone());
+            // 等待子进程结束或者exec
+            if vfork.wait().is_err() {
+                // 父进程被致命信号打断，它即将退出，子进程不再需要唤醒它
+                pcb.thread.write_irqsave().vfork_done = None;
+            }
         }
 
-        if pcb.thread.read_irqsave().set_child_tid.is_some() {
-            let addr = pcb.thread.read_irqsave().set_child_tid.unwrap();
-            let mut writer =
-                UserBufferWriter::new(addr.as_ptr::<i32>(), core::mem::size_of::<i32>(), true)?;
-            writer.copy_one_to_user(&(pcb.pid().data() as i32), 0)?;
+        return Ok(pcb.pid_vnr().0);
+    }
+
+    /// # clone3系统调用
+    ///
+    /// ## 参数
+    ///
+    /// - `current_trapframe`：当前进程的trapframe
+    /// - `uargs`：用户态的clone3参数
+    /// - `size`：用户态参数结构体的大小，用于区分结构体的版本
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：返回子进程的pid
+    /// - `EINVAL`：参数不合法，或者set_tid指定的pid超出了合法的范围
+    /// - `EEXIST`：set_tid指定的pid已经被占用
+    /// - `E2BIG`：用户态的结构体比内核认识的更大，并且多出来的部分不为0
+    pub fn clone3(
+        current_trapframe: &TrapFrame,
+        uargs: *const PosixCloneArgs,
+        size: usize,
+    ) -> Result<usize, SystemError> {
+        if size < PosixCloneArgs::SIZE_VER0 {
+            return Err(SystemError::EINVAL);
+        }
+        if size > MMArch::PAGE_SIZE {
+            return Err(SystemError::E2BIG);
         }
 
-        ProcessManager::wakeup(&pcb).unwrap_or_else(|e| {
-            panic!(
-                "fork: Failed to wakeup new process, pid: [{:?}]. Error: {:?}",
-                pcb.pid(),
-                e
-            )
-        });
+        let reader = UserBufferReader::new(uargs, size, true)?;
+        let ubuf = reader.read_from_user::<u8>(0)?;
 
-        if flags.contains(CloneFlags::CLONE_VFORK) {
-            // 等待子进程结束或者exec;
-            vfork.wait_for_completion_interruptible()?;
+        // 用户态的结构体比内核的新时，多出来的部分必须为0
+        let ksize = core::mem::size_of::<PosixCloneArgs>();
+        if size > ksize && ubuf[ksize..].iter().any(|&b| b != 0) {
+            return Err(SystemError::E2BIG);
+        }
+
+        // 用户态的结构体比内核的旧时，缺少的字段为0
+        let mut args = PosixCloneArgs::default();
+        let len = core::cmp::min(size, ksize);
+        unsafe {
+            core::slice::from_raw_parts_mut(&mut args as *mut PosixCloneArgs as *mut u8, ksize)
+                [..len]
+                .copy_from_slice(&ubuf[..len]);
+        }
+
+        if args.flags & CloneFlags::CLONE_INTO_CGROUP.bits() != 0
+            && (args.cgroup > i32::MAX as u64 || size < PosixCloneArgs::SIZE_VER2)
+        {
+            return Err(SystemError::EINVAL);
         }
 
-        return Ok(pcb.pid().0);
+        let clone_args = KernelCloneArgs::try_from(args)?;
+        return Self::clone(current_trapframe, clone_args);
     }
 
     /// 设置线程地址
@@ -283,9 +650,12 @@ impl Syscall {
         Ok(pcb.pid.0)
     }
 
+    /// @brief 获取当前线程的tid
+    ///
+    /// 每个线程的tid都不相同，线程组组长的tid与其pid相同
     pub fn gettid() -> Result<Pid, SystemError> {
         let pcb = ProcessManager::current_pcb();
-        Ok(pcb.pid)
+        Ok(pcb.pid_vnr())
     }
 
     pub fn getuid() -> Result<usize, SystemError> {
@@ -320,13 +690,13 @@ impl Syscall {
         return Ok(0);
     }
 
-    /// # 设置资源限制
+    /// # 获取/设置资源限制
     ///
-    /// TODO: 目前暂时不支持设置资源限制，只提供读取默认值的功能
+    /// 所有资源的限制都可以被读取和设置，但目前只有RLIMIT_NOFILE和RLIMIT_NPROC会被强制执行
     ///
     /// ## 参数
     ///
-    /// - pid: 进程号
+    /// - pid: 进程号，为0时表示当前进程
     /// - resource: 资源类型
     /// - new_limit: 新的资源限制
     /// - old_limit: 旧的资源限制
@@ -337,61 +707,257 @@ impl Syscall {
     /// - 如果old_limit不为NULL，则返回旧的资源限制到old_limit
     ///
     pub fn prlimit64(
-        _pid: Pid,
+        pid: Pid,
         resource: usize,
-        _new_limit: *const RLimit64,
+        new_limit: *const RLimit64,
         old_limit: *mut RLimit64,
     ) -> Result<usize, SystemError> {
         let resource = RLimitID::try_from(resource)?;
-        let mut writer = None;
+        let pcb = if pid == Pid(0) {
+            ProcessManager::current_pcb()
+        } else {
+            // 用户态传入的是当前pid namespace中的pid
+            ProcessManager::vpid_to_pid(pid)
+                .and_then(ProcessManager::find)
+                .ok_or(SystemError::ESRCH)?
+        };
+
+        let new_limit = if !new_limit.is_null() {
+            let reader = UserBufferReader::new(new_limit, core::mem::size_of::<RLimit64>(), true)?;
+            Some(*reader.read_one_from_user::<RLimit64>(0)?)
+        } else {
+            None
+        };
+
+        let old = match new_limit {
+            Some(new_limit) => pcb.set_rlimit(resource, new_limit)?,
+            None => pcb.rlimit(resource),
+        };
 
         if !old_limit.is_null() {
-            writer = Some(UserBufferWriter::new(
-                old_limit,
-                core::mem::size_of::<RLimit64>(),
-                true,
-            )?);
+            let mut writer =
+                UserBufferWriter::new(old_limit, core::mem::size_of::<RLimit64>(), true)?;
+            writer.copy_one_to_user(&old, 0)?;
         }
 
-        match resource {
-            RLimitID::Stack => {
-                if let Some(mut writer) = writer {
-                    let mut rlimit = writer.buffer::<RLimit64>(0).unwrap()[0];
-                    rlimit.rlim_cur = UserStack::DEFAULT_USER_STACK_SIZE as u64;
-                    rlimit.rlim_max = UserStack::DEFAULT_USER_STACK_SIZE as u64;
-                }
-                return Ok(0);
-            }
+        return Ok(0);
+    }
+
+    /// 获取当前进程的资源限制
+    pub fn getrlimit(resource: usize, rlimit: *mut RLimit64) -> Result<usize, SystemError> {
+        return Self::prlimit64(Pid(0), resource, core::ptr::null::<RLimit64>(), rlimit);
+    }
+
+    /// 设置当前进程的资源限制
+    pub fn setrlimit(resource: usize, rlimit: *const RLimit64) -> Result<usize, SystemError> {
+        return Self::prlimit64(Pid(0), resource, rlimit, core::ptr::null_mut::<RLimit64>());
+    }
 
-            RLimitID::Nofile => {
-                if let Some(mut writer) = writer {
-                    let mut rlimit = writer.buffer::<RLimit64>(0).unwrap()[0];
-                    rlimit.rlim_cur = FileDescriptorVec::PROCESS_MAX_FD as u64;
-                    rlimit.rlim_max = FileDescriptorVec::PROCESS_MAX_FD as u64;
+    /// # 对当前进程（线程）进行操作
+    ///
+    /// ## 参数
+    ///
+    /// - option: 操作类型
+    /// - arg2: 操作的参数，含义取决于option
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL`：不支持的操作类型
+    /// - `EFAULT`：用户空间的地址不合法
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/sys.c#2345
+    pub fn prctl(option: usize, arg2: usize) -> Result<usize, SystemError> {
+        let option = PrctlOption::try_from(option)?;
+        let pcb = ProcessManager::current_pcb();
+        match option {
+            PrctlOption::SetName => {
+                // 与Linux一致，名字最多保留TASK_COMM_LEN - 1个字节
+                let mut name = check_and_clone_cstr(arg2 as *const u8, Some(TASK_COMM_LEN))?;
+                let mut len = name.len().min(TASK_COMM_LEN - 1);
+                while !name.is_char_boundary(len) {
+                    len -= 1;
                 }
-                return Ok(0);
+                name.truncate(len);
+                pcb.set_name(name);
             }
-
-            RLimitID::As | RLimitID::Rss => {
-                if let Some(mut writer) = writer {
-                    let mut rlimit = writer.buffer::<RLimit64>(0).unwrap()[0];
-                    rlimit.rlim_cur = MMArch::USER_END_VADDR.data() as u64;
-                    rlimit.rlim_max = MMArch::USER_END_VADDR.data() as u64;
+            PrctlOption::SetPdeathSig => {
+                let sig = Signal::from(arg2);
+                if sig == Signal::INVALID && arg2 != 0 {
+                    return Err(SystemError::EINVAL);
                 }
-                return Ok(0);
+                pcb.set_pdeath_signal(sig);
+            }
+            PrctlOption::GetPdeathSig => {
+                let mut writer =
+                    UserBufferWriter::new(arg2 as *mut i32, core::mem::size_of::<i32>(), true)?;
+                writer.copy_one_to_user(&(pcb.pdeath_signal() as i32), 0)?;
             }
+            PrctlOption::GetName => {
+                let mut comm = [0u8; TASK_COMM_LEN];
+                let name = pcb.basic().name().to_string();
+                let len = name.len().min(TASK_COMM_LEN - 1);
+                comm[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+                let mut writer = UserBufferWriter::new(arg2 as *mut u8, TASK_COMM_LEN, true)?;
+                writer.copy_to_user(&comm, 0)?;
+            }
+        }
 
-            _ => {
-                return Err(SystemError::ENOSYS);
+        return Ok(0);
+    }
+
+    /// 跟踪进程，或者控制被跟踪的进程
+    ///
+    /// ## 参数
+    ///
+    /// - `request` : 操作类型，目前支持PTRACE_TRACEME、PTRACE_ATTACH、PTRACE_DETACH、
+    ///   PTRACE_CONT、PTRACE_GETREGS、PTRACE_SETREGS
+    /// - `pid` : 被跟踪者的pid，PTRACE_TRACEME时被忽略
+    /// - `addr` : 目前支持的操作都不使用这个参数
+    /// - `data` : PTRACE_CONT、PTRACE_DETACH时为要递送给被跟踪者的信号，
+    ///   PTRACE_GETREGS、PTRACE_SETREGS时为用户空间的寄存器缓冲区
+    ///
+    /// ## 返回值
+    ///
+    /// - `EIO`：不支持的操作类型，或者参数不合法
+    /// - `ESRCH`：进程不存在，或者没有被当前进程跟踪并处于停止状态
+    /// - `EPERM`：不能跟踪这个进程
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/ptrace.c#1270
+    pub fn ptrace(
+        request: usize,
+        pid: i32,
+        _addr: usize,
+        data: usize,
+    ) -> Result<usize, SystemError> {
+        let request = PtraceRequest::try_from(request)?;
+        if request == PtraceRequest::TraceMe {
+            ProcessManager::ptrace_traceme()?;
+            return Ok(0);
+        }
+
+        if pid <= 0 {
+            return Err(SystemError::ESRCH);
+        }
+        let child = ProcessManager::vpid_to_pid(Pid(pid as usize))
+            .and_then(ProcessManager::find)
+            .ok_or(SystemError::ESRCH)?;
+
+        if request == PtraceRequest::Attach {
+            ProcessManager::ptrace_attach(&child)?;
+            return Ok(0);
+        }
+
+        ProcessManager::ptrace_check_attach(&child)?;
+        match request {
+            PtraceRequest::Cont => ProcessManager::ptrace_resume(&child, data)?,
+            PtraceRequest::Detach => ProcessManager::ptrace_detach(&child, data)?,
+            PtraceRequest::GetRegs => ProcessManager::ptrace_getregs(&child, VirtAddr::new(data))?,
+            PtraceRequest::SetRegs => ProcessManager::ptrace_setregs(&child, VirtAddr::new(data))?,
+            PtraceRequest::TraceMe | PtraceRequest::Attach => unreachable!(),
+        }
+        return Ok(0);
+    }
+
+    /// 获取ioprio_get/ioprio_set的目标进程，目前只支持IOPRIO_WHO_PROCESS
+    fn ioprio_target(which: i32, who: i32) -> Result<Arc<ProcessControlBlock>, SystemError> {
+        match IoPrioWho::try_from(which)? {
+            IoPrioWho::Process => {
+                if who == 0 {
+                    return Ok(ProcessManager::current_pcb());
+                }
+                return ProcessManager::vpid_to_pid(Pid::new(who as usize))
+                    .and_then(ProcessManager::find)
+                    .ok_or(SystemError::ESRCH);
             }
+            IoPrioWho::Pgrp | IoPrioWho::User => Err(SystemError::EINVAL),
         }
     }
 
+    /// 设置进程的I/O优先级
+    ///
+    /// I/O优先级保存在进程的I/O上下文中，因此会同时作用于通过CLONE_IO共享这个上下文的所有进程
+    ///
+    /// ## 参数
+    ///
+    /// - `which` : 目标的类型，目前只支持IOPRIO_WHO_PROCESS
+    /// - `who` : 目标进程的pid，为0时表示当前进程
+    /// - `ioprio` : 新的I/O优先级
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/block/ioprio.c#68
+    pub fn ioprio_set(which: i32, who: i32, ioprio: i32) -> Result<usize, SystemError> {
+        let ioprio = u16::try_from(ioprio).map_err(|_| SystemError::EINVAL)?;
+        ioprio_check(ioprio)?;
+        let pcb = Self::ioprio_target(which, who)?;
+        pcb.io_context().set_ioprio(ioprio);
+        return Ok(0);
+    }
+
+    /// 获取进程的I/O优先级
+    ///
+    /// ## 参数
+    ///
+    /// - `which` : 目标的类型，目前只支持IOPRIO_WHO_PROCESS
+    /// - `who` : 目标进程的pid，为0时表示当前进程
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/block/ioprio.c#183
+    pub fn ioprio_get(which: i32, who: i32) -> Result<usize, SystemError> {
+        let pcb = Self::ioprio_target(which, who)?;
+        return Ok(pcb.io_context().ioprio() as usize);
+    }
+
     pub fn uname(name: *mut PosixOldUtsName) -> Result<usize, SystemError> {
         let mut writer =
             UserBufferWriter::new(name, core::mem::size_of::<PosixOldUtsName>(), true)?;
-        writer.copy_one_to_user(&PosixOldUtsName::new(), 0)?;
+        // 主机名与域名来自当前进程所在的uts namespace
+        let uts_name = ProcessManager::current_pcb().uts_ns().name();
+        let mut utsname = PosixOldUtsName::new();
+        utsname.nodename = uts_name.nodename;
+        utsname.domainname = uts_name.domainname;
+        writer.copy_one_to_user(&utsname, 0)?;
+
+        return Ok(0);
+    }
 
+    /// 设置当前进程所在的uts namespace的主机名
+    ///
+    /// ## 参数
+    ///
+    /// - `name` : 主机名，不需要以\0结尾
+    /// - `len` : 主机名的长度
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL` : 长度超过了[`NEW_UTS_LEN`]
+    pub fn sethostname(name: *const u8, len: usize) -> Result<usize, SystemError> {
+        if len > NEW_UTS_LEN {
+            return Err(SystemError::EINVAL);
+        }
+        let reader = UserBufferReader::new(name, len, true)?;
+        let name = reader.read_from_user::<u8>(0)?;
+        ProcessManager::current_pcb().uts_ns().set_nodename(name)?;
+        return Ok(0);
+    }
+
+    /// 设置当前进程所在的uts namespace的域名
+    ///
+    /// ## 参数
+    ///
+    /// - `name` : 域名，不需要以\0结尾
+    /// - `len` : 域名的长度
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL` : 长度超过了[`NEW_UTS_LEN`]
+    pub fn setdomainname(name: *const u8, len: usize) -> Result<usize, SystemError> {
+        if len > NEW_UTS_LEN {
+            return Err(SystemError::EINVAL);
+        }
+        let reader = UserBufferReader::new(name, len, true)?;
+        let name = reader.read_from_user::<u8>(0)?;
+        ProcessManager::current_pcb()
+            .uts_ns()
+            .set_domainname(name)?;
         return Ok(0);
     }
 }
@@ -71,9 +71,18 @@ impl IrqTime {
 
 pub struct CpuTimeFunc;
 impl CpuTimeFunc {
+    /// 时钟tick时，把这个tick的时间计入当前进程的用户态或者内核态时间
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/sched/cputime.c
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb` : 当前进程
+    /// - `user_tick` : 时钟中断发生时，进程是否在用户态运行
+    /// - `ticks` : tick的个数
     pub fn irqtime_account_process_tick(
-        _pcb: &Arc<ProcessControlBlock>,
-        _user_tick: bool,
+        pcb: &Arc<ProcessControlBlock>,
+        user_tick: bool,
         ticks: u64,
     ) {
         let cputime = TICK_NESC as u64 * ticks;
@@ -84,7 +93,35 @@ impl CpuTimeFunc {
             return;
         }
 
-        // TODO: update process time
+        // 处理中断的时间不计入进程
+        pcb.stats().account_cputime(user_tick, cputime - other);
+    }
+
+    /// 按照tick采样得到的用户态、内核态时间的比例，划分调度器精确统计的运行时间
+    ///
+    /// tick采样只能得到比例：进程运行的时间不足一个tick时，可能一次都没有被采样到。
+    /// 没有任何采样时（包括不会调用[`ProcessManager::update_process_times`](crate::process::ProcessManager::update_process_times)的riscv64），全部运行时间被计为用户态时间
+    ///
+    /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/sched/cputime.c
+    ///
+    /// ## 参数
+    ///
+    /// - `rtime` : 调度器统计的运行时间（纳秒）
+    /// - `utime` : tick采样得到的用户态时间（纳秒）
+    /// - `stime` : tick采样得到的内核态时间（纳秒）
+    ///
+    /// ## 返回值
+    ///
+    /// 划分后的(用户态时间, 内核态时间)，两者之和为`rtime`
+    pub fn cputime_adjust(rtime: u64, utime: u64, stime: u64) -> (u64, u64) {
+        if stime == 0 {
+            return (rtime, 0);
+        }
+        if utime == 0 {
+            return (0, rtime);
+        }
+        let stime = (rtime as u128 * stime as u128 / (utime as u128 + stime as u128)) as u64;
+        return (rtime - stime, stime);
     }
 
     pub fn account_other_time(max: u64) -> u64 {
@@ -30,7 +30,10 @@ use crate::{
         spinlock::{SpinLock, SpinLockGuard},
     },
     mm::percpu::{PerCpu, PerCpuVar},
-    process::{ProcessControlBlock, ProcessFlags, ProcessManager, ProcessState, SchedInfo},
+    process::{
+        ProcessControlBlock, ProcessFlags, ProcessManager, ProcessState, SchedInfo,
+        PROCESS_SWITCH_RESULT,
+    },
     sched::idle::IdleScheduler,
     smp::{core::smp_get_processor_id, cpu::ProcessorId},
     time::{clocksource::HZ, timer::clock},
@@ -766,6 +769,14 @@ pub enum OnRq {
 }
 
 impl ProcessManager {
+    /// 时钟中断时调用，把这个tick计入当前进程的用户态或者内核态时间，然后进行调度器的tick处理
+    ///
+    /// 目前只有x86_64的APIC定时器中断会调用这个函数。riscv64还没有处理时钟中断（见`riscv64_do_interrupt`），
+    /// 因此在riscv64上没有tick采样，进程的运行时间全部被报告为用户态时间（见[`CpuTimeFunc::cputime_adjust`]）
+    ///
+    /// ## 参数
+    ///
+    /// - `user_tick` : 时钟中断发生时，当前进程是否在用户态运行
     pub fn update_process_times(user_tick: bool) {
         let pcb = Self::current_pcb();
         CpuTimeFunc::irqtime_account_process_tick(&pcb, user_tick, 1);
@@ -828,6 +839,9 @@ pub fn __schedule(sched_mod: SchedMode) {
 
     // TODO: hrtick_clear(rq);
 
+    // 上一次调度换出的进程已经不在本cpu上运行，可以完成它的迁移
+    ProcessManager::finish_migrate();
+
     let (rq, _guard) = rq.self_lock();
 
     rq.clock_updata_flags = ClockUpdataFlag::from_bits_truncate(rq.clock_updata_flags.bits() << 1);
@@ -858,11 +872,30 @@ pub fn __schedule(sched_mod: SchedMode) {
     //     prev.pid()
     // );
 
+    // 进程是否主动让出cpu，用于统计上下文切换的次数
+    let mut voluntary = false;
+    // 进程需要迁移到其他cpu上：先让它离开本cpu的运行队列，切换完成后再在目标cpu上唤醒
+    let mut migrate = None;
+    if unlikely(prev.flags().contains(ProcessFlags::NEED_MIGRATE))
+        && prev.sched_info().policy() != SchedPolicy::IDLE
+    {
+        let mut writer = prev.sched_info().inner_lock_write_irqsave();
+        if !writer.state().is_exited() {
+            let need_wakeup = !writer.is_mark_sleep();
+            if need_wakeup {
+                writer.set_state(ProcessState::Blocked(false));
+                writer.set_sleep();
+            }
+            migrate = Some(need_wakeup);
+        }
+    }
+
     // kerror!("prev pid {:?} {:?}", prev.pid(), prev.sched_info().policy());
-    if !sched_mod.contains(SchedMode::SM_MASK_PREEMPT)
+    if (!sched_mod.contains(SchedMode::SM_MASK_PREEMPT) || migrate.is_some())
         && prev.sched_info().policy() != SchedPolicy::IDLE
         && prev.sched_info().inner_lock_read_irqsave().is_mark_sleep()
     {
+        voluntary = migrate != Some(true);
         // kwarn!("deactivate_task prev {:?}", prev.pid());
         // TODO: 这里需要处理信号
         // https://code.dragonos.org.cn/xref/linux-6.6.21/kernel/sched/core.c?r=&mo=172979&fi=6578#6630
@@ -893,6 +926,16 @@ pub fn __schedule(sched_mod: SchedMode) {
     prev.flags().remove(ProcessFlags::NEED_SCHEDULE);
     fence(Ordering::SeqCst);
     if likely(!Arc::ptr_eq(&prev, &next)) {
+        prev.stats().inc_csw(voluntary);
+        if let Some(need_wakeup) = migrate {
+            unsafe {
+                PROCESS_SWITCH_RESULT
+                    .as_mut()
+                    .unwrap()
+                    .get_mut()
+                    .migrate_pcb = Some((prev.clone(), need_wakeup));
+            }
+        }
         rq.set_current(Arc::downgrade(&next));
         // kwarn!(
         //     "switch_process prev {:?} next {:?} sched_mode {sched_mod:?}",
@@ -937,6 +980,8 @@ pub fn sched_fork(pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
     let current = ProcessManager::current_pcb();
 
     prio_guard.prio = current.sched_info().prio_data.read_irqsave().normal_prio;
+    pcb.sched_info()
+        .set_cpus_allowed(current.sched_info().cpus_allowed());
 
     if PrioUtil::dl_prio(prio_guard.prio) {
         return Err(SystemError::EAGAIN_OR_EWOULDBLOCK);
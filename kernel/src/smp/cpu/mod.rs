@@ -188,8 +188,19 @@ impl SmpCpuManager {
         unsafe { self.set_cpuhp_state(cpu_id, CpuHpState::Online) };
     }
 
+    /// 把CPU标记为离线，并清空它的当前进程缓存
+    ///
+    /// 离线的CPU之后再次上线时，运行的是新的进程，不能再使用下线之前缓存的pcb
+    ///
+    /// ## Safety
+    ///
+    /// 调用时，`cpu_id`上不能有正在运行的进程
+    pub unsafe fn set_offline_cpu(&self, cpu_id: ProcessorId) {
+        self.set_cpuhp_state(cpu_id, CpuHpState::Offline);
+        ProcessManager::invalidate_current_pcb_cache(cpu_id);
+    }
+
     /// 获取出现在系统中的CPU
-    #[allow(dead_code)]
     pub fn present_cpus(&self) -> &CpuMask {
         &self.present_cpus
     }
@@ -247,6 +258,10 @@ impl SmpCpuManager {
         if let Err(e) = self.do_cpuhp_kick_ap(hpstate) {
             self.cpuhp_reset_state(hpstate, prev_state);
             self.do_cpuhp_kick_ap(hpstate).ok();
+            // 启动失败的CPU回到离线状态
+            if prev_state <= CpuHpState::Offline {
+                unsafe { self.set_offline_cpu(cpu_id) };
+            }
 
             return Err(e);
         }
@@ -1,15 +1,77 @@
+use alloc::sync::Arc;
 use bitmap::traits::BitMapOps;
 use system_error::SystemError;
 
-use crate::syscall::Syscall;
+use crate::{
+    libs::cpumask::CpuMask,
+    process::{Pid, ProcessControlBlock, ProcessManager},
+    syscall::Syscall,
+};
 
 use super::cpu::smp_cpu_manager;
 
 impl Syscall {
-    pub fn getaffinity(_pid: i32, set: &mut [u8]) -> Result<usize, SystemError> {
-        let cpu_manager = smp_cpu_manager();
-        let src = unsafe { cpu_manager.possible_cpus().inner().as_bytes() };
+    /// 获取进程允许运行的cpu集合
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 目标线程的tid，为0时表示当前线程
+    /// - `set` : 用户提供的缓冲区，长度不能小于内核cpu掩码的长度
+    ///
+    /// ## 返回值
+    ///
+    /// 成功时返回写入缓冲区的字节数
+    pub fn getaffinity(pid: i32, set: &mut [u8]) -> Result<usize, SystemError> {
+        let pcb = Self::affinity_target(pid)?;
+        let mask = pcb.sched_info().cpus_allowed();
+        let src = unsafe { mask.inner().as_bytes() };
+        if set.len() < src.len() {
+            return Err(SystemError::EINVAL);
+        }
         set[0..src.len()].copy_from_slice(src);
+        Ok(src.len())
+    }
+
+    /// 设置进程允许运行的cpu集合
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 目标线程的tid，为0时表示当前线程
+    /// - `set` : 用户提供的cpu掩码，超出系统cpu数量的位会被忽略
+    ///
+    /// ## 返回值
+    ///
+    /// - `EINVAL` : 掩码中没有任何已经上线的cpu
+    /// - `ESRCH` : 找不到目标线程
+    pub fn setaffinity(pid: i32, set: &[u8]) -> Result<usize, SystemError> {
+        let pcb = Self::affinity_target(pid)?;
+
+        let mut mask = CpuMask::new();
+        for cpu in smp_cpu_manager().possible_cpus().iter_cpu() {
+            let index = cpu.data() as usize;
+            let allowed = set
+                .get(index / 8)
+                .map(|byte| byte & (1 << (index % 8)) != 0)
+                .unwrap_or(false);
+            if allowed {
+                mask.set(cpu, true);
+            }
+        }
+
+        ProcessManager::set_cpus_allowed(&pcb, &mask)?;
         Ok(0)
     }
+
+    fn affinity_target(pid: i32) -> Result<Arc<ProcessControlBlock>, SystemError> {
+        if pid < 0 {
+            return Err(SystemError::ESRCH);
+        }
+        if pid == 0 {
+            return Ok(ProcessManager::current_pcb());
+        }
+        // 用户态传入的是当前pid namespace中的pid
+        ProcessManager::vpid_to_pid(Pid::new(pid as usize))
+            .and_then(ProcessManager::find)
+            .ok_or(SystemError::ESRCH)
+    }
 }
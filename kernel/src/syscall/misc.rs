@@ -1,7 +1,9 @@
 use crate::{
     arch::{mm::LockedFrameAllocator, rand::rand},
+    filesystem::vfs::syscall::ModeType,
     libs::rand::GRandFlags,
     mm::allocator::page_frame::FrameAllocator,
+    process::ProcessManager,
 };
 use alloc::vec::Vec;
 use core::cmp;
@@ -46,7 +48,9 @@ impl Syscall {
         sysinfo.bufferram = 0;
         sysinfo.totalswap = 0;
         sysinfo.freeswap = 0;
-        sysinfo.procs = 0;
+        let mut procs: u16 = 0;
+        ProcessManager::for_each_process(|_| procs = procs.saturating_add(1));
+        sysinfo.procs = procs;
         sysinfo.pad = 0;
         sysinfo.totalhigh = 0;
         sysinfo.freehigh = 0;
@@ -57,9 +61,11 @@ impl Syscall {
         return Ok(0);
     }
 
-    pub fn umask(_mask: u32) -> Result<usize, SystemError> {
-        kwarn!("SYS_UMASK has not yet been implemented\n");
-        return Ok(0o777);
+    /// 设置当前进程的umask，并返回旧的umask
+    pub fn umask(mask: u32) -> Result<usize, SystemError> {
+        let fs = ProcessManager::current_pcb().basic().fs();
+        let old = fs.write().set_umask(ModeType::from_bits_truncate(mask));
+        return Ok(old.bits() as usize);
     }
 
     /// ## 将随机字节填入buf
@@ -8,12 +8,15 @@ use crate::{
     arch::{ipc::signal::SigSet, syscall::nr::*},
     driver::base::device::device_number::DeviceNumber,
     filesystem::vfs::syscall::{PosixStatfs, PosixStatx},
-    ipc::shm::{ShmCtlCmd, ShmFlags, ShmId, ShmKey},
+    ipc::{
+        shm::{ShmCtlCmd, ShmFlags, ShmId, ShmKey},
+        signal_types::{PosixSigInfo, PosixSigStack},
+    },
     libs::{futex::constant::FutexFlag, rand::GRandFlags},
     mm::syscall::MremapFlags,
     net::syscall::MsgHdr,
     process::{
-        fork::KernelCloneArgs,
+        fork::{KernelCloneArgs, PosixCloneArgs},
         resource::{RLimit64, RUsage},
         ProcessFlags, ProcessManager,
     },
@@ -37,7 +40,11 @@ use crate::{
     libs::align::page_align_up,
     mm::{verify_area, MemoryManagementArch, VirtAddr},
     net::syscall::SockAddr,
-    process::{fork::CloneFlags, syscall::PosixOldUtsName, Pid},
+    process::{
+        fork::{legacy_clone_exit_signal, CloneFlags, CLONE_LEGACY_FLAGS},
+        syscall::PosixOldUtsName,
+        Pid,
+    },
     time::{
         syscall::{PosixTimeZone, PosixTimeval},
         PosixTimeSpec,
@@ -46,10 +53,11 @@ use crate::{
 
 use self::{
     misc::SysInfo,
-    user_access::{UserBufferReader, UserBufferWriter},
+    user_access::{access_ok, UserBufferReader, UserBufferWriter},
 };
 
 pub mod misc;
+pub mod rseq;
 pub mod user_access;
 
 // 与linux不一致的调用，在linux基础上累加
@@ -58,6 +66,8 @@ pub const SYS_SBRK: usize = 100001;
 /// todo: 该系统调用与Linux不一致，将来需要删除该系统调用！！！ 删的时候记得改C版本的libc
 pub const SYS_CLOCK: usize = 100002;
 pub const SYS_SCHED: usize = 100003;
+/// 创建一个执行新程序的子进程，不拷贝父进程的地址空间（posix_spawn）
+pub const SYS_SPAWN: usize = 100004;
 
 #[derive(Debug)]
 pub struct Syscall;
@@ -211,6 +221,13 @@ impl Syscall {
             #[cfg(target_arch = "x86_64")]
             SYS_VFORK => Self::vfork(frame),
 
+            SYS_SPAWN => Self::spawn(
+                frame,
+                args[0] as *const u8,
+                args[1] as *const *const u8,
+                args[2] as *const *const u8,
+            ),
+
             SYS_BRK => {
                 let new_brk = VirtAddr::new(args[0]);
                 Self::brk(new_brk).map(|vaddr| vaddr.data())
@@ -236,7 +253,7 @@ impl Syscall {
                 let len = args[2];
                 let virt_addr: VirtAddr = VirtAddr::new(buf_vaddr);
                 // 判断缓冲区是否来自用户态，进行权限校验
-                let res = if frame.is_from_user() && verify_area(virt_addr, len).is_err() {
+                let res = if frame.is_from_user() && access_ok(virt_addr, len, true).is_err() {
                     // 来自用户态，而buffer在内核态，这样的操作不被允许
                     Err(SystemError::EPERM)
                 } else if buf_vaddr == 0 {
@@ -285,6 +302,15 @@ impl Syscall {
                 Self::wait4(pid.into(), wstatus, options, rusage)
             }
 
+            SYS_WAITID => {
+                let idtype = args[0] as i32;
+                let id = args[1] as i32;
+                let infop = args[2] as *mut PosixSigInfo;
+                let options = args[3] as c_int;
+                let rusage = args[4] as *mut c_void;
+                Self::waitid(idtype, id, infop, options, rusage)
+            }
+
             SYS_EXIT => {
                 let exit_code = args[0];
                 Self::exit(exit_code)
@@ -369,17 +395,55 @@ impl Syscall {
                 Self::unlink(path)
             }
             SYS_KILL => {
-                let pid = Pid::new(args[0]);
+                let pid = args[0] as i32;
                 let sig = args[1] as c_int;
                 // kdebug!("KILL SYSCALL RECEIVED");
-                Self::kill(pid, sig)
+                if pid > 0 || (pid < -1 && pid != i32::MIN) {
+                    // 用户态传入的是当前pid namespace中的pid（或者进程组id）
+                    match ProcessManager::vpid_to_pid(Pid::new(pid.unsigned_abs() as usize)) {
+                        Some(global) => Self::kill(global.data() as i32 * pid.signum(), sig),
+                        None => Err(SystemError::ESRCH),
+                    }
+                } else {
+                    Self::kill(pid, sig)
+                }
+            }
+
+            SYS_RT_SIGQUEUEINFO => {
+                let pid = Pid::new(args[0]);
+                let sig = args[1] as c_int;
+                let uinfo = args[2] as *const PosixSigInfo;
+                Self::rt_sigqueueinfo(pid, sig, uinfo)
+            }
+
+            SYS_PIDFD_SEND_SIGNAL => {
+                let pidfd = args[0] as i32;
+                let sig = args[1] as c_int;
+                let uinfo = args[2] as *const PosixSigInfo;
+                let flags = args[3] as u32;
+                Self::pidfd_send_signal(pidfd, sig, uinfo, flags)
+            }
+
+            SYS_SETNS => {
+                let fd = args[0] as i32;
+                let nstype = args[1] as i32;
+                Self::setns(fd, nstype)
+            }
+
+            SYS_UNSHARE => {
+                let flags = args[0] as u64;
+                Self::unshare(flags)
             }
 
+            SYS_IOPRIO_SET => Self::ioprio_set(args[0] as i32, args[1] as i32, args[2] as i32),
+            SYS_IOPRIO_GET => Self::ioprio_get(args[0] as i32, args[1] as i32),
+
             SYS_RT_SIGACTION => {
                 let sig = args[0] as c_int;
                 let act = args[1];
                 let old_act = args[2];
-                Self::sigaction(sig, act, old_act, frame.is_from_user())
+                let sigsetsize = args[3];
+                Self::sigaction(sig, act, old_act, sigsetsize, frame.is_from_user())
             }
 
             SYS_GETPID => Self::getpid().map(|pid| pid.into()),
@@ -401,6 +465,13 @@ impl Syscall {
                 Self::dup2(oldfd, newfd)
             }
 
+            SYS_DUP3 => {
+                let oldfd: i32 = args[0] as c_int;
+                let newfd: i32 = args[1] as c_int;
+                let flags: u32 = args[2] as u32;
+                Self::dup3(oldfd, newfd, flags)
+            }
+
             SYS_SOCKET => Self::socket(args[0], args[1], args[2]),
             SYS_SETSOCKOPT => {
                 let optval = args[3] as *const u8;
@@ -421,14 +492,14 @@ impl Syscall {
                 let virt_optval = VirtAddr::new(optval as usize);
                 let virt_optlen = VirtAddr::new(optlen as usize);
                 let security_check = || {
-                    // 验证optval的地址是否合法
-                    if verify_area(virt_optval, PAGE_4K_SIZE as usize).is_err() {
+                    // 验证optval的地址是否合法。目前支持的选项的值都是u32
+                    if access_ok(virt_optval, core::mem::size_of::<u32>(), true).is_err() {
                         // 地址空间超出了用户空间的范围，不合法
                         return Err(SystemError::EFAULT);
                     }
 
                     // 验证optlen的地址是否合法
-                    if verify_area(virt_optlen, core::mem::size_of::<u32>()).is_err() {
+                    if access_ok(virt_optlen, core::mem::size_of::<u32>(), true).is_err() {
                         // 地址空间超出了用户空间的范围，不合法
                         return Err(SystemError::EFAULT);
                     }
@@ -496,7 +567,7 @@ impl Syscall {
                 let virt_addr = VirtAddr::new(addr as usize);
                 let security_check = || {
                     // 验证buf的地址是否合法
-                    if verify_area(virt_buf, len).is_err() {
+                    if access_ok(virt_buf, len, true).is_err() {
                         // 地址空间超出了用户空间的范围，不合法
                         return Err(SystemError::EFAULT);
                     }
@@ -607,7 +678,7 @@ impl Syscall {
                 let buf = args[0] as *mut u8;
                 let size = args[1];
                 let security_check = || {
-                    verify_area(VirtAddr::new(buf as usize), size)?;
+                    access_ok(VirtAddr::new(buf as usize), size, true)?;
                     return Ok(());
                 };
                 let r = security_check();
@@ -621,6 +692,9 @@ impl Syscall {
 
             SYS_GETPGID => Self::getpgid(Pid::new(args[0])).map(|pid| pid.into()),
 
+            #[cfg(target_arch = "x86_64")]
+            SYS_GETPGRP => Self::getpgid(Pid::new(0)).map(|pid| pid.into()),
+
             SYS_GETPPID => Self::getppid().map(|pid| pid.into()),
             SYS_FSTAT => {
                 let fd = args[0] as i32;
@@ -675,14 +749,27 @@ impl Syscall {
                 verify_area(child_tid, core::mem::size_of::<i32>())?;
 
                 let mut clone_args = KernelCloneArgs::new();
-                clone_args.flags = CloneFlags::from_bits_truncate(args[0] as u64);
+                // 低8位是子进程退出时发送给父进程的信号，而不是标志位
+                clone_args.flags =
+                    CloneFlags::from_bits_truncate(args[0] as u64 & CLONE_LEGACY_FLAGS);
+                clone_args.exit_signal = legacy_clone_exit_signal(args[0] as u64);
                 clone_args.stack = args[1];
                 clone_args.parent_tid = parent_tid;
                 clone_args.child_tid = child_tid;
                 clone_args.tls = args[4];
+                // clone系统调用通过parent_tid返回pidfd
+                if clone_args.flags.contains(CloneFlags::CLONE_PIDFD) {
+                    clone_args.pidfd = parent_tid;
+                }
                 Self::clone(frame, clone_args)
             }
 
+            SYS_CLONE3 => {
+                let uargs = args[0] as *const PosixCloneArgs;
+                let size = args[1];
+                Self::clone3(frame, uargs, size)
+            }
+
             SYS_FUTEX => {
                 let uaddr = VirtAddr::new(args[0]);
                 let operation = FutexFlag::from_bits(args[1] as u32).ok_or(SystemError::ENOSYS)?;
@@ -831,34 +918,74 @@ impl Syscall {
             }
 
             SYS_SETPGID => {
-                kwarn!("SYS_SETPGID has not yet been implemented");
-                Ok(0)
+                let pid = args[0] as i32;
+                let pgid = args[1] as i32;
+                Self::setpgid(pid, pgid)
             }
 
             SYS_RT_SIGPROCMASK => {
-                kwarn!("SYS_RT_SIGPROCMASK has not yet been implemented");
-                Ok(0)
+                let how = args[0] as i32;
+                let new_set = args[1] as *const SigSet;
+                let old_set = args[2] as *mut SigSet;
+                let sigsetsize = args[3];
+                Self::rt_sigprocmask(how, new_set, old_set, sigsetsize)
+            }
+
+            SYS_RT_SIGPENDING => {
+                let set = args[0] as *mut SigSet;
+                let sigsetsize = args[1];
+                Self::rt_sigpending(set, sigsetsize)
             }
 
             SYS_TKILL => {
-                kwarn!("SYS_TKILL has not yet been implemented");
-                Ok(0)
+                let tid = args[0] as i32;
+                let sig = args[1] as c_int;
+                Self::tkill(tid, sig)
+            }
+
+            SYS_TGKILL => {
+                let tgid = args[0] as i32;
+                let tid = args[1] as i32;
+                let sig = args[2] as c_int;
+                Self::tgkill(tgid, tid, sig)
+            }
+
+            #[cfg(target_arch = "x86_64")]
+            SYS_SIGNALFD => {
+                let fd = args[0] as i32;
+                let mask = args[1] as *const SigSet;
+                let sizemask = args[2];
+                Self::signalfd4(fd, mask, sizemask, 0)
+            }
+
+            SYS_SIGNALFD4 => {
+                let fd = args[0] as i32;
+                let mask = args[1] as *const SigSet;
+                let sizemask = args[2];
+                let flags = args[3] as u32;
+                Self::signalfd4(fd, mask, sizemask, flags)
             }
 
             SYS_SIGALTSTACK => {
-                kwarn!("SYS_SIGALTSTACK has not yet been implemented");
-                Ok(0)
+                let ss = args[0] as *const PosixSigStack;
+                let old_ss = args[1] as *mut PosixSigStack;
+                Self::sigaltstack(ss, old_ss, frame.sp())
             }
 
             SYS_EXIT_GROUP => {
-                kwarn!("SYS_EXIT_GROUP has not yet been implemented");
-                Ok(0)
+                let exit_code = args[0];
+                Self::exit_group(exit_code)
             }
 
             SYS_MADVISE => {
-                // 这个太吵了，总是打印，先注释掉
-                // kwarn!("SYS_MADVISE has not yet been implemented");
-                Ok(0)
+                let addr = args[0];
+                let len = page_align_up(args[1]);
+                if addr & (MMArch::PAGE_SIZE - 1) != 0 {
+                    // The addr argument is not a multiple of the page size
+                    Err(SystemError::EINVAL)
+                } else {
+                    Self::madvise(VirtAddr::new(addr), len, args[2])
+                }
             }
             SYS_GETTID => Self::gettid().map(|tid| tid.into()),
             SYS_GETUID => Self::getuid(),
@@ -884,10 +1011,8 @@ impl Syscall {
                 kwarn!("SYS_SETGID has not yet been implemented");
                 Ok(0)
             }
-            SYS_SETSID => {
-                kwarn!("SYS_SETSID has not yet been implemented");
-                Ok(0)
-            }
+            SYS_SETSID => Self::setsid().map(|sid| sid.into()),
+            SYS_GETSID => Self::getsid(Pid::new(args[0])).map(|sid| sid.into()),
             SYS_GETEUID => Self::geteuid(),
             SYS_GETEGID => Self::getegid(),
             SYS_GETRUSAGE => {
@@ -922,6 +1047,9 @@ impl Syscall {
                 Self::prlimit64(pid, resource, new_limit, old_limit)
             }
 
+            SYS_PRCTL => Self::prctl(args[0], args[1]),
+            SYS_PTRACE => Self::ptrace(args[0], args[1] as i32, args[2], args[3]),
+
             #[cfg(target_arch = "x86_64")]
             SYS_ACCESS => {
                 let pathname = args[0] as *const u8;
@@ -971,8 +1099,11 @@ impl Syscall {
             }
 
             SYS_RSEQ => {
-                kwarn!("SYS_RSEQ has not yet been implemented");
-                Ok(0)
+                let rseq = VirtAddr::new(args[0]);
+                let rseq_len = args[1] as u32;
+                let flags = args[2] as i32;
+                let sig = args[3] as u32;
+                Self::rseq(rseq, rseq_len, flags, sig)
             }
 
             #[cfg(target_arch = "x86_64")]
@@ -1005,17 +1136,32 @@ impl Syscall {
                 Self::getaffinity(pid, set)
             }
 
+            SYS_SCHED_SETAFFINITY => {
+                let pid = args[0] as i32;
+                let size = args[1];
+                let set_vaddr = args[2];
+
+                let user_buffer_reader =
+                    UserBufferReader::new(set_vaddr as *const u8, size, frame.is_from_user())?;
+                let set: &[u8] = user_buffer_reader.read_from_user(0)?;
+
+                Self::setaffinity(pid, set)
+            }
+
             #[cfg(target_arch = "x86_64")]
             SYS_GETRLIMIT => {
                 let resource = args[0];
                 let rlimit = args[1] as *mut RLimit64;
 
-                Self::prlimit64(
-                    ProcessManager::current_pcb().pid(),
-                    resource,
-                    core::ptr::null::<RLimit64>(),
-                    rlimit,
-                )
+                Self::getrlimit(resource, rlimit)
+            }
+
+            #[cfg(target_arch = "x86_64")]
+            SYS_SETRLIMIT => {
+                let resource = args[0];
+                let rlimit = args[1] as *const RLimit64;
+
+                Self::setrlimit(resource, rlimit)
             }
 
             SYS_FADVISE64 => {
@@ -1042,6 +1188,18 @@ impl Syscall {
                 Self::uname(name)
             }
 
+            SYS_SETHOSTNAME => {
+                let name = args[0] as *const u8;
+                let len = args[1];
+                Self::sethostname(name, len)
+            }
+
+            SYS_SETDOMAINNAME => {
+                let name = args[0] as *const u8;
+                let len = args[1];
+                Self::setdomainname(name, len)
+            }
+
             SYS_SHMGET => {
                 let key = ShmKey::new(args[0]);
                 let size = args[1];
@@ -0,0 +1,373 @@
+//! Restartable sequences (rseq)
+//!
+//! 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/rseq.c
+
+use core::{
+    intrinsics::unlikely,
+    mem,
+    sync::atomic::{compiler_fence, Ordering},
+};
+
+use kdepends::memoffset::offset_of;
+use system_error::SystemError;
+
+use crate::{
+    arch::{interrupt::TrapFrame, ipc::signal::Signal},
+    mm::VirtAddr,
+    process::{ProcessControlBlock, ProcessFlags, ProcessManager},
+    smp::core::smp_get_processor_id,
+};
+
+use super::{
+    user_access::{access_ok, UserBufferReader, UserBufferWriter},
+    Syscall,
+};
+
+/// 用户态还没有注册rseq时，cpu_id字段的值
+pub const RSEQ_CPU_ID_UNINITIALIZED: i32 = -1;
+/// 注册rseq失败时，cpu_id字段的值
+pub const RSEQ_CPU_ID_REGISTRATION_FAILED: i32 = -2;
+
+bitflags! {
+    /// sys_rseq的flags参数
+    pub struct RseqFlags: u32 {
+        /// 取消注册
+        const RSEQ_FLAG_UNREGISTER = 1 << 0;
+    }
+
+    /// RseqCs的flags字段，用于指定在哪些事件发生时不需要中止临界区
+    pub struct RseqCsFlags: u32 {
+        const RSEQ_CS_FLAG_NO_RESTART_ON_PREEMPT = 1 << 0;
+        const RSEQ_CS_FLAG_NO_RESTART_ON_SIGNAL = 1 << 1;
+        const RSEQ_CS_FLAG_NO_RESTART_ON_MIGRATE = 1 << 2;
+    }
+
+    /// 自上次返回用户态以来，进程经历的会打断rseq临界区的事件
+    ///
+    /// 每一位与RseqCsFlags中对应的NO_RESTART位相同
+    pub struct RseqEventMask: u32 {
+        const RSEQ_EVENT_PREEMPT = 1 << 0;
+        const RSEQ_EVENT_SIGNAL = 1 << 1;
+        const RSEQ_EVENT_MIGRATE = 1 << 2;
+    }
+}
+
+/// 用户态的rseq结构体
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/rseq.h#62
+#[repr(C, align(32))]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Rseq {
+    /// 进程开始执行rseq临界区时，所在的cpu的id
+    pub cpu_id_start: u32,
+    /// 进程当前所在的cpu的id
+    pub cpu_id: u32,
+    /// 指向当前正在执行的RseqCs的指针
+    pub rseq_cs: u64,
+    pub flags: u32,
+}
+
+/// rseq临界区的描述符
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/include/uapi/linux/rseq.h#46
+#[repr(C, align(32))]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RseqCs {
+    pub version: u32,
+    pub flags: u32,
+    /// 临界区的起始地址
+    pub start_ip: u64,
+    /// 临界区的长度（从start_ip开始）
+    pub post_commit_offset: u64,
+    /// 临界区被打断后，跳转到的地址
+    pub abort_ip: u64,
+}
+
+/// 进程注册到内核的rseq信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RseqRegistration {
+    /// 用户态Rseq结构体的地址
+    pub ptr: VirtAddr,
+    /// 用户态Rseq结构体的长度
+    pub len: u32,
+    /// abort_ip之前的签名
+    pub sig: u32,
+    /// 待处理的事件
+    pub event_mask: RseqEventMask,
+    /// 上一次写入用户态的cpu id
+    pub cpu_id: u32,
+}
+
+impl RseqRegistration {
+    pub fn new(ptr: VirtAddr, len: u32, sig: u32) -> Self {
+        Self {
+            ptr,
+            len,
+            sig,
+            event_mask: RseqEventMask::empty(),
+            cpu_id: RSEQ_CPU_ID_UNINITIALIZED as u32,
+        }
+    }
+
+    /// 把cpu id写入到用户态的Rseq结构体中
+    ///
+    /// cpu_id_start总是先于cpu_id被写入。用户态在临界区开始时读取cpu_id_start作为推测值，
+    /// 并在临界区内通过cpu_id确认，因此只要看到了新的cpu_id，cpu_id_start也一定已经是新的值。
+    ///
+    /// 必须在该进程的地址空间中调用
+    fn write_cpu_id(&self, cpu_id_start: u32, cpu_id: u32) -> Result<(), SystemError> {
+        access_ok(self.ptr, mem::size_of::<[u32; 2]>(), true)?;
+        let mut writer =
+            UserBufferWriter::new(self.ptr.as_ptr::<u32>(), mem::size_of::<[u32; 2]>(), true)?;
+        let fields = writer.buffer::<u32>(0)?;
+        fields[0] = cpu_id_start;
+        compiler_fence(Ordering::SeqCst);
+        fields[1] = cpu_id;
+        return Ok(());
+    }
+
+    /// 把当前cpu的id写入到用户态的Rseq结构体中
+    ///
+    /// 注册时以及每次返回用户态之前更新rseq时都会调用，从而保证cpu_id_start与cpu_id始终相等
+    pub fn update_cpu_id(&mut self) -> Result<(), SystemError> {
+        let cpu_id = smp_get_processor_id().data();
+        self.write_cpu_id(cpu_id, cpu_id)?;
+        self.cpu_id = cpu_id;
+        return Ok(());
+    }
+
+    /// 取消注册时，重置用户态Rseq结构体中的cpu id
+    pub fn reset_cpu_id(&self) -> Result<(), SystemError> {
+        return self.write_cpu_id(0, RSEQ_CPU_ID_UNINITIALIZED as u32);
+    }
+
+    /// 用户态Rseq结构体中rseq_cs字段的地址
+    fn rseq_cs_field(&self) -> VirtAddr {
+        return self.ptr + offset_of!(Rseq, rseq_cs);
+    }
+
+    /// 读取用户态当前正在执行的临界区描述符
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(None)`：当前不在临界区中
+    /// - `Ok(Some(RseqCs))`：合法的临界区描述符
+    /// - `Err(SystemError)`：描述符不可访问，或者内容不合法（包括签名不匹配）
+    fn read_rseq_cs(&self) -> Result<Option<RseqCs>, SystemError> {
+        let field = self.rseq_cs_field();
+        access_ok(field, mem::size_of::<u64>(), false)?;
+        let reader = UserBufferReader::new(field.as_ptr::<u64>(), mem::size_of::<u64>(), true)?;
+        let cs_ptr = *reader.read_one_from_user::<u64>(0)?;
+        if cs_ptr == 0 {
+            return Ok(None);
+        }
+
+        let cs_ptr = VirtAddr::new(cs_ptr as usize);
+        if !cs_ptr.check_aligned(mem::align_of::<RseqCs>()) {
+            return Err(SystemError::EINVAL);
+        }
+        access_ok(cs_ptr, mem::size_of::<RseqCs>(), false)?;
+        let reader =
+            UserBufferReader::new(cs_ptr.as_ptr::<RseqCs>(), mem::size_of::<RseqCs>(), true)?;
+        let cs = *reader.read_one_from_user::<RseqCs>(0)?;
+
+        if cs.version != 0 {
+            return Err(SystemError::EINVAL);
+        }
+        let end_ip = cs
+            .start_ip
+            .checked_add(cs.post_commit_offset)
+            .ok_or(SystemError::EINVAL)?;
+        if !VirtAddr::new(end_ip as usize).check_user()
+            || !VirtAddr::new(cs.abort_ip as usize).check_user()
+        {
+            return Err(SystemError::EINVAL);
+        }
+        // abort_ip不能位于临界区内
+        if cs.abort_ip >= cs.start_ip && cs.abort_ip < end_ip {
+            return Err(SystemError::EINVAL);
+        }
+
+        // abort_ip之前的4个字节必须是注册时的签名
+        let sig_addr = VirtAddr::new(
+            (cs.abort_ip as usize)
+                .checked_sub(mem::size_of::<u32>())
+                .ok_or(SystemError::EINVAL)?,
+        );
+        access_ok(sig_addr, mem::size_of::<u32>(), false)?;
+        let reader = UserBufferReader::new(sig_addr.as_ptr::<u32>(), mem::size_of::<u32>(), true)?;
+        if *reader.read_one_from_user::<u32>(0)? != self.sig {
+            return Err(SystemError::EINVAL);
+        }
+
+        return Ok(Some(cs));
+    }
+
+    /// 清除用户态Rseq结构体中的rseq_cs字段
+    fn clear_rseq_cs(&self) -> Result<(), SystemError> {
+        let field = self.rseq_cs_field();
+        access_ok(field, mem::size_of::<u64>(), true)?;
+        let mut writer = UserBufferWriter::new(field.as_ptr::<u64>(), mem::size_of::<u64>(), true)?;
+        writer.copy_one_to_user(&0u64, 0)?;
+        return Ok(());
+    }
+
+    /// 如果进程在临界区内被打断，那么把返回地址修改为abort_ip
+    fn ip_fixup(&self, frame: &mut TrapFrame) -> Result<(), SystemError> {
+        let cs = match self.read_rseq_cs()? {
+            Some(cs) => cs,
+            None => return Ok(()),
+        };
+
+        let ip = frame.ip() as u64;
+        // 不在临界区内，rseq_cs是上一次执行留下的，清除掉即可
+        if ip < cs.start_ip || ip - cs.start_ip >= cs.post_commit_offset {
+            return self.clear_rseq_cs();
+        }
+
+        // 所有发生过的事件都被设置为不需要中止
+        let no_restart = RseqEventMask::from_bits_truncate(cs.flags);
+        if (self.event_mask - no_restart).is_empty() {
+            return Ok(());
+        }
+
+        self.clear_rseq_cs()?;
+        frame.set_ip(cs.abort_ip as usize);
+        return Ok(());
+    }
+}
+
+/// 进程被切换到cpu上运行时调用
+///
+/// 记录进程被抢占（以及可能被迁移到了其他cpu上），并标记它在返回用户态之前需要处理rseq
+pub fn rseq_preempt(pcb: &ProcessControlBlock) {
+    let mut registration = match *pcb.get_rseq() {
+        Some(registration) => registration,
+        None => return,
+    };
+    registration.event_mask |= RseqEventMask::RSEQ_EVENT_PREEMPT;
+    if registration.cpu_id != smp_get_processor_id().data() {
+        registration.event_mask |= RseqEventMask::RSEQ_EVENT_MIGRATE;
+    }
+    pcb.set_rseq(Some(registration));
+    pcb.flags().insert(ProcessFlags::NEED_RSEQ);
+}
+
+/// 向进程投递信号之前调用，若进程正处于rseq临界区中，则先中止临界区
+///
+/// 这样信号处理函数返回时，会回到abort_ip而不是临界区内
+pub fn rseq_signal_deliver(frame: &mut TrapFrame) {
+    let pcb = ProcessManager::current_pcb();
+    let mut registration = match *pcb.get_rseq() {
+        Some(registration) => registration,
+        None => return,
+    };
+    registration.event_mask |= RseqEventMask::RSEQ_EVENT_SIGNAL;
+    pcb.set_rseq(Some(registration));
+    pcb.flags().insert(ProcessFlags::NEED_RSEQ);
+    rseq_handle_notify_resume(frame);
+}
+
+/// 返回用户态之前调用
+///
+/// 若进程在rseq临界区中被打断，则把返回地址修改为abort_ip，然后把当前cpu的id写入到用户态的Rseq结构体中。
+/// 如果用户态的Rseq结构体或临界区描述符不可访问，或者签名不匹配，则强制向进程发送SIGSEGV。
+/// 此时进程已经无法正确地执行rseq临界区，即使它屏蔽或忽略了SIGSEGV，也会被终止
+pub fn rseq_handle_notify_resume(frame: &mut TrapFrame) {
+    let pcb = ProcessManager::current_pcb();
+    if !pcb.flags().contains(ProcessFlags::NEED_RSEQ) {
+        return;
+    }
+    pcb.flags().remove(ProcessFlags::NEED_RSEQ);
+
+    let mut registration = match *pcb.get_rseq() {
+        Some(registration) => registration,
+        None => return,
+    };
+    let r = registration
+        .ip_fixup(frame)
+        .and_then(|_| registration.update_cpu_id());
+    registration.event_mask = RseqEventMask::empty();
+    pcb.set_rseq(Some(registration));
+
+    if r.is_err() {
+        let _r = Signal::SIGSEGV.force_send(pcb);
+    }
+}
+
+impl Syscall {
+    /// # 注册/取消注册 restartable sequences
+    ///
+    /// ## 参数
+    ///
+    /// - `rseq`：用户态Rseq结构体的地址
+    /// - `rseq_len`：用户态Rseq结构体的长度
+    /// - `flags`：RseqFlags
+    /// - `sig`：abort_ip之前的签名，取消注册时必须与注册时的一致
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(0)`：成功
+    /// - `Err(SystemError::EINVAL)`：参数不合法，或者签名不匹配
+    /// - `Err(SystemError::EBUSY)`：当前线程已经注册过rseq
+    /// - `Err(SystemError::EFAULT)`：rseq不是合法的用户空间地址
+    pub fn rseq(rseq: VirtAddr, rseq_len: u32, flags: i32, sig: u32) -> Result<usize, SystemError> {
+        let flags = RseqFlags::from_bits(flags as u32).ok_or(SystemError::EINVAL)?;
+        let pcb = ProcessManager::current_pcb();
+        let current = *pcb.get_rseq();
+
+        if flags.contains(RseqFlags::RSEQ_FLAG_UNREGISTER) {
+            if flags != RseqFlags::RSEQ_FLAG_UNREGISTER {
+                return Err(SystemError::EINVAL);
+            }
+            let registration = current.ok_or(SystemError::EINVAL)?;
+            if registration.ptr != rseq || registration.len != rseq_len {
+                return Err(SystemError::EINVAL);
+            }
+            if registration.sig != sig {
+                return Err(SystemError::EINVAL);
+            }
+            registration.reset_cpu_id()?;
+            pcb.set_rseq(None);
+            return Ok(0);
+        }
+
+        if unlikely(!flags.is_empty()) {
+            return Err(SystemError::EINVAL);
+        }
+
+        if let Some(registration) = current {
+            // 对同一个地址重复注册时，参数必须与之前的一致
+            if registration.ptr == rseq && (registration.len != rseq_len || registration.sig != sig)
+            {
+                return Err(SystemError::EINVAL);
+            }
+            return Err(SystemError::EBUSY);
+        }
+
+        if rseq_len as usize != mem::size_of::<Rseq>()
+            || !rseq.check_aligned(mem::align_of::<Rseq>())
+        {
+            return Err(SystemError::EINVAL);
+        }
+
+        let mut registration = RseqRegistration::new(rseq, rseq_len, sig);
+        // 整个结构体都必须位于用户空间并且已经映射为可写，否则之后返回用户态时无法更新它
+        access_ok(rseq, rseq_len as usize, true)?;
+        registration.update_cpu_id()?;
+        pcb.set_rseq(Some(registration));
+
+        return Ok(0);
+    }
+}
+
+impl ProcessControlBlock {
+    /// 在fork时，初始化子进程的rseq注册信息
+    ///
+    /// 无论是否与父进程共享地址空间，子进程都从未注册的状态开始：
+    /// - CLONE_THREAD创建的线程：rseq结构体位于每个线程各自的TLS中，新线程需要为自己的结构体注册
+    /// - fork出的进程：需要重新调用sys_rseq注册，之后内核才会更新它的rseq结构体
+    pub fn rseq_fork(&self) {
+        self.set_rseq(None);
+    }
+}
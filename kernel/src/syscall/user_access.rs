@@ -7,10 +7,120 @@ use core::{
 
 use alloc::{string::String, vec::Vec};
 
-use crate::mm::{verify_area, VirtAddr};
+use crate::{
+    arch::MMArch,
+    mm::{ucontext::InnerAddressSpace, verify_area, MemoryManagementArch, VirtAddr},
+    process::ProcessManager,
+};
 
 use super::SystemError;
 
+/// 检查当前进程的用户空间中，指定范围的内存是否可以访问（access_ok）
+///
+/// verify_area只检查地址是否位于用户空间，而这个函数还会检查[addr, addr+len)中的每一个字节
+/// 都被某个映射覆盖（中间不能有空洞），用于在内核不能处理缺页的场景下
+/// （例如进程退出、返回用户态之前）安全地访问用户空间。
+///
+/// 内核写入只读的用户页面会触发缺页（CR0.WP），而内核态的缺页没有修复（fixup）路径，
+/// 因此需要写权限时，还会预先处理范围内写时复制的页面，使得之后内核的写入不会再触发缺页
+///
+/// ## 参数
+///
+/// - `addr`：用户空间的起始地址
+/// - `len`：长度
+/// - `write`：是否需要写权限
+///
+/// ## 错误
+///
+/// - `EFAULT`：地址不合法，或者有一部分没有被映射，或者没有所需的权限
+/// - `ENOMEM`：没有足够的内存来复制写时复制的页面
+pub fn access_ok(addr: VirtAddr, len: usize, write: bool) -> Result<(), SystemError> {
+    verify_area(addr, len).map_err(|_| SystemError::EFAULT)?;
+    if len == 0 {
+        return Ok(());
+    }
+
+    let vm = ProcessManager::current_pcb()
+        .basic()
+        .user_vm()
+        .ok_or(SystemError::EFAULT)?;
+    let guard = vm.read_irqsave();
+    let cow_pages = user_range_cow_pages(&guard, addr, len, write)?;
+    drop(guard);
+    // 只有范围内存在仍然被写保护的页面（写时复制）时，才需要获取写锁
+    if cow_pages.is_empty() {
+        return Ok(());
+    }
+
+    let mut guard = vm.write_irqsave();
+    for page in cow_pages {
+        guard.handle_cow_fault(page)?;
+    }
+    return Ok(());
+}
+
+/// 与[`access_ok`]相同，但是由已经持有地址空间写锁的调用者使用（例如加载ELF文件时）
+///
+/// ## 参数
+///
+/// - `vm`：用户地址空间，之后访问的时候，它必须是当前的地址空间
+/// - `addr`：用户空间的起始地址
+/// - `len`：长度
+/// - `write`：是否需要写权限
+pub fn access_ok_locked(
+    vm: &mut InnerAddressSpace,
+    addr: VirtAddr,
+    len: usize,
+    write: bool,
+) -> Result<(), SystemError> {
+    verify_area(addr, len).map_err(|_| SystemError::EFAULT)?;
+    if len == 0 {
+        return Ok(());
+    }
+
+    for page in user_range_cow_pages(vm, addr, len, write)? {
+        vm.handle_cow_fault(page)?;
+    }
+    return Ok(());
+}
+
+/// 检查[addr, addr+len)是否都被具有所需权限的映射覆盖，并找出其中仍然被写保护的页面
+///
+/// 不需要写权限时，返回空的列表
+fn user_range_cow_pages(
+    vm: &InnerAddressSpace,
+    addr: VirtAddr,
+    len: usize,
+    write: bool,
+) -> Result<Vec<VirtAddr>, SystemError> {
+    let end = addr + len;
+    let mut vaddr = addr;
+    while vaddr < end {
+        let vma = vm.mappings.contains(vaddr).ok_or(SystemError::EFAULT)?;
+        let vma_guard = vma.lock();
+        if write && !vma_guard.flags().has_write() {
+            return Err(SystemError::EFAULT);
+        }
+        vaddr = vma_guard.region().end();
+    }
+    if !write {
+        return Ok(Vec::new());
+    }
+
+    let first_page = VirtAddr::new(addr.data() & MMArch::PAGE_MASK);
+    let cow_pages = (first_page.data()..end.data())
+        .step_by(MMArch::PAGE_SIZE)
+        .map(VirtAddr::new)
+        .filter(|page| {
+            vm.user_mapper
+                .utable
+                .translate(*page)
+                .map_or(false, |(_, flags)| !flags.has_write())
+        })
+        .collect();
+    return Ok(cow_pages);
+}
+
 /// 清空用户空间指定范围内的数据
 ///
 /// ## 参数
@@ -26,7 +136,7 @@ use super::SystemError;
 ///
 /// - `EFAULT`：目标地址不合法
 pub unsafe fn clear_user(dest: VirtAddr, len: usize) -> Result<usize, SystemError> {
-    verify_area(dest, len).map_err(|_| SystemError::EFAULT)?;
+    access_ok(dest, len, true)?;
 
     let p = dest.data() as *mut u8;
     // 清空用户空间的数据
@@ -34,8 +144,21 @@ pub unsafe fn clear_user(dest: VirtAddr, len: usize) -> Result<usize, SystemErro
     return Ok(len);
 }
 
+/// 与[`clear_user`]相同，但是由已经持有地址空间写锁的调用者使用，见[`access_ok_locked`]
+pub unsafe fn clear_user_locked(
+    vm: &mut InnerAddressSpace,
+    dest: VirtAddr,
+    len: usize,
+) -> Result<usize, SystemError> {
+    access_ok_locked(vm, dest, len, true)?;
+
+    let p = dest.data() as *mut u8;
+    p.write_bytes(0, len);
+    return Ok(len);
+}
+
 pub unsafe fn copy_to_user(dest: VirtAddr, src: &[u8]) -> Result<usize, SystemError> {
-    verify_area(dest, src.len()).map_err(|_| SystemError::EFAULT)?;
+    access_ok(dest, src.len(), true)?;
 
     let p = dest.data() as *mut u8;
     // 拷贝数据
@@ -43,9 +166,22 @@ pub unsafe fn copy_to_user(dest: VirtAddr, src: &[u8]) -> Result<usize, SystemEr
     return Ok(src.len());
 }
 
+/// 与[`copy_to_user`]相同，但是由已经持有地址空间写锁的调用者使用，见[`access_ok_locked`]
+pub unsafe fn copy_to_user_locked(
+    vm: &mut InnerAddressSpace,
+    dest: VirtAddr,
+    src: &[u8],
+) -> Result<usize, SystemError> {
+    access_ok_locked(vm, dest, src.len(), true)?;
+
+    let p = dest.data() as *mut u8;
+    p.copy_from_nonoverlapping(src.as_ptr(), src.len());
+    return Ok(src.len());
+}
+
 /// 从用户空间拷贝数据到内核空间
 pub unsafe fn copy_from_user(dst: &mut [u8], src: VirtAddr) -> Result<usize, SystemError> {
-    verify_area(src, dst.len()).map_err(|_| SystemError::EFAULT)?;
+    access_ok(src, dst.len(), false)?;
 
     let src: &[u8] = core::slice::from_raw_parts(src.data() as *const u8, dst.len());
     // 拷贝数据
@@ -160,12 +296,13 @@ impl<'a> UserBufferReader<'a> {
     ///
     /// @param addr 用户空间指针
     /// @param len 缓冲区的字节长度
-    /// @param frm_user 代表是否要检验地址来自用户空间
+    /// @param frm_user 代表是否要检验地址来自用户空间。为true时，还会检查整个缓冲区都已经被映射
     /// @return 构造成功返回UserbufferReader实例，否则返回错误码
     ///
+    /// 调用者不能持有当前进程的地址空间的锁
     pub fn new<U>(addr: *const U, len: usize, from_user: bool) -> Result<Self, SystemError> {
-        if from_user && verify_area(VirtAddr::new(addr as usize), len).is_err() {
-            return Err(SystemError::EFAULT);
+        if from_user {
+            access_ok(VirtAddr::new(addr as usize), len, false)?;
         }
         return Ok(Self {
             buffer: unsafe { core::slice::from_raw_parts(addr as *const u8, len) },
@@ -269,11 +406,14 @@ impl<'a> UserBufferWriter<'a> {
     ///
     /// @param addr 用户空间指针
     /// @param len 缓冲区的字节长度
+    /// @param from_user 代表是否要检验地址来自用户空间。为true时，还会检查整个缓冲区都已经被映射并且可写，
+    /// 并预先复制其中写时复制的页面
     /// @return 构造成功返回UserbufferWriter实例，否则返回错误码
     ///
+    /// 调用者不能持有当前进程的地址空间的锁
     pub fn new<U>(addr: *mut U, len: usize, from_user: bool) -> Result<Self, SystemError> {
-        if from_user && verify_area(VirtAddr::new(addr as usize), len).is_err() {
-            return Err(SystemError::EFAULT);
+        if from_user {
+            access_ok(VirtAddr::new(addr as usize), len, true)?;
         }
         return Ok(Self {
             buffer: unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) },
@@ -21,10 +21,19 @@ pub const ACTHZ: u32 = sh_div(CLOCK_TICK_RATE, LATCH, 8);
 pub const TICK_NESC: u32 = (NSEC_PER_SEC + (HZ as u32) / 2) / HZ as u32;
 //TODO 编写测试，保证始终跳动间隔与现实一致（两种时钟源进行对拍）
 pub const NSEC_PER_JIFFY: u32 = (((NSEC_PER_SEC as u64) << 8) / ACTHZ as u64) as u32;
+/// 用户态看到的时钟节拍率（sysconf(_SC_CLK_TCK)），与内核的HZ无关
+pub const USER_HZ: u64 = 100;
 pub const fn sh_div(nom: u32, den: u32, lsh: u32) -> u32 {
     (((nom) / (den)) << (lsh)) + ((((nom) % (den)) << (lsh)) + (den) / 2) / (den)
 }
 
+/// 将内核的jiffies转换为用户态的时钟节拍数（以USER_HZ为单位）
+///
+/// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/kernel/time/time.c#627
+pub const fn jiffies_to_clock_t(jiffies: u64) -> u64 {
+    jiffies * USER_HZ / HZ
+}
+
 #[derive(Debug)]
 pub struct ClocksourceJiffies(SpinLock<InnerJiffies>);
 
@@ -0,0 +1,117 @@
+/**
+ * 测量fork的延迟:
+ * 1. 分别在映射了0、1MB、64MB匿名内存(已经被写入)的情况下, fork出立即退出的子进程
+ * 2. 统计父进程中fork调用耗时的中位数与p99
+ * 3. 回归检查: fork采用写时复制, 不拷贝页面的内容, 因此每增加一个页面带来的额外耗时应当远小于拷贝一个页面的耗时。
+ *    64MB的情况下, 平均到每个页面的额外耗时超过PER_PAGE_LIMIT_NS时, 认为出现了性能回退
+ *
+ * 用法: bench_fork [迭代次数], 迭代次数默认为ITERATIONS
+ */
+
+#include <errno.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+#define ITERATIONS 100
+#define MB (1024UL * 1024UL)
+/* 拷贝一个4K页面需要数百纳秒, 只复制页表项则远小于这个值 */
+#define PER_PAGE_LIMIT_NS 200
+
+static int failed = 0;
+
+static long long now_ns(void)
+{
+    struct timespec ts;
+    clock_gettime(CLOCK_MONOTONIC, &ts);
+    return ts.tv_sec * 1000000000LL + ts.tv_nsec;
+}
+
+static int cmp_ll(const void *a, const void *b)
+{
+    long long x = *(const long long *)a, y = *(const long long *)b;
+    return (x > y) - (x < y);
+}
+
+/* 在映射了size字节匿名内存的情况下测量fork的延迟, 返回中位数(纳秒) */
+static long long bench(size_t size, int iterations)
+{
+    char *mem = NULL;
+    if (size > 0)
+    {
+        mem = mmap(NULL, size, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+        if (mem == MAP_FAILED)
+        {
+            printf("bench_fork: mmap %zu MB failed (errno: %s)\n", size / MB, strerror(errno));
+            failed = 1;
+            return -1;
+        }
+        /* 写入每一个页面, 使它们都被映射 */
+        memset(mem, 1, size);
+    }
+
+    long long *samples = malloc(sizeof(long long) * iterations);
+    int n = 0;
+    for (int i = 0; i < iterations; i++)
+    {
+        long long start = now_ns();
+        pid_t pid = fork();
+        if (pid == 0)
+            _exit(0);
+        long long end = now_ns();
+        if (pid < 0)
+        {
+            printf("bench_fork: fork failed (errno: %s)\n", strerror(errno));
+            failed = 1;
+            break;
+        }
+        waitpid(pid, NULL, 0);
+        samples[n++] = end - start;
+    }
+
+    long long median = -1;
+    if (n > 0)
+    {
+        qsort(samples, n, sizeof(long long), cmp_ll);
+        median = samples[n / 2];
+        long long p99 = samples[(n * 99) / 100];
+        printf("bench_fork: %2zu MB mapped: median %lld us, p99 %lld us (%d iterations)\n",
+               size / MB, median / 1000, p99 / 1000, n);
+    }
+
+    free(samples);
+    if (mem != NULL)
+        munmap(mem, size);
+    return median;
+}
+
+int main(int argc, char *argv[])
+{
+    int iterations = argc > 1 ? atoi(argv[1]) : ITERATIONS;
+    if (iterations <= 0)
+        iterations = ITERATIONS;
+
+    long long base = bench(0, iterations);
+    bench(1 * MB, iterations);
+    long long large = bench(64 * MB, iterations);
+
+    if (base >= 0 && large >= 0)
+    {
+        long pages = 64 * MB / sysconf(_SC_PAGESIZE);
+        long long per_page = (large - base) / pages;
+        printf("bench_fork: %lld ns per mapped page (limit %d ns)\n", per_page > 0 ? per_page : 0,
+               PER_PAGE_LIMIT_NS);
+        if (per_page > PER_PAGE_LIMIT_NS)
+        {
+            printf("bench_fork: fork latency scales with the size of the address space\n");
+            failed = 1;
+        }
+    }
+
+    printf("bench_fork: %s\n", failed ? "failed" : "ok");
+    return failed;
+}
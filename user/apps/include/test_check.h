@@ -0,0 +1,42 @@
+/**
+ * 测试程序共用的检查函数
+ *
+ * 包含这个头文件之前需要定义测试程序的名字, 它会出现在输出的每一行中, 例如:
+ *
+ *     #define TEST_NAME "test_xxx"
+ *     #include "test_check.h"
+ *
+ * check()在条件不成立时输出失败信息并记录失败(fork出的子进程可以用failed作为退出码),
+ * 测试结束时用test_report()输出结果, 它的返回值作为main的返回值
+ */
+
+#ifndef TEST_CHECK_H
+#define TEST_CHECK_H
+
+#include <errno.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+#ifndef TEST_NAME
+#error "TEST_NAME must be defined before including test_check.h"
+#endif
+
+static int failed = 0;
+
+static inline void check(int cond, const char *what)
+{
+    if (!cond)
+    {
+        printf(TEST_NAME ": [pid %d] %s failed (errno: %s)\n", getpid(), what, strerror(errno));
+        failed = 1;
+    }
+}
+
+static inline int test_report(void)
+{
+    printf(TEST_NAME ": %s\n", failed ? "failed" : "ok");
+    return failed;
+}
+
+#endif
@@ -0,0 +1,91 @@
+/**
+ * 测试brk以及fork之后的堆:
+ * 1. brk(0)以及不合法的地址返回当前的program break, 不会失败
+ * 2. 扩展堆之后可以读写新的区域
+ * 3. fork之后子进程继承父进程的堆内容和program break, 子进程扩展、写入自己的堆不影响父进程
+ * 4. 父进程在子进程退出之后仍然可以继续扩展自己的堆
+ * 5. 堆不能扩展到已有的映射上, 此时program break保持不变
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_brk_fork"
+#include "test_check.h"
+
+#ifndef MAP_FIXED_NOREPLACE
+#define MAP_FIXED_NOREPLACE 0x100000
+#endif
+
+static long page_size;
+
+/* 直接调用brk系统调用, 返回新的program break */
+static char *brk_(void *addr)
+{
+    return (char *)syscall(SYS_brk, addr);
+}
+
+int main()
+{
+    page_size = sysconf(_SC_PAGESIZE);
+
+    char *start = brk_(0);
+    check(start != NULL && start != (char *)-1, "brk(0)");
+    check(brk_((void *)1) == start, "brk with invalid address returns current break");
+
+    /* 对齐到页, 方便后面的检查 */
+    char *base = (char *)(((uintptr_t)start + page_size - 1) & ~(uintptr_t)(page_size - 1));
+    char *end = base + page_size * 2;
+    check(brk_(end) == end, "grow heap");
+    memset(base, 'h', page_size * 2);
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        if (brk_(0) != end || base[0] != 'h' || base[page_size * 2 - 1] != 'h')
+            _exit(1);
+        char *child_end = end + page_size * 4;
+        if (brk_(child_end) != child_end)
+            _exit(2);
+        memset(end, 'c', page_size * 4);
+        memset(base, 'c', page_size * 2);
+        /* 缩小之后再扩展 */
+        if (brk_(end) != end || brk_(child_end) != child_end)
+            _exit(3);
+        _exit(0);
+    }
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child extends its own heap");
+
+    check(brk_(0) == end, "parent break unchanged by child");
+    check(base[0] == 'h' && base[page_size * 2 - 1] == 'h', "parent heap unchanged by child");
+
+    char *parent_end = end + page_size;
+    check(brk_(parent_end) == parent_end, "parent extends heap after fork");
+    memset(end, 'p', page_size);
+    check(end[0] == 'p', "parent writes its new heap page");
+
+    /* 在堆之后放置一个映射, 堆不能扩展到它上面 */
+    char *blocker = mmap(parent_end + page_size, page_size, PROT_READ | PROT_WRITE,
+                         MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED_NOREPLACE, -1, 0);
+    check(blocker == parent_end + page_size, "mmap right after the heap");
+    if (blocker == parent_end + page_size)
+    {
+        blocker[0] = 'b';
+        check(brk_(blocker + page_size) == parent_end, "heap cannot grow over a mapping");
+        check(blocker[0] == 'b', "mapping after the heap is kept");
+        munmap(blocker, page_size);
+    }
+
+    check(brk_(start) == start, "shrink heap");
+
+    return test_report();
+}
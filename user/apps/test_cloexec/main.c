@@ -0,0 +1,97 @@
+/**
+ * 测试execve时对FD_CLOEXEC的处理:
+ * 1. 设置了FD_CLOEXEC的文件描述符, 在execve之后被关闭
+ * 2. 没有设置FD_CLOEXEC的文件描述符, 在execve之后仍然可用
+ * 3. dup出来的文件描述符不继承FD_CLOEXEC
+ */
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_FILE "/bin/test_cloexec"
+
+/* execve之后, 由新的进程映像检查文件描述符的状态 */
+static int check_after_exec(int cloexec_fd, int keep_fd)
+{
+    int failed = 0;
+
+    errno = 0;
+    if (fcntl(cloexec_fd, F_GETFD) != -1 || errno != EBADF)
+    {
+        printf("fd %d with FD_CLOEXEC is still open after execve\n", cloexec_fd);
+        failed = 1;
+    }
+
+    if (fcntl(keep_fd, F_GETFD) != 0)
+    {
+        printf("fd %d without FD_CLOEXEC is not usable after execve\n", keep_fd);
+        failed = 1;
+    }
+
+    return failed;
+}
+
+int main(int argc, char *argv[])
+{
+    if (argc == 4 && strcmp(argv[1], "child") == 0)
+        return check_after_exec(atoi(argv[2]), atoi(argv[3]));
+
+    int cloexec_fd = open(TEST_FILE, O_RDONLY);
+    if (cloexec_fd < 0)
+    {
+        perror("open");
+        return 1;
+    }
+    if (fcntl(cloexec_fd, F_SETFD, FD_CLOEXEC) != 0 || fcntl(cloexec_fd, F_GETFD) != FD_CLOEXEC)
+    {
+        printf("failed to set FD_CLOEXEC\n");
+        return 1;
+    }
+
+    // dup出来的文件描述符不带有FD_CLOEXEC, 在execve之后仍然可用
+    int keep_fd = dup(cloexec_fd);
+    if (keep_fd < 0 || fcntl(keep_fd, F_GETFD) != 0)
+    {
+        printf("dup should clear FD_CLOEXEC\n");
+        return 1;
+    }
+
+    pid_t pid = fork();
+    if (pid < 0)
+    {
+        perror("fork");
+        return 1;
+    }
+
+    if (pid == 0)
+    {
+        // fork之后, 子进程的文件描述符保留FD_CLOEXEC标志
+        if (fcntl(cloexec_fd, F_GETFD) != FD_CLOEXEC)
+        {
+            printf("FD_CLOEXEC is not preserved across fork\n");
+            _exit(1);
+        }
+
+        char arg1[16], arg2[16];
+        snprintf(arg1, sizeof(arg1), "%d", cloexec_fd);
+        snprintf(arg2, sizeof(arg2), "%d", keep_fd);
+        execl(TEST_FILE, "test_cloexec", "child", arg1, arg2, NULL);
+        perror("execl");
+        _exit(1);
+    }
+
+    int status;
+    if (waitpid(pid, &status, 0) != pid || !WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("test_cloexec: failed\n");
+        return 1;
+    }
+
+    printf("test_cloexec: ok\n");
+    return 0;
+}
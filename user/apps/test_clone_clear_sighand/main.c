@@ -0,0 +1,121 @@
+/**
+ * 测试clone3的CLONE_CLEAR_SIGHAND标志:
+ * 1. 父进程中设置了处理函数的信号, 在子进程中恢复为SIG_DFL, 并且sa_flags被清空
+ * 2. 父进程中被忽略的信号, 在子进程中仍然被忽略
+ * 3. 父进程的信号处理函数不受影响
+ * 4. 没有使用CLONE_CLEAR_SIGHAND时, 子进程继承父进程的处理函数
+ * 5. CLONE_CLEAR_SIGHAND与CLONE_SIGHAND同时使用时返回EINVAL
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_clone_clear_sighand"
+#include "test_check.h"
+
+#ifndef SYS_clone3
+#define SYS_clone3 435
+#endif
+
+#ifndef CLONE_CLEAR_SIGHAND
+#define CLONE_CLEAR_SIGHAND 0x100000000ULL
+#endif
+
+/* 与内核中的struct clone_args的第一个版本相同 */
+struct clone3_args
+{
+    uint64_t flags;
+    uint64_t pidfd;
+    uint64_t child_tid;
+    uint64_t parent_tid;
+    uint64_t exit_signal;
+    uint64_t stack;
+    uint64_t stack_size;
+    uint64_t tls;
+};
+
+static void usr1_handler(int sig)
+{
+    (void)sig;
+}
+
+static long raw_clone3(uint64_t flags)
+{
+    struct clone3_args args;
+    memset(&args, 0, sizeof(args));
+    args.flags = flags;
+    args.exit_signal = SIGCHLD;
+    return syscall(SYS_clone3, &args, sizeof(args));
+}
+
+static void get_action(int sig, struct sigaction *sa)
+{
+    memset(sa, 0, sizeof(*sa));
+    sigaction(sig, NULL, sa);
+}
+
+static void wait_child(long pid, const char *what)
+{
+    int status = 0;
+    check(pid > 0 && waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+              WEXITSTATUS(status) == 0,
+          what);
+}
+
+int main()
+{
+    struct sigaction sa;
+    memset(&sa, 0, sizeof(sa));
+    sa.sa_handler = usr1_handler;
+    sa.sa_flags = SA_RESTART;
+    sigemptyset(&sa.sa_mask);
+    sigaddset(&sa.sa_mask, SIGTERM);
+    check(sigaction(SIGUSR1, &sa, NULL) == 0, "install SIGUSR1 handler");
+    check(signal(SIGUSR2, SIG_IGN) != SIG_ERR, "ignore SIGUSR2");
+
+    struct sigaction old;
+    long pid = raw_clone3(CLONE_CLEAR_SIGHAND);
+    if (pid == 0)
+    {
+        get_action(SIGUSR1, &old);
+        check(old.sa_handler == SIG_DFL, "handled signal reset to SIG_DFL");
+        check(!(old.sa_flags & SA_RESTART), "sa_flags cleared");
+        check(!sigismember(&old.sa_mask, SIGTERM), "sa_mask cleared");
+        get_action(SIGUSR2, &old);
+        check(old.sa_handler == SIG_IGN, "ignored signal stays ignored");
+        _exit(failed);
+    }
+    wait_child(pid, "CLONE_CLEAR_SIGHAND child");
+
+    get_action(SIGUSR1, &old);
+    check(old.sa_handler == usr1_handler, "parent handler unchanged");
+    get_action(SIGUSR2, &old);
+    check(old.sa_handler == SIG_IGN, "parent ignore unchanged");
+
+    pid = raw_clone3(0);
+    if (pid == 0)
+    {
+        get_action(SIGUSR1, &old);
+        check(old.sa_handler == usr1_handler, "handler inherited without CLONE_CLEAR_SIGHAND");
+        _exit(failed);
+    }
+    wait_child(pid, "clone3 child");
+
+    errno = 0;
+    pid = raw_clone3(CLONE_CLEAR_SIGHAND | CLONE_SIGHAND | CLONE_VM);
+    if (pid == 0)
+        syscall(SYS_exit, 0);
+    if (pid > 0)
+        waitpid(pid, NULL, 0);
+    check(pid == -1 && errno == EINVAL, "CLONE_CLEAR_SIGHAND | CLONE_SIGHAND returns EINVAL");
+
+    return test_report();
+}
@@ -0,0 +1,71 @@
+/**
+ * 测试clone的flags参数中低8位指定的退出信号:
+ * 1. 指定SIGUSR1时, 子进程退出后父进程收到SIGUSR1, 而不是SIGCHLD
+ * 2. 指定0时, 子进程退出后父进程不会收到任何信号
+ * 3. fork(即SIGCHLD)时, 父进程收到SIGCHLD
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_clone_exit_signal"
+#include "test_check.h"
+
+static volatile sig_atomic_t got_usr1 = 0;
+static volatile sig_atomic_t got_chld = 0;
+
+static void handler(int sig)
+{
+    if (sig == SIGUSR1)
+        got_usr1++;
+    else if (sig == SIGCHLD)
+        got_chld++;
+}
+
+/* 以exit_signal作为退出信号创建子进程, 子进程立即退出, 然后回收它 */
+static void clone_and_reap(int exit_signal, const char *what)
+{
+    got_usr1 = 0;
+    got_chld = 0;
+
+    pid_t pid = syscall(SYS_clone, (unsigned long)exit_signal, 0, NULL, NULL, 0);
+    if (pid == 0)
+        _exit(0);
+    check(pid > 0, what);
+
+    /* 退出信号不是SIGCHLD的子进程需要使用__WALL才能被等待 */
+    int status = 0;
+    check(waitpid(pid, &status, __WALL) == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0,
+          what);
+    /* 给信号的递送留出时间 */
+    usleep(10000);
+}
+
+int main()
+{
+    struct sigaction sa;
+    memset(&sa, 0, sizeof(sa));
+    sa.sa_handler = handler;
+    sigemptyset(&sa.sa_mask);
+    check(sigaction(SIGUSR1, &sa, NULL) == 0, "sigaction SIGUSR1");
+    check(sigaction(SIGCHLD, &sa, NULL) == 0, "sigaction SIGCHLD");
+
+    clone_and_reap(SIGUSR1, "clone with SIGUSR1");
+    check(got_usr1 == 1, "parent received SIGUSR1");
+    check(got_chld == 0, "parent did not receive SIGCHLD");
+
+    clone_and_reap(0, "clone with exit signal 0");
+    check(got_usr1 == 0 && got_chld == 0, "no signal for exit signal 0");
+
+    clone_and_reap(SIGCHLD, "clone with SIGCHLD");
+    check(got_chld == 1, "parent received SIGCHLD");
+    check(got_usr1 == 0, "parent did not receive SIGUSR1");
+
+    return test_report();
+}
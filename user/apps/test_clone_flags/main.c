@@ -0,0 +1,137 @@
+/**
+ * 测试clone对非法标志位组合的检查:
+ * 1. CLONE_SIGHAND 而没有 CLONE_VM 时返回EINVAL
+ * 2. CLONE_THREAD 而没有 CLONE_SIGHAND 时返回EINVAL
+ * 3. CLONE_NEWNS | CLONE_FS 时返回EINVAL
+ * 4. CLONE_THREAD 与 CLONE_NEWPID 同时使用时返回EINVAL
+ * 5. 合法的标志位组合仍然可以正常创建子进程
+ * 6. clone3的flags中包含退出信号(CSIGNAL)或者CLONE_DETACHED时返回EINVAL,
+ *    退出信号只能通过exit_signal字段指定
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static long raw_clone(unsigned long flags)
+{
+    return syscall(SYS_clone, flags, 0, 0, 0, 0);
+}
+
+#ifndef SYS_clone3
+#define SYS_clone3 435
+#endif
+
+/* 与内核中的struct clone_args的第一个版本相同 */
+struct clone3_args
+{
+    uint64_t flags;
+    uint64_t pidfd;
+    uint64_t child_tid;
+    uint64_t parent_tid;
+    uint64_t exit_signal;
+    uint64_t stack;
+    uint64_t stack_size;
+    uint64_t tls;
+};
+
+static long raw_clone3(uint64_t flags, uint64_t exit_signal)
+{
+    struct clone3_args args;
+    memset(&args, 0, sizeof(args));
+    args.flags = flags;
+    args.exit_signal = exit_signal;
+    return syscall(SYS_clone3, &args, sizeof(args));
+}
+
+/* 等待子进程正常退出 */
+static int wait_child(const char *name, long pid)
+{
+    int status;
+    if (pid < 0 || waitpid(pid, &status, 0) != pid || !WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("%s with valid flags failed\n", name);
+        return 1;
+    }
+    return 0;
+}
+
+static int expect_clone3_einval(const char *name, uint64_t flags, uint64_t exit_signal)
+{
+    errno = 0;
+    long ret = raw_clone3(flags, exit_signal);
+    if (ret == 0)
+        _exit(0);
+    if (ret > 0)
+    {
+        waitpid(ret, NULL, 0);
+        printf("clone3(%s) should fail, but created pid %ld\n", name, ret);
+        return 1;
+    }
+    if (errno != EINVAL)
+    {
+        printf("clone3(%s) failed with errno %d, expected EINVAL\n", name, errno);
+        return 1;
+    }
+    return 0;
+}
+
+static int expect_einval(const char *name, unsigned long flags)
+{
+    errno = 0;
+    long ret = raw_clone(flags);
+    if (ret == 0)
+        _exit(0);
+    if (ret > 0)
+    {
+        waitpid(ret, NULL, 0);
+        printf("clone(%s) should fail, but created pid %ld\n", name, ret);
+        return 1;
+    }
+    if (errno != EINVAL)
+    {
+        printf("clone(%s) failed with errno %d, expected EINVAL\n", name, errno);
+        return 1;
+    }
+    return 0;
+}
+
+int main()
+{
+    int failed = 0;
+
+    failed |= expect_einval("CLONE_SIGHAND", CLONE_SIGHAND | SIGCHLD);
+    failed |= expect_einval("CLONE_THREAD | CLONE_VM", CLONE_THREAD | CLONE_VM);
+    failed |= expect_einval("CLONE_NEWNS | CLONE_FS", CLONE_NEWNS | CLONE_FS | SIGCHLD);
+    failed |= expect_einval("CLONE_THREAD | CLONE_NEWPID",
+                            CLONE_THREAD | CLONE_SIGHAND | CLONE_VM | CLONE_NEWPID);
+
+    long pid = raw_clone(SIGCHLD);
+    if (pid == 0)
+        _exit(0);
+    failed |= wait_child("clone", pid);
+
+    failed |= expect_clone3_einval("SIGCHLD in flags", SIGCHLD, 0);
+    failed |= expect_clone3_einval("CLONE_DETACHED", 0x00400000, SIGCHLD);
+
+    pid = raw_clone3(0, SIGCHLD);
+    if (pid == 0)
+        _exit(0);
+    failed |= wait_child("clone3", pid);
+
+    if (failed)
+    {
+        printf("test_clone_flags: failed\n");
+        return 1;
+    }
+
+    printf("test_clone_flags: ok\n");
+    return 0;
+}
@@ -0,0 +1,95 @@
+/**
+ * 测试CLONE_IO与I/O优先级:
+ * 1. ioprio_set/ioprio_get可以设置与读取当前进程的I/O优先级, 非法的优先级与which返回EINVAL
+ * 2. 没有使用CLONE_IO的子进程继承父进程的I/O优先级, 但是修改它不影响父进程
+ * 3. 使用CLONE_IO的子进程与父进程共享I/O上下文, 子进程修改I/O优先级之后父进程也能看到
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_clone_io"
+#include "test_check.h"
+
+#define IOPRIO_CLASS_SHIFT 13
+#define IOPRIO_PRIO_VALUE(class, data) (((class) << IOPRIO_CLASS_SHIFT) | (data))
+#define IOPRIO_CLASS_BE 2
+#define IOPRIO_CLASS_IDLE 3
+#define IOPRIO_WHO_PROCESS 1
+
+/* 与fork相同, 但是可以指定额外的clone标志 */
+static pid_t clone_fork(unsigned long flags)
+{
+    return syscall(SYS_clone, flags | SIGCHLD, 0, NULL, NULL, 0);
+}
+
+static int ioprio_set(int which, int who, int ioprio)
+{
+    return syscall(SYS_ioprio_set, which, who, ioprio);
+}
+
+static int ioprio_get(int which, int who)
+{
+    return syscall(SYS_ioprio_get, which, who);
+}
+
+static void wait_child(pid_t pid, const char *what)
+{
+    int status = 0;
+    check(pid > 0 && waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+              WEXITSTATUS(status) == 0,
+          what);
+}
+
+int main()
+{
+    int be4 = IOPRIO_PRIO_VALUE(IOPRIO_CLASS_BE, 4);
+    int be7 = IOPRIO_PRIO_VALUE(IOPRIO_CLASS_BE, 7);
+    int idle = IOPRIO_PRIO_VALUE(IOPRIO_CLASS_IDLE, 0);
+
+    check(ioprio_get(IOPRIO_WHO_PROCESS, 0) == 0, "default ioprio");
+
+    errno = 0;
+    check(ioprio_set(IOPRIO_WHO_PROCESS, 0, IOPRIO_PRIO_VALUE(4, 0)) == -1 && errno == EINVAL,
+          "reject invalid class");
+    errno = 0;
+    check(ioprio_set(IOPRIO_WHO_PROCESS, 0, IOPRIO_PRIO_VALUE(IOPRIO_CLASS_BE, 8)) == -1 &&
+              errno == EINVAL,
+          "reject invalid level");
+    errno = 0;
+    check(ioprio_get(5, 0) == -1 && errno == EINVAL, "reject invalid which");
+
+    check(ioprio_set(IOPRIO_WHO_PROCESS, 0, be4) == 0, "set ioprio");
+    check(ioprio_get(IOPRIO_WHO_PROCESS, getpid()) == be4, "get ioprio by pid");
+
+    /* 没有CLONE_IO: 继承优先级, 但是拥有自己的I/O上下文 */
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        check(ioprio_get(IOPRIO_WHO_PROCESS, 0) == be4, "child inherits ioprio");
+        check(ioprio_set(IOPRIO_WHO_PROCESS, 0, idle) == 0, "child sets own ioprio");
+        _exit(failed);
+    }
+    wait_child(pid, "fork child");
+    check(ioprio_get(IOPRIO_WHO_PROCESS, 0) == be4, "parent ioprio unchanged by fork child");
+
+    /* CLONE_IO: 共享I/O上下文 */
+    pid = clone_fork(CLONE_IO);
+    if (pid == 0)
+    {
+        check(ioprio_get(IOPRIO_WHO_PROCESS, 0) == be4, "CLONE_IO child sees parent ioprio");
+        check(ioprio_set(IOPRIO_WHO_PROCESS, 0, be7) == 0, "CLONE_IO child sets ioprio");
+        _exit(failed);
+    }
+    wait_child(pid, "CLONE_IO child");
+    check(ioprio_get(IOPRIO_WHO_PROCESS, 0) == be7, "parent sees ioprio set by CLONE_IO child");
+
+    return test_report();
+}
@@ -0,0 +1,97 @@
+/**
+ * 测试clone的CLONE_CHILD_SETTID与CLONE_PARENT_SETTID:
+ * 1. 与父进程共享地址空间(CLONE_VM)时, clone返回之前ptid已经被写入子进程的pid,
+ *    子进程开始运行时ctid已经被写入它自己的pid, 父进程也能看到这两个值
+ * 2. 不共享地址空间时, ctid只被写入子进程的地址空间, 父进程中的ctid保持不变
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_clone_settid"
+#include "test_check.h"
+
+#define STACK_SIZE (64 * 1024)
+
+static volatile pid_t child_tid;
+static volatile pid_t parent_tid;
+/* 由CLONE_VM的子进程写入: 它开始运行时看到的ctid以及它自己的pid */
+static volatile pid_t seen_ctid;
+static volatile pid_t seen_pid;
+
+/* 与父进程共享地址空间, 只使用系统调用 */
+static int shared_vm_child(void *arg)
+{
+    (void)arg;
+    seen_ctid = child_tid;
+    seen_pid = syscall(SYS_getpid);
+    return 0;
+}
+
+/* 不共享地址空间, 通过退出码报告自己看到的ctid是否正确 */
+static int private_vm_child(void *arg)
+{
+    (void)arg;
+    return child_tid == getpid() ? 0 : 1;
+}
+
+static void test_shared_vm(char *stack)
+{
+    child_tid = 0;
+    parent_tid = 0;
+    seen_ctid = -1;
+    seen_pid = -1;
+
+    pid_t pid = clone(shared_vm_child, stack + STACK_SIZE,
+                      CLONE_VM | CLONE_CHILD_SETTID | CLONE_PARENT_SETTID | SIGCHLD, NULL,
+                      &parent_tid, NULL, &child_tid);
+    check(pid > 0, "clone(CLONE_VM | CLONE_CHILD_SETTID | CLONE_PARENT_SETTID)");
+    if (pid <= 0)
+        return;
+    check(parent_tid == pid, "ptid is written before clone returns");
+
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "shared vm child exits normally");
+    check(seen_pid == pid, "child pid");
+    check(seen_ctid == pid, "ctid is written before the child runs");
+    check(child_tid == pid, "parent sees ctid written by the child");
+}
+
+static void test_private_vm(char *stack)
+{
+    child_tid = 0;
+
+    pid_t pid = clone(private_vm_child, stack + STACK_SIZE, CLONE_CHILD_SETTID | SIGCHLD, NULL,
+                      NULL, NULL, &child_tid);
+    check(pid > 0, "clone(CLONE_CHILD_SETTID)");
+    if (pid <= 0)
+        return;
+
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child sees ctid in its address space");
+    check(child_tid == 0, "ctid in the parent address space is unchanged");
+}
+
+int main()
+{
+    char *stack = malloc(STACK_SIZE);
+    check(stack != NULL, "malloc stack");
+    if (stack == NULL)
+        return 1;
+
+    test_shared_vm(stack);
+    test_private_vm(stack);
+
+    free(stack);
+    return test_report();
+}
@@ -0,0 +1,134 @@
+/**
+ * 测试clone的CLONE_SETTLS:
+ * 1. 使用CLONE_SETTLS创建的子进程, tls寄存器(x86_64的fs base, riscv64的tp)是clone传入的值,
+ *    并且可以通过它读取到tls块中的数据
+ * 2. 没有CLONE_SETTLS时, 子进程继承父进程的tls寄存器
+ * 3. 子进程设置tls之后, 父进程的tls寄存器不受影响
+ *
+ * 子进程与父进程共享地址空间(CLONE_VM), 它把读取到的值写入全局变量, 由父进程检查。
+ * 子进程的tls不是libc的线程块, 因此子进程只能直接使用系统调用, 不能调用libc的函数
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_clone_settls"
+#include "test_check.h"
+
+#define STACK_SIZE (64 * 1024)
+#define TLS_MAGIC 0x746c73746c73UL
+
+#if defined(__x86_64__)
+#define ARCH_GET_FS 0x1003
+#endif
+
+/* 子进程的tls块: 第一个字是指向自己的指针(与x86_64的tls约定相同), 第二个字是标记 */
+static uintptr_t tls_block[4] __attribute__((aligned(64)));
+
+/* 由子进程写入 */
+static volatile uintptr_t child_tls;
+static volatile uintptr_t child_tls_word;
+
+/* 读取当前的tls寄存器, 不经过libc */
+static uintptr_t read_tls(void)
+{
+#if defined(__x86_64__)
+    uintptr_t base = 0;
+    long ret;
+    __asm__ __volatile__("syscall"
+                         : "=a"(ret)
+                         : "a"(SYS_arch_prctl), "D"(ARCH_GET_FS), "S"(&base)
+                         : "rcx", "r11", "memory");
+    return ret == 0 ? base : 0;
+#elif defined(__riscv)
+    uintptr_t tp;
+    __asm__ __volatile__("mv %0, tp" : "=r"(tp));
+    return tp;
+#else
+#error "unsupported architecture"
+#endif
+}
+
+/* 通过tls寄存器读取tls块中的标记 */
+static uintptr_t read_tls_word(void)
+{
+#if defined(__x86_64__)
+    uintptr_t self;
+    __asm__ __volatile__("mov %%fs:0, %0" : "=r"(self));
+    return ((uintptr_t *)self)[1];
+#elif defined(__riscv)
+    return ((uintptr_t *)read_tls())[1];
+#endif
+}
+
+static void raw_exit(int code)
+{
+    syscall(SYS_exit, code);
+}
+
+static int settls_child(void *arg)
+{
+    (void)arg;
+    child_tls = read_tls();
+    child_tls_word = read_tls_word();
+    raw_exit(0);
+    return 0;
+}
+
+static int inherit_child(void *arg)
+{
+    (void)arg;
+    child_tls = read_tls();
+    raw_exit(0);
+    return 0;
+}
+
+static pid_t run_child(int (*fn)(void *), int flags, void *tls, char *stack)
+{
+    child_tls = 0;
+    child_tls_word = 0;
+    pid_t pid = clone(fn, stack + STACK_SIZE, CLONE_VM | SIGCHLD | flags, NULL, NULL, tls, NULL);
+    check(pid > 0, "clone");
+    if (pid <= 0)
+        return pid;
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child exits normally");
+    return pid;
+}
+
+int main()
+{
+    char *stack = malloc(STACK_SIZE);
+    check(stack != NULL, "malloc stack");
+    if (stack == NULL)
+        return 1;
+
+    uintptr_t parent_tls = read_tls();
+    check(parent_tls != 0, "read parent tls");
+
+    tls_block[0] = (uintptr_t)tls_block;
+    tls_block[1] = TLS_MAGIC;
+    if (run_child(settls_child, CLONE_SETTLS, tls_block, stack) > 0)
+    {
+        check(child_tls == (uintptr_t)tls_block, "child tls is the clone tls argument");
+        check(child_tls_word == TLS_MAGIC, "child reads its tls block through the tls register");
+    }
+
+    if (run_child(inherit_child, 0, NULL, stack) > 0)
+        check(child_tls == parent_tls, "child without CLONE_SETTLS inherits the parent tls");
+
+    check(read_tls() == parent_tls, "parent tls is unchanged");
+
+    free(stack);
+    return test_report();
+}
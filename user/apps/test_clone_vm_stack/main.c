@@ -0,0 +1,103 @@
+/**
+ * 测试使用CLONE_VM创建的线程的用户栈:
+ * 1. 两个使用CLONE_VM创建的线程分别运行在clone指定的栈上, 而不是父进程的栈上
+ * 2. 两个线程同时运行时, 各自栈上的数据不会被对方破坏
+ * 3. 使用CLONE_VM而没有指定栈(并且不是vfork)时, clone返回EINVAL(linux允许这样做, 由调用者保证栈不被同时使用)
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_clone_vm_stack"
+#include "test_check.h"
+
+#define STACK_SIZE (64 * 1024)
+#define NR_THREADS 2
+#define ROUNDS 100000
+
+/* 由线程写入, 父进程在线程退出之后检查 */
+static volatile unsigned long local_addr[NR_THREADS];
+static volatile int corrupted[NR_THREADS];
+/* 两个线程都开始运行之后才开始检查, 确保它们同时使用各自的栈 */
+static volatile int started = 0;
+
+static int thread_main(void *arg)
+{
+    int id = (int)(long)arg;
+    volatile unsigned long buf[64];
+    local_addr[id] = (unsigned long)buf;
+
+    __atomic_add_fetch(&started, 1, __ATOMIC_SEQ_CST);
+    while (__atomic_load_n(&started, __ATOMIC_SEQ_CST) < NR_THREADS)
+        ;
+
+    /* 反复写入并检查栈上的数据, 若两个线程使用同一个栈, 数据会被对方覆盖 */
+    for (int round = 0; round < ROUNDS; round++)
+    {
+        for (int i = 0; i < 64; i++)
+            buf[i] = ((unsigned long)id << 32) | (unsigned long)(round + i);
+        for (int i = 0; i < 64; i++)
+        {
+            if (buf[i] != (((unsigned long)id << 32) | (unsigned long)(round + i)))
+                corrupted[id] = 1;
+        }
+    }
+    return 0;
+}
+
+int main()
+{
+    char *stacks[NR_THREADS];
+    pid_t pids[NR_THREADS];
+    int parent_local = 0;
+
+    for (int i = 0; i < NR_THREADS; i++)
+    {
+        stacks[i] = malloc(STACK_SIZE);
+        check(stacks[i] != NULL, "malloc stack");
+        /* 栈向下增长, 传入栈的最高地址 */
+        pids[i] = clone(thread_main, stacks[i] + STACK_SIZE,
+                        CLONE_VM | CLONE_FS | CLONE_FILES | SIGCHLD, (void *)(long)i);
+        check(pids[i] > 0, "clone with stack");
+    }
+
+    for (int i = 0; i < NR_THREADS; i++)
+    {
+        int status = 0;
+        check(waitpid(pids[i], &status, 0) == pids[i] && WIFEXITED(status) &&
+                  WEXITSTATUS(status) == 0,
+              "thread exit status");
+    }
+
+    for (int i = 0; i < NR_THREADS; i++)
+    {
+        unsigned long addr = local_addr[i];
+        unsigned long lo = (unsigned long)stacks[i], hi = lo + STACK_SIZE;
+        check(lo <= addr && addr < hi, "thread runs on its own stack");
+        check(addr != (unsigned long)&parent_local, "thread does not use parent's stack");
+        check(!corrupted[i], "stack data not corrupted by the other thread");
+    }
+    check(local_addr[0] != local_addr[1], "threads use different stacks");
+
+    /* CLONE_VM而没有指定栈 */
+    errno = 0;
+    long ret = syscall(SYS_clone, CLONE_VM | SIGCHLD, 0, NULL, NULL, 0);
+    if (ret == 0)
+        syscall(SYS_exit, 0);
+    if (ret > 0)
+        waitpid(ret, NULL, 0);
+    check(ret == -1 && errno == EINVAL, "CLONE_VM without a stack returns EINVAL");
+
+    for (int i = 0; i < NR_THREADS; i++)
+        free(stacks[i]);
+
+    return test_report();
+}
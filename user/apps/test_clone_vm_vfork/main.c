@@ -0,0 +1,130 @@
+/**
+ * 测试子进程的标志位(/proc/<pid>/stat的第9个字段):
+ * 1. 使用CLONE_VM创建的子进程带有VFORK标志(与父进程共享地址空间), 并且不带有KTHREAD标志
+ * 2. fork出的子进程不带有VFORK标志
+ * 3. 父进程自己不带有VFORK标志
+ *
+ * 标志位的值是DragonOS的ProcessFlags, 与linux的PF_*不同
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_clone_vm_vfork"
+#include "test_check.h"
+
+#define PF_KTHREAD (1UL << 0)
+#define PF_VFORK (1UL << 2)
+
+#define STACK_SIZE (64 * 1024)
+
+static int sync_pipe[2];
+
+/* 读取进程的标志位, 失败时返回-1 */
+static long long read_flags(pid_t pid)
+{
+    char path[64];
+    char buf[1024];
+
+    snprintf(path, sizeof(path), "/proc/%d/stat", pid);
+    FILE *f = fopen(path, "r");
+    if (f == NULL)
+        return -1;
+    size_t n = fread(buf, 1, sizeof(buf) - 1, f);
+    fclose(f);
+    buf[n] = '\0';
+
+    /* 进程名中可能含有空格, 从最后一个')'之后开始解析, 它后面是第3个字段 */
+    char *p = strrchr(buf, ')');
+    if (p == NULL)
+        return -1;
+    p++;
+    for (int field = 3; field <= 9; field++)
+    {
+        while (*p == ' ')
+            p++;
+        if (*p == '\0')
+            return -1;
+        if (field == 9)
+        {
+            long long value = -1;
+            sscanf(p, "%lld", &value);
+            return value;
+        }
+        while (*p != ' ' && *p != '\0')
+            p++;
+    }
+    return -1;
+}
+
+/* 子进程阻塞在管道上, 直到父进程读取完它的标志位; 与父进程共享地址空间时只使用系统调用 */
+static int child_main(void *arg)
+{
+    (void)arg;
+    char c;
+    read(sync_pipe[0], &c, 1);
+    _exit(0);
+}
+
+static void release_and_reap(pid_t pid)
+{
+    write(sync_pipe[1], "g", 1);
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child exits normally");
+}
+
+static void test_clone_vm(void)
+{
+    char *stack = malloc(STACK_SIZE);
+    check(stack != NULL, "malloc stack");
+    if (stack == NULL)
+        return;
+
+    pid_t pid = clone(child_main, stack + STACK_SIZE, CLONE_VM | SIGCHLD, NULL);
+    check(pid > 0, "clone(CLONE_VM)");
+    if (pid > 0)
+    {
+        long long flags = read_flags(pid);
+        check(flags >= 0, "read flags of the CLONE_VM child");
+        check(flags >= 0 && (flags & PF_VFORK), "CLONE_VM child has VFORK");
+        check(flags >= 0 && !(flags & PF_KTHREAD), "CLONE_VM child is not a kthread");
+        release_and_reap(pid);
+    }
+    free(stack);
+}
+
+static void test_fork(void)
+{
+    pid_t pid = fork();
+    if (pid == 0)
+        child_main(NULL);
+    check(pid > 0, "fork");
+    if (pid > 0)
+    {
+        long long flags = read_flags(pid);
+        check(flags >= 0, "read flags of the forked child");
+        check(flags >= 0 && !(flags & PF_VFORK), "forked child does not have VFORK");
+        release_and_reap(pid);
+    }
+}
+
+int main()
+{
+    check(pipe(sync_pipe) == 0, "pipe");
+
+    long long flags = read_flags(getpid());
+    check(flags >= 0 && !(flags & PF_VFORK), "parent does not have VFORK");
+
+    test_clone_vm();
+    test_fork();
+
+    return test_report();
+}
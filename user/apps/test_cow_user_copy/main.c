@@ -0,0 +1,110 @@
+/**
+ * 测试内核向用户页面写入(系统调用的输出缓冲区):
+ * 1. read到mprotect(PROT_READ)的缓冲区, 返回EFAULT, 而不是使内核崩溃
+ * 2. read到字符串常量(只读的代码/数据段), 返回EFAULT
+ * 3. read到包含未映射地址的缓冲区, 返回EFAULT
+ * 4. fork之后, 父进程read到与子进程共享的(写时复制)缓冲区, 读取成功, 子进程看到的仍然是fork时的内容
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_cow_user_copy"
+#include "test_check.h"
+
+static long page_size;
+static int fds[2];
+
+/* 向管道写入数据之后, 从管道读取到buf中 */
+static ssize_t read_into(void *buf, size_t len)
+{
+    write(fds[1], "data", 4);
+    errno = 0;
+    ssize_t r = read(fds[0], buf, len);
+    if (r < 0)
+    {
+        /* 读取失败时数据可能还留在管道中, 把它读走(管道是非阻塞的) */
+        char drain[4];
+        read(fds[0], drain, sizeof(drain));
+    }
+    return r;
+}
+
+static void test_readonly(void)
+{
+    char *buf = mmap(NULL, page_size, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(buf != MAP_FAILED, "mmap");
+    if (buf == MAP_FAILED)
+        return;
+    buf[0] = 'o';
+    check(mprotect(buf, page_size, PROT_READ) == 0, "mprotect(PROT_READ)");
+    check(read_into(buf, 4) == -1 && errno == EFAULT, "read into a read-only buffer");
+    check(buf[0] == 'o', "read-only buffer is unchanged");
+    munmap(buf, page_size);
+
+    check(read_into((void *)"string literal", 4) == -1 && errno == EFAULT,
+          "read into a string literal");
+}
+
+static void test_hole(void)
+{
+    char *buf = mmap(NULL, page_size * 2, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1,
+                     0);
+    check(buf != MAP_FAILED, "mmap");
+    if (buf == MAP_FAILED)
+        return;
+    munmap(buf + page_size, page_size);
+    check(read_into(buf + page_size - 2, 4) == -1 && errno == EFAULT,
+          "read into a buffer crossing an unmapped page");
+    munmap(buf, page_size);
+}
+
+static void test_cow(void)
+{
+    char *buf = mmap(NULL, page_size, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(buf != MAP_FAILED, "mmap");
+    if (buf == MAP_FAILED)
+        return;
+    memset(buf, 'o', 4);
+
+    /* 子进程保持页面共享, 直到父进程读取完成 */
+    int sync[2];
+    check(pipe(sync) == 0, "pipe");
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        char c;
+        close(sync[1]);
+        read(sync[0], &c, 1);
+        _exit(memcmp(buf, "oooo", 4) == 0 ? 0 : 1);
+    }
+    close(sync[0]);
+
+    check(read_into(buf, 4) == 4, "read into a COW buffer");
+    check(memcmp(buf, "data", 4) == 0, "parent sees the data read");
+
+    write(sync[1], "g", 1);
+    close(sync[1]);
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child still sees the content at fork");
+    munmap(buf, page_size);
+}
+
+int main()
+{
+    page_size = sysconf(_SC_PAGESIZE);
+    check(pipe2(fds, O_NONBLOCK) == 0, "pipe2");
+
+    test_readonly();
+    test_hole();
+    test_cow();
+
+    return test_report();
+}
@@ -0,0 +1,77 @@
+/**
+ * 测试内核根据pid查找进程:
+ * 1. fork出的子进程在退出前能够通过pid被找到(kill(pid, 0), getpgid, getsid, /proc/<pid>)
+ * 2. 子进程被回收之后, 再通过pid查找会返回ESRCH
+ */
+
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <stdio.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static int lookup_ok(pid_t pid)
+{
+    char path[64];
+    snprintf(path, sizeof(path), "/proc/%d/status", pid);
+    int fd = open(path, O_RDONLY);
+    if (fd < 0)
+        return 0;
+    close(fd);
+
+    return kill(pid, 0) == 0 && getpgid(pid) == getpgid(0) && getsid(pid) == getsid(0);
+}
+
+int main()
+{
+    int failed = 0;
+
+    pid_t pid = fork();
+    if (pid < 0)
+    {
+        perror("fork");
+        return 1;
+    }
+    if (pid == 0)
+    {
+        pause();
+        _exit(0);
+    }
+
+    if (!lookup_ok(pid))
+    {
+        printf("failed to look up the child %d\n", pid);
+        failed = 1;
+    }
+
+    kill(pid, SIGKILL);
+    int status;
+    if (waitpid(pid, &status, 0) != pid)
+    {
+        perror("waitpid");
+        return 1;
+    }
+
+    errno = 0;
+    if (kill(pid, 0) != -1 || errno != ESRCH)
+    {
+        printf("the reaped child %d can still be signaled\n", pid);
+        failed = 1;
+    }
+    errno = 0;
+    if (getpgid(pid) != -1 || errno != ESRCH)
+    {
+        printf("getpgid of the reaped child %d should fail with ESRCH\n", pid);
+        failed = 1;
+    }
+
+    if (failed)
+    {
+        printf("test_find_process: failed\n");
+        return 1;
+    }
+
+    printf("test_find_process: ok\n");
+    return 0;
+}
@@ -0,0 +1,74 @@
+/**
+ * 测试进程遍历(通过sysinfo返回的进程数量):
+ * 1. fork之后, 进程数量增加1
+ * 2. 创建线程不会增加进程数量
+ * 3. 子进程被回收之后, 进程数量恢复
+ */
+
+#include <errno.h>
+#include <pthread.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/sysinfo.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_for_each_process"
+#include "test_check.h"
+
+static int nr_procs(void)
+{
+    struct sysinfo info;
+    if (sysinfo(&info) != 0)
+        return -1;
+    return info.procs;
+}
+
+static int thread_pipe[2];
+
+static void *thread_fn(void *arg)
+{
+    char c;
+    (void)arg;
+    read(thread_pipe[0], &c, 1);
+    return NULL;
+}
+
+int main()
+{
+    int before = nr_procs();
+    check(before > 0, "sysinfo procs");
+
+    int pipefd[2];
+    check(pipe(pipefd) == 0, "pipe");
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        char c;
+        close(pipefd[1]);
+        /* 等待父进程统计完毕之后再退出 */
+        read(pipefd[0], &c, 1);
+        _exit(0);
+    }
+    close(pipefd[0]);
+    check(pid > 0, "fork");
+
+    int after_fork = nr_procs();
+    check(after_fork == before + 1, "one more process after fork");
+
+    check(pipe(thread_pipe) == 0, "pipe for thread");
+    pthread_t thread;
+    check(pthread_create(&thread, NULL, thread_fn, NULL) == 0, "pthread_create");
+    check(nr_procs() == after_fork, "threads are not counted as processes");
+    write(thread_pipe[1], "x", 1);
+    pthread_join(thread, NULL);
+
+    write(pipefd[1], "x", 1);
+    close(pipefd[1]);
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid && WIFEXITED(status), "wait child");
+    check(nr_procs() == before, "process count restored after reaping");
+
+    return test_report();
+}
@@ -0,0 +1,114 @@
+/**
+ * 测试进程退出时释放资源:
+ * 1. 子进程退出之后, 它持有的管道写端被关闭, 父进程读到EOF
+ * 2. 反复fork出映射并写入了匿名内存、打开了文件的子进程并回收它们之后, 空闲内存没有明显减少
+ *    (每个子进程的地址空间、文件描述符表以及内核栈都应该在退出时被释放)
+ */
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_fork_exit"
+#include "test_check.h"
+
+#define ROUNDS 1000
+#define WARMUP_ROUNDS 50
+/* 每个子进程映射并写入的匿名内存的大小 */
+#define CHILD_MAP_SIZE (64 * 1024)
+/* 允许的空闲内存减少量(kB)。若每个子进程泄露一个内核栈, 减少量会远大于这个值 */
+#define LEAK_LIMIT_KB (8 * 1024)
+
+/* 从/proc/meminfo中读取MemFree, 单位为kB */
+static long mem_free_kb(void)
+{
+    char line[128];
+    long value = -1;
+    FILE *f = fopen("/proc/meminfo", "r");
+    if (f == NULL)
+        return -1;
+    while (fgets(line, sizeof(line), f) != NULL)
+    {
+        if (sscanf(line, "MemFree: %ld", &value) == 1)
+            break;
+    }
+    fclose(f);
+    return value;
+}
+
+/* 子进程: 映射并写入匿名内存, 打开一些文件, 然后不做任何清理直接退出 */
+static void child_main(void)
+{
+    char *mem = mmap(NULL, CHILD_MAP_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS,
+                     -1, 0);
+    if (mem == MAP_FAILED)
+        _exit(1);
+    memset(mem, 1, CHILD_MAP_SIZE);
+    int fds[2];
+    if (pipe(fds) != 0)
+        _exit(1);
+    if (open("/proc/meminfo", O_RDONLY) < 0)
+        _exit(1);
+    _exit(0);
+}
+
+static void fork_and_reap(int rounds)
+{
+    for (int i = 0; i < rounds; i++)
+    {
+        pid_t pid = fork();
+        if (pid == 0)
+            child_main();
+        int status = 0;
+        if (pid < 0 || waitpid(pid, &status, 0) != pid || !WIFEXITED(status) ||
+            WEXITSTATUS(status) != 0)
+        {
+            check(0, "fork and reap child");
+            return;
+        }
+    }
+}
+
+int main()
+{
+    /* 子进程退出时关闭它持有的管道写端 */
+    int fds[2];
+    check(pipe(fds) == 0, "pipe");
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        close(fds[0]);
+        /* 不关闭写端, 直接退出 */
+        _exit(0);
+    }
+    check(pid > 0, "fork");
+    close(fds[1]);
+    char buf;
+    check(read(fds[0], &buf, 1) == 0, "read EOF after the writer exited");
+    close(fds[0]);
+    check(waitpid(pid, NULL, 0) == pid, "waitpid");
+
+    /* 预热, 使得内核中的各种缓存已经被分配 */
+    fork_and_reap(WARMUP_ROUNDS);
+
+    long before = mem_free_kb();
+    check(before >= 0, "read MemFree");
+    fork_and_reap(ROUNDS);
+    long after = mem_free_kb();
+    check(after >= 0, "read MemFree");
+
+    if (before >= 0 && after >= 0)
+    {
+        long leaked = before - after;
+        printf("test_fork_exit: MemFree %ld kB -> %ld kB after %d children\n", before, after,
+               ROUNDS);
+        check(leaked < LEAK_LIMIT_KB, "memory is released after children exit");
+    }
+
+    return test_report();
+}
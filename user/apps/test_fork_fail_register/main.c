@@ -0,0 +1,77 @@
+/**
+ * 测试向procfs注册进程失败时fork的错误处理(通过/proc/fail_register进行故障注入):
+ * 1. 注册失败时fork返回ENOMEM, 内核不会panic, 也不会留下子进程
+ * 2. 故障注入的次数用完之后, fork恢复正常, 新进程在/proc中可见
+ */
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_fork_fail_register"
+#include "test_check.h"
+
+#define FAIL_REGISTER "/proc/fail_register"
+
+static int set_fail_count(const char *count)
+{
+    int fd = open(FAIL_REGISTER, O_WRONLY);
+    if (fd < 0)
+        return -1;
+    ssize_t n = write(fd, count, strlen(count));
+    close(fd);
+    return n == (ssize_t)strlen(count) ? 0 : -1;
+}
+
+static int read_fail_count(void)
+{
+    char buf[32] = {0};
+    int fd = open(FAIL_REGISTER, O_RDONLY);
+    if (fd < 0)
+        return -1;
+    ssize_t n = read(fd, buf, sizeof(buf) - 1);
+    close(fd);
+    if (n <= 0)
+        return -1;
+    int count = -1;
+    sscanf(buf, "%d", &count);
+    return count;
+}
+
+int main()
+{
+    check(set_fail_count("1\n") == 0, "arm fault injection");
+    check(read_fail_count() == 1, "read fault injection count");
+
+    errno = 0;
+    pid_t pid = fork();
+    if (pid == 0)
+        _exit(0);
+    check(pid == -1 && errno == ENOMEM, "fork fails with ENOMEM");
+    errno = 0;
+    check(waitpid(-1, NULL, WNOHANG) == -1 && errno == ECHILD, "no child left behind");
+    check(read_fail_count() == 0, "fault injection consumed");
+
+    pid = fork();
+    if (pid == 0)
+    {
+        char path[64];
+        struct stat st;
+        snprintf(path, sizeof(path), "/proc/%d", getpid());
+        _exit(stat(path, &st) == 0 ? 0 : 1);
+    }
+    int status = 0;
+    check(pid > 0 && waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+              WEXITSTATUS(status) == 0,
+          "fork after fault injection");
+
+    /* 非法的输入被拒绝 */
+    errno = 0;
+    check(set_fail_count("abc") == -1 && errno == EINVAL, "reject invalid count");
+
+    return test_report();
+}
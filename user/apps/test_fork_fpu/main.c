@@ -0,0 +1,79 @@
+/**
+ * 测试fork之后子进程的浮点寄存器状态:
+ * 1. 子进程继承父进程在fork时的浮点寄存器(舍入模式与xmm寄存器)
+ * 2. 子进程在execve之前使用浮点寄存器并多次被调度, 计算结果保持正确,
+ *    并且不会影响父进程的浮点寄存器
+ */
+
+#include <fenv.h>
+#include <sched.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_fork_fpu"
+#include "test_check.h"
+
+#define YIELD_TIMES 100
+
+#ifdef __x86_64__
+static void set_xmm8(uint64_t value)
+{
+    asm volatile("movq %0, %%xmm8" : : "r"(value) : "xmm8");
+}
+
+static uint64_t get_xmm8(void)
+{
+    uint64_t value;
+    asm volatile("movq %%xmm8, %0" : "=r"(value));
+    return value;
+}
+#endif
+
+/* 在多次让出cpu的过程中进行浮点运算, 返回1表示结果正确 */
+static int compute(double seed)
+{
+    volatile double sum = 0;
+    for (int i = 1; i <= YIELD_TIMES; i++)
+    {
+        sum += seed / i;
+        sched_yield();
+    }
+    volatile double expected = 0;
+    for (int i = 1; i <= YIELD_TIMES; i++)
+        expected += seed / i;
+    return sum == expected;
+}
+
+int main()
+{
+    fesetround(FE_UPWARD);
+#ifdef __x86_64__
+    set_xmm8(0x1122334455667788ULL);
+#endif
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+#ifdef __x86_64__
+        check(get_xmm8() == 0x1122334455667788ULL, "inherit xmm8");
+#endif
+        check(fegetround() == FE_UPWARD, "inherit rounding mode");
+
+        fesetround(FE_TOWARDZERO);
+        check(compute(3.0), "use fpu before exec");
+        check(fegetround() == FE_TOWARDZERO, "keep rounding mode");
+        _exit(failed);
+    }
+
+    check(compute(7.0), "parent use fpu");
+    check(fegetround() == FE_UPWARD, "parent rounding mode unchanged");
+
+    int status = 0;
+    check(pid > 0 && waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+              WEXITSTATUS(status) == 0,
+          "child exit status");
+
+    return test_report();
+}
@@ -0,0 +1,109 @@
+/**
+ * fork顺序的压力测试:
+ * 多个工作进程(分布在不同的cpu上)同时反复fork, 检查新进程第一次运行时看到的状态是完整的:
+ * 1. 子进程的getppid()是创建它的进程
+ * 2. 子进程第一次运行时, 它自己的/proc/<pid>/stat已经存在, 并且其中的pid与ppid正确
+ * 3. fork返回之后, 父进程立即就能看到子进程的/proc/<pid>目录, 并能向它发送信号
+ * 4. fork返回之后立即杀死子进程, 子进程一定会被SIGKILL杀死
+ */
+
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_fork_order"
+#include "test_check.h"
+
+#define NR_WORKERS 4
+#define NR_FORKS 200
+
+/* 读取/proc/<pid>/stat中的pid与ppid */
+static int read_stat(pid_t pid, int *stat_pid, int *stat_ppid)
+{
+    char path[64], buf[512];
+    snprintf(path, sizeof(path), "/proc/%d/stat", pid);
+    int fd = open(path, O_RDONLY);
+    if (fd < 0)
+        return -1;
+    ssize_t n = read(fd, buf, sizeof(buf) - 1);
+    close(fd);
+    if (n <= 0)
+        return -1;
+    buf[n] = '\0';
+    /* 进程名可能包含空格与括号, 因此从最后一个')'之后开始解析 */
+    char *end = strrchr(buf, ')');
+    if (sscanf(buf, "%d", stat_pid) != 1 || end == NULL)
+        return -1;
+    char state;
+    return sscanf(end + 1, " %c %d", &state, stat_ppid) == 2 ? 0 : -1;
+}
+
+static void worker(void)
+{
+    pid_t self = getpid();
+    for (int i = 0; i < NR_FORKS && !failed; i++)
+    {
+        pid_t pid = fork();
+        if (pid == 0)
+        {
+            int stat_pid = 0, stat_ppid = 0;
+            int ok = getppid() == self && read_stat(getpid(), &stat_pid, &stat_ppid) == 0 &&
+                     stat_pid == getpid() && stat_ppid == self;
+            _exit(ok ? 0 : 1);
+        }
+        check(pid > 0, "fork");
+        if (pid < 0)
+            break;
+
+        char path[64];
+        snprintf(path, sizeof(path), "/proc/%d", pid);
+        /* 子进程被回收之前, 它的procfs目录一直存在 */
+        check(access(path, F_OK) == 0, "child procfs dir exists right after fork");
+        check(kill(pid, 0) == 0, "signal child right after fork");
+
+        int status = 0;
+        check(waitpid(pid, &status, 0) == pid, "waitpid");
+        check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child sees complete state");
+
+        /* 子进程可能还没有第一次运行就收到了SIGKILL */
+        pid = fork();
+        if (pid == 0)
+        {
+            for (;;)
+                pause();
+        }
+        check(pid > 0 && kill(pid, SIGKILL) == 0, "kill child right after fork");
+        check(waitpid(pid, &status, 0) == pid && WIFSIGNALED(status) &&
+                  WTERMSIG(status) == SIGKILL,
+              "child killed before first run");
+    }
+}
+
+int main()
+{
+    pid_t workers[NR_WORKERS];
+    for (int i = 0; i < NR_WORKERS; i++)
+    {
+        workers[i] = fork();
+        if (workers[i] == 0)
+        {
+            worker();
+            _exit(failed);
+        }
+        check(workers[i] > 0, "fork worker");
+    }
+
+    for (int i = 0; i < NR_WORKERS; i++)
+    {
+        int status = 0;
+        check(workers[i] > 0 && waitpid(workers[i], &status, 0) == workers[i] &&
+                  WIFEXITED(status) && WEXITSTATUS(status) == 0,
+              "worker exit status");
+    }
+
+    return test_report();
+}
@@ -0,0 +1,135 @@
+/**
+ * 测试fork速率限制(/sys/kernel/fork_rate_limit, 默认为0, 即不限制):
+ * 1. 设置限制之后, 短时间内大量fork时, fork最终返回EAGAIN, 而不是耗尽资源导致内核崩溃
+ * 2. 达到限制时, 仍然可以创建线程(CLONE_THREAD不受限制)
+ * 3. 等待滑动窗口过去之后, fork恢复正常
+ * 4. 测试结束后恢复原来的限制
+ */
+
+#include <errno.h>
+#include <pthread.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define LIMIT_PATH "/sys/kernel/fork_rate_limit"
+/* 测试时使用的限制 */
+#define TEST_LIMIT 64
+/* 尝试fork的最大次数, 需要大于TEST_LIMIT */
+#define MAX_ATTEMPTS 4096
+
+static int reap_children(void)
+{
+    int nr = 0;
+    while (waitpid(-1, NULL, 0) > 0)
+        nr++;
+    return nr;
+}
+
+/* 读取当前的限制, 失败时返回-1 */
+static long read_limit(void)
+{
+    FILE *f = fopen(LIMIT_PATH, "r");
+    if (f == NULL)
+        return -1;
+    long limit = -1;
+    if (fscanf(f, "%ld", &limit) != 1)
+        limit = -1;
+    fclose(f);
+    return limit;
+}
+
+static int write_limit(long limit)
+{
+    FILE *f = fopen(LIMIT_PATH, "w");
+    if (f == NULL)
+        return -1;
+    int r = fprintf(f, "%ld\n", limit);
+    if (fclose(f) != 0)
+        r = -1;
+    return r < 0 ? -1 : 0;
+}
+
+static void *thread_main(void *arg)
+{
+    return arg;
+}
+
+int main()
+{
+    int failed = 0;
+    int forked = 0;
+    int got_eagain = 0;
+
+    long orig_limit = read_limit();
+    if (orig_limit < 0 || write_limit(TEST_LIMIT) != 0)
+    {
+        printf("test_fork_rate: failed to set %s: %s\n", LIMIT_PATH, strerror(errno));
+        return 1;
+    }
+
+    for (int i = 0; i < MAX_ATTEMPTS; i++)
+    {
+        pid_t pid = fork();
+        if (pid == 0)
+            _exit(0);
+        if (pid < 0)
+        {
+            if (errno == EAGAIN)
+                got_eagain = 1;
+            else
+                printf("fork failed with errno %d, expected EAGAIN\n", errno);
+            break;
+        }
+        forked++;
+    }
+
+    if (!got_eagain)
+    {
+        printf("fork did not return EAGAIN after %d forks\n", forked);
+        failed = 1;
+    }
+
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, thread_main, NULL) != 0 || pthread_join(thread, NULL) != 0)
+    {
+        printf("creating a thread is limited by the fork rate\n");
+        failed = 1;
+    }
+
+    if (reap_children() != forked)
+    {
+        printf("failed to reap all %d children\n", forked);
+        failed = 1;
+    }
+
+    // 等待滑动窗口过去
+    sleep(2);
+
+    pid_t pid = fork();
+    if (pid == 0)
+        _exit(0);
+    int status;
+    if (pid < 0 || waitpid(pid, &status, 0) != pid || !WIFEXITED(status))
+    {
+        printf("fork does not recover after the rate window\n");
+        failed = 1;
+    }
+
+    if (write_limit(orig_limit) != 0)
+    {
+        printf("failed to restore %s\n", LIMIT_PATH);
+        failed = 1;
+    }
+
+    if (failed)
+    {
+        printf("test_fork_rate: failed\n");
+        return 1;
+    }
+
+    printf("test_fork_rate: ok (%d forks before EAGAIN)\n", forked);
+    return 0;
+}
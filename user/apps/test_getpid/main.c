@@ -0,0 +1,95 @@
+/**
+ * 测试getpid、getppid、gettid在进程与线程中的返回值:
+ * 1. fork出的子进程拥有新的pid和tid, 且其ppid为父进程的pid
+ * 2. 同一进程中的线程共享pid和ppid, 但tid各不相同
+ */
+
+#include <pthread.h>
+#include <stdio.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static pid_t my_gettid(void)
+{
+    return (pid_t)syscall(SYS_gettid);
+}
+
+struct ids
+{
+    pid_t pid;
+    pid_t ppid;
+    pid_t tid;
+};
+
+static void *thread_func(void *arg)
+{
+    struct ids *ids = (struct ids *)arg;
+    ids->pid = getpid();
+    ids->ppid = getppid();
+    ids->tid = my_gettid();
+    return NULL;
+}
+
+int main()
+{
+    pid_t pid = getpid();
+    pid_t ppid = getppid();
+    pid_t tid = my_gettid();
+    int failed = 0;
+
+    if (pid != tid)
+    {
+        printf("main thread: pid %d != tid %d\n", pid, tid);
+        failed = 1;
+    }
+
+    pid_t child = fork();
+    if (child < 0)
+    {
+        perror("fork");
+        return 1;
+    }
+
+    if (child == 0)
+    {
+        if (getppid() != pid || getpid() == pid || getpid() != my_gettid())
+        {
+            printf("child process: pid %d, ppid %d, tid %d\n", getpid(), getppid(), my_gettid());
+            _exit(1);
+        }
+        _exit(0);
+    }
+
+    int status;
+    if (waitpid(child, &status, 0) != child || !WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("child process reported wrong ids\n");
+        failed = 1;
+    }
+
+    struct ids ids;
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, thread_func, &ids) != 0)
+    {
+        perror("pthread_create");
+        return 1;
+    }
+    pthread_join(thread, NULL);
+
+    if (ids.pid != pid || ids.ppid != ppid || ids.tid == tid)
+    {
+        printf("thread: pid %d, ppid %d, tid %d (process: pid %d, ppid %d, tid %d)\n", ids.pid, ids.ppid,
+               ids.tid, pid, ppid, tid);
+        failed = 1;
+    }
+
+    if (failed)
+    {
+        printf("test_getpid: failed\n");
+        return 1;
+    }
+
+    printf("test_getpid: ok\n");
+    return 0;
+}
@@ -0,0 +1,145 @@
+/**
+ * 测试getrusage:
+ * 1. 在用户态忙循环之后, RUSAGE_SELF与RUSAGE_THREAD的ru_utime增加, 并且ru_maxrss不为0
+ * 2. fork之后写入与子进程共享的页面, 写时复制使RUSAGE_SELF的ru_minflt增加
+ * 3. 回收子进程之后, RUSAGE_CHILDREN包含子进程的cpu时间、缺页次数以及最大常驻集
+ * 4. 不合法的who返回EINVAL
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/resource.h>
+#include <sys/time.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_getrusage"
+#include "test_check.h"
+
+#define NR_PAGES 8
+
+static long page_size;
+
+static long long tv_usec(struct timeval tv)
+{
+    return tv.tv_sec * 1000000LL + tv.tv_usec;
+}
+
+static long long now_usec(void)
+{
+    struct timeval tv;
+    gettimeofday(&tv, NULL);
+    return tv_usec(tv);
+}
+
+/* 在用户态忙循环大约usec微秒 */
+static void busy_loop(long long usec)
+{
+    volatile unsigned long counter = 0;
+    long long start = now_usec();
+    while (now_usec() - start < usec)
+    {
+        for (int i = 0; i < 100000; i++)
+            counter++;
+    }
+}
+
+static void test_self_utime(void)
+{
+    struct rusage before, after, thread;
+    check(getrusage(RUSAGE_SELF, &before) == 0, "getrusage(RUSAGE_SELF)");
+    busy_loop(300000);
+    check(getrusage(RUSAGE_SELF, &after) == 0, "getrusage(RUSAGE_SELF) after busy loop");
+    check(tv_usec(after.ru_utime) > tv_usec(before.ru_utime), "utime grows after busy loop");
+    check(after.ru_utime.tv_usec >= 0 && after.ru_utime.tv_usec < 1000000,
+          "ru_utime is a timeval");
+    check(after.ru_maxrss > 0, "ru_maxrss is non-zero");
+
+    check(getrusage(RUSAGE_THREAD, &thread) == 0, "getrusage(RUSAGE_THREAD)");
+    check(tv_usec(thread.ru_utime) > tv_usec(before.ru_utime), "thread utime grows");
+}
+
+static void test_cow_minflt(void)
+{
+    char *buf = mmap(NULL, page_size * NR_PAGES, PROT_READ | PROT_WRITE,
+                     MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(buf != MAP_FAILED, "mmap");
+    if (buf == MAP_FAILED)
+        return;
+    memset(buf, 'o', page_size * NR_PAGES);
+
+    /* 子进程保持页面共享, 直到父进程写完 */
+    int fds[2];
+    check(pipe(fds) == 0, "pipe");
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        char c;
+        close(fds[1]);
+        read(fds[0], &c, 1);
+        _exit(buf[0] == 'o' ? 0 : 1);
+    }
+    close(fds[0]);
+
+    struct rusage before, after;
+    check(getrusage(RUSAGE_SELF, &before) == 0, "getrusage before COW");
+    for (int i = 0; i < NR_PAGES; i++)
+        buf[i * page_size] = 'p';
+    check(getrusage(RUSAGE_SELF, &after) == 0, "getrusage after COW");
+    check(after.ru_minflt >= before.ru_minflt + NR_PAGES, "COW faults are counted in ru_minflt");
+
+    write(fds[1], "g", 1);
+    close(fds[1]);
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child still sees the content at fork");
+    munmap(buf, page_size * NR_PAGES);
+}
+
+static void test_children(void)
+{
+    struct rusage before, after;
+    check(getrusage(RUSAGE_CHILDREN, &before) == 0, "getrusage(RUSAGE_CHILDREN)");
+
+    char *buf = mmap(NULL, page_size * NR_PAGES, PROT_READ | PROT_WRITE,
+                     MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(buf != MAP_FAILED, "mmap");
+    if (buf == MAP_FAILED)
+        return;
+    memset(buf, 'o', page_size * NR_PAGES);
+
+    /* 子进程忙循环, 并且写入与父进程共享的页面 */
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        for (int i = 0; i < NR_PAGES; i++)
+            buf[i * page_size] = 'c';
+        busy_loop(300000);
+        _exit(0);
+    }
+    check(waitpid(pid, NULL, 0) == pid, "waitpid");
+    munmap(buf, page_size * NR_PAGES);
+
+    check(getrusage(RUSAGE_CHILDREN, &after) == 0, "getrusage(RUSAGE_CHILDREN) after reap");
+    check(tv_usec(after.ru_utime) > tv_usec(before.ru_utime), "children utime grows");
+    check(after.ru_minflt >= before.ru_minflt + NR_PAGES, "children minflt grows");
+    check(after.ru_maxrss > 0, "children maxrss is non-zero");
+}
+
+int main()
+{
+    page_size = sysconf(_SC_PAGESIZE);
+
+    test_self_utime();
+    test_cow_minflt();
+    test_children();
+
+    struct rusage ru;
+    errno = 0;
+    check(getrusage(2, &ru) == -1 && errno == EINVAL, "getrusage with invalid who");
+
+    return test_report();
+}
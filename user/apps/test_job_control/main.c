@@ -0,0 +1,118 @@
+/**
+ * 测试作业控制中的停止与继续:
+ * 1. 向子进程发送SIGSTOP/SIGTSTP之后, waitpid(WUNTRACED)报告停止,
+ *    父进程收到si_code为CLD_STOPPED的SIGCHLD
+ * 2. 子进程中的其他线程也一起停止
+ * 3. 发送SIGCONT之后, waitpid(WCONTINUED)报告继续,
+ *    父进程收到si_code为CLD_CONTINUED的SIGCHLD, 子进程的线程继续运行
+ */
+
+#include <errno.h>
+#include <pthread.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_job_control"
+#include "test_check.h"
+
+static volatile sig_atomic_t last_code = 0;
+static volatile unsigned long *counter;
+
+static void sigchld_handler(int sig, siginfo_t *info, void *ucontext)
+{
+    (void)sig;
+    (void)ucontext;
+    last_code = info->si_code;
+}
+
+/* 等待父进程收到指定si_code的SIGCHLD */
+static int wait_sigchld(int code)
+{
+    for (int i = 0; i < 100 && last_code != code; i++)
+        usleep(10000);
+    return last_code == code;
+}
+
+static void *spin(void *arg)
+{
+    (void)arg;
+    for (;;)
+        (*counter)++;
+    return NULL;
+}
+
+static void child(void)
+{
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, spin, NULL) != 0)
+        _exit(1);
+    for (;;)
+        pause();
+}
+
+static void stop_and_continue(pid_t pid, int stop_sig)
+{
+    int status = 0;
+
+    last_code = 0;
+    kill(pid, stop_sig);
+    check(waitpid(pid, &status, WUNTRACED) == pid, "waitpid WUNTRACED");
+    check(WIFSTOPPED(status) && WSTOPSIG(status) == stop_sig, "stop status");
+    check(wait_sigchld(CLD_STOPPED), "SIGCHLD with CLD_STOPPED");
+
+    /* 停止之后，子进程中的线程不再运行 */
+    unsigned long before = *counter;
+    usleep(100000);
+    check(*counter == before, "thread group stopped together");
+
+    last_code = 0;
+    kill(pid, SIGCONT);
+    check(waitpid(pid, &status, WCONTINUED) == pid, "waitpid WCONTINUED");
+    check(WIFCONTINUED(status), "continue status");
+    check(wait_sigchld(CLD_CONTINUED), "SIGCHLD with CLD_CONTINUED");
+
+    before = *counter;
+    usleep(100000);
+    check(*counter != before, "thread resumed after SIGCONT");
+}
+
+int main()
+{
+    struct sigaction sa;
+    memset(&sa, 0, sizeof(sa));
+    sa.sa_sigaction = sigchld_handler;
+    sa.sa_flags = SA_SIGINFO | SA_RESTART;
+    sigaction(SIGCHLD, &sa, NULL);
+
+    counter = mmap(NULL, sizeof(*counter), PROT_READ | PROT_WRITE, MAP_SHARED | MAP_ANONYMOUS,
+                   -1, 0);
+    if (counter == MAP_FAILED)
+    {
+        printf("test_job_control: mmap failed: %s\n", strerror(errno));
+        return 1;
+    }
+    *counter = 0;
+
+    pid_t pid = fork();
+    if (pid == 0)
+        child();
+
+    /* 等待子进程中的线程开始运行 */
+    for (int i = 0; i < 100 && *counter == 0; i++)
+        usleep(10000);
+    check(*counter != 0, "child thread running");
+
+    stop_and_continue(pid, SIGSTOP);
+    stop_and_continue(pid, SIGTSTP);
+
+    kill(pid, SIGKILL);
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid && WIFSIGNALED(status) && WTERMSIG(status) == SIGKILL,
+          "reap killed child");
+
+    return test_report();
+}
@@ -0,0 +1,52 @@
+/**
+ * 运行内核自检: 向/sys/kernel/selftest写入自检的名字, 测试通过时写入成功
+ * 1. kthread_entry: 内核线程从干净的入口栈帧开始执行
+ * 2. wait_queue: 两个内核线程通过等待队列交替执行时, 唤醒不会丢失
+ * 3. 不存在的自检返回EINVAL
+ *
+ * 也可以在命令行中指定要运行的自检, 例如: test_kernel_selftest kthread_entry
+ * 性能测试不会被默认运行, 需要在命令行中指定, 例如: test_kernel_selftest bench_copy_mm,
+ * 结果输出到内核日志中
+ */
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_kernel_selftest"
+#include "test_check.h"
+
+#define SELFTEST_PATH "/sys/kernel/selftest"
+
+/* 运行一个自检, 返回write的结果 */
+static int run_selftest(const char *name)
+{
+    int fd = open(SELFTEST_PATH, O_WRONLY);
+    if (fd < 0)
+        return -1;
+    errno = 0;
+    int r = write(fd, name, strlen(name));
+    int saved_errno = errno;
+    close(fd);
+    errno = saved_errno;
+    return r;
+}
+
+int main(int argc, char *argv[])
+{
+    if (argc > 1)
+    {
+        for (int i = 1; i < argc; i++)
+            check(run_selftest(argv[i]) == (int)strlen(argv[i]), argv[i]);
+    }
+    else
+    {
+        check(run_selftest("kthread_entry") == (int)strlen("kthread_entry"), "kthread_entry");
+        check(run_selftest("wait_queue") == (int)strlen("wait_queue"), "wait_queue");
+        check(run_selftest("no_such_test") == -1 && errno == EINVAL, "unknown selftest");
+    }
+
+    return test_report();
+}
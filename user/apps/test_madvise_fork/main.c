@@ -0,0 +1,136 @@
+/**
+ * 测试madvise的MADV_DONTFORK与MADV_WIPEONFORK对fork的影响:
+ * 1. 标记为MADV_DONTFORK的区域不会被子进程继承, 子进程访问它时收到SIGSEGV.
+ *    只标记区域中间的一页时, 前后的页面仍然会被继承
+ * 2. MADV_DOFORK撤销MADV_DONTFORK之后, 子进程可以访问这个区域
+ * 3. 标记为MADV_WIPEONFORK的区域在子进程中全部为0, 父进程中的内容不受影响
+ * 4. MADV_KEEPONFORK撤销MADV_WIPEONFORK之后, 子进程看到父进程的内容
+ * 5. 对共享映射使用MADV_WIPEONFORK返回EINVAL, 区域中包含未映射的地址时返回ENOMEM
+ */
+
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_madvise_fork"
+#include "test_check.h"
+
+static long page_size;
+
+/* 在子进程中读取addr处的一个字节, 返回子进程的退出状态 */
+static int child_read(volatile char *addr, char expected)
+{
+    pid_t pid = fork();
+    if (pid == 0)
+        _exit(*addr == expected ? 0 : 1);
+    int status = 0;
+    if (waitpid(pid, &status, 0) != pid)
+        return -1;
+    return status;
+}
+
+static int killed_by_segv(int status)
+{
+    return status != -1 && WIFSIGNALED(status) && WTERMSIG(status) == SIGSEGV;
+}
+
+static int exited_ok(int status)
+{
+    return status != -1 && WIFEXITED(status) && WEXITSTATUS(status) == 0;
+}
+
+static void test_dontfork(void)
+{
+    char *buf = mmap(NULL, page_size * 3, PROT_READ | PROT_WRITE,
+                     MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(buf != MAP_FAILED, "mmap");
+    if (buf == MAP_FAILED)
+        return;
+    memset(buf, 'a', page_size * 3);
+
+    /* 只标记中间的一页 */
+    check(madvise(buf + page_size, page_size, MADV_DONTFORK) == 0, "madvise(MADV_DONTFORK)");
+    check(killed_by_segv(child_read(buf + page_size, 'a')), "child cannot access DONTFORK page");
+    check(exited_ok(child_read(buf, 'a')), "child inherits the page before the DONTFORK page");
+    check(exited_ok(child_read(buf + page_size * 2, 'a')),
+          "child inherits the page after the DONTFORK page");
+    check(buf[page_size] == 'a', "parent still accesses the DONTFORK page");
+
+    check(madvise(buf + page_size, page_size, MADV_DOFORK) == 0, "madvise(MADV_DOFORK)");
+    check(exited_ok(child_read(buf + page_size, 'a')), "child inherits the page after MADV_DOFORK");
+
+    munmap(buf, page_size * 3);
+}
+
+static void test_wipeonfork(void)
+{
+    char *buf = mmap(NULL, page_size * 2, PROT_READ | PROT_WRITE,
+                     MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(buf != MAP_FAILED, "mmap");
+    if (buf == MAP_FAILED)
+        return;
+    memset(buf, 's', page_size * 2);
+
+    check(madvise(buf, page_size * 2, MADV_WIPEONFORK) == 0, "madvise(MADV_WIPEONFORK)");
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        for (long i = 0; i < page_size * 2; i++)
+        {
+            if (buf[i] != 0)
+                _exit(1);
+        }
+        /* 子进程可以正常写入这个区域 */
+        buf[0] = 'c';
+        _exit(buf[0] == 'c' ? 0 : 2);
+    }
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(exited_ok(status), "child sees zeros in WIPEONFORK region");
+    check(buf[0] == 's' && buf[page_size * 2 - 1] == 's', "parent keeps WIPEONFORK contents");
+
+    check(madvise(buf, page_size * 2, MADV_KEEPONFORK) == 0, "madvise(MADV_KEEPONFORK)");
+    check(exited_ok(child_read(buf, 's')), "child inherits contents after MADV_KEEPONFORK");
+
+    munmap(buf, page_size * 2);
+}
+
+static void test_errors(void)
+{
+    char *shared = mmap(NULL, page_size, PROT_READ | PROT_WRITE,
+                        MAP_SHARED | MAP_ANONYMOUS, -1, 0);
+    check(shared != MAP_FAILED, "mmap shared");
+    if (shared != MAP_FAILED)
+    {
+        errno = 0;
+        check(madvise(shared, page_size, MADV_WIPEONFORK) == -1 && errno == EINVAL,
+              "MADV_WIPEONFORK on shared mapping");
+        munmap(shared, page_size);
+    }
+
+    /* 第二页没有被映射 */
+    char *buf = mmap(NULL, page_size * 2, PROT_READ | PROT_WRITE,
+                     MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(buf != MAP_FAILED, "mmap");
+    if (buf == MAP_FAILED)
+        return;
+    munmap(buf + page_size, page_size);
+    errno = 0;
+    check(madvise(buf, page_size * 2, MADV_DONTFORK) == -1 && errno == ENOMEM,
+          "madvise on partly unmapped range");
+    munmap(buf, page_size);
+}
+
+int main()
+{
+    page_size = sysconf(_SC_PAGESIZE);
+    test_dontfork();
+    test_wipeonfork();
+    test_errors();
+
+    return test_report();
+}
@@ -0,0 +1,173 @@
+/**
+ * 测试mmap/munmap创建的匿名映射在fork之后的行为:
+ * 1. MAP_PRIVATE的映射: 子进程看到fork时父进程写入的内容, 之后双方的写入互不可见(写时复制)
+ * 2. MAP_SHARED的映射: 子进程的写入对父进程可见
+ * 3. MAP_FIXED替换已有映射的一部分, 被替换的部分变为0, 其余部分不变
+ * 4. 对只读映射写入时收到SIGSEGV, munmap之后访问也会收到SIGSEGV
+ * 5. 参数检查: 没有指定MAP_SHARED/MAP_PRIVATE、长度为0、MAP_FIXED的地址没有对齐时返回EINVAL
+ */
+
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_mmap_fork"
+#include "test_check.h"
+
+static long page_size;
+
+static char *map_anon(size_t len, int prot, int flags)
+{
+    return mmap(NULL, len, prot, flags | MAP_ANONYMOUS, -1, 0);
+}
+
+/* 等待子进程退出, 返回它的退出状态 */
+static int wait_child(pid_t pid)
+{
+    int status = 0;
+    if (pid < 0 || waitpid(pid, &status, 0) != pid)
+        return -1;
+    return status;
+}
+
+static int exited_ok(int status)
+{
+    return status != -1 && WIFEXITED(status) && WEXITSTATUS(status) == 0;
+}
+
+static int killed_by_segv(int status)
+{
+    return status != -1 && WIFSIGNALED(status) && WTERMSIG(status) == SIGSEGV;
+}
+
+static void test_private(void)
+{
+    char *buf = map_anon(page_size * 2, PROT_READ | PROT_WRITE, MAP_PRIVATE);
+    check(buf != MAP_FAILED, "mmap private");
+    if (buf == MAP_FAILED)
+        return;
+    memset(buf, 'p', page_size * 2);
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        if (buf[0] != 'p' || buf[page_size * 2 - 1] != 'p')
+            _exit(1);
+        memset(buf, 'c', page_size * 2);
+        /* 等待父进程写入之后, 检查自己的内容没有被改变 */
+        usleep(50000);
+        _exit(buf[page_size] == 'c' ? 0 : 2);
+    }
+    buf[page_size] = 'P';
+    check(exited_ok(wait_child(pid)), "child sees its own private copy");
+    check(buf[0] == 'p' && buf[page_size] == 'P', "parent does not see child's private writes");
+
+    munmap(buf, page_size * 2);
+}
+
+static void test_shared(void)
+{
+    char *buf = map_anon(page_size, PROT_READ | PROT_WRITE, MAP_SHARED);
+    check(buf != MAP_FAILED, "mmap shared");
+    if (buf == MAP_FAILED)
+        return;
+    memset(buf, 's', page_size);
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        if (buf[0] != 's')
+            _exit(1);
+        strcpy(buf, "hello from child");
+        _exit(0);
+    }
+    check(exited_ok(wait_child(pid)), "child writes shared mapping");
+    check(strcmp(buf, "hello from child") == 0, "parent sees child's shared writes");
+
+    munmap(buf, page_size);
+}
+
+static void test_fixed(void)
+{
+    char *buf = map_anon(page_size * 3, PROT_READ | PROT_WRITE, MAP_PRIVATE);
+    check(buf != MAP_FAILED, "mmap");
+    if (buf == MAP_FAILED)
+        return;
+    memset(buf, 'f', page_size * 3);
+
+    char *mid = mmap(buf + page_size, page_size, PROT_READ | PROT_WRITE,
+                     MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED, -1, 0);
+    check(mid == buf + page_size, "MAP_FIXED over an existing mapping");
+    check(buf[page_size] == 0 && buf[page_size * 2 - 1] == 0, "replaced page is zeroed");
+    check(buf[0] == 'f' && buf[page_size * 2] == 'f', "neighbouring pages are kept");
+
+    munmap(buf, page_size * 3);
+}
+
+static void test_permissions(void)
+{
+    char *ro = map_anon(page_size, PROT_READ, MAP_PRIVATE);
+    check(ro != MAP_FAILED, "mmap read-only");
+    if (ro != MAP_FAILED)
+    {
+        pid_t pid = fork();
+        if (pid == 0)
+        {
+            *(volatile char *)ro = 1;
+            _exit(0);
+        }
+        check(killed_by_segv(wait_child(pid)), "write to read-only mapping raises SIGSEGV");
+        munmap(ro, page_size);
+    }
+
+    char *buf = map_anon(page_size, PROT_READ | PROT_WRITE, MAP_PRIVATE);
+    check(buf != MAP_FAILED, "mmap");
+    if (buf != MAP_FAILED)
+    {
+        check(munmap(buf, page_size) == 0, "munmap");
+        pid_t pid = fork();
+        if (pid == 0)
+        {
+            (void)*(volatile char *)buf;
+            _exit(0);
+        }
+        check(killed_by_segv(wait_child(pid)), "access after munmap raises SIGSEGV");
+    }
+}
+
+static void test_errors(void)
+{
+    errno = 0;
+    check(mmap(NULL, page_size, PROT_READ, MAP_ANONYMOUS, -1, 0) == MAP_FAILED && errno == EINVAL,
+          "mmap without MAP_SHARED/MAP_PRIVATE");
+    errno = 0;
+    check(map_anon(0, PROT_READ, MAP_PRIVATE) == MAP_FAILED && errno == EINVAL,
+          "mmap with zero length");
+
+    char *buf = map_anon(page_size * 2, PROT_READ | PROT_WRITE, MAP_PRIVATE);
+    check(buf != MAP_FAILED, "mmap");
+    if (buf == MAP_FAILED)
+        return;
+    errno = 0;
+    check(mmap(buf + 1, page_size, PROT_READ, MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED, -1, 0) ==
+                  MAP_FAILED &&
+              errno == EINVAL,
+          "MAP_FIXED with unaligned address");
+    munmap(buf, page_size * 2);
+}
+
+int main()
+{
+    page_size = sysconf(_SC_PAGESIZE);
+    test_private();
+    test_shared();
+    test_fixed();
+    test_permissions();
+    test_errors();
+
+    return test_report();
+}
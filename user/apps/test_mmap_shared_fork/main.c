@@ -0,0 +1,92 @@
+/**
+ * 测试fork时对不同类型映射的处理:
+ * 1. MAP_SHARED的映射在父子进程间共享, 一方的写入对另一方可见
+ * 2. MAP_PRIVATE的映射在父子进程间相互独立, 一方的写入对另一方不可见
+ */
+
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define MAP_LEN (4 * 4096)
+
+int main()
+{
+    int failed = 0;
+
+    char *shared = mmap(NULL, MAP_LEN, PROT_READ | PROT_WRITE, MAP_SHARED | MAP_ANONYMOUS, -1, 0);
+    char *private = mmap(NULL, MAP_LEN, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    if (shared == MAP_FAILED || private == MAP_FAILED)
+    {
+        perror("mmap");
+        return 1;
+    }
+    memset(shared, 'p', MAP_LEN);
+    memset(private, 'p', MAP_LEN);
+
+    pid_t pid = fork();
+    if (pid < 0)
+    {
+        perror("fork");
+        return 1;
+    }
+    if (pid == 0)
+    {
+        // 子进程能看到fork之前父进程写入的内容
+        if (shared[0] != 'p' || private[0] != 'p')
+            _exit(1);
+
+        // 每一页都写入, 确保跨页的共享也正确
+        for (int i = 0; i < MAP_LEN; i += 4096)
+        {
+            shared[i] = 'c';
+            private[i] = 'c';
+        }
+        _exit(0);
+    }
+
+    int status;
+    if (waitpid(pid, &status, 0) != pid || !WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("child failed to see the parent's data\n");
+        failed = 1;
+    }
+
+    for (int i = 0; i < MAP_LEN; i += 4096)
+    {
+        if (shared[i] != 'c')
+        {
+            printf("child's write to the shared mapping at offset %d is not visible\n", i);
+            failed = 1;
+            break;
+        }
+        if (private[i] != 'p')
+        {
+            printf("child's write to the private mapping at offset %d leaked to the parent\n", i);
+            failed = 1;
+            break;
+        }
+    }
+
+    // 父进程的写入也应当对之后fork出的子进程可见
+    shared[1] = 'P';
+    pid = fork();
+    if (pid == 0)
+        _exit(shared[1] == 'P' ? 0 : 1);
+    if (pid < 0 || waitpid(pid, &status, 0) != pid || !WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("parent's write to the shared mapping is not visible in the child\n");
+        failed = 1;
+    }
+
+    if (failed)
+    {
+        printf("test_mmap_shared_fork: failed\n");
+        return 1;
+    }
+
+    printf("test_mmap_shared_fork: ok\n");
+    return 0;
+}
@@ -0,0 +1,110 @@
+/**
+ * 测试CLONE_NEWNS:
+ * 1. 新mount namespace中的子进程挂载文件系统之后, 能在挂载点下看到新文件系统中的文件
+ * 2. 子进程的挂载不影响父进程: 父进程看到的挂载点仍然是原来的目录
+ * 3. 子进程与父进程的/proc/<pid>/ns/mnt不同
+ * 4. CLONE_NEWNS与CLONE_FS不能同时使用
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_mnt_namespace"
+#include "test_check.h"
+
+#define MOUNT_POINT "/test_mnt_namespace"
+#define CHILD_FILE MOUNT_POINT "/child_file"
+#define PARENT_FILE MOUNT_POINT "/parent_file"
+
+/* 与fork相同, 但是可以指定额外的clone标志 */
+static pid_t clone_fork(unsigned long flags)
+{
+    return syscall(SYS_clone, flags | SIGCHLD, 0, NULL, NULL, 0);
+}
+
+static void read_ns(pid_t pid, char *buf, size_t size)
+{
+    char path[64];
+    snprintf(path, sizeof(path), "/proc/%d/ns/mnt", pid);
+    buf[0] = '\0';
+    int fd = open(path, O_RDONLY);
+    if (fd < 0)
+        return;
+    ssize_t n = read(fd, buf, size - 1);
+    buf[n > 0 ? n : 0] = '\0';
+    close(fd);
+}
+
+static int exists(const char *path)
+{
+    return access(path, F_OK) == 0;
+}
+
+int main()
+{
+    errno = 0;
+    check(clone_fork(CLONE_NEWNS | CLONE_FS) == -1 && errno == EINVAL,
+          "reject CLONE_NEWNS | CLONE_FS");
+
+    mkdir(MOUNT_POINT, 0755);
+    int fd = open(PARENT_FILE, O_CREAT | O_WRONLY, 0644);
+    check(fd >= 0, "create file under mount point");
+    close(fd);
+
+    int pipefd[2];
+    check(pipe(pipefd) == 0, "pipe");
+
+    pid_t pid = clone_fork(CLONE_NEWNS);
+    if (pid == 0)
+    {
+        close(pipefd[1]);
+        check(mount("", MOUNT_POINT, "ramfs", 0, NULL) == 0, "mount ramfs in child");
+        check(!exists(PARENT_FILE), "mount hides the original directory");
+        fd = open(CHILD_FILE, O_CREAT | O_WRONLY, 0644);
+        check(fd >= 0, "create file in mounted ramfs");
+        close(fd);
+        check(exists(CHILD_FILE), "child sees its own mount");
+
+        /* 等待父进程检查完毕之后再退出 */
+        char c;
+        read(pipefd[0], &c, 1);
+        _exit(failed);
+    }
+    close(pipefd[0]);
+    check(pid > 0, "clone with CLONE_NEWNS");
+
+    /* 等待子进程完成挂载 */
+    usleep(100000);
+    check(exists(PARENT_FILE), "parent still sees the original directory");
+    check(!exists(CHILD_FILE), "parent does not see child's mount");
+
+    char self_ns[64], child_ns[64];
+    read_ns(getpid(), self_ns, sizeof(self_ns));
+    read_ns(pid, child_ns, sizeof(child_ns));
+    check(strncmp(self_ns, "mnt:[", 5) == 0, "read own mount namespace");
+    check(strncmp(child_ns, "mnt:[", 5) == 0, "read child mount namespace");
+    check(strcmp(self_ns, child_ns) != 0, "child is in a different mount namespace");
+
+    write(pipefd[1], "x", 1);
+    close(pipefd[1]);
+
+    int status = 0;
+    check(pid > 0 && waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+              WEXITSTATUS(status) == 0,
+          "child exit status");
+
+    unlink(PARENT_FILE);
+    check(rmdir(MOUNT_POINT) == 0, "mount point is not busy in parent");
+
+    return test_report();
+}
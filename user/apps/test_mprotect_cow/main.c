@@ -0,0 +1,115 @@
+/**
+ * 测试mprotect与写时复制:
+ * 1. fork之后(页面仍与子进程共享), 父进程用mprotect把区域改为只读, 写入时收到SIGSEGV, 而不是复制页面
+ * 2. 再用mprotect恢复可写之后, 写入触发写时复制: 父进程写入成功, 子进程看到的仍然是fork时的内容
+ * 3. 没有被共享的私有页面在只读时写入同样收到SIGSEGV
+ * 4. 对包含未映射地址的区域调用mprotect返回ENOMEM
+ */
+
+#include <errno.h>
+#include <setjmp.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_mprotect_cow"
+#include "test_check.h"
+
+static long page_size;
+static sigjmp_buf segv_env;
+
+static void segv_handler(int sig)
+{
+    (void)sig;
+    siglongjmp(segv_env, 1);
+}
+
+/* 向addr写入value, 返回写入时是否收到了SIGSEGV */
+static int write_faults(volatile char *addr, char value)
+{
+    if (sigsetjmp(segv_env, 1) != 0)
+        return 1;
+    *addr = value;
+    return 0;
+}
+
+static void test_cow_readonly(void)
+{
+    char *buf = mmap(NULL, page_size, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(buf != MAP_FAILED, "mmap");
+    if (buf == MAP_FAILED)
+        return;
+    buf[0] = 'o';
+
+    /* 子进程保持页面共享, 直到父进程通知它检查内容 */
+    int fds[2];
+    check(pipe(fds) == 0, "pipe");
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        char c;
+        close(fds[1]);
+        read(fds[0], &c, 1);
+        _exit(buf[0] == 'o' ? 0 : 1);
+    }
+    close(fds[0]);
+
+    check(mprotect(buf, page_size, PROT_READ) == 0, "mprotect(PROT_READ)");
+    check(write_faults(buf, 'x'), "write to read-only COW page raises SIGSEGV");
+    check(buf[0] == 'o', "read-only COW page is unchanged");
+
+    check(mprotect(buf, page_size, PROT_READ | PROT_WRITE) == 0,
+          "mprotect(PROT_READ | PROT_WRITE)");
+    check(!write_faults(buf, 'n'), "write after restoring PROT_WRITE");
+    check(buf[0] == 'n', "parent sees its own write");
+
+    write(fds[1], "g", 1);
+    close(fds[1]);
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child still sees the content at fork");
+
+    munmap(buf, page_size);
+}
+
+static void test_private_readonly(void)
+{
+    char *buf = mmap(NULL, page_size, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(buf != MAP_FAILED, "mmap");
+    if (buf == MAP_FAILED)
+        return;
+    buf[0] = 'p';
+    check(mprotect(buf, page_size, PROT_READ) == 0, "mprotect(PROT_READ)");
+    check(write_faults(buf, 'x'), "write to read-only private page raises SIGSEGV");
+    check(buf[0] == 'p', "read-only private page is unchanged");
+    munmap(buf, page_size);
+}
+
+static void test_unmapped(void)
+{
+    char *buf = mmap(NULL, page_size * 2, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1,
+                     0);
+    check(buf != MAP_FAILED, "mmap");
+    if (buf == MAP_FAILED)
+        return;
+    munmap(buf + page_size, page_size);
+    errno = 0;
+    check(mprotect(buf, page_size * 2, PROT_READ) == -1 && errno == ENOMEM,
+          "mprotect on partly unmapped range");
+    munmap(buf, page_size);
+}
+
+int main()
+{
+    page_size = sysconf(_SC_PAGESIZE);
+    signal(SIGSEGV, segv_handler);
+
+    test_cow_readonly();
+    test_private_readonly();
+    test_unmapped();
+
+    return test_report();
+}
@@ -0,0 +1,157 @@
+/**
+ * 测试fork时namespace的共享与替换:
+ * 1. 没有使用CLONE_NEW*标志的子进程与父进程位于相同的mnt、uts、pid namespace中
+ * 2. 使用CLONE_NEWUTS或者CLONE_NEWNS时, 只有对应的namespace被替换, 其他namespace仍然与父进程相同
+ * 3. 同时使用两个标志时, 两个namespace都被替换, 并且子进程再fork出的进程与它位于相同的namespace中
+ * 4. unshare只替换对应的namespace
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_nsproxy"
+#include "test_check.h"
+
+#define NR_NS 3
+
+static const char *ns_names[NR_NS] = {"mnt", "uts", "pid"};
+
+struct ns_ids
+{
+    char id[NR_NS][64];
+};
+
+/* 与fork相同, 但是可以指定额外的clone标志 */
+static pid_t clone_fork(unsigned long flags)
+{
+    return syscall(SYS_clone, flags | SIGCHLD, 0, NULL, NULL, 0);
+}
+
+/* 读取/proc/self/ns/<name>, 得到形如"uts:[<编号>]"的namespace标识 */
+static void read_ns(const char *name, char *buf, size_t size)
+{
+    char path[64];
+    snprintf(path, sizeof(path), "/proc/%d/ns/%s", getpid(), name);
+    ssize_t n = readlink(path, buf, size - 1);
+    if (n < 0)
+    {
+        int fd = open(path, O_RDONLY);
+        n = fd < 0 ? 0 : read(fd, buf, size - 1);
+        if (fd >= 0)
+            close(fd);
+    }
+    buf[n > 0 ? n : 0] = '\0';
+    /* 去掉结尾的换行符 */
+    buf[strcspn(buf, "\n")] = '\0';
+}
+
+static void read_all_ns(struct ns_ids *ids)
+{
+    for (int i = 0; i < NR_NS; i++)
+        read_ns(ns_names[i], ids->id[i], sizeof(ids->id[i]));
+}
+
+/* 检查当前进程的namespace: changed中的namespace与old不同, 其他的与old相同 */
+static void check_ns(const struct ns_ids *old, const char **changed, const char *what)
+{
+    struct ns_ids now;
+    char msg[128];
+    read_all_ns(&now);
+    for (int i = 0; i < NR_NS; i++)
+    {
+        int expect_changed = 0;
+        for (const char **c = changed; *c != NULL; c++)
+            expect_changed |= strcmp(*c, ns_names[i]) == 0;
+
+        snprintf(msg, sizeof(msg), "%s: %s namespace %s", what, ns_names[i],
+                 expect_changed ? "replaced" : "shared");
+        check(now.id[i][0] != '\0' && (strcmp(now.id[i], old->id[i]) != 0) == expect_changed,
+              msg);
+    }
+}
+
+static void wait_child(pid_t pid, const char *what)
+{
+    int status = 0;
+    check(pid > 0 && waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+              WEXITSTATUS(status) == 0,
+          what);
+}
+
+int main()
+{
+    struct ns_ids parent;
+    read_all_ns(&parent);
+    for (int i = 0; i < NR_NS; i++)
+        check(parent.id[i][0] != '\0', "read own namespaces");
+
+    const char *none[] = {NULL};
+    const char *uts[] = {"uts", NULL};
+    const char *mnt[] = {"mnt", NULL};
+    const char *mnt_uts[] = {"mnt", "uts", NULL};
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        check_ns(&parent, none, "fork");
+        _exit(failed);
+    }
+    wait_child(pid, "fork child");
+
+    pid = clone_fork(CLONE_NEWUTS);
+    if (pid == 0)
+    {
+        check_ns(&parent, uts, "CLONE_NEWUTS");
+        _exit(failed);
+    }
+    wait_child(pid, "CLONE_NEWUTS child");
+
+    pid = clone_fork(CLONE_NEWNS);
+    if (pid == 0)
+    {
+        check_ns(&parent, mnt, "CLONE_NEWNS");
+        _exit(failed);
+    }
+    wait_child(pid, "CLONE_NEWNS child");
+
+    pid = clone_fork(CLONE_NEWUTS | CLONE_NEWNS);
+    if (pid == 0)
+    {
+        check_ns(&parent, mnt_uts, "CLONE_NEWUTS | CLONE_NEWNS");
+
+        /* 子进程再fork出的进程与它位于相同的namespace中 */
+        struct ns_ids child;
+        read_all_ns(&child);
+        pid_t grandchild = fork();
+        if (grandchild == 0)
+        {
+            check_ns(&child, none, "fork in new namespaces");
+            _exit(failed);
+        }
+        wait_child(grandchild, "grandchild");
+        _exit(failed);
+    }
+    wait_child(pid, "CLONE_NEWUTS | CLONE_NEWNS child");
+
+    pid = fork();
+    if (pid == 0)
+    {
+        check(unshare(CLONE_NEWUTS) == 0, "unshare CLONE_NEWUTS");
+        check_ns(&parent, uts, "unshare CLONE_NEWUTS");
+        _exit(failed);
+    }
+    wait_child(pid, "unshare child");
+
+    /* 父进程自身的namespace不受影响 */
+    check_ns(&parent, none, "parent");
+
+    return test_report();
+}
@@ -0,0 +1,109 @@
+/**
+ * 测试OOM killer:
+ * 1. /proc/<pid>/oom_score_adj可以读写, 超出[-1000, 1000]范围的值返回EINVAL
+ * 2. fork出的子进程继承父进程的oom_score_adj
+ * 3. 子进程将oom_score_adj设置为1000之后不断申请内存, 内存耗尽时子进程被SIGKILL杀死,
+ *    而不是内核panic; 父进程(oom_score_adj为-1000)不受影响, 之后仍然可以申请内存
+ */
+
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_oom"
+#include "test_check.h"
+
+#define CHUNK_SIZE (1024 * 1024)
+
+static int write_adj(pid_t pid, const char *value)
+{
+    char path[64];
+    snprintf(path, sizeof(path), "/proc/%d/oom_score_adj", pid);
+    int fd = open(path, O_WRONLY);
+    if (fd < 0)
+        return -1;
+    ssize_t n = write(fd, value, strlen(value));
+    close(fd);
+    return n == (ssize_t)strlen(value) ? 0 : -1;
+}
+
+static int read_adj(pid_t pid, int *adj)
+{
+    char path[64], buf[32];
+    snprintf(path, sizeof(path), "/proc/%d/oom_score_adj", pid);
+    int fd = open(path, O_RDONLY);
+    if (fd < 0)
+        return -1;
+    ssize_t n = read(fd, buf, sizeof(buf) - 1);
+    close(fd);
+    if (n <= 0)
+        return -1;
+    buf[n] = '\0';
+    return sscanf(buf, "%d", adj) == 1 ? 0 : -1;
+}
+
+int main()
+{
+    int adj = 0;
+    check(read_adj(getpid(), &adj) == 0 && adj == 0, "default oom_score_adj is 0");
+
+    errno = 0;
+    check(write_adj(getpid(), "1001") == -1 && errno == EINVAL, "reject oom_score_adj 1001");
+    errno = 0;
+    check(write_adj(getpid(), "abc") == -1 && errno == EINVAL, "reject non-numeric value");
+
+    /* 保护父进程自身不被OOM killer选中 */
+    check(write_adj(getpid(), "-1000") == 0, "set oom_score_adj to -1000");
+    check(read_adj(getpid(), &adj) == 0 && adj == -1000, "read back oom_score_adj");
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        int child_adj = 0;
+        _exit(read_adj(getpid(), &child_adj) == 0 && child_adj == -1000 ? 0 : 1);
+    }
+    int status = 0;
+    check(pid > 0 && waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+              WEXITSTATUS(status) == 0,
+          "child inherits oom_score_adj");
+
+    pid = fork();
+    if (pid == 0)
+    {
+        if (write_adj(getpid(), "1000") != 0)
+            _exit(2);
+        /* 不断申请内存, 直到被OOM killer杀死 */
+        for (;;)
+        {
+            char *p = mmap(NULL, CHUNK_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS,
+                           -1, 0);
+            if (p == MAP_FAILED)
+                _exit(1);
+            memset(p, 0x5a, CHUNK_SIZE);
+        }
+    }
+    check(pid > 0, "fork");
+
+    status = 0;
+    check(waitpid(pid, &status, 0) == pid, "wait for memory hog");
+    if (WIFEXITED(status))
+        printf("test_oom: memory hog exited with %d instead of being killed\n",
+               WEXITSTATUS(status));
+    check(WIFSIGNALED(status) && WTERMSIG(status) == SIGKILL, "memory hog killed by SIGKILL");
+
+    /* 被杀死的进程释放了内存, 父进程仍然可以申请内存 */
+    char *p = mmap(NULL, CHUNK_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(p != MAP_FAILED, "mmap after oom kill");
+    if (p != MAP_FAILED)
+    {
+        memset(p, 0, CHUNK_SIZE);
+        munmap(p, CHUNK_SIZE);
+    }
+
+    return test_report();
+}
@@ -0,0 +1,94 @@
+/**
+ * 测试prctl(PR_SET_PDEATHSIG):
+ * 1. PR_GET_PDEATHSIG能够读取到设置的信号
+ * 2. 父进程退出时, 子进程收到设置的信号(SIGTERM)
+ */
+
+#include <signal.h>
+#include <stdio.h>
+#include <sys/prctl.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static int result_pipe[2];
+
+static void on_sigterm(int sig)
+{
+    (void)sig;
+    char c = 'T';
+    write(result_pipe[1], &c, 1);
+    _exit(0);
+}
+
+/* 中间进程: 创建设置了PR_SET_PDEATHSIG的子进程, 等它准备好之后退出 */
+static void run_parent(void)
+{
+    int ready_pipe[2];
+    if (pipe(ready_pipe) != 0)
+        _exit(1);
+
+    pid_t pid = fork();
+    if (pid < 0)
+        _exit(1);
+    if (pid == 0)
+    {
+        signal(SIGTERM, on_sigterm);
+
+        char c = 'E';
+        int sig = 0;
+        if (prctl(PR_SET_PDEATHSIG, SIGTERM) == 0 && prctl(PR_GET_PDEATHSIG, &sig) == 0 && sig == SIGTERM)
+            c = 'R';
+        write(ready_pipe[1], &c, 1);
+        if (c != 'R')
+            _exit(1);
+
+        for (;;)
+            pause();
+    }
+
+    char c;
+    if (read(ready_pipe[0], &c, 1) != 1 || c != 'R')
+        _exit(1);
+    _exit(0);
+}
+
+int main()
+{
+    if (pipe(result_pipe) != 0)
+    {
+        perror("pipe");
+        return 1;
+    }
+
+    pid_t pid = fork();
+    if (pid < 0)
+    {
+        perror("fork");
+        return 1;
+    }
+    if (pid == 0)
+        run_parent();
+
+    close(result_pipe[1]);
+
+    int status;
+    if (waitpid(pid, &status, 0) != pid || !WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("failed to set up the child with PR_SET_PDEATHSIG\n");
+        printf("test_pdeathsig: failed\n");
+        return 1;
+    }
+
+    // 父进程退出后, 子进程应当收到SIGTERM, 并通过管道通知
+    alarm(5);
+    char c = 0;
+    if (read(result_pipe[0], &c, 1) != 1 || c != 'T')
+    {
+        printf("the child did not receive SIGTERM after its parent exited\n");
+        printf("test_pdeathsig: failed\n");
+        return 1;
+    }
+
+    printf("test_pdeathsig: ok\n");
+    return 0;
+}
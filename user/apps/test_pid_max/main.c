@@ -0,0 +1,134 @@
+/**
+ * 测试pid上限(/proc/sys/kernel/pid_max):
+ * 1. 写入不合法的上限(不大于RESERVED_PIDS)时返回EINVAL
+ * 2. 把上限调低之后, 不断fork直到pid耗尽, fork返回EAGAIN
+ * 3. 回收一个子进程之后, 再次fork能够成功, 并且分配到的pid小于上限
+ * 4. 恢复原来的上限之后, fork恢复正常
+ *
+ * 需要修改系统设置, 因此在linux上需要以root身份运行
+ */
+
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_pid_max"
+#include "test_check.h"
+
+#define PID_MAX_PATH "/proc/sys/kernel/pid_max"
+/* 与内核中的RESERVED_PIDS相同, pid上限必须大于它 */
+#define RESERVED_PIDS 300
+#define TINY_PID_MAX (RESERVED_PIDS + 10)
+/* pid上限为TINY_PID_MAX时, 最多只能有这么多个进程 */
+#define MAX_CHILDREN TINY_PID_MAX
+
+static pid_t children[MAX_CHILDREN];
+static int nr_children = 0;
+
+static long read_pid_max(void)
+{
+    char buf[32] = {0};
+    int fd = open(PID_MAX_PATH, O_RDONLY);
+    if (fd < 0)
+        return -1;
+    long ret = read(fd, buf, sizeof(buf) - 1) > 0 ? atol(buf) : -1;
+    close(fd);
+    return ret;
+}
+
+/* 写入新的pid上限, 成功时返回0, 失败时返回-1并设置errno */
+static int write_pid_max(long value)
+{
+    char buf[32];
+    int len = snprintf(buf, sizeof(buf), "%ld\n", value);
+    int fd = open(PID_MAX_PATH, O_WRONLY);
+    if (fd < 0)
+        return -1;
+    int ret = write(fd, buf, len) == len ? 0 : -1;
+    int saved = errno;
+    close(fd);
+    errno = saved;
+    return ret;
+}
+
+/* fork出一个一直睡眠的子进程, 返回fork的返回值 */
+static pid_t spawn_sleeper(void)
+{
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        for (;;)
+            pause();
+    }
+    return pid;
+}
+
+static void reap(pid_t pid)
+{
+    kill(pid, SIGKILL);
+    waitpid(pid, NULL, 0);
+}
+
+int main()
+{
+    long old_max = read_pid_max();
+    check(old_max > RESERVED_PIDS, "read pid_max");
+    if (old_max <= RESERVED_PIDS)
+    {
+        printf("test_pid_max: failed\n");
+        return 1;
+    }
+
+    errno = 0;
+    check(write_pid_max(RESERVED_PIDS) == -1 && errno == EINVAL, "pid_max <= RESERVED_PIDS");
+    check(read_pid_max() == old_max, "pid_max unchanged after invalid write");
+
+    check(write_pid_max(TINY_PID_MAX) == 0, "set tiny pid_max");
+    check(read_pid_max() == TINY_PID_MAX, "read back tiny pid_max");
+
+    /* 耗尽pid */
+    int exhausted = 0;
+    while (nr_children < MAX_CHILDREN)
+    {
+        errno = 0;
+        pid_t pid = spawn_sleeper();
+        if (pid < 0)
+        {
+            exhausted = errno == EAGAIN;
+            break;
+        }
+        children[nr_children++] = pid;
+    }
+    check(exhausted, "fork returns EAGAIN when pids are exhausted");
+
+    /* 回收一个子进程之后, 它的pid可以被重新分配 */
+    if (nr_children > 0)
+    {
+        reap(children[--nr_children]);
+        pid_t pid = spawn_sleeper();
+        check(pid > 0, "fork after reaping a child");
+        if (pid > 0)
+        {
+            check(pid < TINY_PID_MAX, "new pid is below pid_max");
+            children[nr_children++] = pid;
+        }
+    }
+
+    check(write_pid_max(old_max) == 0, "restore pid_max");
+    while (nr_children > 0)
+        reap(children[--nr_children]);
+
+    pid_t pid = fork();
+    if (pid == 0)
+        _exit(0);
+    check(pid > 0, "fork after restoring pid_max");
+    if (pid > 0)
+        waitpid(pid, NULL, 0);
+
+    return test_report();
+}
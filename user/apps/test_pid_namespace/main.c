@@ -0,0 +1,126 @@
+/**
+ * 测试CLONE_NEWPID:
+ * 1. 新pid namespace中的第一个进程的pid为1, 它的父进程位于namespace之外, 因此getppid()返回0
+ * 2. namespace中的进程fork出的子进程, 在namespace中的pid从2开始分配,
+ *    并且可以使用namespace中的pid进行waitpid
+ * 3. 父进程看到的是子进程在父进程所在namespace中的pid, 它不等于1
+ * 4. 子进程与父进程的/proc/<pid>/ns/pid不同
+ * 5. CLONE_NEWPID与CLONE_THREAD不能同时使用
+ * 6. tgkill使用namespace中的id指定目标线程
+ * 7. getpgid、setpgid、getsid使用namespace中的id, 位于namespace之外的进程组和会话的id为0
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_pid_namespace"
+#include "test_check.h"
+
+static volatile sig_atomic_t got_sigusr1 = 0;
+
+/* 与fork相同, 但是可以指定额外的clone标志 */
+static pid_t clone_fork(unsigned long flags)
+{
+    return syscall(SYS_clone, flags | SIGCHLD, 0, NULL, NULL, 0);
+}
+
+static void read_ns(pid_t pid, char *buf, size_t size)
+{
+    char path[64];
+    snprintf(path, sizeof(path), "/proc/%d/ns/pid", pid);
+    buf[0] = '\0';
+    int fd = open(path, O_RDONLY);
+    if (fd < 0)
+        return;
+    ssize_t n = read(fd, buf, size - 1);
+    buf[n > 0 ? n : 0] = '\0';
+    close(fd);
+}
+
+static void sigusr1_handler(int sig)
+{
+    (void)sig;
+    got_sigusr1 = 1;
+}
+
+/* 新namespace中的init进程 */
+static int ns_init(int ready_fd)
+{
+    check(getpid() == 1, "pid in new namespace is 1");
+    check(getppid() == 0, "parent outside namespace is invisible");
+    check(syscall(SYS_gettid) == 1, "tid in new namespace is 1");
+
+    signal(SIGUSR1, sigusr1_handler);
+    check(syscall(SYS_tgkill, 1, 1, SIGUSR1) == 0 && got_sigusr1, "tgkill by namespace ids");
+
+    /* 进程组与会话继承自namespace之外的父进程 */
+    check(getpgid(0) == 0, "process group outside namespace is invisible");
+    check(getsid(0) == 0, "session outside namespace is invisible");
+    check(setpgid(0, 0) == 0, "setpgid");
+    check(getpgid(0) == 1 && getpgid(1) == 1, "getpgid returns namespace id");
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        check(getpid() == 2, "second process in namespace is 2");
+        check(getppid() == 1, "parent of second process is namespace init");
+        check(getpgid(0) == 1, "child inherits the namespace process group");
+        check(setpgid(0, 2) == 0 && getpgid(2) == 2, "setpgid by namespace id");
+        _exit(failed);
+    }
+    check(pid == 2, "fork returns pid in namespace");
+
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0,
+          "wait for child by namespace pid");
+
+    /* 等待父进程读取/proc/<pid>/ns/pid之后再退出 */
+    char c;
+    read(ready_fd, &c, 1);
+    return failed;
+}
+
+int main()
+{
+    errno = 0;
+    check(clone_fork(CLONE_NEWPID | CLONE_THREAD | CLONE_SIGHAND | CLONE_VM) == -1 &&
+              errno == EINVAL,
+          "reject CLONE_NEWPID | CLONE_THREAD");
+
+    int pipefd[2];
+    check(pipe(pipefd) == 0, "pipe");
+
+    pid_t pid = clone_fork(CLONE_NEWPID);
+    if (pid == 0)
+    {
+        close(pipefd[1]);
+        _exit(ns_init(pipefd[0]));
+    }
+    close(pipefd[0]);
+    check(pid > 1, "parent sees pid in its own namespace");
+
+    char self_ns[64], child_ns[64];
+    read_ns(getpid(), self_ns, sizeof(self_ns));
+    read_ns(pid, child_ns, sizeof(child_ns));
+    check(strncmp(self_ns, "pid:[", 5) == 0, "read own pid namespace");
+    check(strncmp(child_ns, "pid:[", 5) == 0, "read child pid namespace");
+    check(strcmp(self_ns, child_ns) != 0, "child is in a different pid namespace");
+
+    write(pipefd[1], "x", 1);
+    close(pipefd[1]);
+
+    int status = 0;
+    check(pid > 0 && waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+              WEXITSTATUS(status) == 0,
+          "child exit status");
+
+    return test_report();
+}
@@ -0,0 +1,102 @@
+/**
+ * 测试/proc/<pid>/cmdline:
+ * 1. 文件的内容为进程的命令行参数, 每个参数以\0结尾
+ * 2. fork出的子进程在execve之前, 命令行参数与父进程相同, 并且其他进程也能读取
+ * 3. execve之后, 文件的内容为新的命令行参数(包括含有空格的参数与空参数)
+ */
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_proc_cmdline"
+#include "test_check.h"
+
+#define SELF_PATH "/bin/test_proc_cmdline"
+
+/* 读取/proc/<pid>/cmdline, 返回读取到的长度 */
+static ssize_t read_cmdline(pid_t pid, char *buf, size_t size)
+{
+    char path[64];
+    snprintf(path, sizeof(path), "/proc/%d/cmdline", pid);
+    int fd = open(path, O_RDONLY);
+    if (fd < 0)
+        return -1;
+    ssize_t total = 0, n;
+    while ((n = read(fd, buf + total, size - total)) > 0)
+        total += n;
+    close(fd);
+    return n < 0 ? -1 : total;
+}
+
+/* 将参数拼接为cmdline的格式 */
+static size_t join_args(char *const argv[], char *buf, size_t size)
+{
+    size_t len = 0;
+    for (int i = 0; argv[i] != NULL; i++)
+    {
+        size_t n = strlen(argv[i]) + 1;
+        if (len + n > size)
+            break;
+        memcpy(buf + len, argv[i], n);
+        len += n;
+    }
+    return len;
+}
+
+/* 检查进程pid的cmdline是否与argv一致 */
+static void check_cmdline(pid_t pid, char *const argv[], const char *what)
+{
+    char expected[1024], buf[1024];
+    size_t len = join_args(argv, expected, sizeof(expected));
+    ssize_t n = read_cmdline(pid, buf, sizeof(buf));
+    check(n == (ssize_t)len && memcmp(buf, expected, len) == 0, what);
+}
+
+int main(int argc, char *argv[])
+{
+    if (argc > 1 && strcmp(argv[1], "exec") == 0)
+    {
+        check_cmdline(getpid(), argv, "cmdline after execve");
+        return failed;
+    }
+
+    check_cmdline(getpid(), argv, "own cmdline");
+
+    /* fork出的子进程与父进程的命令行参数相同 */
+    int pipefd[2];
+    check(pipe(pipefd) == 0, "pipe");
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        close(pipefd[1]);
+        check_cmdline(getpid(), argv, "forked child cmdline");
+        char c;
+        read(pipefd[0], &c, 1);
+        _exit(failed);
+    }
+    close(pipefd[0]);
+    check(pid > 0, "fork");
+    check_cmdline(pid, argv, "read forked child cmdline from parent");
+    close(pipefd[1]);
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0,
+          "forked child exit status");
+
+    /* execve之后的命令行参数 */
+    pid = fork();
+    if (pid == 0)
+    {
+        char *const new_argv[] = {"test_proc_cmdline", "exec", "hello world", "", "last", NULL};
+        execv(SELF_PATH, new_argv);
+        _exit(127);
+    }
+    check(pid > 0, "fork for execve");
+    check(waitpid(pid, &status, 0) == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0,
+          "execve child exit status");
+
+    return test_report();
+}
@@ -0,0 +1,115 @@
+/**
+ * 测试/proc/<pid>/maps:
+ * 1. 通过mmap映射的私有匿名内存出现在maps中, 地址范围与权限(rw-p)正确
+ * 2. 共享映射的权限的最后一位为s
+ * 3. mprotect之后, 映射被拆分, 被修改的部分显示新的权限
+ * 4. munmap之后, 映射不再出现在maps中
+ * 5. fork出的子进程(写时复制)中, 私有映射仍然显示为p, 共享映射仍然显示为s
+ * 6. 用户栈被标记为[stack]
+ */
+
+#include <errno.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_proc_maps"
+#include "test_check.h"
+
+/* 在maps中查找包含[start, end)的映射(相邻的映射可能被合并), 找到时把权限写入perms并返回0 */
+static int find_mapping(unsigned long start, unsigned long end, char perms[5])
+{
+    char line[512];
+    FILE *f = fopen("/proc/self/maps", "r");
+    if (f == NULL)
+        return -1;
+    int ret = -1;
+    while (fgets(line, sizeof(line), f) != NULL)
+    {
+        unsigned long s, e;
+        char p[5];
+        if (sscanf(line, "%lx-%lx %4s", &s, &e, p) != 3)
+            continue;
+        if (s <= start && end <= e)
+        {
+            memcpy(perms, p, 5);
+            ret = 0;
+            break;
+        }
+    }
+    fclose(f);
+    return ret;
+}
+
+/* 检查包含[start, end)的映射存在并且权限为expected */
+static void check_mapping(void *start, size_t len, const char *expected, const char *what)
+{
+    char perms[5] = {0};
+    unsigned long s = (unsigned long)start;
+    check(find_mapping(s, s + len, perms) == 0 && strcmp(perms, expected) == 0, what);
+}
+
+/* 检查maps中是否有包含当前栈上的变量的[stack]映射 */
+static void check_stack(void)
+{
+    char line[512];
+    int local = 0;
+    unsigned long addr = (unsigned long)&local;
+    int found = 0;
+    FILE *f = fopen("/proc/self/maps", "r");
+    check(f != NULL, "open maps");
+    if (f == NULL)
+        return;
+    while (fgets(line, sizeof(line), f) != NULL)
+    {
+        unsigned long s, e;
+        if (sscanf(line, "%lx-%lx", &s, &e) == 2 && s <= addr && addr < e)
+            found = strstr(line, "[stack]") != NULL;
+    }
+    fclose(f);
+    check(found, "stack is marked as [stack]");
+}
+
+int main()
+{
+    long page = sysconf(_SC_PAGESIZE);
+
+    char *priv = mmap(NULL, 4 * page, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(priv != MAP_FAILED, "mmap private");
+    char *shared = mmap(NULL, 2 * page, PROT_READ, MAP_SHARED | MAP_ANONYMOUS, -1, 0);
+    check(shared != MAP_FAILED, "mmap shared");
+    memset(priv, 1, 4 * page);
+
+    check_mapping(priv, 4 * page, "rw-p", "private mapping");
+    check_mapping(shared, 2 * page, "r--s", "shared mapping");
+    check_stack();
+
+    /* 把私有映射的后半部分改为只读 */
+    check(mprotect(priv + 2 * page, 2 * page, PROT_READ) == 0, "mprotect");
+    check_mapping(priv, 2 * page, "rw-p", "unchanged part after mprotect");
+    check_mapping(priv + 2 * page, 2 * page, "r--p", "changed part after mprotect");
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        check_mapping(priv, 2 * page, "rw-p", "private mapping in child");
+        check_mapping(shared, 2 * page, "r--s", "shared mapping in child");
+        /* 写入之后复制出私有的页面, 映射仍然是私有的 */
+        priv[0] = 2;
+        check_mapping(priv, 2 * page, "rw-p", "private mapping in child after write");
+        _exit(failed);
+    }
+    check(pid > 0, "fork");
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0,
+          "child exit status");
+
+    check(munmap(shared, 2 * page) == 0, "munmap");
+    char perms[5];
+    unsigned long s = (unsigned long)shared;
+    check(find_mapping(s, s + 2 * page, perms) != 0, "mapping removed after munmap");
+
+    return test_report();
+}
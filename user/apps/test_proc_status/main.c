@@ -0,0 +1,168 @@
+/**
+ * 测试/proc/<pid>/status:
+ * 1. Name与/proc/<pid>/comm一致, Tgid、Pid、PPid正确, 存在Uid与Gid字段
+ * 2. State: 运行中为R, 睡眠为S, 被SIGSTOP停止为T, 退出之后未被回收为Z
+ * 3. Threads为线程组中的线程数
+ * 4. SigBlk、SigIgn、SigCgt分别反映被屏蔽、被忽略、设置了处理函数的信号
+ * 5. VmSize与VmRSS不为0
+ */
+
+#include <errno.h>
+#include <pthread.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_proc_status"
+#include "test_check.h"
+
+/* 读取/proc/<pid>/status中字段key的值(去掉开头的空白与结尾的换行符), 不存在时返回-1 */
+static int read_field(pid_t pid, const char *key, char *value, size_t size)
+{
+    char path[64], line[256];
+    size_t len = strlen(key);
+    snprintf(path, sizeof(path), "/proc/%d/status", pid);
+    FILE *f = fopen(path, "r");
+    if (f == NULL)
+        return -1;
+    int ret = -1;
+    while (fgets(line, sizeof(line), f) != NULL)
+    {
+        if (strncmp(line, key, len) == 0 && line[len] == ':')
+        {
+            char *p = line + len + 1;
+            while (*p == ' ' || *p == '\t')
+                p++;
+            p[strcspn(p, "\n")] = '\0';
+            snprintf(value, size, "%s", p);
+            ret = 0;
+            break;
+        }
+    }
+    fclose(f);
+    return ret;
+}
+
+static long read_long(pid_t pid, const char *key)
+{
+    char value[128];
+    if (read_field(pid, key, value, sizeof(value)) != 0)
+        return -1;
+    return strtol(value, NULL, 10);
+}
+
+static unsigned long long read_mask(pid_t pid, const char *key)
+{
+    char value[128];
+    if (read_field(pid, key, value, sizeof(value)) != 0)
+        return 0;
+    return strtoull(value, NULL, 16);
+}
+
+static char read_state(pid_t pid)
+{
+    char value[128];
+    if (read_field(pid, "State", value, sizeof(value)) != 0)
+        return '?';
+    return value[0];
+}
+
+static unsigned long long sig_bit(int sig)
+{
+    return 1ULL << (sig - 1);
+}
+
+static void usr1_handler(int sig)
+{
+    (void)sig;
+}
+
+static pthread_mutex_t lock = PTHREAD_MUTEX_INITIALIZER;
+
+static void *thread_main(void *arg)
+{
+    (void)arg;
+    pthread_mutex_lock(&lock);
+    pthread_mutex_unlock(&lock);
+    return NULL;
+}
+
+int main()
+{
+    pid_t self = getpid();
+    char value[128], comm[64];
+
+    /* 基本字段 */
+    FILE *f = fopen("/proc/self/comm", "r");
+    check(f != NULL && fgets(comm, sizeof(comm), f) != NULL, "read comm");
+    if (f != NULL)
+        fclose(f);
+    comm[strcspn(comm, "\n")] = '\0';
+    check(read_field(self, "Name", value, sizeof(value)) == 0 && strcmp(value, comm) == 0,
+          "Name matches comm");
+    check(read_state(self) == 'R', "State of running process");
+    check(read_long(self, "Tgid") == self, "Tgid");
+    check(read_long(self, "Pid") == self, "Pid");
+    check(read_long(self, "PPid") == getppid(), "PPid");
+    check(read_field(self, "Uid", value, sizeof(value)) == 0, "Uid present");
+    check(read_field(self, "Gid", value, sizeof(value)) == 0, "Gid present");
+    check(read_long(self, "VmSize") > 0, "VmSize");
+    check(read_long(self, "VmRSS") > 0, "VmRSS");
+
+    /* 线程数 */
+    check(read_long(self, "Threads") == 1, "Threads of single-threaded process");
+    pthread_t thread;
+    pthread_mutex_lock(&lock);
+    check(pthread_create(&thread, NULL, thread_main, NULL) == 0, "pthread_create");
+    check(read_long(self, "Threads") == 2, "Threads after pthread_create");
+    pthread_mutex_unlock(&lock);
+    pthread_join(thread, NULL);
+
+    /* 信号屏蔽字与处理方式 */
+    sigset_t set;
+    sigemptyset(&set);
+    sigaddset(&set, SIGTERM);
+    check(sigprocmask(SIG_BLOCK, &set, NULL) == 0, "block SIGTERM");
+    check(signal(SIGUSR2, SIG_IGN) != SIG_ERR, "ignore SIGUSR2");
+    check(signal(SIGUSR1, usr1_handler) != SIG_ERR, "catch SIGUSR1");
+    check(read_mask(self, "SigBlk") & sig_bit(SIGTERM), "SigBlk contains SIGTERM");
+    check(read_mask(self, "SigIgn") & sig_bit(SIGUSR2), "SigIgn contains SIGUSR2");
+    check(!(read_mask(self, "SigIgn") & sig_bit(SIGUSR1)), "SigIgn excludes SIGUSR1");
+    check(read_mask(self, "SigCgt") & sig_bit(SIGUSR1), "SigCgt contains SIGUSR1");
+    check(!(read_mask(self, "SigCgt") & sig_bit(SIGUSR2)), "SigCgt excludes SIGUSR2");
+    sigprocmask(SIG_UNBLOCK, &set, NULL);
+
+    /* 子进程的状态 */
+    int pipefd[2];
+    check(pipe(pipefd) == 0, "pipe");
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        close(pipefd[1]);
+        char c;
+        read(pipefd[0], &c, 1);
+        _exit(0);
+    }
+    close(pipefd[0]);
+    check(pid > 0, "fork");
+    usleep(100000);
+    check(read_state(pid) == 'S', "State of sleeping child");
+    check(read_long(pid, "PPid") == self, "PPid of child");
+
+    check(kill(pid, SIGSTOP) == 0, "stop child");
+    int status = 0;
+    check(waitpid(pid, &status, WUNTRACED) == pid && WIFSTOPPED(status), "wait stopped child");
+    check(read_state(pid) == 'T', "State of stopped child");
+    check(kill(pid, SIGCONT) == 0, "continue child");
+
+    close(pipefd[1]);
+    siginfo_t info;
+    check(waitid(P_PID, pid, &info, WEXITED | WNOWAIT) == 0, "wait child exit without reaping");
+    check(read_state(pid) == 'Z', "State of zombie child");
+    check(waitpid(pid, &status, 0) == pid, "reap child");
+
+    return test_report();
+}
@@ -0,0 +1,129 @@
+/**
+ * 测试ptrace的基本功能:
+ * 1. PTRACE_ATTACH之后, 被跟踪者停止, 跟踪者通过waitpid看到SIGSTOP导致的停止
+ * 2. PTRACE_GETREGS能读取被跟踪者的寄存器, PTRACE_SETREGS写回之后寄存器不变
+ * 3. 被跟踪者收到信号时停止, PTRACE_CONT指定的信号被递送给被跟踪者
+ * 4. PTRACE_TRACEME之后, 子进程收到的信号在PTRACE_CONT 0时被丢弃
+ * 5. 没有被跟踪或者没有停止的进程不能被PTRACE_CONT; PTRACE_DETACH之后进程不再被跟踪
+ */
+
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/ptrace.h>
+#include <sys/user.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_ptrace"
+#include "test_check.h"
+
+static volatile sig_atomic_t got_usr1 = 0;
+
+static void usr1_handler(int sig)
+{
+    (void)sig;
+    got_usr1 = 1;
+}
+
+/* 等待被跟踪者因为信号sig而停止 */
+static void wait_stopped(pid_t pid, int sig, const char *what)
+{
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid && WIFSTOPPED(status) && WSTOPSIG(status) == sig, what);
+}
+
+static void wait_exited(pid_t pid, int code, const char *what)
+{
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid && WIFEXITED(status) && WEXITSTATUS(status) == code,
+          what);
+}
+
+static void test_attach(void)
+{
+    int pipefd[2];
+    check(pipe(pipefd) == 0, "pipe");
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        signal(SIGUSR1, usr1_handler);
+        close(pipefd[1]);
+        char c;
+        /* 父进程关闭管道时继续 */
+        while (read(pipefd[0], &c, 1) != 0)
+            ;
+        _exit(got_usr1 ? 0 : 1);
+    }
+    close(pipefd[0]);
+    check(pid > 0, "fork");
+
+    errno = 0;
+    check(ptrace(PTRACE_CONT, pid, NULL, NULL) == -1 && errno == ESRCH, "cont untraced child");
+
+    check(ptrace(PTRACE_ATTACH, pid, NULL, NULL) == 0, "attach");
+    wait_stopped(pid, SIGSTOP, "stopped by attach");
+
+    struct user_regs_struct regs, regs2;
+    memset(&regs, 0, sizeof(regs));
+    check(ptrace(PTRACE_GETREGS, pid, NULL, &regs) == 0, "getregs");
+#if defined(__x86_64__)
+    check(regs.rip != 0 && regs.rsp != 0, "registers are valid");
+#endif
+    check(ptrace(PTRACE_SETREGS, pid, NULL, &regs) == 0, "setregs");
+    memset(&regs2, 0, sizeof(regs2));
+    check(ptrace(PTRACE_GETREGS, pid, NULL, &regs2) == 0, "getregs after setregs");
+#if defined(__x86_64__)
+    check(regs2.rip == regs.rip && regs2.rsp == regs.rsp && regs2.rbx == regs.rbx,
+          "registers unchanged after setregs");
+#endif
+
+    /* 丢弃SIGSTOP, 让子进程继续运行 */
+    check(ptrace(PTRACE_CONT, pid, NULL, NULL) == 0, "cont after attach");
+
+    /* 子进程收到SIGUSR1时先停止, 由跟踪者递送这个信号 */
+    check(kill(pid, SIGUSR1) == 0, "kill SIGUSR1");
+    wait_stopped(pid, SIGUSR1, "stopped by SIGUSR1");
+    check(ptrace(PTRACE_CONT, pid, NULL, (void *)(long)SIGUSR1) == 0, "cont with SIGUSR1");
+
+    /* 再次停止之后脱离跟踪 */
+    check(kill(pid, SIGUSR2) == 0, "kill SIGUSR2");
+    wait_stopped(pid, SIGUSR2, "stopped by SIGUSR2");
+    check(ptrace(PTRACE_DETACH, pid, NULL, NULL) == 0, "detach");
+    errno = 0;
+    check(ptrace(PTRACE_GETREGS, pid, NULL, &regs) == -1 && errno == ESRCH,
+          "getregs after detach");
+
+    close(pipefd[1]);
+    wait_exited(pid, 0, "child got SIGUSR1 from tracer");
+}
+
+static void test_traceme(void)
+{
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        if (ptrace(PTRACE_TRACEME, 0, NULL, NULL) != 0)
+            _exit(2);
+        /* 已经被跟踪的进程不能再次PTRACE_TRACEME */
+        if (ptrace(PTRACE_TRACEME, 0, NULL, NULL) != -1 || errno != EPERM)
+            _exit(3);
+        raise(SIGUSR1);
+        /* SIGUSR1被跟踪者丢弃, 否则进程会被它杀死 */
+        _exit(0);
+    }
+    check(pid > 0, "fork");
+    wait_stopped(pid, SIGUSR1, "traceme child stopped by SIGUSR1");
+    check(ptrace(PTRACE_CONT, pid, NULL, NULL) == 0, "cont traceme child");
+    wait_exited(pid, 0, "traceme child exit");
+}
+
+int main()
+{
+    test_attach();
+    test_traceme();
+
+    return test_report();
+}
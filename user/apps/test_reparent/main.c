@@ -0,0 +1,87 @@
+/**
+ * 测试孤儿进程的收养:
+ * 1. 父进程fork出子进程, 子进程再fork出孙进程, 然后子进程立即退出
+ * 2. 孙进程成为孤儿进程后, 它的父进程应当变为init进程(pid 1)
+ * 3. 原父进程只能回收自己的子进程, 对孙进程调用wait4应当返回ECHILD
+ */
+
+#include <errno.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+int main()
+{
+    int pipefd[2];
+    if (pipe(pipefd) == -1)
+    {
+        perror("pipe");
+        return 1;
+    }
+
+    pid_t child = fork();
+    if (child < 0)
+    {
+        perror("fork");
+        return 1;
+    }
+
+    if (child == 0)
+    {
+        pid_t grandchild = fork();
+        if (grandchild < 0)
+        {
+            perror("fork");
+            exit(1);
+        }
+
+        if (grandchild == 0)
+        {
+            close(pipefd[0]);
+            // 等待父进程退出, 使自己成为孤儿进程
+            while (getppid() != 1)
+                usleep(1000);
+            write(pipefd[1], "ok", 2);
+            close(pipefd[1]);
+            exit(0);
+        }
+
+        // 将孙进程的pid告诉原父进程
+        write(pipefd[1], &grandchild, sizeof(grandchild));
+        exit(0);
+    }
+
+    close(pipefd[1]);
+
+    pid_t grandchild;
+    if (read(pipefd[0], &grandchild, sizeof(grandchild)) != sizeof(grandchild))
+    {
+        printf("failed to read grandchild pid\n");
+        return 1;
+    }
+
+    int status;
+    if (waitpid(child, &status, 0) != child || !WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("failed to reap child %d\n", child);
+        return 1;
+    }
+
+    char buf[2];
+    if (read(pipefd[0], buf, sizeof(buf)) != sizeof(buf))
+    {
+        printf("grandchild %d was not reparented to init\n", grandchild);
+        return 1;
+    }
+    close(pipefd[0]);
+
+    if (waitpid(grandchild, &status, WNOHANG) != -1 || errno != ECHILD)
+    {
+        printf("grandchild %d should not be waitable by its grandparent\n", grandchild);
+        return 1;
+    }
+
+    printf("test_reparent: ok\n");
+    return 0;
+}
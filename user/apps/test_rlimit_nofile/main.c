@@ -0,0 +1,127 @@
+/**
+ * 测试RLIMIT_NOFILE对文件描述符分配的限制:
+ * 1. 降低软限制后, 编号达到限制的文件描述符无法再被分配, open返回EMFILE
+ * 2. 已经打开的文件描述符不受影响, 仍然可以正常使用
+ * 3. dup2的目标文件描述符超过限制时返回EBADF
+ * 4. 子进程继承父进程的限制
+ */
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <sys/resource.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_FILE "/bin/test_rlimit_nofile"
+#define LIMIT 16
+
+int main()
+{
+    struct rlimit old_rl;
+    if (getrlimit(RLIMIT_NOFILE, &old_rl) != 0)
+    {
+        perror("getrlimit");
+        return 1;
+    }
+
+    // 在降低限制之前打开一个编号较大的文件描述符
+    int high_fd = open(TEST_FILE, O_RDONLY);
+    if (high_fd < 0 || dup2(high_fd, LIMIT + 4) != LIMIT + 4)
+    {
+        perror("open");
+        return 1;
+    }
+    close(high_fd);
+    high_fd = LIMIT + 4;
+
+    struct rlimit rl = {.rlim_cur = LIMIT, .rlim_max = old_rl.rlim_max};
+    if (setrlimit(RLIMIT_NOFILE, &rl) != 0)
+    {
+        perror("setrlimit");
+        return 1;
+    }
+
+    struct rlimit cur_rl;
+    if (getrlimit(RLIMIT_NOFILE, &cur_rl) != 0 || cur_rl.rlim_cur != LIMIT)
+    {
+        printf("getrlimit does not report the new limit\n");
+        return 1;
+    }
+
+    int failed = 0;
+    int last_fd = -1;
+    for (;;)
+    {
+        int fd = open(TEST_FILE, O_RDONLY);
+        if (fd < 0)
+        {
+            if (errno != EMFILE)
+            {
+                printf("open failed with errno %d, expected EMFILE\n", errno);
+                failed = 1;
+            }
+            break;
+        }
+        if (fd >= LIMIT)
+        {
+            printf("got fd %d beyond the limit %d\n", fd, LIMIT);
+            failed = 1;
+            break;
+        }
+        last_fd = fd;
+    }
+
+    if (last_fd != LIMIT - 1)
+    {
+        printf("expected the last fd to be %d, got %d\n", LIMIT - 1, last_fd);
+        failed = 1;
+    }
+
+    // 已经打开的文件描述符仍然可以使用
+    char buf[4];
+    if (read(last_fd, buf, sizeof(buf)) != sizeof(buf) || read(high_fd, buf, sizeof(buf)) != sizeof(buf))
+    {
+        printf("existing fds are not usable\n");
+        failed = 1;
+    }
+
+    if (dup2(last_fd, LIMIT + 1) != -1 || errno != EBADF)
+    {
+        printf("dup2 beyond the limit should fail with EBADF\n");
+        failed = 1;
+    }
+
+    // 关闭一个文件描述符后, 可以再次打开
+    close(last_fd);
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        struct rlimit child_rl;
+        if (getrlimit(RLIMIT_NOFILE, &child_rl) != 0 || child_rl.rlim_cur != LIMIT)
+            _exit(1);
+        _exit(0);
+    }
+    int status;
+    if (pid < 0 || waitpid(pid, &status, 0) != pid || !WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("child did not inherit the limit\n");
+        failed = 1;
+    }
+
+    if (open(TEST_FILE, O_RDONLY) != last_fd)
+    {
+        printf("failed to reopen fd %d after closing it\n", last_fd);
+        failed = 1;
+    }
+
+    if (failed)
+    {
+        printf("test_rlimit_nofile: failed\n");
+        return 1;
+    }
+
+    printf("test_rlimit_nofile: ok\n");
+    return 0;
+}
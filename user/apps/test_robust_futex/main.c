@@ -0,0 +1,207 @@
+/**
+ * 测试robust futex列表:
+ * 1. set_robust_list拒绝长度错误的头部, get_robust_list返回注册的头部地址与长度
+ * 2. fork出的子进程不继承robust list
+ * 3. 持有robust futex的进程被SIGKILL杀死之后, futex字被标记为FUTEX_OWNER_DIED, 并且持有者的tid被清除
+ * 4. 持有robust futex的线程退出时, 正在等待这个futex的线程被唤醒, 并且看到FUTEX_OWNER_DIED
+ * 5. 通过CLONE_CHILD_CLEARTID等待线程退出的线程被唤醒时, robust futex已经被标记为FUTEX_OWNER_DIED
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <linux/futex.h>
+#include <sched.h>
+#include <signal.h>
+#include <stddef.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_robust_futex"
+#include "test_check.h"
+
+#define STACK_SIZE (64 * 1024)
+
+struct robust_lock
+{
+    struct robust_list node;
+    volatile int futex;
+};
+
+static long set_robust_list(struct robust_list_head *head, size_t len)
+{
+    return syscall(SYS_set_robust_list, head, len);
+}
+
+static long get_robust_list(int pid, struct robust_list_head **head, size_t *len)
+{
+    return syscall(SYS_get_robust_list, pid, head, len);
+}
+
+static long futex(volatile int *uaddr, int op, int val)
+{
+    return syscall(SYS_futex, uaddr, op, val, NULL, NULL, 0);
+}
+
+/* 注册一个空的robust list */
+static void init_robust_list(struct robust_list_head *head)
+{
+    head->list.next = &head->list;
+    head->futex_offset = offsetof(struct robust_lock, futex);
+    head->list_op_pending = NULL;
+}
+
+/* 与libc相同: 获取锁, 并把它加入当前线程的robust list */
+static void robust_lock(struct robust_list_head *head, struct robust_lock *lock)
+{
+    head->list_op_pending = &lock->node;
+    __atomic_store_n(&lock->futex, (int)syscall(SYS_gettid), __ATOMIC_SEQ_CST);
+    lock->node.next = head->list.next;
+    head->list.next = &lock->node;
+    head->list_op_pending = NULL;
+}
+
+static int owner_died(int val)
+{
+    return (val & FUTEX_OWNER_DIED) && (val & FUTEX_TID_MASK) == 0;
+}
+
+static struct robust_list_head thread_head;
+static struct robust_lock *thread_lock;
+
+/* 持有锁之后, 等待其他线程开始等待这个锁, 然后不释放锁直接退出 */
+static int holder_thread(void *arg)
+{
+    int wait_for_waiter = (int)(long)arg;
+    struct timespec ts = {0, 50 * 1000 * 1000};
+
+    init_robust_list(&thread_head);
+    if (set_robust_list(&thread_head, sizeof(thread_head)) != 0)
+        syscall(SYS_exit, 1);
+    robust_lock(&thread_head, thread_lock);
+
+    if (wait_for_waiter)
+    {
+        while (!(__atomic_load_n(&thread_lock->futex, __ATOMIC_SEQ_CST) & FUTEX_WAITERS))
+            ;
+        /* 确保等待者已经进入futex_wait */
+        syscall(SYS_nanosleep, &ts, NULL);
+    }
+    syscall(SYS_exit, 0);
+    return 0;
+}
+
+static pid_t clone_holder(char *stack, int wait_for_waiter, volatile int *ctid)
+{
+    int flags = CLONE_VM | CLONE_FS | CLONE_FILES | SIGCHLD;
+    if (ctid != NULL)
+        flags |= CLONE_CHILD_SETTID | CLONE_CHILD_CLEARTID;
+    return clone(holder_thread, stack + STACK_SIZE, flags, (void *)(long)wait_for_waiter, NULL,
+                 NULL, ctid);
+}
+
+/* 直接使用clone系统调用fork, 避免libc在子进程中重新注册robust list */
+static pid_t raw_fork(void)
+{
+    return syscall(SYS_clone, SIGCHLD, 0, NULL, NULL, 0);
+}
+
+static void wait_child(pid_t pid, const char *what)
+{
+    int status = 0;
+    check(pid > 0 && waitpid(pid, &status, __WALL) == pid && WIFEXITED(status) &&
+              WEXITSTATUS(status) == 0,
+          what);
+}
+
+int main()
+{
+    struct robust_list_head head;
+    struct robust_list_head *got = NULL;
+    size_t len = 0;
+
+    init_robust_list(&head);
+    errno = 0;
+    check(set_robust_list(&head, sizeof(head) - 1) == -1 && errno == EINVAL, "reject bad len");
+    check(set_robust_list(&head, sizeof(head)) == 0, "set_robust_list");
+    check(get_robust_list(0, &got, &len) == 0 && got == &head && len == sizeof(head),
+          "get_robust_list");
+
+    /* fork出的子进程需要重新注册robust list */
+    pid_t pid = raw_fork();
+    if (pid == 0)
+    {
+        got = &head;
+        len = 0;
+        _exit(get_robust_list(0, &got, &len) == 0 && got == NULL && len == sizeof(head) ? 0 : 1);
+    }
+    wait_child(pid, "fork child has no robust list");
+
+    /* 持有锁的进程被杀死 */
+    struct robust_lock *shared = mmap(NULL, sizeof(struct robust_lock), PROT_READ | PROT_WRITE,
+                                      MAP_SHARED | MAP_ANONYMOUS, -1, 0);
+    check(shared != MAP_FAILED, "mmap shared lock");
+    int pipefd[2];
+    check(pipe(pipefd) == 0, "pipe");
+    pid = fork();
+    if (pid == 0)
+    {
+        struct robust_list_head child_head;
+        init_robust_list(&child_head);
+        if (set_robust_list(&child_head, sizeof(child_head)) != 0)
+            _exit(1);
+        robust_lock(&child_head, shared);
+        write(pipefd[1], "x", 1);
+        for (;;)
+            pause();
+    }
+    char c;
+    check(pid > 0 && read(pipefd[0], &c, 1) == 1, "wait for lock holder");
+    check(shared->futex == pid, "lock held by child");
+    check(kill(pid, SIGKILL) == 0, "kill lock holder");
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid && WIFSIGNALED(status), "wait lock holder");
+    check(owner_died(shared->futex), "FUTEX_OWNER_DIED after SIGKILL");
+
+    char *stack = malloc(STACK_SIZE);
+    check(stack != NULL, "malloc stack");
+
+    /* 等待者被唤醒 */
+    thread_lock = calloc(1, sizeof(struct robust_lock));
+    pid = clone_holder(stack, 1, NULL);
+    check(pid > 0, "clone holder");
+    int val;
+    while ((val = __atomic_load_n(&thread_lock->futex, __ATOMIC_SEQ_CST)) != pid)
+        ;
+    check(__atomic_compare_exchange_n(&thread_lock->futex, &val, val | FUTEX_WAITERS, 0,
+                                      __ATOMIC_SEQ_CST, __ATOMIC_SEQ_CST),
+          "set FUTEX_WAITERS");
+    while ((val = __atomic_load_n(&thread_lock->futex, __ATOMIC_SEQ_CST)) & FUTEX_TID_MASK)
+    {
+        if (futex(&thread_lock->futex, FUTEX_WAIT, val) != 0 && errno != EAGAIN && errno != EINTR)
+        {
+            check(0, "futex wait");
+            break;
+        }
+    }
+    check(owner_died(val) && (val & FUTEX_WAITERS), "waiter sees FUTEX_OWNER_DIED");
+    wait_child(pid, "holder thread exit");
+
+    /* robust list在clear_child_tid之前处理 */
+    static volatile int ctid;
+    thread_lock->futex = 0;
+    ctid = -1;
+    pid = clone_holder(stack, 0, &ctid);
+    check(pid > 0, "clone holder with CLONE_CHILD_CLEARTID");
+    while ((val = ctid) != 0)
+        futex(&ctid, FUTEX_WAIT, val);
+    check(owner_died(thread_lock->futex), "FUTEX_OWNER_DIED before clear_child_tid");
+    wait_child(pid, "holder thread with CLONE_CHILD_CLEARTID exit");
+
+    return test_report();
+}
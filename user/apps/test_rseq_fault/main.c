@@ -0,0 +1,70 @@
+/**
+ * 测试rseq对非法用户地址的处理:
+ * 1. 注册内核地址、未映射的地址或者只读的地址时, sys_rseq返回EFAULT
+ * 2. 注册之后rseq结构体被解除映射, 进程在返回用户态更新cpu_id时被SIGSEGV终止,
+ *    即使进程忽略了SIGSEGV
+ */
+
+#include <errno.h>
+#include <signal.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_rseq_fault"
+#include "test_check.h"
+
+#define RSEQ_SIG 0x53053053
+#define RSEQ_LEN 32
+
+#ifndef SYS_rseq
+#define SYS_rseq 334
+#endif
+
+static long sys_rseq(void *r)
+{
+    return syscall(SYS_rseq, r, RSEQ_LEN, 0, RSEQ_SIG);
+}
+
+static int rejected(void *r)
+{
+    errno = 0;
+    return sys_rseq(r) == -1 && errno == EFAULT;
+}
+
+int main()
+{
+    long page_size = sysconf(_SC_PAGESIZE);
+
+    check(rejected((void *)0xffff800000000000UL), "reject kernel address");
+
+    void *page = mmap(NULL, page_size, PROT_READ, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(page != MAP_FAILED, "mmap");
+    check(rejected(page), "reject read-only page");
+    munmap(page, page_size);
+    check(rejected(page), "reject unmapped page");
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        signal(SIGSEGV, SIG_IGN);
+        void *rs = mmap(NULL, page_size, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1,
+                        0);
+        if (rs == MAP_FAILED || sys_rseq(rs) != 0)
+            _exit(1);
+        munmap(rs, page_size);
+        /* 被调度出去之后，返回用户态时内核无法写入cpu_id */
+        for (int i = 0; i < 100; i++)
+            usleep(10000);
+        _exit(0);
+    }
+    int status = 0;
+    check(pid > 0 && waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFSIGNALED(status) && WTERMSIG(status) == SIGSEGV, "killed by SIGSEGV");
+
+    return test_report();
+}
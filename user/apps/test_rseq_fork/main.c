@@ -0,0 +1,113 @@
+/**
+ * 测试fork与execve时rseq注册信息的处理:
+ * 1. fork出的子进程没有注册rseq, 对父进程的结构体取消注册会返回EINVAL
+ * 2. 子进程可以再次调用sys_rseq注册, 并且注册之后cpu_id被内核更新
+ * 3. execve之后的进程没有注册rseq
+ */
+
+#include <errno.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define RSEQ_SIG 0x53053053
+#define RSEQ_FLAG_UNREGISTER 1
+#define RSEQ_CPU_ID_UNINITIALIZED ((uint32_t)-1)
+
+#ifndef SYS_rseq
+#define SYS_rseq 334
+#endif
+
+struct rseq
+{
+    uint32_t cpu_id_start;
+    uint32_t cpu_id;
+    uint64_t rseq_cs;
+    uint32_t flags;
+} __attribute__((aligned(32)));
+
+static struct rseq rs;
+
+static int sys_rseq(struct rseq *r, int flags)
+{
+    return syscall(SYS_rseq, r, sizeof(*r), flags, RSEQ_SIG);
+}
+
+/* 当前进程没有注册rseq时返回1 */
+static int unregistered(void)
+{
+    errno = 0;
+    return sys_rseq(&rs, RSEQ_FLAG_UNREGISTER) == -1 && errno == EINVAL;
+}
+
+static int registered_again(void)
+{
+    rs.cpu_id = RSEQ_CPU_ID_UNINITIALIZED;
+    if (sys_rseq(&rs, 0) != 0)
+        return 0;
+    return rs.cpu_id != RSEQ_CPU_ID_UNINITIALIZED && rs.cpu_id_start == rs.cpu_id;
+}
+
+int main(int argc, char *argv[])
+{
+    if (argc == 2 && strcmp(argv[1], "exec") == 0)
+        return unregistered() && registered_again() ? 0 : 1;
+
+    rs.cpu_id = RSEQ_CPU_ID_UNINITIALIZED;
+    if (sys_rseq(&rs, 0) != 0)
+    {
+        perror("rseq");
+        return 1;
+    }
+
+    int failed = 0;
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        if (!unregistered())
+            _exit(1);
+        if (!registered_again())
+            _exit(2);
+        _exit(0);
+    }
+    int status;
+    if (pid < 0 || waitpid(pid, &status, 0) != pid || !WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("forked child: rseq is %s\n",
+               WIFEXITED(status) && WEXITSTATUS(status) == 1 ? "still registered" : "not re-registrable");
+        failed = 1;
+    }
+
+    // 父进程的注册不受子进程影响
+    errno = 0;
+    if (sys_rseq(&rs, 0) != -1 || errno != EBUSY)
+    {
+        printf("parent lost its rseq registration after fork\n");
+        failed = 1;
+    }
+
+    pid = fork();
+    if (pid == 0)
+    {
+        execl("/bin/test_rseq_fork", "test_rseq_fork", "exec", NULL);
+        _exit(3);
+    }
+    if (pid < 0 || waitpid(pid, &status, 0) != pid || !WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("rseq is still registered after execve\n");
+        failed = 1;
+    }
+
+    if (failed)
+    {
+        printf("test_rseq_fork: failed\n");
+        return 1;
+    }
+
+    printf("test_rseq_fork: ok\n");
+    return 0;
+}
@@ -0,0 +1,178 @@
+/**
+ * 测试进程迁移到其他cpu之后, rseq的cpu_id_start与cpu_id:
+ * 1. 注册rseq之后, cpu_id_start与cpu_id都被初始化为当前cpu
+ * 2. 进程绑定到cpu A之后, 通过sched_setaffinity把自己迁移到cpu B,
+ *    两个字段都更新为B, 并且每次读取时cpu_id_start都与cpu_id相等
+ * 3. 子进程绑定到cpu A并忙循环(不主动让出cpu), 父进程通过sched_setaffinity把它迁移到cpu B,
+ *    子进程看到两个字段都更新为B, 并且每次读取时cpu_id_start都与cpu_id相等
+ *
+ * 只有一个cpu时, 跳过迁移的测试
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <sched.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_rseq_migrate"
+#include "test_check.h"
+
+#define RSEQ_SIG 0x53053053
+#define RSEQ_CPU_ID_UNINITIALIZED ((uint32_t)-1)
+
+#ifndef SYS_rseq
+#define SYS_rseq 334
+#endif
+
+/* 等待迁移完成的最长时间(毫秒) */
+#define MIGRATE_TIMEOUT_MS 2000
+
+struct rseq
+{
+    uint32_t cpu_id_start;
+    uint32_t cpu_id;
+    uint64_t rseq_cs;
+    uint32_t flags;
+} __attribute__((aligned(32)));
+
+static struct rseq rs;
+
+static int sys_rseq(struct rseq *r, int flags)
+{
+    return syscall(SYS_rseq, r, sizeof(*r), flags, RSEQ_SIG);
+}
+
+static long long now_ms(void)
+{
+    struct timespec ts;
+    clock_gettime(CLOCK_MONOTONIC, &ts);
+    return ts.tv_sec * 1000LL + ts.tv_nsec / 1000000;
+}
+
+static int pin_to(pid_t pid, int cpu)
+{
+    cpu_set_t set;
+    CPU_ZERO(&set);
+    CPU_SET(cpu, &set);
+    return sched_setaffinity(pid, sizeof(set), &set);
+}
+
+/*
+ * 先读取cpu_id再读取cpu_id_start: 内核总是先写入cpu_id_start,
+ * 因此两者不相等说明看到了只更新了一半的值
+ */
+static int read_cpu(uint32_t *cpu)
+{
+    uint32_t cpu_id = *(volatile uint32_t *)&rs.cpu_id;
+    __asm__ __volatile__("" ::: "memory");
+    uint32_t cpu_id_start = *(volatile uint32_t *)&rs.cpu_id_start;
+    *cpu = cpu_id;
+    return cpu_id_start == cpu_id;
+}
+
+/*
+ * 等待rseq记录的cpu变为cpu, 期间的每次读取都必须是一致的
+ * yield为0时忙循环, 不主动让出cpu
+ */
+static int wait_for_cpu(int cpu, int yield)
+{
+    long long deadline = now_ms() + MIGRATE_TIMEOUT_MS;
+    uint32_t cur;
+    do
+    {
+        if (!read_cpu(&cur))
+            return 0;
+        if (cur == (uint32_t)cpu)
+            return 1;
+        if (yield)
+            sched_yield();
+    } while (now_ms() < deadline);
+    return 0;
+}
+
+static void test_self_migrate(int cpu_a, int cpu_b)
+{
+    check(pin_to(0, cpu_a) == 0, "pin to cpu A");
+    check(wait_for_cpu(cpu_a, 1), "rseq reports cpu A after pinning");
+
+    check(pin_to(0, cpu_b) == 0, "migrate to cpu B");
+    check(wait_for_cpu(cpu_b, 1), "rseq reports cpu B after migrating");
+}
+
+static void test_migrated_by_parent(int cpu_a, int cpu_b)
+{
+    int ready[2];
+    check(pipe(ready) == 0, "pipe");
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        /* fork出的子进程没有注册rseq */
+        rs.cpu_id = RSEQ_CPU_ID_UNINITIALIZED;
+        if (sys_rseq(&rs, 0) != 0 || pin_to(0, cpu_a) != 0 || !wait_for_cpu(cpu_a, 1))
+            _exit(2);
+        close(ready[0]);
+        write(ready[1], "r", 1);
+        _exit(wait_for_cpu(cpu_b, 0) ? 0 : 1);
+    }
+    close(ready[1]);
+
+    char c;
+    check(read(ready[0], &c, 1) == 1, "child is pinned to cpu A");
+    check(pin_to(pid, cpu_b) == 0, "migrate the child to cpu B");
+    close(ready[0]);
+
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child sees cpu B after being migrated");
+}
+
+int main()
+{
+    cpu_set_t orig;
+    CPU_ZERO(&orig);
+    check(sched_getaffinity(0, sizeof(orig), &orig) == 0, "sched_getaffinity");
+
+    rs.cpu_id = RSEQ_CPU_ID_UNINITIALIZED;
+    check(sys_rseq(&rs, 0) == 0, "rseq register");
+    uint32_t cpu;
+    check(read_cpu(&cpu) && cpu != RSEQ_CPU_ID_UNINITIALIZED,
+          "cpu_id_start and cpu_id are initialized at registration");
+    if (failed)
+    {
+        printf("test_rseq_migrate: failed\n");
+        return 1;
+    }
+
+    /* 找到允许运行的前两个cpu */
+    int cpu_a = -1, cpu_b = -1;
+    for (int i = 0; i < CPU_SETSIZE && cpu_b < 0; i++)
+    {
+        if (!CPU_ISSET(i, &orig))
+            continue;
+        if (cpu_a < 0)
+            cpu_a = i;
+        else
+            cpu_b = i;
+    }
+
+    if (cpu_b < 0)
+    {
+        printf("test_rseq_migrate: only one cpu, skip migrating\n");
+    }
+    else
+    {
+        test_self_migrate(cpu_a, cpu_b);
+        test_self_migrate(cpu_b, cpu_a);
+        test_migrated_by_parent(cpu_a, cpu_b);
+        sched_setaffinity(0, sizeof(orig), &orig);
+    }
+
+    return test_report();
+}
@@ -0,0 +1,146 @@
+/**
+ * 测试sched_setaffinity与sched_getaffinity:
+ * 1. 设置空的cpu掩码会返回EINVAL
+ * 2. 把进程绑定到某个cpu之后, sched_getaffinity返回同样的掩码,
+ *    并且进程只在这个cpu上运行(通过rseq的cpu_id检查, cpu_id_start与cpu_id保持一致)
+ * 3. fork出的子进程继承父进程的cpu掩码
+ * 4. 通过clone系统调用创建的子进程同样继承cpu掩码, 并且从一开始就只在这个cpu上运行
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define RSEQ_SIG 0x53053053
+#define RSEQ_CPU_ID_UNINITIALIZED ((uint32_t)-1)
+
+#ifndef SYS_rseq
+#define SYS_rseq 334
+#endif
+
+#define YIELD_TIMES 100
+
+struct rseq
+{
+    uint32_t cpu_id_start;
+    uint32_t cpu_id;
+    uint64_t rseq_cs;
+    uint32_t flags;
+} __attribute__((aligned(32)));
+
+static struct rseq rs;
+
+static int sys_rseq(struct rseq *r, int flags)
+{
+    return syscall(SYS_rseq, r, sizeof(*r), flags, RSEQ_SIG);
+}
+
+/* 返回1表示进程的cpu掩码只包含cpu */
+static int affinity_is(int cpu)
+{
+    cpu_set_t set;
+    CPU_ZERO(&set);
+    if (sched_getaffinity(0, sizeof(set), &set) != 0)
+        return 0;
+    return CPU_COUNT(&set) == 1 && CPU_ISSET(cpu, &set);
+}
+
+/* 让出cpu若干次, 每次都检查rseq记录的cpu */
+static int runs_only_on(int cpu)
+{
+    for (int i = 0; i < YIELD_TIMES; i++)
+    {
+        sched_yield();
+        uint32_t cpu_id = rs.cpu_id;
+        if (cpu_id != (uint32_t)cpu || rs.cpu_id_start != cpu_id)
+            return 0;
+    }
+    return 1;
+}
+
+int main()
+{
+    int failed = 0;
+    cpu_set_t orig, set;
+
+    CPU_ZERO(&orig);
+    if (sched_getaffinity(0, sizeof(orig), &orig) != 0 || CPU_COUNT(&orig) == 0)
+    {
+        printf("test_sched_affinity: sched_getaffinity failed: %s\n", strerror(errno));
+        return 1;
+    }
+
+    rs.cpu_id = RSEQ_CPU_ID_UNINITIALIZED;
+    if (sys_rseq(&rs, 0) != 0)
+    {
+        printf("test_sched_affinity: rseq register failed: %s\n", strerror(errno));
+        return 1;
+    }
+
+    CPU_ZERO(&set);
+    errno = 0;
+    if (sched_setaffinity(0, sizeof(set), &set) != -1 || errno != EINVAL)
+    {
+        printf("test_sched_affinity: empty mask was not rejected with EINVAL\n");
+        failed = 1;
+    }
+
+    for (int cpu = 0; cpu < CPU_SETSIZE; cpu++)
+    {
+        if (!CPU_ISSET(cpu, &orig))
+            continue;
+
+        CPU_ZERO(&set);
+        CPU_SET(cpu, &set);
+        if (sched_setaffinity(0, sizeof(set), &set) != 0)
+        {
+            printf("test_sched_affinity: pin to cpu %d failed: %s\n", cpu, strerror(errno));
+            failed = 1;
+            continue;
+        }
+        if (!affinity_is(cpu))
+        {
+            printf("test_sched_affinity: getaffinity mismatch after pinning to cpu %d\n", cpu);
+            failed = 1;
+        }
+        if (!runs_only_on(cpu))
+        {
+            printf("test_sched_affinity: ran outside cpu %d (rseq cpu_id %u)\n", cpu, rs.cpu_id);
+            failed = 1;
+        }
+
+        pid_t pid = fork();
+        if (pid == 0)
+            _exit(affinity_is(cpu) ? 0 : 1);
+        int status = 0;
+        if (pid < 0 || waitpid(pid, &status, 0) != pid || !WIFEXITED(status) ||
+            WEXITSTATUS(status) != 0)
+        {
+            printf("test_sched_affinity: child did not inherit the mask of cpu %d\n", cpu);
+            failed = 1;
+        }
+
+        /* 直接使用clone系统调用, 而不是libc的fork */
+        pid = syscall(SYS_clone, SIGCHLD, 0, NULL, NULL, 0);
+        if (pid == 0)
+            _exit(affinity_is(cpu) && runs_only_on(cpu) ? 0 : 1);
+        if (pid < 0 || waitpid(pid, &status, 0) != pid || !WIFEXITED(status) ||
+            WEXITSTATUS(status) != 0)
+        {
+            printf("test_sched_affinity: clone child did not stay on cpu %d\n", cpu);
+            failed = 1;
+        }
+    }
+
+    sched_setaffinity(0, sizeof(orig), &orig);
+
+    printf("test_sched_affinity: %s\n", failed ? "failed" : "ok");
+    return failed;
+}
@@ -0,0 +1,103 @@
+/**
+ * 测试setns:
+ * 1. fd无效时返回EBADF, fd不是namespace文件时返回EINVAL
+ * 2. nstype中含有非namespace的标志时返回EINVAL
+ * 3. 对于/proc/self/ns/下的每个namespace文件:
+ *    - 读取到的内容形如"<类型>:[<编号>]"
+ *    - nstype为0或者与namespace的类型一致时, 能够加入自己所在的namespace
+ *    - nstype与namespace的类型不一致时返回EINVAL
+ */
+
+#define _GNU_SOURCE
+#include <dirent.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <sched.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_setns"
+#include "test_check.h"
+
+static int sys_setns(int fd, int nstype)
+{
+    return syscall(SYS_setns, fd, nstype);
+}
+
+static const struct
+{
+    const char *name;
+    int flag;
+} ns_types[] = {
+    {"mnt", CLONE_NEWNS},   {"uts", CLONE_NEWUTS}, {"ipc", CLONE_NEWIPC},
+    {"user", CLONE_NEWUSER}, {"pid", CLONE_NEWPID}, {"net", CLONE_NEWNET},
+    {"cgroup", CLONE_NEWCGROUP},
+};
+
+static int ns_flag(const char *name)
+{
+    for (size_t i = 0; i < sizeof(ns_types) / sizeof(ns_types[0]); i++)
+    {
+        if (strcmp(ns_types[i].name, name) == 0)
+            return ns_types[i].flag;
+    }
+    return 0;
+}
+
+static void test_ns_file(const char *name)
+{
+    char path[64];
+    char buf[64];
+    char expected[32];
+    int flag = ns_flag(name);
+
+    check(flag != 0, "known namespace type");
+    snprintf(path, sizeof(path), "/proc/%d/ns/%s", getpid(), name);
+    int fd = open(path, O_RDONLY);
+    check(fd >= 0, "open namespace file");
+    if (fd < 0)
+        return;
+
+    ssize_t n = read(fd, buf, sizeof(buf) - 1);
+    buf[n > 0 ? n : 0] = '\0';
+    snprintf(expected, sizeof(expected), "%s:[", name);
+    check(strncmp(buf, expected, strlen(expected)) == 0, "namespace file content");
+
+    check(sys_setns(fd, 0) == 0, "setns with nstype 0");
+    check(sys_setns(fd, flag) == 0, "setns with matching nstype");
+    int other = flag == CLONE_NEWUTS ? CLONE_NEWIPC : CLONE_NEWUTS;
+    check(sys_setns(fd, other) == -1 && errno == EINVAL, "setns with mismatched nstype");
+    close(fd);
+}
+
+int main()
+{
+    check(sys_setns(-1, 0) == -1 && errno == EBADF, "setns with invalid fd");
+
+    int pipefd[2];
+    check(pipe(pipefd) == 0, "pipe");
+    check(sys_setns(pipefd[0], 0) == -1 && errno == EINVAL, "setns with non-namespace fd");
+    check(sys_setns(pipefd[0], CLONE_VM) == -1 && errno == EINVAL, "setns with non-namespace flag");
+    close(pipefd[0]);
+    close(pipefd[1]);
+
+    char path[64];
+    snprintf(path, sizeof(path), "/proc/%d/ns", getpid());
+    DIR *dir = opendir(path);
+    check(dir != NULL, "open ns directory");
+    if (dir != NULL)
+    {
+        struct dirent *ent;
+        while ((ent = readdir(dir)) != NULL)
+        {
+            if (strcmp(ent->d_name, ".") == 0 || strcmp(ent->d_name, "..") == 0)
+                continue;
+            test_ns_file(ent->d_name);
+        }
+        closedir(dir);
+    }
+
+    return test_report();
+}
@@ -0,0 +1,127 @@
+/**
+ * 测试sigaltstack:
+ * 1. 设置了SA_ONSTACK的信号处理函数在备用栈上执行
+ * 2. 在备用栈上执行时, 不能修改备用栈(EPERM), 并且查询到的ss_flags为SS_ONSTACK
+ * 3. 备用栈过小时返回ENOMEM
+ * 4. 子进程继承父进程的备用栈
+ */
+
+#include <errno.h>
+#include <signal.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define ALT_STACK_SIZE (64 * 1024)
+
+static char *alt_stack;
+static volatile uintptr_t handler_sp;
+static volatile int handler_eperm;
+static volatile int handler_onstack;
+
+static void handler(int sig)
+{
+    int local;
+    handler_sp = (uintptr_t)&local;
+
+    stack_t ss = {.ss_sp = alt_stack, .ss_size = ALT_STACK_SIZE, .ss_flags = 0};
+    handler_eperm = sigaltstack(&ss, NULL) == -1 && errno == EPERM;
+
+    stack_t cur;
+    handler_onstack = sigaltstack(NULL, &cur) == 0 && (cur.ss_flags & SS_ONSTACK);
+}
+
+int main()
+{
+    int failed = 0;
+
+    alt_stack = malloc(ALT_STACK_SIZE);
+    if (alt_stack == NULL)
+    {
+        perror("malloc");
+        return 1;
+    }
+
+    stack_t small = {.ss_sp = alt_stack, .ss_size = MINSIGSTKSZ - 1, .ss_flags = 0};
+    if (sigaltstack(&small, NULL) != -1 || errno != ENOMEM)
+    {
+        printf("sigaltstack with a too small stack should fail with ENOMEM\n");
+        failed = 1;
+    }
+
+    stack_t ss = {.ss_sp = alt_stack, .ss_size = ALT_STACK_SIZE, .ss_flags = 0};
+    if (sigaltstack(&ss, NULL) != 0)
+    {
+        perror("sigaltstack");
+        return 1;
+    }
+
+    stack_t cur;
+    if (sigaltstack(NULL, &cur) != 0 || cur.ss_sp != alt_stack || cur.ss_size != ALT_STACK_SIZE ||
+        cur.ss_flags != 0)
+    {
+        printf("sigaltstack does not report the installed stack\n");
+        failed = 1;
+    }
+
+    struct sigaction sa;
+    sa.sa_handler = handler;
+    sa.sa_flags = SA_ONSTACK;
+    sigemptyset(&sa.sa_mask);
+    if (sigaction(SIGUSR1, &sa, NULL) != 0)
+    {
+        perror("sigaction");
+        return 1;
+    }
+
+    kill(getpid(), SIGUSR1);
+
+    if (handler_sp < (uintptr_t)alt_stack || handler_sp >= (uintptr_t)alt_stack + ALT_STACK_SIZE)
+    {
+        printf("handler did not run on the alternate stack (sp=%p)\n", (void *)handler_sp);
+        failed = 1;
+    }
+    if (!handler_eperm)
+    {
+        printf("changing the alternate stack while on it should fail with EPERM\n");
+        failed = 1;
+    }
+    if (!handler_onstack)
+    {
+        printf("SS_ONSTACK is not reported while running on the alternate stack\n");
+        failed = 1;
+    }
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        stack_t child;
+        if (sigaltstack(NULL, &child) != 0 || child.ss_sp != alt_stack || child.ss_size != ALT_STACK_SIZE)
+            _exit(1);
+        _exit(0);
+    }
+    int status;
+    if (pid < 0 || waitpid(pid, &status, 0) != pid || !WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    {
+        printf("child did not inherit the alternate stack\n");
+        failed = 1;
+    }
+
+    stack_t disable = {.ss_flags = SS_DISABLE};
+    if (sigaltstack(&disable, NULL) != 0 || sigaltstack(NULL, &cur) != 0 || cur.ss_flags != SS_DISABLE)
+    {
+        printf("failed to disable the alternate stack\n");
+        failed = 1;
+    }
+
+    if (failed)
+    {
+        printf("test_sigaltstack: failed\n");
+        return 1;
+    }
+
+    printf("test_sigaltstack: ok\n");
+    return 0;
+}
@@ -0,0 +1,183 @@
+/**
+ * 测试子进程状态变化时发送给父进程的SIGCHLD:
+ * 1. 子进程正常退出时, SIGCHLD的si_code为CLD_EXITED, si_pid为子进程的pid, si_status为退出码
+ * 2. 子进程被信号杀死时, si_code为CLD_KILLED, si_status为导致子进程终止的信号
+ * 3. 子进程停止、继续运行时, si_code分别为CLD_STOPPED、CLD_CONTINUED
+ * 4. 子进程消耗了cpu时间时, si_utime + si_stime大于0
+ * 5. 父进程忽略SIGCHLD时, 子进程退出后被自动回收, wait返回ECHILD
+ * 6. 父进程为SIGCHLD设置了SA_NOCLDWAIT时, 处理函数仍然被调用, 子进程被自动回收
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_sigchld"
+#include "test_check.h"
+
+/* 由SIGCHLD处理函数记录最近一次收到的siginfo */
+static volatile sig_atomic_t got = 0;
+static volatile pid_t got_pid;
+static volatile int got_code;
+static volatile int got_status;
+static volatile long got_time;
+
+static void handler(int sig, siginfo_t *info, void *ucontext)
+{
+    (void)ucontext;
+    if (sig != SIGCHLD)
+        return;
+    got_pid = info->si_pid;
+    got_code = info->si_code;
+    got_status = info->si_status;
+    got_time = (long)info->si_utime + (long)info->si_stime;
+    got++;
+}
+
+static void install(int flags)
+{
+    struct sigaction sa;
+    memset(&sa, 0, sizeof(sa));
+    sa.sa_sigaction = handler;
+    sa.sa_flags = SA_SIGINFO | SA_RESTART | flags;
+    sigemptyset(&sa.sa_mask);
+    check(sigaction(SIGCHLD, &sa, NULL) == 0, "sigaction SIGCHLD");
+}
+
+/* 等待SIGCHLD处理函数被调用 */
+static void wait_for_signal(int expected)
+{
+    for (int i = 0; i < 1000 && got < expected; i++)
+        usleep(1000);
+}
+
+static void busy_loop(long ms)
+{
+    struct timespec start, now;
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    do
+    {
+        clock_gettime(CLOCK_MONOTONIC, &now);
+    } while ((now.tv_sec - start.tv_sec) * 1000 + (now.tv_nsec - start.tv_nsec) / 1000000 < ms);
+}
+
+static void test_exited(void)
+{
+    got = 0;
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        busy_loop(100);
+        _exit(3);
+    }
+    check(pid > 0, "fork");
+    wait_for_signal(1);
+    check(got == 1, "SIGCHLD received on exit");
+    check(got_pid == pid, "si_pid is the child's pid");
+    check(got_code == CLD_EXITED, "si_code is CLD_EXITED");
+    check(got_status == 3, "si_status is the exit code");
+    check(got_time > 0, "si_utime + si_stime is non-zero after busy loop");
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid && WIFEXITED(status) && WEXITSTATUS(status) == 3,
+          "reap exited child");
+}
+
+static void test_killed(void)
+{
+    got = 0;
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        for (;;)
+            pause();
+    }
+    check(pid > 0, "fork");
+    check(kill(pid, SIGKILL) == 0, "kill child");
+    wait_for_signal(1);
+    check(got == 1 && got_pid == pid, "SIGCHLD received on kill");
+    check(got_code == CLD_KILLED, "si_code is CLD_KILLED");
+    check(got_status == SIGKILL, "si_status is SIGKILL");
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid && WIFSIGNALED(status) && WTERMSIG(status) == SIGKILL,
+          "reap killed child");
+}
+
+static void test_stop_continue(void)
+{
+    got = 0;
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        for (;;)
+            pause();
+    }
+    check(pid > 0, "fork");
+    int status = 0;
+
+    check(kill(pid, SIGSTOP) == 0, "stop child");
+    check(waitpid(pid, &status, WUNTRACED) == pid && WIFSTOPPED(status), "wait stopped child");
+    wait_for_signal(1);
+    check(got == 1 && got_code == CLD_STOPPED && got_status == SIGSTOP,
+          "SIGCHLD with CLD_STOPPED");
+
+    check(kill(pid, SIGCONT) == 0, "continue child");
+    check(waitpid(pid, &status, WCONTINUED) == pid && WIFCONTINUED(status),
+          "wait continued child");
+    wait_for_signal(2);
+    check(got == 2 && got_code == CLD_CONTINUED && got_status == SIGCONT,
+          "SIGCHLD with CLD_CONTINUED");
+
+    kill(pid, SIGKILL);
+    waitpid(pid, &status, 0);
+}
+
+static void test_ignored(void)
+{
+    check(signal(SIGCHLD, SIG_IGN) != SIG_ERR, "ignore SIGCHLD");
+    pid_t pid = fork();
+    if (pid == 0)
+        _exit(0);
+    check(pid > 0, "fork");
+    /* 子进程被自动回收, 在它退出之后wait返回ECHILD */
+    errno = 0;
+    check(waitpid(pid, NULL, 0) == -1 && errno == ECHILD, "wait returns ECHILD with SIG_IGN");
+    errno = 0;
+    check(kill(pid, 0) == -1 && errno == ESRCH, "child is not a zombie with SIG_IGN");
+    check(signal(SIGCHLD, SIG_DFL) != SIG_ERR, "restore SIGCHLD");
+}
+
+static void test_nocldwait(void)
+{
+    install(SA_NOCLDWAIT);
+    got = 0;
+    pid_t pid = fork();
+    if (pid == 0)
+        _exit(5);
+    check(pid > 0, "fork");
+    errno = 0;
+    check(waitpid(pid, NULL, 0) == -1 && errno == ECHILD,
+          "wait returns ECHILD with SA_NOCLDWAIT");
+    wait_for_signal(1);
+    check(got == 1 && got_pid == pid && got_code == CLD_EXITED && got_status == 5,
+          "handler called with SA_NOCLDWAIT");
+    errno = 0;
+    check(kill(pid, 0) == -1 && errno == ESRCH, "child is not a zombie with SA_NOCLDWAIT");
+    install(0);
+}
+
+int main()
+{
+    install(0);
+    test_exited();
+    test_killed();
+    test_stop_continue();
+    test_ignored();
+    test_nocldwait();
+
+    return test_report();
+}
@@ -0,0 +1,66 @@
+/**
+ * 测试spawn系统调用:
+ * 1. 子进程执行指定的程序, 并且能够得到参数与环境变量
+ * 2. 子进程继承父进程的文件描述符
+ * 3. 程序不存在时返回ENOENT, 并且不会留下子进程
+ */
+
+#include <errno.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_spawn"
+#include "test_check.h"
+
+#define SYS_spawn 100004
+
+static pid_t spawn(const char *path, char *const argv[], char *const envp[])
+{
+    return syscall(SYS_spawn, path, argv, envp);
+}
+
+/* 被spawn出的子进程: 检查环境变量, 向管道写入一个字节, 然后以参数指定的值退出 */
+static int child(int argc, char *argv[])
+{
+    const char *value = getenv("TEST_SPAWN");
+    if (value == NULL || strcmp(value, "1") != 0)
+        return 1;
+    int fd = atoi(argv[2]);
+    if (write(fd, "x", 1) != 1)
+        return 2;
+    return atoi(argv[3]);
+}
+
+int main(int argc, char *argv[])
+{
+    if (argc == 4 && strcmp(argv[1], "child") == 0)
+        return child(argc, argv);
+
+    int fds[2];
+    check(pipe(fds) == 0, "pipe");
+
+    char fd_arg[16];
+    snprintf(fd_arg, sizeof(fd_arg), "%d", fds[1]);
+    char *child_argv[] = {"test_spawn", "child", fd_arg, "42", NULL};
+    char *child_envp[] = {"TEST_SPAWN=1", NULL};
+
+    pid_t pid = spawn("/bin/test_spawn", child_argv, child_envp);
+    check(pid > 0, "spawn");
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 42, "child exit status");
+    char c = 0;
+    check(read(fds[0], &c, 1) == 1 && c == 'x', "child inherits fd");
+
+    errno = 0;
+    check(spawn("/bin/test_spawn_not_exist", child_argv, child_envp) == -1 && errno == ENOENT,
+          "spawn a missing program");
+    errno = 0;
+    check(waitpid(-1, NULL, WNOHANG) == -1 && errno == ECHILD, "no child left behind");
+
+    return test_report();
+}
@@ -0,0 +1,92 @@
+/**
+ * 测试进程的启动时间(/proc/<pid>/stat的第22个字段):
+ * 1. 父进程等待一段时间之后fork, 子进程的启动时间晚于父进程
+ * 2. 子进程的启动时间不会随着子进程的运行而改变
+ */
+
+#include <stdio.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_start_time"
+#include "test_check.h"
+
+/* 等待的时间远大于一个时钟节拍 */
+#define DELAY_US 200000
+
+/* 读取进程的启动时间, 失败时返回-1 */
+static long long read_start_time(pid_t pid)
+{
+    char path[64];
+    char buf[1024];
+
+    snprintf(path, sizeof(path), "/proc/%d/stat", pid);
+    FILE *f = fopen(path, "r");
+    if (f == NULL)
+        return -1;
+    size_t n = fread(buf, 1, sizeof(buf) - 1, f);
+    fclose(f);
+    buf[n] = '\0';
+
+    /* 进程名中可能含有空格, 从最后一个')'之后开始解析, 它后面是第3个字段 */
+    char *p = strrchr(buf, ')');
+    if (p == NULL)
+        return -1;
+    p++;
+    for (int field = 3; field <= 22; field++)
+    {
+        while (*p == ' ')
+            p++;
+        if (*p == '\0')
+            return -1;
+        if (field == 22)
+        {
+            long long value = -1;
+            sscanf(p, "%lld", &value);
+            return value;
+        }
+        while (*p != ' ' && *p != '\0')
+            p++;
+    }
+    return -1;
+}
+
+int main()
+{
+    long long parent_start = read_start_time(getpid());
+    check(parent_start >= 0, "read parent start time");
+
+    usleep(DELAY_US);
+
+    int pipefd[2];
+    check(pipe(pipefd) == 0, "pipe");
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        char c;
+        close(pipefd[1]);
+        /* 等待父进程读取启动时间 */
+        read(pipefd[0], &c, 1);
+        _exit(0);
+    }
+    close(pipefd[0]);
+
+    long long child_start = read_start_time(pid);
+    check(child_start > parent_start, "child starts later than parent");
+    check(read_start_time(getpid()) == parent_start, "parent start time unchanged");
+
+    usleep(DELAY_US);
+    check(read_start_time(pid) == child_start, "child start time unchanged");
+
+    write(pipefd[1], "x", 1);
+    close(pipefd[1]);
+
+    int status = 0;
+    check(pid > 0 && waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+              WEXITSTATUS(status) == 0,
+          "child exit status");
+
+    return test_report();
+}
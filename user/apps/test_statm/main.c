@@ -0,0 +1,102 @@
+/**
+ * 测试/proc/<pid>/statm:
+ * 1. mmap之后, 总大小与驻留内存都增加了映射的页数
+ * 2. fork之后, 子进程的总大小与父进程相同, 写时复制共享的页面同时计入父子进程的驻留内存
+ * 3. 子进程写入共享的页面(触发写时复制)之后, 驻留内存不变
+ * 4. munmap之后, 总大小与驻留内存都减少了映射的页数
+ */
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_statm"
+#include "test_check.h"
+
+#define MAP_PAGES 64
+
+struct statm
+{
+    unsigned long size, resident, shared, text, lib, data, dt;
+};
+
+static int read_statm(pid_t pid, struct statm *st)
+{
+    char path[64], buf[256];
+    snprintf(path, sizeof(path), "/proc/%d/statm", pid);
+    int fd = open(path, O_RDONLY);
+    if (fd < 0)
+        return -1;
+    ssize_t n = read(fd, buf, sizeof(buf) - 1);
+    close(fd);
+    if (n <= 0)
+        return -1;
+    buf[n] = '\0';
+    int r = sscanf(buf, "%lu %lu %lu %lu %lu %lu %lu", &st->size, &st->resident, &st->shared,
+                   &st->text, &st->lib, &st->data, &st->dt);
+    return r == 7 ? 0 : -1;
+}
+
+int main()
+{
+    long page_size = sysconf(_SC_PAGESIZE);
+    struct statm before, mapped, child, parent, unmapped;
+
+    check(read_statm(getpid(), &before) == 0, "read statm");
+    check(before.resident <= before.size, "resident <= size");
+
+    char *p = mmap(NULL, MAP_PAGES * page_size, PROT_READ | PROT_WRITE,
+                   MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(p != MAP_FAILED, "mmap");
+    memset(p, 1, MAP_PAGES * page_size);
+
+    check(read_statm(getpid(), &mapped) == 0, "read statm after mmap");
+    check(mapped.size >= before.size + MAP_PAGES, "size grows after mmap");
+    check(mapped.resident >= before.resident + MAP_PAGES, "resident grows after mmap");
+    check(mapped.data >= before.data + MAP_PAGES, "data grows after mmap");
+
+    int to_parent[2], to_child[2];
+    check(pipe(to_parent) == 0 && pipe(to_child) == 0, "pipe");
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        char c;
+        /* 等待父进程读取完子进程的statm之后, 再写入共享的页面 */
+        read(to_child[0], &c, 1);
+        memset(p, 2, MAP_PAGES * page_size);
+        struct statm st;
+        check(read_statm(getpid(), &st) == 0, "child read own statm");
+        check(st.resident >= MAP_PAGES, "child resident after copy-on-write");
+        write(to_parent[1], &st, sizeof(st));
+        _exit(failed);
+    }
+    check(pid > 0, "fork");
+
+    check(read_statm(pid, &child) == 0, "read child statm");
+    check(read_statm(getpid(), &parent) == 0, "read parent statm after fork");
+    check(child.size == parent.size, "child size equals parent size");
+    check(child.resident >= MAP_PAGES, "shared pages count toward child resident");
+    check(parent.resident >= mapped.resident, "shared pages still count toward parent resident");
+
+    write(to_child[1], "x", 1);
+    struct statm after_cow;
+    check(read(to_parent[0], &after_cow, sizeof(after_cow)) == sizeof(after_cow),
+          "receive child statm");
+    check(after_cow.resident == child.resident, "copy-on-write keeps child resident");
+
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0,
+          "child exit status");
+
+    check(munmap(p, MAP_PAGES * page_size) == 0, "munmap");
+    check(read_statm(getpid(), &unmapped) == 0, "read statm after munmap");
+    check(unmapped.size + MAP_PAGES == parent.size, "size shrinks after munmap");
+    check(unmapped.resident + MAP_PAGES == parent.resident, "resident shrinks after munmap");
+
+    return test_report();
+}
@@ -0,0 +1,193 @@
+/**
+ * 测试发送给线程组(进程)的信号如何选择处理它的线程:
+ * 1. 主线程屏蔽了SIGUSR1而另一个线程没有屏蔽时, kill发送的SIGUSR1由另一个线程处理
+ * 2. 所有线程都屏蔽了SIGUSR1时, 信号留在进程的待处理信号中, 某个线程解除屏蔽之后由它处理
+ * 3. 向非组长线程发送默认处理方式为终止进程的信号(SIGTERM)时, 整个进程退出
+ * 4. 用kill发送SIGKILL时, 整个进程退出, 即使组长线程之外的线程仍在运行
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <pthread.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_thread_group_signal"
+#include "test_check.h"
+
+/* 处理SIGUSR1的线程的tid */
+static volatile pid_t handled_tid = 0;
+/* 另一个线程的tid */
+static volatile pid_t worker_tid = 0;
+/* 主线程通知另一个线程屏蔽(1)、解除屏蔽(3)SIGUSR1, 另一个线程完成之后分别改为2、4 */
+static volatile int unblock_now = 0;
+static volatile int stop_worker = 0;
+
+static pid_t gettid_(void)
+{
+    return (pid_t)syscall(SYS_gettid);
+}
+
+static void handler(int sig)
+{
+    (void)sig;
+    handled_tid = gettid_();
+}
+
+static void block_sigusr1(int how)
+{
+    sigset_t set;
+    sigemptyset(&set);
+    sigaddset(&set, SIGUSR1);
+    pthread_sigmask(how, &set, NULL);
+}
+
+/* 等待handled_tid被设置 */
+static void wait_handled(void)
+{
+    for (int i = 0; i < 1000 && handled_tid == 0; i++)
+        usleep(1000);
+}
+
+/* 读取/proc/self/status中进程共享的待处理信号(ShdPnd) */
+static unsigned long long shared_pending(void)
+{
+    char line[128];
+    unsigned long long value = 0;
+    FILE *f = fopen("/proc/self/status", "r");
+    if (f == NULL)
+        return 0;
+    while (fgets(line, sizeof(line), f) != NULL)
+    {
+        if (strncmp(line, "ShdPnd:", 7) == 0)
+        {
+            value = strtoull(line + 7, NULL, 16);
+            break;
+        }
+    }
+    fclose(f);
+    return value;
+}
+
+/* 另一个线程: 一开始不屏蔽SIGUSR1, 收到通知之后先屏蔽, 再在下一次通知时解除屏蔽 */
+static void *worker(void *arg)
+{
+    (void)arg;
+    /* 新线程继承了主线程的信号屏蔽字 */
+    block_sigusr1(SIG_UNBLOCK);
+    worker_tid = gettid_();
+    while (!stop_worker)
+    {
+        if (unblock_now == 1)
+        {
+            block_sigusr1(SIG_BLOCK);
+            unblock_now = 2;
+        }
+        else if (unblock_now == 3)
+        {
+            block_sigusr1(SIG_UNBLOCK);
+            unblock_now = 4;
+        }
+        usleep(1000);
+    }
+    return NULL;
+}
+
+static void wait_state(int state)
+{
+    for (int i = 0; i < 1000 && unblock_now != state; i++)
+        usleep(1000);
+}
+
+static void test_select_thread(void)
+{
+    signal(SIGUSR1, handler);
+    block_sigusr1(SIG_BLOCK);
+
+    pthread_t thread;
+    check(pthread_create(&thread, NULL, worker, NULL) == 0, "pthread_create");
+    while (worker_tid == 0)
+        usleep(1000);
+
+    /* 主线程屏蔽了SIGUSR1, 信号由另一个线程处理 */
+    handled_tid = 0;
+    check(kill(getpid(), SIGUSR1) == 0, "kill SIGUSR1");
+    wait_handled();
+    check(handled_tid == worker_tid, "SIGUSR1 is handled by the unblocked thread");
+
+    /* 两个线程都屏蔽了SIGUSR1, 信号留在进程的待处理信号中 */
+    unblock_now = 1;
+    wait_state(2);
+    handled_tid = 0;
+    check(kill(getpid(), SIGUSR1) == 0, "kill SIGUSR1 while blocked by all threads");
+    usleep(50000);
+    check(handled_tid == 0, "SIGUSR1 is not handled while blocked by all threads");
+    check(shared_pending() & (1ULL << (SIGUSR1 - 1)), "SIGUSR1 is pending on the process");
+
+    /* 另一个线程解除屏蔽之后, 由它处理这个信号 */
+    unblock_now = 3;
+    wait_state(4);
+    wait_handled();
+    check(handled_tid == worker_tid, "pending SIGUSR1 is handled after the thread unblocks it");
+
+    stop_worker = 1;
+    pthread_join(thread, NULL);
+    block_sigusr1(SIG_UNBLOCK);
+    signal(SIGUSR1, SIG_DFL);
+}
+
+static void *spin(void *arg)
+{
+    *(volatile pid_t *)arg = gettid_();
+    for (;;)
+        pause();
+    return NULL;
+}
+
+/* 创建一个包含两个线程的子进程, 向它(to_thread为真时向它的非组长线程)发送sig, 检查整个子进程因sig而退出 */
+static void test_group_kill(int sig, int to_thread)
+{
+    int fds[2];
+    check(pipe(fds) == 0, "pipe");
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        static volatile pid_t tid = 0;
+        pthread_t thread;
+        pthread_create(&thread, NULL, spin, (void *)&tid);
+        while (tid == 0)
+            usleep(1000);
+        pid_t t = tid;
+        write(fds[1], &t, sizeof(t));
+        for (;;)
+            pause();
+    }
+    check(pid > 0, "fork");
+    close(fds[1]);
+    pid_t tid = 0;
+    check(read(fds[0], &tid, sizeof(tid)) == sizeof(tid), "read tid of the child's thread");
+    close(fds[0]);
+
+    if (to_thread)
+        check(syscall(SYS_tgkill, pid, tid, sig) == 0, "tgkill the non-leader thread");
+    else
+        check(kill(pid, sig) == 0, "kill the child");
+
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFSIGNALED(status) && WTERMSIG(status) == sig, "the whole process is killed");
+}
+
+int main()
+{
+    test_select_thread();
+    test_group_kill(SIGTERM, 1);
+    test_group_kill(SIGKILL, 0);
+
+    return test_report();
+}
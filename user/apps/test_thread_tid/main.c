@@ -0,0 +1,74 @@
+/**
+ * 测试线程的tid与线程组id:
+ * 1. 线程组组长的tid等于getpid()
+ * 2. 新线程拥有自己的tid, 但getpid()返回组长的线程组id
+ * 3. /proc/<pid>/task/<tid>/status中的Tgid与Pid分别为线程组id与tid
+ */
+
+#include <pthread.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_thread_tid"
+#include "test_check.h"
+
+static pid_t thread_tid;
+static pid_t thread_pid;
+
+static pid_t sys_gettid(void)
+{
+    return (pid_t)syscall(SYS_gettid);
+}
+
+/* 读取status文件中某一项的值, 失败时返回-1 */
+static long read_status(pid_t pid, pid_t tid, const char *key)
+{
+    char path[64];
+    char line[128];
+    long value = -1;
+    size_t len = strlen(key);
+
+    snprintf(path, sizeof(path), "/proc/%d/task/%d/status", pid, tid);
+    FILE *f = fopen(path, "r");
+    if (f == NULL)
+        return -1;
+    while (fgets(line, sizeof(line), f) != NULL)
+    {
+        if (strncmp(line, key, len) == 0 && line[len] == ':')
+        {
+            sscanf(line + len + 1, "%ld", &value);
+            break;
+        }
+    }
+    fclose(f);
+    return value;
+}
+
+static void *thread_main(void *arg)
+{
+    (void)arg;
+    thread_tid = sys_gettid();
+    thread_pid = getpid();
+
+    check(read_status(thread_pid, thread_tid, "Tgid") == thread_pid, "thread status Tgid");
+    check(read_status(thread_pid, thread_tid, "Pid") == thread_tid, "thread status Pid");
+    return NULL;
+}
+
+int main()
+{
+    pid_t pid = getpid();
+    check(sys_gettid() == pid, "leader tid equals pid");
+    check(read_status(pid, pid, "Pid") == pid, "leader status Pid");
+
+    pthread_t thread;
+    check(pthread_create(&thread, NULL, thread_main, NULL) == 0, "pthread_create");
+    pthread_join(thread, NULL);
+
+    check(thread_pid == pid, "thread getpid equals leader pid");
+    check(thread_tid > 0 && thread_tid != pid, "thread has its own tid");
+
+    return test_report();
+}
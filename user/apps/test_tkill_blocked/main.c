@@ -0,0 +1,126 @@
+/**
+ * 测试发送给指定线程的信号(tgkill/pthread_kill):
+ * 1. 目标线程屏蔽了信号时, 即使其他线程没有屏蔽, 信号也不会被其他线程处理
+ * 2. 信号留在目标线程私有的待处理信号中: 目标线程的sigpending包含它, 其他线程的sigpending不包含它
+ * 3. 目标线程解除屏蔽之后, 信号由目标线程处理
+ * 4. 用pthread_kill发送的信号同样只由目标线程处理
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <pthread.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_tkill_blocked"
+#include "test_check.h"
+
+/* 处理SIGUSR1的线程的tid */
+static volatile pid_t handled_tid = 0;
+static volatile pid_t worker_tid = 0;
+/* 主线程通知另一个线程进行下一步操作, 另一个线程完成之后把它加1 */
+static volatile int step = 0;
+/* 另一个线程在解除屏蔽之前观察到的待处理信号中是否包含SIGUSR1 */
+static volatile int worker_saw_pending = 0;
+
+static pid_t gettid_(void)
+{
+    return (pid_t)syscall(SYS_gettid);
+}
+
+static void handler(int sig)
+{
+    (void)sig;
+    handled_tid = gettid_();
+}
+
+static void sigusr1_mask(int how)
+{
+    sigset_t set;
+    sigemptyset(&set);
+    sigaddset(&set, SIGUSR1);
+    pthread_sigmask(how, &set, NULL);
+}
+
+static int sigusr1_pending(void)
+{
+    sigset_t set;
+    sigemptyset(&set);
+    if (sigpending(&set) != 0)
+        return -1;
+    return sigismember(&set, SIGUSR1);
+}
+
+static void wait_step(int value)
+{
+    for (int i = 0; i < 1000 && step != value; i++)
+        usleep(1000);
+}
+
+static void wait_handled(void)
+{
+    for (int i = 0; i < 1000 && handled_tid == 0; i++)
+        usleep(1000);
+}
+
+/* 另一个线程: 屏蔽SIGUSR1, 等待主线程发送信号之后检查待处理信号, 然后解除屏蔽 */
+static void *worker(void *arg)
+{
+    (void)arg;
+    sigusr1_mask(SIG_BLOCK);
+    worker_tid = gettid_();
+
+    wait_step(1);
+    worker_saw_pending = sigusr1_pending() == 1;
+    step = 2;
+
+    wait_step(3);
+    sigusr1_mask(SIG_UNBLOCK);
+    step = 4;
+
+    /* 等待用pthread_kill发送的信号 */
+    wait_step(5);
+    return NULL;
+}
+
+int main()
+{
+    signal(SIGUSR1, handler);
+    /* 主线程不屏蔽SIGUSR1 */
+    sigusr1_mask(SIG_UNBLOCK);
+
+    pthread_t thread;
+    check(pthread_create(&thread, NULL, worker, NULL) == 0, "pthread_create");
+    while (worker_tid == 0)
+        usleep(1000);
+
+    /* 向屏蔽了SIGUSR1的线程发送信号, 主线程不会处理它 */
+    check(syscall(SYS_tgkill, getpid(), worker_tid, SIGUSR1) == 0, "tgkill the blocked thread");
+    usleep(50000);
+    check(handled_tid == 0, "SIGUSR1 is not handled while the target thread blocks it");
+    check(sigusr1_pending() == 0, "SIGUSR1 is not pending on the main thread");
+
+    step = 1;
+    wait_step(2);
+    check(worker_saw_pending, "SIGUSR1 is pending on the target thread");
+
+    /* 目标线程解除屏蔽之后处理这个信号 */
+    step = 3;
+    wait_step(4);
+    wait_handled();
+    check(handled_tid == worker_tid, "SIGUSR1 is handled by the target thread after unblocking");
+
+    /* pthread_kill同样只把信号发送给目标线程 */
+    handled_tid = 0;
+    check(pthread_kill(thread, SIGUSR1) == 0, "pthread_kill");
+    wait_handled();
+    check(handled_tid == worker_tid, "pthread_kill signal is handled by the target thread");
+
+    step = 5;
+    pthread_join(thread, NULL);
+
+    return test_report();
+}
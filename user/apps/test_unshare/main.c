@@ -0,0 +1,140 @@
+/**
+ * 测试unshare:
+ * 1. 不支持的标志返回EINVAL, flags为0时什么也不做
+ * 2. CLONE_FILES: 与父进程共享文件描述符表的子进程unshare之后, 关闭文件描述符不影响父进程;
+ *    没有unshare时, 子进程关闭文件描述符会影响父进程
+ * 3. CLONE_FS: 与父进程共享文件系统上下文的子进程unshare之后, chdir不影响父进程
+ * 4. CLONE_NEWUTS: unshare之后修改主机名不影响父进程
+ * 5. CLONE_NEWPID: 调用者自身的pid不变, 之后创建的子进程位于新的pid namespace中,
+ *    再次使用CLONE_NEWPID时返回EINVAL
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <sys/utsname.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_unshare"
+#include "test_check.h"
+
+/* 与fork相同, 但是可以指定额外的clone标志 */
+static pid_t clone_fork(unsigned long flags)
+{
+    return syscall(SYS_clone, flags | SIGCHLD, 0, NULL, NULL, 0);
+}
+
+static void wait_child(pid_t pid, const char *what)
+{
+    int status = 0;
+    check(pid > 0 && waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+              WEXITSTATUS(status) == 0,
+          what);
+}
+
+static int fd_valid(int fd)
+{
+    return fcntl(fd, F_GETFD) != -1;
+}
+
+static void test_files(void)
+{
+    int pipefd[2];
+    check(pipe(pipefd) == 0, "pipe");
+
+    /* unshare之后关闭, 不影响父进程 */
+    pid_t pid = clone_fork(CLONE_FILES);
+    if (pid == 0)
+    {
+        check(unshare(CLONE_FILES) == 0, "unshare(CLONE_FILES)");
+        check(close(pipefd[0]) == 0, "close in child after unshare");
+        _exit(failed);
+    }
+    wait_child(pid, "CLONE_FILES child with unshare");
+    check(fd_valid(pipefd[0]), "fd still open in parent after unshare");
+
+    /* 没有unshare时, 子进程关闭的是共享的文件描述符 */
+    pid = clone_fork(CLONE_FILES);
+    if (pid == 0)
+        _exit(close(pipefd[1]) == 0 ? 0 : 1);
+    wait_child(pid, "CLONE_FILES child without unshare");
+    errno = 0;
+    check(!fd_valid(pipefd[1]) && errno == EBADF, "fd closed by sharing child");
+
+    close(pipefd[0]);
+}
+
+static void test_fs(void)
+{
+    char cwd[256], now[256];
+    check(getcwd(cwd, sizeof(cwd)) != NULL, "getcwd");
+
+    pid_t pid = clone_fork(CLONE_FS);
+    if (pid == 0)
+    {
+        check(unshare(CLONE_FS) == 0, "unshare(CLONE_FS)");
+        check(chdir("/dev") == 0, "chdir in child");
+        _exit(failed);
+    }
+    wait_child(pid, "CLONE_FS child");
+    check(getcwd(now, sizeof(now)) != NULL && strcmp(cwd, now) == 0,
+          "parent cwd unchanged after child unshare");
+}
+
+static void test_uts(void)
+{
+    struct utsname orig, now;
+    check(uname(&orig) == 0, "uname");
+
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        check(unshare(CLONE_NEWUTS) == 0, "unshare(CLONE_NEWUTS)");
+        check(sethostname("unshare-child", strlen("unshare-child")) == 0, "sethostname in child");
+        _exit(failed);
+    }
+    wait_child(pid, "CLONE_NEWUTS child");
+    check(uname(&now) == 0 && strcmp(orig.nodename, now.nodename) == 0,
+          "parent hostname unchanged");
+}
+
+static void test_pid(void)
+{
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        pid_t self = getpid();
+        check(unshare(CLONE_NEWPID) == 0, "unshare(CLONE_NEWPID)");
+        check(getpid() == self, "own pid unchanged");
+        errno = 0;
+        check(unshare(CLONE_NEWPID) == -1 && errno == EINVAL, "second unshare(CLONE_NEWPID)");
+
+        pid_t child = fork();
+        if (child == 0)
+            _exit(getpid() == 1 ? 0 : 1);
+        wait_child(child, "first child in new pid namespace is 1");
+        _exit(failed);
+    }
+    wait_child(pid, "CLONE_NEWPID child");
+}
+
+int main()
+{
+    errno = 0;
+    check(unshare(CLONE_VM) == -1 && errno == EINVAL, "reject unsupported flag");
+    check(unshare(0) == 0, "unshare(0)");
+
+    test_files();
+    test_fs();
+    test_uts();
+    test_pid();
+
+    return test_report();
+}
@@ -0,0 +1,115 @@
+/**
+ * 测试CLONE_NEWUTS:
+ * 1. 新uts namespace中的子进程修改主机名与域名, 不影响父进程
+ * 2. 新uts namespace最初是父进程的namespace的拷贝
+ * 3. 没有使用CLONE_NEWUTS的子进程与父进程共享主机名
+ * 4. 子进程与父进程的/proc/<pid>/ns/uts不同
+ * 5. 主机名长度超过64时返回EINVAL
+ */
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/utsname.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_uts_namespace"
+#include "test_check.h"
+
+/* 与fork相同, 但是可以指定额外的clone标志 */
+static pid_t clone_fork(unsigned long flags)
+{
+    return syscall(SYS_clone, flags | SIGCHLD, 0, NULL, NULL, 0);
+}
+
+static void read_ns(pid_t pid, char *buf, size_t size)
+{
+    char path[64];
+    snprintf(path, sizeof(path), "/proc/%d/ns/uts", pid);
+    buf[0] = '\0';
+    int fd = open(path, O_RDONLY);
+    if (fd < 0)
+        return;
+    ssize_t n = read(fd, buf, size - 1);
+    buf[n > 0 ? n : 0] = '\0';
+    close(fd);
+}
+
+static void wait_child(pid_t pid, const char *what)
+{
+    int status = 0;
+    check(pid > 0 && waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+              WEXITSTATUS(status) == 0,
+          what);
+}
+
+int main()
+{
+    struct utsname orig, now;
+    check(uname(&orig) == 0, "uname");
+
+    char too_long[65];
+    memset(too_long, 'a', sizeof(too_long));
+    errno = 0;
+    check(sethostname(too_long, 65) == -1 && errno == EINVAL, "reject too long hostname");
+
+    int pipefd[2];
+    check(pipe(pipefd) == 0, "pipe");
+
+    pid_t pid = clone_fork(CLONE_NEWUTS);
+    if (pid == 0)
+    {
+        close(pipefd[1]);
+        struct utsname child;
+        check(uname(&child) == 0 && strcmp(child.nodename, orig.nodename) == 0,
+              "new namespace starts with parent's hostname");
+        check(sethostname("uts-child", strlen("uts-child")) == 0, "sethostname in child");
+        check(setdomainname("child.domain", strlen("child.domain")) == 0,
+              "setdomainname in child");
+        check(uname(&child) == 0 && strcmp(child.nodename, "uts-child") == 0 &&
+                  strcmp(child.domainname, "child.domain") == 0,
+              "child sees its own names");
+
+        /* 等待父进程检查完毕之后再退出 */
+        char c;
+        read(pipefd[0], &c, 1);
+        _exit(failed);
+    }
+    close(pipefd[0]);
+    check(pid > 0, "clone with CLONE_NEWUTS");
+
+    /* 等待子进程修改主机名 */
+    usleep(100000);
+    check(uname(&now) == 0 && strcmp(now.nodename, orig.nodename) == 0 &&
+              strcmp(now.domainname, orig.domainname) == 0,
+          "parent names unchanged");
+
+    char self_ns[64], child_ns[64];
+    read_ns(getpid(), self_ns, sizeof(self_ns));
+    read_ns(pid, child_ns, sizeof(child_ns));
+    check(strncmp(self_ns, "uts:[", 5) == 0, "read own uts namespace");
+    check(strncmp(child_ns, "uts:[", 5) == 0, "read child uts namespace");
+    check(strcmp(self_ns, child_ns) != 0, "child is in a different uts namespace");
+
+    write(pipefd[1], "x", 1);
+    close(pipefd[1]);
+    wait_child(pid, "CLONE_NEWUTS child exit status");
+
+    /* 普通fork的子进程与父进程共享uts namespace */
+    pid = fork();
+    if (pid == 0)
+        _exit(sethostname("uts-shared", strlen("uts-shared")) == 0 ? 0 : 1);
+    wait_child(pid, "fork child exit status");
+    check(uname(&now) == 0 && strcmp(now.nodename, "uts-shared") == 0,
+          "parent sees hostname set by fork child");
+
+    check(sethostname(orig.nodename, strlen(orig.nodename)) == 0, "restore hostname");
+
+    return test_report();
+}
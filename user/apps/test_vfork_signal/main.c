@@ -0,0 +1,114 @@
+/**
+ * 测试vfork的父进程在子进程运行期间收到信号时的行为:
+ * 1. 父进程收到普通信号时, 仍然等到子进程退出之后才从vfork返回, 然后处理这个信号
+ * 2. 父进程收到SIGKILL时, 不必等待子进程退出就会被杀死
+ *
+ * vfork的子进程借用父进程的栈, 因此子进程只直接使用系统调用, 不调用libc的函数
+ */
+
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_vfork_signal"
+#include "test_check.h"
+
+static volatile sig_atomic_t got_signal;
+/* 由vfork的子进程在退出之前写入 */
+static volatile int child_done;
+
+static void handler(int sig)
+{
+    (void)sig;
+    got_signal = 1;
+}
+
+/* 不经过libc睡眠指定的毫秒数 */
+static void raw_sleep_ms(long ms)
+{
+    struct timespec ts = {ms / 1000, (ms % 1000) * 1000000};
+    syscall(SYS_nanosleep, &ts, NULL);
+}
+
+static long now_ms(void)
+{
+    struct timespec ts;
+    clock_gettime(CLOCK_MONOTONIC, &ts);
+    return ts.tv_sec * 1000 + ts.tv_nsec / 1000000;
+}
+
+static void test_signal_during_vfork(void)
+{
+    struct sigaction sa;
+    memset(&sa, 0, sizeof(sa));
+    sa.sa_handler = handler;
+    check(sigaction(SIGUSR1, &sa, NULL) == 0, "sigaction");
+
+    got_signal = 0;
+    child_done = 0;
+    pid_t parent = getpid();
+
+    /* 在vfork的子进程运行期间向父进程发送信号 */
+    pid_t signaler = fork();
+    check(signaler >= 0, "fork signaler");
+    if (signaler == 0)
+    {
+        usleep(100 * 1000);
+        kill(parent, SIGUSR1);
+        _exit(0);
+    }
+
+    pid_t pid = vfork();
+    if (pid == 0)
+    {
+        raw_sleep_ms(500);
+        child_done = 1;
+        syscall(SYS_exit, 0);
+    }
+    check(pid > 0, "vfork");
+    check(child_done == 1, "parent returns from vfork only after the child exits");
+
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid, "waitpid vfork child");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "vfork child exits normally");
+    if (signaler > 0)
+        check(waitpid(signaler, NULL, 0) == signaler, "waitpid signaler");
+    check(got_signal == 1, "signal is handled after vfork returns");
+}
+
+static void test_kill_during_vfork(void)
+{
+    pid_t pid = fork();
+    check(pid >= 0, "fork");
+    if (pid == 0)
+    {
+        /* 子进程在vfork中等待一个运行很久的子进程 */
+        if (vfork() == 0)
+        {
+            raw_sleep_ms(3000);
+            syscall(SYS_exit, 0);
+        }
+        _exit(0);
+    }
+
+    usleep(300 * 1000);
+    long start = now_ms();
+    check(kill(pid, SIGKILL) == 0, "kill");
+    int status = 0;
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFSIGNALED(status) && WTERMSIG(status) == SIGKILL, "vfork parent is killed");
+    check(now_ms() - start < 2000, "SIGKILL does not wait for the vfork child");
+}
+
+int main()
+{
+    test_signal_during_vfork();
+    test_kill_during_vfork();
+
+    return test_report();
+}
@@ -0,0 +1,81 @@
+/**
+ * 测试wait4返回的子进程cpu时间:
+ * 1. 子进程在用户态忙循环之后退出, wait4返回的rusage中ru_utime不为0
+ * 2. 回收子进程之后, getrusage(RUSAGE_CHILDREN)包含这个子进程的时间
+ * 3. 子进程回收的孙进程的时间, 也会计入wait4返回的子进程的rusage
+ */
+
+#include <errno.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/resource.h>
+#include <sys/time.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_wait_rusage"
+#include "test_check.h"
+
+static long long tv_usec(struct timeval tv)
+{
+    return tv.tv_sec * 1000000LL + tv.tv_usec;
+}
+
+static long long now_usec(void)
+{
+    struct timeval tv;
+    gettimeofday(&tv, NULL);
+    return tv_usec(tv);
+}
+
+/* 在用户态忙循环大约usec微秒 */
+static void busy_loop(long long usec)
+{
+    volatile unsigned long counter = 0;
+    long long start = now_usec();
+    while (now_usec() - start < usec)
+    {
+        for (int i = 0; i < 100000; i++)
+            counter++;
+    }
+}
+
+int main()
+{
+    /* 子进程忙循环300ms */
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        busy_loop(300000);
+        _exit(0);
+    }
+    struct rusage ru;
+    memset(&ru, 0, sizeof(ru));
+    check(wait4(pid, NULL, 0, &ru) == pid, "wait4");
+    long long child_utime = tv_usec(ru.ru_utime);
+    check(child_utime > 0, "child utime is non-zero");
+    check(ru.ru_utime.tv_usec >= 0 && ru.ru_utime.tv_usec < 1000000, "ru_utime is a timeval");
+
+    struct rusage children;
+    check(getrusage(RUSAGE_CHILDREN, &children) == 0, "getrusage(RUSAGE_CHILDREN)");
+    check(tv_usec(children.ru_utime) >= child_utime, "reaped child time is in RUSAGE_CHILDREN");
+
+    /* 子进程本身不占用cpu, 只回收一个忙循环的孙进程 */
+    pid = fork();
+    if (pid == 0)
+    {
+        pid_t grandchild = fork();
+        if (grandchild == 0)
+        {
+            busy_loop(300000);
+            _exit(0);
+        }
+        waitpid(grandchild, NULL, 0);
+        _exit(0);
+    }
+    memset(&ru, 0, sizeof(ru));
+    check(wait4(pid, NULL, 0, &ru) == pid, "wait4 the parent of a busy grandchild");
+    check(tv_usec(ru.ru_utime) > 0, "grandchild time is included in the child's rusage");
+
+    return test_report();
+}
@@ -0,0 +1,85 @@
+/**
+ * 测试waitid:
+ * 1. 正常退出的子进程报告CLD_EXITED与退出码, WNOWAIT只查看而不回收
+ * 2. 被信号杀死的子进程报告CLD_KILLED与信号
+ * 3. 停止与继续的子进程分别报告CLD_STOPPED与CLD_CONTINUED
+ * 4. WNOHANG且没有子进程状态变化时, siginfo被清零
+ * 5. 没有匹配的子进程时返回ECHILD
+ */
+
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define TEST_NAME "test_waitid"
+#include "test_check.h"
+
+static int info_is(siginfo_t *info, pid_t pid, int code, int status)
+{
+    return info->si_signo == SIGCHLD && info->si_pid == pid && info->si_code == code &&
+           info->si_status == status;
+}
+
+static pid_t spawn_sleeper(void)
+{
+    pid_t pid = fork();
+    if (pid == 0)
+    {
+        for (;;)
+            pause();
+    }
+    return pid;
+}
+
+int main()
+{
+    siginfo_t info;
+
+    /* 正常退出，先用WNOWAIT查看，再回收 */
+    pid_t pid = fork();
+    if (pid == 0)
+        _exit(3);
+    memset(&info, 0, sizeof(info));
+    check(waitid(P_PID, pid, &info, WEXITED | WNOWAIT) == 0, "waitid WNOWAIT");
+    check(info_is(&info, pid, CLD_EXITED, 3), "CLD_EXITED with WNOWAIT");
+    memset(&info, 0, sizeof(info));
+    check(waitid(P_ALL, 0, &info, WEXITED) == 0, "waitid P_ALL");
+    check(info_is(&info, pid, CLD_EXITED, 3), "CLD_EXITED");
+
+    /* 被信号杀死 */
+    pid = spawn_sleeper();
+    kill(pid, SIGKILL);
+    memset(&info, 0, sizeof(info));
+    check(waitid(P_PID, pid, &info, WEXITED) == 0, "waitid killed child");
+    check(info_is(&info, pid, CLD_KILLED, SIGKILL), "CLD_KILLED");
+
+    /* 停止与继续 */
+    pid = spawn_sleeper();
+    memset(&info, 0xff, sizeof(info));
+    check(waitid(P_PID, pid, &info, WEXITED | WNOHANG) == 0, "waitid WNOHANG");
+    check(info.si_pid == 0, "siginfo cleared with WNOHANG");
+
+    kill(pid, SIGSTOP);
+    memset(&info, 0, sizeof(info));
+    check(waitid(P_PID, pid, &info, WSTOPPED) == 0, "waitid stopped child");
+    check(info_is(&info, pid, CLD_STOPPED, SIGSTOP), "CLD_STOPPED");
+
+    kill(pid, SIGCONT);
+    memset(&info, 0, sizeof(info));
+    check(waitid(P_PID, pid, &info, WCONTINUED) == 0, "waitid continued child");
+    check(info_is(&info, pid, CLD_CONTINUED, SIGCONT), "CLD_CONTINUED");
+
+    kill(pid, SIGKILL);
+    check(waitid(P_PID, pid, &info, WEXITED) == 0, "reap stopped child");
+
+    /* 参数错误与没有子进程 */
+    errno = 0;
+    check(waitid(P_ALL, 0, &info, WNOHANG) == -1 && errno == EINVAL, "EINVAL without wait flags");
+    errno = 0;
+    check(waitid(P_ALL, 0, &info, WEXITED) == -1 && errno == ECHILD, "ECHILD");
+
+    return test_report();
+}